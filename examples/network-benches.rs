@@ -14,9 +14,9 @@ use ecdsa::hazmat::DigestPrimitive;
 use elliptic_curve::{ops::Reduce, Curve};
 use haisou_chan::{channel, Bandwidth};
 
+use clap::StructOpt;
 use k256::{FieldBytes, Scalar, Secp256k1};
 use rand_core::OsRng;
-use clap::StructOpt;
 
 fn scalar_hash(msg: &[u8]) -> Scalar {
     let digest = <Secp256k1 as DigestPrimitive>::Digest::new_with_prefix(msg);
@@ -0,0 +1,424 @@
+//! This module provides adaptor signatures, built on top of presignatures.
+//!
+//! An adaptor signature is a signature encrypted under some adaptor point `Y = y * G`.
+//! Anybody can check that it's a valid encryption of a signature, but only
+//! someone knowing `y` can complete it into an actual signature. Once that
+//! signature is published, the signers can extract `y` from it. This is the
+//! basis of atomic swaps, and of discreet log contracts.
+//!
+//! A presignature has a nonce commitment `R = k^-1 * G`. The signers first compute
+//! `R_Y = k^-1 * Y` together, along with a proof that `R` and `R_Y` have the same
+//! discrete logarithm, relative to `G` and `Y`. They produce this proof jointly, like in
+//! the verifiable random function: they commit to their contributions and nonces, then reveal
+//! them, and finally send their responses, which are checked individually, so that a
+//! misbehaving participant can be identified. The signature is then
+//! produced like a normal signature, using the x coordinate of `R_Y`, giving
+//! `s' = k * (h + r * x)`. Completing it gives `s = s' * y^-1`, which is a
+//! signature with nonce commitment `R_Y`.
+use elliptic_curve::{ops::Invert, Field, Group, ScalarPrimitive};
+use rand_core::{CryptoRngCore, OsRng};
+
+use crate::{
+    compat::{self, CSCurve, SerializablePoint},
+    crypto::{commit, fork_rng, Commitment, Randomizer},
+    fiat_shamir::{FiatShamir, Transcript},
+    participants::{ParticipantCounter, ParticipantList, ParticipantMap},
+    proofs::dlogeq,
+    protocol::{
+        internal::{make_protocol, Context, SharedChannel},
+        Fault, InitializationError, Participant, Protocol, ProtocolError,
+    },
+    serde::encode,
+    FullSignature, PresignOutput,
+};
+
+/// The label we use for the transcript of the proof.
+const LABEL: &[u8] = b"cait-sith v0.8.0 adaptor signature";
+
+/// The transcript of the proof, which anybody checking the signature can reproduce.
+fn proof_transcript<C: CSCurve>(public_key: &C::AffinePoint, msg_hash: &C::Scalar) -> Transcript {
+    let mut transcript = Transcript::new(LABEL);
+    transcript.message(b"group", C::NAME);
+    transcript.message(
+        b"public key",
+        &encode(&SerializablePoint::<C>::from_projective(
+            &(*public_key).into(),
+        )),
+    );
+    let msg_hash: ScalarPrimitive<C> = (*msg_hash).into();
+    transcript.message(b"message", &encode(&msg_hash));
+    transcript
+}
+
+/// A signature encrypted under an adaptor point.
+#[derive(Clone)]
+pub struct AdaptorSignature<C: CSCurve> {
+    /// The nonce commitment of the presignature used.
+    pub big_r: C::AffinePoint,
+    /// The nonce commitment multiplied by the adaptor secret, which the completed signature uses.
+    pub big_r_adapted: C::AffinePoint,
+    /// The encrypted second scalar.
+    pub s: C::Scalar,
+    /// A proof that `big_r_adapted` is `big_r` multiplied by the adaptor secret.
+    pub proof: dlogeq::Proof<C>,
+}
+
+impl<C: CSCurve> AdaptorSignature<C> {
+    /// Check that this is a valid encryption of a signature of a message, under an adaptor point.
+    ///
+    /// This checks the proof that `big_r_adapted` is `big_r` multiplied by the adaptor secret,
+    /// so that completing this gives a valid signature.
+    #[must_use]
+    pub fn verify(
+        &self,
+        public_key: &C::AffinePoint,
+        msg_hash: &C::Scalar,
+        adaptor_point: &C::AffinePoint,
+    ) -> bool {
+        let big_r = C::ProjectivePoint::from(self.big_r);
+        let big_y = C::ProjectivePoint::from(*adaptor_point);
+        let big_r_adapted = C::ProjectivePoint::from(self.big_r_adapted);
+        if bool::from(big_y.is_identity()) {
+            return false;
+        }
+        let statement = dlogeq::Statement::<C> {
+            public0: &big_r,
+            generator1: &big_y,
+            public1: &big_r_adapted,
+        };
+        if !dlogeq::verify(
+            &mut proof_transcript::<C>(public_key, msg_hash),
+            statement,
+            &self.proof,
+        ) {
+            return false;
+        }
+
+        let r: C::Scalar = compat::x_coordinate::<C>(&self.big_r_adapted);
+        if r.is_zero().into() || self.s.is_zero().into() {
+            return false;
+        }
+        let s_inv = self.s.invert_vartime().unwrap();
+        let reproduced = (C::ProjectivePoint::generator() * (*msg_hash * s_inv))
+            + (C::ProjectivePoint::from(*public_key) * (r * s_inv));
+        reproduced == big_r
+    }
+
+    /// Complete this into a signature, using the adaptor secret.
+    ///
+    /// This returns `None` if the secret doesn't match the adaptor point used.
+    pub fn complete(&self, adaptor_secret: &C::Scalar) -> Option<FullSignature<C>> {
+        if C::ProjectivePoint::from(self.big_r) * adaptor_secret
+            != C::ProjectivePoint::from(self.big_r_adapted)
+        {
+            return None;
+        }
+        let y_inv: Option<C::Scalar> = Field::invert(adaptor_secret).into();
+        Some(FullSignature::normalized(
+            self.big_r_adapted,
+            self.s * y_inv?,
+        ))
+    }
+
+    /// Extract the adaptor secret, from the completed signature.
+    ///
+    /// This returns `None` if the signature isn't a completion of this adaptor signature.
+    pub fn extract(
+        &self,
+        signature: &FullSignature<C>,
+        adaptor_point: &C::AffinePoint,
+    ) -> Option<C::Scalar> {
+        let s_inv: Option<C::Scalar> = signature.s.invert_vartime().into();
+        let y = self.s * s_inv?;
+        // Normalizing the signature might have negated s, and thus the secret.
+        [y, -y]
+            .into_iter()
+            .find(|y| (C::ProjectivePoint::generator() * y).into() == *adaptor_point)
+    }
+}
+
+/// What a participant reveals: its weighted share of `k^-1`, times `G` and `Y`,
+/// and its nonce for the proof, times `G` and `Y`.
+type Contribution<C> = [SerializablePoint<C>; 4];
+
+#[allow(clippy::too_many_arguments)]
+async fn do_sign_adaptor<C: CSCurve>(
+    mut rng: impl CryptoRngCore,
+    mut chan: SharedChannel,
+    participants: ParticipantList,
+    me: Participant,
+    public_key: C::AffinePoint,
+    presignature: PresignOutput<C>,
+    msg_hash: C::Scalar,
+    adaptor_point: C::AffinePoint,
+) -> Result<AdaptorSignature<C>, ProtocolError> {
+    let big_y = C::ProjectivePoint::from(adaptor_point);
+
+    let lambda = participants.lagrange::<C>(me);
+    let k_inv_i = lambda * presignature.k_inv;
+    let a_i = C::Scalar::random(&mut rng);
+    let mine: Contribution<C> = [
+        C::ProjectivePoint::generator() * k_inv_i,
+        big_y * k_inv_i,
+        C::ProjectivePoint::generator() * a_i,
+        big_y * a_i,
+    ]
+    .map(|p| SerializablePoint::from_projective(&p));
+    let (my_commitment, my_randomizer) = commit(&mut rng, &mine);
+
+    // First, commit to our contribution, so that nobody can choose theirs based on ours.
+    let wait0 = chan.next_waitpoint();
+    chan.send_many(wait0, &my_commitment).await;
+
+    let mut all_commitments = ParticipantMap::new(&participants);
+    all_commitments.put(me, my_commitment);
+    while !all_commitments.full() {
+        let (from, commitment): (_, Commitment) = chan.recv(wait0).await?;
+        all_commitments.put(from, commitment);
+    }
+
+    let wait1 = chan.next_waitpoint();
+    chan.send_many(wait1, &(&mine, &my_randomizer)).await;
+
+    let mut contributions = ParticipantMap::new(&participants);
+    contributions.put(me, mine);
+    let mut seen = ParticipantCounter::new(&participants);
+    seen.put(me);
+    while !seen.full() {
+        let (from, (theirs, their_randomizer)): (_, (Contribution<C>, Randomizer)) =
+            chan.recv(wait1).await?;
+        if !seen.put(from) {
+            continue;
+        }
+        if !all_commitments[from].check(&theirs, &their_randomizer) {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait1,
+                fault: Fault::BadCommitment,
+            });
+        }
+        contributions.put(from, theirs);
+    }
+
+    let order: Vec<Participant> = participants.clone().into();
+    let mut sums = [C::ProjectivePoint::identity(); 4];
+    for &p in &order {
+        for (sum, point) in sums.iter_mut().zip(contributions[p]) {
+            *sum += point.to_projective();
+        }
+    }
+    let [big_r, big_r_y, big_a, big_b] = sums;
+    if big_r != presignature.big_r.into() {
+        return Err(ProtocolError::Failed {
+            round: wait1,
+            fault: Fault::BadShare,
+        });
+    }
+    let big_k = (
+        SerializablePoint::<C>::from_projective(&big_a),
+        SerializablePoint::<C>::from_projective(&big_b),
+    );
+    let e = dlogeq::challenge::<C>(
+        &mut proof_transcript::<C>(&public_key, &msg_hash),
+        dlogeq::Statement {
+            public0: &big_r,
+            generator1: &big_y,
+            public1: &big_r_y,
+        },
+        &big_k,
+    );
+    let big_r_adapted: C::AffinePoint = big_r_y.into();
+
+    // Then, respond to the challenge, and sign like usual, except with r coming from R_Y.
+    let z_i = a_i + e * k_inv_i;
+    let r = compat::x_coordinate::<C>(&big_r_adapted);
+    let s_i = msg_hash * lambda * presignature.k + r * lambda * presignature.sigma;
+
+    let wait2 = chan.next_waitpoint();
+    {
+        let z_i: ScalarPrimitive<C> = z_i.into();
+        let s_i: ScalarPrimitive<C> = s_i.into();
+        chan.send_many(wait2, &(z_i, s_i)).await;
+    }
+
+    let mut z = z_i;
+    let mut s = s_i;
+    seen.clear();
+    seen.put(me);
+    while !seen.full() {
+        let (from, (z_j, s_j)): (_, (ScalarPrimitive<C>, ScalarPrimitive<C>)) =
+            chan.recv(wait2).await?;
+        if !seen.put(from) {
+            continue;
+        }
+        let z_j = C::Scalar::from(z_j);
+        let [big_r_j, big_r_y_j, big_a_j, big_b_j] =
+            contributions[from].map(SerializablePoint::to_projective);
+        if C::ProjectivePoint::generator() * z_j != big_a_j + big_r_j * e
+            || big_y * z_j != big_b_j + big_r_y_j * e
+        {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait2,
+                fault: Fault::BadShare,
+            });
+        }
+        z += z_j;
+        s += C::Scalar::from(s_j);
+    }
+
+    let sig = AdaptorSignature {
+        big_r: presignature.big_r,
+        big_r_adapted,
+        s,
+        proof: dlogeq::Proof::from_parts(big_k, z),
+    };
+    if !sig.verify(&public_key, &msg_hash, &adaptor_point) {
+        return Err(ProtocolError::Failed {
+            round: wait2,
+            fault: Fault::InvalidResult("adaptor signature failed to verify"),
+        });
+    }
+
+    Ok(sig)
+}
+
+/// Like [`sign`](crate::sign), except producing a signature encrypted under an adaptor point.
+///
+/// The same caveats apply: the message must be hashed, and the presignature
+/// must never be used again, including for a normal signature.
+pub fn sign_adaptor<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    public_key: C::AffinePoint,
+    presignature: PresignOutput<C>,
+    msg_hash: C::Scalar,
+    adaptor_point: C::AffinePoint,
+) -> Result<impl Protocol<Output = AdaptorSignature<C>>, InitializationError> {
+    sign_adaptor_with_rng(
+        participants,
+        me,
+        public_key,
+        presignature,
+        msg_hash,
+        adaptor_point,
+        &mut OsRng,
+    )
+}
+
+/// Like [`sign_adaptor`], except drawing all of the randomness from a given rng.
+pub fn sign_adaptor_with_rng<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    public_key: C::AffinePoint,
+    presignature: PresignOutput<C>,
+    msg_hash: C::Scalar,
+    adaptor_point: C::AffinePoint,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = AdaptorSignature<C>>, InitializationError> {
+    if participants.len() < 2 {
+        return Err(InitializationError::BadParameters(format!(
+            "participant count cannot be < 2, found: {}",
+            participants.len()
+        )));
+    };
+
+    let participants = ParticipantList::new(participants).ok_or_else(|| {
+        InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
+    })?;
+
+    if !participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain this participant".to_string(),
+        ));
+    }
+
+    if bool::from(C::ProjectivePoint::from(adaptor_point).is_identity()) {
+        return Err(InitializationError::BadParameters(
+            "adaptor point cannot be the identity".to_string(),
+        ));
+    }
+
+    let ctx = Context::new();
+    let fut = do_sign_adaptor(
+        fork_rng(rng),
+        ctx.shared_channel(),
+        participants,
+        me,
+        public_key,
+        presignature,
+        msg_hash,
+        adaptor_point,
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
+#[cfg(test)]
+mod test {
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
+
+    use super::*;
+    use crate::{compat::scalar_hash, math::Polynomial, protocol::run_protocol};
+
+    #[test]
+    fn test_sign_adaptor() -> Result<(), Box<dyn std::error::Error>> {
+        let threshold = 2;
+        let msg_hash = scalar_hash(b"hello?");
+
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let x = f.evaluate_zero();
+        let public_key = (ProjectivePoint::GENERATOR * x).to_affine();
+
+        let k = Scalar::generate_biased(&mut OsRng);
+        let big_r = (ProjectivePoint::GENERATOR * k.invert().unwrap()).to_affine();
+        let g = Polynomial::<Secp256k1>::extend_random(&mut OsRng, threshold, &k);
+        let h = Polynomial::<Secp256k1>::extend_random(&mut OsRng, threshold, &(k * x));
+        let g_inv =
+            Polynomial::<Secp256k1>::extend_random(&mut OsRng, threshold, &k.invert().unwrap());
+
+        let y = Scalar::generate_biased(&mut OsRng);
+        let big_y = (ProjectivePoint::GENERATOR * y).to_affine();
+
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = AdaptorSignature<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        for p in &participants {
+            let p_scalar = p.scalar::<Secp256k1>();
+            let presignature = PresignOutput {
+                big_r,
+                k: g.evaluate(&p_scalar),
+                sigma: h.evaluate(&p_scalar),
+                k_inv: g_inv.evaluate(&p_scalar),
+            };
+            let protocol =
+                sign_adaptor(&participants, *p, public_key, presignature, msg_hash, big_y)?;
+            protocols.push((*p, Box::new(protocol)));
+        }
+        let result = run_protocol(protocols)?;
+        let adaptor_sig = result[0].1.clone();
+        assert!(adaptor_sig.verify(&public_key, &msg_hash, &big_y));
+        // The adapted nonce commitment must be the one the proof is about.
+        let mut tampered = adaptor_sig.clone();
+        tampered.big_r_adapted =
+            (ProjectivePoint::from(adaptor_sig.big_r) * (y + Scalar::ONE)).to_affine();
+        assert!(!tampered.verify(&public_key, &msg_hash, &big_y));
+        // And it's only valid under the adaptor point used.
+        let other = (ProjectivePoint::GENERATOR * (y + Scalar::ONE)).to_affine();
+        assert!(!adaptor_sig.verify(&public_key, &msg_hash, &other));
+        // It's not a signature by itself.
+        assert!(
+            !FullSignature::<Secp256k1>::normalized(adaptor_sig.big_r, adaptor_sig.s)
+                .verify(&public_key, &msg_hash)
+        );
+
+        assert!(adaptor_sig.complete(&(y + Scalar::ONE)).is_none());
+        let sig = adaptor_sig.complete(&y).unwrap();
+        assert!(sig.verify(&public_key, &msg_hash));
+        assert_eq!(adaptor_sig.extract(&sig, &big_y), Some(y));
+
+        Ok(())
+    }
+}
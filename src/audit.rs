@@ -0,0 +1,358 @@
+//! Auditing key generation, after the fact.
+//!
+//! Most of what participants send each other during key generation is public:
+//! commitments to their polynomials, the polynomials themselves, and proofs
+//! of knowledge of their constant terms. The audited variant of key generation
+//! collects all of this, along with the session id and the set of participants,
+//! into a [`KeygenAudit`], which can be archived, and checked independently
+//! with [`verify_keygen_audit`], without access to any secrets.
+//!
+//! To show that they received valid shares, each participant also attests to
+//! the ceremony, with a proof of knowledge of its share, bound to everything else
+//! in the audit. This acts as a signature by each participant, under the public
+//! counterpart of its share. Binding participants to their long-term identities
+//! is left to whoever archives the audit, by signing it with those identities.
+use std::{error, fmt};
+
+use magikitten::MeowRng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compat::CSCurve,
+    crypto::{hash, Commitment, Randomizer},
+    dealer::KeyCommitment,
+    fiat_shamir::{FiatShamir, FiatShamirBackend, Transcript},
+    keyshare::keyshare_transcript,
+    math::GroupPolynomial,
+    participants::{ParticipantCounter, ParticipantList, ParticipantMap},
+    proofs::dlog,
+    protocol::{internal::SharedChannel, Fault, Participant, ProtocolError, SessionId},
+    serde::encode,
+};
+
+/// What a participant revealed while dealing its contribution to the key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub(crate) struct Dealing<C: CSCurve> {
+    pub commitment: Commitment,
+    pub polynomial: GroupPolynomial<C>,
+    pub randomizer: Randomizer,
+    pub proof: dlog::Proof<C>,
+}
+
+/// The public record of a key generation, which can be verified by anyone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct KeygenAudit<C: CSCurve> {
+    session: SessionId,
+    participants: Vec<Participant>,
+    threshold: usize,
+    dealings: Vec<Dealing<C>>,
+    attestations: Vec<dlog::Proof<C>>,
+}
+
+impl<C: CSCurve> KeygenAudit<C> {
+    /// The id of the session the key was generated in.
+    pub fn session(&self) -> SessionId {
+        self.session
+    }
+
+    /// The participants which generated the key, in sorted order.
+    pub fn participants(&self) -> &[Participant] {
+        &self.participants
+    }
+
+    /// The number of shares needed to reconstruct the key.
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// The commitment to the shares of the key.
+    ///
+    /// This can be used to check a share, with [`KeygenOutput::verify`](crate::KeygenOutput::verify).
+    /// This doesn't verify the audit, which should be done first.
+    pub fn commitment(&self) -> KeyCommitment<C> {
+        let mut polynomial = GroupPolynomial::zero(self.threshold);
+        for d in &self.dealings {
+            polynomial += &d.polynomial;
+        }
+        KeyCommitment::from_polynomial(polynomial)
+    }
+}
+
+/// Represents an error which can happen when verifying an audit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditError {
+    /// The audit doesn't describe a valid ceremony, regardless of its contents.
+    Malformed(&'static str),
+    /// The material of some participant failed a check.
+    Faulty {
+        participant: Participant,
+        fault: Fault,
+    },
+}
+
+impl fmt::Display for AuditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuditError::Malformed(e) => write!(f, "malformed audit: {}", e),
+            AuditError::Faulty { participant, fault } => {
+                write!(f, "participant {:?} is at fault: {}", participant, fault)
+            }
+        }
+    }
+}
+
+impl error::Error for AuditError {}
+
+/// Add the dealings of every participant to the transcript, before attesting to them.
+fn attestation_transcript<C: CSCurve>(transcript: &mut Transcript, dealings: &[Dealing<C>]) {
+    transcript.message(b"dealings", &encode(&dealings));
+}
+
+/// Attest to a ceremony, and collect the attestations of everyone else.
+///
+/// The transcript should be the one used during key generation, after every dealing was checked.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn do_attest<C: CSCurve>(
+    rng: &mut MeowRng,
+    chan: &mut SharedChannel,
+    transcript: &mut Transcript,
+    participants: &ParticipantList,
+    me: Participant,
+    threshold: usize,
+    session: SessionId,
+    dealings: Vec<Dealing<C>>,
+    big_f: &GroupPolynomial<C>,
+    x_i: &C::Scalar,
+) -> Result<KeygenAudit<C>, ProtocolError> {
+    attestation_transcript(transcript, &dealings);
+
+    let my_attestation = dlog::prove(
+        rng,
+        &mut transcript.forked(b"attest", &me.bytes()),
+        dlog::Statement::<C> {
+            public: &big_f.evaluate(&me.scalar::<C>()),
+        },
+        dlog::Witness::<C> { x: x_i },
+    );
+
+    let wait = chan.next_waitpoint();
+    chan.send_many(wait, &my_attestation).await;
+
+    let mut attestations = ParticipantMap::new(participants);
+    attestations.put(me, my_attestation);
+    let mut seen = ParticipantCounter::new(participants);
+    seen.put(me);
+    while !seen.full() {
+        let (from, attestation): (_, dlog::Proof<C>) = chan.recv(wait).await?;
+        if !seen.put(from) {
+            continue;
+        }
+        let statement = dlog::Statement::<C> {
+            public: &big_f.evaluate(&from.scalar::<C>()),
+        };
+        if !dlog::verify(
+            &mut transcript.forked(b"attest", &from.bytes()),
+            statement,
+            &attestation,
+        ) {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait,
+                fault: Fault::BadProof,
+            });
+        }
+        attestations.put(from, attestation);
+    }
+
+    let participants: Vec<Participant> = participants.clone().into();
+    let attestations = participants
+        .iter()
+        .map(|&p| attestations[p].clone())
+        .collect();
+    Ok(KeygenAudit {
+        session,
+        participants,
+        threshold,
+        dealings,
+        attestations,
+    })
+}
+
+/// Verify the record of a key generation, returning the public key it produced.
+///
+/// This checks every commitment and proof sent during the ceremony, as well as
+/// the attestation of each participant that it received a valid share.
+pub fn verify_keygen_audit<C: CSCurve>(
+    audit: &KeygenAudit<C>,
+) -> Result<C::AffinePoint, AuditError> {
+    let participants = ParticipantList::new(&audit.participants).ok_or(AuditError::Malformed(
+        "participant list cannot contain duplicates",
+    ))?;
+    if participants.len() < 2 {
+        return Err(AuditError::Malformed("participant count cannot be < 2"));
+    }
+    if audit.threshold == 0 || audit.threshold > participants.len() {
+        return Err(AuditError::Malformed(
+            "threshold must be between 1 and the participant count",
+        ));
+    }
+    if audit.participants != Vec::<Participant>::from(participants.clone()) {
+        return Err(AuditError::Malformed("participant list must be sorted"));
+    }
+    if audit.dealings.len() != participants.len() || audit.attestations.len() != participants.len()
+    {
+        return Err(AuditError::Malformed(
+            "there must be one dealing and one attestation per participant",
+        ));
+    }
+
+    let mut transcript = keyshare_transcript::<C>(
+        &participants,
+        audit.threshold,
+        Some(audit.session.as_bytes()),
+        FiatShamirBackend::default(),
+    );
+    let mut all_commitments = ParticipantMap::new(&participants);
+    for (&p, dealing) in audit.participants.iter().zip(&audit.dealings) {
+        all_commitments.put(p, dealing.commitment);
+    }
+    transcript.message(b"confirmation", hash(&all_commitments).as_ref());
+
+    let mut big_f = GroupPolynomial::<C>::zero(audit.threshold);
+    for (&p, dealing) in audit.participants.iter().zip(&audit.dealings) {
+        let faulty = |fault| AuditError::Faulty {
+            participant: p,
+            fault,
+        };
+        if dealing.polynomial.len() != audit.threshold {
+            return Err(faulty(Fault::InvalidMessage(
+                "polynomial has the wrong length",
+            )));
+        }
+        if !dealing
+            .commitment
+            .check(&dealing.polynomial, &dealing.randomizer)
+        {
+            return Err(faulty(Fault::BadCommitment));
+        }
+        let statement = dlog::Statement::<C> {
+            public: &dealing.polynomial.evaluate_zero(),
+        };
+        if !dlog::verify(
+            &mut transcript.forked(b"dlog0", &p.bytes()),
+            statement,
+            &dealing.proof,
+        ) {
+            return Err(faulty(Fault::BadProof));
+        }
+        big_f += &dealing.polynomial;
+    }
+
+    attestation_transcript(&mut transcript, &audit.dealings);
+    for (&p, attestation) in audit.participants.iter().zip(&audit.attestations) {
+        let statement = dlog::Statement::<C> {
+            public: &big_f.evaluate(&p.scalar::<C>()),
+        };
+        if !dlog::verify(
+            &mut transcript.forked(b"attest", &p.bytes()),
+            statement,
+            attestation,
+        ) {
+            return Err(AuditError::Faulty {
+                participant: p,
+                fault: Fault::BadProof,
+            });
+        }
+    }
+
+    Ok(big_f.evaluate_zero().into())
+}
+
+#[cfg(test)]
+mod test {
+    use k256::Secp256k1;
+
+    use super::*;
+    use crate::{
+        keygen_audited,
+        protocol::{run_protocol, Protocol},
+        serde::decode,
+        KeygenOutput,
+    };
+
+    #[test]
+    fn test_keygen_audit() -> Result<(), Box<dyn error::Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let session = SessionId::derive(b"ceremony", &participants, 0, 0);
+
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = (KeygenOutput<Secp256k1>, KeygenAudit<Secp256k1>)>>,
+        )> = Vec::with_capacity(participants.len());
+        for &p in &participants {
+            let protocol = keygen_audited(&participants, p, 2, session)?;
+            protocols.push((p, Box::new(protocol)));
+        }
+        let result = run_protocol(protocols)?;
+
+        // Everyone ends up with the same record.
+        let audit = &result[0].1 .1;
+        assert!(result.iter().all(|(_, (_, a))| encode(a) == encode(audit)));
+        let audit: KeygenAudit<Secp256k1> = decode(&encode(audit))?;
+        assert_eq!(audit.session(), session);
+        assert_eq!(audit.participants(), &participants);
+        assert_eq!(audit.threshold(), 2);
+
+        let public_key = verify_keygen_audit(&audit)?;
+        let commitment = audit.commitment();
+        for (p, (output, _)) in &result {
+            assert_eq!(output.public_key, public_key);
+            assert!(output.verify(&public_key, *p, &commitment));
+        }
+
+        let mut bad = audit.clone();
+        bad.session = SessionId::derive(b"ceremony", &participants, 1, 0);
+        assert!(matches!(
+            verify_keygen_audit(&bad),
+            Err(AuditError::Faulty {
+                fault: Fault::BadProof,
+                ..
+            })
+        ));
+
+        let mut bad = audit.clone();
+        bad.attestations.swap(0, 1);
+        assert_eq!(
+            verify_keygen_audit(&bad),
+            Err(AuditError::Faulty {
+                participant: participants[0],
+                fault: Fault::BadProof
+            })
+        );
+
+        let mut bad = audit.clone();
+        bad.dealings[1].randomizer = bad.dealings[0].randomizer;
+        assert_eq!(
+            verify_keygen_audit(&bad),
+            Err(AuditError::Faulty {
+                participant: participants[1],
+                fault: Fault::BadCommitment
+            })
+        );
+
+        let mut bad = audit;
+        bad.dealings.pop();
+        assert!(matches!(
+            verify_keygen_audit(&bad),
+            Err(AuditError::Malformed(_))
+        ));
+        Ok(())
+    }
+}
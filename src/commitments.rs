@@ -0,0 +1,241 @@
+//! This module provides an abstraction over the commitment schemes our protocols use.
+//!
+//! Key generation and triple generation commit to their polynomials with a hash,
+//! which is cheap, and works for any serializable value. Some protocol variants
+//! need homomorphic commitments instead, which can be combined before being opened.
+//! The [`CommitmentScheme`] trait covers both, so that these variants can pick
+//! a scheme, without changing the rest of the protocol.
+//!
+//! Two schemes are provided:
+//! - [`HashCommitments`], the hash based scheme used by our protocols,
+//!   which commits to any serializable value.
+//! - [`PedersenCommitments`], committing to a scalar `x` as `x * G + r * H`,
+//!   with `H` a generator whose discrete logarithm nobody knows. These commitments
+//!   can be added, with the sum opening to the sum of the values.
+use std::ops::Add;
+
+use elliptic_curve::{
+    group::cofactor::CofactorGroup,
+    hash2curve::{ExpandMsgXmd, GroupDigest},
+    Field, Group,
+};
+use rand_core::CryptoRngCore;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{
+    compat::{CSCurve, SerializablePoint},
+    crypto::{commit, Commitment, Randomizer},
+    serde::{deserialize_scalar, serialize_scalar},
+};
+
+/// The domain separation tag we use to derive the second Pedersen generator.
+const PEDERSEN_DST: &[u8] = b"cait-sith v0.8.0 pedersen generator";
+
+/// A scheme for committing to values of type `T`.
+///
+/// Commitments are binding, in that they can't be opened to a different value
+/// than the one committed, and hiding, in that they reveal nothing about the value
+/// until they're opened.
+pub trait CommitmentScheme<T: ?Sized> {
+    /// A commitment to a value.
+    type Commitment: Clone + Eq + Serialize + DeserializeOwned;
+    /// The extra information needed to open a commitment.
+    type Opening: Clone + Serialize + DeserializeOwned;
+
+    /// Commit to a value, returning the commitment, and the opening to send along with the value.
+    fn commit(&self, rng: &mut impl CryptoRngCore, val: &T) -> (Self::Commitment, Self::Opening);
+
+    /// Check that a value and an opening match a commitment.
+    #[must_use]
+    fn check(&self, commitment: &Self::Commitment, val: &T, opening: &Self::Opening) -> bool;
+}
+
+/// Commitments made by hashing a value, along with a random string.
+///
+/// This is the scheme used by key generation, and triple generation.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HashCommitments;
+
+impl<T: Serialize + ?Sized> CommitmentScheme<T> for HashCommitments {
+    type Commitment = Commitment;
+    type Opening = Randomizer;
+
+    fn commit(&self, rng: &mut impl CryptoRngCore, val: &T) -> (Commitment, Randomizer) {
+        commit(rng, &val)
+    }
+
+    fn check(&self, commitment: &Commitment, val: &T, opening: &Randomizer) -> bool {
+        commitment.check(&val, opening)
+    }
+}
+
+/// Pedersen commitments to scalars.
+///
+/// The second generator is derived by hashing to the curve, so nobody knows its
+/// discrete logarithm, which would let them open commitments to any value.
+#[derive(Clone, Copy, Debug)]
+pub struct PedersenCommitments<C: CSCurve> {
+    big_h: C::ProjectivePoint,
+}
+
+impl<C> PedersenCommitments<C>
+where
+    C: CSCurve + GroupDigest,
+    C::ProjectivePoint: CofactorGroup,
+{
+    /// Create the scheme, deriving its second generator from the curve's name.
+    pub fn new() -> Self {
+        // This only fails if the tag is too long, which ours isn't.
+        let big_h = C::hash_from_bytes::<ExpandMsgXmd<Sha256>>(&[C::NAME], &[PEDERSEN_DST])
+            .expect("hashing to a point should not fail");
+        Self { big_h }
+    }
+}
+
+impl<C> Default for PedersenCommitments<C>
+where
+    C: CSCurve + GroupDigest,
+    C::ProjectivePoint: CofactorGroup,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: CSCurve> PedersenCommitments<C> {
+    /// The second generator, used to hide the value.
+    pub fn generator(&self) -> C::ProjectivePoint {
+        self.big_h
+    }
+
+    fn compute(&self, val: &C::Scalar, r: &C::Scalar) -> PedersenCommitment<C> {
+        let point = C::ProjectivePoint::generator() * val + self.big_h * r;
+        PedersenCommitment(SerializablePoint::from_projective(&point))
+    }
+}
+
+impl<C: CSCurve> CommitmentScheme<C::Scalar> for PedersenCommitments<C> {
+    type Commitment = PedersenCommitment<C>;
+    type Opening = PedersenOpening<C>;
+
+    fn commit(
+        &self,
+        rng: &mut impl CryptoRngCore,
+        val: &C::Scalar,
+    ) -> (PedersenCommitment<C>, PedersenOpening<C>) {
+        let r = C::Scalar::random(rng);
+        (self.compute(val, &r), PedersenOpening(r))
+    }
+
+    fn check(
+        &self,
+        commitment: &PedersenCommitment<C>,
+        val: &C::Scalar,
+        opening: &PedersenOpening<C>,
+    ) -> bool {
+        self.compute(val, &opening.0) == *commitment
+    }
+}
+
+/// A Pedersen commitment to a scalar.
+///
+/// Adding two commitments gives a commitment to the sum of their values,
+/// opened by the sum of their openings.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct PedersenCommitment<C: CSCurve>(SerializablePoint<C>);
+
+impl<C: CSCurve> PedersenCommitment<C> {
+    /// The point this commitment consists of.
+    pub fn point(&self) -> C::ProjectivePoint {
+        self.0.to_projective()
+    }
+}
+
+impl<C: CSCurve> PartialEq for PedersenCommitment<C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.point() == other.point()
+    }
+}
+
+impl<C: CSCurve> Eq for PedersenCommitment<C> {}
+
+impl<C: CSCurve> Add for PedersenCommitment<C> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(SerializablePoint::from_projective(
+            &(self.point() + rhs.point()),
+        ))
+    }
+}
+
+/// The opening of a Pedersen commitment.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct PedersenOpening<C: CSCurve>(
+    #[serde(
+        serialize_with = "serialize_scalar::<C, _>",
+        deserialize_with = "deserialize_scalar::<C, _>"
+    )]
+    C::Scalar,
+);
+
+impl<C: CSCurve> Add for PedersenOpening<C> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use k256::{Scalar, Secp256k1};
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::math::GroupPolynomial;
+
+    #[test]
+    fn test_hash_commitments() {
+        let scheme = HashCommitments;
+        let val = GroupPolynomial::<Secp256k1>::zero(3);
+        let (commitment, opening) = scheme.commit(&mut OsRng, &val);
+        assert!(scheme.check(&commitment, &val, &opening));
+        assert!(!scheme.check(
+            &commitment,
+            &GroupPolynomial::<Secp256k1>::zero(2),
+            &opening
+        ));
+    }
+
+    #[test]
+    fn test_pedersen_commitments() {
+        let scheme = PedersenCommitments::<Secp256k1>::new();
+        assert_ne!(
+            scheme.generator(),
+            k256::ProjectivePoint::GENERATOR,
+            "the second generator should be independent"
+        );
+
+        let a = Scalar::random(&mut OsRng);
+        let b = Scalar::random(&mut OsRng);
+        let (commitment_a, opening_a) = scheme.commit(&mut OsRng, &a);
+        let (commitment_b, opening_b) = scheme.commit(&mut OsRng, &b);
+        assert!(scheme.check(&commitment_a, &a, &opening_a));
+        assert!(!scheme.check(&commitment_a, &b, &opening_a));
+        assert!(!scheme.check(&commitment_a, &a, &opening_b));
+
+        // The commitments are homomorphic.
+        assert!(scheme.check(
+            &(commitment_a + commitment_b),
+            &(a + b),
+            &(opening_a + opening_b)
+        ));
+
+        let decoded: PedersenCommitment<Secp256k1> =
+            crate::serde::decode(&crate::serde::encode(&commitment_a)).unwrap();
+        assert_eq!(decoded, commitment_a);
+    }
+}
@@ -1,6 +1,12 @@
-use elliptic_curve::{ops::Reduce, point::AffineCoordinates, Curve, CurveArithmetic, PrimeCurve};
+use elliptic_curve::{
+    hash2curve::{ExpandMsg, ExpandMsgXmd, Expander},
+    ops::Reduce,
+    point::AffineCoordinates,
+    Curve, CurveArithmetic, Field, PrimeCurve,
+};
 use rand_core::CryptoRngCore;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::Sha256;
 
 /// Represents a curve suitable for use in cait-sith.
 ///
@@ -33,6 +39,47 @@ pub trait CSCurve: PrimeCurve + CurveArithmetic {
     /// By this, it's meant that we will make pull a fixed amount of
     /// data from the rng.
     fn sample_scalar_constant_time<R: CryptoRngCore>(r: &mut R) -> Self::Scalar;
+
+    /// Hash a message to a scalar, following the `hash_to_field` method of RFC 9380.
+    ///
+    /// The default implementation uses `expand_message_xmd` with SHA-256, matching
+    /// the `hash_to_scalar` of the RustCrypto curves. Curves can override this to use
+    /// another expansion, with [`expand_to_scalar`].
+    fn hash_to_field(msg: &[u8], dst: &[u8]) -> Self::Scalar {
+        expand_to_scalar::<Self, ExpandMsgXmd<Sha256>>(&[msg], dst)
+    }
+}
+
+/// The security level targeted when hashing to a scalar.
+///
+/// This is fixed by RFC 9380 for the curves we support, independently of our own
+/// security parameter, so that the output matches other implementations.
+const HASH_TO_FIELD_SECURITY: usize = 128;
+
+/// Hash messages to a scalar, according to RFC 9380, with a given expansion.
+///
+/// This expands the messages and the domain separation tag to `ceil((BITS + 128) / 8)`
+/// bytes, and reduces these modulo the order of the curve. The extra 128 bits make
+/// the bias of the result negligible.
+pub fn expand_to_scalar<C, X>(msgs: &[&[u8]], dst: &[u8]) -> C::Scalar
+where
+    C: CSCurve,
+    X: for<'a> ExpandMsg<'a>,
+{
+    let len = (C::BITS + HASH_TO_FIELD_SECURITY).div_ceil(8);
+    // Padding with leading zeros lets us absorb the bytes 64 bits at a time.
+    let padded_len = len.next_multiple_of(8);
+    let mut okm = vec![0u8; padded_len];
+    let dsts = [dst];
+    X::expand_message(msgs, &dsts, len)
+        .expect("the output length should be valid for the expansion")
+        .fill_bytes(&mut okm[padded_len - len..]);
+
+    let two_64 = C::Scalar::from(u64::MAX) + C::Scalar::ONE;
+    okm.chunks_exact(8).fold(C::Scalar::ZERO, |acc, chunk| {
+        let limb = u64::from_be_bytes(chunk.try_into().unwrap());
+        acc * two_64 + C::Scalar::from(limb)
+    })
 }
 
 #[cfg(any(feature = "k256", test))]
@@ -67,27 +114,126 @@ mod k256_impl {
     }
 }
 
+#[cfg(any(feature = "p256", test))]
+mod p256_impl {
+    use super::*;
+
+    use elliptic_curve::bigint::{Bounded, U256};
+    use p256::{FieldBytes, NistP256, Scalar};
+
+    impl CSCurve for NistP256 {
+        const NAME: &'static [u8] = b"P-256";
+        const BITS: usize = <Self::Uint as Bounded>::BITS;
+
+        fn serialize_point<S: Serializer>(
+            point: &Self::AffinePoint,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            point.serialize(serializer)
+        }
+
+        fn deserialize_point<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Self::AffinePoint, D::Error> {
+            Self::AffinePoint::deserialize(deserializer)
+        }
+
+        fn sample_scalar_constant_time<R: CryptoRngCore>(r: &mut R) -> Self::Scalar {
+            // There's no wide reduction for this curve, so we reduce two halves
+            // of 512 random bits, and combine them as hi * 2^256 + lo.
+            let mut hi = FieldBytes::default();
+            let mut lo = FieldBytes::default();
+            r.fill_bytes(&mut hi);
+            r.fill_bytes(&mut lo);
+            let hi = <Scalar as Reduce<U256>>::reduce_bytes(&hi);
+            let lo = <Scalar as Reduce<U256>>::reduce_bytes(&lo);
+            let two_64 = Scalar::from(u64::MAX) + Scalar::ONE;
+            let two_256 = two_64.square().square();
+            hi * two_256 + lo
+        }
+    }
+}
+
+#[cfg(any(feature = "stark", test))]
+mod stark_impl {
+    use super::*;
+
+    use elliptic_curve::bigint::{Bounded, U512};
+
+    use crate::stark::Stark;
+
+    impl CSCurve for Stark {
+        const NAME: &'static [u8] = b"Stark";
+        const BITS: usize = <Self::Uint as Bounded>::BITS;
+
+        fn serialize_point<S: Serializer>(
+            point: &Self::AffinePoint,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            point.serialize(serializer)
+        }
+
+        fn deserialize_point<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Self::AffinePoint, D::Error> {
+            Self::AffinePoint::deserialize(deserializer)
+        }
+
+        fn sample_scalar_constant_time<R: CryptoRngCore>(r: &mut R) -> Self::Scalar {
+            let mut data = [0u8; 64];
+            r.fill_bytes(&mut data);
+            <Self::Scalar as Reduce<U512>>::reduce_bytes(&data.into())
+        }
+    }
+}
+
 #[cfg(test)]
 mod test_scalar_hash {
     use super::*;
 
-    use digest::{Digest, FixedOutput};
-    use ecdsa::hazmat::DigestPrimitive;
-    use elliptic_curve::{ops::Reduce, Curve};
-    use k256::{FieldBytes, Scalar, Secp256k1};
+    use k256::Secp256k1;
+
+    /// The domain separation tag used for hashing messages in tests.
+    const TEST_DST: &[u8] = b"cait-sith v0.8.0 test message hash";
 
-    #[cfg(test)]
     pub(crate) fn scalar_hash(msg: &[u8]) -> <Secp256k1 as CurveArithmetic>::Scalar {
-        let digest = <Secp256k1 as DigestPrimitive>::Digest::new_with_prefix(msg);
-        let m_bytes: FieldBytes = digest.finalize_fixed();
-        <Scalar as Reduce<<Secp256k1 as Curve>::Uint>>::reduce_bytes(&m_bytes)
+        Secp256k1::hash_to_field(msg, TEST_DST)
+    }
+
+    #[test]
+    fn test_hash_to_field_matches_rust_crypto() {
+        use elliptic_curve::hash2curve::GroupDigest;
+        use p256::NistP256;
+
+        for msg in [&b""[..], b"abc", &[0xAB; 300]] {
+            assert_eq!(
+                Secp256k1::hash_to_field(msg, TEST_DST),
+                <Secp256k1 as GroupDigest>::hash_to_scalar::<ExpandMsgXmd<Sha256>>(
+                    &[msg],
+                    &[TEST_DST]
+                )
+                .unwrap()
+            );
+            assert_eq!(
+                NistP256::hash_to_field(msg, TEST_DST),
+                <NistP256 as GroupDigest>::hash_to_scalar::<ExpandMsgXmd<Sha256>>(
+                    &[msg],
+                    &[TEST_DST]
+                )
+                .unwrap()
+            );
+        }
+        assert_ne!(
+            Secp256k1::hash_to_field(b"abc", TEST_DST),
+            Secp256k1::hash_to_field(b"abc", b"another tag")
+        );
     }
 }
 
 #[cfg(test)]
 pub(crate) use test_scalar_hash::scalar_hash;
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug)]
 pub(crate) struct SerializablePoint<C: CSCurve>(C::AffinePoint);
 
 impl<C: CSCurve> SerializablePoint<C> {
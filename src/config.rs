@@ -0,0 +1,699 @@
+//! Builders for setting up protocols, checking their parameters up front.
+//!
+//! The functions starting each protocol take all of their parameters at once, and
+//! report problems with them as an [`InitializationError`], holding a description.
+//! Some problems, like a triple with the wrong threshold, only surface once the
+//! protocol runs, as a failure in some round. The builders here, like [`KeygenConfig`],
+//! and [`PresignConfig`], collect the parameters one at a time, and check all of them
+//! before starting anything, returning a [`ConfigError`] saying exactly what's wrong.
+//!
+//! ```ignore
+//! let protocol = KeygenConfig::new()
+//!     .participants(&participants)
+//!     .me(me)
+//!     .threshold(2)
+//!     .build::<Secp256k1>()?;
+//! ```
+use std::{collections::HashSet, error, fmt};
+
+use rand_core::OsRng;
+
+use crate::{
+    compat::CSCurve,
+    fiat_shamir::FiatShamirBackend,
+    keyshare::keygen_with_options,
+    presign_many,
+    protocol::{InitializationError, Participant, Protocol},
+    sign_many,
+    triples::{
+        generate_triple_with_options, OtPrg, StoredTriple, TripleGenerationOutput, TripleId,
+    },
+    vault::ShareVault,
+    FullSignature, KeygenOutput, PresignArguments, PresignOutput, PresignatureId, ProofTransform,
+};
+
+/// Represents a problem with the parameters given to a builder.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// A required parameter, with this name, wasn't given.
+    Missing(&'static str),
+    /// The protocol needs at least two participants, but only this many were given.
+    TooFewParticipants(usize),
+    /// This participant was listed more than once.
+    DuplicateParticipant(Participant),
+    /// This participant needs to be amongst the participants, but isn't.
+    NotAParticipant(Participant),
+    /// The threshold needs to be between 1 and the number of participants.
+    ThresholdOutOfRange {
+        threshold: usize,
+        participants: usize,
+    },
+    /// Something was created with another threshold than the one given.
+    ThresholdMismatch { expected: usize, found: usize },
+    /// There's nothing to do, since no item was added to the batch.
+    EmptyBatch,
+    /// This triple was given more than once.
+    DuplicateTriple(TripleId),
+    /// This presignature was given more than once.
+    DuplicatePresignature(PresignatureId),
+    /// The protocol rejected the parameters, for a reason not checked by the builder.
+    Other(InitializationError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(name) => write!(f, "missing parameter: {}", name),
+            Self::TooFewParticipants(n) => {
+                write!(f, "need at least 2 participants, found: {}", n)
+            }
+            Self::DuplicateParticipant(p) => write!(f, "participant {:?} is listed twice", p),
+            Self::NotAParticipant(p) => write!(f, "{:?} is not one of the participants", p),
+            Self::ThresholdOutOfRange {
+                threshold,
+                participants,
+            } => write!(
+                f,
+                "threshold {} must be between 1 and the participant count {}",
+                threshold, participants
+            ),
+            Self::ThresholdMismatch { expected, found } => {
+                write!(f, "expected a threshold of {}, found: {}", expected, found)
+            }
+            Self::EmptyBatch => write!(f, "batch cannot be empty"),
+            Self::DuplicateTriple(id) => write!(f, "triple {:?} is used twice", id),
+            Self::DuplicatePresignature(id) => write!(f, "presignature {:?} is used twice", id),
+            Self::Other(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Other(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<InitializationError> for ConfigError {
+    fn from(e: InitializationError) -> Self {
+        Self::Other(e)
+    }
+}
+
+impl From<ConfigError> for InitializationError {
+    fn from(e: ConfigError) -> Self {
+        match e {
+            ConfigError::Other(e) => e,
+            e => InitializationError::BadParameters(e.to_string()),
+        }
+    }
+}
+
+/// Check that there are enough participants, without duplicates, including `me`.
+fn check_participants(participants: &[Participant], me: Participant) -> Result<(), ConfigError> {
+    if participants.len() < 2 {
+        return Err(ConfigError::TooFewParticipants(participants.len()));
+    }
+    let mut seen = HashSet::with_capacity(participants.len());
+    for &p in participants {
+        if !seen.insert(p) {
+            return Err(ConfigError::DuplicateParticipant(p));
+        }
+    }
+    if !seen.contains(&me) {
+        return Err(ConfigError::NotAParticipant(me));
+    }
+    Ok(())
+}
+
+fn check_threshold(threshold: usize, participants: usize) -> Result<(), ConfigError> {
+    if threshold == 0 || threshold > participants {
+        return Err(ConfigError::ThresholdOutOfRange {
+            threshold,
+            participants,
+        });
+    }
+    Ok(())
+}
+
+/// Sets up key generation, with [`keygen`](crate::keygen).
+#[derive(Debug, Clone, Default)]
+pub struct KeygenConfig {
+    participants: Vec<Participant>,
+    me: Option<Participant>,
+    threshold: Option<usize>,
+    transform: ProofTransform,
+    fiat_shamir: FiatShamirBackend,
+}
+
+impl KeygenConfig {
+    /// Start setting up key generation, without any parameters yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The participants generating the key.
+    pub fn participants(mut self, participants: &[Participant]) -> Self {
+        self.participants = participants.to_vec();
+        self
+    }
+
+    /// Which of the participants we are.
+    pub fn me(mut self, me: Participant) -> Self {
+        self.me = Some(me);
+        self
+    }
+
+    /// The number of participants needed to use the key.
+    pub fn threshold(mut self, threshold: usize) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// How the proofs of knowledge are made, see [`ProofTransform`], which defaults to Fiat-Shamir.
+    pub fn transform(mut self, transform: ProofTransform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// The transcripts backing the proofs, see [`FiatShamirBackend`], which defaults to Meow.
+    pub fn fiat_shamir(mut self, backend: FiatShamirBackend) -> Self {
+        self.fiat_shamir = backend;
+        self
+    }
+
+    /// Check the parameters, without starting anything.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let me = self.me.ok_or(ConfigError::Missing("me"))?;
+        let threshold = self.threshold.ok_or(ConfigError::Missing("threshold"))?;
+        check_participants(&self.participants, me)?;
+        check_threshold(threshold, self.participants.len())
+    }
+
+    /// Check the parameters, and start key generation.
+    pub fn build<C: CSCurve>(
+        &self,
+    ) -> Result<impl Protocol<Output = KeygenOutput<C>>, ConfigError> {
+        self.validate()?;
+        // Unwrapping is fine, since validation checked that these are present.
+        Ok(keygen_with_options(
+            &self.participants,
+            self.me.unwrap(),
+            self.threshold.unwrap(),
+            self.transform,
+            self.fiat_shamir,
+            &mut OsRng,
+        )?)
+    }
+}
+
+/// Sets up the generation of a triple, with [`generate_triple`](crate::triples::generate_triple).
+#[derive(Debug, Clone, Default)]
+pub struct TripleConfig {
+    participants: Vec<Participant>,
+    me: Option<Participant>,
+    threshold: Option<usize>,
+    prg: OtPrg,
+    fiat_shamir: FiatShamirBackend,
+}
+
+impl TripleConfig {
+    /// Start setting up triple generation, without any parameters yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The participants generating the triple.
+    pub fn participants(mut self, participants: &[Participant]) -> Self {
+        self.participants = participants.to_vec();
+        self
+    }
+
+    /// Which of the participants we are.
+    pub fn me(mut self, me: Participant) -> Self {
+        self.me = Some(me);
+        self
+    }
+
+    /// The threshold of the triple, which needs to match that of the key it presigns with.
+    pub fn threshold(mut self, threshold: usize) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// The generator used by the OT extensions, see [`OtPrg`], which defaults to Meow.
+    pub fn prg(mut self, prg: OtPrg) -> Self {
+        self.prg = prg;
+        self
+    }
+
+    /// The transcripts backing the proofs, see [`FiatShamirBackend`], which defaults to Meow.
+    pub fn fiat_shamir(mut self, backend: FiatShamirBackend) -> Self {
+        self.fiat_shamir = backend;
+        self
+    }
+
+    /// Check the parameters, without starting anything.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let me = self.me.ok_or(ConfigError::Missing("me"))?;
+        let threshold = self.threshold.ok_or(ConfigError::Missing("threshold"))?;
+        check_participants(&self.participants, me)?;
+        check_threshold(threshold, self.participants.len())
+    }
+
+    /// Check the parameters, and start generating the triple.
+    pub fn build<C: CSCurve>(
+        &self,
+    ) -> Result<impl Protocol<Output = TripleGenerationOutput<C>>, ConfigError> {
+        self.validate()?;
+        // Unwrapping is fine, since validation checked that these are present.
+        Ok(generate_triple_with_options(
+            &self.participants,
+            self.me.unwrap(),
+            self.threshold.unwrap(),
+            self.prg,
+            self.fiat_shamir,
+            &mut OsRng,
+        )?)
+    }
+}
+
+/// Sets up the creation of presignatures, with [`presign_many`](crate::presign_many).
+///
+/// Each pair of triples added with [`PresignConfig::triples`] creates one presignature.
+#[derive(Debug, Clone)]
+pub struct PresignConfig<C: CSCurve, K = KeygenOutput<C>> {
+    participants: Vec<Participant>,
+    me: Option<Participant>,
+    triple_participants: Option<(Vec<Participant>, Participant)>,
+    threshold: Option<usize>,
+    key: Option<K>,
+    triples: Vec<(StoredTriple<C>, StoredTriple<C>)>,
+}
+
+impl<C: CSCurve, K> Default for PresignConfig<C, K> {
+    fn default() -> Self {
+        Self {
+            participants: Vec::new(),
+            me: None,
+            triple_participants: None,
+            threshold: None,
+            key: None,
+            triples: Vec::new(),
+        }
+    }
+}
+
+impl<C: CSCurve, K: ShareVault<C> + Clone + Send + 'static> PresignConfig<C, K> {
+    /// Start setting up presigning, without any parameters yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The participants creating the presignatures, who will later sign with them.
+    pub fn participants(mut self, participants: &[Participant]) -> Self {
+        self.participants = participants.to_vec();
+        self
+    }
+
+    /// Which of the participants we are.
+    pub fn me(mut self, me: Participant) -> Self {
+        self.me = Some(me);
+        self
+    }
+
+    /// The participants which generated the triples, and which of them we were.
+    ///
+    /// By default, these are the same as the participants presigning.
+    pub fn triple_participants(mut self, participants: &[Participant], me: Participant) -> Self {
+        self.triple_participants = Some((participants.to_vec(), me));
+        self
+    }
+
+    /// The threshold of the key, which needs to match that of every triple.
+    pub fn threshold(mut self, threshold: usize) -> Self {
+        self.threshold = Some(threshold);
+        self
+    }
+
+    /// Our share of the key.
+    pub fn key(mut self, key: K) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    /// Add a presignature to create, with the two triples it uses.
+    pub fn triples(mut self, triple0: StoredTriple<C>, triple1: StoredTriple<C>) -> Self {
+        self.triples.push((triple0, triple1));
+        self
+    }
+
+    /// Check the parameters, without starting anything.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let me = self.me.ok_or(ConfigError::Missing("me"))?;
+        let threshold = self.threshold.ok_or(ConfigError::Missing("threshold"))?;
+        if self.key.is_none() {
+            return Err(ConfigError::Missing("key"));
+        }
+        check_participants(&self.participants, me)?;
+        check_threshold(threshold, self.participants.len())?;
+        if let Some((participants, me)) = &self.triple_participants {
+            check_participants(participants, *me)?;
+        }
+
+        if self.triples.is_empty() {
+            return Err(ConfigError::EmptyBatch);
+        }
+        let mut seen = HashSet::with_capacity(2 * self.triples.len());
+        for (_, triple_pub) in self.triples.iter().flat_map(|(t0, t1)| [t0, t1]) {
+            if triple_pub.threshold != threshold {
+                return Err(ConfigError::ThresholdMismatch {
+                    expected: threshold,
+                    found: triple_pub.threshold,
+                });
+            }
+            let id = TripleId::of(triple_pub);
+            if !seen.insert(id) {
+                return Err(ConfigError::DuplicateTriple(id));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the parameters, and start presigning.
+    pub fn build(self) -> Result<impl Protocol<Output = Vec<PresignOutput<C>>>, ConfigError> {
+        self.validate()?;
+        // Unwrapping is fine, since validation checked that these are present.
+        let me = self.me.unwrap();
+        let threshold = self.threshold.unwrap();
+        let key = self.key.unwrap();
+        let (triple_participants, triple_me) = self
+            .triple_participants
+            .unwrap_or_else(|| (self.participants.clone(), me));
+        let args = self
+            .triples
+            .into_iter()
+            .map(|(triple0, triple1)| PresignArguments {
+                triple0,
+                triple1,
+                keygen_out: key.clone(),
+                threshold,
+            })
+            .collect();
+        Ok(presign_many(
+            &self.participants,
+            me,
+            &triple_participants,
+            triple_me,
+            args,
+        )?)
+    }
+}
+
+/// Sets up signing, with [`sign_many`](crate::sign_many).
+///
+/// Each message added with [`SignConfig::message`] gets signed, in order.
+#[derive(Debug, Clone, Default)]
+pub struct SignConfig<C: CSCurve> {
+    participants: Vec<Participant>,
+    me: Option<Participant>,
+    public_key: Option<C::AffinePoint>,
+    messages: Vec<(PresignOutput<C>, C::Scalar)>,
+}
+
+impl<C: CSCurve> SignConfig<C> {
+    /// Start setting up signing, without any parameters yet.
+    pub fn new() -> Self {
+        Self {
+            participants: Vec::new(),
+            me: None,
+            public_key: None,
+            messages: Vec::new(),
+        }
+    }
+
+    /// The participants signing, which need to be those which created the presignatures.
+    pub fn participants(mut self, participants: &[Participant]) -> Self {
+        self.participants = participants.to_vec();
+        self
+    }
+
+    /// Which of the participants we are.
+    pub fn me(mut self, me: Participant) -> Self {
+        self.me = Some(me);
+        self
+    }
+
+    /// The public key to sign under.
+    pub fn public_key(mut self, public_key: C::AffinePoint) -> Self {
+        self.public_key = Some(public_key);
+        self
+    }
+
+    /// Add a message hash to sign, with the presignature to use.
+    ///
+    /// **WARNING** As with [`sign`](crate::sign), the message must be hashed first.
+    pub fn message(mut self, presignature: PresignOutput<C>, msg_hash: C::Scalar) -> Self {
+        self.messages.push((presignature, msg_hash));
+        self
+    }
+
+    /// Check the parameters, without starting anything.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let me = self.me.ok_or(ConfigError::Missing("me"))?;
+        if self.public_key.is_none() {
+            return Err(ConfigError::Missing("public key"));
+        }
+        check_participants(&self.participants, me)?;
+        if self.messages.is_empty() {
+            return Err(ConfigError::EmptyBatch);
+        }
+        let mut seen = HashSet::with_capacity(self.messages.len());
+        for (presignature, _) in &self.messages {
+            let id = PresignatureId::of(presignature);
+            if !seen.insert(id) {
+                return Err(ConfigError::DuplicatePresignature(id));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the parameters, and start signing.
+    pub fn build(self) -> Result<impl Protocol<Output = Vec<FullSignature<C>>>, ConfigError> {
+        self.validate()?;
+        let (presignatures, msg_hashes) = self.messages.into_iter().unzip();
+        // Unwrapping is fine, since validation checked that these are present.
+        Ok(sign_many(
+            &self.participants,
+            self.me.unwrap(),
+            self.public_key.unwrap(),
+            presignatures,
+            msg_hashes,
+        )?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::{compat::scalar_hash, math::Polynomial, protocol::run_protocol, triples};
+
+    #[test]
+    fn test_keygen_config() -> Result<(), Box<dyn error::Error>> {
+        let participants: Vec<_> = (0..3u32).map(Participant::from).collect();
+        let config = KeygenConfig::new().participants(&participants).threshold(2);
+        assert!(matches!(config.validate(), Err(ConfigError::Missing("me"))));
+
+        let validate = |participants: &[Participant], me, threshold| {
+            KeygenConfig::new()
+                .participants(participants)
+                .me(me)
+                .threshold(threshold)
+                .validate()
+        };
+        let stranger = Participant::from(7u32);
+        assert!(matches!(
+            validate(&participants, stranger, 2),
+            Err(ConfigError::NotAParticipant(p)) if p == stranger
+        ));
+        assert!(matches!(
+            validate(&participants[..1], participants[0], 1),
+            Err(ConfigError::TooFewParticipants(1))
+        ));
+        let duplicated = [participants[0], participants[1], participants[0]];
+        assert!(matches!(
+            validate(&duplicated, participants[0], 2),
+            Err(ConfigError::DuplicateParticipant(p)) if p == participants[0]
+        ));
+        for threshold in [0, 4] {
+            assert!(matches!(
+                validate(&participants, participants[0], threshold),
+                Err(ConfigError::ThresholdOutOfRange {
+                    participants: 3,
+                    ..
+                })
+            ));
+        }
+
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        for &p in &participants {
+            let config = config.clone().me(p);
+            protocols.push((p, Box::new(config.build::<Secp256k1>()?)));
+        }
+        let result = run_protocol(protocols)?;
+        assert_eq!(result[0].1.public_key, result[1].1.public_key);
+        Ok(())
+    }
+
+    #[test]
+    fn test_fiat_shamir_config() -> Result<(), Box<dyn error::Error>> {
+        let participants: Vec<_> = (0..3u32).map(Participant::from).collect();
+        let config = KeygenConfig::new()
+            .participants(&participants)
+            .threshold(2)
+            .transform(ProofTransform::Fischlin);
+
+        let keygen = |backend: &dyn Fn(Participant) -> FiatShamirBackend| {
+            #[allow(clippy::type_complexity)]
+            let mut protocols: Vec<(
+                Participant,
+                Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+            )> = Vec::with_capacity(participants.len());
+            for &p in &participants {
+                let config = config.clone().me(p).fiat_shamir(backend(p));
+                protocols.push((p, Box::new(config.build::<Secp256k1>().unwrap())));
+            }
+            run_protocol(protocols)
+        };
+        let result = keygen(&|_| FiatShamirBackend::Merlin)?;
+        assert_eq!(result[0].1.public_key, result[2].1.public_key);
+        // Participants which disagree on the backend can't finish together.
+        assert!(keygen(&|p| if p == participants[0] {
+            FiatShamirBackend::Merlin
+        } else {
+            FiatShamirBackend::Meow
+        })
+        .is_err());
+
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = TripleGenerationOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(2);
+        for &p in &participants[..2] {
+            let config = TripleConfig::new()
+                .participants(&participants[..2])
+                .me(p)
+                .threshold(2)
+                .fiat_shamir(FiatShamirBackend::Merlin);
+            protocols.push((p, Box::new(config.build::<Secp256k1>()?)));
+        }
+        let result = run_protocol(protocols)?;
+        assert_eq!(result[0].1 .1, result[1].1 .1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_presign_and_sign_config() -> Result<(), Box<dyn error::Error>> {
+        let participants: Vec<_> = (0..2u32).map(Participant::from).collect();
+        let threshold = 2;
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let public_key = (ProjectivePoint::GENERATOR * f.evaluate_zero()).to_affine();
+        let key = |p: Participant| KeygenOutput {
+            private_share: f.evaluate(&p.scalar::<Secp256k1>()),
+            public_key,
+        };
+        let (triple0_pub, triple0_shares) = triples::deal(&mut OsRng, &participants, threshold);
+        let (triple1_pub, triple1_shares) = triples::deal(&mut OsRng, &participants, threshold);
+        let (other_pub, other_shares) = triples::deal(&mut OsRng, &participants, 1);
+
+        let config = |i: usize| {
+            PresignConfig::new()
+                .participants(&participants)
+                .me(participants[i])
+                .threshold(threshold)
+                .key(key(participants[i]))
+        };
+        let triple = |i: usize, j: usize| match j {
+            0 => (triple0_shares[i].clone(), triple0_pub.clone()),
+            1 => (triple1_shares[i].clone(), triple1_pub.clone()),
+            _ => (other_shares[i].clone(), other_pub.clone()),
+        };
+        assert!(matches!(config(0).validate(), Err(ConfigError::EmptyBatch)));
+        assert!(matches!(
+            config(0).triples(triple(0, 0), triple(0, 0)).validate(),
+            Err(ConfigError::DuplicateTriple(id)) if id == TripleId::of(&triple0_pub)
+        ));
+        assert!(matches!(
+            config(0).triples(triple(0, 0), triple(0, 2)).validate(),
+            Err(ConfigError::ThresholdMismatch {
+                expected: 2,
+                found: 1
+            })
+        ));
+        assert!(matches!(
+            PresignConfig::<Secp256k1>::new()
+                .participants(&participants)
+                .me(participants[0])
+                .threshold(threshold)
+                .triples(triple(0, 0), triple(0, 1))
+                .validate(),
+            Err(ConfigError::Missing("key"))
+        ));
+
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = Vec<PresignOutput<Secp256k1>>>>,
+        )> = Vec::with_capacity(participants.len());
+        for (i, &p) in participants.iter().enumerate() {
+            let protocol = config(i).triples(triple(i, 0), triple(i, 1)).build()?;
+            protocols.push((p, Box::new(protocol)));
+        }
+        let presignatures = run_protocol(protocols)?;
+
+        let msg_hash = scalar_hash(b"hello?");
+        let sign_config = |me: Participant| {
+            SignConfig::new()
+                .participants(&participants)
+                .me(me)
+                .public_key(public_key)
+        };
+        let presignature = presignatures[0].1[0].clone();
+        assert!(matches!(
+            sign_config(participants[0]).validate(),
+            Err(ConfigError::EmptyBatch)
+        ));
+        assert!(matches!(
+            sign_config(participants[0])
+                .message(presignature.clone(), msg_hash)
+                .message(presignature.clone(), Scalar::ONE)
+                .validate(),
+            Err(ConfigError::DuplicatePresignature(id)) if id == PresignatureId::of(&presignature)
+        ));
+
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = Vec<FullSignature<Secp256k1>>>>,
+        )> = Vec::with_capacity(participants.len());
+        for (p, presignatures) in presignatures {
+            let presignature = presignatures.into_iter().next().unwrap();
+            let protocol = sign_config(p).message(presignature, msg_hash).build()?;
+            protocols.push((p, Box::new(protocol)));
+        }
+        let signatures = run_protocol(protocols)?;
+        assert!(signatures[0].1[0].verify(&public_key, &msg_hash));
+        Ok(())
+    }
+}
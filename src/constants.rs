@@ -1,2 +1,10 @@
 /// The security parameter we use for different constructions
+///
+/// This is 128 bits by default, and 256 bits with the `security-256` feature,
+/// which raises the security level of OT extension, at the cost of larger
+/// messages, and more computation, during triple generation.
+/// All participants need to use the same value.
+#[cfg(not(feature = "security-256"))]
 pub const SECURITY_PARAMETER: usize = 128;
+#[cfg(feature = "security-256")]
+pub const SECURITY_PARAMETER: usize = 256;
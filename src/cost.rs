@@ -0,0 +1,257 @@
+//! This module measures how much communication each protocol needs.
+//!
+//! The functions here run a protocol between all of the participants, in
+//! the current process, counting the rounds, messages, and bytes of each participant.
+//! Because they run the actual protocol, with the given parameters, the counts
+//! are exact, and stay in sync with the implementation, which makes them useful for
+//! capacity planning, and for catching regressions across releases.
+//! The flip side is that they take as long as running the protocol does.
+//!
+//! Rounds are counted by running the participants in lock-step: in each step,
+//! every participant sends all of the messages it can, and these are only delivered
+//! at the end of the step. A participant's round count is the number of steps in which it sent something.
+use std::collections::HashMap;
+
+use elliptic_curve::{Field, Group};
+use rand_core::OsRng;
+
+use crate::{
+    compat::CSCurve,
+    math::Polynomial,
+    protocol::{Action, InitializationError, MessageData, Participant, Protocol, ProtocolError},
+    triples::{self, TripleGenerationOutput},
+    KeygenOutput, PresignArguments, PresignOutput,
+};
+
+/// The communication used by one participant in a protocol.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Cost {
+    /// The number of rounds in which this participant sent messages.
+    pub rounds: usize,
+    /// The number of messages sent, counting a message sent to everyone once per recipient.
+    pub messages: usize,
+    /// The number of bytes sent, counting a message sent to everyone once per recipient.
+    pub bytes_sent: usize,
+    /// The number of bytes received.
+    pub bytes_received: usize,
+}
+
+/// Run a protocol to completion, measuring the communication of each participant.
+///
+/// This works like [run_protocol()](crate::protocol::run_protocol), except that
+/// it returns the cost of each participant along with its output.
+#[allow(clippy::type_complexity)]
+pub fn measure<T>(
+    mut ps: Vec<(Participant, Box<dyn Protocol<Output = T>>)>,
+) -> Result<Vec<(Participant, Cost, T)>, ProtocolError> {
+    let indices: HashMap<Participant, usize> =
+        ps.iter().enumerate().map(|(i, (p, _))| (*p, i)).collect();
+
+    let size = ps.len();
+    let mut costs = vec![Cost::default(); size];
+    let mut outs: Vec<Option<T>> = (0..size).map(|_| None).collect();
+    let mut done = 0;
+    while done < size {
+        let mut in_flight: Vec<(usize, Participant, MessageData)> = Vec::new();
+        for i in 0..size {
+            let from = ps[i].0;
+            let mut sent = false;
+            loop {
+                match ps[i].1.poke()? {
+                    Action::Wait => break,
+                    Action::SendMany(m) => {
+                        for j in (0..size).filter(|j| *j != i) {
+                            costs[i].messages += 1;
+                            costs[i].bytes_sent += m.len();
+                            in_flight.push((j, from, m.clone()));
+                        }
+                        sent = true;
+                    }
+                    Action::SendPrivate(to, m) => {
+                        costs[i].messages += 1;
+                        costs[i].bytes_sent += m.len();
+                        in_flight.push((indices[&to], from, m));
+                        sent = true;
+                    }
+                    Action::Return(r) => {
+                        if outs[i].is_none() {
+                            outs[i] = Some(r);
+                            done += 1;
+                        }
+                        break;
+                    }
+                }
+            }
+            if sent {
+                costs[i].rounds += 1;
+            }
+        }
+        if in_flight.is_empty() && done < size {
+            return Err(ProtocolError::Other(
+                "protocol stopped before every participant returned".into(),
+            ));
+        }
+        for (j, from, m) in in_flight {
+            costs[j].bytes_received += m.len();
+            ps[j].1.message(from, m);
+        }
+    }
+
+    Ok(ps
+        .into_iter()
+        .zip(costs)
+        .zip(outs)
+        .map(|(((p, _), cost), out)| (p, cost, out.unwrap()))
+        .collect())
+}
+
+fn participants(count: usize) -> Vec<Participant> {
+    (0..count as u32).map(Participant::from).collect()
+}
+
+fn boxed<T, P: Protocol<Output = T> + 'static>(
+    protocol: Result<P, InitializationError>,
+) -> Result<Box<dyn Protocol<Output = T>>, ProtocolError> {
+    match protocol {
+        Ok(protocol) => Ok(Box::new(protocol)),
+        Err(e) => Err(ProtocolError::Other(Box::new(e))),
+    }
+}
+
+fn costs<T>(measured: Vec<(Participant, Cost, T)>) -> Vec<(Participant, Cost)> {
+    measured.into_iter().map(|(p, cost, _)| (p, cost)).collect()
+}
+
+/// Measure the cost of [keygen()](crate::keygen), with a given number of participants.
+pub fn keygen<C: CSCurve>(
+    participant_count: usize,
+    threshold: usize,
+) -> Result<Vec<(Participant, Cost)>, ProtocolError> {
+    let participants = participants(participant_count);
+    let ps = participants
+        .iter()
+        .map(|p| Ok((*p, boxed(crate::keygen::<C>(&participants, *p, threshold))?)))
+        .collect::<Result<_, ProtocolError>>()?;
+    Ok(costs(measure(ps)?))
+}
+
+/// Measure the cost of generating a batch of `N` triples, with [triples::generate_triple_many].
+#[allow(clippy::type_complexity)]
+pub fn triple_generation<C: CSCurve, const N: usize>(
+    participant_count: usize,
+    threshold: usize,
+) -> Result<Vec<(Participant, Cost)>, ProtocolError> {
+    let participants = participants(participant_count);
+    let ps: Vec<(
+        _,
+        Box<dyn Protocol<Output = Vec<TripleGenerationOutput<C>>>>,
+    )> = participants
+        .iter()
+        .map(|p| {
+            let protocol = triples::generate_triple_many::<C, N>(&participants, *p, threshold);
+            Ok((*p, boxed(protocol)?))
+        })
+        .collect::<Result<_, ProtocolError>>()?;
+    Ok(costs(measure(ps)?))
+}
+
+/// Run presigning with dealt triples and keys, measuring its cost.
+#[allow(clippy::type_complexity)]
+fn measure_presign<C: CSCurve>(
+    participants: &[Participant],
+    threshold: usize,
+) -> Result<(C::AffinePoint, Vec<(Participant, Cost, PresignOutput<C>)>), ProtocolError> {
+    let f = Polynomial::<C>::random(&mut OsRng, threshold);
+    let public_key = (C::ProjectivePoint::generator() * f.evaluate_zero()).into();
+    let (triple0_pub, triple0_shares) = triples::deal::<C>(&mut OsRng, participants, threshold);
+    let (triple1_pub, triple1_shares) = triples::deal::<C>(&mut OsRng, participants, threshold);
+
+    let mut ps: Vec<(_, Box<dyn Protocol<Output = PresignOutput<C>>>)> = Vec::new();
+    for ((p, triple0), triple1) in participants.iter().zip(triple0_shares).zip(triple1_shares) {
+        let keygen_out = KeygenOutput {
+            private_share: f.evaluate(&p.scalar::<C>()),
+            public_key,
+        };
+        let protocol = crate::presign(
+            participants,
+            *p,
+            participants,
+            *p,
+            PresignArguments {
+                triple0: (triple0, triple0_pub.clone()),
+                triple1: (triple1, triple1_pub.clone()),
+                keygen_out,
+                threshold,
+            },
+        );
+        ps.push((*p, boxed(protocol)?));
+    }
+    Ok((public_key, measure(ps)?))
+}
+
+/// Measure the cost of [presign()](crate::presign), with a given number of participants.
+///
+/// This uses triples and a key which are dealt locally, rather than generated.
+pub fn presign<C: CSCurve>(
+    participant_count: usize,
+    threshold: usize,
+) -> Result<Vec<(Participant, Cost)>, ProtocolError> {
+    let participants = participants(participant_count);
+    let (_, measured) = measure_presign::<C>(&participants, threshold)?;
+    Ok(costs(measured))
+}
+
+/// Measure the cost of [sign()](crate::sign), with a given number of participants.
+///
+/// This presigns with triples and a key which are dealt locally, and only
+/// measures the signing protocol itself.
+pub fn sign<C: CSCurve>(
+    participant_count: usize,
+    threshold: usize,
+) -> Result<Vec<(Participant, Cost)>, ProtocolError> {
+    let participants = participants(participant_count);
+    let (public_key, presignatures) = measure_presign::<C>(&participants, threshold)?;
+    let msg_hash = C::Scalar::random(&mut OsRng);
+    let ps = presignatures
+        .into_iter()
+        .map(|(p, _, presignature)| {
+            let protocol = crate::sign(&participants, p, public_key, presignature, msg_hash);
+            Ok((p, boxed(protocol)?))
+        })
+        .collect::<Result<_, ProtocolError>>()?;
+    Ok(costs(measure(ps)?))
+}
+
+#[cfg(test)]
+mod test {
+    use k256::Secp256k1;
+
+    use super::*;
+
+    fn check_consistent(costs: &[(Participant, Cost)]) {
+        let sent: usize = costs.iter().map(|(_, c)| c.bytes_sent).sum();
+        let received: usize = costs.iter().map(|(_, c)| c.bytes_received).sum();
+        assert_eq!(sent, received);
+        assert!(costs.iter().all(|(_, c)| c.bytes_sent > 0));
+    }
+
+    #[test]
+    fn test_keygen_cost() -> Result<(), ProtocolError> {
+        let costs = keygen::<Secp256k1>(3, 2)?;
+        assert_eq!(costs.len(), 3);
+        check_consistent(&costs);
+        // Commitments, and then confirmations, polynomials, and shares together.
+        assert!(costs.iter().all(|(_, c)| c.rounds == 2));
+        assert!(costs.iter().all(|(_, c)| c.messages == 4 * 2));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_cost() -> Result<(), ProtocolError> {
+        let costs = sign::<Secp256k1>(3, 2)?;
+        check_consistent(&costs);
+        assert!(costs.iter().all(|(_, c)| c.rounds == 1 && c.messages == 2));
+        check_consistent(&presign::<Secp256k1>(3, 2)?);
+        Ok(())
+    }
+}
@@ -1,10 +1,11 @@
 use std::io::Write;
 
 use ck_meow::Meow;
+use magikitten::MeowRng;
 use rand_core::CryptoRngCore;
 use serde::{Deserialize, Serialize};
 
-use crate::serde::encode_writer;
+use crate::serde::{encode_writer, Schema, Wire};
 
 const COMMIT_LABEL: &[u8] = b"cait-sith v0.8.0 commitment";
 const COMMIT_LEN: usize = 32;
@@ -95,7 +96,7 @@ pub fn commit<T: Serialize, R: CryptoRngCore>(rng: &mut R, val: &T) -> (Commitme
 }
 
 /// The output of a generic hash function.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Digest([u8; HASH_LEN]);
 
 impl AsRef<[u8]> for Digest {
@@ -104,6 +105,21 @@ impl AsRef<[u8]> for Digest {
     }
 }
 
+// Randomizers, commitments, and digests are sent as their bytes.
+macro_rules! impl_wire_bytes {
+    ($($ty:ty),*) => {
+        $(
+            impl Wire for $ty {
+                fn schema() -> Schema {
+                    Schema::Bytes
+                }
+            }
+        )*
+    };
+}
+
+impl_wire_bytes!(Randomizer, Commitment, Digest);
+
 /// Hash some value to produce a short digest.
 pub fn hash<T: Serialize>(val: &T) -> Digest {
     let mut meow = Meow::new(HASH_LABEL);
@@ -114,3 +130,13 @@ pub fn hash<T: Serialize>(val: &T) -> Digest {
 
     Digest(out)
 }
+
+/// Derive a fresh rng, independent from the one it was drawn from.
+///
+/// This lets each concurrent task inside of a protocol have its own randomness,
+/// so that the values it samples don't depend on how the tasks get scheduled.
+pub fn fork_rng(rng: &mut impl CryptoRngCore) -> MeowRng {
+    let mut seed = [0u8; 32];
+    rng.fill_bytes(&mut seed);
+    MeowRng::new(&seed)
+}
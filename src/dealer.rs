@@ -0,0 +1,177 @@
+//! Generating shares of a key with a trusted dealer.
+//!
+//! Key generation normally runs as a protocol, so that nobody ever learns the key.
+//! Some deployments accept trusting a single dealer instead, which knows the key,
+//! splits it into shares, and then forgets about it. This is also convenient
+//! for creating test fixtures.
+//!
+//! Along with the shares, the dealer produces a [`KeyCommitment`], which commits
+//! to the polynomial used to split the key. This is public, and lets each participant
+//! check that the share it got is consistent with the public key, and with everyone else's.
+use elliptic_curve::{Field, Group};
+use rand_core::{CryptoRngCore, OsRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compat::CSCurve,
+    math::{GroupPolynomial, Polynomial},
+    participants::ParticipantList,
+    protocol::{InitializationError, Participant},
+    KeygenOutput,
+};
+
+/// A public commitment to the shares of a key.
+///
+/// This commits to each coefficient of the polynomial whose evaluations are the shares,
+/// so that the share of any participant can be checked against it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct KeyCommitment<C: CSCurve> {
+    polynomial: GroupPolynomial<C>,
+}
+
+impl<C: CSCurve> KeyCommitment<C> {
+    pub(crate) fn from_polynomial(polynomial: GroupPolynomial<C>) -> Self {
+        Self { polynomial }
+    }
+
+    /// The public key the shares belong to.
+    pub fn public_key(&self) -> C::AffinePoint {
+        self.polynomial.evaluate_zero().into()
+    }
+
+    /// The number of shares needed to reconstruct the key.
+    pub fn threshold(&self) -> usize {
+        self.polynomial.len()
+    }
+
+    /// The public counterpart of the share of a participant.
+    pub fn public_share(&self, participant: Participant) -> C::AffinePoint {
+        self.polynomial.evaluate(&participant.scalar::<C>()).into()
+    }
+
+    /// Check that a private share for a participant matches this commitment.
+    pub fn verify_share(&self, participant: Participant, share: &C::Scalar) -> bool {
+        C::ProjectivePoint::generator() * share
+            == self.polynomial.evaluate(&participant.scalar::<C>())
+    }
+}
+
+/// The result of dealing a key: a commitment to the shares, and the output of each participant.
+pub type DealtKey<C> = (KeyCommitment<C>, Vec<(Participant, KeygenOutput<C>)>);
+
+/// Split a key into shares, with a trusted dealer.
+///
+/// This splits `private_key` if one is given, and a fresh random key otherwise,
+/// so that any `threshold` of the participants can use it, returning the output
+/// of each participant, along with a public commitment to the shares.
+///
+/// The dealer learns the private key, and needs to be trusted to forget it,
+/// and to hand the shares to the right participants, over private channels.
+pub fn deal_key<C: CSCurve>(
+    participants: &[Participant],
+    threshold: usize,
+    private_key: Option<C::Scalar>,
+) -> Result<DealtKey<C>, InitializationError> {
+    deal_key_with_rng(participants, threshold, private_key, &mut OsRng)
+}
+
+/// Like [deal_key()], except drawing all of the randomness from a given rng.
+pub fn deal_key_with_rng<C: CSCurve>(
+    participants: &[Participant],
+    threshold: usize,
+    private_key: Option<C::Scalar>,
+    rng: &mut impl CryptoRngCore,
+) -> Result<DealtKey<C>, InitializationError> {
+    if participants.len() < 2 {
+        return Err(InitializationError::BadParameters(format!(
+            "participant count cannot be < 2, found: {}",
+            participants.len()
+        )));
+    };
+    if threshold > participants.len() {
+        return Err(InitializationError::BadParameters(
+            "threshold must be <= participant count".to_string(),
+        ));
+    }
+    if threshold == 0 {
+        return Err(InitializationError::BadParameters(
+            "threshold cannot be 0".to_string(),
+        ));
+    }
+    if ParticipantList::new(participants).is_none() {
+        return Err(InitializationError::BadParameters(
+            "participant list cannot contain duplicates".to_string(),
+        ));
+    }
+    let private_key = private_key.unwrap_or_else(|| C::Scalar::random(&mut *rng));
+    if bool::from(private_key.is_zero()) {
+        return Err(InitializationError::BadParameters(
+            "private key cannot be zero".to_string(),
+        ));
+    }
+
+    let f = Polynomial::<C>::extend_random(rng, threshold, &private_key);
+    let commitment = KeyCommitment::from_polynomial(f.commit());
+    let public_key = commitment.public_key();
+    let outputs = participants
+        .iter()
+        .map(|&p| {
+            let output = KeygenOutput {
+                private_share: f.evaluate(&p.scalar::<C>()),
+                public_key,
+            };
+            (p, output)
+        })
+        .collect();
+    Ok((commitment, outputs))
+}
+
+#[cfg(test)]
+mod test {
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
+
+    use super::*;
+    use crate::serde::{decode, encode};
+
+    #[test]
+    fn test_deal_key() -> Result<(), Box<dyn std::error::Error>> {
+        let participants: Vec<_> = (0..5u32).map(Participant::from).collect();
+        let private_key = Scalar::random(&mut OsRng);
+        let (commitment, outputs) = deal_key::<Secp256k1>(&participants, 3, Some(private_key))?;
+
+        let public_key = (ProjectivePoint::GENERATOR * private_key).to_affine();
+        assert_eq!(commitment.public_key(), public_key);
+        assert_eq!(commitment.threshold(), 3);
+        let commitment: KeyCommitment<Secp256k1> = decode(&encode(&commitment))?;
+        for (p, output) in &outputs {
+            assert_eq!(output.public_key, public_key);
+            assert!(commitment.verify_share(*p, &output.private_share));
+            assert_eq!(
+                commitment.public_share(*p),
+                (ProjectivePoint::GENERATOR * output.private_share).to_affine()
+            );
+        }
+        assert!(!commitment.verify_share(participants[0], &outputs[1].1.private_share));
+
+        // Any threshold of the shares reconstruct the key.
+        let list = ParticipantList::new(&participants[2..]).unwrap();
+        let reconstructed: Scalar = outputs[2..]
+            .iter()
+            .map(|(p, output)| list.lagrange::<Secp256k1>(*p) * output.private_share)
+            .sum();
+        assert_eq!(reconstructed, private_key);
+        Ok(())
+    }
+
+    #[test]
+    fn test_deal_key_parameters() {
+        let participants: Vec<_> = (0..3u32).map(Participant::from).collect();
+        assert!(deal_key::<Secp256k1>(&participants, 2, None).is_ok());
+        assert!(deal_key::<Secp256k1>(&participants, 4, None).is_err());
+        assert!(deal_key::<Secp256k1>(&participants, 0, None).is_err());
+        assert!(deal_key::<Secp256k1>(&participants[..1], 1, None).is_err());
+        assert!(deal_key::<Secp256k1>(&[participants[0], participants[0]], 1, None).is_err());
+        assert!(deal_key::<Secp256k1>(&participants, 2, Some(Scalar::ZERO)).is_err());
+    }
+}
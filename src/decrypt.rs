@@ -0,0 +1,329 @@
+//! This module provides threshold decryption, using the same key shares as signing.
+//!
+//! This is ECIES, in the form of a key encapsulation mechanism: [`encrypt_key`] picks
+//! an ephemeral scalar `r`, and produces `E = r * G`, along with a symmetric key derived
+//! from `r * X`, where `X` is the public key. The symmetric key can then be used
+//! with any authenticated cipher, which is left to the caller.
+//!
+//! To recover the symmetric key, a set of participants agree on who decrypts,
+//! and each of them produces a [`PartialDecryption`], which contains their
+//! contribution to `x * E`, weighted by their lagrange coefficient, along with their
+//! contribution to `X`, and a proof that both use the same share. A combiner, which
+//! can be anyone, then checks these and adds them up, with [`combine_decryptions`].
+//!
+//! Producing a partial decryption doesn't require any interaction, but whoever
+//! combines the partial decryptions learns the symmetric key. Participants should
+//! only hand their partial decryptions to someone allowed to read the plaintext.
+use std::{error, fmt};
+
+use elliptic_curve::{Field, Group};
+use rand_core::{CryptoRngCore, OsRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compat::{CSCurve, SerializablePoint},
+    crypto::hash,
+    fiat_shamir::{FiatShamir, Transcript},
+    participants::ParticipantList,
+    proofs::dlogeq,
+    protocol::{Fault, InitializationError, Participant},
+    serde::encode,
+    KeygenOutput,
+};
+
+/// The label we use for the transcript of the proofs.
+const LABEL: &[u8] = b"cait-sith v0.8.0 threshold decryption";
+/// The label we use for deriving the symmetric key.
+const KEY_LABEL: &[u8] = b"cait-sith v0.8.0 threshold decryption key";
+
+/// A symmetric key, encrypted to a threshold public key.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct EncryptedKey<C: CSCurve> {
+    ephemeral: SerializablePoint<C>,
+}
+
+impl<C: CSCurve> EncryptedKey<C> {
+    /// The ephemeral point used to encrypt the key.
+    pub fn ephemeral(&self) -> C::AffinePoint {
+        self.ephemeral.to_projective().into()
+    }
+}
+
+/// Derive the symmetric key, from the ephemeral point and the shared point.
+fn derive_key<C: CSCurve>(ephemeral: &C::ProjectivePoint, shared: &C::ProjectivePoint) -> [u8; 32] {
+    let digest = hash(&(
+        KEY_LABEL,
+        C::NAME,
+        SerializablePoint::<C>::from_projective(ephemeral),
+        SerializablePoint::<C>::from_projective(shared),
+    ));
+    let mut out = [0u8; 32];
+    out.copy_from_slice(digest.as_ref());
+    out
+}
+
+/// Encrypt a fresh symmetric key to a public key, returning the encrypted key, and the key itself.
+pub fn encrypt_key<C: CSCurve>(public_key: &C::AffinePoint) -> (EncryptedKey<C>, [u8; 32]) {
+    encrypt_key_with_rng(public_key, &mut OsRng)
+}
+
+/// Like [encrypt_key()], except drawing all of the randomness from a given rng.
+pub fn encrypt_key_with_rng<C: CSCurve>(
+    public_key: &C::AffinePoint,
+    rng: &mut impl CryptoRngCore,
+) -> (EncryptedKey<C>, [u8; 32]) {
+    let r = C::Scalar::random(rng);
+    let ephemeral = C::ProjectivePoint::generator() * r;
+    let shared = C::ProjectivePoint::from(*public_key) * r;
+    let key = derive_key::<C>(&ephemeral, &shared);
+    let encrypted = EncryptedKey {
+        ephemeral: SerializablePoint::from_projective(&ephemeral),
+    };
+    (encrypted, key)
+}
+
+/// The contribution of one participant to decrypting a key.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct PartialDecryption<C: CSCurve> {
+    public: SerializablePoint<C>,
+    shared: SerializablePoint<C>,
+    proof: dlogeq::Proof<C>,
+}
+
+fn decryption_transcript<C: CSCurve>(
+    participants: &ParticipantList,
+    encrypted: &EncryptedKey<C>,
+) -> Transcript {
+    let mut transcript = Transcript::new(LABEL);
+    transcript.message(b"group", C::NAME);
+    transcript.message(b"participants", &encode(participants));
+    transcript.message(b"ephemeral", &encode(&encrypted.ephemeral));
+    transcript
+}
+
+fn check_participants(participants: &[Participant]) -> Result<ParticipantList, &'static str> {
+    if participants.len() < 2 {
+        return Err("participant count cannot be < 2");
+    }
+    ParticipantList::new(participants).ok_or("participant list cannot contain duplicates")
+}
+
+/// Produce our partial decryption of a key, as one of the participants decrypting it.
+///
+/// The participants need to be the same for everyone, and contain at least a threshold of
+/// the participants which generated the key.
+pub fn partial_decrypt<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    keygen_out: &KeygenOutput<C>,
+    encrypted: &EncryptedKey<C>,
+) -> Result<PartialDecryption<C>, InitializationError> {
+    partial_decrypt_with_rng(participants, me, keygen_out, encrypted, &mut OsRng)
+}
+
+/// Like [partial_decrypt()], except drawing all of the randomness from a given rng.
+pub fn partial_decrypt_with_rng<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    keygen_out: &KeygenOutput<C>,
+    encrypted: &EncryptedKey<C>,
+    rng: &mut impl CryptoRngCore,
+) -> Result<PartialDecryption<C>, InitializationError> {
+    let participants = check_participants(participants)
+        .map_err(|e| InitializationError::BadParameters(e.to_string()))?;
+    if !participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain this participant".to_string(),
+        ));
+    }
+
+    let transcript = decryption_transcript(&participants, encrypted);
+    let x_i = participants.lagrange::<C>(me) * keygen_out.private_share;
+    let ephemeral = encrypted.ephemeral.to_projective();
+    let public = C::ProjectivePoint::generator() * x_i;
+    let shared = ephemeral * x_i;
+    let proof = dlogeq::prove(
+        rng,
+        &mut transcript.forked(b"dlogeq", &me.bytes()),
+        dlogeq::Statement::<C> {
+            public0: &public,
+            generator1: &ephemeral,
+            public1: &shared,
+        },
+        dlogeq::Witness { x: &x_i },
+    );
+    Ok(PartialDecryption {
+        public: SerializablePoint::from_projective(&public),
+        shared: SerializablePoint::from_projective(&shared),
+        proof,
+    })
+}
+
+/// Represents an error which can happen when combining partial decryptions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecryptionError {
+    /// The partial decryptions can't be combined, regardless of their contents.
+    Malformed(&'static str),
+    /// The partial decryption of some participant failed a check.
+    Faulty {
+        participant: Participant,
+        fault: Fault,
+    },
+    /// The partial decryptions are valid, but don't use shares of the public key.
+    ///
+    /// This happens if fewer than a threshold of participants decrypted, or if
+    /// some of them used the wrong share, which can't be attributed to anyone.
+    WrongKey,
+}
+
+impl fmt::Display for DecryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecryptionError::Malformed(e) => write!(f, "malformed partial decryptions: {}", e),
+            DecryptionError::Faulty { participant, fault } => {
+                write!(f, "participant {:?} is at fault: {}", participant, fault)
+            }
+            DecryptionError::WrongKey => {
+                write!(f, "partial decryptions don't match the public key")
+            }
+        }
+    }
+}
+
+impl error::Error for DecryptionError {}
+
+/// Combine the partial decryptions of every participant, recovering the symmetric key.
+///
+/// The participants need to be the same list given to [`partial_decrypt`].
+pub fn combine_decryptions<C: CSCurve>(
+    participants: &[Participant],
+    public_key: &C::AffinePoint,
+    encrypted: &EncryptedKey<C>,
+    partials: &[(Participant, PartialDecryption<C>)],
+) -> Result<[u8; 32], DecryptionError> {
+    let participants = check_participants(participants).map_err(DecryptionError::Malformed)?;
+    if partials.len() != participants.len()
+        || partials.iter().any(|(p, _)| !participants.contains(*p))
+        || ParticipantList::new(&partials.iter().map(|(p, _)| *p).collect::<Vec<_>>()).is_none()
+    {
+        return Err(DecryptionError::Malformed(
+            "there must be one partial decryption per participant",
+        ));
+    }
+
+    let transcript = decryption_transcript(&participants, encrypted);
+    let ephemeral = encrypted.ephemeral.to_projective();
+    let mut public = C::ProjectivePoint::identity();
+    let mut shared = C::ProjectivePoint::identity();
+    for (p, partial) in partials {
+        let public_j = partial.public.to_projective();
+        let shared_j = partial.shared.to_projective();
+        let statement = dlogeq::Statement::<C> {
+            public0: &public_j,
+            generator1: &ephemeral,
+            public1: &shared_j,
+        };
+        if !dlogeq::verify(
+            &mut transcript.forked(b"dlogeq", &p.bytes()),
+            statement,
+            &partial.proof,
+        ) {
+            return Err(DecryptionError::Faulty {
+                participant: *p,
+                fault: Fault::BadProof,
+            });
+        }
+        public += public_j;
+        shared += shared_j;
+    }
+    if public != C::ProjectivePoint::from(*public_key) {
+        return Err(DecryptionError::WrongKey);
+    }
+
+    Ok(derive_key::<C>(&ephemeral, &shared))
+}
+
+#[cfg(test)]
+mod test {
+    use k256::Secp256k1;
+
+    use super::*;
+    use crate::{
+        deal_key,
+        serde::{decode, encode},
+    };
+
+    #[test]
+    fn test_threshold_decryption() -> Result<(), Box<dyn error::Error>> {
+        let participants: Vec<_> = (0..5u32).map(Participant::from).collect();
+        let (_, outputs) = deal_key::<Secp256k1>(&participants, 3, None)?;
+        let public_key = outputs[0].1.public_key;
+
+        let (encrypted, key) = encrypt_key::<Secp256k1>(&public_key);
+        let encrypted: EncryptedKey<Secp256k1> = decode(&encode(&encrypted))?;
+
+        let decrypters = &participants[1..4];
+        let mut partials = Vec::new();
+        for (p, output) in &outputs[1..4] {
+            let partial = partial_decrypt(decrypters, *p, output, &encrypted)?;
+            let partial: PartialDecryption<Secp256k1> = decode(&encode(&partial))?;
+            partials.push((*p, partial));
+        }
+        assert_eq!(
+            combine_decryptions(decrypters, &public_key, &encrypted, &partials)?,
+            key
+        );
+
+        // Not enough participants.
+        let (p, output) = &outputs[0];
+        let too_few = &participants[..2];
+        let partials_few: Vec<_> = outputs[..2]
+            .iter()
+            .map(|(p, output)| {
+                (
+                    *p,
+                    partial_decrypt(too_few, *p, output, &encrypted).unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            combine_decryptions(too_few, &public_key, &encrypted, &partials_few),
+            Err(DecryptionError::WrongKey)
+        );
+
+        // A partial decryption made for another set of participants.
+        let mut bad = partials.clone();
+        bad[0].1 = partial_decrypt(
+            &participants[..3],
+            participants[1],
+            &outputs[1].1,
+            &encrypted,
+        )?;
+        assert_eq!(
+            combine_decryptions(decrypters, &public_key, &encrypted, &bad),
+            Err(DecryptionError::Faulty {
+                participant: participants[1],
+                fault: Fault::BadProof
+            })
+        );
+
+        let mut bad = partials.clone();
+        bad[1].1 = partials[2].1.clone();
+        assert!(matches!(
+            combine_decryptions(decrypters, &public_key, &encrypted, &bad),
+            Err(DecryptionError::Faulty { .. })
+        ));
+
+        let mut bad = partials;
+        bad.pop();
+        assert!(matches!(
+            combine_decryptions(decrypters, &public_key, &encrypted, &bad),
+            Err(DecryptionError::Malformed(_))
+        ));
+
+        assert!(partial_decrypt(decrypters, *p, output, &encrypted).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,219 @@
+//! Non-hardened hierarchical deterministic derivation, following BIP32.
+//!
+//! Non-hardened derivation only needs the public key, and the chain code,
+//! to compute a tweak, which gets added to the private key.
+//! Because shares of a key are shares of a polynomial, adding the same tweak
+//! to each share produces shares of the tweaked key. This means that each
+//! participant can derive child keys locally, without running any protocol.
+//!
+//! Hardened derivation requires hashing the private key itself, which isn't possible
+//! without reconstructing it, and is thus not supported.
+use std::{error, fmt};
+
+use elliptic_curve::{Field, Group, ScalarPrimitive};
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+
+use crate::{compat::CSCurve, export::public_key_to_sec1, KeygenOutput};
+
+/// The chain code used in BIP32, which gets combined with the public key to derive children.
+pub type ChainCode = [u8; 32];
+
+/// The first index for hardened children.
+const HARDENED: u32 = 1 << 31;
+
+/// Represents an error which can happen when deriving a child key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DerivationError {
+    /// This index is for hardened derivation, which isn't supported.
+    HardenedIndex(u32),
+    /// This index produced an invalid key, and the next index should be used instead.
+    ///
+    /// This happens with negligible probability.
+    InvalidChild(u32),
+}
+
+impl fmt::Display for DerivationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DerivationError::HardenedIndex(i) => {
+                write!(f, "cannot derive hardened child at index {}", i)
+            }
+            DerivationError::InvalidChild(i) => write!(f, "child at index {} is invalid", i),
+        }
+    }
+}
+
+impl error::Error for DerivationError {}
+
+fn derive_child<C: CSCurve>(
+    public_key: &C::AffinePoint,
+    chain_code: &ChainCode,
+    index: u32,
+) -> Result<(C::Scalar, C::AffinePoint, ChainCode), DerivationError> {
+    if index >= HARDENED {
+        return Err(DerivationError::HardenedIndex(index));
+    }
+
+    let mut mac =
+        Hmac::<Sha512>::new_from_slice(chain_code).expect("HMAC accepts keys of any size");
+    mac.update(&public_key_to_sec1::<C>(public_key));
+    mac.update(&index.to_be_bytes());
+    let out = mac.finalize().into_bytes();
+    let (left, right) = out.split_at(32);
+
+    // BIP32 rejects tweaks that are not reduced, rather than reducing them.
+    let tweak: C::Scalar = ScalarPrimitive::<C>::from_slice(left)
+        .map_err(|_| DerivationError::InvalidChild(index))?
+        .into();
+    let child: C::ProjectivePoint =
+        C::ProjectivePoint::from(*public_key) + C::ProjectivePoint::generator() * tweak;
+    if bool::from(child.is_identity()) {
+        return Err(DerivationError::InvalidChild(index));
+    }
+
+    let mut child_chain_code = [0u8; 32];
+    child_chain_code.copy_from_slice(right);
+    Ok((tweak, child.into(), child_chain_code))
+}
+
+/// Derive the tweak, public key, and chain code, for a non-hardened path.
+///
+/// The tweak is the amount that needs to be added to the private key, or to
+/// each share of it, to get the derived private key.
+///
+/// This only requires public information, and can be used to derive addresses
+/// without having a share of the key.
+pub fn derive_public<C: CSCurve>(
+    public_key: &C::AffinePoint,
+    chain_code: &ChainCode,
+    path: &[u32],
+) -> Result<(C::Scalar, C::AffinePoint, ChainCode), DerivationError> {
+    let mut tweak = C::Scalar::ZERO;
+    let mut public_key = *public_key;
+    let mut chain_code = *chain_code;
+    for &index in path {
+        let (tweak_i, public_key_i, chain_code_i) =
+            derive_child::<C>(&public_key, &chain_code, index)?;
+        tweak += tweak_i;
+        public_key = public_key_i;
+        chain_code = chain_code_i;
+    }
+    Ok((tweak, public_key, chain_code))
+}
+
+impl<C: CSCurve> KeygenOutput<C> {
+    /// Derive the output for a child key, along a non-hardened path.
+    ///
+    /// Each participant can do this locally, and the resulting shares can be used
+    /// anywhere the output of key generation can, for example to create presignatures.
+    ///
+    /// This also returns the chain code of the child, in order to derive further.
+    pub fn derive(
+        &self,
+        chain_code: &ChainCode,
+        path: &[u32],
+    ) -> Result<(Self, ChainCode), DerivationError> {
+        let (tweak, public_key, chain_code) =
+            derive_public::<C>(&self.public_key, chain_code, path)?;
+        Ok((
+            KeygenOutput {
+                private_share: self.private_share + tweak,
+                public_key,
+            },
+            chain_code,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::{math::Polynomial, participants::ParticipantList, protocol::Participant};
+
+    fn hex32(s: &str) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        for (i, o) in out.iter_mut().enumerate() {
+            *o = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn test_bip32_vector() {
+        // From the first BIP32 test vector, deriving m/0H/1 from m/0H.
+        let parent_key = Scalar::from(
+            ScalarPrimitive::<Secp256k1>::from_slice(&hex32(
+                "edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea",
+            ))
+            .unwrap(),
+        );
+        let parent_chain_code =
+            hex32("47fdacbd0f1097043b78c63c20c34ef4ed9a111d980047ad16282c7ae6236141");
+        let expected_key =
+            hex32("3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368");
+        let expected_chain_code =
+            hex32("2a7857631386ba23dacac34180dd1983734e444fdbf774041578e9b6adb37c19");
+
+        let parent = KeygenOutput::<Secp256k1> {
+            private_share: parent_key,
+            public_key: (ProjectivePoint::GENERATOR * parent_key).to_affine(),
+        };
+        let (child, chain_code) = parent.derive(&parent_chain_code, &[1]).unwrap();
+        assert_eq!(&child.private_share.to_bytes()[..], &expected_key);
+        assert_eq!(chain_code, expected_chain_code);
+        assert_eq!(
+            (ProjectivePoint::GENERATOR * child.private_share).to_affine(),
+            child.public_key
+        );
+    }
+
+    #[test]
+    fn test_derive_shares() {
+        let participants = [
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, 2);
+        let public_key = (ProjectivePoint::GENERATOR * f.evaluate_zero()).to_affine();
+        let chain_code = [7u8; 32];
+        let path = [0, 42, 7];
+
+        let children: Vec<_> = participants
+            .iter()
+            .map(|p| {
+                let out = KeygenOutput::<Secp256k1> {
+                    private_share: f.evaluate(&p.scalar::<Secp256k1>()),
+                    public_key,
+                };
+                out.derive(&chain_code, &path).unwrap().0
+            })
+            .collect();
+
+        let p_list = ParticipantList::new(&participants[1..]).unwrap();
+        let x = p_list.lagrange::<Secp256k1>(participants[1]) * children[1].private_share
+            + p_list.lagrange::<Secp256k1>(participants[2]) * children[2].private_share;
+        assert_eq!(
+            (ProjectivePoint::GENERATOR * x).to_affine(),
+            children[0].public_key
+        );
+        let (_, expected, _) = derive_public::<Secp256k1>(&public_key, &chain_code, &path).unwrap();
+        assert_eq!(children[0].public_key, expected);
+    }
+
+    #[test]
+    fn test_hardened_rejected() {
+        let out = KeygenOutput::<Secp256k1> {
+            private_share: Scalar::ONE,
+            public_key: ProjectivePoint::GENERATOR.to_affine(),
+        };
+        assert_eq!(
+            out.derive(&[0u8; 32], &[HARDENED]).unwrap_err(),
+            DerivationError::HardenedIndex(HARDENED)
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! Versioned envelopes for persisted values.
+//!
+//! Key shares, triple shares, and presignatures outlive the process producing them,
+//! and will be read by later versions of this library, whose structures may have changed.
+//! Rather than persisting the bare encoding of such a value, [`Versioned::to_envelope`]
+//! wraps it in an envelope, recording the kind of value, the curve, and the version of
+//! its encoding, along with a checksum over all of this.
+//!
+//! [`Versioned::from_envelope`] checks all of these, and decodes values written with an
+//! older version through [`Versioned::migrate`], so that changing a structure means
+//! bumping its version, and writing down how to read the previous one, rather than
+//! silently misreading existing files. Data from before envelopes existed, which
+//! doesn't start with the envelope's magic string, is migrated as version 0.
+//!
+//! An envelope consists of:
+//!
+//! - the magic string `CSENVLOP`;
+//! - the version, as 2 little endian bytes;
+//! - the kind, and then the name of the curve, each preceded by its length, as a byte;
+//! - the encoded value;
+//! - a 32 byte checksum over everything else.
+//!
+//! Envelopes aren't encrypted: secret shares should be sealed, with
+//! [`KeygenOutput::seal`], or kept on encrypted storage.
+use std::{error, fmt};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    compat::CSCurve,
+    crypto::hash,
+    serde::{decode, encode},
+    triples::TripleShare,
+    KeygenOutput, PresignOutput,
+};
+
+const MAGIC: &[u8; 8] = b"CSENVLOP";
+const CHECKSUM_LEN: usize = 32;
+
+/// Represents an error which can happen when opening an envelope.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvelopeError {
+    /// The envelope isn't well formed, for the reason given.
+    Corrupted(String),
+    /// The envelope contains a different kind of value.
+    WrongKind,
+    /// The envelope contains a value over a different curve.
+    WrongCurve,
+    /// The envelope was written with a version we don't know how to read.
+    UnsupportedVersion(u16),
+}
+
+impl fmt::Display for EnvelopeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Corrupted(e) => write!(f, "corrupted envelope: {}", e),
+            Self::WrongKind => write!(f, "envelope contains a different kind of value"),
+            Self::WrongCurve => write!(f, "envelope contains a value over a different curve"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported version {} for envelope", v),
+        }
+    }
+}
+
+impl error::Error for EnvelopeError {}
+
+fn corrupted(e: impl fmt::Display) -> EnvelopeError {
+    EnvelopeError::Corrupted(e.to_string())
+}
+
+/// A value which can be persisted in a versioned envelope.
+pub trait Versioned: Serialize + DeserializeOwned {
+    /// Identifies the kind of value, so that it's never read as another kind.
+    const KIND: &'static [u8];
+    /// The name of the curve the value is over.
+    const CURVE: &'static [u8];
+    /// The current version of the encoding, which should be bumped whenever it changes.
+    const VERSION: u16;
+
+    /// Decode a value written with an older version, turning it into the current structure.
+    ///
+    /// This is only called with versions below [`Self::VERSION`], and by default,
+    /// doesn't support any of them.
+    fn migrate(version: u16, _payload: &[u8]) -> Result<Self, EnvelopeError> {
+        Err(EnvelopeError::UnsupportedVersion(version))
+    }
+
+    /// Encode this value, wrapped in an envelope with the current version.
+    fn to_envelope(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&Self::VERSION.to_le_bytes());
+        for tag in [Self::KIND, Self::CURVE] {
+            out.push(u8::try_from(tag.len()).expect("tag is too long"));
+            out.extend_from_slice(tag);
+        }
+        out.extend_from_slice(&encode(self));
+        let checksum = hash(&out);
+        out.extend_from_slice(checksum.as_ref());
+        out
+    }
+
+    /// Decode a value from an envelope, migrating it if it's from an older version.
+    fn from_envelope(data: &[u8]) -> Result<Self, EnvelopeError> {
+        let Some(rest) = data.strip_prefix(MAGIC) else {
+            return Self::migrate(0, data);
+        };
+        if rest.len() < CHECKSUM_LEN {
+            return Err(corrupted("missing checksum"));
+        }
+        let (contents, checksum) = data.split_at(data.len() - CHECKSUM_LEN);
+        if hash(&contents).as_ref() != checksum {
+            return Err(corrupted("checksum mismatch"));
+        }
+
+        let mut rest = &contents[MAGIC.len()..];
+        let mut take = |n: usize| {
+            if rest.len() < n {
+                return Err(corrupted("truncated header"));
+            }
+            let (head, tail) = rest.split_at(n);
+            rest = tail;
+            Ok(head)
+        };
+        let version = u16::from_le_bytes(take(2)?.try_into().unwrap());
+        let kind_len = take(1)?[0];
+        let kind = take(kind_len.into())?;
+        let curve_len = take(1)?[0];
+        let curve = take(curve_len.into())?;
+        if kind != Self::KIND {
+            return Err(EnvelopeError::WrongKind);
+        }
+        if curve != Self::CURVE {
+            return Err(EnvelopeError::WrongCurve);
+        }
+
+        match version {
+            v if v == Self::VERSION => decode(rest).map_err(corrupted),
+            v if v < Self::VERSION => Self::migrate(v, rest),
+            v => Err(EnvelopeError::UnsupportedVersion(v)),
+        }
+    }
+}
+
+impl<C: CSCurve> Versioned for KeygenOutput<C>
+where
+    Self: Serialize + DeserializeOwned,
+{
+    const KIND: &'static [u8] = b"keygen output";
+    const CURVE: &'static [u8] = C::NAME;
+    const VERSION: u16 = 1;
+
+    /// Version 0 is the bare encoding, which hasn't changed since.
+    fn migrate(version: u16, payload: &[u8]) -> Result<Self, EnvelopeError> {
+        match version {
+            0 => decode(payload).map_err(corrupted),
+            v => Err(EnvelopeError::UnsupportedVersion(v)),
+        }
+    }
+}
+
+impl<C: CSCurve> Versioned for TripleShare<C>
+where
+    Self: Serialize + DeserializeOwned,
+{
+    const KIND: &'static [u8] = b"triple share";
+    const CURVE: &'static [u8] = C::NAME;
+    const VERSION: u16 = 1;
+
+    /// Version 0 is the bare encoding, which hasn't changed since.
+    fn migrate(version: u16, payload: &[u8]) -> Result<Self, EnvelopeError> {
+        match version {
+            0 => decode(payload).map_err(corrupted),
+            v => Err(EnvelopeError::UnsupportedVersion(v)),
+        }
+    }
+}
+
+// Presignatures had no encoding before envelopes, so there's nothing to migrate yet.
+impl<C: CSCurve> Versioned for PresignOutput<C>
+where
+    Self: Serialize + DeserializeOwned,
+{
+    const KIND: &'static [u8] = b"presign output";
+    const CURVE: &'static [u8] = C::NAME;
+    const VERSION: u16 = 1;
+}
+
+#[cfg(test)]
+mod test {
+    use elliptic_curve::Field;
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
+    use rand_core::OsRng;
+
+    use super::*;
+
+    fn share() -> KeygenOutput<Secp256k1> {
+        let x = Scalar::random(&mut OsRng);
+        KeygenOutput {
+            private_share: x,
+            public_key: (ProjectivePoint::GENERATOR * x).to_affine(),
+        }
+    }
+
+    #[test]
+    fn test_envelope_round_trip() -> Result<(), EnvelopeError> {
+        let output = share();
+        let opened = KeygenOutput::<Secp256k1>::from_envelope(&output.to_envelope())?;
+        assert_eq!(opened.private_share, output.private_share);
+        assert_eq!(opened.public_key, output.public_key);
+
+        let presignature = PresignOutput::<Secp256k1> {
+            big_r: output.public_key,
+            k: Scalar::random(&mut OsRng),
+            sigma: Scalar::random(&mut OsRng),
+            k_inv: Scalar::random(&mut OsRng),
+        };
+        let opened = PresignOutput::<Secp256k1>::from_envelope(&presignature.to_envelope())?;
+        assert_eq!(opened.sigma, presignature.sigma);
+        Ok(())
+    }
+
+    #[test]
+    fn test_envelope_migrates_bare_encoding() -> Result<(), EnvelopeError> {
+        let triple = TripleShare::<Secp256k1> {
+            a: Scalar::random(&mut OsRng),
+            b: Scalar::random(&mut OsRng),
+            c: Scalar::random(&mut OsRng),
+        };
+        let opened = TripleShare::<Secp256k1>::from_envelope(&encode(&triple))?;
+        assert_eq!(opened.c, triple.c);
+
+        // Presignatures never had a bare encoding.
+        assert_eq!(
+            PresignOutput::<Secp256k1>::from_envelope(b"anything").err(),
+            Some(EnvelopeError::UnsupportedVersion(0))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_envelope_rejects_mismatches() {
+        let envelope = share().to_envelope();
+
+        assert_eq!(
+            TripleShare::<Secp256k1>::from_envelope(&envelope).err(),
+            Some(EnvelopeError::WrongKind)
+        );
+        assert_eq!(
+            KeygenOutput::<p256::NistP256>::from_envelope(&envelope).err(),
+            Some(EnvelopeError::WrongCurve)
+        );
+
+        let mut tampered = envelope.clone();
+        tampered[20] ^= 1;
+        assert!(matches!(
+            KeygenOutput::<Secp256k1>::from_envelope(&tampered),
+            Err(EnvelopeError::Corrupted(_))
+        ));
+        assert!(matches!(
+            KeygenOutput::<Secp256k1>::from_envelope(&envelope[..MAGIC.len() + 4]),
+            Err(EnvelopeError::Corrupted(_))
+        ));
+
+        // A newer version than we know about, with a valid checksum.
+        let mut newer = envelope[..envelope.len() - CHECKSUM_LEN].to_vec();
+        newer[MAGIC.len()] = 2;
+        let checksum = hash(&newer);
+        newer.extend_from_slice(checksum.as_ref());
+        assert_eq!(
+            KeygenOutput::<Secp256k1>::from_envelope(&newer).err(),
+            Some(EnvelopeError::UnsupportedVersion(2))
+        );
+    }
+}
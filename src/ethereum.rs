@@ -0,0 +1,207 @@
+//! This module provides hashing compatible with Ethereum.
+//!
+//! Ethereum signs the keccak256 hash of a message, interpreted as a big endian
+//! integer, and reduced modulo the order of secp256k1, which is what `ecrecover`
+//! expects. Signing with a different hash, like SHA-256, produces signatures
+//! that verify fine, but recover to a different address.
+//!
+//! Note that keccak256 is the original Keccak submission, which pads differently
+//! than the standardized SHA3-256.
+use elliptic_curve::{ops::Reduce, sec1::ToEncodedPoint};
+use k256::{AffinePoint, FieldBytes, Scalar, Secp256k1, U256};
+
+use crate::FullSignature;
+
+/// The rate of keccak256, in bytes.
+const RATE: usize = 136;
+
+fn absorb(state: &mut [u64; 25], block: &[u8]) {
+    for (lane, chunk) in state.iter_mut().zip(block.chunks_exact(8)) {
+        *lane ^= u64::from_le_bytes(chunk.try_into().expect("chunks should be 8 bytes"));
+    }
+    keccak::f1600(state);
+}
+
+/// Hash some data with keccak256, as used by Ethereum.
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut state = [0u64; 25];
+    let mut blocks = data.chunks_exact(RATE);
+    for block in &mut blocks {
+        absorb(&mut state, block);
+    }
+    let rest = blocks.remainder();
+    let mut last = [0u8; RATE];
+    last[..rest.len()].copy_from_slice(rest);
+    last[rest.len()] ^= 0x01;
+    last[RATE - 1] ^= 0x80;
+    absorb(&mut state, &last);
+
+    let mut out = [0u8; 32];
+    for (chunk, lane) in out.chunks_exact_mut(8).zip(state.iter()) {
+        chunk.copy_from_slice(&lane.to_le_bytes());
+    }
+    out
+}
+
+/// Hash a message to a scalar, the way Ethereum does, for use with [`crate::sign`].
+pub fn ethereum_message_hash(msg: &[u8]) -> Scalar {
+    let mut bytes = FieldBytes::default();
+    bytes.copy_from_slice(&keccak256(msg));
+    <Scalar as Reduce<U256>>::reduce_bytes(&bytes)
+}
+
+/// Hash a message to a scalar, following EIP-191, as done by `personal_sign`.
+///
+/// The message is prefixed with `"\x19Ethereum Signed Message:\n"`, and its length
+/// in decimal, so that signed messages can't be confused with transactions.
+pub fn ethereum_personal_message_hash(msg: &[u8]) -> Scalar {
+    let mut data = format!("\x19Ethereum Signed Message:\n{}", msg.len()).into_bytes();
+    data.extend_from_slice(msg);
+    ethereum_message_hash(&data)
+}
+
+/// The Ethereum address of a public key.
+///
+/// This is the last 20 bytes of the hash of the uncompressed point, without its prefix.
+pub fn ethereum_address(public_key: &AffinePoint) -> [u8; 20] {
+    let point = public_key.to_encoded_point(false);
+    let hash = keccak256(&point.as_bytes()[1..]);
+    let mut out = [0u8; 20];
+    out.copy_from_slice(&hash[12..]);
+    out
+}
+
+/// A signature, split into the `(r, s, v)` fields of an Ethereum transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EthereumSignature {
+    /// The x coordinate of the first point, reduced, in big endian.
+    pub r: [u8; 32],
+    /// The second scalar, which is always low, in big endian.
+    pub s: [u8; 32],
+    /// The recovery value, which depends on the chain id, with EIP-155.
+    pub v: u64,
+}
+
+impl FullSignature<Secp256k1> {
+    /// Encode this signature as the 65 bytes `r || s || v` expected by Ethereum.
+    ///
+    /// Here, `v` is the recovery id plus 27, as used for messages and legacy transactions.
+    pub fn to_ethereum_bytes(&self) -> [u8; 65] {
+        let mut out = [0u8; 65];
+        let r = crate::compat::x_coordinate::<Secp256k1>(&self.big_r);
+        out[..32].copy_from_slice(&r.to_bytes());
+        out[32..64].copy_from_slice(&self.s.to_bytes());
+        out[64] = 27 + self.recovery_id();
+        out
+    }
+
+    /// The parity of the y coordinate of the first point, as used by typed transactions.
+    ///
+    /// Since EIP-2930, transactions use this directly as `v`, rather than
+    /// deriving it from the chain id, which is part of the signed payload instead.
+    pub fn y_parity(&self) -> u8 {
+        self.recovery_id() & 1
+    }
+
+    /// Split this signature into the `(r, s, v)` fields of a legacy transaction.
+    ///
+    /// With a chain id, `v` is derived from it following EIP-155, as `35 + 2 * chain_id`
+    /// plus the parity. Without one, `v` is the parity plus 27, as before EIP-155.
+    ///
+    /// Ethereum can't represent signatures whose x coordinate had to be reduced,
+    /// which only happens with negligible probability.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `v` doesn't fit in a `u64`, which needs a chain id above `2^63`.
+    pub fn to_ethereum(&self, chain_id: Option<u64>) -> EthereumSignature {
+        let bytes = self.to_ethereum_bytes();
+        let parity = u64::from(self.y_parity());
+        let v = match chain_id {
+            Some(chain_id) => chain_id
+                .checked_mul(2)
+                .and_then(|v| v.checked_add(35 + parity))
+                .expect("chain id should be small enough for v to fit in a u64"),
+            None => 27 + parity,
+        };
+        let mut r = [0u8; 32];
+        let mut s = [0u8; 32];
+        r.copy_from_slice(&bytes[..32]);
+        s.copy_from_slice(&bytes[32..64]);
+        EthereumSignature { r, s, v }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ecdsa::{RecoveryId, Signature, VerifyingKey};
+    use k256::{ProjectivePoint, PublicKey};
+
+    use super::*;
+    use crate::test::run_e2e;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_keccak256() {
+        assert_eq!(
+            hex(&keccak256(b"")),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+        assert_eq!(
+            hex(&keccak256(b"abc")),
+            "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"
+        );
+        // Padding at the block boundary.
+        assert_ne!(keccak256(&[0u8; 136]), keccak256(&[0u8; 135]));
+    }
+
+    #[test]
+    fn test_ethereum_address() {
+        let public_key = ProjectivePoint::GENERATOR.to_affine();
+        assert_eq!(
+            hex(&ethereum_address(&public_key)),
+            "7e5f4552091a69125d5dfcb7b8c2659029395bdf"
+        );
+    }
+
+    #[test]
+    fn test_eip155() {
+        let (_, sig) = run_e2e::<Secp256k1>(ethereum_message_hash(b"transaction"));
+        let bytes = sig.to_ethereum_bytes();
+        let parity = u64::from(sig.y_parity());
+
+        let legacy = sig.to_ethereum(None);
+        assert_eq!(&legacy.r[..], &bytes[..32]);
+        assert_eq!(&legacy.s[..], &bytes[32..64]);
+        assert_eq!(legacy.v, u64::from(bytes[64]));
+        assert_eq!(legacy.v, 27 + parity);
+
+        // Mainnet, and a chain id too large to fit in a byte.
+        assert_eq!(sig.to_ethereum(Some(1)).v, 37 + parity);
+        assert_eq!(sig.to_ethereum(Some(11155111)).v, 22310257 + parity);
+        assert_eq!(sig.to_ethereum(Some(1)).r, legacy.r);
+    }
+
+    #[test]
+    fn test_ecrecover() {
+        let msg = b"hello ethereum";
+        let (public_key, sig) = run_e2e::<Secp256k1>(ethereum_personal_message_hash(msg));
+
+        let bytes = sig.to_ethereum_bytes();
+        let signature = Signature::<Secp256k1>::from_slice(&bytes[..64]).unwrap();
+        let recovery_id = RecoveryId::from_byte(bytes[64] - 27).unwrap();
+        let mut data = format!("\x19Ethereum Signed Message:\n{}", msg.len()).into_bytes();
+        data.extend_from_slice(msg);
+        let recovered =
+            VerifyingKey::recover_from_prehash(&keccak256(&data), &signature, recovery_id).unwrap();
+        assert_eq!(
+            recovered,
+            VerifyingKey::from(&PublicKey::from_affine(public_key).unwrap())
+        );
+        let recovered = recovered.as_affine();
+        assert_eq!(ethereum_address(recovered), ethereum_address(&public_key));
+    }
+}
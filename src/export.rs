@@ -0,0 +1,130 @@
+//! Exporting public keys in standard formats.
+//!
+//! The threshold public key, or any child key derived from it with
+//! [`derive_public`](crate::derive_public), is an ordinary public key, which other
+//! tools expect in one of a few standard encodings. These helpers produce them:
+//!
+//! - compressed SEC1 bytes, as used by Bitcoin, or BIP32;
+//! - a DER encoded `SubjectPublicKeyInfo`, as used in X.509 certificates;
+//! - the PEM encoding of the latter, with a `PUBLIC KEY` label, as used by OpenSSL.
+//!
+//! The latter two need the curve to have an object identifier, which is the case
+//! for secp256k1 and P-256, but not for the Stark curve.
+use elliptic_curve::{
+    pkcs8::{AssociatedOid, EncodePublicKey, LineEnding},
+    point::AffineCoordinates,
+    sec1::{FromEncodedPoint, ModulusSize, ToEncodedPoint},
+    FieldBytesSize, PublicKey,
+};
+
+use crate::compat::CSCurve;
+
+/// Encode a public key as compressed SEC1 bytes.
+pub fn public_key_to_sec1<C: CSCurve>(public_key: &C::AffinePoint) -> Vec<u8> {
+    let mut out = vec![0x02 | public_key.y_is_odd().unwrap_u8()];
+    out.extend_from_slice(&public_key.x());
+    out
+}
+
+fn to_public_key<C>(public_key: &C::AffinePoint) -> Result<PublicKey<C>, elliptic_curve::Error>
+where
+    C: CSCurve + AssociatedOid,
+    C::AffinePoint: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    PublicKey::from_affine(*public_key)
+}
+
+/// Encode a public key as a DER encoded `SubjectPublicKeyInfo`.
+///
+/// This fails if the public key is the identity, which has no such encoding.
+pub fn public_key_to_der<C>(public_key: &C::AffinePoint) -> Result<Vec<u8>, elliptic_curve::Error>
+where
+    C: CSCurve + AssociatedOid,
+    C::AffinePoint: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    let document = to_public_key::<C>(public_key)?
+        .to_public_key_der()
+        .map_err(|_| elliptic_curve::Error)?;
+    Ok(document.into_vec())
+}
+
+/// Encode a public key as a PEM encoded `SubjectPublicKeyInfo`, with `\n` line endings.
+///
+/// This fails if the public key is the identity, which has no such encoding.
+pub fn public_key_to_pem<C>(public_key: &C::AffinePoint) -> Result<String, elliptic_curve::Error>
+where
+    C: CSCurve + AssociatedOid,
+    C::AffinePoint: FromEncodedPoint<C> + ToEncodedPoint<C>,
+    FieldBytesSize<C>: ModulusSize,
+{
+    to_public_key::<C>(public_key)?
+        .to_public_key_pem(LineEnding::LF)
+        .map_err(|_| elliptic_curve::Error)
+}
+
+#[cfg(test)]
+mod test {
+    use elliptic_curve::{pkcs8::DecodePublicKey, Field};
+    use k256::{AffinePoint, ProjectivePoint, Scalar, Secp256k1};
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::derive_public;
+
+    const G_X: &str = "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn test_export_generator() -> Result<(), elliptic_curve::Error> {
+        let g = AffinePoint::GENERATOR;
+        assert_eq!(
+            hex(&public_key_to_sec1::<Secp256k1>(&g)),
+            format!("02{}", G_X)
+        );
+
+        // The algorithm identifier for id-ecPublicKey with secp256k1, then the uncompressed point.
+        let der = hex(&public_key_to_der::<Secp256k1>(&g)?);
+        let prefix = "3056301006072a8648ce3d020106052b8104000a03420004";
+        assert_eq!(&der[..prefix.len()], prefix);
+        assert_eq!(&der[prefix.len()..prefix.len() + 64], G_X);
+
+        let p256 = public_key_to_der::<p256::NistP256>(&p256::AffinePoint::GENERATOR)?;
+        assert_eq!(
+            hex(&p256[..27]),
+            "3059301306072a8648ce3d020106082a8648ce3d03010703420004"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        let public_key = (ProjectivePoint::GENERATOR * Scalar::random(&mut OsRng)).to_affine();
+        let (_, child, _) = derive_public::<Secp256k1>(&public_key, &[7u8; 32], &[0, 1])?;
+
+        for key in [public_key, child] {
+            let der = public_key_to_der::<Secp256k1>(&key)?;
+            let pem = public_key_to_pem::<Secp256k1>(&key)?;
+            assert!(pem.starts_with("-----BEGIN PUBLIC KEY-----\n"));
+            assert_eq!(
+                *k256::PublicKey::from_public_key_der(&der)?.as_affine(),
+                key
+            );
+            assert_eq!(
+                *k256::PublicKey::from_public_key_pem(&pem)?.as_affine(),
+                key
+            );
+            assert_eq!(
+                k256::PublicKey::from_sec1_bytes(&public_key_to_sec1::<Secp256k1>(&key))?,
+                k256::PublicKey::from_affine(key)?
+            );
+        }
+
+        assert!(public_key_to_der::<Secp256k1>(&AffinePoint::IDENTITY).is_err());
+        Ok(())
+    }
+}
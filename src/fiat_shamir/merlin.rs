@@ -0,0 +1,137 @@
+//! A transcript following [Merlin](https://merlin.cool), using the `merlin` crate.
+//!
+//! Challenges are extracted from the transcript as specified by Merlin, producing
+//! the same bytes as other implementations, so that proofs can be checked against them.
+use rand_core::{CryptoRng, RngCore};
+
+use super::FiatShamir;
+
+/// The label of the transcripts we derive challenge streams from.
+const CHALLENGE_RNG_LABEL: &[u8] = b"cait-sith merlin challenge";
+/// The number of bytes we extract from the transcript for each challenge.
+const CHALLENGE_SEED_SIZE: usize = 32;
+/// The number of bytes a challenge stream produces at a time.
+const BLOCK_SIZE: usize = 64;
+
+/// A transcript backed by Merlin.
+#[derive(Clone)]
+pub struct MerlinTranscript(merlin::Transcript);
+
+impl FiatShamir for MerlinTranscript {
+    type Challenge = MerlinRng;
+
+    fn new(protocol: &'static [u8]) -> Self {
+        Self(merlin::Transcript::new(protocol))
+    }
+
+    fn message(&mut self, label: &'static [u8], data: &[u8]) {
+        self.0.append_message(label, data)
+    }
+
+    fn challenge(&mut self, label: &'static [u8]) -> Self::Challenge {
+        let mut seed = [0u8; CHALLENGE_SEED_SIZE];
+        self.0.challenge_bytes(label, &mut seed);
+        MerlinRng::new(&seed)
+    }
+
+    fn forked(&self, label: &'static [u8], data: &[u8]) -> Self {
+        let mut out = self.clone();
+        out.message(label, data);
+        out
+    }
+}
+
+/// The stream of bytes produced by a challenge of a [`MerlinTranscript`].
+///
+/// This seeds a fresh transcript with bytes from the original one, and then
+/// extracts output from it in blocks, so that the stream doesn't depend on how it's read.
+pub struct MerlinRng {
+    transcript: merlin::Transcript,
+    block: [u8; BLOCK_SIZE],
+    used: usize,
+}
+
+impl MerlinRng {
+    fn new(seed: &[u8; CHALLENGE_SEED_SIZE]) -> Self {
+        let mut transcript = merlin::Transcript::new(CHALLENGE_RNG_LABEL);
+        transcript.append_message(b"seed", seed);
+        Self {
+            transcript,
+            block: [0u8; BLOCK_SIZE],
+            used: BLOCK_SIZE,
+        }
+    }
+}
+
+impl RngCore for MerlinRng {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, mut dest: &mut [u8]) {
+        while !dest.is_empty() {
+            if self.used == BLOCK_SIZE {
+                self.transcript.challenge_bytes(b"block", &mut self.block);
+                self.used = 0;
+            }
+            let n = dest.len().min(BLOCK_SIZE - self.used);
+            dest[..n].copy_from_slice(&self.block[self.used..self.used + n]);
+            self.used += n;
+            dest = &mut dest[n..];
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for MerlinRng {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_merlin_vector() {
+        // From the test suite of the reference implementation.
+        let mut transcript = MerlinTranscript::new(b"test protocol");
+        transcript.message(b"some label", b"some data");
+        let mut challenge = [0u8; 32];
+        transcript.0.challenge_bytes(b"challenge", &mut challenge);
+        let expected = "d5a21972d0d5fe320c0d263fac7fffb8145aa640af6e9bca177c03c7efcf0615";
+        let hex: String = challenge.iter().map(|b| format!("{b:02x}")).collect();
+        assert_eq!(hex, expected);
+    }
+
+    #[test]
+    fn test_challenge_stream() {
+        let mut transcript = MerlinTranscript::new(b"test protocol");
+        transcript.message(b"some label", b"some data");
+        let mut forked = transcript.forked(b"fork", b"");
+
+        let mut one_go = [0u8; 100];
+        transcript.challenge(b"challenge").fill_bytes(&mut one_go);
+        let mut rng = forked.challenge(b"challenge");
+        let mut pieces = [0u8; 100];
+        rng.fill_bytes(&mut pieces[..10]);
+        rng.fill_bytes(&mut pieces[10..]);
+        // Forking separates the transcripts.
+        assert_ne!(one_go, pieces);
+
+        // And the stream doesn't depend on how it's read.
+        let mut transcript = MerlinTranscript::new(b"test protocol");
+        transcript.message(b"some label", b"some data");
+        let mut again = [0u8; 100];
+        let mut rng = transcript.challenge(b"challenge");
+        rng.fill_bytes(&mut again[..10]);
+        rng.fill_bytes(&mut again[10..70]);
+        rng.fill_bytes(&mut again[70..]);
+        assert_eq!(one_go, again);
+    }
+}
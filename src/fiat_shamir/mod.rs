@@ -0,0 +1,175 @@
+//! This module abstracts over the transcripts used for the Fiat-Shamir transform.
+//!
+//! Our proofs, and the protocols using them, only need a few operations from a
+//! transcript: absorbing labelled messages, forking, and squeezing challenges.
+//! The [`FiatShamir`] trait captures exactly these, so that the framework can
+//! be reasoned about independently of the protocols.
+//!
+//! The proofs work with any implementation of the trait. Two are provided:
+//! [`MeowTranscript`], using [Magikitten](https://github.com/cronokirby/magikitten),
+//! built on Meow, and, with the `merlin` feature, `MerlinTranscript`, following
+//! [Merlin](https://merlin.cool), built on STROBE and Keccak.
+//!
+//! Protocols use a [`Transcript`], which is backed by either of these, as chosen
+//! with a [`FiatShamirBackend`] when it's created, Meow being the default.
+#[cfg(any(feature = "merlin", test))]
+mod merlin;
+
+use rand_core::{CryptoRng, CryptoRngCore, RngCore};
+
+#[cfg(any(feature = "merlin", test))]
+pub use merlin::{MerlinRng, MerlinTranscript};
+
+/// A transcript for the Fiat-Shamir transform.
+pub trait FiatShamir: Send + Sync + 'static {
+    /// The stream of bytes produced for a challenge.
+    type Challenge: CryptoRngCore;
+
+    /// Create a new transcript, for a given protocol.
+    fn new(protocol: &'static [u8]) -> Self;
+
+    /// Absorb a labelled message into this transcript.
+    fn message(&mut self, label: &'static [u8], data: &[u8]);
+
+    /// Generate a challenge, given everything absorbed so far.
+    fn challenge(&mut self, label: &'static [u8]) -> Self::Challenge;
+
+    /// Create a fork of this transcript, separated from others with a labelled message.
+    ///
+    /// This lets us verify proofs made by different participants from the same point.
+    fn forked(&self, label: &'static [u8], data: &[u8]) -> Self;
+}
+
+/// A transcript backed by Magikitten.
+pub struct MeowTranscript(magikitten::Transcript);
+
+impl FiatShamir for MeowTranscript {
+    type Challenge = magikitten::MeowRng;
+
+    fn new(protocol: &'static [u8]) -> Self {
+        Self(magikitten::Transcript::new(protocol))
+    }
+
+    fn message(&mut self, label: &'static [u8], data: &[u8]) {
+        self.0.message(label, data)
+    }
+
+    fn challenge(&mut self, label: &'static [u8]) -> Self::Challenge {
+        self.0.challenge(label)
+    }
+
+    fn forked(&self, label: &'static [u8], data: &[u8]) -> Self {
+        Self(self.0.forked(label, data))
+    }
+}
+
+/// Which transcript backs the Fiat-Shamir transform of a protocol.
+///
+/// Every participant in a protocol needs to choose the same backend, since
+/// the challenges produced by each of them differ.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FiatShamirBackend {
+    /// Magikitten, built on Meow.
+    #[default]
+    Meow,
+    /// Merlin, built on STROBE and Keccak, with the `merlin` feature.
+    #[cfg(any(feature = "merlin", test))]
+    Merlin,
+}
+
+enum Backend {
+    Meow(MeowTranscript),
+    #[cfg(any(feature = "merlin", test))]
+    Merlin(MerlinTranscript),
+}
+
+/// The transcript used by our protocols, backed by the transcript of a [`FiatShamirBackend`].
+///
+/// Creating one with [`FiatShamir::new`] uses the default backend.
+pub struct Transcript(Backend);
+
+impl Transcript {
+    /// Create a new transcript, for a given protocol, with a given backend.
+    pub fn with_backend(backend: FiatShamirBackend, protocol: &'static [u8]) -> Self {
+        match backend {
+            FiatShamirBackend::Meow => Self(Backend::Meow(MeowTranscript::new(protocol))),
+            #[cfg(any(feature = "merlin", test))]
+            FiatShamirBackend::Merlin => Self(Backend::Merlin(MerlinTranscript::new(protocol))),
+        }
+    }
+
+    /// The backend of this transcript.
+    pub fn backend(&self) -> FiatShamirBackend {
+        match self.0 {
+            Backend::Meow(_) => FiatShamirBackend::Meow,
+            #[cfg(any(feature = "merlin", test))]
+            Backend::Merlin(_) => FiatShamirBackend::Merlin,
+        }
+    }
+}
+
+impl FiatShamir for Transcript {
+    type Challenge = Challenge;
+
+    fn new(protocol: &'static [u8]) -> Self {
+        Self::with_backend(FiatShamirBackend::default(), protocol)
+    }
+
+    fn message(&mut self, label: &'static [u8], data: &[u8]) {
+        match &mut self.0 {
+            Backend::Meow(t) => t.message(label, data),
+            #[cfg(any(feature = "merlin", test))]
+            Backend::Merlin(t) => t.message(label, data),
+        }
+    }
+
+    fn challenge(&mut self, label: &'static [u8]) -> Self::Challenge {
+        match &mut self.0 {
+            Backend::Meow(t) => Challenge(ChallengeBackend::Meow(t.challenge(label))),
+            #[cfg(any(feature = "merlin", test))]
+            Backend::Merlin(t) => Challenge(ChallengeBackend::Merlin(t.challenge(label))),
+        }
+    }
+
+    fn forked(&self, label: &'static [u8], data: &[u8]) -> Self {
+        match &self.0 {
+            Backend::Meow(t) => Self(Backend::Meow(t.forked(label, data))),
+            #[cfg(any(feature = "merlin", test))]
+            Backend::Merlin(t) => Self(Backend::Merlin(t.forked(label, data))),
+        }
+    }
+}
+
+enum ChallengeBackend {
+    Meow(magikitten::MeowRng),
+    #[cfg(any(feature = "merlin", test))]
+    Merlin(MerlinRng),
+}
+
+/// The stream of bytes produced for a challenge of a [`Transcript`].
+pub struct Challenge(ChallengeBackend);
+
+impl RngCore for Challenge {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match &mut self.0 {
+            ChallengeBackend::Meow(rng) => rng.fill_bytes(dest),
+            #[cfg(any(feature = "merlin", test))]
+            ChallengeBackend::Merlin(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for Challenge {}
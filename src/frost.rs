@@ -0,0 +1,339 @@
+//! This module converts key shares to and from the key packages of `frost-core`.
+//!
+//! FROST implementations, like the `frost-secp256k1` and `frost-secp256k1-tr` crates,
+//! share keys with Shamir's scheme, just like this library does, so the same shares
+//! can be used with both, as long as everyone agrees on the evaluation point of each share.
+//! FROST calls these points identifiers, and derives the identifier `n` from the integer `n`,
+//! so participant `i` of this library, whose share is the evaluation at `i + 1`,
+//! corresponds to the identifier `i + 1`.
+//!
+//! Key packages are exchanged in the JSON encoding of `frost-core`, in which scalars and points
+//! are lowercase hex strings, since that's the encoding both sides can read without sharing code.
+//! Identifiers which aren't derived from an integer, like those `frost-core` derives
+//! by hashing a name, have no participant here, and can't be imported.
+use std::{error, fmt};
+
+use elliptic_curve::{
+    sec1::{FromEncodedPoint, ToEncodedPoint},
+    PrimeField,
+};
+use k256::{AffinePoint, EncodedPoint, FieldBytes, ProjectivePoint, Scalar, Secp256k1};
+use serde::{Deserialize, Serialize};
+
+use crate::{protocol::Participant, KeygenOutput};
+
+/// The version of the key package encoding we read and write.
+const HEADER_VERSION: u8 = 0;
+
+/// The FROST ciphersuite a key package is for.
+///
+/// Both use the same shares, but they sign differently, and `frost-core` checks
+/// that a key package is used with the ciphersuite it was made for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrostCiphersuite {
+    /// FROST(secp256k1, SHA-256), as specified in RFC 9591.
+    Secp256k1,
+    /// The variant of FROST(secp256k1, SHA-256) producing BIP340 signatures, for Taproot.
+    ///
+    /// This is the one producing the same signatures as [`schnorr_sign`](crate::schnorr_sign).
+    Secp256k1Taproot,
+}
+
+impl FrostCiphersuite {
+    /// The identifier `frost-core` uses for this ciphersuite.
+    pub fn id(self) -> &'static str {
+        match self {
+            Self::Secp256k1 => "FROST-secp256k1-SHA256-v1",
+            Self::Secp256k1Taproot => "FROST-secp256k1-SHA256-TR-v1",
+        }
+    }
+}
+
+/// Represents an error which can happen when converting a key package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrostError {
+    /// The key package isn't well formed, for the reason given.
+    Malformed(String),
+    /// The key package is for a different ciphersuite.
+    WrongCiphersuite,
+    /// The key package uses a version of the encoding we don't know how to read.
+    UnsupportedVersion(u8),
+    /// The identifier doesn't correspond to any participant.
+    UnsupportedIdentifier,
+    /// The threshold doesn't fit in a key package.
+    UnsupportedThreshold(usize),
+    /// The signing share doesn't match the verifying share in the key package.
+    InconsistentShare,
+}
+
+impl fmt::Display for FrostError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed(e) => write!(f, "malformed key package: {}", e),
+            Self::WrongCiphersuite => write!(f, "key package is for a different ciphersuite"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported key package version {}", v),
+            Self::UnsupportedIdentifier => {
+                write!(f, "identifier doesn't correspond to a participant")
+            }
+            Self::UnsupportedThreshold(t) => write!(f, "unsupported threshold {}", t),
+            Self::InconsistentShare => write!(f, "signing share doesn't match verifying share"),
+        }
+    }
+}
+
+impl error::Error for FrostError {}
+
+fn malformed(e: impl fmt::Display) -> FrostError {
+    FrostError::Malformed(e.to_string())
+}
+
+/// The header of a key package, as encoded by `frost-core`.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Header {
+    version: u8,
+    ciphersuite: String,
+}
+
+/// A key package, as encoded by `frost-core`.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeyPackage {
+    header: Header,
+    identifier: String,
+    signing_share: String,
+    verifying_share: String,
+    verifying_key: String,
+    min_signers: u16,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Result<Vec<u8>, FrostError> {
+    let digit = |c: u8| match c {
+        b'0'..=b'9' => Ok(c - b'0'),
+        b'a'..=b'f' => Ok(c - b'a' + 10),
+        _ => Err(malformed("expected lowercase hex")),
+    };
+    let pairs = s.as_bytes().chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return Err(malformed("expected an even number of hex digits"));
+    }
+    pairs
+        .map(|pair| Ok(digit(pair[0])? << 4 | digit(pair[1])?))
+        .collect()
+}
+
+fn scalar_from_hex(s: &str) -> Result<Scalar, FrostError> {
+    let bytes: [u8; 32] = from_hex(s)?
+        .try_into()
+        .map_err(|_| malformed("scalars need 32 bytes"))?;
+    Option::from(Scalar::from_repr(FieldBytes::from(bytes)))
+        .ok_or_else(|| malformed("scalar isn't reduced"))
+}
+
+fn point_to_hex(point: &AffinePoint) -> String {
+    to_hex(point.to_encoded_point(true).as_bytes())
+}
+
+fn point_from_hex(s: &str) -> Result<AffinePoint, FrostError> {
+    let bytes = from_hex(s)?;
+    if bytes.len() != 33 {
+        return Err(malformed("points need 33 bytes"));
+    }
+    let encoded = EncodedPoint::from_bytes(&bytes).map_err(malformed)?;
+    Option::from(AffinePoint::from_encoded_point(&encoded))
+        .ok_or_else(|| malformed("point isn't on the curve"))
+}
+
+/// The share of a key, along with what FROST needs to know about it.
+#[derive(Debug, Clone)]
+pub struct FrostKeyPackage {
+    /// The participant holding the share.
+    pub participant: Participant,
+    /// The number of participants needed to sign, which FROST calls `min_signers`.
+    pub threshold: usize,
+    /// The share itself, and the public key.
+    pub output: KeygenOutput<Secp256k1>,
+}
+
+impl FrostKeyPackage {
+    /// Encode this share as a `frost-core` key package, for a given ciphersuite.
+    pub fn to_json(&self, ciphersuite: FrostCiphersuite) -> Result<String, FrostError> {
+        let min_signers = u16::try_from(self.threshold)
+            .map_err(|_| FrostError::UnsupportedThreshold(self.threshold))?;
+        let verifying_share = (ProjectivePoint::GENERATOR * self.output.private_share).to_affine();
+        let package = KeyPackage {
+            header: Header {
+                version: HEADER_VERSION,
+                ciphersuite: ciphersuite.id().to_string(),
+            },
+            identifier: to_hex(&self.participant.scalar::<Secp256k1>().to_repr()),
+            signing_share: to_hex(&self.output.private_share.to_repr()),
+            verifying_share: point_to_hex(&verifying_share),
+            verifying_key: point_to_hex(&self.output.public_key),
+            min_signers,
+        };
+        serde_json::to_string(&package).map_err(malformed)
+    }
+
+    /// Decode a share from a `frost-core` key package, for a given ciphersuite.
+    ///
+    /// This checks that the signing share matches the verifying share, but can't check
+    /// that it's consistent with the verifying key, which needs the other shares.
+    pub fn from_json(ciphersuite: FrostCiphersuite, json: &str) -> Result<Self, FrostError> {
+        let package: KeyPackage = serde_json::from_str(json).map_err(malformed)?;
+        if package.header.version != HEADER_VERSION {
+            return Err(FrostError::UnsupportedVersion(package.header.version));
+        }
+        if package.header.ciphersuite != ciphersuite.id() {
+            return Err(FrostError::WrongCiphersuite);
+        }
+
+        let identifier = scalar_from_hex(&package.identifier)?;
+        let participant = identifier_to_participant(&identifier)?;
+        let private_share = scalar_from_hex(&package.signing_share)?;
+        let verifying_share = point_from_hex(&package.verifying_share)?;
+        let public_key = point_from_hex(&package.verifying_key)?;
+        if ProjectivePoint::GENERATOR * private_share != ProjectivePoint::from(verifying_share)
+            || bool::from(private_share.is_zero())
+        {
+            return Err(FrostError::InconsistentShare);
+        }
+        if package.min_signers == 0 {
+            return Err(FrostError::UnsupportedThreshold(0));
+        }
+
+        Ok(Self {
+            participant,
+            threshold: usize::from(package.min_signers),
+            output: KeygenOutput {
+                private_share,
+                public_key,
+            },
+        })
+    }
+}
+
+/// Find the participant whose evaluation point is a given identifier, if there is one.
+fn identifier_to_participant(identifier: &Scalar) -> Result<Participant, FrostError> {
+    let bytes = identifier.to_bytes();
+    if bytes[..28].iter().any(|&b| b != 0) {
+        return Err(FrostError::UnsupportedIdentifier);
+    }
+    let n = u32::from_be_bytes(bytes[28..].try_into().unwrap());
+    let id = n.checked_sub(1).ok_or(FrostError::UnsupportedIdentifier)?;
+    Ok(Participant::from(id))
+}
+
+#[cfg(test)]
+mod test {
+    use elliptic_curve::Field;
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::{deal_key, participants::ParticipantList};
+
+    #[test]
+    fn test_frost_key_package_round_trip() -> Result<(), Box<dyn error::Error>> {
+        let participants: Vec<_> = (0..3u32).map(Participant::from).collect();
+        let (_, outputs) = deal_key::<Secp256k1>(&participants, 2, None)?;
+
+        let mut imported = Vec::new();
+        for (participant, output) in outputs {
+            let package = FrostKeyPackage {
+                participant,
+                threshold: 2,
+                output,
+            };
+            let json = package.to_json(FrostCiphersuite::Secp256k1Taproot)?;
+            let opened = FrostKeyPackage::from_json(FrostCiphersuite::Secp256k1Taproot, &json)?;
+            assert_eq!(opened.participant, participant);
+            assert_eq!(opened.threshold, 2);
+            assert_eq!(opened.output.private_share, package.output.private_share);
+            assert_eq!(opened.output.public_key, package.output.public_key);
+            imported.push(opened);
+        }
+
+        let subset = ParticipantList::new(&participants[1..]).unwrap();
+        let key = imported[1..]
+            .iter()
+            .map(|p| subset.lagrange::<Secp256k1>(p.participant) * p.output.private_share)
+            .fold(Scalar::ZERO, |acc, x| acc + x);
+        assert_eq!(
+            (ProjectivePoint::GENERATOR * key).to_affine(),
+            imported[0].output.public_key
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_frost_key_package_format() -> Result<(), FrostError> {
+        let package = FrostKeyPackage {
+            participant: Participant::from(2u32),
+            threshold: 2,
+            output: KeygenOutput {
+                private_share: Scalar::ONE,
+                public_key: AffinePoint::GENERATOR,
+            },
+        };
+        let g = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+        let expected = format!(
+            concat!(
+                r#"{{"header":{{"version":0,"ciphersuite":"FROST-secp256k1-SHA256-v1"}},"#,
+                r#""identifier":"{}03","signing_share":"{}01","#,
+                r#""verifying_share":"{}","verifying_key":"{}","min_signers":2}}"#
+            ),
+            "00".repeat(31),
+            "00".repeat(31),
+            g,
+            g
+        );
+        assert_eq!(package.to_json(FrostCiphersuite::Secp256k1)?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_frost_key_package_rejects_bad_packages() -> Result<(), FrostError> {
+        let x = Scalar::random(&mut OsRng);
+        let package = FrostKeyPackage {
+            participant: Participant::from(0u32),
+            threshold: 2,
+            output: KeygenOutput {
+                private_share: x,
+                public_key: (ProjectivePoint::GENERATOR * x).to_affine(),
+            },
+        };
+        let json = package.to_json(FrostCiphersuite::Secp256k1)?;
+
+        assert_eq!(
+            FrostKeyPackage::from_json(FrostCiphersuite::Secp256k1Taproot, &json).err(),
+            Some(FrostError::WrongCiphersuite)
+        );
+
+        let signing_share = to_hex(&x.to_repr());
+        let other_share = to_hex(&(x + Scalar::ONE).to_repr());
+        let inconsistent = json.replace(&signing_share, &other_share);
+        assert_eq!(
+            FrostKeyPackage::from_json(FrostCiphersuite::Secp256k1, &inconsistent).err(),
+            Some(FrostError::InconsistentShare)
+        );
+
+        // An identifier derived by hashing a name.
+        let hashed = to_hex(&Scalar::random(&mut OsRng).to_repr());
+        let identifier = format!("{}01", "00".repeat(31));
+        let derived = json.replace(&identifier, &hashed);
+        assert_eq!(
+            FrostKeyPackage::from_json(FrostCiphersuite::Secp256k1, &derived).err(),
+            Some(FrostError::UnsupportedIdentifier)
+        );
+
+        assert!(matches!(
+            FrostKeyPackage::from_json(FrostCiphersuite::Secp256k1, &json.replace('}', ",}")),
+            Err(FrostError::Malformed(_))
+        ));
+        Ok(())
+    }
+}
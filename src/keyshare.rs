@@ -1,38 +1,130 @@
 use elliptic_curve::{Field, Group, ScalarPrimitive};
-use magikitten::Transcript;
-use rand_core::OsRng;
+use magikitten::MeowRng;
+use rand_core::{CryptoRngCore, OsRng};
 use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
+use crate::audit::{do_attest, Dealing, KeygenAudit};
+use crate::commitments::{CommitmentScheme, HashCommitments};
 use crate::compat::CSCurve;
-use crate::crypto::{commit, hash, Digest};
+use crate::crypto::{fork_rng, hash, Digest};
+use crate::dealer::KeyCommitment;
+use crate::fiat_shamir::{FiatShamir, FiatShamirBackend, Transcript};
 use crate::math::{GroupPolynomial, Polynomial};
 use crate::participants::{ParticipantCounter, ParticipantList, ParticipantMap};
-use crate::proofs::dlog;
+use crate::proofs::{dlog, fischlin};
 use crate::protocol::internal::{make_protocol, Context, SharedChannel};
-use crate::protocol::{InitializationError, Participant, Protocol, ProtocolError};
+use crate::protocol::{
+    Fault, InitializationError, Participant, Protocol, ProtocolError, SessionId,
+};
 use crate::serde::encode;
 
 const LABEL: &[u8] = b"cait-sith v0.8.0 keygen";
 
+/// The scheme we use to commit to our polynomials.
+const COMMITMENTS: HashCommitments = HashCommitments;
+
+/// How the proofs of knowledge sent during key generation are made non-interactive.
+///
+/// Every participant needs to use the same transform.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProofTransform {
+    /// The Fiat-Shamir transform, which gives the smallest and cheapest proofs.
+    #[default]
+    FiatShamir,
+    /// The Fischlin transform, which gives straight-line extractable proofs.
+    ///
+    /// These proofs are about 16 times larger, and take a few thousand hashes to make,
+    /// but their security doesn't rely on rewinding the prover, which matters
+    /// when proving the security of many sessions run concurrently.
+    Fischlin,
+}
+
+/// A proof of knowledge of the constant term of a polynomial, with either transform.
+enum PhiProof<C: CSCurve> {
+    FiatShamir(dlog::Proof<C>),
+    Fischlin(fischlin::Proof<C>),
+}
+
+impl<C: CSCurve> PhiProof<C> {
+    fn prove(
+        transform: ProofTransform,
+        rng: &mut impl CryptoRngCore,
+        transcript: &mut Transcript,
+        statement: dlog::Statement<'_, C>,
+        witness: dlog::Witness<'_, C>,
+    ) -> Self {
+        match transform {
+            ProofTransform::FiatShamir => {
+                Self::FiatShamir(dlog::prove(rng, transcript, statement, witness))
+            }
+            ProofTransform::Fischlin => {
+                Self::Fischlin(fischlin::prove(rng, transcript, statement, witness))
+            }
+        }
+    }
+
+    fn verify(&self, transcript: &mut Transcript, statement: dlog::Statement<'_, C>) -> bool {
+        match self {
+            Self::FiatShamir(proof) => dlog::verify(transcript, statement, proof),
+            Self::Fischlin(proof) => fischlin::verify(transcript, statement, proof),
+        }
+    }
+
+    /// The underlying proof, which audits store, since they only support Fiat-Shamir.
+    fn into_fiat_shamir(self) -> dlog::Proof<C> {
+        match self {
+            Self::FiatShamir(proof) => proof,
+            Self::Fischlin(_) => unreachable!("audited key generation uses Fiat-Shamir"),
+        }
+    }
+}
+
+/// Create the transcript for sharing a key, bound to the session, if auditing.
+pub(crate) fn keyshare_transcript<C: CSCurve>(
+    participants: &ParticipantList,
+    threshold: usize,
+    session: Option<&[u8]>,
+    backend: FiatShamirBackend,
+) -> Transcript {
+    let mut transcript = Transcript::with_backend(backend, LABEL);
+    transcript.message(b"group", C::NAME);
+    transcript.message(b"participants", &encode(participants));
+    // To allow interop between platforms where usize is different!
+    transcript.message(
+        b"threshold",
+        &u64::try_from(threshold).unwrap().to_be_bytes(),
+    );
+    if let Some(session) = session {
+        transcript.message(b"session", session);
+    }
+    transcript
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 async fn do_keyshare<C: CSCurve>(
+    mut rng: MeowRng,
     mut chan: SharedChannel,
     participants: ParticipantList,
     me: Participant,
     threshold: usize,
     s_i: C::Scalar,
     big_s: Option<C::ProjectivePoint>,
-) -> Result<(C::Scalar, C::AffinePoint), ProtocolError> {
-    let mut rng = OsRng;
-    let mut transcript = Transcript::new(LABEL);
-
+    session: Option<SessionId>,
+    transform: ProofTransform,
+    backend: FiatShamirBackend,
+) -> Result<(C::Scalar, C::AffinePoint, Option<KeygenAudit<C>>), ProtocolError> {
     // Spec 1.2
-    transcript.message(b"group", C::NAME);
-    transcript.message(b"participants", &encode(&participants));
-    // To allow interop between platforms where usize is different!
-    transcript.message(
-        b"threshold",
-        &u64::try_from(threshold).unwrap().to_be_bytes(),
+    let mut transcript = keyshare_transcript::<C>(
+        &participants,
+        threshold,
+        session.as_ref().map(|s| &s.as_bytes()[..]),
+        backend,
     );
+    // Leaving the default transform out keeps transcripts, and audits, as they were.
+    if transform == ProofTransform::Fischlin {
+        transcript.message(b"transform", b"fischlin");
+    }
 
     // Spec 1.3
     let f: Polynomial<C> = Polynomial::extend_random(&mut rng, threshold, &s_i);
@@ -41,7 +133,7 @@ async fn do_keyshare<C: CSCurve>(
     let mut big_f = f.commit();
 
     // Spec 1.5
-    let (my_commitment, my_randomizer) = commit(&mut rng, &big_f);
+    let (my_commitment, my_randomizer) = COMMITMENTS.commit(&mut rng, &big_f);
 
     // Spec 1.6
     let wait0 = chan.next_waitpoint();
@@ -72,7 +164,8 @@ async fn do_keyshare<C: CSCurve>(
     let witness = dlog::Witness::<C> {
         x: &f.evaluate_zero(),
     };
-    let my_phi_proof = dlog::prove(
+    let my_phi_proof = PhiProof::prove(
+        transform,
         &mut rng,
         &mut transcript.forked(b"dlog0", &me.bytes()),
         statement,
@@ -81,8 +174,28 @@ async fn do_keyshare<C: CSCurve>(
 
     // Spec 2.6
     let wait2 = chan.next_waitpoint();
-    chan.send_many(wait2, &(&big_f, &my_randomizer, my_phi_proof))
-        .await;
+    match &my_phi_proof {
+        PhiProof::FiatShamir(proof) => {
+            chan.send_many(wait2, &(&big_f, &my_randomizer, proof))
+                .await
+        }
+        PhiProof::Fischlin(proof) => {
+            chan.send_many(wait2, &(&big_f, &my_randomizer, proof))
+                .await
+        }
+    }
+    let mut dealings = ParticipantMap::new(&participants);
+    if session.is_some() {
+        dealings.put(
+            me,
+            Dealing {
+                commitment: my_commitment,
+                polynomial: big_f.clone(),
+                randomizer: my_randomizer,
+                proof: my_phi_proof.into_fiat_shamir(),
+            },
+        );
+    }
 
     // Spec 2.7
     let wait3 = chan.next_waitpoint();
@@ -101,50 +214,76 @@ async fn do_keyshare<C: CSCurve>(
             continue;
         }
         if confirmation != my_confirmation {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "confirmation from {from:?} did not match expectation"
-            )));
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait1,
+                fault: Fault::InconsistentBroadcast,
+            });
         }
     }
 
     // Spec 3.3 + 3.4, and also part of 3.6, for summing up the Fs.
+    let mut all_big_f = ParticipantMap::new(&participants);
     seen.clear();
     seen.put(me);
     while !seen.full() {
-        let (from, (their_big_f, their_randomizer, their_phi_proof)): (
-            _,
-            (GroupPolynomial<C>, _, _),
-        ) = chan.recv(wait2).await?;
+        let (from, (their_big_f, their_randomizer, their_phi_proof)) = match transform {
+            ProofTransform::FiatShamir => {
+                let (from, (big_f, randomizer, proof)): (_, (GroupPolynomial<C>, _, _)) =
+                    chan.recv(wait2).await?;
+                (from, (big_f, randomizer, PhiProof::FiatShamir(proof)))
+            }
+            ProofTransform::Fischlin => {
+                let (from, (big_f, randomizer, proof)): (_, (GroupPolynomial<C>, _, _)) =
+                    chan.recv(wait2).await?;
+                (from, (big_f, randomizer, PhiProof::Fischlin(proof)))
+            }
+        };
         if !seen.put(from) {
             continue;
         }
 
         if their_big_f.len() != threshold {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "polynomial from {from:?} has the wrong length"
-            )));
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait2,
+                fault: Fault::InvalidMessage("polynomial has the wrong length"),
+            });
         }
-        if !all_commitments[from].check(&their_big_f, &their_randomizer) {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "commitment from {from:?} did not match revealed F"
-            )));
+        if !COMMITMENTS.check(&all_commitments[from], &their_big_f, &their_randomizer) {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait2,
+                fault: Fault::BadCommitment,
+            });
         }
         let statement = dlog::Statement::<C> {
             public: &their_big_f.evaluate_zero(),
         };
-        if !dlog::verify(
-            &mut transcript.forked(b"dlog0", &from.bytes()),
-            statement,
-            &their_phi_proof,
-        ) {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "dlog proof from {from:?} failed to verify"
-            )));
+        if !their_phi_proof.verify(&mut transcript.forked(b"dlog0", &from.bytes()), statement) {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait2,
+                fault: Fault::BadProof,
+            });
         }
         big_f += &their_big_f;
+        if session.is_some() {
+            dealings.put(
+                from,
+                Dealing {
+                    commitment: all_commitments[from],
+                    polynomial: their_big_f.clone(),
+                    randomizer: their_randomizer,
+                    proof: their_phi_proof.into_fiat_shamir(),
+                },
+            );
+        }
+        all_big_f.put(from, their_big_f);
     }
 
     // Spec 3.5 + 3.6
+    let mut all_x_j_i = ParticipantMap::new(&participants);
     seen.clear();
     seen.put(me);
     while !seen.full() {
@@ -152,29 +291,70 @@ async fn do_keyshare<C: CSCurve>(
         if !seen.put(from) {
             continue;
         }
-        x_i += C::Scalar::from(x_j_i);
+        let x_j_i = C::Scalar::from(x_j_i);
+        x_i += x_j_i;
+        all_x_j_i.put(from, x_j_i);
     }
 
     // Spec 3.7
     if big_f.evaluate(&me.scalar::<C>()) != C::ProjectivePoint::generator() * x_i {
-        return Err(ProtocolError::AssertionFailed(
-            "received bad private share".to_string(),
-        ));
+        // Checking each share individually is more expensive, so we only do it
+        // to find out who's responsible once we know something is wrong.
+        for p in participants.others(me) {
+            if all_big_f[p].evaluate(&me.scalar::<C>())
+                != C::ProjectivePoint::generator() * all_x_j_i[p]
+            {
+                return Err(ProtocolError::Malicious {
+                    participant: p,
+                    round: wait3,
+                    fault: Fault::BadShare,
+                });
+            }
+        }
+        return Err(ProtocolError::Failed {
+            round: wait3,
+            fault: Fault::BadShare,
+        });
     }
 
     // Spec 3.8
     let big_x = big_f.evaluate_zero();
     match big_s {
         Some(big_s) if big_s != big_x => {
-            return Err(ProtocolError::AssertionFailed(
-                "new public key does not match old public key".to_string(),
-            ))
+            return Err(ProtocolError::Failed {
+                round: wait2,
+                fault: Fault::InvalidResult("new public key does not match old public key"),
+            })
         }
         _ => {}
     };
 
+    let audit = match session {
+        Some(session) => {
+            let dealings = Vec::<Participant>::from(participants.clone())
+                .into_iter()
+                .map(|p| dealings[p].clone())
+                .collect();
+            let audit = do_attest(
+                &mut rng,
+                &mut chan,
+                &mut transcript,
+                &participants,
+                me,
+                threshold,
+                session,
+                dealings,
+                &big_f,
+                &x_i,
+            )
+            .await?;
+            Some(audit)
+        }
+        None => None,
+    };
+
     // Spec 3.9
-    Ok((x_i, big_x.into()))
+    Ok((x_i, big_x.into(), audit))
 }
 
 /// Represents the output of the key generation protocol.
@@ -186,34 +366,74 @@ pub struct KeygenOutput<C: CSCurve> {
     pub public_key: C::AffinePoint,
 }
 
-async fn do_keygen<C: CSCurve>(
+impl<C: CSCurve> Zeroize for KeygenOutput<C> {
+    fn zeroize(&mut self) {
+        self.private_share.zeroize();
+    }
+}
+
+impl<C: CSCurve> Drop for KeygenOutput<C> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<C: CSCurve> ZeroizeOnDrop for KeygenOutput<C> {}
+
+impl<C: CSCurve> KeygenOutput<C> {
+    /// Check that this is a valid share, for the participant `me`, of a given public key.
+    ///
+    /// The share is checked against a commitment to the shares, as produced by [deal_key()],
+    /// which should be stored apart from the share itself. This is meant to detect
+    /// a corrupted share at startup, rather than through failing signatures later on.
+    ///
+    /// [deal_key()]: crate::deal_key
+    pub fn verify(
+        &self,
+        public_key: &C::AffinePoint,
+        me: Participant,
+        commitment: &KeyCommitment<C>,
+    ) -> bool {
+        self.public_key == *public_key
+            && commitment.public_key() == *public_key
+            && commitment.verify_share(me, &self.private_share)
+    }
+}
+
+pub(crate) async fn do_keygen<C: CSCurve>(
+    mut rng: MeowRng,
     chan: SharedChannel,
     participants: ParticipantList,
     me: Participant,
     threshold: usize,
+    transform: ProofTransform,
+    backend: FiatShamirBackend,
 ) -> Result<KeygenOutput<C>, ProtocolError> {
-    let s_i = C::Scalar::random(&mut OsRng);
-    let (private_share, public_key) =
-        do_keyshare::<C>(chan, participants, me, threshold, s_i, None).await?;
+    let s_i = C::Scalar::random(&mut rng);
+    let (private_share, public_key, _) = do_keyshare::<C>(
+        rng,
+        chan,
+        participants,
+        me,
+        threshold,
+        s_i,
+        None,
+        None,
+        transform,
+        backend,
+    )
+    .await?;
     Ok(KeygenOutput {
         private_share,
         public_key,
     })
 }
 
-/// The key generation protocol, with a given threshold.
-///
-/// This produces a new key pair, such that any set of participants
-/// of size `>= threshold` can reconstruct the private key,
-/// but no smaller set can do the same.
-///
-/// This needs to be run once, before then being able to perform threshold
-/// signatures using the key.
-pub fn keygen<C: CSCurve>(
+fn check_keygen_parameters(
     participants: &[Participant],
     me: Participant,
     threshold: usize,
-) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+) -> Result<ParticipantList, InitializationError> {
     if participants.len() < 2 {
         return Err(InitializationError::BadParameters(format!(
             "participant count cannot be < 2, found: {}",
@@ -237,157 +457,1091 @@ pub fn keygen<C: CSCurve>(
         ));
     }
 
-    let ctx = Context::new();
-    let fut = do_keygen(ctx.shared_channel(), participants, me, threshold);
-    Ok(make_protocol(ctx, fut))
+    Ok(participants)
 }
 
-async fn do_reshare<C: CSCurve>(
-    chan: SharedChannel,
-    participants: ParticipantList,
-    old_subset: ParticipantList,
+/// The key generation protocol, with a given threshold.
+///
+/// This produces a new key pair, such that any set of participants
+/// of size `>= threshold` can reconstruct the private key,
+/// but no smaller set can do the same.
+///
+/// This needs to be run once, before then being able to perform threshold
+/// signatures using the key.
+pub fn keygen<C: CSCurve>(
+    participants: &[Participant],
     me: Participant,
     threshold: usize,
-    my_share: Option<C::Scalar>,
-    public_key: C::AffinePoint,
-) -> Result<C::Scalar, ProtocolError> {
-    let s_i = my_share
-        .map(|x_i| old_subset.lagrange::<C>(me) * x_i)
-        .unwrap_or(C::Scalar::ZERO);
-    let big_s: C::ProjectivePoint = public_key.into();
-    let (private_share, _) =
-        do_keyshare::<C>(chan, participants, me, threshold, s_i, Some(big_s)).await?;
-    Ok(private_share)
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    keygen_with_rng(participants, me, threshold, &mut OsRng)
 }
 
-/// The resharing protocol.
+/// Like [keygen()], except drawing all of the randomness from a given rng.
 ///
-/// The purpose of this protocol is to take a key generated with one set of participants,
-/// and transfer it to another set of participants, potentially with a new threshold.
-///
-/// Not all participants must be present in the new set, but enough need to be present
-/// so that the old key can be reconstructed.
-///
-/// This protocol creates fresh shares for every party, without revealing the key,
-/// of course. The output of the protocol is the new share for this party.
-pub fn reshare<C: CSCurve>(
-    old_participants: &[Participant],
-    old_threshold: usize,
-    new_participants: &[Participant],
-    new_threshold: usize,
+/// Given the same rng state and the same messages, the protocol will behave
+/// the exact same way, which is useful to resume it after a restart.
+pub fn keygen_with_rng<C: CSCurve>(
+    participants: &[Participant],
     me: Participant,
-    my_share: Option<C::Scalar>,
-    public_key: C::AffinePoint,
-) -> Result<impl Protocol<Output = C::Scalar>, InitializationError> {
-    if new_participants.len() < 2 {
-        return Err(InitializationError::BadParameters(format!(
-            "participant count cannot be < 2, found: {}",
-            new_participants.len()
-        )));
-    };
-    // Spec 1.1
-    if new_threshold > new_participants.len() {
-        return Err(InitializationError::BadParameters(
-            "threshold must be <= participant count".to_string(),
-        ));
-    }
-
-    let new_participants = ParticipantList::new(new_participants).ok_or_else(|| {
-        InitializationError::BadParameters(
-            "new participant list cannot contain duplicates".to_string(),
-        )
-    })?;
-
-    if !new_participants.contains(me) {
-        return Err(InitializationError::BadParameters(
-            "new participant list must contain this participant".to_string(),
-        ));
-    }
+    threshold: usize,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    keygen_with_transform_with_rng(participants, me, threshold, ProofTransform::default(), rng)
+}
 
-    let old_participants = ParticipantList::new(old_participants).ok_or_else(|| {
-        InitializationError::BadParameters(
-            "old participant list cannot contain duplicates".to_string(),
-        )
-    })?;
+/// Like [keygen()], except choosing how the proofs sent during the protocol are made.
+///
+/// Using [`ProofTransform::Fischlin`] makes these proofs straight-line extractable,
+/// at the cost of larger messages, and more work for each participant.
+pub fn keygen_with_transform<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    transform: ProofTransform,
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    keygen_with_transform_with_rng(participants, me, threshold, transform, &mut OsRng)
+}
 
-    let old_subset = old_participants.intersection(&new_participants);
-    if old_subset.len() < old_threshold {
-        return Err(InitializationError::BadParameters(
-            "not enough old participants to reconstruct private key for resharing".to_string(),
-        ));
-    }
+/// Like [keygen_with_transform()], except drawing all of the randomness from a given rng.
+pub fn keygen_with_transform_with_rng<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    transform: ProofTransform,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    keygen_with_options(
+        participants,
+        me,
+        threshold,
+        transform,
+        FiatShamirBackend::default(),
+        rng,
+    )
+}
 
-    if old_subset.contains(me) && my_share.is_none() {
-        return Err(InitializationError::BadParameters(
-            "this party is present in the old participant list but provided no share".to_string(),
-        ));
-    }
+/// Set up key generation, with every choice it allows, as done by [`KeygenConfig`](crate::KeygenConfig).
+pub(crate) fn keygen_with_options<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    transform: ProofTransform,
+    backend: FiatShamirBackend,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    let participants = check_keygen_parameters(participants, me, threshold)?;
 
     let ctx = Context::new();
-    let fut = do_reshare::<C>(
+    let fut = do_keygen(
+        fork_rng(rng),
         ctx.shared_channel(),
-        new_participants,
-        old_subset,
+        participants,
         me,
-        new_threshold,
-        my_share,
-        public_key,
+        threshold,
+        transform,
+        backend,
     );
     Ok(make_protocol(ctx, fut))
 }
 
-/// The refresh protocol.
-///
-/// This is like resharing, but with extra constraints to ensure that the set
-/// of participants and threshold do not change.
-pub fn refresh<C: CSCurve>(
-    participants: &[Participant],
-    threshold: usize,
+async fn do_keygen_audited<C: CSCurve>(
+    mut rng: MeowRng,
+    chan: SharedChannel,
+    participants: ParticipantList,
     me: Participant,
-    my_share: C::Scalar,
-    public_key: C::AffinePoint,
-) -> Result<impl Protocol<Output = C::Scalar>, InitializationError> {
-    reshare::<C>(
-        participants,
-        threshold,
+    threshold: usize,
+    session: SessionId,
+) -> Result<(KeygenOutput<C>, KeygenAudit<C>), ProtocolError> {
+    let s_i = C::Scalar::random(&mut rng);
+    let (private_share, public_key, audit) = do_keyshare::<C>(
+        rng,
+        chan,
         participants,
-        threshold,
         me,
-        Some(my_share),
-        public_key,
+        threshold,
+        s_i,
+        None,
+        Some(session),
+        ProofTransform::FiatShamir,
+        FiatShamirBackend::default(),
     )
+    .await?;
+    let output = KeygenOutput {
+        private_share,
+        public_key,
+    };
+    // Unwrapping is fine, since we passed in a session.
+    Ok((output, audit.unwrap()))
 }
 
-#[cfg(test)]
-mod test {
-    use std::error::Error;
+/// The key generation protocol, also producing a public record of the ceremony.
+///
+/// This is like [keygen()], with an extra round, in which each participant attests
+/// to having received a valid share. The resulting [`KeygenAudit`] contains no secrets,
+/// and can be checked by anyone with [`verify_keygen_audit()`](crate::verify_keygen_audit).
+///
+/// Every participant needs to use the same session id, which should be unique
+/// to this ceremony, as derived by [`SessionId::derive`].
+pub fn keygen_audited<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    session: SessionId,
+) -> Result<impl Protocol<Output = (KeygenOutput<C>, KeygenAudit<C>)>, InitializationError> {
+    keygen_audited_with_rng(participants, me, threshold, session, &mut OsRng)
+}
 
-    use k256::{ProjectivePoint, Scalar, Secp256k1};
+/// Like [keygen_audited()], except drawing all of the randomness from a given rng.
+pub fn keygen_audited_with_rng<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    session: SessionId,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = (KeygenOutput<C>, KeygenAudit<C>)>, InitializationError> {
+    let participants = check_keygen_parameters(participants, me, threshold)?;
 
-    use super::*;
-    use crate::protocol::{run_protocol, Participant};
+    let ctx = Context::new();
+    let fut = do_keygen_audited(
+        fork_rng(rng),
+        ctx.shared_channel(),
+        participants,
+        me,
+        threshold,
+        session,
+    );
+    Ok(make_protocol(ctx, fut))
+}
 
-    #[allow(clippy::type_complexity)]
-    fn do_keygen(
-        participants: &[Participant],
-        threshold: usize,
-    ) -> Result<Vec<(Participant, KeygenOutput<Secp256k1>)>, Box<dyn Error>> {
-        let mut protocols: Vec<(
-            Participant,
-            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
-        )> = Vec::with_capacity(participants.len());
+async fn do_keygen_many<C: CSCurve>(
+    mut rng: MeowRng,
+    mut chan: SharedChannel,
+    participants: ParticipantList,
+    me: Participant,
+    threshold: usize,
+    count: usize,
+) -> Result<Vec<KeygenOutput<C>>, ProtocolError> {
+    let mut transcript = Transcript::new(LABEL);
 
-        for p in participants.iter() {
-            let protocol = keygen(participants, *p, threshold)?;
-            protocols.push((*p, Box::new(protocol)));
-        }
+    transcript.message(b"group", C::NAME);
+    transcript.message(b"participants", &encode(&participants));
+    transcript.message(
+        b"threshold",
+        &u64::try_from(threshold).unwrap().to_be_bytes(),
+    );
+    transcript.message(b"count", &u64::try_from(count).unwrap().to_be_bytes());
 
-        let result = run_protocol(protocols)?;
-        Ok(result)
-    }
+    // This follows the same steps as a single key generation, except that each
+    // message contains the values for every key, under a single commitment.
+    let f_v: Vec<Polynomial<C>> = (0..count)
+        .map(|_| Polynomial::random(&mut rng, threshold))
+        .collect();
+    let mut big_f_v: Vec<GroupPolynomial<C>> = f_v.iter().map(|f| f.commit()).collect();
+    let (my_commitment, my_randomizer) = COMMITMENTS.commit(&mut rng, &big_f_v);
 
-    #[test]
-    fn test_keygen() -> Result<(), Box<dyn Error>> {
+    let wait0 = chan.next_waitpoint();
+    chan.send_many(wait0, &my_commitment).await;
+
+    let mut all_commitments = ParticipantMap::new(&participants);
+    all_commitments.put(me, my_commitment);
+    while !all_commitments.full() {
+        let (from, commitment) = chan.recv(wait0).await?;
+        all_commitments.put(from, commitment);
+    }
+
+    let my_confirmation = hash(&all_commitments);
+    transcript.message(b"confirmation", my_confirmation.as_ref());
+
+    let wait1 = chan.next_waitpoint();
+    chan.send_many(wait1, &my_confirmation).await;
+
+    let my_phi_proofs: Vec<_> = f_v
+        .iter()
+        .zip(&big_f_v)
+        .map(|(f, big_f)| {
+            dlog::prove(
+                &mut rng,
+                &mut transcript.forked(b"dlog0", &me.bytes()),
+                dlog::Statement::<C> {
+                    public: &big_f.evaluate_zero(),
+                },
+                dlog::Witness::<C> {
+                    x: &f.evaluate_zero(),
+                },
+            )
+        })
+        .collect();
+
+    let wait2 = chan.next_waitpoint();
+    chan.send_many(wait2, &(&big_f_v, &my_randomizer, my_phi_proofs))
+        .await;
+
+    let wait3 = chan.next_waitpoint();
+    for p in participants.others(me) {
+        let x_i_j_v: Vec<ScalarPrimitive<C>> = f_v
+            .iter()
+            .map(|f| f.evaluate(&p.scalar::<C>()).into())
+            .collect();
+        chan.send_private(wait3, p, &x_i_j_v).await;
+    }
+    let mut x_i_v: Vec<C::Scalar> = f_v.iter().map(|f| f.evaluate(&me.scalar::<C>())).collect();
+
+    let mut seen = ParticipantCounter::new(&participants);
+    seen.put(me);
+    while !seen.full() {
+        let (from, confirmation): (_, Digest) = chan.recv(wait1).await?;
+        if !seen.put(from) {
+            continue;
+        }
+        if confirmation != my_confirmation {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait1,
+                fault: Fault::InconsistentBroadcast,
+            });
+        }
+    }
+
+    let mut all_big_f_v = ParticipantMap::new(&participants);
+    seen.clear();
+    seen.put(me);
+    while !seen.full() {
+        let (from, (their_big_f_v, their_randomizer, their_phi_proofs)): (
+            _,
+            (Vec<GroupPolynomial<C>>, _, Vec<_>),
+        ) = chan.recv(wait2).await?;
+        if !seen.put(from) {
+            continue;
+        }
+
+        if their_big_f_v.len() != count || their_phi_proofs.len() != count {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait2,
+                fault: Fault::InvalidMessage("wrong number of keys"),
+            });
+        }
+        if their_big_f_v.iter().any(|big_f| big_f.len() != threshold) {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait2,
+                fault: Fault::InvalidMessage("polynomial has the wrong length"),
+            });
+        }
+        if !COMMITMENTS.check(&all_commitments[from], &their_big_f_v, &their_randomizer) {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait2,
+                fault: Fault::BadCommitment,
+            });
+        }
+        let their_publics: Vec<_> = their_big_f_v.iter().map(|f| f.evaluate_zero()).collect();
+        let proofs = their_publics
+            .iter()
+            .zip(&their_phi_proofs)
+            .map(|(public, proof)| {
+                (
+                    transcript.forked(b"dlog0", &from.bytes()),
+                    dlog::Statement::<C> { public },
+                    proof,
+                )
+            });
+        if !dlog::verify_batch(&mut rng, proofs) {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait2,
+                fault: Fault::BadProof,
+            });
+        }
+        for (big_f, their_big_f) in big_f_v.iter_mut().zip(&their_big_f_v) {
+            *big_f += their_big_f;
+        }
+        all_big_f_v.put(from, their_big_f_v);
+    }
+
+    let mut all_x_j_i_v = ParticipantMap::new(&participants);
+    seen.clear();
+    seen.put(me);
+    while !seen.full() {
+        let (from, x_j_i_v): (_, Vec<ScalarPrimitive<C>>) = chan.recv(wait3).await?;
+        if !seen.put(from) {
+            continue;
+        }
+        if x_j_i_v.len() != count {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait3,
+                fault: Fault::InvalidMessage("wrong number of shares"),
+            });
+        }
+        let x_j_i_v: Vec<C::Scalar> = x_j_i_v.into_iter().map(C::Scalar::from).collect();
+        for (x_i, x_j_i) in x_i_v.iter_mut().zip(&x_j_i_v) {
+            *x_i += x_j_i;
+        }
+        all_x_j_i_v.put(from, x_j_i_v);
+    }
+
+    let mut out = Vec::with_capacity(count);
+    for (k, (big_f, x_i)) in big_f_v.iter().zip(x_i_v).enumerate() {
+        if big_f.evaluate(&me.scalar::<C>()) != C::ProjectivePoint::generator() * x_i {
+            for p in participants.others(me) {
+                let x_j_i: &Vec<C::Scalar> = &all_x_j_i_v[p];
+                if all_big_f_v[p][k].evaluate(&me.scalar::<C>())
+                    != C::ProjectivePoint::generator() * x_j_i[k]
+                {
+                    return Err(ProtocolError::Malicious {
+                        participant: p,
+                        round: wait3,
+                        fault: Fault::BadShare,
+                    });
+                }
+            }
+            return Err(ProtocolError::Failed {
+                round: wait3,
+                fault: Fault::BadShare,
+            });
+        }
+        out.push(KeygenOutput {
+            private_share: x_i,
+            public_key: big_f.evaluate_zero().into(),
+        });
+    }
+
+    Ok(out)
+}
+
+/// The key generation protocol, producing `count` independent keys at once.
+///
+/// This is equivalent to running [keygen()] `count` times, but the keys share
+/// the same rounds of communication, with one message per round carrying the
+/// values for every key, which is much cheaper when provisioning many keys.
+pub fn keygen_many<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    count: usize,
+) -> Result<impl Protocol<Output = Vec<KeygenOutput<C>>>, InitializationError> {
+    keygen_many_with_rng(participants, me, threshold, count, &mut OsRng)
+}
+
+/// Like [keygen_many()], except drawing all of the randomness from a given rng.
+pub fn keygen_many_with_rng<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    count: usize,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = Vec<KeygenOutput<C>>>, InitializationError> {
+    if count == 0 {
+        return Err(InitializationError::BadParameters(
+            "key count cannot be 0".to_string(),
+        ));
+    }
+    let participants = check_keygen_parameters(participants, me, threshold)?;
+
+    let ctx = Context::new();
+    let fut = do_keygen_many(
+        fork_rng(rng),
+        ctx.shared_channel(),
+        participants,
+        me,
+        threshold,
+        count,
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
+async fn do_import<C: CSCurve>(
+    rng: MeowRng,
+    chan: SharedChannel,
+    participants: ParticipantList,
+    me: Participant,
+    threshold: usize,
+    s_i: C::Scalar,
+    public_key: C::AffinePoint,
+) -> Result<KeygenOutput<C>, ProtocolError> {
+    let (private_share, public_key, _) = do_keyshare::<C>(
+        rng,
+        chan,
+        participants,
+        me,
+        threshold,
+        s_i,
+        Some(public_key.into()),
+        None,
+        ProofTransform::FiatShamir,
+        FiatShamirBackend::default(),
+    )
+    .await?;
+    Ok(KeygenOutput {
+        private_share,
+        public_key,
+    })
+}
+
+fn import_inner<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    s_i: C::Scalar,
+    public_key: C::AffinePoint,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    let participants = check_keygen_parameters(participants, me, threshold)?;
+
+    let ctx = Context::new();
+    let fut = do_import(
+        fork_rng(rng),
+        ctx.shared_channel(),
+        participants,
+        me,
+        threshold,
+        s_i,
+        public_key,
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
+/// Import an existing private key, held by a single participant.
+///
+/// This splits the key into threshold shares, in the same format as key generation,
+/// without revealing it to any participant other than the importer.
+///
+/// Exactly one participant, the importer, should pass in the private key,
+/// with everyone else passing `None`. Everyone needs to know the public key,
+/// which is used to check that the right key was split.
+pub fn import_key<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    private_key: Option<C::Scalar>,
+    public_key: C::AffinePoint,
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    import_key_with_rng(
+        participants,
+        me,
+        threshold,
+        private_key,
+        public_key,
+        &mut OsRng,
+    )
+}
+
+/// Like [import_key()], except drawing all of the randomness from a given rng.
+pub fn import_key_with_rng<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    private_key: Option<C::Scalar>,
+    public_key: C::AffinePoint,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    if let Some(x) = private_key {
+        if (C::ProjectivePoint::generator() * x).into() != public_key {
+            return Err(InitializationError::BadParameters(
+                "private key does not match public key".to_string(),
+            ));
+        }
+    }
+    let s_i = private_key.unwrap_or(C::Scalar::ZERO);
+    import_inner(participants, me, threshold, s_i, public_key, rng)
+}
+
+/// Import an existing private key, held as additive shares by the participants.
+///
+/// This is like [import_key()], except that no single participant knows the key:
+/// instead, the key is the sum of the `additive_share` of each participant.
+/// At no point does any participant learn the key.
+pub fn import_additive_key<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    additive_share: C::Scalar,
+    public_key: C::AffinePoint,
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    import_additive_key_with_rng(
+        participants,
+        me,
+        threshold,
+        additive_share,
+        public_key,
+        &mut OsRng,
+    )
+}
+
+/// Like [import_additive_key()], except drawing all of the randomness from a given rng.
+pub fn import_additive_key_with_rng<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    additive_share: C::Scalar,
+    public_key: C::AffinePoint,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    import_inner(participants, me, threshold, additive_share, public_key, rng)
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn do_reshare<C: CSCurve>(
+    rng: MeowRng,
+    chan: SharedChannel,
+    participants: ParticipantList,
+    old_subset: ParticipantList,
+    me: Participant,
+    threshold: usize,
+    my_share: Option<C::Scalar>,
+    public_key: C::AffinePoint,
+) -> Result<KeygenOutput<C>, ProtocolError> {
+    let s_i = my_share
+        .map(|x_i| old_subset.lagrange::<C>(me) * x_i)
+        .unwrap_or(C::Scalar::ZERO);
+    let big_s: C::ProjectivePoint = public_key.into();
+    let (private_share, public_key, _) = do_keyshare::<C>(
+        rng,
+        chan,
+        participants,
+        me,
+        threshold,
+        s_i,
+        Some(big_s),
+        None,
+        ProofTransform::FiatShamir,
+        FiatShamirBackend::default(),
+    )
+    .await?;
+    Ok(KeygenOutput {
+        private_share,
+        public_key,
+    })
+}
+
+/// The resharing protocol.
+///
+/// The purpose of this protocol is to take a key generated with one set of participants,
+/// and transfer it to another set of participants, potentially with a new threshold.
+///
+/// Not all participants must be present in the new set, but enough need to be present
+/// so that the old key can be reconstructed.
+///
+/// Old participants pass in the output they got from key generation, or from
+/// a previous resharing, while new participants only need to know the public key.
+///
+/// This protocol creates fresh shares for every party, without revealing the key,
+/// of course. The output of the protocol is the new share for this party, along
+/// with the public key, in the same format as key generation.
+pub fn reshare<C: CSCurve>(
+    old_participants: &[Participant],
+    old_threshold: usize,
+    new_participants: &[Participant],
+    new_threshold: usize,
+    me: Participant,
+    keygen_out: Option<KeygenOutput<C>>,
+    public_key: C::AffinePoint,
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    reshare_with_rng(
+        old_participants,
+        old_threshold,
+        new_participants,
+        new_threshold,
+        me,
+        keygen_out,
+        public_key,
+        &mut OsRng,
+    )
+}
+
+/// Like [reshare()], except drawing all of the randomness from a given rng.
+#[allow(clippy::too_many_arguments)]
+pub fn reshare_with_rng<C: CSCurve>(
+    old_participants: &[Participant],
+    old_threshold: usize,
+    new_participants: &[Participant],
+    new_threshold: usize,
+    me: Participant,
+    keygen_out: Option<KeygenOutput<C>>,
+    public_key: C::AffinePoint,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    if new_participants.len() < 2 {
+        return Err(InitializationError::BadParameters(format!(
+            "participant count cannot be < 2, found: {}",
+            new_participants.len()
+        )));
+    };
+    // Spec 1.1
+    if new_threshold > new_participants.len() {
+        return Err(InitializationError::BadParameters(
+            "threshold must be <= participant count".to_string(),
+        ));
+    }
+
+    let new_participants = ParticipantList::new(new_participants).ok_or_else(|| {
+        InitializationError::BadParameters(
+            "new participant list cannot contain duplicates".to_string(),
+        )
+    })?;
+
+    if !new_participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "new participant list must contain this participant".to_string(),
+        ));
+    }
+
+    let old_participants = ParticipantList::new(old_participants).ok_or_else(|| {
+        InitializationError::BadParameters(
+            "old participant list cannot contain duplicates".to_string(),
+        )
+    })?;
+
+    let old_subset = old_participants.intersection(&new_participants);
+    if old_subset.len() < old_threshold {
+        return Err(InitializationError::BadParameters(
+            "not enough old participants to reconstruct private key for resharing".to_string(),
+        ));
+    }
+
+    if old_subset.contains(me) && keygen_out.is_none() {
+        return Err(InitializationError::BadParameters(
+            "this party is present in the old participant list but provided no share".to_string(),
+        ));
+    }
+
+    if let Some(out) = &keygen_out {
+        if out.public_key != public_key {
+            return Err(InitializationError::BadParameters(
+                "the public key of the share provided does not match the public key".to_string(),
+            ));
+        }
+    }
+    // Only participants in the old subset contribute their share.
+    let my_share = keygen_out
+        .filter(|_| old_subset.contains(me))
+        .map(|out| out.private_share);
+
+    let ctx = Context::new();
+    let fut = do_reshare::<C>(
+        fork_rng(rng),
+        ctx.shared_channel(),
+        new_participants,
+        old_subset,
+        me,
+        new_threshold,
+        my_share,
+        public_key,
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn do_handoff<C: CSCurve>(
+    mut rng: MeowRng,
+    mut chan: SharedChannel,
+    dealers: ParticipantList,
+    receivers: ParticipantList,
+    me: Participant,
+    new_threshold: usize,
+    my_share: Option<C::Scalar>,
+    public_key: C::AffinePoint,
+) -> Result<Option<KeygenOutput<C>>, ProtocolError> {
+    let everyone = {
+        let mut all: Vec<Participant> = dealers.clone().into();
+        all.extend(Vec::<Participant>::from(receivers.clone()));
+        ParticipantList::new(&all).expect("dealers and receivers should be disjoint")
+    };
+
+    let mut transcript = Transcript::new(LABEL);
+    transcript.message(b"group", C::NAME);
+    transcript.message(b"handoff dealers", &encode(&dealers));
+    transcript.message(b"handoff receivers", &encode(&receivers));
+    transcript.message(
+        b"threshold",
+        &u64::try_from(new_threshold).unwrap().to_be_bytes(),
+    );
+
+    // Each dealer shares their part of the key with the receivers,
+    // publishing a commitment to the polynomial they use, and proving they know its constant.
+    let wait0 = chan.next_waitpoint();
+    let wait1 = chan.next_waitpoint();
+    let mut all_big_f = ParticipantMap::new(&dealers);
+    if let Some(x_i) = my_share {
+        let s_i = dealers.lagrange::<C>(me) * x_i;
+        let f: Polynomial<C> = Polynomial::extend_random(&mut rng, new_threshold, &s_i);
+        let big_f = f.commit();
+        let my_phi_proof = dlog::prove(
+            &mut rng,
+            &mut transcript.forked(b"dlog0", &me.bytes()),
+            dlog::Statement::<C> {
+                public: &big_f.evaluate_zero(),
+            },
+            dlog::Witness::<C> {
+                x: &f.evaluate_zero(),
+            },
+        );
+        chan.send_many(wait0, &(&big_f, my_phi_proof)).await;
+        for p in receivers.others(me) {
+            let x_i_j: ScalarPrimitive<C> = f.evaluate(&p.scalar::<C>()).into();
+            chan.send_private(wait1, p, &x_i_j).await;
+        }
+        all_big_f.put(me, big_f);
+    }
+
+    while !all_big_f.full() {
+        let (from, (their_big_f, their_phi_proof)): (_, (GroupPolynomial<C>, _)) =
+            chan.recv(wait0).await?;
+        if !dealers.contains(from) || from == me {
+            continue;
+        }
+        if their_big_f.len() != new_threshold {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait0,
+                fault: Fault::InvalidMessage("polynomial has the wrong length"),
+            });
+        }
+        let statement = dlog::Statement::<C> {
+            public: &their_big_f.evaluate_zero(),
+        };
+        if !dlog::verify(
+            &mut transcript.forked(b"dlog0", &from.bytes()),
+            statement,
+            &their_phi_proof,
+        ) {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait0,
+                fault: Fault::BadProof,
+            });
+        }
+        all_big_f.put(from, their_big_f);
+    }
+
+    // Everyone checks that they got the same commitments, so that the
+    // receivers end up with shares of the same polynomial.
+    let my_confirmation = hash(&all_big_f);
+    let wait2 = chan.next_waitpoint();
+    chan.send_many(wait2, &my_confirmation).await;
+    let mut seen = ParticipantCounter::new(&everyone);
+    seen.put(me);
+    while !seen.full() {
+        let (from, confirmation): (_, Digest) = chan.recv(wait2).await?;
+        if !seen.put(from) {
+            continue;
+        }
+        if confirmation != my_confirmation {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait2,
+                fault: Fault::InconsistentBroadcast,
+            });
+        }
+    }
+
+    // The commitments to the new polynomial must share the old public key.
+    let order: Vec<Participant> = dealers.clone().into();
+    let mut big_f = all_big_f[order[0]].clone();
+    for p in &order[1..] {
+        big_f += &all_big_f[*p];
+    }
+    if big_f.evaluate_zero() != C::ProjectivePoint::from(public_key) {
+        return Err(ProtocolError::Failed {
+            round: wait0,
+            fault: Fault::InvalidResult("new public key does not match old public key"),
+        });
+    }
+
+    if !receivers.contains(me) {
+        return Ok(None);
+    }
+
+    let mut all_x_j_i = ParticipantMap::new(&dealers);
+    while !all_x_j_i.full() {
+        let (from, x_j_i): (_, ScalarPrimitive<C>) = chan.recv(wait1).await?;
+        all_x_j_i.put(from, C::Scalar::from(x_j_i));
+    }
+    let mut x_i = C::Scalar::ZERO;
+    for p in &order {
+        let x_j_i = all_x_j_i[*p];
+        if all_big_f[*p].evaluate(&me.scalar::<C>()) != C::ProjectivePoint::generator() * x_j_i {
+            return Err(ProtocolError::Malicious {
+                participant: *p,
+                round: wait1,
+                fault: Fault::BadShare,
+            });
+        }
+        x_i += x_j_i;
+    }
+
+    Ok(Some(KeygenOutput {
+        private_share: x_i,
+        public_key,
+    }))
+}
+
+/// The key handoff protocol.
+///
+/// This transfers a key from its current participants to an entirely different set
+/// of participants, potentially with a new threshold. Unlike [reshare()], no participant
+/// needs to be in both sets.
+///
+/// The old participants taking part, of which there need to be at least `old_threshold`,
+/// pass in their output from key generation, and deal shares to the new participants,
+/// publishing commitments which show that the new shares are shares of the same key.
+/// Old participants get `None` as their output, while new participants get their
+/// share, along with the public key, in the same format as key generation.
+///
+/// The old shares still work after this, so the old participants should delete them.
+#[allow(clippy::too_many_arguments)]
+pub fn handoff<C: CSCurve>(
+    old_participants: &[Participant],
+    old_threshold: usize,
+    new_participants: &[Participant],
+    new_threshold: usize,
+    me: Participant,
+    keygen_out: Option<KeygenOutput<C>>,
+    public_key: C::AffinePoint,
+) -> Result<impl Protocol<Output = Option<KeygenOutput<C>>>, InitializationError> {
+    handoff_with_rng(
+        old_participants,
+        old_threshold,
+        new_participants,
+        new_threshold,
+        me,
+        keygen_out,
+        public_key,
+        &mut OsRng,
+    )
+}
+
+/// Like [handoff()], except drawing all of the randomness from a given rng.
+#[allow(clippy::too_many_arguments)]
+pub fn handoff_with_rng<C: CSCurve>(
+    old_participants: &[Participant],
+    old_threshold: usize,
+    new_participants: &[Participant],
+    new_threshold: usize,
+    me: Participant,
+    keygen_out: Option<KeygenOutput<C>>,
+    public_key: C::AffinePoint,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = Option<KeygenOutput<C>>>, InitializationError> {
+    if new_participants.len() < 2 {
+        return Err(InitializationError::BadParameters(format!(
+            "participant count cannot be < 2, found: {}",
+            new_participants.len()
+        )));
+    };
+    if new_threshold > new_participants.len() {
+        return Err(InitializationError::BadParameters(
+            "threshold must be <= participant count".to_string(),
+        ));
+    }
+
+    let new_participants = ParticipantList::new(new_participants).ok_or_else(|| {
+        InitializationError::BadParameters(
+            "new participant list cannot contain duplicates".to_string(),
+        )
+    })?;
+    let old_participants = ParticipantList::new(old_participants).ok_or_else(|| {
+        InitializationError::BadParameters(
+            "old participant list cannot contain duplicates".to_string(),
+        )
+    })?;
+
+    if old_participants.intersection(&new_participants).len() > 0 {
+        return Err(InitializationError::BadParameters(
+            "old and new participants must be disjoint, use resharing otherwise".to_string(),
+        ));
+    }
+    if old_participants.len() < old_threshold {
+        return Err(InitializationError::BadParameters(
+            "not enough old participants to reconstruct private key for handoff".to_string(),
+        ));
+    }
+    if !old_participants.contains(me) && !new_participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain this participant".to_string(),
+        ));
+    }
+
+    let my_share = match keygen_out {
+        Some(out) if old_participants.contains(me) => {
+            if out.public_key != public_key {
+                return Err(InitializationError::BadParameters(
+                    "the public key of the share provided does not match the public key"
+                        .to_string(),
+                ));
+            }
+            Some(out.private_share)
+        }
+        None if old_participants.contains(me) => {
+            return Err(InitializationError::BadParameters(
+                "this party is present in the old participant list but provided no share"
+                    .to_string(),
+            ));
+        }
+        _ => None,
+    };
+
+    let ctx = Context::new();
+    let fut = do_handoff::<C>(
+        fork_rng(rng),
+        ctx.shared_channel(),
+        old_participants,
+        new_participants,
+        me,
+        new_threshold,
+        my_share,
+        public_key,
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
+async fn do_refresh<C: CSCurve>(
+    rng: MeowRng,
+    chan: SharedChannel,
+    participants: ParticipantList,
+    me: Participant,
+    threshold: usize,
+    keygen_out: KeygenOutput<C>,
+) -> Result<KeygenOutput<C>, ProtocolError> {
+    // Sharing zero gives us a polynomial with no constant term, which we can
+    // add to our existing share without changing the key.
+    let (delta_i, _, _) = do_keyshare::<C>(
+        rng,
+        chan,
+        participants,
+        me,
+        threshold,
+        C::Scalar::ZERO,
+        Some(C::ProjectivePoint::identity()),
+        None,
+        ProofTransform::FiatShamir,
+        FiatShamirBackend::default(),
+    )
+    .await?;
+    Ok(KeygenOutput {
+        private_share: keygen_out.private_share + delta_i,
+        public_key: keygen_out.public_key,
+    })
+}
+
+/// The refresh protocol.
+///
+/// This re-randomizes the shares of an existing key, without changing the
+/// public key, the set of participants, or the threshold.
+///
+/// This works by having the participants generate shares of zero, which then
+/// get added to their existing shares. Shares from before the refresh can't
+/// be combined with shares from after it, so refreshing periodically limits
+/// how much time an attacker has to compromise enough participants.
+///
+/// Unlike resharing, every participant needs to be present.
+pub fn refresh<C: CSCurve>(
+    participants: &[Participant],
+    threshold: usize,
+    me: Participant,
+    keygen_out: KeygenOutput<C>,
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    refresh_with_rng(participants, threshold, me, keygen_out, &mut OsRng)
+}
+
+/// Like [refresh()], except drawing all of the randomness from a given rng.
+pub fn refresh_with_rng<C: CSCurve>(
+    participants: &[Participant],
+    threshold: usize,
+    me: Participant,
+    keygen_out: KeygenOutput<C>,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    if participants.len() < 2 {
+        return Err(InitializationError::BadParameters(format!(
+            "participant count cannot be < 2, found: {}",
+            participants.len()
+        )));
+    };
+    if threshold > participants.len() {
+        return Err(InitializationError::BadParameters(
+            "threshold must be <= participant count".to_string(),
+        ));
+    }
+
+    let participants = ParticipantList::new(participants).ok_or_else(|| {
+        InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
+    })?;
+
+    if !participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain this participant".to_string(),
+        ));
+    }
+
+    let ctx = Context::new();
+    let fut = do_refresh(
+        fork_rng(rng),
+        ctx.shared_channel(),
+        participants,
+        me,
+        threshold,
+        keygen_out,
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
+
+    use super::*;
+    use crate::protocol::{run_protocol, Action, MessageData, Participant};
+
+    /// Wraps a protocol, replacing the contents of every message it sends with garbage.
+    struct Garbled<P>(P);
+
+    impl<P: Protocol> Protocol for Garbled<P> {
+        type Output = P::Output;
+
+        fn poke(&mut self) -> Result<Action<Self::Output>, ProtocolError> {
+            // Keep the header, so that the message gets routed, but ruin the payload.
+            let garble = |data: MessageData| {
+                let mut data = data[..30].to_vec();
+                data.push(0xc1);
+                MessageData::from(data)
+            };
+            Ok(match self.0.poke()? {
+                Action::SendMany(data) => Action::SendMany(garble(data)),
+                Action::SendPrivate(to, data) => Action::SendPrivate(to, garble(data)),
+                action => action,
+            })
+        }
+
+        fn message(&mut self, from: Participant, data: MessageData) {
+            self.0.message(from, data)
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn do_keygen(
+        participants: &[Participant],
+        threshold: usize,
+    ) -> Result<Vec<(Participant, KeygenOutput<Secp256k1>)>, Box<dyn Error>> {
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+
+        for p in participants.iter() {
+            let protocol = keygen(participants, *p, threshold)?;
+            protocols.push((*p, Box::new(protocol)));
+        }
+
+        let result = run_protocol(protocols)?;
+        Ok(result)
+    }
+
+    #[test]
+    fn test_keygen() -> Result<(), Box<dyn Error>> {
         let participants = vec![
             Participant::from(0u32),
             Participant::from(1u32),
@@ -403,7 +1557,7 @@ mod test {
         let pub_key = result[2].1.public_key;
 
         let participants = vec![result[0].0, result[1].0, result[2].0];
-        let shares = vec![
+        let shares = [
             result[0].1.private_share,
             result[1].1.private_share,
             result[2].1.private_share,
@@ -418,47 +1572,388 @@ mod test {
     }
 
     #[test]
+    #[allow(clippy::type_complexity)]
+    fn test_keygen_with_fischlin() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        for p in participants.iter() {
+            let protocol =
+                keygen_with_transform(&participants, *p, threshold, ProofTransform::Fischlin)?;
+            protocols.push((*p, Box::new(protocol)));
+        }
+        let result = run_protocol(protocols)?;
+
+        let p_list = ParticipantList::new(&[result[0].0, result[1].0]).unwrap();
+        let x = p_list.lagrange::<Secp256k1>(result[0].0) * result[0].1.private_share
+            + p_list.lagrange::<Secp256k1>(result[1].0) * result[1].1.private_share;
+        for (_, output) in &result {
+            assert_eq!(output.public_key, result[0].1.public_key);
+        }
+        assert_eq!(ProjectivePoint::GENERATOR * x, result[0].1.public_key);
+
+        // Participants which disagree on the transform can't finish together.
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        for p in participants.iter() {
+            let transform = if *p == participants[0] {
+                ProofTransform::Fischlin
+            } else {
+                ProofTransform::FiatShamir
+            };
+            let protocol = keygen_with_transform(&participants, *p, threshold, transform)?;
+            protocols.push((*p, Box::new(protocol)));
+        }
+        assert!(run_protocol(protocols).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::type_complexity)]
+    fn test_keygen_many() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+        let count = 3;
+
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = Vec<KeygenOutput<Secp256k1>>>>,
+        )> = Vec::with_capacity(participants.len());
+        for p in &participants {
+            let protocol = keygen_many(&participants, *p, threshold, count)?;
+            protocols.push((*p, Box::new(protocol)));
+        }
+        let result = run_protocol(protocols)?;
+
+        let p_list = ParticipantList::new(&participants[..threshold]).unwrap();
+        let mut public_keys = Vec::new();
+        for k in 0..count {
+            let public_key = result[0].1[k].public_key;
+            assert!(result
+                .iter()
+                .all(|(_, out)| out[k].public_key == public_key));
+            let x = result
+                .iter()
+                .filter(|(p, _)| p_list.contains(*p))
+                .map(|(p, out)| p_list.lagrange::<Secp256k1>(*p) * out[k].private_share)
+                .fold(Scalar::ZERO, |acc, x| acc + x);
+            assert_eq!(ProjectivePoint::GENERATOR * x, public_key);
+            public_keys.push(public_key);
+        }
+        // The keys are independent.
+        assert_ne!(public_keys[0], public_keys[1]);
+        assert_ne!(public_keys[1], public_keys[2]);
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::type_complexity)]
+    fn test_keygen_reports_culprit() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let bad = participants[2];
+
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        for p in participants.iter() {
+            let protocol = keygen(&participants, *p, 2)?;
+            if *p == bad {
+                protocols.push((*p, Box::new(Garbled(protocol))));
+            } else {
+                protocols.push((*p, Box::new(protocol)));
+            }
+        }
+
+        let err = run_protocol(protocols).unwrap_err();
+        assert_eq!(err.culprit(), Some(bad));
+        assert_eq!(err.round(), Some(0));
+        assert!(matches!(err.fault(), Some(Fault::Serialization(_))));
+
+        Ok(())
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn run_import(
+        participants: &[Participant],
+        threshold: usize,
+        make: impl Fn(
+            Participant,
+        ) -> Result<
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+            InitializationError,
+        >,
+    ) -> Result<Vec<(Participant, KeygenOutput<Secp256k1>)>, Box<dyn Error>> {
+        let mut protocols = Vec::with_capacity(participants.len());
+        for p in participants {
+            protocols.push((*p, make(*p)?));
+        }
+        let result = run_protocol(protocols)?;
+        for (_, out) in &result {
+            assert_eq!(out.public_key, result[0].1.public_key);
+        }
+
+        let subset: Vec<_> = result[..threshold].iter().map(|(p, _)| *p).collect();
+        let p_list = ParticipantList::new(&subset).unwrap();
+        let x = result[..threshold]
+            .iter()
+            .map(|(p, out)| p_list.lagrange::<Secp256k1>(*p) * out.private_share)
+            .fold(Scalar::ZERO, |acc, x| acc + x);
+        assert_eq!(
+            (ProjectivePoint::GENERATOR * x).to_affine(),
+            result[0].1.public_key
+        );
+        Ok(result)
+    }
+
+    #[test]
+    fn test_import_key() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+        let private_key = Scalar::generate_biased(&mut OsRng);
+        let public_key = (ProjectivePoint::GENERATOR * private_key).to_affine();
+
+        let result = run_import(&participants, threshold, |p| {
+            let private_key = (p == participants[1]).then_some(private_key);
+            let protocol =
+                import_key::<Secp256k1>(&participants, p, threshold, private_key, public_key)?;
+            Ok(Box::new(protocol))
+        })?;
+        assert_eq!(result[0].1.public_key, public_key);
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::type_complexity)]
+    fn test_import_key_wrong_public_key() {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let public_key =
+            (ProjectivePoint::GENERATOR * Scalar::generate_biased(&mut OsRng)).to_affine();
+
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        )> = Vec::new();
+        for &p in &participants {
+            let protocol = import_key::<Secp256k1>(&participants, p, 2, None, public_key).unwrap();
+            protocols.push((p, Box::new(protocol)));
+        }
+        assert!(run_protocol(protocols).is_err());
+    }
+
+    #[test]
+    fn test_import_additive_key() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+        let shares: Vec<_> = participants
+            .iter()
+            .map(|_| Scalar::generate_biased(&mut OsRng))
+            .collect();
+        let private_key = shares.iter().fold(Scalar::ZERO, |acc, x| acc + x);
+        let public_key = (ProjectivePoint::GENERATOR * private_key).to_affine();
+
+        let result = run_import(&participants, threshold, |p| {
+            let share = shares[u32::from(p) as usize];
+            let protocol =
+                import_additive_key::<Secp256k1>(&participants, p, threshold, share, public_key)?;
+            Ok(Box::new(protocol))
+        })?;
+        assert_eq!(result[0].1.public_key, public_key);
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::type_complexity)]
     fn test_refresh() -> Result<(), Box<dyn Error>> {
         let participants = vec![
             Participant::from(0u32),
             Participant::from(1u32),
             Participant::from(2u32),
         ];
-        let threshold = 3;
+        let threshold = 2;
 
         let result0 = do_keygen(&participants, threshold)?;
 
         let pub_key = result0[2].1.public_key;
 
         // Refresh
-        let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = Scalar>>)> =
-            Vec::with_capacity(participants.len());
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
 
         for (p, out) in result0.iter() {
-            let protocol = refresh::<Secp256k1>(
-                &participants,
-                threshold,
-                *p,
-                out.private_share,
-                out.public_key,
-            )?;
+            let protocol = refresh::<Secp256k1>(&participants, threshold, *p, out.clone())?;
             protocols.push((*p, Box::new(protocol)));
         }
 
         let result1 = run_protocol(protocols)?;
+        for ((_, old), (_, new)) in result0.iter().zip(result1.iter()) {
+            assert_ne!(old.private_share, new.private_share);
+            assert_eq!(new.public_key, pub_key);
+        }
 
         let participants = vec![result1[0].0, result1[1].0, result1[2].0];
-        let shares = vec![result1[0].1, result1[1].1, result1[2].1];
+        let shares = [
+            result1[0].1.private_share,
+            result1[1].1.private_share,
+            result1[2].1.private_share,
+        ];
         let p_list = ParticipantList::new(&participants).unwrap();
         let x = p_list.lagrange::<Secp256k1>(participants[0]) * shares[0]
             + p_list.lagrange::<Secp256k1>(participants[1]) * shares[1]
             + p_list.lagrange::<Secp256k1>(participants[2]) * shares[2];
         assert_eq!(ProjectivePoint::GENERATOR * x, pub_key);
 
+        let p_list = ParticipantList::new(&participants[1..]).unwrap();
+        let x = p_list.lagrange::<Secp256k1>(participants[1]) * shares[1]
+            + p_list.lagrange::<Secp256k1>(participants[2]) * shares[2];
+        assert_eq!(ProjectivePoint::GENERATOR * x, pub_key);
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::type_complexity)]
+    fn test_deterministic_with_rng() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+
+        let run = || -> Result<Vec<(Participant, KeygenOutput<Secp256k1>)>, Box<dyn Error>> {
+            let mut protocols: Vec<(
+                Participant,
+                Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+            )> = Vec::with_capacity(participants.len());
+            for &p in &participants {
+                let mut rng = MeowRng::new(&[u32::from(p) as u8; 32]);
+                let protocol = keygen_with_rng(&participants, p, threshold, &mut rng)?;
+                protocols.push((p, Box::new(protocol)));
+            }
+            let keygen_out = run_protocol(protocols)?;
+
+            let mut protocols: Vec<(
+                Participant,
+                Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+            )> = Vec::with_capacity(participants.len());
+            for (p, out) in keygen_out {
+                let mut rng = MeowRng::new(&[u32::from(p) as u8 + 3; 32]);
+                let protocol = refresh_with_rng(&participants, threshold, p, out, &mut rng)?;
+                protocols.push((p, Box::new(protocol)));
+            }
+            Ok(run_protocol(protocols)?)
+        };
+
+        let first = run()?;
+        let second = run()?;
+        for ((p0, out0), (p1, out1)) in first.iter().zip(second.iter()) {
+            assert_eq!(p0, p1);
+            assert_eq!(out0.private_share, out1.private_share);
+            assert_eq!(out0.public_key, out1.public_key);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::type_complexity)]
+    fn test_handoff() -> Result<(), Box<dyn Error>> {
+        let old: Vec<_> = (0..3u32).map(Participant::from).collect();
+        let new: Vec<_> = (3..7u32).map(Participant::from).collect();
+        let old_threshold = 2;
+        let new_threshold = 3;
+
+        let result0 = do_keygen(&old, old_threshold)?;
+        let pub_key = result0[0].1.public_key;
+
+        // Only a threshold of the old participants is needed.
+        let mut setup: Vec<_> = result0
+            .into_iter()
+            .filter(|(p, _)| old[..old_threshold].contains(p))
+            .map(|(p, out)| (p, Some(out)))
+            .collect();
+        setup.extend(new.iter().map(|p| (*p, None)));
+
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = Option<KeygenOutput<Secp256k1>>>>,
+        )> = Vec::with_capacity(setup.len());
+        for (p, out) in setup {
+            let protocol = handoff::<Secp256k1>(
+                &old[..old_threshold],
+                old_threshold,
+                &new,
+                new_threshold,
+                p,
+                out,
+                pub_key,
+            )?;
+            protocols.push((p, Box::new(protocol)));
+        }
+
+        let result1 = run_protocol(protocols)?;
+        let mut shares = Vec::new();
+        for (p, out) in result1 {
+            match out {
+                Some(out) => {
+                    assert!(new.contains(&p));
+                    assert_eq!(out.public_key, pub_key);
+                    shares.push((p, out.private_share));
+                }
+                None => assert!(old.contains(&p)),
+            }
+        }
+        assert_eq!(shares.len(), new.len());
+
+        let subset: Vec<_> = shares[1..].iter().map(|(p, _)| *p).collect();
+        let p_list = ParticipantList::new(&subset).unwrap();
+        let x = shares[1..]
+            .iter()
+            .map(|(p, x_i)| p_list.lagrange::<Secp256k1>(*p) * x_i)
+            .fold(Scalar::ZERO, |acc, x| acc + x);
+        assert_eq!(ProjectivePoint::GENERATOR * x, pub_key);
+
+        // Overlapping sets should use resharing instead.
+        assert!(handoff::<Secp256k1>(&old, old_threshold, &old, 2, old[0], None, pub_key).is_err());
+
         Ok(())
     }
 
     #[test]
+    #[allow(clippy::type_complexity)]
     fn test_reshare() -> Result<(), Box<dyn Error>> {
         let participants = vec![
             Participant::from(0u32),
@@ -474,32 +1969,37 @@ mod test {
         let pub_key = result0[2].1.public_key;
 
         // Reshare
-        let mut setup: Vec<_> = result0
-            .into_iter()
-            .map(|(p, out)| (p, (Some(out.private_share), out.public_key)))
-            .collect();
-        setup.push((Participant::from(3u32), (None, pub_key)));
+        let mut setup: Vec<_> = result0.into_iter().map(|(p, out)| (p, Some(out))).collect();
+        setup.push((Participant::from(3u32), None));
 
-        let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = Scalar>>)> =
-            Vec::with_capacity(participants.len());
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
 
-        for (p, out) in setup.iter() {
+        for (p, out) in setup.into_iter() {
             let protocol = reshare::<Secp256k1>(
                 &participants[..3],
                 threshold0,
                 &participants,
                 threshold1,
-                *p,
-                out.0,
-                out.1,
+                p,
+                out,
+                pub_key,
             )?;
-            protocols.push((*p, Box::new(protocol)));
+            protocols.push((p, Box::new(protocol)));
         }
 
         let result1 = run_protocol(protocols)?;
+        assert!(result1.iter().all(|(_, out)| out.public_key == pub_key));
 
         let participants = vec![result1[0].0, result1[1].0, result1[2].0, result1[3].0];
-        let shares = vec![result1[0].1, result1[1].1, result1[2].1, result1[3].1];
+        let shares = [
+            result1[0].1.private_share,
+            result1[1].1.private_share,
+            result1[2].1.private_share,
+            result1[3].1.private_share,
+        ];
         let p_list = ParticipantList::new(&participants).unwrap();
         let x = p_list.lagrange::<Secp256k1>(participants[0]) * shares[0]
             + p_list.lagrange::<Secp256k1>(participants[1]) * shares[1]
@@ -509,4 +2009,23 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_verify_share() -> Result<(), Box<dyn Error>> {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let (commitment, mut outputs) = crate::deal_key::<Secp256k1>(&participants, 2, None)?;
+        let (other_commitment, _) = crate::deal_key::<Secp256k1>(&participants, 2, None)?;
+        let public_key = commitment.public_key();
+
+        let (p, output) = &outputs[0];
+        assert!(output.verify(&public_key, *p, &commitment));
+        assert!(!output.verify(&public_key, participants[1], &commitment));
+        assert!(!output.verify(&other_commitment.public_key(), *p, &other_commitment));
+        assert!(!output.verify(&public_key, *p, &other_commitment));
+
+        let (p, output) = &mut outputs[1];
+        output.private_share += Scalar::ONE;
+        assert!(!output.verify(&public_key, *p, &commitment));
+        Ok(())
+    }
 }
@@ -0,0 +1,246 @@
+//! This module provides a persistent record of which presignatures were used.
+//!
+//! Using a presignature to sign two different messages reveals the private key.
+//! A [`PresignaturePool`](crate::PresignaturePool) prevents this while it lives,
+//! but a process can crash after releasing its share of a signature, and then,
+//! after restarting, be asked to use the same presignature for another message.
+//!
+//! A [`NonceLedger`] records each use of a presignature durably, before
+//! [`sign_with_ledger`](crate::sign_with_ledger) or
+//! [`signature_share_with_ledger`](crate::signature_share_with_ledger) do anything with it.
+//! The ledger refuses to record a second message for the same presignature,
+//! but allows recording the same message again, so that signing can be retried.
+//!
+//! [`FileNonceLedger`] implements this trait with one file per presignature.
+use std::{
+    error, fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use rand_core::{OsRng, RngCore};
+
+use crate::{
+    crypto::{hash, Digest},
+    PresignatureId,
+};
+
+/// A durable record of the message each presignature was used for.
+pub trait NonceLedger {
+    type Error: error::Error + Send + Sync + 'static;
+
+    /// Record that a presignature is being used to sign a given message.
+    ///
+    /// This must only return once the record will survive a crash.
+    /// Recording the same message again succeeds, but recording a different
+    /// message for a presignature which was already used must fail.
+    fn record(&mut self, id: PresignatureId, message: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// Represents an error which can happen when using a [`FileNonceLedger`].
+#[derive(Debug)]
+pub enum FileNonceLedgerError {
+    /// Some error happened reading or writing a file.
+    Io(io::Error),
+    /// A file didn't contain a valid record.
+    Corrupted(PathBuf),
+    /// This presignature was already used for another message.
+    AlreadyUsed(PresignatureId),
+}
+
+impl fmt::Display for FileNonceLedgerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {}", e),
+            Self::Corrupted(path) => write!(f, "corrupted record in {:?}", path),
+            Self::AlreadyUsed(id) => write!(
+                f,
+                "presignature {:?} was already used for another message",
+                id
+            ),
+        }
+    }
+}
+
+impl error::Error for FileNonceLedgerError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FileNonceLedgerError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+const RECORD_EXT: &str = "used";
+
+/// Make the entries of a directory durable.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    fs::File::open(dir)?.sync_all()
+}
+
+/// Make the entries of a directory durable.
+///
+/// Directories can't be opened as files here, and the file system
+/// makes their entries durable along with the files themselves.
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// A ledger keeping the record for each presignature in its own file, inside of a directory.
+///
+/// Each file contains a hash of the message signed with that presignature.
+/// Records are written to a temporary file, unique to each attempt, and then linked into
+/// place, which fails if a record already exists, so that two processes sharing the directory
+/// can't both record a message for the same presignature. The directory is synced after
+/// linking, so that the record survives a crash.
+pub struct FileNonceLedger {
+    dir: PathBuf,
+}
+
+impl FileNonceLedger {
+    /// Open a ledger in a given directory, creating it if necessary.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, FileNonceLedgerError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    fn path(&self, id: PresignatureId, ext: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", id.to_hex(), ext))
+    }
+
+    fn check(
+        &self,
+        path: &Path,
+        id: PresignatureId,
+        digest: &Digest,
+    ) -> Result<(), FileNonceLedgerError> {
+        let data = fs::read(path)?;
+        if data.len() != digest.as_ref().len() {
+            return Err(FileNonceLedgerError::Corrupted(path.to_path_buf()));
+        }
+        if data != digest.as_ref() {
+            return Err(FileNonceLedgerError::AlreadyUsed(id));
+        }
+        Ok(())
+    }
+}
+
+impl NonceLedger for FileNonceLedger {
+    type Error = FileNonceLedgerError;
+
+    fn record(&mut self, id: PresignatureId, message: &[u8]) -> Result<(), Self::Error> {
+        let digest = hash(&(b"cait-sith nonce ledger", message));
+        let path = self.path(id, RECORD_EXT);
+        if path.exists() {
+            return self.check(&path, id, &digest);
+        }
+
+        // Every attempt gets its own temporary file, so that processes sharing the
+        // directory never write to, or remove, each other's.
+        let tmp = self.path(id, &format!("{:016x}.tmp", OsRng.next_u64()));
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&tmp)?;
+        file.write_all(digest.as_ref())?;
+        file.sync_all()?;
+        let linked = fs::hard_link(&tmp, &path);
+        fs::remove_file(&tmp)?;
+        match linked {
+            Ok(()) => {
+                // The link itself only survives a crash once the directory is synced.
+                sync_dir(&self.dir)?;
+                Ok(())
+            }
+            // Someone else recorded a message in the meantime.
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => self.check(&path, id, &digest),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
+    use rand_core::{OsRng, RngCore};
+
+    use super::*;
+    use crate::PresignOutput;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("cait-sith-ledger-{:016x}", OsRng.next_u64()))
+    }
+
+    #[test]
+    fn test_file_ledger() -> Result<(), FileNonceLedgerError> {
+        let dir = temp_dir();
+        let [id0, id1] = [1u64, 2].map(|i| {
+            PresignatureId::of(&PresignOutput::<Secp256k1> {
+                big_r: (ProjectivePoint::GENERATOR * Scalar::from(i)).to_affine(),
+                k: Scalar::ZERO,
+                sigma: Scalar::ZERO,
+                k_inv: Scalar::ZERO,
+            })
+        });
+
+        let mut ledger = FileNonceLedger::open(&dir)?;
+        ledger.record(id0, b"hello")?;
+        ledger.record(id0, b"hello")?;
+        ledger.record(id1, b"world")?;
+
+        // The records survive reopening the ledger.
+        let mut ledger = FileNonceLedger::open(&dir)?;
+        assert!(matches!(
+            ledger.record(id0, b"world"),
+            Err(FileNonceLedgerError::AlreadyUsed(id)) if id == id0
+        ));
+        ledger.record(id0, b"hello")?;
+
+        fs::write(ledger.path(id1, RECORD_EXT), b"garbage")?;
+        assert!(matches!(
+            ledger.record(id1, b"world"),
+            Err(FileNonceLedgerError::Corrupted(_))
+        ));
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_racing_records() -> Result<(), FileNonceLedgerError> {
+        let dir = temp_dir();
+        let id = PresignatureId::of(&PresignOutput::<Secp256k1> {
+            big_r: ProjectivePoint::GENERATOR.to_affine(),
+            k: Scalar::ZERO,
+            sigma: Scalar::ZERO,
+            k_inv: Scalar::ZERO,
+        });
+
+        // Every ledger races to record its own message, like separate processes would.
+        let results: Vec<_> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..8u8)
+                .map(|i| {
+                    let dir = &dir;
+                    s.spawn(move || FileNonceLedger::open(dir)?.record(id, &[i]))
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(results.iter().filter(|r| r.is_ok()).count(), 1);
+        assert!(results
+            .iter()
+            .all(|r| matches!(r, Ok(()) | Err(FileNonceLedgerError::AlreadyUsed(_)))));
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -46,16 +46,22 @@
 //! Then, the parties need to generate a key pair so that they can sign messages:
 //!
 //! - The parties run a distributed key generation protocol to setup a new key pair,
-//! which can be used for many signatures.
+//!   which can be used for many signatures.
 //!
 //! When the parties want to sign using a given key:
 //!
 //! - Using their shares of a private key, the parties can create a *presignature*,
-//! before knowing the message to sign.
+//!   before knowing the message to sign.
 //! - Once they know this message, they can use the presignature to create a complete signature.
 //!
 //! It's important that presignatures and triples are **never** reused.
 //!
+//! [`ThresholdSigner`] runs all of these protocols behind a single interface,
+//! for applications which just want to generate a key, and sign with it.
+//!
+//! With exactly two parties, who both need to sign, [`keygen_two_party`] and
+//! [`presign_two_party`] skip the triples entirely, taking fewer rounds, and fewer bytes.
+//!
 //! ## API Design
 //!
 //! Internally, the API tries to be as simple as possible abstracting away
@@ -100,9 +106,22 @@
 //! | Curve | Feature |
 //! |-------|---------|
 //! |Secp256k1|`k256`|
+//! |P-256|`p256`|
+//! |Stark|`stark`|
 //!
 //! For supporting any message hash, the API requires the user to supply
 //! the hash of a message when signing as a scalar directly.
+//! For Ethereum, the `ethereum` feature provides `ethereum_message_hash` and
+//! `ethereum_personal_message_hash`, which hash with keccak256, so that signatures
+//! can be checked with `ecrecover`.
+//! For interoperating with FROST implementations, the `frost` feature converts
+//! key shares to and from the key packages of `frost-core`.
+//!
+//! Curves need to implement RustCrypto's `PrimeCurve` and `CurveArithmetic`
+//! traits, which rules out edwards25519 for now, since no implementation of
+//! that curve provides them. Threshold EdDSA is thus not supported yet.
+//! The signing protocol would be the same as for the Schnorr signatures
+//! over secp256k1, with a different challenge, so adding it mostly means adding the curve.
 //!
 //! # Shortcomings
 //!
@@ -115,22 +134,105 @@
 //! While these can be desirable in certain situations, we aren't satisfied
 //! with the way the property of identifiable aborts is modeled currently,
 //! and are working on improvements to this model.
+mod adaptor;
+mod audit;
+mod commitments;
 mod compat;
+mod config;
 mod constants;
+pub mod cost;
 mod crypto;
+mod dealer;
+mod decrypt;
+mod derivation;
+mod envelope;
+#[cfg(any(feature = "ethereum", test))]
+mod ethereum;
+mod export;
+pub mod fiat_shamir;
+#[cfg(any(feature = "frost", test))]
+mod frost;
 mod keyshare;
+mod ledger;
 mod math;
+mod migration;
+mod parallel;
 mod participants;
+mod pool;
 mod presign;
-mod proofs;
+pub mod proofs;
 pub mod protocol;
+#[cfg(any(feature = "k256", test))]
+mod schnorr;
+mod seal;
 mod serde;
 mod sign;
+mod signer;
+#[cfg(any(feature = "stark", test))]
+pub mod stark;
 #[cfg(test)]
 mod test;
+mod trace;
 pub mod triples;
+mod tweak;
+mod two_party;
+mod vault;
+mod vrf;
+mod x509;
 
-pub use compat::CSCurve;
-pub use keyshare::{keygen, refresh, reshare, KeygenOutput};
-pub use presign::{presign, PresignArguments, PresignOutput};
-pub use sign::{combine_signature_shares, sign, signature_share, FullSignature};
+pub use adaptor::{sign_adaptor, sign_adaptor_with_rng, AdaptorSignature};
+pub use audit::{verify_keygen_audit, AuditError, KeygenAudit};
+pub use commitments::{
+    CommitmentScheme, HashCommitments, PedersenCommitment, PedersenCommitments, PedersenOpening,
+};
+pub use compat::{expand_to_scalar, CSCurve};
+pub use config::{ConfigError, KeygenConfig, PresignConfig, SignConfig, TripleConfig};
+pub use crypto::{Commitment, Randomizer};
+pub use dealer::{deal_key, deal_key_with_rng, DealtKey, KeyCommitment};
+pub use decrypt::{
+    combine_decryptions, encrypt_key, encrypt_key_with_rng, partial_decrypt,
+    partial_decrypt_with_rng, DecryptionError, EncryptedKey, PartialDecryption,
+};
+pub use derivation::{derive_public, ChainCode, DerivationError};
+pub use envelope::{EnvelopeError, Versioned};
+#[cfg(any(feature = "ethereum", test))]
+pub use ethereum::{
+    ethereum_address, ethereum_message_hash, ethereum_personal_message_hash, keccak256,
+    EthereumSignature,
+};
+pub use export::{public_key_to_der, public_key_to_pem, public_key_to_sec1};
+#[cfg(any(feature = "frost", test))]
+pub use frost::{FrostCiphersuite, FrostError, FrostKeyPackage};
+pub use keyshare::{
+    handoff, handoff_with_rng, import_additive_key, import_additive_key_with_rng, import_key,
+    import_key_with_rng, keygen, keygen_audited, keygen_audited_with_rng, keygen_many,
+    keygen_many_with_rng, keygen_with_rng, keygen_with_transform, keygen_with_transform_with_rng,
+    refresh, refresh_with_rng, reshare, reshare_with_rng, KeygenOutput, ProofTransform,
+};
+pub use ledger::{FileNonceLedger, FileNonceLedgerError, NonceLedger};
+pub use migration::{import_gg_key, import_gg_key_with_rng};
+pub use pool::{PoolError, PresignatureId, PresignaturePool};
+pub use presign::{
+    presign, presign_bound, presign_from_source, presign_many, presign_tolerant,
+    BoundPresignOutput, PresignArguments, PresignBinding, PresignOutput,
+};
+#[cfg(any(feature = "k256", test))]
+pub use schnorr::{
+    schnorr_blind, schnorr_blind_with_rng, schnorr_presign, schnorr_presign_with_rng, schnorr_sign,
+    schnorr_sign_blind, SchnorrPresignOutput, SchnorrSignature, SchnorrUnblinder,
+};
+pub use seal::{SealError, SealKey, SealParams};
+pub use sign::{
+    sign, sign_bound, sign_many, sign_tolerant, sign_with_ledger, sign_with_policy,
+    signature_share, signature_share_bound, signature_share_with_ledger, FullSignature,
+    SignatureShare, SigningPolicy,
+};
+pub use signer::{SignerAction, SignerConfig, ThresholdSigner};
+pub use tweak::{tweak, Tweak};
+pub use two_party::{
+    keygen_two_party, keygen_two_party_with_rng, presign_two_party, presign_two_party_with_rng,
+    sign_two_party,
+};
+pub use vault::ShareVault;
+pub use vrf::{vrf_evaluate, vrf_evaluate_with_rng, VrfProof};
+pub use x509::{NameAttribute, ToBeSigned, X509Error};
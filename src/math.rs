@@ -1,12 +1,13 @@
 use std::ops::{Add, AddAssign, Index, Mul, MulAssign};
 
-use elliptic_curve::{Field, Group};
+use elliptic_curve::{bigint::ArrayEncoding, Field, Group, ScalarPrimitive};
 use rand_core::CryptoRngCore;
 use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use crate::{
     compat::CSCurve,
-    serde::{deserialize_projective_points, serialize_projective_points},
+    serde::{deserialize_projective_points, serialize_projective_points, Schema, Wire},
 };
 
 /// Represents a polynomial with coefficients in the scalar field of the curve.
@@ -16,6 +17,20 @@ pub struct Polynomial<C: CSCurve> {
     coefficients: Vec<C::Scalar>,
 }
 
+impl<C: CSCurve> Zeroize for Polynomial<C> {
+    fn zeroize(&mut self) {
+        self.coefficients.zeroize();
+    }
+}
+
+impl<C: CSCurve> Drop for Polynomial<C> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<C: CSCurve> ZeroizeOnDrop for Polynomial<C> {}
+
 impl<C: CSCurve> Polynomial<C> {
     /// Generate a random polynomial with a certain number of coefficients.
     pub fn random(rng: &mut impl CryptoRngCore, size: usize) -> Self {
@@ -69,7 +84,7 @@ impl<C: CSCurve> Polynomial<C> {
     ///
     /// This is much more efficient than evaluating at other points.
     pub fn evaluate_zero(&self) -> C::Scalar {
-        self.coefficients.get(0).cloned().unwrap_or_default()
+        self.coefficients.first().cloned().unwrap_or_default()
     }
 
     /// Set the zero value of this polynomial to a new scalar
@@ -152,7 +167,20 @@ pub struct GroupPolynomial<C: CSCurve> {
     coefficients: Vec<C::ProjectivePoint>,
 }
 
+impl<C: CSCurve> Wire for GroupPolynomial<C> {
+    fn schema() -> Schema {
+        Schema::message([("coefficients", 1, Schema::repeated(Schema::Bytes))])
+    }
+}
+
 impl<C: CSCurve> GroupPolynomial<C> {
+    /// A polynomial of a given length, with every coefficient set to the identity.
+    pub fn zero(len: usize) -> Self {
+        Self {
+            coefficients: vec![C::ProjectivePoint::identity(); len],
+        }
+    }
+
     /// Modify this polynomial by adding another one.
     pub fn add_mut(&mut self, other: &Self) {
         self.coefficients
@@ -176,16 +204,21 @@ impl<C: CSCurve> GroupPolynomial<C> {
     ///
     /// This is more efficient than evaluating at an arbitrary point.
     pub fn evaluate_zero(&self) -> C::ProjectivePoint {
-        self.coefficients.get(0).cloned().unwrap_or_default()
+        self.coefficients.first().cloned().unwrap_or_default()
     }
 
     /// Evaluate this polynomial at a specific value.
     pub fn evaluate(&self, x: &C::Scalar) -> C::ProjectivePoint {
-        let mut out = C::ProjectivePoint::identity();
-        for c in self.coefficients.iter().rev() {
-            out = out * x + c;
-        }
-        out
+        let powers: Vec<_> = self
+            .coefficients
+            .iter()
+            .scan(C::Scalar::ONE, |power, _| {
+                let out = *power;
+                *power *= x;
+                Some(out)
+            })
+            .collect();
+        multiscalar_mul::<C>(&powers, &self.coefficients)
     }
 
     /// Set the zero value of this polynomial to a new group value.
@@ -217,10 +250,63 @@ impl<C: CSCurve> AddAssign<&Self> for GroupPolynomial<C> {
     }
 }
 
+/// The number of bits of a scalar handled in each step of [multiscalar_mul].
+const WINDOW_BITS: usize = 4;
+
+/// Compute the sum of `scalars[i] * points[i]`.
+///
+/// This uses Straus' method, sharing the doublings between all of the terms,
+/// which makes it much faster than computing each product separately.
+///
+/// This runs in variable time, so it should only be used with public values.
+pub fn multiscalar_mul<C: CSCurve>(
+    scalars: &[C::Scalar],
+    points: &[C::ProjectivePoint],
+) -> C::ProjectivePoint {
+    assert_eq!(scalars.len(), points.len(), "mismatched scalars and points");
+
+    // Each table holds 0, P, 2P, ..., (2^w - 1)P.
+    let tables: Vec<Vec<C::ProjectivePoint>> = points
+        .iter()
+        .map(|point| {
+            let mut table = vec![C::ProjectivePoint::identity(); 1 << WINDOW_BITS];
+            for i in 1..table.len() {
+                table[i] = table[i - 1] + point;
+            }
+            table
+        })
+        .collect();
+    let digits: Vec<_> = scalars
+        .iter()
+        .map(|s| {
+            Into::<ScalarPrimitive<C>>::into(*s)
+                .as_uint()
+                .to_be_byte_array()
+        })
+        .collect();
+
+    let mut out = C::ProjectivePoint::identity();
+    let windows = digits.first().map_or(0, |d| d.len() * 8 / WINDOW_BITS);
+    for i in 0..windows {
+        for _ in 0..WINDOW_BITS {
+            out = out.double();
+        }
+        let shift = 8 - WINDOW_BITS * (1 + i % (8 / WINDOW_BITS));
+        for (table, digits) in tables.iter().zip(&digits) {
+            let digit = (digits[i * WINDOW_BITS / 8] >> shift) as usize & ((1 << WINDOW_BITS) - 1);
+            if digit != 0 {
+                out += table[digit];
+            }
+        }
+    }
+    out
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use k256::{Scalar, Secp256k1};
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
+    use rand_core::OsRng;
 
     #[test]
     fn test_addition() {
@@ -259,5 +345,34 @@ mod test {
         };
         assert_eq!(f.evaluate(&Scalar::from(1u32)), Scalar::from(3u32));
         assert_eq!(f.evaluate(&Scalar::from(2u32)), Scalar::from(5u32));
+
+        let big_g = ProjectivePoint::GENERATOR;
+        let big_f = f.commit();
+        assert_eq!(
+            big_f.evaluate(&Scalar::from(2u32)),
+            big_g * Scalar::from(5u32)
+        );
+        assert_eq!(
+            GroupPolynomial::<Secp256k1>::zero(0).evaluate(&Scalar::from(2u32)),
+            ProjectivePoint::IDENTITY
+        );
+    }
+
+    #[test]
+    fn test_multiscalar_mul() {
+        for n in [0, 1, 2, 7] {
+            let scalars: Vec<_> = (0..n).map(|_| Scalar::random(&mut OsRng)).collect();
+            let points: Vec<_> = (0..n)
+                .map(|_| ProjectivePoint::GENERATOR * Scalar::random(&mut OsRng))
+                .collect();
+            let expected: ProjectivePoint = scalars.iter().zip(&points).map(|(s, p)| *p * s).sum();
+            assert_eq!(multiscalar_mul::<Secp256k1>(&scalars, &points), expected);
+        }
+        let edge = [Scalar::ZERO, Scalar::ONE, -Scalar::ONE];
+        let points = [ProjectivePoint::GENERATOR; 3];
+        assert_eq!(
+            multiscalar_mul::<Secp256k1>(&edge, &points),
+            ProjectivePoint::IDENTITY
+        );
     }
 }
@@ -0,0 +1,264 @@
+//! Migrating key shares from GG18 and GG20 implementations.
+//!
+//! Implementations of the GG18 and GG20 protocols, like `multi-party-ecdsa`, split the
+//! key with Shamir's scheme, giving the party with index `i`, starting from 1, the
+//! evaluation at `i` of a polynomial whose constant term is the key. With a threshold
+//! of `t`, in their convention, any `t + 1` of these shares determine the key.
+//!
+//! This lets deployments holding such shares move to this library without moving funds:
+//! enough of the holders run [import_gg_key()], along with any new participants,
+//! turning their shares into additive shares of the same key, which get reshared
+//! among the new participants, exactly as with [import_additive_key()](crate::import_additive_key).
+//! The key itself never gets reconstructed.
+//!
+//! In `multi-party-ecdsa`, the share, index, and public key of a `LocalKey` are
+//! its `keys_linear.x_i`, `i`, and `y_sum_s` fields respectively.
+use elliptic_curve::{Field, Group};
+use rand_core::{CryptoRngCore, OsRng};
+
+use crate::{
+    compat::CSCurve,
+    import_additive_key_with_rng,
+    participants::ParticipantList,
+    protocol::{InitializationError, Participant, Protocol},
+    KeygenOutput,
+};
+
+/// The lagrange coefficient of index `i`, for evaluating at 0, amongst the given indices.
+fn lagrange<C: CSCurve>(indices: &[u16], i: u16) -> C::Scalar {
+    let x_i = C::Scalar::from(u64::from(i));
+    let mut top = C::Scalar::ONE;
+    let mut bot = C::Scalar::ONE;
+    for &j in indices.iter().filter(|&&j| j != i) {
+        let x_j = C::Scalar::from(u64::from(j));
+        top *= x_j;
+        bot *= x_j - x_i;
+    }
+    top * bot.invert().unwrap()
+}
+
+/// Import a key shared by a GG18 or GG20 implementation.
+///
+/// `holders` lists the participants holding an old share, along with the index
+/// of that share in the old implementation, and needs to contain at least
+/// `t + 1` of them, for the old threshold `t`. Every participant needs to pass the same list.
+/// Holders pass their old `share`, and the other participants, which join the key, pass `None`.
+///
+/// The output is a fresh sharing of `public_key` amongst `participants`,
+/// any `threshold` of which can use the key. This fails, without anyone learning
+/// anything about the key, if the old shares can't be combined into it.
+pub fn import_gg_key<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    holders: &[(Participant, u16)],
+    share: Option<C::Scalar>,
+    public_key: C::AffinePoint,
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    import_gg_key_with_rng(
+        participants,
+        me,
+        threshold,
+        holders,
+        share,
+        public_key,
+        &mut OsRng,
+    )
+}
+
+/// Like [import_gg_key()], except drawing all of the randomness from a given rng.
+pub fn import_gg_key_with_rng<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    holders: &[(Participant, u16)],
+    share: Option<C::Scalar>,
+    public_key: C::AffinePoint,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    if holders.is_empty() {
+        return Err(InitializationError::BadParameters(
+            "there must be at least one holder".to_string(),
+        ));
+    }
+    let holder_list: Vec<_> = holders.iter().map(|(p, _)| *p).collect();
+    if ParticipantList::new(&holder_list).is_none() {
+        return Err(InitializationError::BadParameters(
+            "holder list cannot contain duplicates".to_string(),
+        ));
+    }
+    if holder_list.iter().any(|p| !participants.contains(p)) {
+        return Err(InitializationError::BadParameters(
+            "every holder must be a participant".to_string(),
+        ));
+    }
+    let mut indices: Vec<_> = holders.iter().map(|(_, i)| *i).collect();
+    indices.sort_unstable();
+    if indices[0] == 0 {
+        return Err(InitializationError::BadParameters(
+            "old indices start from 1".to_string(),
+        ));
+    }
+    if indices.windows(2).any(|w| w[0] == w[1]) {
+        return Err(InitializationError::BadParameters(
+            "old indices cannot contain duplicates".to_string(),
+        ));
+    }
+
+    let my_index = holders.iter().find(|(p, _)| *p == me).map(|(_, i)| *i);
+    let additive_share = match (my_index, share) {
+        (Some(i), Some(x_i)) => {
+            if bool::from(x_i.is_zero()) {
+                return Err(InitializationError::BadParameters(
+                    "old share cannot be zero".to_string(),
+                ));
+            }
+            lagrange::<C>(&indices, i) * x_i
+        }
+        (None, None) => C::Scalar::ZERO,
+        (Some(_), None) => {
+            return Err(InitializationError::BadParameters(
+                "holders must provide their old share".to_string(),
+            ))
+        }
+        (None, Some(_)) => {
+            return Err(InitializationError::BadParameters(
+                "only holders can provide an old share".to_string(),
+            ))
+        }
+    };
+    if bool::from(C::ProjectivePoint::from(public_key).is_identity()) {
+        return Err(InitializationError::BadParameters(
+            "public key cannot be the identity".to_string(),
+        ));
+    }
+
+    import_additive_key_with_rng(participants, me, threshold, additive_share, public_key, rng)
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use k256::{AffinePoint, ProjectivePoint, Scalar, Secp256k1};
+
+    use super::*;
+    use crate::{math::Polynomial, protocol::run_protocol};
+
+    /// Share a key the way GG18 does, with a threshold of `t`, returning the shares at indices 1..=n.
+    fn gg_shares(t: usize, n: u16) -> (Scalar, Vec<Scalar>) {
+        let key = Scalar::random(&mut OsRng);
+        let f = Polynomial::<Secp256k1>::extend_random(&mut OsRng, t + 1, &key);
+        let shares = (1..=n)
+            .map(|i| f.evaluate(&Scalar::from(u64::from(i))))
+            .collect();
+        (key, shares)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn run_import(
+        participants: &[Participant],
+        threshold: usize,
+        holders: &[(Participant, u16)],
+        shares: &[Scalar],
+        public_key: AffinePoint,
+    ) -> Result<Vec<(Participant, KeygenOutput<Secp256k1>)>, Box<dyn Error>> {
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        for &p in participants {
+            let share = holders
+                .iter()
+                .find(|(q, _)| *q == p)
+                .map(|(_, i)| shares[usize::from(*i) - 1]);
+            let protocol =
+                import_gg_key::<Secp256k1>(participants, p, threshold, holders, share, public_key)?;
+            protocols.push((p, Box::new(protocol)));
+        }
+        Ok(run_protocol(protocols)?)
+    }
+
+    #[test]
+    fn test_import_gg_key() -> Result<(), Box<dyn Error>> {
+        // A 2-of-4 key, in GG18 terms, of which parties 2, 3, and 4 migrate, with a newcomer.
+        let (key, shares) = gg_shares(2, 4);
+        let public_key = (ProjectivePoint::GENERATOR * key).to_affine();
+        let participants: Vec<_> = (0..4u32).map(Participant::from).collect();
+        let holders = [
+            (participants[0], 2),
+            (participants[1], 4),
+            (participants[2], 3),
+        ];
+        let threshold = 3;
+
+        let result = run_import(&participants, threshold, &holders, &shares, public_key)?;
+
+        let subset: Vec<_> = result[1..].iter().map(|(p, _)| *p).collect();
+        let p_list = ParticipantList::new(&subset).unwrap();
+        let x = result[1..]
+            .iter()
+            .map(|(p, out)| {
+                assert_eq!(out.public_key, public_key);
+                p_list.lagrange::<Secp256k1>(*p) * out.private_share
+            })
+            .fold(Scalar::ZERO, |acc, x| acc + x);
+        assert_eq!(x, key);
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_gg_key_with_too_few_holders_fails() -> Result<(), Box<dyn Error>> {
+        let (key, shares) = gg_shares(2, 4);
+        let public_key = (ProjectivePoint::GENERATOR * key).to_affine();
+        let participants: Vec<_> = (0..3u32).map(Participant::from).collect();
+        let holders = [(participants[0], 1), (participants[1], 2)];
+
+        assert!(run_import(&participants, 2, &holders, &shares, public_key).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_gg_key_checks_parameters() {
+        let participants: Vec<_> = (0..3u32).map(Participant::from).collect();
+        let public_key = ProjectivePoint::GENERATOR.to_affine();
+        let share = Some(Scalar::ONE);
+        let bad_holders: [&[(Participant, u16)]; 4] = [
+            &[],
+            &[(participants[0], 0), (participants[1], 1)],
+            &[(participants[0], 1), (participants[1], 1)],
+            &[(participants[0], 1), (Participant::from(7u32), 2)],
+        ];
+        for holders in bad_holders {
+            assert!(import_gg_key::<Secp256k1>(
+                &participants,
+                participants[0],
+                2,
+                holders,
+                share,
+                public_key
+            )
+            .is_err());
+        }
+
+        let holders = [(participants[0], 1), (participants[1], 2)];
+        assert!(import_gg_key::<Secp256k1>(
+            &participants,
+            participants[0],
+            2,
+            &holders,
+            None,
+            public_key
+        )
+        .is_err());
+        assert!(import_gg_key::<Secp256k1>(
+            &participants,
+            participants[2],
+            2,
+            &holders,
+            share,
+            public_key
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,40 @@
+//! Helpers for running independent computations in parallel.
+//!
+//! With the `parallel` feature, these spread the work over rayon's thread pool,
+//! otherwise they simply run sequentially. Either way, the results are the same,
+//! and come back in order.
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Map a function over the elements of a slice, along with their index.
+pub fn par_map<T, U, F>(xs: &[T], f: F) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(usize, &T) -> U + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    {
+        xs.par_iter().enumerate().map(|(i, x)| f(i, x)).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        xs.iter().enumerate().map(|(i, x)| f(i, x)).collect()
+    }
+}
+
+/// Map a function over the indices `0..n`.
+pub fn par_map_range<U, F>(n: usize, f: F) -> Vec<U>
+where
+    U: Send,
+    F: Fn(usize) -> U + Sync + Send,
+{
+    #[cfg(feature = "parallel")]
+    {
+        (0..n).into_par_iter().map(f).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        (0..n).map(f).collect()
+    }
+}
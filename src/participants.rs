@@ -4,13 +4,29 @@
 //! or getting the field values corresponding to each participant, etc.
 //! This module tries to provide useful data structures for doing that.
 
-use std::{collections::HashMap, mem, ops::Index};
+use std::{collections::HashMap, mem, ops::Index, sync::OnceLock};
 
-use elliptic_curve::Field;
+use elliptic_curve::{Field, PrimeField};
 use serde::Serialize;
 
 use crate::{compat::CSCurve, protocol::Participant};
 
+/// The lagrange coefficients of a list of participants, for some curve.
+///
+/// Because curves don't have to be `'static`, the coefficients are stored as bytes,
+/// along with the name of their curve.
+#[derive(Clone, Debug)]
+struct LagrangeCoefficients {
+    curve: &'static [u8],
+    coefficients: Vec<Vec<u8>>,
+}
+
+fn decode_scalar<C: CSCurve>(bytes: &[u8]) -> C::Scalar {
+    let mut repr = <C::Scalar as PrimeField>::Repr::default();
+    repr.as_mut().copy_from_slice(bytes);
+    C::Scalar::from_repr(repr).expect("cached scalars should be valid")
+}
+
 /// Represents a sorted list of participants.
 ///
 /// The advantage of this data structure is that it can be hashed in the protocol transcript,
@@ -21,6 +37,12 @@ pub struct ParticipantList {
     /// This maps each participant to their index in the vector above.
     #[serde(skip_serializing)]
     indices: HashMap<Participant, usize>,
+    /// The lagrange coefficients of this list, computed the first time they're needed.
+    ///
+    /// Protocols look up the coefficient of each participant in turn,
+    /// so this saves inverting the same values over and over again.
+    #[serde(skip_serializing)]
+    lagrange: OnceLock<LagrangeCoefficients>,
 }
 
 impl ParticipantList {
@@ -41,6 +63,7 @@ impl ParticipantList {
         Some(Self {
             participants,
             indices,
+            lagrange: OnceLock::new(),
         })
     }
 
@@ -74,20 +97,70 @@ impl ParticipantList {
 
     /// Get the lagrange coefficient for a participant, relative to this list.
     pub fn lagrange<C: CSCurve>(&self, p: Participant) -> C::Scalar {
-        let p_scalar = p.scalar::<C>();
+        let i = self.index(p);
+        self.with_lagrange_coefficients::<C, _>(|coefficients| decode_scalar::<C>(&coefficients[i]))
+    }
+
+    /// Get the lagrange coefficients of every participant, in order, relative to this list.
+    pub fn lagrange_coefficients<C: CSCurve>(&self) -> Vec<C::Scalar> {
+        self.with_lagrange_coefficients::<C, _>(|coefficients| {
+            coefficients.iter().map(|x| decode_scalar::<C>(x)).collect()
+        })
+    }
+
+    /// Look at the encoded lagrange coefficients for this list, computing them if necessary.
+    ///
+    /// Only the coefficients of the first curve used are kept, since a list is
+    /// almost always used with a single curve; other curves compute them each time.
+    fn with_lagrange_coefficients<C: CSCurve, T>(&self, f: impl FnOnce(&[Vec<u8>]) -> T) -> T {
+        let encode = || LagrangeCoefficients {
+            curve: C::NAME,
+            coefficients: self
+                .compute_lagrange_coefficients::<C>()
+                .iter()
+                .map(|x| x.to_repr().as_ref().to_vec())
+                .collect(),
+        };
+        let cached = self.lagrange.get_or_init(encode);
+        if cached.curve == C::NAME {
+            f(&cached.coefficients)
+        } else {
+            f(&encode().coefficients)
+        }
+    }
 
-        let mut top = C::Scalar::ONE;
-        let mut bot = C::Scalar::ONE;
-        for q in &self.participants {
-            if p == *q {
-                continue;
+    fn compute_lagrange_coefficients<C: CSCurve>(&self) -> Vec<C::Scalar> {
+        let scalars: Vec<C::Scalar> = self.participants.iter().map(|p| p.scalar::<C>()).collect();
+        let mut tops = Vec::with_capacity(scalars.len());
+        let mut bots = Vec::with_capacity(scalars.len());
+        for (i, p_scalar) in scalars.iter().enumerate() {
+            let mut top = C::Scalar::ONE;
+            let mut bot = C::Scalar::ONE;
+            for (j, q_scalar) in scalars.iter().enumerate() {
+                if i == j {
+                    continue;
+                }
+                top *= q_scalar;
+                bot *= *q_scalar - p_scalar;
             }
-            let q_scalar = q.scalar::<C>();
-            top *= q_scalar;
-            bot *= q_scalar - p_scalar;
+            tops.push(top);
+            bots.push(bot);
         }
 
-        top * bot.invert().unwrap()
+        // Invert all of the denominators at once, with a single inversion.
+        let mut acc = C::Scalar::ONE;
+        let mut prefixes = Vec::with_capacity(bots.len());
+        for bot in &bots {
+            prefixes.push(acc);
+            acc *= bot;
+        }
+        let mut inv = acc.invert().unwrap();
+        let mut out = vec![C::Scalar::ZERO; bots.len()];
+        for i in (0..bots.len()).rev() {
+            out[i] = tops[i] * inv * prefixes[i];
+            inv *= bots[i];
+        }
+        out
     }
 
     /// Return the intersection of this list with another list.
@@ -231,3 +304,35 @@ impl<'a> ParticipantCounter<'a> {
         self.counter == 0
     }
 }
+
+#[cfg(test)]
+mod test {
+    use k256::{Scalar, Secp256k1};
+
+    use super::*;
+
+    #[test]
+    fn test_lagrange_coefficients() {
+        let participants: Vec<Participant> = [0u32, 3, 4, 9].map(Participant::from).to_vec();
+        let list = ParticipantList::new(&participants).unwrap();
+
+        for (i, p) in participants.iter().enumerate() {
+            let p_scalar = p.scalar::<Secp256k1>();
+            let mut expected = Scalar::ONE;
+            for q in participants.iter().filter(|q| *q != p) {
+                let q_scalar = q.scalar::<Secp256k1>();
+                expected *= q_scalar * (q_scalar - p_scalar).invert().unwrap();
+            }
+            assert_eq!(list.lagrange_coefficients::<Secp256k1>()[i], expected);
+            // The second lookup hits the cache.
+            assert_eq!(list.lagrange::<Secp256k1>(*p), expected);
+        }
+
+        // Interpolating a constant polynomial gives back that constant.
+        let sum: Scalar = list.lagrange_coefficients::<Secp256k1>().into_iter().sum();
+        assert_eq!(sum, Scalar::ONE);
+
+        // The coefficients live on the list, and are kept by its clones.
+        assert_eq!(list.clone().lagrange.get().unwrap().curve, Secp256k1::NAME);
+    }
+}
@@ -0,0 +1,420 @@
+//! This module provides a pool for managing presignatures.
+//!
+//! Presignatures must **never** be used more than once, otherwise the private
+//! key can be recovered from the two signatures produced. A [`PresignaturePool`]
+//! keeps track of which presignatures are available, and which have already been used,
+//! refusing to hand out the same presignature twice.
+//!
+//! The pool can also run the protocol generating new presignatures itself,
+//! whenever the number of available presignatures drops below some watermark.
+//! To do this, the pool implements [`Protocol`], forwarding messages to
+//! the replenishment currently running, if any.
+use std::{
+    collections::{HashSet, VecDeque},
+    error, fmt,
+};
+
+use elliptic_curve::point::AffineCoordinates;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    compat::CSCurve,
+    crypto::{hash, Digest},
+    protocol::{
+        Action, Format, InitializationError, MessageData, Participant, Protocol, ProtocolError,
+        QueueLimits,
+    },
+    PresignOutput,
+};
+
+/// Identifies a presignature.
+///
+/// This is derived from the public nonce of the presignature, so all the participants
+/// holding a share of a presignature agree on its identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PresignatureId(Digest);
+
+impl PresignatureId {
+    /// The identifier for a given presignature.
+    pub fn of<C: CSCurve>(presignature: &PresignOutput<C>) -> Self {
+        let big_r = &presignature.big_r;
+        Self(hash(&(&big_r.x()[..], big_r.y_is_odd().unwrap_u8())))
+    }
+
+    pub(crate) fn to_hex(self) -> String {
+        self.0
+            .as_ref()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+/// Represents an error when getting presignatures in or out of a pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoolError {
+    /// This presignature has already been used, and can't be used again.
+    AlreadyUsed(PresignatureId),
+    /// This presignature is already in the pool.
+    Duplicate(PresignatureId),
+    /// This presignature isn't in the pool.
+    Unknown(PresignatureId),
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolError::AlreadyUsed(id) => write!(f, "presignature {:?} was already used", id),
+            PoolError::Duplicate(id) => write!(f, "presignature {:?} is already in the pool", id),
+            PoolError::Unknown(id) => write!(f, "presignature {:?} is not in the pool", id),
+        }
+    }
+}
+
+impl error::Error for PoolError {}
+
+/// A pool of presignatures, guaranteeing that each of them is used at most once.
+///
+/// Each participant keeps their own pool. When signing, one participant should pick
+/// a presignature with [`PresignaturePool::take_next`], and tell the others its identifier,
+/// so that they can each [`PresignaturePool::take`] their share of the same presignature.
+///
+/// Note that the pool only remembers which presignatures were used for as long
+/// as it lives. Presignatures should not outlive the pool that tracks them,
+/// unless their use is also recorded in a [`NonceLedger`](crate::NonceLedger).
+pub struct PresignaturePool<C: CSCurve> {
+    available: VecDeque<(PresignatureId, PresignOutput<C>)>,
+    used: HashSet<PresignatureId>,
+    low_watermark: usize,
+    batch_size: usize,
+    replenishing: Option<Box<dyn Protocol<Output = Vec<PresignOutput<C>>>>>,
+    queue_limits: QueueLimits,
+    format: Format,
+}
+
+impl<C: CSCurve> PresignaturePool<C> {
+    /// Create an empty pool.
+    ///
+    /// The pool will ask for replenishment once fewer than `low_watermark`
+    /// presignatures are available, generating `batch_size` new presignatures at a time.
+    pub fn new(low_watermark: usize, batch_size: usize) -> Result<Self, InitializationError> {
+        if batch_size == 0 {
+            return Err(InitializationError::BadParameters(
+                "batch size must be at least 1".to_string(),
+            ));
+        }
+        Ok(Self {
+            available: VecDeque::new(),
+            used: HashSet::new(),
+            low_watermark,
+            batch_size,
+            replenishing: None,
+            queue_limits: QueueLimits::default(),
+            format: Format::default(),
+        })
+    }
+
+    /// The number of presignatures available.
+    pub fn len(&self) -> usize {
+        self.available.len()
+    }
+
+    /// Whether or not there are no presignatures available.
+    pub fn is_empty(&self) -> bool {
+        self.available.is_empty()
+    }
+
+    /// The identifiers of the presignatures available, from oldest to newest.
+    pub fn ids(&self) -> impl Iterator<Item = PresignatureId> + '_ {
+        self.available.iter().map(|(id, _)| *id)
+    }
+
+    /// Whether or not a replenishment is currently running.
+    pub fn is_replenishing(&self) -> bool {
+        self.replenishing.is_some()
+    }
+
+    /// Whether or not the pool is below its watermark, with no replenishment running.
+    pub fn needs_replenishment(&self) -> bool {
+        self.replenishing.is_none() && self.available.len() < self.low_watermark
+    }
+
+    /// Start a protocol generating new presignatures.
+    ///
+    /// The function passed here is given the number of presignatures to create,
+    /// and should create the protocol doing so, for example with [`presign_many`](crate::presign_many).
+    /// The pool then needs to be poked, and passed messages, until the protocol is done,
+    /// at which point the new presignatures are added to the pool.
+    ///
+    /// Only one replenishment can run at a time.
+    pub fn replenish<P>(
+        &mut self,
+        make: impl FnOnce(usize) -> Result<P, InitializationError>,
+    ) -> Result<(), InitializationError>
+    where
+        P: Protocol<Output = Vec<PresignOutput<C>>> + 'static,
+    {
+        if self.replenishing.is_some() {
+            return Err(InitializationError::BadParameters(
+                "a replenishment is already running".to_string(),
+            ));
+        }
+        let mut protocol = make(self.batch_size)?;
+        protocol.set_queue_limits(self.queue_limits);
+        protocol.set_format(self.format);
+        self.replenishing = Some(Box::new(protocol));
+        Ok(())
+    }
+
+    /// Add a presignature to the pool, returning its identifier.
+    ///
+    /// This fails if that presignature was already used, or is already in the pool.
+    pub fn insert(&mut self, presignature: PresignOutput<C>) -> Result<PresignatureId, PoolError> {
+        let id = PresignatureId::of(&presignature);
+        if self.used.contains(&id) {
+            return Err(PoolError::AlreadyUsed(id));
+        }
+        if self.available.iter().any(|(id_j, _)| *id_j == id) {
+            return Err(PoolError::Duplicate(id));
+        }
+        self.available.push_back((id, presignature));
+        Ok(id)
+    }
+
+    /// Take a specific presignature out of the pool, marking it as used.
+    pub fn take(&mut self, id: PresignatureId) -> Result<PresignOutput<C>, PoolError> {
+        if self.used.contains(&id) {
+            return Err(PoolError::AlreadyUsed(id));
+        }
+        let i = self
+            .available
+            .iter()
+            .position(|(id_j, _)| *id_j == id)
+            .ok_or(PoolError::Unknown(id))?;
+        self.used.insert(id);
+        Ok(self
+            .available
+            .remove(i)
+            .expect("index should be in bounds")
+            .1)
+    }
+
+    /// Take the oldest presignature out of the pool, marking it as used.
+    pub fn take_next(&mut self) -> Option<(PresignatureId, PresignOutput<C>)> {
+        let (id, presignature) = self.available.pop_front()?;
+        self.used.insert(id);
+        Some((id, presignature))
+    }
+}
+
+/// Running the pool as a protocol drives the current replenishment.
+///
+/// Once a replenishment completes, this returns the number of presignatures added,
+/// after which the pool can keep being used, and replenished again.
+/// If no replenishment is running, this just waits.
+impl<C: CSCurve> Protocol for PresignaturePool<C> {
+    type Output = usize;
+
+    fn poke(&mut self) -> Result<Action<Self::Output>, ProtocolError> {
+        let protocol = match self.replenishing.as_mut() {
+            Some(protocol) => protocol,
+            None => return Ok(Action::Wait),
+        };
+        let action = match protocol.poke() {
+            Ok(action) => action,
+            Err(e) => {
+                self.replenishing = None;
+                return Err(e);
+            }
+        };
+        Ok(match action {
+            Action::Wait => Action::Wait,
+            Action::SendMany(data) => Action::SendMany(data),
+            Action::SendPrivate(to, data) => Action::SendPrivate(to, data),
+            Action::Return(presignatures) => {
+                self.replenishing = None;
+                let mut added = 0;
+                for presignature in presignatures {
+                    // A fresh presignature can only collide with an existing one with negligible probability.
+                    if self.insert(presignature).is_ok() {
+                        added += 1;
+                    }
+                }
+                Action::Return(added)
+            }
+        })
+    }
+
+    fn message(&mut self, from: Participant, data: MessageData) {
+        if let Some(protocol) = self.replenishing.as_mut() {
+            protocol.message(from, data);
+        }
+    }
+
+    /// Cancel the current replenishment, if any.
+    ///
+    /// The pool itself can keep being used, and replenished again.
+    fn cancel(&mut self) {
+        if let Some(mut protocol) = self.replenishing.take() {
+            protocol.cancel();
+        }
+    }
+
+    /// Set the limits for the current replenishment, and for all of the later ones.
+    fn set_queue_limits(&mut self, limits: QueueLimits) {
+        self.queue_limits = limits;
+        if let Some(protocol) = self.replenishing.as_mut() {
+            protocol.set_queue_limits(limits);
+        }
+    }
+
+    /// Set the format of all of the later replenishments.
+    ///
+    /// The current replenishment, if any, has already started, and keeps its format.
+    fn set_format(&mut self, format: Format) {
+        self.format = format;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use k256::{ProjectivePoint, Secp256k1};
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::{
+        math::Polynomial, participants::ParticipantList, presign_many, triples, KeygenOutput,
+        PresignArguments,
+    };
+
+    /// Run a replenishment on each pool, until they all finish.
+    fn drive(pools: &mut [(Participant, PresignaturePool<Secp256k1>)]) -> Vec<usize> {
+        let mut out = vec![None; pools.len()];
+        while out.iter().any(Option::is_none) {
+            for i in 0..pools.len() {
+                if out[i].is_some() {
+                    continue;
+                }
+                loop {
+                    let from = pools[i].0;
+                    match pools[i].1.poke().unwrap() {
+                        Action::Wait => break,
+                        Action::SendMany(data) => {
+                            for (j, (_, pool)) in pools.iter_mut().enumerate() {
+                                if j != i {
+                                    pool.message(from, data.clone());
+                                }
+                            }
+                        }
+                        Action::SendPrivate(to, data) => {
+                            let j = pools.iter().position(|(p, _)| *p == to).unwrap();
+                            pools[j].1.message(from, data);
+                        }
+                        Action::Return(added) => {
+                            out[i] = Some(added);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        out.into_iter().map(Option::unwrap).collect()
+    }
+
+    #[test]
+    fn test_pool() {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let big_x = (ProjectivePoint::GENERATOR * f.evaluate_zero()).to_affine();
+        let batch_size = 3;
+
+        let triples: Vec<_> = (0..2 * batch_size)
+            .map(|_| triples::deal(&mut OsRng, &participants, threshold))
+            .collect();
+
+        let mut pools: Vec<_> = participants
+            .iter()
+            .map(|p| {
+                (
+                    *p,
+                    PresignaturePool::<Secp256k1>::new(2, batch_size).unwrap(),
+                )
+            })
+            .collect();
+        for (i, (p, pool)) in pools.iter_mut().enumerate() {
+            assert!(pool.needs_replenishment());
+            let participants = participants.clone();
+            let keygen_out = KeygenOutput {
+                private_share: f.evaluate(&p.scalar::<Secp256k1>()),
+                public_key: big_x,
+            };
+            let triples = triples.clone();
+            pool.replenish(|n| {
+                let args = triples
+                    .chunks(2)
+                    .take(n)
+                    .map(|pair| PresignArguments {
+                        triple0: (pair[0].1[i].clone(), pair[0].0.clone()),
+                        triple1: (pair[1].1[i].clone(), pair[1].0.clone()),
+                        keygen_out: keygen_out.clone(),
+                        threshold,
+                    })
+                    .collect();
+                presign_many(&participants, *p, &participants, *p, args)
+            })
+            .unwrap();
+            assert!(!pool.needs_replenishment());
+        }
+
+        assert_eq!(drive(&mut pools), vec![batch_size; 3]);
+        let ids: Vec<_> = pools[0].1.ids().collect();
+        assert!(pools
+            .iter()
+            .all(|(_, pool)| pool.ids().eq(ids.iter().copied())));
+
+        // The first participant picks a presignature, and another one follows.
+        let (id, presig0) = pools[0].1.take_next().unwrap();
+        let presig1 = pools[1].1.take(id).unwrap();
+        let p_list = ParticipantList::new(&participants[..2]).unwrap();
+        let k = p_list.lagrange::<Secp256k1>(participants[0]) * presig0.k
+            + p_list.lagrange::<Secp256k1>(participants[1]) * presig1.k;
+        assert_eq!(
+            (ProjectivePoint::GENERATOR * k.invert().unwrap()).to_affine(),
+            presig0.big_r
+        );
+
+        // Neither can be used again, or put back in the pool.
+        assert_eq!(pools[1].1.take(id).unwrap_err(), PoolError::AlreadyUsed(id));
+        assert_eq!(
+            pools[0].1.insert(presig0).unwrap_err(),
+            PoolError::AlreadyUsed(id)
+        );
+        assert_eq!(pools[0].1.len(), batch_size - 1);
+        assert!(!pools[0].1.needs_replenishment());
+
+        pools[0].1.take_next().unwrap();
+        assert!(pools[0].1.needs_replenishment());
+    }
+
+    #[test]
+    fn test_pool_duplicate() {
+        let presignature = PresignOutput::<Secp256k1> {
+            big_r: ProjectivePoint::GENERATOR.to_affine(),
+            k: k256::Scalar::ONE,
+            sigma: k256::Scalar::ONE,
+            k_inv: k256::Scalar::ONE,
+        };
+        let mut pool = PresignaturePool::new(0, 1).unwrap();
+        let id = pool.insert(presignature.clone()).unwrap();
+        assert_eq!(
+            pool.insert(presignature).unwrap_err(),
+            PoolError::Duplicate(id)
+        );
+        assert_eq!(pool.len(), 1);
+    }
+}
@@ -1,21 +1,23 @@
 use elliptic_curve::{Field, Group, ScalarPrimitive};
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use crate::compat::CSCurve;
-use crate::participants::ParticipantCounter;
 use crate::protocol::internal::{make_protocol, Context, SharedChannel};
 use crate::protocol::{InitializationError, Protocol};
-use crate::triples::{TriplePub, TripleShare};
+use crate::triples::{TripleId, TriplePub, TripleShare, TripleSource};
+use crate::vault::ShareVault;
 use crate::KeygenOutput;
 use crate::{
-    participants::ParticipantList,
-    protocol::{Participant, ProtocolError},
+    participants::{ParticipantCounter, ParticipantList},
+    protocol::{Fault, Participant, ProtocolError},
 };
 
 /// The output of the presigning protocol.
 ///
 /// This output is basically all the parts of the signature that we can perform
 /// without knowing the message.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PresignOutput<C: CSCurve> {
     /// The public nonce commitment.
     pub big_r: C::AffinePoint,
@@ -23,198 +25,575 @@ pub struct PresignOutput<C: CSCurve> {
     pub k: C::Scalar,
     /// Our share of the sigma value.
     pub sigma: C::Scalar,
+    /// Our share of the inverse of the nonce value, whose commitment is `big_r`.
+    ///
+    /// This is only needed for adaptor signatures.
+    pub k_inv: C::Scalar,
+}
+
+impl<C: CSCurve> Zeroize for PresignOutput<C> {
+    fn zeroize(&mut self) {
+        self.k.zeroize();
+        self.sigma.zeroize();
+        self.k_inv.zeroize();
+    }
+}
+
+impl<C: CSCurve> Drop for PresignOutput<C> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<C: CSCurve> ZeroizeOnDrop for PresignOutput<C> {}
+
+impl<C: CSCurve> PresignOutput<C> {
+    /// Bind this presignature, so that it can only ever be used as allowed by a binding.
+    ///
+    /// This can't be undone: the resulting presignature can only be used with
+    /// [`sign_bound`](crate::sign_bound), or [`signature_share_bound`](crate::signature_share_bound).
+    pub fn bind(self, binding: PresignBinding<C>) -> BoundPresignOutput<C> {
+        BoundPresignOutput {
+            presignature: self,
+            binding,
+        }
+    }
+}
+
+/// What a presignature is bound to, restricting what it can be used to sign.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresignBinding<C: CSCurve> {
+    /// The presignature can only sign this message hash.
+    Message(C::Scalar),
+    /// The presignature can only sign messages under the policy with this hash.
+    ///
+    /// What a policy means is up to the caller, which needs to pass in the hash of the
+    /// policy the message was authorized under, when signing.
+    Policy([u8; 32]),
+}
+
+/// A presignature, bound to a specific message, or policy.
+///
+/// Each participant enforces the binding of its own share, so as long as one
+/// of the signers is honest, a bound presignature can't sign anything else.
+#[derive(Debug, Clone)]
+pub struct BoundPresignOutput<C: CSCurve> {
+    presignature: PresignOutput<C>,
+    binding: PresignBinding<C>,
+}
+
+impl<C: CSCurve> BoundPresignOutput<C> {
+    /// What this presignature is bound to.
+    pub fn binding(&self) -> &PresignBinding<C> {
+        &self.binding
+    }
+
+    /// The public nonce commitment.
+    pub fn big_r(&self) -> C::AffinePoint {
+        self.presignature.big_r
+    }
+
+    /// Release the presignature, if the binding allows signing a message, under a policy.
+    pub(crate) fn unbind(
+        self,
+        msg_hash: &C::Scalar,
+        policy: Option<&[u8; 32]>,
+    ) -> Result<PresignOutput<C>, InitializationError> {
+        match &self.binding {
+            PresignBinding::Message(bound) if bound != msg_hash => {
+                Err(InitializationError::BadParameters(
+                    "presignature is bound to another message".to_string(),
+                ))
+            }
+            PresignBinding::Policy(bound) if Some(bound) != policy => {
+                Err(InitializationError::BadParameters(
+                    "presignature is bound to another policy".to_string(),
+                ))
+            }
+            _ => Ok(self.presignature),
+        }
+    }
 }
 
 /// The arguments needed to create a presignature.
+///
+/// Our share of the secret key can be kept in any [`ShareVault`], instead
+/// of the output of key generation.
 #[derive(Debug, Clone)]
-pub struct PresignArguments<C: CSCurve> {
+pub struct PresignArguments<C: CSCurve, K = KeygenOutput<C>> {
     /// The first triple's public information, and our share.
     pub triple0: (TripleShare<C>, TriplePub<C>),
     /// Ditto, for the second triple.
     pub triple1: (TripleShare<C>, TriplePub<C>),
     /// The output of key generation, i.e. our share of the secret key, and the public key.
-    pub keygen_out: KeygenOutput<C>,
+    pub keygen_out: K,
     /// The desired threshold for the presignature, which must match the original threshold
     pub threshold: usize,
 }
 
-async fn do_presign<C: CSCurve>(
+async fn do_presign_many<C: CSCurve, K: ShareVault<C>>(
     mut chan: SharedChannel,
     participants: ParticipantList,
     me: Participant,
     bt_participants: ParticipantList,
     bt_id: Participant,
-    args: PresignArguments<C>,
-) -> Result<PresignOutput<C>, ProtocolError> {
-    // Spec 1.2 + 1.3
-    let big_k: C::ProjectivePoint = args.triple0.1.big_a.into();    
-    
-    let big_d = args.triple0.1.big_b;
-    let big_kd = args.triple0.1.big_c;
-
-    let big_x: C::ProjectivePoint = args.keygen_out.public_key.into();
-
-    let big_a: C::ProjectivePoint = args.triple1.1.big_a.into();
-    let big_b: C::ProjectivePoint = args.triple1.1.big_b.into();
-
+    args: Vec<PresignArguments<C, K>>,
+) -> Result<Vec<PresignOutput<C>>, ProtocolError> {
+    let vault_error = |e: K::Error| ProtocolError::Other(Box::new(e));
     let sk_lambda = participants.lagrange::<C>(me);
     let bt_lambda = bt_participants.lagrange::<C>(bt_id);
 
-    let k_i = args.triple0.0.a;
-    let k_prime_i = bt_lambda * k_i;
-    let kd_i: C::Scalar = bt_lambda * args.triple0.0.c;  // if this is zero, then the broadcast kdi is also zero.
-
-    let a_i = args.triple1.0.a;
-    let b_i = args.triple1.0.b;
-    let c_i = args.triple1.0.c;
-    let a_prime_i = bt_lambda * a_i;
-    let b_prime_i = bt_lambda * b_i;
-
-    let x_prime_i = sk_lambda * args.keygen_out.private_share;
+    // Spec 1.2 + 1.3, and 1.9, for each presignature.
+    let mut kd_is = Vec::with_capacity(args.len());
+    let mut ka_xb_is = Vec::with_capacity(args.len());
+    for args in &args {
+        let k_prime_i = bt_lambda * args.triple0.0.a;
+        let kd_i: C::Scalar = bt_lambda * args.triple0.0.c; // if this is zero, then the broadcast kdi is also zero.
+        let a_prime_i = bt_lambda * args.triple1.0.a;
+        let b_prime_i = bt_lambda * args.triple1.0.b;
+
+        let ka_i: C::Scalar = k_prime_i + a_prime_i;
+        // This is x_prime_i + b_prime_i, with x_prime_i = sk_lambda * x_i.
+        let xb_i: C::Scalar = args
+            .keygen_out
+            .mul_add(&sk_lambda, &b_prime_i)
+            .map_err(vault_error)?;
+
+        let kd_i_primitive: ScalarPrimitive<C> = kd_i.into();
+        let ka_xb_i: (ScalarPrimitive<C>, ScalarPrimitive<C>) = (ka_i.into(), xb_i.into());
+        kd_is.push(kd_i_primitive);
+        ka_xb_is.push(ka_xb_i);
+    }
 
     // Spec 1.4
     let wait0 = chan.next_waitpoint();
-    {
-        let kd_i: ScalarPrimitive<C> = kd_i.into();
-        chan.send_many(wait0, &kd_i).await;
-    }
-
-    // Spec 1.9
-    let ka_i: C::Scalar = k_prime_i + a_prime_i;
-    let xb_i: C::Scalar = x_prime_i + b_prime_i;
+    chan.send_many(wait0, &kd_is).await;
 
     // Spec 1.10
     let wait1 = chan.next_waitpoint();
-    {
-        let ka_i: ScalarPrimitive<C> = ka_i.into();
-        let xb_i: ScalarPrimitive<C> = xb_i.into();
-        chan.send_many(wait1, &(ka_i, xb_i)).await;
-    }
+    chan.send_many(wait1, &ka_xb_is).await;
 
     // Spec 2.1 and 2.2
-    let mut kd = kd_i;
-    let mut seen = ParticipantCounter::new(&participants);
-    seen.put(me);
-    while !seen.full() {
-        let (from, kd_j): (_, ScalarPrimitive<C>) = chan.recv(wait0).await?;
+    let kd_j = chan.recv_echoed(&participants, me, wait0, kd_is).await?;
+    let mut kd: Vec<C::Scalar> = kd_j[me].iter().map(|kd_i| C::Scalar::from(*kd_i)).collect();
+    for from in participants.others(me) {
+        let kd_j = &kd_j[from];
+        if kd_j.len() != args.len() {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait0,
+                fault: Fault::InvalidMessage("wrong number of shares of kd"),
+            });
+        }
+        for (kd, kd_j) in kd.iter_mut().zip(kd_j) {
+            if kd_j.is_zero().into() {
+                return Err(ProtocolError::Malicious {
+                    participant: from,
+                    round: wait0,
+                    fault: Fault::InvalidMessage(
+                        "zero share of kd, indicating a triple wasn't available",
+                    ),
+                });
+            }
+            *kd += C::Scalar::from(*kd_j);
+        }
+    }
 
-        if kd_j.is_zero().into() {
-            return Err(ProtocolError::AssertionFailed(
-                "Received zero share of kd, indicating a triple wasn't available.".to_string(),
-            ));
+    // Spec 2.3
+    for (args, kd) in args.iter().zip(&kd) {
+        if args.triple0.1.big_c != (C::ProjectivePoint::generator() * kd).into() {
+            return Err(ProtocolError::Failed {
+                round: wait0,
+                fault: Fault::BadShare,
+            });
         }
+    }
 
-        if !seen.put(from) {
-            continue;
+    // Spec 2.4 and 2.5
+    let ka_xb_j = chan.recv_echoed(&participants, me, wait1, ka_xb_is).await?;
+    let mut ka_xb: Vec<(C::Scalar, C::Scalar)> = ka_xb_j[me]
+        .iter()
+        .map(|(ka_i, xb_i)| (C::Scalar::from(*ka_i), C::Scalar::from(*xb_i)))
+        .collect();
+    for from in participants.others(me) {
+        let ka_xb_j = &ka_xb_j[from];
+        if ka_xb_j.len() != args.len() {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait1,
+                fault: Fault::InvalidMessage("wrong number of shares of ka and xb"),
+            });
+        }
+        for ((ka, xb), (ka_j, xb_j)) in ka_xb.iter_mut().zip(ka_xb_j) {
+            *ka += C::Scalar::from(*ka_j);
+            *xb += C::Scalar::from(*xb_j);
         }
-        kd += C::Scalar::from(kd_j);
     }
 
-    // Spec 2.3
-    if big_kd != (C::ProjectivePoint::generator() * kd).into() {
-        return Err(ProtocolError::AssertionFailed(
-            "received incorrect shares of kd".to_string(),
+    let lambda_diff = bt_lambda * sk_lambda.invert().expect("to invert sk_lambda");
+    let mut out = Vec::with_capacity(args.len());
+    for ((args, kd), (ka, xb)) in args.into_iter().zip(kd).zip(ka_xb) {
+        let big_k: C::ProjectivePoint = args.triple0.1.big_a.into();
+        let big_d = args.triple0.1.big_b;
+        let big_x: C::ProjectivePoint = args.keygen_out.public_key().into();
+        let big_a: C::ProjectivePoint = args.triple1.1.big_a.into();
+        let big_b: C::ProjectivePoint = args.triple1.1.big_b.into();
+
+        // Spec 2.6
+        if (C::ProjectivePoint::generator() * ka != big_k + big_a)
+            || (C::ProjectivePoint::generator() * xb != big_x + big_b)
+        {
+            return Err(ProtocolError::Failed {
+                round: wait1,
+                fault: Fault::BadShare,
+            });
+        }
+
+        // Spec 2.7
+        let kd_inv: Option<C::Scalar> = kd.invert().into();
+        let kd_inv = kd_inv.ok_or_else(|| ProtocolError::Failed {
+            round: wait0,
+            fault: Fault::InvalidResult("kd is zero"),
+        })?;
+        let big_r = (C::ProjectivePoint::from(big_d) * kd_inv).into();
+
+        // Spec 2.8
+        let a_i = args.triple1.0.a;
+        let c_i = args.triple1.0.c;
+        let sigma_i = args
+            .keygen_out
+            .mul_add(&ka, &(-(xb * a_i - c_i) * lambda_diff))
+            .map_err(vault_error)?;
+
+        // Since R = D / kd, our share of d, divided by kd, is a share of the inverse of k.
+        let k_inv_i = args.triple0.0.b * lambda_diff * kd_inv;
+
+        out.push(PresignOutput {
+            big_r,
+            k: args.triple0.0.a * lambda_diff,
+            sigma: sigma_i,
+            k_inv: k_inv_i,
+        });
+    }
+
+    Ok(out)
+}
+
+/// The presignature protocol.
+///
+/// This is the first phase of performing a signature, in which we perform
+/// all the work we can do without yet knowing the message to be signed.
+///
+/// This work does depend on the private key though, and it's crucial
+/// that a presignature is never used.
+pub fn presign<C: CSCurve, K: ShareVault<C> + Send + 'static>(
+    participants: &[Participant],
+    me: Participant,
+    bt_participants: &[Participant],
+    bt_id: Participant,
+    args: PresignArguments<C, K>,
+) -> Result<impl Protocol<Output = PresignOutput<C>>, InitializationError> {
+    let args = vec![args];
+    let (participants, bt_participants) = validate(participants, bt_participants, &args)?;
+
+    let ctx = Context::new();
+    let fut = do_presign_many(
+        ctx.shared_channel(),
+        participants,
+        me,
+        bt_participants,
+        bt_id,
+        args,
+    );
+    let fut = async move {
+        let mut out = fut.await?;
+        Ok(out.swap_remove(0))
+    };
+    Ok(make_protocol(ctx, fut))
+}
+
+/// As [`presign`], but producing a presignature bound to a message, or to a policy.
+///
+/// The binding isn't part of the protocol, and every participant can pick its own,
+/// although they should agree, for the presignature to be usable.
+pub fn presign_bound<C: CSCurve, K: ShareVault<C> + Send + 'static>(
+    participants: &[Participant],
+    me: Participant,
+    bt_participants: &[Participant],
+    bt_id: Participant,
+    args: PresignArguments<C, K>,
+    binding: PresignBinding<C>,
+) -> Result<impl Protocol<Output = BoundPresignOutput<C>>, InitializationError> {
+    let args = vec![args];
+    let (participants, bt_participants) = validate(participants, bt_participants, &args)?;
+
+    let ctx = Context::new();
+    let fut = do_presign_many(
+        ctx.shared_channel(),
+        participants,
+        me,
+        bt_participants,
+        bt_id,
+        args,
+    );
+    let fut = async move {
+        let mut out = fut.await?;
+        Ok(out.swap_remove(0).bind(binding))
+    };
+    Ok(make_protocol(ctx, fut))
+}
+
+/// As [`presign`], but creating many presignatures at once.
+///
+/// Each set of arguments produces one presignature, in the same order.
+/// This takes as many rounds as creating a single presignature, so it's
+/// much faster than running [`presign`] once per presignature.
+///
+/// Every participant needs to pass the arguments for the same triples, in the same order.
+pub fn presign_many<C: CSCurve, K: ShareVault<C> + Send + 'static>(
+    participants: &[Participant],
+    me: Participant,
+    bt_participants: &[Participant],
+    bt_id: Participant,
+    args: Vec<PresignArguments<C, K>>,
+) -> Result<impl Protocol<Output = Vec<PresignOutput<C>>>, InitializationError> {
+    if args.is_empty() {
+        return Err(InitializationError::BadParameters(
+            "must create at least one presignature".to_string(),
         ));
     }
+    let (participants, bt_participants) = validate(participants, bt_participants, &args)?;
 
-    // Spec 2.4 and 2.5
-    let mut ka = ka_i;
-    let mut xb = xb_i;
-    seen.clear();
+    let ctx = Context::new();
+    let fut = do_presign_many(
+        ctx.shared_channel(),
+        participants,
+        me,
+        bt_participants,
+        bt_id,
+        args,
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
+/// As [`presign`], but taking the two triples from a [`TripleSource`].
+///
+/// Every participant needs to pass the identifiers of the same triples, in the same order.
+/// The triples are taken before anything else happens, so they're gone
+/// from the source even if the protocol fails.
+#[allow(clippy::too_many_arguments)]
+pub fn presign_from_source<C: CSCurve, K: ShareVault<C> + Send + 'static>(
+    participants: &[Participant],
+    me: Participant,
+    bt_participants: &[Participant],
+    bt_id: Participant,
+    source: &mut impl TripleSource<C>,
+    triples: [TripleId; 2],
+    keygen_out: K,
+    threshold: usize,
+) -> Result<impl Protocol<Output = PresignOutput<C>>, InitializationError> {
+    if triples[0] == triples[1] {
+        return Err(InitializationError::BadParameters(
+            "cannot use the same triple twice".to_string(),
+        ));
+    }
+    let mut take = |id| {
+        source
+            .take(id)
+            .map_err(|e| InitializationError::BadParameters(format!("cannot take triple: {}", e)))
+    };
+    let triple0 = take(triples[0])?;
+    let triple1 = take(triples[1])?;
+    presign(
+        participants,
+        me,
+        bt_participants,
+        bt_id,
+        PresignArguments {
+            triple0,
+            triple1,
+            keygen_out,
+            threshold,
+        },
+    )
+}
+
+async fn do_presign_tolerant<C: CSCurve, K: ShareVault<C>>(
+    mut chan: SharedChannel,
+    participants: ParticipantList,
+    me: Participant,
+    args: PresignArguments<C, K>,
+) -> Result<PresignOutput<C>, ProtocolError> {
+    let vault_error = |e: K::Error| ProtocolError::Other(Box::new(e));
+
+    // Without any Lagrange coefficients, these are shares of kd, ka, and xb,
+    // which any threshold of the participants can combine.
+    let kd_i = args.triple0.0.c;
+    let ka_i = args.triple0.0.a + args.triple1.0.a;
+    let xb_i = args
+        .keygen_out
+        .mul_add(&C::Scalar::ONE, &args.triple1.0.b)
+        .map_err(vault_error)?;
+
+    let wait0 = chan.next_waitpoint();
+    {
+        let shares: (ScalarPrimitive<C>, ScalarPrimitive<C>, ScalarPrimitive<C>) =
+            (kd_i.into(), ka_i.into(), xb_i.into());
+        chan.send_many(wait0, &shares).await;
+    }
+
+    // We combine the shares of the first participants to answer, whoever they are.
+    let mut shares = vec![(me, (kd_i, ka_i, xb_i))];
+    let mut seen = ParticipantCounter::new(&participants);
     seen.put(me);
-    while !seen.full() {
-        let (from, (ka_j, xb_j)): (_, (ScalarPrimitive<C>, ScalarPrimitive<C>)) =
-            chan.recv(wait1).await?;
+    while shares.len() < args.threshold {
+        let (from, (kd_j, ka_j, xb_j)): (
+            _,
+            (ScalarPrimitive<C>, ScalarPrimitive<C>, ScalarPrimitive<C>),
+        ) = chan.recv(wait0).await?;
         if !seen.put(from) {
             continue;
         }
-        ka += C::Scalar::from(ka_j);
-        xb += C::Scalar::from(xb_j);
+        if kd_j.is_zero().into() {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait0,
+                fault: Fault::InvalidMessage(
+                    "zero share of kd, indicating a triple wasn't available",
+                ),
+            });
+        }
+        shares.push((from, (kd_j.into(), ka_j.into(), xb_j.into())));
     }
 
-    // Spec 2.6
-    if (C::ProjectivePoint::generator() * ka != big_k + big_a)
+    let responders: Vec<_> = shares.iter().map(|(p, _)| *p).collect();
+    // Unwrapping is fine, since the counter only lets each participant in once.
+    let responders = ParticipantList::new(&responders).unwrap();
+    let mut kd = C::Scalar::ZERO;
+    let mut ka = C::Scalar::ZERO;
+    let mut xb = C::Scalar::ZERO;
+    for (p, (kd_j, ka_j, xb_j)) in shares {
+        let lambda = responders.lagrange::<C>(p);
+        kd += lambda * kd_j;
+        ka += lambda * ka_j;
+        xb += lambda * xb_j;
+    }
+
+    let big_k: C::ProjectivePoint = args.triple0.1.big_a.into();
+    let big_d = args.triple0.1.big_b;
+    let big_x: C::ProjectivePoint = args.keygen_out.public_key().into();
+    let big_a: C::ProjectivePoint = args.triple1.1.big_a.into();
+    let big_b: C::ProjectivePoint = args.triple1.1.big_b.into();
+    if (C::ProjectivePoint::generator() * kd != args.triple0.1.big_c.into())
+        || (C::ProjectivePoint::generator() * ka != big_k + big_a)
         || (C::ProjectivePoint::generator() * xb != big_x + big_b)
     {
-        return Err(ProtocolError::AssertionFailed(
-            "received incorrect shares of additive triple phase.".to_string(),
-        ));
+        return Err(ProtocolError::Failed {
+            round: wait0,
+            fault: Fault::BadShare,
+        });
     }
 
-    // Spec 2.7
     let kd_inv: Option<C::Scalar> = kd.invert().into();
-    let kd_inv =
-        kd_inv.ok_or_else(|| ProtocolError::AssertionFailed("failed to invert kd".to_string()))?;
+    let kd_inv = kd_inv.ok_or_else(|| ProtocolError::Failed {
+        round: wait0,
+        fault: Fault::InvalidResult("kd is zero"),
+    })?;
     let big_r = (C::ProjectivePoint::from(big_d) * kd_inv).into();
 
-    // Spec 2.8
-    let lambda_diff = bt_lambda * sk_lambda.invert().expect("to invert sk_lambda");
-    let sigma_i = ka * args.keygen_out.private_share - (xb * a_i - c_i) * lambda_diff;
+    let a_i = args.triple1.0.a;
+    let c_i = args.triple1.0.c;
+    let sigma_i = args
+        .keygen_out
+        .mul_add(&ka, &(c_i - xb * a_i))
+        .map_err(vault_error)?;
 
     Ok(PresignOutput {
         big_r,
-        k: k_i * lambda_diff,
+        k: args.triple0.0.a,
         sigma: sigma_i,
+        k_inv: args.triple0.0.b * kd_inv,
     })
 }
 
-/// The presignature protocol.
+/// As [`presign`], but tolerating participants which drop out midway.
 ///
-/// This is the first phase of performing a signature, in which we perform
-/// all the work we can do without yet knowing the message to be signed.
+/// The protocol is started with more participants than the threshold, and every
+/// participant finishes as soon as it hears from a threshold of them, including itself.
+/// Since the remaining participants aren't waited on, they can go offline without
+/// stalling anyone else. The resulting presignature can then be used by any
+/// threshold of the participants, with [`sign`](crate::sign), or
+/// [`sign_tolerant`](crate::sign_tolerant).
 ///
-/// This work does depend on the private key though, and it's crucial
-/// that a presignature is never used.
-pub fn presign<C: CSCurve>(
+/// Unlike with [`presign`], the triples need to have been generated by
+/// participants with the same identities, so there's no separate `bt_participants`.
+/// The broadcast isn't echoed, since whichever shares a participant ends up combining
+/// get checked against the triples and the public key, so inconsistent shares make it fail.
+pub fn presign_tolerant<C: CSCurve, K: ShareVault<C> + Send + 'static>(
     participants: &[Participant],
     me: Participant,
-    bt_participants: &[Participant],
-    bt_id: Participant,
-    args: PresignArguments<C>,
+    args: PresignArguments<C, K>,
 ) -> Result<impl Protocol<Output = PresignOutput<C>>, InitializationError> {
+    let (participants, _) = validate(participants, participants, std::slice::from_ref(&args))?;
+    if !participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain this participant".to_string(),
+        ));
+    }
+    if args.threshold == 0 {
+        return Err(InitializationError::BadParameters(
+            "threshold must be at least 1".to_string(),
+        ));
+    }
+
+    let ctx = Context::new();
+    let fut = do_presign_tolerant(ctx.shared_channel(), participants, me, args);
+    Ok(make_protocol(ctx, fut))
+}
+
+/// Check the arguments for presigning, returning the participant lists.
+fn validate<C: CSCurve, K>(
+    participants: &[Participant],
+    bt_participants: &[Participant],
+    args: &[PresignArguments<C, K>],
+) -> Result<(ParticipantList, ParticipantList), InitializationError> {
     if participants.len() < 2 {
         return Err(InitializationError::BadParameters(format!(
             "participant count cannot be < 2, found: {}",
             participants.len()
         )));
     };
-    // Spec 1.1
-    if args.threshold > participants.len() {
-        return Err(InitializationError::BadParameters(
-            "threshold must be <= participant count".to_string(),
-        ));
-    }
-    // NOTE: We omit the check that the new participant set was present for
-    // the triple generation, because presumably they need to have been present
-    // in order to have shares.
+    for args in args {
+        // Spec 1.1
+        if args.threshold > participants.len() {
+            return Err(InitializationError::BadParameters(
+                "threshold must be <= participant count".to_string(),
+            ));
+        }
+        // NOTE: We omit the check that the new participant set was present for
+        // the triple generation, because presumably they need to have been present
+        // in order to have shares.
 
-    // Also check that we have enough participants to reconstruct shares.
-    if args.threshold != args.triple0.1.threshold || args.threshold != args.triple1.1.threshold {
-        return Err(InitializationError::BadParameters(
-            "New threshold must match the threshold of both triples".to_string(),
-        ));
+        // Also check that we have enough participants to reconstruct shares.
+        if args.threshold != args.triple0.1.threshold || args.threshold != args.triple1.1.threshold
+        {
+            return Err(InitializationError::BadParameters(
+                "New threshold must match the threshold of both triples".to_string(),
+            ));
+        }
     }
 
     let participants = ParticipantList::new(participants).ok_or_else(|| {
         InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
     })?;
 
-    let all_bt_ids = ParticipantList::new(bt_participants).ok_or_else(|| {
+    let bt_participants = ParticipantList::new(bt_participants).ok_or_else(|| {
         InitializationError::BadParameters(
             "bt_participants list cannot contain duplicates".to_string(),
         )
     })?;
 
-    let ctx = Context::new();
-    let fut = do_presign(
-        ctx.shared_channel(),
-        participants,
-        me,
-        all_bt_ids,
-        bt_id,
-        args,
-    );
-    Ok(make_protocol(ctx, fut))
+    Ok((participants, bt_participants))
 }
 
 #[cfg(test)]
@@ -224,7 +603,7 @@ mod test {
 
     use crate::{math::Polynomial, protocol::run_protocol, triples};
 
-    use k256::{ProjectivePoint, Secp256k1};
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
 
     #[test]
     fn test_presign() {
@@ -253,8 +632,8 @@ mod test {
         for ((p, triple0), triple1) in participants
             .iter()
             .take(3)
-            .zip(triple0_shares.into_iter())
-            .zip(triple1_shares.into_iter())
+            .zip(triple0_shares)
+            .zip(triple1_shares)
         {
             let protocol = presign(
                 &participants[..3],
@@ -287,8 +666,8 @@ mod test {
         let big_k = result[2].1.big_r;
 
         let participants = vec![result[0].0, result[1].0];
-        let k_shares = vec![result[0].1.k, result[1].1.k];
-        let sigma_shares = vec![result[0].1.sigma, result[1].1.sigma];
+        let k_shares = [result[0].1.k, result[1].1.k];
+        let sigma_shares = [result[0].1.sigma, result[1].1.sigma];
         let p_list = ParticipantList::new(&participants).unwrap();
         let k = p_list.lagrange::<Secp256k1>(participants[0]) * k_shares[0]
             + p_list.lagrange::<Secp256k1>(participants[1]) * k_shares[1];
@@ -296,5 +675,209 @@ mod test {
         let sigma = p_list.lagrange::<Secp256k1>(participants[0]) * sigma_shares[0]
             + p_list.lagrange::<Secp256k1>(participants[1]) * sigma_shares[1];
         assert_eq!(sigma, k * f.evaluate_zero());
+        let k_inv = p_list.lagrange::<Secp256k1>(participants[0]) * result[0].1.k_inv
+            + p_list.lagrange::<Secp256k1>(participants[1]) * result[1].1.k_inv;
+        assert_eq!(k_inv, k.invert().unwrap());
+    }
+
+    #[test]
+    fn test_presign_many() {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let big_x = (ProjectivePoint::GENERATOR * f.evaluate_zero()).to_affine();
+        let count = 3;
+
+        let triples: Vec<_> = (0..2 * count)
+            .map(|_| triples::deal(&mut OsRng, &participants, threshold))
+            .collect();
+
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = Vec<PresignOutput<Secp256k1>>>>,
+        )> = Vec::with_capacity(participants.len());
+
+        for (i, p) in participants.iter().enumerate() {
+            let args = triples
+                .chunks(2)
+                .map(|pair| PresignArguments {
+                    triple0: (pair[0].1[i].clone(), pair[0].0.clone()),
+                    triple1: (pair[1].1[i].clone(), pair[1].0.clone()),
+                    keygen_out: KeygenOutput {
+                        private_share: f.evaluate(&p.scalar::<Secp256k1>()),
+                        public_key: big_x,
+                    },
+                    threshold,
+                })
+                .collect();
+            let protocol = presign_many(&participants, *p, &participants, *p, args).unwrap();
+            protocols.push((*p, Box::new(protocol)));
+        }
+
+        let result = run_protocol(protocols).unwrap();
+        assert_eq!(result.len(), 3);
+
+        let p_list = ParticipantList::new(&[result[0].0, result[1].0]).unwrap();
+        for j in 0..count {
+            let big_r = result[0].1[j].big_r;
+            assert!(result.iter().all(|(_, out)| out[j].big_r == big_r));
+            // Each presignature uses a different nonce.
+            if j > 0 {
+                assert_ne!(result[0].1[j - 1].big_r, big_r);
+            }
+
+            let k = p_list.lagrange::<Secp256k1>(result[0].0) * result[0].1[j].k
+                + p_list.lagrange::<Secp256k1>(result[1].0) * result[1].1[j].k;
+            assert_eq!(ProjectivePoint::GENERATOR * k.invert().unwrap(), big_r);
+            let sigma = p_list.lagrange::<Secp256k1>(result[0].0) * result[0].1[j].sigma
+                + p_list.lagrange::<Secp256k1>(result[1].0) * result[1].1[j].sigma;
+            assert_eq!(sigma, k * f.evaluate_zero());
+        }
+    }
+    #[test]
+    fn test_presign_tolerant() {
+        let participants: Vec<_> = (0..5u32).map(Participant::from).collect();
+        let threshold = 3;
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let big_x = (ProjectivePoint::GENERATOR * f.evaluate_zero()).to_affine();
+
+        let (triple0_pub, triple0_shares) = triples::deal(&mut OsRng, &participants, threshold);
+        let (triple1_pub, triple1_shares) = triples::deal(&mut OsRng, &participants, threshold);
+
+        // The last participant drops out before doing anything.
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = PresignOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        for ((p, triple0), triple1) in participants
+            .iter()
+            .take(4)
+            .zip(triple0_shares)
+            .zip(triple1_shares)
+        {
+            let protocol = presign_tolerant(
+                &participants,
+                *p,
+                PresignArguments {
+                    triple0: (triple0, triple0_pub.clone()),
+                    triple1: (triple1, triple1_pub.clone()),
+                    keygen_out: KeygenOutput {
+                        private_share: f.evaluate(&p.scalar::<Secp256k1>()),
+                        public_key: big_x,
+                    },
+                    threshold,
+                },
+            )
+            .unwrap();
+            protocols.push((*p, Box::new(protocol)));
+        }
+
+        let result = run_protocol(protocols).unwrap();
+        assert_eq!(result.len(), 4);
+        let big_r = result[0].1.big_r;
+        assert!(result.iter().all(|(_, out)| out.big_r == big_r));
+
+        // Any threshold of the participants which finished can use the presignature.
+        for subset in [&result[..3], &result[1..]] {
+            let p_list =
+                ParticipantList::new(&subset.iter().map(|(p, _)| *p).collect::<Vec<_>>()).unwrap();
+            let combine = |share: fn(&PresignOutput<Secp256k1>) -> Scalar| {
+                subset
+                    .iter()
+                    .map(|(p, out)| p_list.lagrange::<Secp256k1>(*p) * share(out))
+                    .fold(Scalar::ZERO, |acc, x| acc + x)
+            };
+            let k = combine(|out| out.k);
+            assert_eq!(ProjectivePoint::GENERATOR * k.invert().unwrap(), big_r);
+            assert_eq!(combine(|out| out.sigma), k * f.evaluate_zero());
+            assert_eq!(combine(|out| out.k_inv), k.invert().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_zeroize() {
+        let mut out = PresignOutput::<Secp256k1> {
+            big_r: ProjectivePoint::GENERATOR.to_affine(),
+            k: Scalar::ONE,
+            sigma: Scalar::ONE,
+            k_inv: Scalar::ONE,
+        };
+        out.zeroize();
+        assert_eq!(out.k, Scalar::ZERO);
+        assert_eq!(out.sigma, Scalar::ZERO);
+        assert_eq!(out.k_inv, Scalar::ZERO);
+    }
+
+    #[test]
+    fn test_presign_from_source() -> Result<(), Box<dyn std::error::Error>> {
+        use rand_core::RngCore;
+
+        use crate::triples::{FileTripleStore, TripleId, TripleStore};
+
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let threshold = 2;
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let big_x = (ProjectivePoint::GENERATOR * f.evaluate_zero()).to_affine();
+        let (triple0_pub, triple0_shares) = triples::deal(&mut OsRng, &participants, threshold);
+        let (triple1_pub, triple1_shares) = triples::deal(&mut OsRng, &participants, threshold);
+        let ids = [TripleId::of(&triple0_pub), TripleId::of(&triple1_pub)];
+
+        let dir = std::env::temp_dir().join(format!("cait-sith-source-{:016x}", OsRng.next_u64()));
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = PresignOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        let mut stores = Vec::new();
+        for ((p, triple0), triple1) in participants.iter().zip(triple0_shares).zip(triple1_shares) {
+            let mut store = FileTripleStore::open(dir.join(format!("{:?}", p)))?;
+            store.save((triple0, triple0_pub.clone()))?;
+            store.save((triple1, triple1_pub.clone()))?;
+            let protocol = presign_from_source(
+                &participants,
+                *p,
+                &participants,
+                *p,
+                &mut store,
+                ids,
+                KeygenOutput {
+                    private_share: f.evaluate(&p.scalar::<Secp256k1>()),
+                    public_key: big_x,
+                },
+                threshold,
+            )?;
+            protocols.push((*p, Box::new(protocol)));
+            stores.push(store);
+        }
+        let result = run_protocol(protocols)?;
+        assert_eq!(result[0].1.big_r, result[1].1.big_r);
+
+        // The triples can't be taken again.
+        for store in &stores {
+            assert!(TripleStore::<Secp256k1>::available(store)?.is_empty());
+        }
+        assert!(presign_from_source(
+            &participants,
+            participants[0],
+            &participants,
+            participants[0],
+            &mut stores[0],
+            ids,
+            KeygenOutput {
+                private_share: f.evaluate(&participants[0].scalar::<Secp256k1>()),
+                public_key: big_x,
+            },
+            threshold,
+        )
+        .is_err());
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
     }
 }
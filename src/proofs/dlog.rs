@@ -1,11 +1,15 @@
+//! Proofs of knowledge of a discrete logarithm, with the Fiat-Shamir transform.
 use elliptic_curve::{Field, Group};
-use magikitten::Transcript;
 use rand_core::CryptoRngCore;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     compat::{CSCurve, SerializablePoint},
-    serde::{deserialize_scalar, encode, serialize_projective_point, serialize_scalar},
+    fiat_shamir::FiatShamir,
+    math::multiscalar_mul,
+    serde::{
+        deserialize_scalar, encode, serialize_projective_point, serialize_scalar, Schema, Wire,
+    },
 };
 
 /// The label we use for hashing the statement.
@@ -20,6 +24,7 @@ const CHALLENGE_LABEL: &[u8] = b"dlog proof challenge";
 /// This statement claims knowledge of the discrete logarithm of some point.
 #[derive(Debug, Clone, Copy, Serialize)]
 pub struct Statement<'a, C: CSCurve> {
+    /// The point `x * G` whose discrete logarithm `x` is known.
     #[serde(serialize_with = "serialize_projective_point::<C, _>")]
     pub public: &'a C::ProjectivePoint,
 }
@@ -36,17 +41,18 @@ impl<'a, C: CSCurve> Statement<'a, C> {
 /// This holds the scalar the prover needs to know.
 #[derive(Clone, Copy)]
 pub struct Witness<'a, C: CSCurve> {
+    /// The discrete logarithm of the public point.
     pub x: &'a C::Scalar,
 }
 
 /// Represents a proof of the statement.
+///
+/// This contains the commitment of the prover, rather than the challenge,
+/// so that many proofs can be verified at once, with [`verify_batch`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct Proof<C: CSCurve> {
-    #[serde(
-        serialize_with = "serialize_scalar::<C, _>",
-        deserialize_with = "deserialize_scalar::<C, _>"
-    )]
-    e: C::Scalar,
+    big_k: SerializablePoint<C>,
     #[serde(
         serialize_with = "serialize_scalar::<C, _>",
         deserialize_with = "deserialize_scalar::<C, _>"
@@ -54,30 +60,40 @@ pub struct Proof<C: CSCurve> {
     s: C::Scalar,
 }
 
+impl<C: CSCurve> Wire for Proof<C> {
+    fn schema() -> Schema {
+        Schema::message([("big_k", 1, Schema::Bytes), ("s", 2, Schema::Bytes)])
+    }
+}
+
+/// Derive the challenge for a proof, given the commitment of the prover.
+fn challenge<C: CSCurve>(
+    transcript: &mut impl FiatShamir,
+    statement: Statement<'_, C>,
+    big_k: &SerializablePoint<C>,
+) -> C::Scalar {
+    transcript.message(STATEMENT_LABEL, &encode(&statement));
+    transcript.message(COMMITMENT_LABEL, &encode(big_k));
+    C::Scalar::random(&mut transcript.challenge(CHALLENGE_LABEL))
+}
+
 /// Prove that a witness satisfies a given statement.
 ///
 /// We need some randomness for the proof, and also a transcript, which is
 /// used for the Fiat-Shamir transform.
 pub fn prove<'a, C: CSCurve>(
     rng: &mut impl CryptoRngCore,
-    transcript: &mut Transcript,
+    transcript: &mut impl FiatShamir,
     statement: Statement<'a, C>,
     witness: Witness<'a, C>,
 ) -> Proof<C> {
-    transcript.message(STATEMENT_LABEL, &encode(&statement));
-
     let k = C::Scalar::random(rng);
-    let big_k = statement.phi(&k);
-
-    transcript.message(
-        COMMITMENT_LABEL,
-        &encode(&SerializablePoint::<C>::from_projective(&big_k)),
-    );
+    let big_k = SerializablePoint::<C>::from_projective(&statement.phi(&k));
 
-    let e = C::Scalar::random(&mut transcript.challenge(CHALLENGE_LABEL));
+    let e = challenge(transcript, statement, &big_k);
 
     let s = k + e * witness.x;
-    Proof { e, s }
+    Proof { big_k, s }
 }
 
 /// Verify that a proof attesting to the validity of some statement.
@@ -85,23 +101,41 @@ pub fn prove<'a, C: CSCurve>(
 /// We use a transcript in order to verify the Fiat-Shamir transformation.
 #[must_use]
 pub fn verify<C: CSCurve>(
-    transcript: &mut Transcript,
+    transcript: &mut impl FiatShamir,
     statement: Statement<'_, C>,
     proof: &Proof<C>,
 ) -> bool {
-    let statement_data = encode(&statement);
-    transcript.message(STATEMENT_LABEL, &statement_data);
-
-    let big_k: C::ProjectivePoint = statement.phi(&proof.s) - *statement.public * proof.e;
+    let e = challenge(transcript, statement, &proof.big_k);
 
-    transcript.message(
-        COMMITMENT_LABEL,
-        &encode(&SerializablePoint::<C>::from_projective(&big_k)),
+    let big_k = multiscalar_mul::<C>(
+        &[proof.s, -e],
+        &[C::ProjectivePoint::generator(), *statement.public],
     );
+    big_k == proof.big_k.to_projective()
+}
 
-    let e = C::Scalar::random(&mut transcript.challenge(CHALLENGE_LABEL));
-
-    e == proof.e
+/// Verify many proofs at once, each with its own transcript.
+///
+/// This checks a random linear combination of the verification equations,
+/// which is cheaper than checking each of them. If this fails, at least one
+/// of the proofs is invalid, and [`verify`] can be used to find out which.
+#[must_use]
+pub fn verify_batch<'a, C: CSCurve, T: FiatShamir>(
+    rng: &mut impl CryptoRngCore,
+    proofs: impl IntoIterator<Item = (T, Statement<'a, C>, &'a Proof<C>)>,
+) -> bool {
+    // We check that sum_i w_i (s_i G - K_i - e_i X_i) is the identity,
+    // with the multiples of G gathered into the first term.
+    let mut scalars = vec![C::Scalar::ZERO];
+    let mut points = vec![C::ProjectivePoint::generator()];
+    for (mut transcript, statement, proof) in proofs {
+        let e = challenge(&mut transcript, statement, &proof.big_k);
+        let weight = C::Scalar::random(&mut *rng);
+        scalars[0] += weight * proof.s;
+        scalars.extend([-weight, -weight * e]);
+        points.extend([proof.big_k.to_projective(), *statement.public]);
+    }
+    bool::from(multiscalar_mul::<C>(&scalars, &points).is_identity())
 }
 
 #[cfg(test)]
@@ -109,6 +143,7 @@ mod test {
     use rand_core::OsRng;
 
     use super::*;
+    use crate::fiat_shamir::{FiatShamirBackend, MerlinTranscript, Transcript};
     use k256::{ProjectivePoint, Scalar, Secp256k1};
 
     #[test]
@@ -133,4 +168,73 @@ mod test {
 
         assert!(ok);
     }
+
+    #[test]
+    fn test_batch_verification() {
+        let transcript = Transcript::new(b"protocol");
+        let xs: Vec<_> = (0..4)
+            .map(|_| Scalar::generate_biased(&mut OsRng))
+            .collect();
+        let publics: Vec<_> = xs.iter().map(|x| ProjectivePoint::GENERATOR * x).collect();
+        let proofs: Vec<_> = xs
+            .iter()
+            .zip(&publics)
+            .map(|(x, public)| {
+                prove(
+                    &mut OsRng,
+                    &mut transcript.forked(b"party", &[1]),
+                    Statement::<Secp256k1> { public },
+                    Witness { x },
+                )
+            })
+            .collect();
+
+        let batch = |publics: &[ProjectivePoint]| {
+            verify_batch(
+                &mut OsRng,
+                publics.iter().zip(&proofs).map(|(public, proof)| {
+                    (
+                        transcript.forked(b"party", &[1]),
+                        Statement::<Secp256k1> { public },
+                        proof,
+                    )
+                }),
+            )
+        };
+        assert!(batch(&publics));
+
+        let mut wrong = publics.clone();
+        wrong.swap(1, 2);
+        assert!(!batch(&wrong));
+    }
+
+    #[test]
+    fn test_proofs_depend_on_the_backend() {
+        let x = Scalar::generate_biased(&mut OsRng);
+        let statement = Statement::<Secp256k1> {
+            public: &(ProjectivePoint::GENERATOR * x),
+        };
+
+        let proof = prove(
+            &mut OsRng,
+            &mut MerlinTranscript::new(b"protocol"),
+            statement,
+            Witness { x: &x },
+        );
+        assert!(verify(
+            &mut MerlinTranscript::new(b"protocol"),
+            statement,
+            &proof
+        ));
+        assert!(verify(
+            &mut Transcript::with_backend(FiatShamirBackend::Merlin, b"protocol"),
+            statement,
+            &proof
+        ));
+        assert!(!verify(
+            &mut Transcript::new(b"protocol"),
+            statement,
+            &proof
+        ));
+    }
 }
@@ -1,11 +1,15 @@
+//! Proofs that two points have the same discrete logarithm, under different generators.
 use elliptic_curve::{Field, Group};
-use magikitten::Transcript;
 use rand_core::CryptoRngCore;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     compat::{CSCurve, SerializablePoint},
-    serde::{deserialize_scalar, encode, serialize_projective_point, serialize_scalar},
+    fiat_shamir::FiatShamir,
+    math::multiscalar_mul,
+    serde::{
+        deserialize_scalar, encode, serialize_projective_point, serialize_scalar, Schema, Wire,
+    },
 };
 
 /// The label we use for hashing the statement.
@@ -21,10 +25,13 @@ const CHALLENGE_LABEL: &[u8] = b"dlogeq proof challenge";
 /// of one point under the standard generator, and of another point under an alternate generator.
 #[derive(Debug, Clone, Copy, Serialize)]
 pub struct Statement<'a, C: CSCurve> {
+    /// The point `x * G`.
     #[serde(serialize_with = "serialize_projective_point::<C, _>")]
     pub public0: &'a C::ProjectivePoint,
+    /// The alternate generator `H`.
     #[serde(serialize_with = "serialize_projective_point::<C, _>")]
     pub generator1: &'a C::ProjectivePoint,
+    /// The point `x * H`.
     #[serde(serialize_with = "serialize_projective_point::<C, _>")]
     pub public1: &'a C::ProjectivePoint,
 }
@@ -41,17 +48,18 @@ impl<'a, C: CSCurve> Statement<'a, C> {
 /// This holds the scalar the prover needs to know.
 #[derive(Clone, Copy)]
 pub struct Witness<'a, C: CSCurve> {
+    /// The discrete logarithm shared by both public points.
     pub x: &'a C::Scalar,
 }
 
 /// Represents a proof of the statement.
+///
+/// This contains the commitments of the prover, rather than the challenge,
+/// so that many proofs can be verified at once, with [`verify_batch`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
 pub struct Proof<C: CSCurve> {
-    #[serde(
-        serialize_with = "serialize_scalar::<C, _>",
-        deserialize_with = "deserialize_scalar::<C, _>"
-    )]
-    e: C::Scalar,
+    big_k: (SerializablePoint<C>, SerializablePoint<C>),
     #[serde(
         serialize_with = "serialize_scalar::<C, _>",
         deserialize_with = "deserialize_scalar::<C, _>"
@@ -59,33 +67,57 @@ pub struct Proof<C: CSCurve> {
     s: C::Scalar,
 }
 
+impl<C: CSCurve> Proof<C> {
+    /// Assemble a proof made jointly, from the sums of the commitments and responses of each prover.
+    ///
+    /// The challenge the responses answer must come from [`challenge`], over the summed commitments.
+    pub(crate) fn from_parts(
+        big_k: (SerializablePoint<C>, SerializablePoint<C>),
+        s: C::Scalar,
+    ) -> Self {
+        Self { big_k, s }
+    }
+}
+
+impl<C: CSCurve> Wire for Proof<C> {
+    fn schema() -> Schema {
+        let big_k = Schema::message([("", 1, Schema::Bytes), ("", 2, Schema::Bytes)]);
+        Schema::message([("big_k", 1, big_k), ("s", 2, Schema::Bytes)])
+    }
+}
+
+/// Derive the challenge for a proof, given the commitments of the prover.
+pub(crate) fn challenge<C: CSCurve>(
+    transcript: &mut impl FiatShamir,
+    statement: Statement<'_, C>,
+    big_k: &(SerializablePoint<C>, SerializablePoint<C>),
+) -> C::Scalar {
+    transcript.message(STATEMENT_LABEL, &encode(&statement));
+    transcript.message(COMMITMENT_LABEL, &encode(big_k));
+    C::Scalar::random(&mut transcript.challenge(CHALLENGE_LABEL))
+}
+
 /// Prove that a witness satisfies a given statement.
 ///
 /// We need some randomness for the proof, and also a transcript, which is
 /// used for the Fiat-Shamir transform.
 pub fn prove<'a, C: CSCurve>(
     rng: &mut impl CryptoRngCore,
-    transcript: &mut Transcript,
+    transcript: &mut impl FiatShamir,
     statement: Statement<'a, C>,
     witness: Witness<'a, C>,
 ) -> Proof<C> {
-    transcript.message(STATEMENT_LABEL, &encode(&statement));
-
     let k = C::Scalar::random(rng);
-    let big_k = statement.phi(&k);
-
-    transcript.message(
-        COMMITMENT_LABEL,
-        &encode(&(
-            SerializablePoint::<C>::from_projective(&big_k.0),
-            SerializablePoint::<C>::from_projective(&big_k.1),
-        )),
+    let (big_k0, big_k1) = statement.phi(&k);
+    let big_k = (
+        SerializablePoint::<C>::from_projective(&big_k0),
+        SerializablePoint::<C>::from_projective(&big_k1),
     );
 
-    let e = C::Scalar::random(&mut transcript.challenge(CHALLENGE_LABEL));
+    let e = challenge(transcript, statement, &big_k);
 
     let s = k + e * witness.x;
-    Proof { e, s }
+    Proof { big_k, s }
 }
 
 /// Verify that a proof attesting to the validity of some statement.
@@ -93,28 +125,55 @@ pub fn prove<'a, C: CSCurve>(
 /// We use a transcript in order to verify the Fiat-Shamir transformation.
 #[must_use]
 pub fn verify<C: CSCurve>(
-    transcript: &mut Transcript,
+    transcript: &mut impl FiatShamir,
     statement: Statement<'_, C>,
     proof: &Proof<C>,
 ) -> bool {
-    let statement_data = encode(&statement);
-    transcript.message(STATEMENT_LABEL, &statement_data);
-
-    let (phi0, phi1) = statement.phi(&proof.s);
-    let big_k0 = phi0 - *statement.public0 * proof.e;
-    let big_k1 = phi1 - *statement.public1 * proof.e;
-
-    transcript.message(
-        COMMITMENT_LABEL,
-        &encode(&(
-            SerializablePoint::<C>::from_projective(&big_k0),
-            SerializablePoint::<C>::from_projective(&big_k1),
-        )),
-    );
+    let e = challenge(transcript, statement, &proof.big_k);
 
-    let e = C::Scalar::random(&mut transcript.challenge(CHALLENGE_LABEL));
+    let big_k0 = multiscalar_mul::<C>(
+        &[proof.s, -e],
+        &[C::ProjectivePoint::generator(), *statement.public0],
+    );
+    let big_k1 = multiscalar_mul::<C>(&[proof.s, -e], &[*statement.generator1, *statement.public1]);
+    big_k0 == proof.big_k.0.to_projective() && big_k1 == proof.big_k.1.to_projective()
+}
 
-    e == proof.e
+/// Verify many proofs at once, each with its own transcript.
+///
+/// This checks a random linear combination of the verification equations,
+/// which is cheaper than checking each of them. If this fails, at least one
+/// of the proofs is invalid, and [`verify`] can be used to find out which.
+#[must_use]
+pub fn verify_batch<'a, C: CSCurve, T: FiatShamir>(
+    rng: &mut impl CryptoRngCore,
+    proofs: impl IntoIterator<Item = (T, Statement<'a, C>, &'a Proof<C>)>,
+) -> bool {
+    // Both equations of a proof get their own weight, and only the
+    // multiples of the standard generator can be gathered into one term.
+    let mut scalars = vec![C::Scalar::ZERO];
+    let mut points = vec![C::ProjectivePoint::generator()];
+    for (mut transcript, statement, proof) in proofs {
+        let e = challenge(&mut transcript, statement, &proof.big_k);
+        let weight0 = C::Scalar::random(&mut *rng);
+        let weight1 = C::Scalar::random(&mut *rng);
+        scalars[0] += weight0 * proof.s;
+        scalars.extend([
+            -weight0,
+            -weight0 * e,
+            weight1 * proof.s,
+            -weight1,
+            -weight1 * e,
+        ]);
+        points.extend([
+            proof.big_k.0.to_projective(),
+            *statement.public0,
+            *statement.generator1,
+            proof.big_k.1.to_projective(),
+            *statement.public1,
+        ]);
+    }
+    bool::from(multiscalar_mul::<C>(&scalars, &points).is_identity())
 }
 
 #[cfg(test)]
@@ -122,6 +181,7 @@ mod test {
     use rand_core::OsRng;
 
     use super::*;
+    use crate::fiat_shamir::Transcript;
 
     use k256::{ProjectivePoint, Scalar, Secp256k1};
 
@@ -150,4 +210,57 @@ mod test {
 
         assert!(ok);
     }
+
+    #[test]
+    fn test_batch_verification() {
+        let transcript = Transcript::new(b"protocol");
+        let statements: Vec<_> = (0..4)
+            .map(|_| {
+                let x = Scalar::generate_biased(&mut OsRng);
+                let big_h = ProjectivePoint::GENERATOR * Scalar::generate_biased(&mut OsRng);
+                (x, ProjectivePoint::GENERATOR * x, big_h, big_h * x)
+            })
+            .collect();
+        let proofs: Vec<_> = statements
+            .iter()
+            .map(|(x, public0, generator1, public1)| {
+                prove(
+                    &mut OsRng,
+                    &mut transcript.forked(b"party", &[1]),
+                    Statement::<Secp256k1> {
+                        public0,
+                        generator1,
+                        public1,
+                    },
+                    Witness { x },
+                )
+            })
+            .collect();
+
+        let batch =
+            |statements: &[(Scalar, ProjectivePoint, ProjectivePoint, ProjectivePoint)]| {
+                verify_batch(
+                    &mut OsRng,
+                    statements.iter().zip(&proofs).map(
+                        |((_, public0, generator1, public1), proof)| {
+                            (
+                                transcript.forked(b"party", &[1]),
+                                Statement::<Secp256k1> {
+                                    public0,
+                                    generator1,
+                                    public1,
+                                },
+                                proof,
+                            )
+                        },
+                    ),
+                )
+            };
+        assert!(batch(&statements));
+
+        // Only the second equation of the last proof is wrong.
+        let mut wrong = statements.clone();
+        wrong[3].3 += ProjectivePoint::GENERATOR;
+        assert!(!batch(&wrong));
+    }
 }
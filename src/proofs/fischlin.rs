@@ -0,0 +1,252 @@
+//! Proofs of knowledge of a discrete logarithm, with the Fischlin transform.
+//!
+//! Instead of deriving a single challenge from a hash, the prover runs many
+//! parallel repetitions of the Schnorr protocol, and searches, for each of them,
+//! for a small challenge whose response hashes to a value starting with enough
+//! zero bits. This makes the proof straight-line extractable: a simulator
+//! observing the hash queries of the prover learns the witness, without rewinding
+//! it, which composes better with concurrent protocols than Fiat-Shamir does.
+//!
+//! This comes at the cost of larger proofs, and of a few thousand hashes for the prover.
+use elliptic_curve::{ff::PrimeField, Field, Group};
+use rand_core::{CryptoRngCore, RngCore};
+use serde::{Deserialize, Serialize};
+
+use super::dlog::{Statement, Witness};
+use crate::{
+    compat::{CSCurve, SerializablePoint},
+    fiat_shamir::FiatShamir,
+    math::multiscalar_mul,
+    serde::{deserialize_scalar, encode, serialize_scalar, Schema, Wire},
+};
+
+/// The label we use for hashing the statement.
+const STATEMENT_LABEL: &[u8] = b"fischlin proof statement";
+/// The label we use for hashing the commitments of every repetition.
+const COMMITMENT_LABEL: &[u8] = b"fischlin proof commitments";
+/// The label we use for hashing a candidate response.
+const RESPONSE_LABEL: &[u8] = b"fischlin proof response";
+/// The label we use for reading the hash of a candidate response.
+const HASH_LABEL: &[u8] = b"fischlin proof hash";
+/// The label we use for generating the weights of the verification equation.
+const WEIGHT_LABEL: &[u8] = b"fischlin proof weights";
+
+/// The number of parallel repetitions.
+const REPETITIONS: usize = 16;
+/// The number of zero bits the hash of each response needs to start with.
+///
+/// A cheating prover succeeds with probability about 2^-(REPETITIONS * ZERO_BITS).
+const ZERO_BITS: u32 = 8;
+/// The number of bits in each challenge.
+///
+/// The prover fails to find a good challenge for some repetition with probability
+/// about exp(-2^(CHALLENGE_BITS - ZERO_BITS)), in which case it starts over.
+const CHALLENGE_BITS: u32 = 12;
+
+/// One repetition of the underlying Schnorr protocol.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+struct Repetition<C: CSCurve> {
+    big_k: SerializablePoint<C>,
+    e: u16,
+    #[serde(
+        serialize_with = "serialize_scalar::<C, _>",
+        deserialize_with = "deserialize_scalar::<C, _>"
+    )]
+    z: C::Scalar,
+}
+
+/// Represents a proof of the statement.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct Proof<C: CSCurve> {
+    repetitions: Vec<Repetition<C>>,
+}
+
+impl<C: CSCurve> Wire for Proof<C> {
+    fn schema() -> Schema {
+        let repetition = Schema::message([
+            ("big_k", 1, Schema::Bytes),
+            ("e", 2, Schema::Uint32),
+            ("z", 3, Schema::Bytes),
+        ]);
+        Schema::message([("repetitions", 1, Schema::repeated(repetition))])
+    }
+}
+
+/// Absorb the statement and the commitments of every repetition.
+fn absorb<C: CSCurve>(
+    transcript: &mut impl FiatShamir,
+    statement: Statement<'_, C>,
+    big_ks: &[SerializablePoint<C>],
+) {
+    transcript.message(STATEMENT_LABEL, &encode(&statement));
+    transcript.message(COMMITMENT_LABEL, &encode(&big_ks));
+}
+
+/// Check whether a candidate response, in a given repetition, hashes to enough zero bits.
+fn is_good<C: CSCurve>(transcript: &impl FiatShamir, i: usize, e: u16, z: &C::Scalar) -> bool {
+    let mut data = Vec::new();
+    data.extend_from_slice(&u64::try_from(i).unwrap().to_be_bytes());
+    data.extend_from_slice(&e.to_be_bytes());
+    data.extend_from_slice(z.to_repr().as_ref());
+    let mut hash = [0u8; 4];
+    transcript
+        .forked(RESPONSE_LABEL, &data)
+        .challenge(HASH_LABEL)
+        .fill_bytes(&mut hash);
+    u32::from_be_bytes(hash).leading_zeros() >= ZERO_BITS
+}
+
+/// Prove that a witness satisfies a given statement.
+///
+/// The transcript binds the proof to its context, as with [`dlog::prove`](super::dlog::prove).
+pub fn prove<'a, C: CSCurve>(
+    rng: &mut impl CryptoRngCore,
+    transcript: &mut impl FiatShamir,
+    statement: Statement<'a, C>,
+    witness: Witness<'a, C>,
+) -> Proof<C> {
+    'attempt: loop {
+        let ks: Vec<_> = (0..REPETITIONS)
+            .map(|_| C::Scalar::random(&mut *rng))
+            .collect();
+        let big_ks: Vec<_> = ks
+            .iter()
+            .map(|k| {
+                SerializablePoint::<C>::from_projective(&(C::ProjectivePoint::generator() * k))
+            })
+            .collect();
+        let mut attempt = transcript.forked(b"attempt", &[]);
+        absorb(&mut attempt, statement, &big_ks);
+
+        let mut repetitions = Vec::with_capacity(REPETITIONS);
+        for (i, (k, big_k)) in ks.iter().zip(big_ks).enumerate() {
+            let found = (0..1u16 << CHALLENGE_BITS).find_map(|e| {
+                let z = *k + C::Scalar::from(u64::from(e)) * witness.x;
+                is_good::<C>(&attempt, i, e, &z).then_some((e, z))
+            });
+            let Some((e, z)) = found else {
+                continue 'attempt;
+            };
+            repetitions.push(Repetition { big_k, e, z });
+        }
+        *transcript = attempt;
+        return Proof { repetitions };
+    }
+}
+
+/// Verify that a proof attesting to the validity of some statement.
+///
+/// This checks every repetition with a single multi-scalar multiplication,
+/// weighted by a challenge derived once the whole proof has been absorbed.
+#[must_use]
+pub fn verify<C: CSCurve>(
+    transcript: &mut impl FiatShamir,
+    statement: Statement<'_, C>,
+    proof: &Proof<C>,
+) -> bool {
+    if proof.repetitions.len() != REPETITIONS {
+        return false;
+    }
+    let big_ks: Vec<_> = proof.repetitions.iter().map(|r| r.big_k).collect();
+    let mut attempt = transcript.forked(b"attempt", &[]);
+    absorb(&mut attempt, statement, &big_ks);
+
+    for (i, r) in proof.repetitions.iter().enumerate() {
+        if r.e >> CHALLENGE_BITS != 0 || !is_good::<C>(&attempt, i, r.e, &r.z) {
+            return false;
+        }
+    }
+
+    // We check that sum_i w_i (z_i G - K_i - e_i X) is the identity.
+    let mut weights = attempt.challenge(WEIGHT_LABEL);
+    let mut scalars = vec![C::Scalar::ZERO, C::Scalar::ZERO];
+    let mut points = vec![C::ProjectivePoint::generator(), *statement.public];
+    for r in &proof.repetitions {
+        let weight = C::Scalar::random(&mut weights);
+        scalars[0] += weight * r.z;
+        scalars[1] -= weight * C::Scalar::from(u64::from(r.e));
+        scalars.push(-weight);
+        points.push(r.big_k.to_projective());
+    }
+    *transcript = attempt;
+    bool::from(multiscalar_mul::<C>(&scalars, &points).is_identity())
+}
+
+#[cfg(test)]
+mod test {
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::fiat_shamir::Transcript;
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
+
+    #[test]
+    fn test_valid_proof_verifies() {
+        let x = Scalar::generate_biased(&mut OsRng);
+
+        let statement = Statement::<Secp256k1> {
+            public: &(ProjectivePoint::GENERATOR * x),
+        };
+        let witness = Witness { x: &x };
+
+        let transcript = Transcript::new(b"protocol");
+
+        let proof = prove(
+            &mut OsRng,
+            &mut transcript.forked(b"party", &[1]),
+            statement,
+            witness,
+        );
+
+        assert!(verify(
+            &mut transcript.forked(b"party", &[1]),
+            statement,
+            &proof
+        ));
+        assert!(!verify(
+            &mut transcript.forked(b"party", &[2]),
+            statement,
+            &proof
+        ));
+    }
+
+    #[test]
+    fn test_tampered_proof_fails() {
+        let x = Scalar::generate_biased(&mut OsRng);
+        let public = ProjectivePoint::GENERATOR * x;
+        let statement = Statement::<Secp256k1> { public: &public };
+        let transcript = Transcript::new(b"protocol");
+        let proof = prove(
+            &mut OsRng,
+            &mut transcript.forked(b"party", &[1]),
+            statement,
+            Witness { x: &x },
+        );
+
+        let mut bad = proof.clone();
+        bad.repetitions[3].z += Scalar::ONE;
+        assert!(!verify(
+            &mut transcript.forked(b"party", &[1]),
+            statement,
+            &bad
+        ));
+
+        let mut bad = proof.clone();
+        bad.repetitions.pop();
+        assert!(!verify(
+            &mut transcript.forked(b"party", &[1]),
+            statement,
+            &bad
+        ));
+
+        let other = ProjectivePoint::GENERATOR * Scalar::generate_biased(&mut OsRng);
+        let statement = Statement::<Secp256k1> { public: &other };
+        assert!(!verify(
+            &mut transcript.forked(b"party", &[1]),
+            statement,
+            &proof
+        ));
+    }
+}
@@ -1,2 +1,43 @@
+//! This module contains the zero-knowledge proofs used by our protocols.
+//!
+//! These are non-interactive proofs of knowledge of a discrete logarithm, in [`dlog`],
+//! of the equality of two discrete logarithms, in [`dlogeq`], and of a discrete
+//! logarithm with straight-line extraction, in [`fischlin`]. Each of them has a
+//! `Statement`, holding the public values, a `Witness`, holding the secret, and a
+//! `Proof`, which can be serialized, along with `prove` and `verify` functions.
+//!
+//! Every proof is bound to a transcript, implementing [`FiatShamir`](crate::fiat_shamir::FiatShamir),
+//! like [`Transcript`](crate::fiat_shamir::Transcript), which should contain everything about the context the proof is made in: the protocol, the session,
+//! the participants, and the messages exchanged so far. Proofs absorb their own statement,
+//! under labels specific to each kind of proof, so a proof for one statement, or of one kind,
+//! never verifies as another. When many participants prove things from the same point
+//! in a protocol, each of them should use a fork of the transcript, with
+//! [`FiatShamir::forked`](crate::fiat_shamir::FiatShamir::forked), labelled with the
+//! identity of the prover, so that proofs can't be replayed between participants:
+//!
+//! ```ignore
+//! use cait_sith::fiat_shamir::{FiatShamir, Transcript};
+//! use cait_sith::proofs::dlog;
+//! use elliptic_curve::{Field, Group};
+//! use k256::{ProjectivePoint, Scalar, Secp256k1};
+//! use rand_core::OsRng;
+//!
+//! let x = Scalar::random(&mut OsRng);
+//! let public = ProjectivePoint::generator() * x;
+//! let statement = dlog::Statement::<Secp256k1> { public: &public };
+//!
+//! let transcript = Transcript::new(b"my protocol v1");
+//! let proof = dlog::prove(
+//!     &mut OsRng,
+//!     &mut transcript.forked(b"prover", b"alice"),
+//!     statement,
+//!     dlog::Witness { x: &x },
+//! );
+//! assert!(dlog::verify(&mut transcript.forked(b"prover", b"alice"), statement, &proof));
+//! assert!(!dlog::verify(&mut transcript.forked(b"prover", b"bob"), statement, &proof));
+//! ```
+//!
+//! See `docs/proofs.md` for a description of each proof.
 pub mod dlog;
 pub mod dlogeq;
+pub mod fischlin;
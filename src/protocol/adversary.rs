@@ -0,0 +1,303 @@
+//! A framework for simulating malicious participants.
+//!
+//! The protocols are meant to abort when a participant cheats, and, when possible,
+//! to blame that participant, and nobody else. Testing this requires participants
+//! which cheat in specific ways. [`Cheating`] wraps the protocol of an honest
+//! participant, and lets a [`Strategy`] decide what to actually send, in place
+//! of each message the honest protocol wants to send.
+//!
+//! This module provides strategies for flipping bits in messages, equivocating
+//! on messages meant for everyone, withholding messages, and replaying old messages.
+//! Other strategies can be written by implementing [`Strategy`].
+//!
+//! Withholding messages makes the honest participants wait forever, so protocols
+//! with cheaters are best run with a [`Simulator`](super::simulator::Simulator),
+//! which reports a timeout when no messages are left, instead of hanging.
+use std::collections::VecDeque;
+
+use super::{Action, Format, MessageData, Participant, Protocol, ProtocolError, QueueLimits};
+
+/// A way of cheating, by changing the messages an honest participant sends.
+pub trait Strategy {
+    /// Decide what to send in place of a message.
+    ///
+    /// The index counts the messages sent by the honest protocol so far, starting at 0.
+    /// A recipient of `None` means the message is for everyone. This returns the messages
+    /// to actually send, which can be empty, to drop the message.
+    fn tamper(
+        &mut self,
+        index: usize,
+        to: Option<Participant>,
+        data: MessageData,
+    ) -> Vec<(Option<Participant>, MessageData)>;
+}
+
+/// Flip a bit in one of the messages, delivering it to every recipient this way.
+#[derive(Debug, Clone, Copy)]
+pub struct BitFlip {
+    /// The index of the message to tamper with.
+    pub message: usize,
+    /// Which bit to flip, modulo the length of the message.
+    ///
+    /// Bits are counted from the end of the message, so that small values flip bits
+    /// in its contents, rather than in the header routing it.
+    pub bit: usize,
+}
+
+fn flip(data: MessageData, bit: usize) -> MessageData {
+    let mut data = Vec::from(data);
+    if !data.is_empty() {
+        let bit = bit % (8 * data.len());
+        let i = data.len() - 1 - bit / 8;
+        data[i] ^= 1 << (bit % 8);
+    }
+    data.into()
+}
+
+impl Strategy for BitFlip {
+    fn tamper(
+        &mut self,
+        index: usize,
+        to: Option<Participant>,
+        data: MessageData,
+    ) -> Vec<(Option<Participant>, MessageData)> {
+        if index == self.message {
+            vec![(to, flip(data, self.bit))]
+        } else {
+            vec![(to, data)]
+        }
+    }
+}
+
+/// Send a different version of a message meant for everyone to one of the participants.
+///
+/// The victim gets the message with a bit flipped, while everyone else
+/// gets the original.
+#[derive(Debug, Clone)]
+pub struct Equivocate {
+    /// The index of the message to equivocate on, which must be meant for everyone.
+    pub message: usize,
+    /// The participant receiving the modified message.
+    pub victim: Participant,
+    /// Every participant other than the cheater.
+    pub others: Vec<Participant>,
+}
+
+impl Strategy for Equivocate {
+    fn tamper(
+        &mut self,
+        index: usize,
+        to: Option<Participant>,
+        data: MessageData,
+    ) -> Vec<(Option<Participant>, MessageData)> {
+        if index != self.message || to.is_some() {
+            return vec![(to, data)];
+        }
+        self.others
+            .iter()
+            .map(|&p| {
+                if p == self.victim {
+                    (Some(p), flip(data.clone(), 0))
+                } else {
+                    (Some(p), data.clone())
+                }
+            })
+            .collect()
+    }
+}
+
+/// Stop sending messages, starting from a given one.
+#[derive(Debug, Clone, Copy)]
+pub struct Withhold {
+    /// The index of the first message to withhold.
+    pub from_message: usize,
+    /// The participant to withhold messages from, or everyone, if `None`.
+    ///
+    /// Messages meant for everyone are withheld from everyone.
+    pub victim: Option<Participant>,
+}
+
+impl Strategy for Withhold {
+    fn tamper(
+        &mut self,
+        index: usize,
+        to: Option<Participant>,
+        data: MessageData,
+    ) -> Vec<(Option<Participant>, MessageData)> {
+        let withheld = index >= self.from_message
+            && (self.victim.is_none() || to.is_none() || to == self.victim);
+        if withheld {
+            Vec::new()
+        } else {
+            vec![(to, data)]
+        }
+    }
+}
+
+/// Send an old message again, in place of a new one.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    message: usize,
+    source: usize,
+    sent: Vec<MessageData>,
+}
+
+impl Replay {
+    /// Send the message at one index again, in place of a later one.
+    ///
+    /// This panics if the message to replay doesn't come before the one it replaces.
+    pub fn new(source: usize, message: usize) -> Self {
+        assert!(source < message, "can only replay an earlier message");
+        Self {
+            message,
+            source,
+            sent: Vec::new(),
+        }
+    }
+}
+
+impl Strategy for Replay {
+    fn tamper(
+        &mut self,
+        index: usize,
+        to: Option<Participant>,
+        data: MessageData,
+    ) -> Vec<(Option<Participant>, MessageData)> {
+        self.sent.push(data.clone());
+        if index == self.message {
+            vec![(to, self.sent[self.source].clone())]
+        } else {
+            vec![(to, data)]
+        }
+    }
+}
+
+/// A participant cheating, by running an honest protocol, and tampering with its messages.
+pub struct Cheating<P: Protocol, S> {
+    protocol: P,
+    strategy: S,
+    sent: usize,
+    outbox: VecDeque<Action<P::Output>>,
+}
+
+impl<P: Protocol, S: Strategy> Cheating<P, S> {
+    /// Wrap an honest protocol, with a strategy for cheating.
+    pub fn new(protocol: P, strategy: S) -> Self {
+        Self {
+            protocol,
+            strategy,
+            sent: 0,
+            outbox: VecDeque::new(),
+        }
+    }
+}
+
+impl<P: Protocol, S: Strategy> Protocol for Cheating<P, S> {
+    type Output = P::Output;
+
+    fn poke(&mut self) -> Result<Action<Self::Output>, ProtocolError> {
+        loop {
+            if let Some(action) = self.outbox.pop_front() {
+                return Ok(action);
+            }
+            let (to, data) = match self.protocol.poke()? {
+                Action::SendMany(data) => (None, data),
+                Action::SendPrivate(to, data) => (Some(to), data),
+                action => return Ok(action),
+            };
+            let index = self.sent;
+            self.sent += 1;
+            for (to, data) in self.strategy.tamper(index, to, data) {
+                self.outbox.push_back(match to {
+                    None => Action::SendMany(data),
+                    Some(to) => Action::SendPrivate(to, data),
+                });
+            }
+        }
+    }
+
+    fn message(&mut self, from: Participant, data: MessageData) {
+        self.protocol.message(from, data)
+    }
+
+    fn set_queue_limits(&mut self, limits: QueueLimits) {
+        self.protocol.set_queue_limits(limits)
+    }
+
+    fn set_format(&mut self, format: Format) {
+        self.protocol.set_format(format)
+    }
+
+    fn cancel(&mut self) {
+        self.protocol.cancel()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use k256::Secp256k1;
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::{
+        keygen,
+        protocol::{simulator::Simulator, Fault},
+        KeygenOutput,
+    };
+
+    /// Run keygen with participant 0 cheating, returning the error an honest participant hit.
+    fn run_keygen_cheating(strategy: impl Strategy + 'static) -> ProtocolError {
+        let participants: Vec<_> = (0..3u32).map(Participant::from).collect();
+        #[allow(clippy::type_complexity)]
+        let mut ps: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        )> = Vec::new();
+        let mut strategy = Some(strategy);
+        for &p in &participants {
+            let protocol = keygen::<Secp256k1>(&participants, p, 2).unwrap();
+            match strategy.take() {
+                Some(strategy) => ps.push((p, Box::new(Cheating::new(protocol, strategy)))),
+                None => ps.push((p, Box::new(protocol))),
+            }
+        }
+        match Simulator::new(ps).run(&mut OsRng) {
+            Ok(_) => panic!("cheating went unnoticed"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn test_cheating_keygen() {
+        let cheater = Participant::from(0u32);
+        let others = vec![Participant::from(1u32), Participant::from(2u32)];
+
+        // Tampering with the opening of a commitment is caught, and blamed.
+        for message in 1..3 {
+            let e = run_keygen_cheating(BitFlip { message, bit: 3 });
+            assert_eq!(e.culprit(), Some(cheater), "{}", e);
+        }
+
+        // Equivocating on a commitment gets caught when the confirmations don't match,
+        // but an honest participant can't tell which of the others equivocated,
+        // so the culprit isn't meaningful here.
+        let e = run_keygen_cheating(Equivocate {
+            message: 0,
+            victim: others[0],
+            others: others.clone(),
+        });
+        assert_eq!(e.fault(), Some(&Fault::InconsistentBroadcast), "{}", e);
+
+        let e = run_keygen_cheating(Withhold {
+            from_message: 1,
+            victim: Some(others[1]),
+        });
+        match e {
+            ProtocolError::Timeout(waiting) => assert!(!waiting.contains(&cheater)),
+            e => panic!("expected a timeout, got {}", e),
+        }
+
+        let e = run_keygen_cheating(Replay::new(0, 1));
+        assert!(e.culprit().is_none_or(|p| p == cheater), "{}", e);
+    }
+}
@@ -0,0 +1,325 @@
+//! This module provides a way to persist a protocol midway, and resume it later.
+//!
+//! The state of a protocol lives inside of a future, which can't be serialized.
+//! Instead, we rely on the fact that protocols are deterministic: given the same
+//! randomness, and the same messages, a protocol will always do the same thing.
+//!
+//! A [`Checkpoint`] thus records the seed used for the randomness of the protocol,
+//! along with every message it has received so far. Resuming from a checkpoint
+//! replays these messages into a fresh instance of the protocol, bringing it back
+//! to where it left off. The checkpoint also remembers what the protocol already sent,
+//! so that the resumed protocol doesn't send the same messages a second time.
+//!
+//! For this to work, the protocol needs to draw all of its randomness from the rng
+//! it's given, which is the case for the `_with_rng` variants of the protocols,
+//! like [`keygen_with_rng`](crate::keygen_with_rng).
+//!
+//! **Note:** a checkpoint contains the seed for all of the secrets of the protocol,
+//! so it needs to be stored as carefully as a private share.
+//! You should also only ever resume from the *latest* checkpoint. Resuming from an older one,
+//! and then receiving different messages than the first time around, might reuse
+//! the same randomness in different contexts, which can leak secrets.
+use std::fmt;
+
+use magikitten::MeowRng;
+use rand_core::{CryptoRng, CryptoRngCore, OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::crypto::{hash, Digest};
+
+use super::{
+    internal::wipe_message, Action, Format, InitializationError, MessageData, Participant,
+    Protocol, ProtocolError, QueueLimits,
+};
+
+/// The source of randomness handed to a protocol when creating or resuming it.
+pub struct CheckpointRng(MeowRng);
+
+impl RngCore for CheckpointRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.0.try_fill_bytes(dest)
+    }
+}
+
+impl CryptoRng for CheckpointRng {}
+
+/// The persistent state of a protocol, from which it can be resumed.
+///
+/// This is wiped when dropped, and its debug output leaves out the seed and the messages.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    seed: [u8; 32],
+    received: Vec<(Participant, MessageData)>,
+    sent: Vec<Digest>,
+}
+
+impl fmt::Debug for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Checkpoint")
+            .field("seed", &"..")
+            .field("received", &self.received.len())
+            .field("sent", &self.sent.len())
+            .finish()
+    }
+}
+
+impl Zeroize for Checkpoint {
+    fn zeroize(&mut self) {
+        self.seed.zeroize();
+        for (_, data) in self.received.drain(..) {
+            wipe_message(data);
+        }
+    }
+}
+
+impl Drop for Checkpoint {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for Checkpoint {}
+
+/// Identify an outgoing message, so that we can recognize it when it gets sent again.
+fn digest_outgoing(to: Option<Participant>, data: &MessageData) -> Digest {
+    hash(&(to, data))
+}
+
+/// Wraps a protocol, keeping track of a checkpoint as it runs.
+///
+/// The checkpoint should be persisted after every call to `message`, and after
+/// every call to `poke` which produces a message, *before* sending that message.
+/// That way, no message is ever lost, or sent twice.
+pub struct Checkpointed<P> {
+    protocol: P,
+    checkpoint: Checkpoint,
+    /// The messages that the protocol already sent before being resumed.
+    already_sent: Vec<Digest>,
+}
+
+impl<P: Protocol> Checkpointed<P> {
+    /// Start a new protocol, which can be checkpointed.
+    ///
+    /// The function passed here creates the protocol, and must use the rng it's given
+    /// for all of its randomness. The same function should be used when resuming.
+    pub fn new(
+        make: impl FnOnce(&mut CheckpointRng) -> Result<P, InitializationError>,
+    ) -> Result<Self, InitializationError> {
+        Self::new_with_rng(&mut OsRng, make)
+    }
+
+    /// Like [`Checkpointed::new`], except drawing the seed from a given rng.
+    pub fn new_with_rng(
+        rng: &mut impl CryptoRngCore,
+        make: impl FnOnce(&mut CheckpointRng) -> Result<P, InitializationError>,
+    ) -> Result<Self, InitializationError> {
+        let mut seed = [0u8; 32];
+        rng.fill_bytes(&mut seed);
+        Self::resume(
+            Checkpoint {
+                seed,
+                received: Vec::new(),
+                sent: Vec::new(),
+            },
+            make,
+        )
+    }
+
+    /// Resume a protocol from a checkpoint.
+    ///
+    /// This needs to be passed the same function used to create the protocol originally.
+    pub fn resume(
+        checkpoint: Checkpoint,
+        make: impl FnOnce(&mut CheckpointRng) -> Result<P, InitializationError>,
+    ) -> Result<Self, InitializationError> {
+        let mut protocol = make(&mut CheckpointRng(MeowRng::new(&checkpoint.seed)))?;
+        for (from, data) in &checkpoint.received {
+            protocol.message(*from, data.clone());
+        }
+        Ok(Self {
+            protocol,
+            already_sent: checkpoint.sent.clone(),
+            checkpoint,
+        })
+    }
+
+    /// The current checkpoint for this protocol.
+    pub fn checkpoint(&self) -> &Checkpoint {
+        &self.checkpoint
+    }
+}
+
+impl<P: Protocol> Protocol for Checkpointed<P> {
+    type Output = P::Output;
+
+    fn poke(&mut self) -> Result<Action<Self::Output>, ProtocolError> {
+        loop {
+            let action = self.protocol.poke()?;
+            let digest = match &action {
+                Action::SendMany(data) => digest_outgoing(None, data),
+                Action::SendPrivate(to, data) => digest_outgoing(Some(*to), data),
+                Action::Wait | Action::Return(_) => return Ok(action),
+            };
+            if let Some(i) = self.already_sent.iter().position(|d| *d == digest) {
+                self.already_sent.swap_remove(i);
+                continue;
+            }
+            self.checkpoint.sent.push(digest);
+            return Ok(action);
+        }
+    }
+
+    fn message(&mut self, from: Participant, data: MessageData) {
+        self.checkpoint.received.push((from, data.clone()));
+        self.protocol.message(from, data);
+    }
+
+    fn set_queue_limits(&mut self, limits: QueueLimits) {
+        self.protocol.set_queue_limits(limits)
+    }
+
+    fn set_format(&mut self, format: Format) {
+        self.protocol.set_format(format)
+    }
+
+    /// Cancel the protocol, and wipe the checkpoint, since it can no longer be resumed.
+    fn cancel(&mut self) {
+        self.protocol.cancel();
+        self.checkpoint.zeroize();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashSet, error::Error};
+
+    use k256::{ProjectivePoint, Secp256k1};
+
+    use super::*;
+    use crate::{
+        keygen_with_rng,
+        participants::ParticipantList,
+        serde::{decode, encode},
+        triples::generate_triple_with_rng,
+    };
+
+    /// Run a protocol between participants, restarting the first one after a few rounds.
+    ///
+    /// This also checks that no message ever gets sent twice.
+    fn run_with_restart<P: Protocol>(
+        participants: &[Participant],
+        restart_round: usize,
+        make: impl Fn(Participant, &mut CheckpointRng) -> Result<P, InitializationError>,
+    ) -> Result<Vec<P::Output>, Box<dyn Error>> {
+        let mut ps = participants
+            .iter()
+            .map(|&p| Checkpointed::new(|rng| make(p, rng)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut outputs: Vec<Option<P::Output>> = participants.iter().map(|_| None).collect();
+        let mut sent = HashSet::new();
+
+        let mut round = 0;
+        while outputs.iter().any(Option::is_none) {
+            for i in 0..ps.len() {
+                let from = participants[i];
+                loop {
+                    match ps[i].poke()? {
+                        Action::Wait => break,
+                        Action::SendMany(data) => {
+                            assert!(sent.insert((from, None, data.clone())));
+                            for j in (0..ps.len()).filter(|&j| j != i) {
+                                ps[j].message(from, data.clone());
+                            }
+                        }
+                        Action::SendPrivate(to, data) => {
+                            assert!(sent.insert((from, Some(to), data.clone())));
+                            let j = participants.iter().position(|&p| p == to).unwrap();
+                            ps[j].message(from, data);
+                        }
+                        Action::Return(out) => {
+                            outputs[i] = Some(out);
+                            break;
+                        }
+                    }
+                }
+            }
+
+            round += 1;
+            if round == restart_round {
+                let checkpoint: Checkpoint = decode(&encode(ps[0].checkpoint()))?;
+                ps[0] = Checkpointed::resume(checkpoint, |rng| make(participants[0], rng))?;
+            }
+        }
+
+        Ok(outputs.into_iter().map(Option::unwrap).collect())
+    }
+
+    #[test]
+    fn test_keygen_resume() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+
+        let outputs = run_with_restart(&participants, 1, |me, rng| {
+            keygen_with_rng::<Secp256k1>(&participants, me, 2, rng)
+        })?;
+
+        let public_key = outputs[0].public_key;
+        assert!(outputs.iter().all(|out| out.public_key == public_key));
+        let p_list = ParticipantList::new(&participants[..2]).unwrap();
+        let x = p_list.lagrange::<Secp256k1>(participants[0]) * outputs[0].private_share
+            + p_list.lagrange::<Secp256k1>(participants[1]) * outputs[1].private_share;
+        assert_eq!(ProjectivePoint::GENERATOR * x, public_key);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_debug_leaves_out_secrets() -> Result<(), Box<dyn Error>> {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let mut p = Checkpointed::new(|rng| {
+            keygen_with_rng::<Secp256k1>(&participants, participants[0], 2, rng)
+        })?;
+        p.message(participants[1], MessageData::from_static(b"secret"));
+        let debug = format!("{:?}", p.checkpoint());
+        assert_eq!(debug, "Checkpoint { seed: \"..\", received: 1, sent: 0 }");
+        Ok(())
+    }
+
+    #[test]
+    fn test_triple_generation_resume() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+
+        let outputs = run_with_restart(&participants, 2, |me, rng| {
+            generate_triple_with_rng::<Secp256k1>(&participants, me, 2, rng)
+        })?;
+
+        let triple_pub = &outputs[0].1;
+        assert!(outputs.iter().all(|(_, pub_i)| pub_i == triple_pub));
+        let p_list = ParticipantList::new(&participants[..2]).unwrap();
+        let c = p_list.lagrange::<Secp256k1>(participants[0]) * outputs[0].0.c
+            + p_list.lagrange::<Secp256k1>(participants[1]) * outputs[1].0.c;
+        assert_eq!(ProjectivePoint::GENERATOR * c, triple_pub.big_c);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,756 @@
+//! A layer compressing large messages on top of any [`Transport`].
+//!
+//! Some rounds of triple generation send messages of several megabytes, which adds up
+//! between participants in different regions. [`CompressedTransport`] compresses every
+//! message above a size threshold, with a [`Codec`] provided by the caller.
+//! The `lz4` feature provides [`Lz4`], which is fast, and written in pure Rust,
+//! and the `zstd` feature provides [`Zstd`], which compresses better, but slower,
+//! and links to the C library. Other libraries are easy to wrap.
+//!
+//! Compression is negotiated for each session: each participant announces its codec,
+//! along with the id of the session, and only compresses messages for peers which announced
+//! the same one for that session, so that participants can enable compression one at a time.
+//! [`CompressedTransport::connect`] negotiates the first session, and
+//! [`CompressedTransport::start_session`] each of the next ones, over the same connection.
+//! Every participant needs to wrap its transport with this layer though, even if it doesn't compress anything.
+//!
+//! Note that much of the data in triple generation is pseudorandom, and doesn't compress well.
+//! Messages which don't shrink are sent as is, so this only costs some time, but it's
+//! worth measuring the gains, with [`Metered`](super::Metered), before deploying this.
+//! To also encrypt messages, this layer should go under [`SecureTransport`](super::SecureTransport),
+//! since encrypted data doesn't compress.
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    error, fmt, mem,
+};
+
+use super::{transport::Transport, MessageData, Participant, SessionId};
+
+const HELLO: u8 = 0;
+const RAW: u8 = 1;
+const COMPRESSED: u8 = 2;
+
+const LEN_LEN: usize = 8;
+const SESSION_ID_LEN: usize = 32;
+
+/// A compression algorithm, for use with [`CompressedTransport`].
+pub trait Codec: Send {
+    /// Identifies the algorithm, so that we only compress for peers using the same one.
+    const NAME: &'static [u8];
+
+    /// Compress some data.
+    fn compress(&self, data: &[u8]) -> Vec<u8>;
+
+    /// Decompress some data, which should decompress to exactly `len` bytes.
+    ///
+    /// This should return `None` if the data is invalid, or decompresses to anything else,
+    /// without ever decompressing more than `len` bytes. The length comes from the peer,
+    /// so this shouldn't allocate all of it up front either, unless the data is
+    /// large enough to decompress to that much.
+    fn decompress(&self, data: &[u8], len: usize) -> Option<Vec<u8>>;
+}
+
+/// LZ4 block compression, using the `lz4_flex` crate.
+#[cfg(any(feature = "lz4", test))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lz4;
+
+/// The most an LZ4 block can expand by, when decompressing.
+///
+/// Each byte of a block produces at most one byte of literals, or extends a match by 255 bytes.
+#[cfg(any(feature = "lz4", test))]
+const LZ4_MAX_RATIO: usize = 255;
+
+#[cfg(any(feature = "lz4", test))]
+impl Codec for Lz4 {
+    const NAME: &'static [u8] = b"lz4";
+
+    fn compress(&self, data: &[u8]) -> Vec<u8> {
+        lz4_flex::block::compress(data)
+    }
+
+    fn decompress(&self, data: &[u8], len: usize) -> Option<Vec<u8>> {
+        // Blocks too short to decompress to the length claimed get rejected before allocating it.
+        if len > data.len().saturating_mul(LZ4_MAX_RATIO) {
+            return None;
+        }
+        // This fails, instead of growing the buffer, if the data decompresses to more.
+        let mut out = vec![0; len];
+        let written = lz4_flex::block::decompress_into(data, &mut out).ok()?;
+        (written == len).then_some(out)
+    }
+}
+
+/// Zstandard compression, using the `zstd` crate.
+#[cfg(any(feature = "zstd", test))]
+#[derive(Debug, Clone, Copy)]
+pub struct Zstd {
+    /// The compression level, from 1 to 22, with higher levels compressing better, but slower.
+    pub level: i32,
+}
+
+#[cfg(any(feature = "zstd", test))]
+impl Default for Zstd {
+    fn default() -> Self {
+        Self { level: 3 }
+    }
+}
+
+#[cfg(any(feature = "zstd", test))]
+impl Codec for Zstd {
+    const NAME: &'static [u8] = b"zstd";
+
+    fn compress(&self, data: &[u8]) -> Vec<u8> {
+        zstd::bulk::compress(data, self.level).expect("compression should succeed")
+    }
+
+    fn decompress(&self, data: &[u8], len: usize) -> Option<Vec<u8>> {
+        use std::io::Read;
+
+        // Zstandard can expand data by a lot, so the buffer grows as the data decompresses,
+        // stopping one byte past the length claimed.
+        let mut out = Vec::new();
+        zstd::stream::read::Decoder::with_buffer(data)
+            .ok()?
+            .take(len as u64 + 1)
+            .read_to_end(&mut out)
+            .ok()?;
+        (out.len() == len).then_some(out)
+    }
+}
+
+/// When to compress messages, and how large they can be.
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionSettings {
+    /// Messages of at least this many bytes get compressed.
+    pub threshold: usize,
+    /// The largest size a message can decompress to, protecting against decompression bombs.
+    pub max_len: usize,
+}
+
+impl Default for CompressionSettings {
+    fn default() -> Self {
+        Self {
+            threshold: 16 * 1024,
+            max_len: 256 * 1024 * 1024,
+        }
+    }
+}
+
+/// Represents an error which can happen when using a [`CompressedTransport`].
+#[derive(Debug)]
+pub enum CompressionError<E> {
+    /// The underlying transport failed.
+    Transport(E),
+    /// We received a message from a participant we don't know about.
+    UnknownPeer(Participant),
+    /// A message from this participant failed to parse or decompress.
+    BadMessage(Participant),
+}
+
+impl<E: fmt::Display> fmt::Display for CompressionError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "transport error: {}", e),
+            Self::UnknownPeer(p) => write!(f, "message from unknown participant {:?}", p),
+            Self::BadMessage(p) => write!(f, "bad message from participant {:?}", p),
+        }
+    }
+}
+
+impl<E: error::Error + 'static> error::Error for CompressionError<E> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Parse an announcement, into the session it's for, and the name of the codec.
+fn parse_hello(data: &[u8]) -> Option<(SessionId, &[u8])> {
+    let rest = data.strip_prefix(&[HELLO])?;
+    if rest.len() < SESSION_ID_LEN {
+        return None;
+    }
+    let (session, name) = rest.split_at(SESSION_ID_LEN);
+    Some((SessionId::from_bytes(session.try_into().unwrap()), name))
+}
+
+/// A transport compressing large messages, for peers which agreed to it.
+pub struct CompressedTransport<Tr, Z> {
+    transport: Tr,
+    codec: Z,
+    settings: CompressionSettings,
+    peers: HashSet<Participant>,
+    /// The peers which announced the same codec as us, for the current session.
+    compress_for: HashSet<Participant>,
+    /// Announcements which arrived before we started the session they're for,
+    /// saying whether the peer uses the same codec as us.
+    announced: HashMap<(Participant, SessionId), bool>,
+    /// Messages which arrived while negotiating, already decompressed.
+    buffered: VecDeque<(Participant, MessageData)>,
+}
+
+impl<Tr: Transport + Send, Z: Codec> CompressedTransport<Tr, Z> {
+    /// Negotiate compression with every other participant, for a first session, over a transport.
+    pub async fn connect(
+        transport: Tr,
+        me: Participant,
+        participants: &[Participant],
+        session: &SessionId,
+        codec: Z,
+        settings: CompressionSettings,
+    ) -> Result<Self, CompressionError<Tr::Error>> {
+        let mut out = Self {
+            transport,
+            codec,
+            settings,
+            peers: participants.iter().copied().filter(|&p| p != me).collect(),
+            compress_for: HashSet::new(),
+            announced: HashMap::new(),
+            buffered: VecDeque::new(),
+        };
+        out.start_session(session).await?;
+        Ok(out)
+    }
+
+    /// Negotiate compression again, for the next session.
+    ///
+    /// Every participant needs to call this after finishing the previous session,
+    /// and before sending any message of the next one.
+    pub async fn start_session(
+        &mut self,
+        session: &SessionId,
+    ) -> Result<(), CompressionError<Tr::Error>> {
+        let mut hello = vec![HELLO];
+        hello.extend_from_slice(session.as_bytes());
+        hello.extend_from_slice(Z::NAME);
+        let hello = MessageData::from(hello);
+        for &p in &self.peers {
+            self.transport
+                .send(p, hello.clone())
+                .await
+                .map_err(CompressionError::Transport)?;
+        }
+
+        // Until a peer announces the new session, its messages are still part of the previous one.
+        let previous = mem::take(&mut self.compress_for);
+        let mut pending = HashSet::with_capacity(self.peers.len());
+        for &p in &self.peers {
+            match self.announced.remove(&(p, *session)) {
+                Some(same) => {
+                    if same {
+                        self.compress_for.insert(p);
+                    }
+                }
+                None => {
+                    pending.insert(p);
+                }
+            }
+        }
+        // Peers might finish negotiating before us, and start sending messages.
+        while !pending.is_empty() {
+            let (from, data) = self.recv_raw().await?;
+            if data.first() == Some(&HELLO) {
+                let (theirs, name) =
+                    parse_hello(&data).ok_or(CompressionError::BadMessage(from))?;
+                if theirs != *session {
+                    self.announce(from, theirs, name)?;
+                } else if pending.remove(&from) {
+                    if name == Z::NAME {
+                        self.compress_for.insert(from);
+                    }
+                } else {
+                    return Err(CompressionError::BadMessage(from));
+                }
+                continue;
+            }
+            let agreed = if pending.contains(&from) {
+                previous.contains(&from)
+            } else {
+                self.compress_for.contains(&from)
+            };
+            let data = self.decompress(from, data, agreed)?;
+            self.buffered.push_back((from, data));
+        }
+        Ok(())
+    }
+
+    /// Receive the next message from the underlying transport, from a known peer.
+    async fn recv_raw(
+        &mut self,
+    ) -> Result<(Participant, MessageData), CompressionError<Tr::Error>> {
+        let (from, data) = self
+            .transport
+            .recv()
+            .await
+            .map_err(CompressionError::Transport)?;
+        if !self.peers.contains(&from) {
+            return Err(CompressionError::UnknownPeer(from));
+        }
+        Ok((from, data))
+    }
+
+    /// Remember an announcement for a session we haven't started yet.
+    fn announce(
+        &mut self,
+        from: Participant,
+        session: SessionId,
+        name: &[u8],
+    ) -> Result<(), CompressionError<Tr::Error>> {
+        if self
+            .announced
+            .insert((from, session), name == Z::NAME)
+            .is_some()
+        {
+            return Err(CompressionError::BadMessage(from));
+        }
+        Ok(())
+    }
+
+    /// Unwrap a message, decompressing it if it was compressed.
+    ///
+    /// Only peers which `agreed` to our codec can send us compressed messages.
+    fn decompress(
+        &self,
+        from: Participant,
+        data: MessageData,
+        agreed: bool,
+    ) -> Result<MessageData, CompressionError<Tr::Error>> {
+        let bad = || CompressionError::BadMessage(from);
+        match data.first() {
+            Some(&RAW) => Ok(data.slice(1..)),
+            Some(&COMPRESSED) if agreed && data.len() > LEN_LEN => {
+                let len = u64::from_le_bytes(data[1..1 + LEN_LEN].try_into().unwrap());
+                let len = usize::try_from(len)
+                    .ok()
+                    .filter(|&len| len <= self.settings.max_len)
+                    .ok_or_else(bad)?;
+                self.codec
+                    .decompress(&data[1 + LEN_LEN..], len)
+                    .filter(|out| out.len() == len)
+                    .map(MessageData::from)
+                    .ok_or_else(bad)
+            }
+            _ => Err(bad()),
+        }
+    }
+}
+
+impl<Tr: Transport + Send, Z: Codec> Transport for CompressedTransport<Tr, Z> {
+    type Error = CompressionError<Tr::Error>;
+
+    async fn send(&mut self, to: Participant, data: MessageData) -> Result<(), Self::Error> {
+        let mut out = None;
+        if data.len() >= self.settings.threshold && self.compress_for.contains(&to) {
+            let compressed = self.codec.compress(&data);
+            // Messages which don't shrink aren't worth decompressing.
+            if compressed.len() + LEN_LEN < data.len() {
+                let mut framed = Vec::with_capacity(1 + LEN_LEN + compressed.len());
+                framed.push(COMPRESSED);
+                framed.extend_from_slice(&(data.len() as u64).to_le_bytes());
+                framed.extend_from_slice(&compressed);
+                out = Some(framed);
+            }
+        }
+        let out = out.unwrap_or_else(|| {
+            let mut framed = Vec::with_capacity(1 + data.len());
+            framed.push(RAW);
+            framed.extend_from_slice(&data);
+            framed
+        });
+        self.transport
+            .send(to, out.into())
+            .await
+            .map_err(CompressionError::Transport)
+    }
+
+    async fn recv(&mut self) -> Result<(Participant, MessageData), Self::Error> {
+        if let Some(message) = self.buffered.pop_front() {
+            return Ok(message);
+        }
+        loop {
+            let (from, data) = self.recv_raw().await?;
+            // Peers finishing the session before us might already announce the next one.
+            if data.first() == Some(&HELLO) {
+                let (session, name) =
+                    parse_hello(&data).ok_or(CompressionError::BadMessage(from))?;
+                self.announce(from, session, name)?;
+                continue;
+            }
+            let agreed = self.compress_for.contains(&from);
+            return Ok((from, self.decompress(from, data, agreed)?));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::HashMap,
+        error::Error,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use async_channel::{Receiver, Sender};
+    use k256::Secp256k1;
+    use smol::{block_on, Executor};
+
+    use super::*;
+    use crate::{keygen, protocol::run_with_transport};
+
+    fn session(purpose: &[u8]) -> SessionId {
+        SessionId::derive(purpose, &[], 0, 0)
+    }
+
+    fn hello(session: &SessionId, name: &[u8]) -> MessageData {
+        let mut hello = vec![HELLO];
+        hello.extend_from_slice(session.as_bytes());
+        hello.extend_from_slice(name);
+        hello.into()
+    }
+
+    /// Run length encoding, which is enough to check that compression happens.
+    struct RunLength;
+
+    impl Codec for RunLength {
+        const NAME: &'static [u8] = b"run length";
+
+        fn compress(&self, data: &[u8]) -> Vec<u8> {
+            let mut out = Vec::new();
+            for chunk in data.chunk_by(|a, b| a == b) {
+                for run in chunk.chunks(255) {
+                    out.extend_from_slice(&[run.len() as u8, run[0]]);
+                }
+            }
+            out
+        }
+
+        fn decompress(&self, data: &[u8], len: usize) -> Option<Vec<u8>> {
+            let mut out = Vec::with_capacity(len);
+            for pair in data.chunks(2) {
+                let &[count, byte] = pair else {
+                    return None;
+                };
+                if out.len() + usize::from(count) > len {
+                    return None;
+                }
+                out.resize(out.len() + usize::from(count), byte);
+            }
+            Some(out)
+        }
+    }
+
+    /// Another codec, for peers which don't agree.
+    struct Other;
+
+    impl Codec for Other {
+        const NAME: &'static [u8] = b"other";
+
+        fn compress(&self, _: &[u8]) -> Vec<u8> {
+            unreachable!("nobody agrees to this codec")
+        }
+
+        fn decompress(&self, _: &[u8], _: usize) -> Option<Vec<u8>> {
+            None
+        }
+    }
+
+    /// A transport over in memory channels, counting the bytes it sends.
+    struct ChannelTransport {
+        me: Participant,
+        senders: HashMap<Participant, Sender<(Participant, MessageData)>>,
+        incoming: Receiver<(Participant, MessageData)>,
+        sent: Arc<AtomicUsize>,
+    }
+
+    #[derive(Debug)]
+    struct Closed;
+
+    impl fmt::Display for Closed {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "channel closed")
+        }
+    }
+
+    impl error::Error for Closed {}
+
+    impl Transport for ChannelTransport {
+        type Error = Closed;
+
+        async fn send(&mut self, to: Participant, data: MessageData) -> Result<(), Closed> {
+            self.sent.fetch_add(data.len(), Ordering::SeqCst);
+            self.senders[&to]
+                .send((self.me, data))
+                .await
+                .map_err(|_| Closed)
+        }
+
+        async fn recv(&mut self) -> Result<(Participant, MessageData), Closed> {
+            self.incoming.recv().await.map_err(|_| Closed)
+        }
+    }
+
+    fn transports(participants: &[Participant], sent: &Arc<AtomicUsize>) -> Vec<ChannelTransport> {
+        let (senders, receivers): (HashMap<_, _>, Vec<_>) = participants
+            .iter()
+            .map(|&p| {
+                let (s, r) = async_channel::unbounded();
+                ((p, s), r)
+            })
+            .unzip();
+        participants
+            .iter()
+            .zip(receivers)
+            .map(|(&me, incoming)| ChannelTransport {
+                me,
+                senders: senders.clone(),
+                incoming,
+                sent: sent.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_compressed_keygen() -> Result<(), Box<dyn Error>> {
+        let participants: Vec<_> = (0..3u32).map(Participant::from).collect();
+        let sent = Arc::new(AtomicUsize::new(0));
+        let settings = CompressionSettings {
+            threshold: 0,
+            ..Default::default()
+        };
+
+        let ex = Executor::new();
+        let results = block_on(ex.run(async {
+            let tasks: Vec<_> = transports(&participants, &sent)
+                .into_iter()
+                .map(|transport| {
+                    let me = transport.me;
+                    let participants = participants.clone();
+                    ex.spawn(async move {
+                        let mut transport = CompressedTransport::connect(
+                            transport,
+                            me,
+                            &participants,
+                            &session(b"keygen"),
+                            RunLength,
+                            settings,
+                        )
+                        .await?;
+                        let protocol = keygen::<Secp256k1>(&participants, me, 2)?;
+                        let out =
+                            run_with_transport(protocol, &participants, me, &mut transport).await?;
+                        Ok::<_, Box<dyn Error + Send + Sync>>(out)
+                    })
+                })
+                .collect();
+            let mut results = Vec::new();
+            for task in tasks {
+                results.push(task.await?);
+            }
+            Ok::<_, Box<dyn Error + Send + Sync>>(results)
+        }))
+        .map_err(|e| e as Box<dyn Error>)?;
+
+        assert_eq!(results[0].public_key, results[1].public_key);
+        assert_eq!(results[1].public_key, results[2].public_key);
+        Ok(())
+    }
+
+    /// Send a message from a participant using one codec, to one using another.
+    fn send_one<Y: Codec, Z: Codec>(
+        sender: Y,
+        receiver: Z,
+        message: &[u8],
+    ) -> Result<(MessageData, usize), Box<dyn Error>> {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let settings = CompressionSettings::default();
+        let sent = Arc::new(AtomicUsize::new(0));
+        let mut transports = transports(&participants, &sent);
+        let t1 = transports.pop().unwrap();
+        let t0 = transports.pop().unwrap();
+        let session = session(b"send one");
+        let (from, data) = block_on(async {
+            let (a, b) = futures_lite::future::zip(
+                CompressedTransport::connect(
+                    t0,
+                    participants[0],
+                    &participants,
+                    &session,
+                    sender,
+                    settings,
+                ),
+                CompressedTransport::connect(
+                    t1,
+                    participants[1],
+                    &participants,
+                    &session,
+                    receiver,
+                    settings,
+                ),
+            )
+            .await;
+            let (mut a, mut b) = (a?, b?);
+            a.send(participants[1], MessageData::copy_from_slice(message))
+                .await?;
+            Ok::<_, Box<dyn Error>>(b.recv().await?)
+        })?;
+        assert_eq!(from, participants[0]);
+        Ok((data, sent.load(Ordering::SeqCst)))
+    }
+
+    #[test]
+    fn test_compression_is_negotiated() -> Result<(), Box<dyn Error>> {
+        let message = vec![7u8; 100_000];
+
+        let (received, compressed_len) = send_one(RunLength, RunLength, &message)?;
+        assert_eq!(received, message);
+        assert!(compressed_len < 10_000);
+
+        let (received, raw_len) = send_one(RunLength, Other, &message)?;
+        assert_eq!(received, message);
+        assert!(raw_len > message.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lz4_round_trip() -> Result<(), Box<dyn Error>> {
+        let message: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Lz4.compress(&message);
+        assert!(compressed.len() < message.len() / 10);
+        assert_eq!(
+            Lz4.decompress(&compressed, message.len()),
+            Some(message.clone())
+        );
+        assert_eq!(Lz4.decompress(&compressed, message.len() - 1), None);
+        assert_eq!(Lz4.decompress(&compressed, message.len() + 1), None);
+        // Lengths no block this short could decompress to are rejected before allocating.
+        assert_eq!(Lz4.decompress(&compressed[..10], usize::MAX), None);
+
+        let (received, sent) = send_one(Lz4, Lz4, &message)?;
+        assert_eq!(received, message);
+        assert!(sent < message.len() / 10);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zstd_round_trip() -> Result<(), Box<dyn Error>> {
+        let message: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+        let compressed = Zstd::default().compress(&message);
+        assert!(compressed.len() < message.len() / 10);
+        assert_eq!(
+            Zstd::default().decompress(&compressed, message.len()),
+            Some(message.clone())
+        );
+        assert_eq!(
+            Zstd::default().decompress(&compressed, message.len() - 1),
+            None
+        );
+        assert_eq!(
+            Zstd::default().decompress(&compressed, message.len() + 1),
+            None
+        );
+        // The buffer only grows as far as the data goes, whatever length is claimed.
+        assert_eq!(
+            Zstd::default().decompress(&compressed, usize::MAX >> 1),
+            None
+        );
+
+        let (received, sent) = send_one(Zstd::default(), Zstd::default(), &message)?;
+        assert_eq!(received, message);
+        assert!(sent < message.len() / 10);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compression_is_negotiated_per_session() -> Result<(), Box<dyn Error>> {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let sent = Arc::new(AtomicUsize::new(0));
+        let mut transports = transports(&participants, &sent);
+        let t1 = transports.pop().unwrap();
+        let mut t0 = transports.pop().unwrap();
+        let settings = CompressionSettings::default();
+        let message = vec![7u8; 100_000];
+
+        let result = block_on(async {
+            // The second session is announced before the first one even starts.
+            t0.send(participants[1], hello(&session(b"first"), RunLength::NAME))
+                .await?;
+            t0.send(participants[1], hello(&session(b"second"), Other::NAME))
+                .await?;
+            let mut b = CompressedTransport::connect(
+                t1,
+                participants[1],
+                &participants,
+                &session(b"first"),
+                RunLength,
+                settings,
+            )
+            .await?;
+
+            // Compressing for the first session is fine.
+            let mut compressed = vec![COMPRESSED];
+            compressed.extend_from_slice(&(message.len() as u64).to_le_bytes());
+            compressed.extend_from_slice(&RunLength.compress(&message));
+            t0.send(participants[1], compressed.clone().into()).await?;
+            let first = b.recv().await?;
+
+            // But not in the second one, where the peer announced another codec.
+            b.start_session(&session(b"second")).await?;
+            t0.send(participants[1], compressed.into()).await?;
+            Ok::<_, Box<dyn Error>>((first, b.recv().await))
+        })?;
+        assert_eq!(result.0, (participants[0], MessageData::from(message)));
+        assert!(matches!(result.1, Err(CompressionError::BadMessage(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_compressed_rejects_bad_messages() {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let sent = Arc::new(AtomicUsize::new(0));
+        let mut transports = transports(&participants, &sent);
+        let t1 = transports.pop().unwrap();
+        let mut t0 = transports.pop().unwrap();
+        let settings = CompressionSettings {
+            threshold: 0,
+            max_len: 1000,
+        };
+
+        let result = block_on(async {
+            t0.send(participants[1], hello(&session(b"bad"), RunLength::NAME))
+                .await
+                .unwrap();
+            let mut b = CompressedTransport::connect(
+                t1,
+                participants[1],
+                &participants,
+                &session(b"bad"),
+                RunLength,
+                settings,
+            )
+            .await
+            .unwrap();
+
+            // A message claiming to decompress to more than the limit.
+            let mut bomb = vec![COMPRESSED];
+            bomb.extend_from_slice(&1001u64.to_le_bytes());
+            bomb.extend_from_slice(&RunLength.compress(&[0; 1001]));
+            t0.send(participants[1], bomb.into()).await.unwrap();
+            let bomb = b.recv().await;
+
+            // A message decompressing to less than it claims.
+            let mut short = vec![COMPRESSED];
+            short.extend_from_slice(&100u64.to_le_bytes());
+            short.extend_from_slice(&RunLength.compress(&[0; 99]));
+            t0.send(participants[1], short.into()).await.unwrap();
+            (bomb, b.recv().await)
+        });
+        assert!(matches!(result.0, Err(CompressionError::BadMessage(_))));
+        assert!(matches!(result.1, Err(CompressionError::BadMessage(_))));
+    }
+}
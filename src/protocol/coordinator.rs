@@ -0,0 +1,467 @@
+//! Running protocols through a designated coordinator, in a star topology.
+//!
+//! Protocols assume that every participant can reach every other one, which is hard
+//! to arrange for co-signers on mobile devices, behind NATs. Instead, each participant
+//! can connect to a single coordinator, with a public address, which forwards messages
+//! between them. Each message then travels as a [`RoutedMessage`], which says who it's from,
+//! and who it's for, so that the coordinator doesn't need to understand the protocol.
+//!
+//! On the side of the participants, [`CoordinatedTransport`] turns a connection to the
+//! coordinator into a [`Transport`] reaching everyone, and [`RoutedMessage::outgoing`]
+//! does the same for the messages produced by [`run_async`](super::run_async).
+//! On the side of the coordinator, [`Coordinator::route`] takes each message along with
+//! the participant whose connection it arrived on, and says where to forward it.
+//!
+//! The coordinator only needs to be available: it learns who talks to whom, and when,
+//! but need not be trusted with anything else, as long as participants wrap their
+//! [`CoordinatedTransport`] with a [`SecureTransport`](super::SecureTransport).
+//! Without that layer, the coordinator can read private messages, and pretend to be
+//! any participant, since the sender of each message is whatever the coordinator says.
+//!
+//! A routed message consists of:
+//!
+//! - a byte, 0 for a message to everyone else, or 1 for a message to a single participant;
+//! - the sender, as 4 little endian bytes;
+//! - the recipient, as 4 little endian bytes, for a message to a single participant;
+//! - the message itself.
+use std::{error, fmt};
+
+use super::{
+    driver::Outgoing, transport::Transport, InitializationError, MessageData, Participant,
+};
+use crate::participants::ParticipantList;
+
+const EVERYONE: u8 = 0;
+const ONE: u8 = 1;
+
+const ID_LEN: usize = 4;
+
+/// Represents an error with a message routed through a coordinator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoutingError {
+    /// The message couldn't be parsed.
+    Malformed,
+    /// The message came from, or was addressed to, someone who isn't a participant.
+    UnknownParticipant(Participant),
+    /// The message was addressed to its own sender.
+    SelfAddressed,
+}
+
+impl fmt::Display for RoutingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed => write!(f, "malformed routed message"),
+            Self::UnknownParticipant(p) => write!(f, "unknown participant {:?}", p),
+            Self::SelfAddressed => write!(f, "message addressed to its own sender"),
+        }
+    }
+}
+
+impl error::Error for RoutingError {}
+
+/// Who a routed message should be delivered to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recipient {
+    /// Every participant but the sender.
+    Everyone,
+    /// A single participant.
+    One(Participant),
+}
+
+/// A message, along with what a coordinator needs to forward it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutedMessage {
+    /// The sender of the message.
+    pub from: Participant,
+    /// The recipient of the message.
+    pub to: Recipient,
+    /// The message itself.
+    pub data: MessageData,
+}
+
+impl RoutedMessage {
+    /// Wrap a message produced by [`run_async`](super::run_async), to send to the coordinator.
+    ///
+    /// Messages to everyone stay a single message, which the coordinator copies for each
+    /// recipient, so that participants with little bandwidth only upload them once.
+    pub fn outgoing(me: Participant, outgoing: Outgoing) -> Self {
+        let (to, data) = match outgoing {
+            Outgoing::Many(data) => (Recipient::Everyone, data),
+            Outgoing::Private(to, data) => (Recipient::One(to), data),
+        };
+        Self { from: me, to, data }
+    }
+
+    /// Encode this message.
+    pub fn to_bytes(&self) -> MessageData {
+        let mut out = Vec::with_capacity(1 + 2 * ID_LEN + self.data.len());
+        match self.to {
+            Recipient::Everyone => {
+                out.push(EVERYONE);
+                out.extend_from_slice(&self.from.bytes());
+            }
+            Recipient::One(to) => {
+                out.push(ONE);
+                out.extend_from_slice(&self.from.bytes());
+                out.extend_from_slice(&to.bytes());
+            }
+        }
+        out.extend_from_slice(&self.data);
+        out.into()
+    }
+
+    /// Decode a message encoded with [`RoutedMessage::to_bytes`].
+    ///
+    /// The contents of the message share their bytes with the encoding, rather than being copied.
+    pub fn from_bytes(bytes: &MessageData) -> Result<Self, RoutingError> {
+        let (&tag, rest) = bytes.split_first().ok_or(RoutingError::Malformed)?;
+        let (from, rest) = read_id(rest)?;
+        let (to, rest) = match tag {
+            EVERYONE => (Recipient::Everyone, rest),
+            ONE => {
+                let (to, rest) = read_id(rest)?;
+                (Recipient::One(to), rest)
+            }
+            _ => return Err(RoutingError::Malformed),
+        };
+        Ok(Self {
+            from,
+            to,
+            data: bytes.slice_ref(rest),
+        })
+    }
+}
+
+/// Read a participant from the start of some bytes, returning the rest.
+fn read_id(bytes: &[u8]) -> Result<(Participant, &[u8]), RoutingError> {
+    if bytes.len() < ID_LEN {
+        return Err(RoutingError::Malformed);
+    }
+    let (id, rest) = bytes.split_at(ID_LEN);
+    Ok((u32::from_le_bytes(id.try_into().unwrap()).into(), rest))
+}
+
+/// Forwards messages between the participants of a protocol, in a star topology.
+#[derive(Debug, Clone)]
+pub struct Coordinator {
+    participants: ParticipantList,
+}
+
+impl Coordinator {
+    /// Create a coordinator for some participants.
+    pub fn new(participants: &[Participant]) -> Result<Self, InitializationError> {
+        let participants = ParticipantList::new(participants).ok_or_else(|| {
+            InitializationError::BadParameters(
+                "participant list cannot contain duplicates".to_string(),
+            )
+        })?;
+        Ok(Self { participants })
+    }
+
+    /// Route a message which arrived on the connection of some participant.
+    ///
+    /// This returns the encoded message to forward to each of its recipients.
+    /// The sender is always taken to be `sender`, whatever the message says,
+    /// so that participants can't impersonate each other through the coordinator,
+    /// as long as the coordinator authenticates their connections.
+    pub fn route(
+        &self,
+        sender: Participant,
+        message: &MessageData,
+    ) -> Result<Vec<(Participant, MessageData)>, RoutingError> {
+        if !self.participants.contains(sender) {
+            return Err(RoutingError::UnknownParticipant(sender));
+        }
+        let mut message = RoutedMessage::from_bytes(message)?;
+        message.from = sender;
+        match message.to {
+            Recipient::Everyone => {
+                let bytes = message.to_bytes();
+                Ok(self
+                    .participants
+                    .others(sender)
+                    .map(|to| (to, bytes.clone()))
+                    .collect())
+            }
+            Recipient::One(to) if to == sender => Err(RoutingError::SelfAddressed),
+            Recipient::One(to) if !self.participants.contains(to) => {
+                Err(RoutingError::UnknownParticipant(to))
+            }
+            Recipient::One(to) => Ok(vec![(to, message.to_bytes())]),
+        }
+    }
+}
+
+/// Represents an error which can happen when using a [`CoordinatedTransport`].
+#[derive(Debug)]
+pub enum CoordinatedTransportError<E> {
+    /// The underlying transport failed.
+    Transport(E),
+    /// We received a message from someone other than the coordinator.
+    UnknownPeer(Participant),
+    /// The coordinator forwarded a bad message.
+    Routing(RoutingError),
+}
+
+impl<E: fmt::Display> fmt::Display for CoordinatedTransportError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "transport error: {}", e),
+            Self::UnknownPeer(p) => write!(f, "message from {:?}, instead of the coordinator", p),
+            Self::Routing(e) => write!(f, "bad message from the coordinator: {}", e),
+        }
+    }
+}
+
+impl<E: error::Error + 'static> error::Error for CoordinatedTransportError<E> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e),
+            Self::Routing(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// A transport reaching every participant through a coordinator.
+///
+/// The underlying transport only needs to reach the coordinator, which it
+/// identifies as some participant, outside of those running the protocol.
+pub struct CoordinatedTransport<Tr> {
+    transport: Tr,
+    me: Participant,
+    coordinator: Participant,
+}
+
+impl<Tr: Transport + Send> CoordinatedTransport<Tr> {
+    /// Wrap a transport reaching the coordinator, identified by `coordinator`.
+    pub fn new(transport: Tr, me: Participant, coordinator: Participant) -> Self {
+        Self {
+            transport,
+            me,
+            coordinator,
+        }
+    }
+}
+
+impl<Tr: Transport + Send> Transport for CoordinatedTransport<Tr> {
+    type Error = CoordinatedTransportError<Tr::Error>;
+
+    async fn send(&mut self, to: Participant, data: MessageData) -> Result<(), Self::Error> {
+        let message = RoutedMessage {
+            from: self.me,
+            to: Recipient::One(to),
+            data,
+        };
+        self.transport
+            .send(self.coordinator, message.to_bytes())
+            .await
+            .map_err(CoordinatedTransportError::Transport)
+    }
+
+    async fn recv(&mut self) -> Result<(Participant, MessageData), Self::Error> {
+        let (from, data) = self
+            .transport
+            .recv()
+            .await
+            .map_err(CoordinatedTransportError::Transport)?;
+        if from != self.coordinator {
+            return Err(CoordinatedTransportError::UnknownPeer(from));
+        }
+        let message =
+            RoutedMessage::from_bytes(&data).map_err(CoordinatedTransportError::Routing)?;
+        match message.to {
+            Recipient::One(to) if to != self.me => Err(CoordinatedTransportError::Routing(
+                RoutingError::UnknownParticipant(to),
+            )),
+            _ => Ok((message.from, message.data)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, error::Error};
+
+    use async_channel::{Receiver, Sender};
+    use k256::Secp256k1;
+    use rand_core::OsRng;
+    use smol::{block_on, Executor};
+
+    use super::*;
+    use crate::{
+        keygen,
+        protocol::{run_with_transport, IdentityKey, SecureTransport, SessionId},
+    };
+
+    /// A connection between a participant and the coordinator.
+    struct Link {
+        me: Participant,
+        coordinator: Participant,
+        outgoing: Sender<(Participant, MessageData)>,
+        incoming: Receiver<MessageData>,
+    }
+
+    #[derive(Debug)]
+    struct Closed;
+
+    impl fmt::Display for Closed {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "channel closed")
+        }
+    }
+
+    impl error::Error for Closed {}
+
+    impl Transport for Link {
+        type Error = Closed;
+
+        async fn send(&mut self, to: Participant, data: MessageData) -> Result<(), Closed> {
+            assert_eq!(to, self.coordinator);
+            self.outgoing
+                .send((self.me, data))
+                .await
+                .map_err(|_| Closed)
+        }
+
+        async fn recv(&mut self) -> Result<(Participant, MessageData), Closed> {
+            let data = self.incoming.recv().await.map_err(|_| Closed)?;
+            Ok((self.coordinator, data))
+        }
+    }
+
+    #[test]
+    fn test_coordinated_keygen() -> Result<(), Box<dyn Error>> {
+        let participants: Vec<_> = (0..3u32).map(Participant::from).collect();
+        let coordinator_id = Participant::from(100u32);
+        let coordinator = Coordinator::new(&participants)?;
+        let identities: Vec<_> = participants
+            .iter()
+            .map(|_| IdentityKey::generate(&mut OsRng))
+            .collect();
+        let peers: HashMap<_, _> = participants
+            .iter()
+            .zip(&identities)
+            .map(|(&p, key)| (p, key.public_key()))
+            .collect();
+
+        let (to_coordinator, inbox) = async_channel::unbounded();
+        let mut outboxes = HashMap::new();
+        let mut links = Vec::new();
+        for &me in &participants {
+            let (s, r) = async_channel::unbounded();
+            outboxes.insert(me, s);
+            links.push(Link {
+                me,
+                coordinator: coordinator_id,
+                outgoing: to_coordinator.clone(),
+                incoming: r,
+            });
+        }
+
+        let ex = Executor::new();
+        ex.spawn(async move {
+            while let Ok((from, data)) = inbox.recv().await {
+                for (to, data) in coordinator.route(from, &data).unwrap() {
+                    outboxes[&to].send(data).await.unwrap();
+                }
+            }
+        })
+        .detach();
+
+        let results = block_on(ex.run(async {
+            let tasks: Vec<_> = links
+                .into_iter()
+                .zip(identities)
+                .map(|(link, identity)| {
+                    let me = link.me;
+                    let participants = participants.clone();
+                    let peers = peers.clone();
+                    ex.spawn(async move {
+                        let transport = CoordinatedTransport::new(link, me, coordinator_id);
+                        let session = SessionId::derive(b"test", &participants, 0, 0);
+                        let mut transport =
+                            SecureTransport::connect(transport, me, &identity, &peers, &session)
+                                .await?;
+                        let protocol = keygen::<Secp256k1>(&participants, me, 2)?;
+                        let out =
+                            run_with_transport(protocol, &participants, me, &mut transport).await?;
+                        Ok::<_, Box<dyn Error + Send + Sync>>(out)
+                    })
+                })
+                .collect();
+            let mut results = Vec::new();
+            for task in tasks {
+                results.push(task.await?);
+            }
+            Ok::<_, Box<dyn Error + Send + Sync>>(results)
+        }))
+        .map_err(|e| e as Box<dyn Error>)?;
+
+        assert_eq!(results[0].public_key, results[1].public_key);
+        assert_eq!(results[1].public_key, results[2].public_key);
+        Ok(())
+    }
+
+    #[test]
+    fn test_route() {
+        let participants: Vec<_> = (0..3u32).map(Participant::from).collect();
+        let coordinator = Coordinator::new(&participants).unwrap();
+        assert!(Coordinator::new(&[participants[0], participants[0]]).is_err());
+
+        // The coordinator copies messages to everyone, and overwrites the sender.
+        let message = RoutedMessage::outgoing(
+            participants[1],
+            Outgoing::Many(MessageData::from_static(b"hi")),
+        );
+        let routed = coordinator.route(participants[0], &message.to_bytes());
+        let routed = routed.unwrap();
+        assert_eq!(
+            routed.iter().map(|(to, _)| *to).collect::<Vec<_>>(),
+            participants[1..]
+        );
+        // Everyone gets the same buffer, rather than a copy of it.
+        assert!(routed
+            .windows(2)
+            .all(|w| w[0].1.as_ptr() == w[1].1.as_ptr()));
+        for (_, bytes) in &routed {
+            let message = RoutedMessage::from_bytes(bytes).unwrap();
+            assert_eq!(message.from, participants[0]);
+            assert_eq!(message.to, Recipient::Everyone);
+            assert_eq!(message.data, b"hi"[..]);
+        }
+
+        let private = |to| {
+            RoutedMessage::outgoing(
+                participants[0],
+                Outgoing::Private(to, MessageData::from_static(b"psst")),
+            )
+            .to_bytes()
+        };
+        let routed = coordinator.route(participants[0], &private(participants[2]));
+        assert_eq!(
+            routed.unwrap(),
+            vec![(participants[2], private(participants[2]))]
+        );
+        assert_eq!(
+            coordinator.route(participants[0], &private(participants[0])),
+            Err(RoutingError::SelfAddressed)
+        );
+        let stranger = Participant::from(7u32);
+        assert_eq!(
+            coordinator.route(participants[0], &private(stranger)),
+            Err(RoutingError::UnknownParticipant(stranger))
+        );
+        assert_eq!(
+            coordinator.route(stranger, &private(participants[0])),
+            Err(RoutingError::UnknownParticipant(stranger))
+        );
+        assert_eq!(
+            coordinator.route(
+                participants[0],
+                &MessageData::from_static(&[ONE, 0, 0, 0, 0, 1])
+            ),
+            Err(RoutingError::Malformed)
+        );
+    }
+}
@@ -0,0 +1,156 @@
+//! This module provides a way to put a deadline on each round of a protocol.
+//!
+//! A protocol waiting for messages will wait forever, since it has no way
+//! of knowing if a message is just late, or if it will never come.
+//! [`Deadline`] wraps a protocol, and keeps track of when it last made progress,
+//! and of which participants it heard from since then.
+//! Once it's been waiting for longer than its timeout, poking it returns
+//! [`ProtocolError::Timeout`], with the participants that stayed silent,
+//! so that the caller can abort, or escalate, instead of hanging.
+//!
+//! A round starts whenever the protocol sends a message or returns, which works well
+//! for our protocols, where each participant usually speaks in each round.
+//! With concurrent sub-protocols, like in triple generation, this is only a heuristic.
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+use super::{Action, Format, MessageData, Participant, Protocol, ProtocolError, QueueLimits};
+
+/// A protocol, with a deadline on each round.
+pub struct Deadline<P> {
+    protocol: P,
+    others: Vec<Participant>,
+    timeout: Duration,
+    round_start: Instant,
+    heard: HashSet<Participant>,
+}
+
+impl<P: Protocol> Deadline<P> {
+    /// Wrap a protocol, run by `me` with some participants, with a timeout for each round.
+    pub fn new(
+        protocol: P,
+        participants: &[Participant],
+        me: Participant,
+        timeout: Duration,
+    ) -> Self {
+        Self {
+            protocol,
+            others: participants.iter().copied().filter(|&p| p != me).collect(),
+            timeout,
+            round_start: Instant::now(),
+            heard: HashSet::new(),
+        }
+    }
+
+    /// The participants we haven't heard from since the start of this round.
+    pub fn waiting_on(&self) -> Vec<Participant> {
+        self.others
+            .iter()
+            .copied()
+            .filter(|p| !self.heard.contains(p))
+            .collect()
+    }
+
+    /// Unwrap the underlying protocol.
+    pub fn into_inner(self) -> P {
+        self.protocol
+    }
+}
+
+impl<P: Protocol> Protocol for Deadline<P> {
+    type Output = P::Output;
+
+    /// Poke the underlying protocol.
+    ///
+    /// This returns [`ProtocolError::Timeout`] if the protocol is waiting,
+    /// and the round deadline has passed.
+    /// This error isn't fatal: the protocol can still be given messages, and poked again.
+    fn poke(&mut self) -> Result<Action<Self::Output>, ProtocolError> {
+        let action = self.protocol.poke()?;
+        match action {
+            Action::Wait => {
+                if self.round_start.elapsed() >= self.timeout {
+                    return Err(ProtocolError::Timeout(self.waiting_on()));
+                }
+            }
+            _ => {
+                self.round_start = Instant::now();
+                self.heard.clear();
+            }
+        }
+        Ok(action)
+    }
+
+    fn message(&mut self, from: Participant, data: MessageData) {
+        self.heard.insert(from);
+        self.protocol.message(from, data);
+    }
+
+    fn set_queue_limits(&mut self, limits: QueueLimits) {
+        self.protocol.set_queue_limits(limits)
+    }
+
+    fn set_format(&mut self, format: Format) {
+        self.protocol.set_format(format)
+    }
+
+    fn cancel(&mut self) {
+        self.protocol.cancel()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use k256::Secp256k1;
+
+    use super::*;
+    use crate::keygen;
+
+    #[test]
+    fn test_deadline() {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let silent = participants[2];
+
+        // The last participant never does anything.
+        let mut protocols: Vec<_> = participants[..2]
+            .iter()
+            .map(|&p| {
+                let protocol = keygen::<Secp256k1>(&participants, p, 2).unwrap();
+                (p, Deadline::new(protocol, &participants, p, Duration::ZERO))
+            })
+            .collect();
+
+        let mut messages = Vec::new();
+        for (p, protocol) in protocols.iter_mut() {
+            loop {
+                match protocol.poke() {
+                    Ok(Action::SendMany(m)) => messages.push((*p, m)),
+                    Ok(Action::SendPrivate(to, m)) if to != silent => messages.push((*p, m)),
+                    Ok(Action::SendPrivate(..)) => {}
+                    Ok(_) => panic!("unexpected action"),
+                    Err(ProtocolError::Timeout(_)) => break,
+                    Err(e) => panic!("unexpected error: {e}"),
+                }
+            }
+        }
+        for (from, m) in messages {
+            for (p, protocol) in protocols.iter_mut() {
+                if *p != from {
+                    protocol.message(from, m.clone());
+                }
+            }
+        }
+        for (_, protocol) in protocols.iter_mut() {
+            match protocol.poke() {
+                Err(ProtocolError::Timeout(ps)) => assert_eq!(ps, vec![silent]),
+                _ => panic!("expected a timeout"),
+            }
+        }
+    }
+}
@@ -0,0 +1,150 @@
+//! This module provides a way to drive protocols from async code.
+//!
+//! The [`Protocol`] trait is synchronous, and leaves it up to the caller to figure
+//! out when to poke the protocol, and when to wait for new messages.
+//! In an async networking stack, this loop is always the same: keep poking
+//! the protocol, forwarding any messages it produces, and once it asks us to wait,
+//! sleep until a new message arrives.
+//!
+//! [`run_async`] implements this loop once, on top of the runtime agnostic
+//! `Stream` and `Future` abstractions, so that it can be used with any executor.
+use std::{error, future::Future, pin::pin};
+
+use futures_lite::stream::{Stream, StreamExt};
+
+use super::{Action, MessageData, Participant, Protocol, ProtocolError};
+
+/// Represents a message that a protocol wants delivered to other participants.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outgoing {
+    /// This message should be sent to all other participants.
+    Many(MessageData),
+    /// This message should be sent privately, to a single participant.
+    Private(Participant, MessageData),
+}
+
+/// Run a protocol to completion, asynchronously.
+///
+/// Messages the protocol wants to send are passed to `send`, and messages
+/// received from other participants are pulled from `incoming`.
+///
+/// The `send` function is awaited before the protocol is poked again,
+/// so it can use this to apply backpressure.
+///
+/// This returns an error if the protocol fails, if sending a message fails,
+/// or if `incoming` ends before the protocol has produced an output.
+///
+/// Note that poking a protocol performs its computation on the current thread,
+/// which can take a while for protocols like triple generation.
+pub async fn run_async<T, P, S, F, R>(
+    mut protocol: P,
+    mut send: S,
+    incoming: R,
+) -> Result<T, ProtocolError>
+where
+    P: Protocol<Output = T>,
+    S: FnMut(Outgoing) -> F,
+    F: Future<Output = Result<(), Box<dyn error::Error + Send + Sync>>>,
+    R: Stream<Item = (Participant, MessageData)>,
+{
+    let mut incoming = pin!(incoming);
+    loop {
+        match protocol.poke()? {
+            Action::Wait => match incoming.next().await {
+                Some((from, data)) => protocol.message(from, data),
+                None => {
+                    return Err(ProtocolError::Other(
+                        "incoming messages ended before the protocol finished".into(),
+                    ))
+                }
+            },
+            Action::SendMany(data) => send(Outgoing::Many(data)).await?,
+            Action::SendPrivate(to, data) => send(Outgoing::Private(to, data)).await?,
+            Action::Return(out) => return Ok(out),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, error::Error};
+
+    use k256::{ProjectivePoint, Secp256k1};
+    use smol::{block_on, channel, future, Executor};
+
+    use super::*;
+    use crate::{keygen, participants::ParticipantList};
+
+    #[test]
+    fn test_run_async_keygen() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+
+        let mut senders = HashMap::new();
+        let mut receivers = Vec::new();
+        for p in &participants {
+            let (s, r) = channel::unbounded::<(Participant, MessageData)>();
+            senders.insert(*p, s);
+            receivers.push((*p, r));
+        }
+
+        let ex = Executor::new();
+        let mut tasks = Vec::new();
+        for (me, incoming) in receivers {
+            let protocol = keygen::<Secp256k1>(&participants, me, threshold)?;
+            let senders = senders.clone();
+            let send = move |out: Outgoing| {
+                let senders = senders.clone();
+                async move {
+                    match out {
+                        Outgoing::Many(data) => {
+                            for (p, s) in &senders {
+                                if *p != me {
+                                    s.send((me, data.clone())).await?;
+                                }
+                            }
+                        }
+                        Outgoing::Private(to, data) => senders[&to].send((me, data)).await?,
+                    }
+                    Ok(())
+                }
+            };
+            tasks.push(ex.spawn(run_async(protocol, send, incoming)));
+        }
+
+        let outputs = block_on(ex.run(async {
+            let mut outputs = Vec::new();
+            for task in tasks {
+                outputs.push(task.await);
+            }
+            outputs
+        }));
+
+        let outputs = outputs.into_iter().collect::<Result<Vec<_>, _>>()?;
+        let public_key = outputs[0].public_key;
+        assert!(outputs.iter().all(|out| out.public_key == public_key));
+
+        let p_list = ParticipantList::new(&participants[..2]).unwrap();
+        let x = p_list.lagrange::<Secp256k1>(participants[0]) * outputs[0].private_share
+            + p_list.lagrange::<Secp256k1>(participants[1]) * outputs[1].private_share;
+        assert_eq!(ProjectivePoint::GENERATOR * x, public_key);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_async_closed_incoming() {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let protocol = keygen::<Secp256k1>(&participants, participants[0], 2).unwrap();
+        let (_, incoming) = channel::unbounded::<(Participant, MessageData)>();
+        incoming.close();
+
+        let send = |_: Outgoing| future::ready(Ok(()));
+        let res = block_on(run_async(protocol, send, incoming));
+        assert!(res.is_err());
+    }
+}
@@ -0,0 +1,309 @@
+//! This module provides a way to run protocols with external participant identifiers.
+//!
+//! Internally, participants are identified by a [`Participant`], which is just
+//! a number. Applications usually have their own identifiers, like peer ids
+//! or account addresses, and would otherwise need to maintain a mapping between
+//! the two, which all parties have to agree on.
+//!
+//! [`ParticipantIds`] derives the participant of each identifier from that identifier
+//! alone, by hashing it, so every party computes the same participants independently,
+//! and an identifier keeps its participant in every set it's part of. A key generated
+//! by some identifiers can then be used by any subset of them.
+//! [`WithIds`] then wraps a protocol so that it can be driven using these
+//! identifiers directly.
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use super::{Action, InitializationError, MessageData, Participant, Protocol, ProtocolError};
+use crate::crypto::hash;
+
+/// A canonical mapping between external identifiers and participants.
+///
+/// The participant for an identifier is the first 4 bytes of its hash, see
+/// [`ParticipantIds::participant_of`], which doesn't depend on the other identifiers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParticipantIds<Id> {
+    ids: BTreeMap<Participant, Id>,
+    participants: BTreeMap<Id, Participant>,
+}
+
+impl<Id: Ord + Clone + Serialize> ParticipantIds<Id> {
+    /// Create a new mapping from a list of identifiers, in any order.
+    ///
+    /// This fails if the list contains duplicates, or, with negligible probability
+    /// for random identifiers, two identifiers with the same participant.
+    pub fn new(ids: &[Id]) -> Result<Self, InitializationError> {
+        let mut out = Self {
+            ids: BTreeMap::new(),
+            participants: BTreeMap::new(),
+        };
+        for id in ids {
+            let participant = Self::participant_of(id);
+            if out.participants.insert(id.clone(), participant).is_some() {
+                return Err(InitializationError::BadParameters(
+                    "participant identifiers must be unique".to_string(),
+                ));
+            }
+            if out.ids.insert(participant, id.clone()).is_some() {
+                return Err(InitializationError::BadParameters(
+                    "two participant identifiers have the same participant".to_string(),
+                ));
+            }
+        }
+        Ok(out)
+    }
+
+    /// The participant of an identifier, in any mapping containing it.
+    pub fn participant_of(id: &Id) -> Participant {
+        let digest = hash(id);
+        let bytes: [u8; 4] = digest.as_ref()[..4].try_into().unwrap();
+        Participant::from(u32::from_be_bytes(bytes))
+    }
+
+    /// The number of participants.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// All the participants, in the same order as their identifiers.
+    pub fn participants(&self) -> Vec<Participant> {
+        self.participants.values().copied().collect()
+    }
+
+    /// Return the participant for an identifier, if it's part of this mapping.
+    pub fn participant(&self, id: &Id) -> Option<Participant> {
+        self.participants.get(id).copied()
+    }
+
+    /// Return the identifier for a participant, if it's part of this mapping.
+    pub fn id(&self, participant: Participant) -> Option<&Id> {
+        self.ids.get(&participant)
+    }
+}
+
+/// Like [`Action`], except that participants are referred to by their identifier.
+#[derive(Debug, Clone)]
+pub enum IdAction<Id, T> {
+    /// Don't do anything.
+    Wait,
+    /// Send a message to all other participants.
+    SendMany(MessageData),
+    /// Send a private message to another participant.
+    SendPrivate(Id, MessageData),
+    /// End the protocol by returning a value.
+    Return(T),
+}
+
+/// A protocol, driven using external identifiers instead of participants.
+///
+/// Errors still refer to participants; use [`ParticipantIds::id`] to find out
+/// who is responsible for them.
+pub struct WithIds<Id, P> {
+    ids: ParticipantIds<Id>,
+    protocol: P,
+}
+
+impl<Id: Ord + Clone + Serialize, P: Protocol> WithIds<Id, P> {
+    /// Wrap a protocol, which must have been created using the participants of `ids`.
+    pub fn new(ids: ParticipantIds<Id>, protocol: P) -> Self {
+        Self { ids, protocol }
+    }
+
+    pub fn ids(&self) -> &ParticipantIds<Id> {
+        &self.ids
+    }
+
+    /// Poke the protocol, see [`Protocol::poke`].
+    pub fn poke(&mut self) -> Result<IdAction<Id, P::Output>, ProtocolError> {
+        Ok(match self.protocol.poke()? {
+            Action::Wait => IdAction::Wait,
+            Action::SendMany(data) => IdAction::SendMany(data),
+            Action::SendPrivate(to, data) => {
+                let to = self.ids.id(to).ok_or_else(|| {
+                    ProtocolError::Other(
+                        format!("protocol sent a message to unknown participant {:?}", to).into(),
+                    )
+                })?;
+                IdAction::SendPrivate(to.clone(), data)
+            }
+            Action::Return(out) => IdAction::Return(out),
+        })
+    }
+
+    /// Inform the protocol of a new message, see [`Protocol::message`].
+    ///
+    /// This fails if the sender isn't part of the mapping.
+    pub fn message(&mut self, from: &Id, data: MessageData) -> Result<(), ProtocolError> {
+        let from = self
+            .ids
+            .participant(from)
+            .ok_or_else(|| ProtocolError::Other("message from unknown participant".into()))?;
+        self.protocol.message(from, data);
+        Ok(())
+    }
+
+    /// Unwrap the underlying protocol.
+    pub fn into_inner(self) -> P {
+        self.protocol
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use k256::Secp256k1;
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::{
+        compat::scalar_hash, keygen, presign, sign, triples, KeygenOutput, PresignArguments,
+    };
+
+    #[test]
+    fn test_participant_ids() {
+        let a = ParticipantIds::new(&[[3u8; 32], [1u8; 32], [2u8; 32]]).unwrap();
+        let b = ParticipantIds::new(&[[2u8; 32], [3u8; 32], [1u8; 32]]).unwrap();
+        assert_eq!(a, b);
+        let one = ParticipantIds::participant_of(&[1u8; 32]);
+        assert_eq!(a.participant(&[1u8; 32]), Some(one));
+        assert_eq!(a.id(one), Some(&[1u8; 32]));
+        assert_eq!(a.participant(&[4u8; 32]), None);
+        assert_eq!(a.id(ParticipantIds::participant_of(&[4u8; 32])), None);
+
+        // Identifiers keep their participant in a subset.
+        let subset = ParticipantIds::new(&[[3u8; 32], [1u8; 32]]).unwrap();
+        assert_eq!(subset.participant(&[1u8; 32]), Some(one));
+        assert_eq!(subset.participant(&[3u8; 32]), a.participant(&[3u8; 32]));
+
+        assert!(ParticipantIds::new(&[[1u8; 32], [1u8; 32]]).is_err());
+    }
+
+    /// Run protocols driven with identifiers to completion, delivering every message.
+    fn run_with_ids<'a, P: Protocol>(
+        mut protocols: HashMap<&'a str, WithIds<&'a str, P>>,
+    ) -> HashMap<&'a str, P::Output> {
+        let names: Vec<&str> = protocols.keys().copied().collect();
+        let mut out = HashMap::new();
+        while out.len() < names.len() {
+            for &from in &names {
+                if out.contains_key(from) {
+                    continue;
+                }
+                loop {
+                    let action = protocols.get_mut(from).unwrap().poke().unwrap();
+                    match action {
+                        IdAction::Wait => break,
+                        IdAction::SendMany(data) => {
+                            for &to in &names {
+                                if to != from {
+                                    let p = protocols.get_mut(to).unwrap();
+                                    p.message(&from, data.clone()).unwrap();
+                                }
+                            }
+                        }
+                        IdAction::SendPrivate(to, data) => {
+                            let p = protocols.get_mut(to).unwrap();
+                            p.message(&from, data).unwrap();
+                        }
+                        IdAction::Return(o) => {
+                            out.insert(from, o);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    fn keygen_with_ids(
+        names: &[&'static str],
+        threshold: usize,
+    ) -> HashMap<&'static str, KeygenOutput<Secp256k1>> {
+        let mut protocols = HashMap::new();
+        for &name in names {
+            let ids = ParticipantIds::new(names).unwrap();
+            let participants = ids.participants();
+            let me = ids.participant(&name).unwrap();
+            let protocol = keygen::<Secp256k1>(&participants, me, threshold).unwrap();
+            protocols.insert(name, WithIds::new(ids, protocol));
+        }
+        run_with_ids(protocols)
+    }
+
+    #[test]
+    fn test_keygen_with_ids() {
+        let names = ["carol", "alice", "bob"];
+        let out = keygen_with_ids(&names, 2);
+        let public_key = out["alice"].public_key;
+        assert!(out.values().all(|o| o.public_key == public_key));
+
+        let ids = ParticipantIds::new(&names).unwrap();
+        let protocol =
+            keygen::<Secp256k1>(&ids.participants(), ids.participant(&"alice").unwrap(), 2)
+                .unwrap();
+        assert!(WithIds::new(ids, protocol)
+            .message(&"mallory", MessageData::new())
+            .is_err());
+    }
+
+    #[test]
+    fn test_sign_with_a_subset_of_ids() {
+        let threshold = 2;
+        let keys = keygen_with_ids(&["carol", "alice", "bob"], threshold);
+        let public_key = keys["alice"].public_key;
+
+        let signers = ["carol", "alice"];
+        let ids = ParticipantIds::new(&signers).unwrap();
+        let participants = ids.participants();
+        let (pub0, shares0) = triples::deal(&mut OsRng, &participants, threshold);
+        let (pub1, shares1) = triples::deal(&mut OsRng, &participants, threshold);
+
+        let mut protocols = HashMap::new();
+        for name in signers {
+            let me = ids.participant(&name).unwrap();
+            // The triples are dealt in the order of the participants.
+            let i = participants.iter().position(|&p| p == me).unwrap();
+            let protocol = presign(
+                &participants,
+                me,
+                &participants,
+                me,
+                PresignArguments {
+                    triple0: (shares0[i].clone(), pub0.clone()),
+                    triple1: (shares1[i].clone(), pub1.clone()),
+                    keygen_out: keys[name].clone(),
+                    threshold,
+                },
+            )
+            .unwrap();
+            protocols.insert(name, WithIds::new(ids.clone(), protocol));
+        }
+        let presignatures = run_with_ids(protocols);
+
+        let msg_hash = scalar_hash(b"hello world");
+        let mut protocols = HashMap::new();
+        for name in signers {
+            let me = ids.participant(&name).unwrap();
+            let protocol = sign(
+                &participants,
+                me,
+                public_key,
+                presignatures[name].clone(),
+                msg_hash,
+            )
+            .unwrap();
+            protocols.insert(name, WithIds::new(ids.clone(), protocol));
+        }
+        let signatures = run_with_ids(protocols);
+        assert!(signatures
+            .values()
+            .all(|sig| sig.verify(&public_key, &msg_hash)));
+    }
+}
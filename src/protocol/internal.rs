@@ -41,21 +41,31 @@
 //! agree on what the identifier for the channels in each part of the protocol is.
 //! This is why we have to take great care that the identifiers a protocol will produce
 //! are deterministic, even in the presence of concurrent tasks.
+use async_channel::{self as channel, Receiver, Sender};
+use async_executor::{Executor, Task};
+use async_lock::Mutex;
 use ck_meow::Meow;
 use event_listener::Event;
+use futures_lite::future::{self, block_on};
 use serde::{de::DeserializeOwned, Serialize};
-use smol::{
-    block_on,
-    channel::{self, Receiver, Sender},
-    future,
-    lock::Mutex,
-    Executor, Task,
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, RwLock},
 };
-use std::{collections::HashMap, error, future::Future, sync::Arc};
+use zeroize::Zeroize;
 
-use crate::serde::{decode, encode_with_tag};
+use crate::{
+    crypto::{hash, Digest},
+    participants::{ParticipantList, ParticipantMap},
+    serde::{decode_message, encode_with_tag, Wire},
+    trace,
+};
 
-use super::{Action, MessageData, Participant, Protocol, ProtocolError};
+use super::{
+    Action, Fault, Format, MessageData, Overflow, Participant, Protocol, ProtocolError,
+    QueueLimits, Version,
+};
 
 /// The domain for our use of meow here.
 const MEOW_DOMAIN: &[u8] = b"cait-sith channel tags";
@@ -115,6 +125,14 @@ impl ChannelTag {
     }
 }
 
+/// Tags are shown by their first bytes in traces, which is enough to tell channels apart.
+#[cfg(any(feature = "tracing", test))]
+impl std::fmt::Display for ChannelTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0[..4].iter().try_for_each(|b| write!(f, "{b:02x}"))
+    }
+}
+
 /// A waitpoint inside of a channel.
 pub type Waitpoint = u64;
 
@@ -131,7 +149,9 @@ struct MessageHeader {
 
 impl MessageHeader {
     /// The number of bytes in this encoding.
-    const LEN: usize = ChannelTag::SIZE + 8;
+    ///
+    /// Headers start with the version of the message, see [`Version`].
+    const LEN: usize = Version::LEN + ChannelTag::SIZE + 8;
 
     fn new(channel: ChannelTag) -> Self {
         Self {
@@ -140,22 +160,30 @@ impl MessageHeader {
         }
     }
 
-    fn to_bytes(self) -> [u8; Self::LEN] {
+    fn to_bytes(self, version: Version) -> [u8; Self::LEN] {
         let mut out = [0u8; Self::LEN];
 
-        out[..ChannelTag::SIZE].copy_from_slice(&self.channel.0);
-        out[ChannelTag::SIZE..].copy_from_slice(&self.waitpoint.to_le_bytes());
+        let (version_bytes, rest) = out.split_at_mut(Version::LEN);
+        version_bytes.copy_from_slice(&version.to_bytes());
+        rest[..ChannelTag::SIZE].copy_from_slice(&self.channel.0);
+        rest[ChannelTag::SIZE..].copy_from_slice(&self.waitpoint.to_le_bytes());
 
         out
     }
 
-    fn from_bytes(bytes: &[u8]) -> Option<Self> {
-        if bytes.len() < Self::LEN {
+    /// Parse a header, returning `None` if it's malformed, or has a different version.
+    fn from_bytes(bytes: &[u8], version: Version) -> Option<Self> {
+        if bytes.len() < Self::LEN || Version::from_bytes(bytes)? != version {
             return None;
         }
+        let bytes = &bytes[Version::LEN..];
         // Unwrapping is fine because we checked the length already.
         let channel = ChannelTag(bytes[..ChannelTag::SIZE].try_into().unwrap());
-        let waitpoint = u64::from_le_bytes(bytes[ChannelTag::SIZE..Self::LEN].try_into().unwrap());
+        let waitpoint = u64::from_le_bytes(
+            bytes[ChannelTag::SIZE..ChannelTag::SIZE + 8]
+                .try_into()
+                .unwrap(),
+        );
 
         Some(Self { channel, waitpoint })
     }
@@ -185,6 +213,27 @@ impl MessageHeader {
 
 type SubMessageQueue = Vec<(Participant, MessageData)>;
 
+/// Wipe a message, if nothing else holds on to it.
+///
+/// Messages are reference counted, so a message sent to everyone might still share
+/// its bytes with the copies on their way to other participants, which we leave alone.
+pub(crate) fn wipe_message(data: MessageData) {
+    if let Ok(mut data) = data.try_into_mut() {
+        data.zeroize();
+    }
+}
+
+/// The messages held in a buffer, along with how much each participant is using.
+#[derive(Default)]
+struct Queues {
+    messages: HashMap<MessageHeader, SubMessageQueue>,
+    /// The number of messages, and of bytes, buffered for each participant.
+    usage: HashMap<Participant, (usize, usize)>,
+    limits: QueueLimits,
+    /// The first participant to go over the limits, if they're enforced by failing.
+    overflowed: Option<(Participant, Waitpoint)>,
+}
+
 /// A message buffer is a concurrent data structure to buffer messages.
 ///
 /// The idea is that we can put messages, and have them organized according to the
@@ -194,24 +243,58 @@ type SubMessageQueue = Vec<(Participant, MessageData)>;
 /// until a message for that slot has arrived.
 #[derive(Clone)]
 struct MessageBuffer {
-    messages: Arc<Mutex<HashMap<MessageHeader, SubMessageQueue>>>,
+    queues: Arc<Mutex<Queues>>,
     events: Arc<Mutex<HashMap<MessageHeader, Event>>>,
 }
 
 impl MessageBuffer {
     fn new() -> Self {
         Self {
-            messages: Arc::new(Mutex::new(HashMap::new())),
+            queues: Arc::new(Mutex::new(Queues::default())),
             events: Arc::new(Mutex::new(HashMap::new())),
         }
     }
 
+    /// Wipe all of the messages in this buffer.
+    async fn wipe(&self) {
+        let mut queues_lock = self.queues.as_ref().lock().await;
+        for (_, queue) in queues_lock.messages.drain() {
+            for (_, message) in queue {
+                wipe_message(message);
+            }
+        }
+        queues_lock.usage.clear();
+    }
+
+    async fn set_limits(&self, limits: QueueLimits) {
+        self.queues.as_ref().lock().await.limits = limits;
+    }
+
+    /// The participant which went over the limits, and the round in which it did.
+    async fn overflowed(&self) -> Option<(Participant, Waitpoint)> {
+        self.queues.as_ref().lock().await.overflowed
+    }
+
     /// Push a message into this buffer.
     ///
     /// We also need the header for the message, and the participant who sent it.
+    /// Messages over the limits for that participant don't get pushed.
     async fn push(&self, header: MessageHeader, from: Participant, message: MessageData) {
-        let mut messages_lock = self.messages.as_ref().lock().await;
-        messages_lock
+        let mut queues_lock = self.queues.as_ref().lock().await;
+        let queues = &mut *queues_lock;
+        let usage = queues.usage.entry(from).or_default();
+        let (messages, bytes) = (usage.0 + 1, usage.1 + message.len());
+        if queues.limits.max_messages.is_some_and(|max| messages > max)
+            || queues.limits.max_bytes.is_some_and(|max| bytes > max)
+        {
+            if queues.limits.overflow == Overflow::Fail {
+                queues.overflowed.get_or_insert((from, header.waitpoint));
+            }
+            return;
+        }
+        *usage = (messages, bytes);
+        queues
+            .messages
             .entry(header)
             .or_default()
             .push((from, message));
@@ -226,9 +309,14 @@ impl MessageBuffer {
     async fn pop(&self, header: MessageHeader) -> (Participant, MessageData) {
         loop {
             let listener = {
-                let mut messages_lock = self.messages.as_ref().lock().await;
-                let messages = messages_lock.entry(header).or_default();
+                let mut queues_lock = self.queues.as_ref().lock().await;
+                let queues = &mut *queues_lock;
+                let messages = queues.messages.entry(header).or_default();
                 if let Some(out) = messages.pop() {
+                    if let Some(usage) = queues.usage.get_mut(&out.0) {
+                        usage.0 -= 1;
+                        usage.1 -= out.1.len();
+                    }
                     return out;
                 }
                 let mut events_lock = self.events.as_ref().lock().await;
@@ -253,6 +341,8 @@ struct Comms {
     buffer: MessageBuffer,
     message_s: Sender<Message>,
     message_r: Receiver<Message>,
+    /// The version of the messages we send, and accept.
+    version: Arc<RwLock<Version>>,
 }
 
 impl Comms {
@@ -263,9 +353,21 @@ impl Comms {
             buffer: MessageBuffer::new(),
             message_s,
             message_r,
+            version: Arc::new(RwLock::new(Version::CURRENT)),
         }
     }
 
+    fn version(&self) -> Version {
+        *self.version.read().expect("failed to read the version")
+    }
+
+    fn set_format(&self, format: Format) {
+        self.version
+            .write()
+            .expect("failed to set the format")
+            .format = format;
+    }
+
     async fn outgoing(&self) -> Message {
         self.message_r
             .recv()
@@ -278,14 +380,38 @@ impl Comms {
             return;
         }
 
-        let header = match MessageHeader::from_bytes(&message) {
+        let header = match MessageHeader::from_bytes(&message, self.version()) {
             Some(h) => h,
-            _ => return,
+            _ => {
+                trace::event!(
+                    TRACE,
+                    from = u32::from(from),
+                    bytes = message.len(),
+                    "ignoring a message with a bad header"
+                );
+                return;
+            }
         };
 
+        trace::event!(
+            TRACE,
+            from = u32::from(from),
+            channel = %header.channel,
+            waitpoint = header.waitpoint,
+            bytes = message.len(),
+            "received a message"
+        );
         self.buffer.push(header, from, message).await
     }
 
+    /// Wipe the messages buffered for the protocol, and the ones it was about to send.
+    async fn wipe(&self) {
+        self.buffer.wipe().await;
+        while let Ok(Message::Many(data) | Message::Private(_, data)) = self.message_r.try_recv() {
+            wipe_message(data);
+        }
+    }
+
     async fn send_raw(&self, data: Message) {
         self.message_s
             .send(data)
@@ -294,27 +420,60 @@ impl Comms {
     }
 
     /// (Indicate that you want to) send a message to everybody else.
-    async fn send_many<T: Serialize>(&self, header: MessageHeader, data: &T) {
-        let header_bytes = header.to_bytes();
-        let message_data = encode_with_tag(&header_bytes, data);
-        self.send_raw(Message::Many(message_data)).await;
+    async fn send_many<T: Serialize + Wire>(&self, header: MessageHeader, data: &T) {
+        let version = self.version();
+        let message_data = encode_with_tag(version.format, &header.to_bytes(version), data);
+        trace::event!(
+            TRACE,
+            channel = %header.channel,
+            waitpoint = header.waitpoint,
+            bytes = message_data.len(),
+            "sending a message to everyone"
+        );
+        self.send_raw(Message::Many(message_data.into())).await;
     }
 
     /// (Indicate that you want to) send a message privately to someone.
-    async fn send_private<T: Serialize>(&self, header: MessageHeader, to: Participant, data: &T) {
-        let header_bytes = header.to_bytes();
-        let message_data = encode_with_tag(&header_bytes, data);
-        self.send_raw(Message::Private(to, message_data)).await;
+    async fn send_private<T: Serialize + Wire>(
+        &self,
+        header: MessageHeader,
+        to: Participant,
+        data: &T,
+    ) {
+        let version = self.version();
+        let message_data = encode_with_tag(version.format, &header.to_bytes(version), data);
+        trace::event!(
+            TRACE,
+            to = u32::from(to),
+            channel = %header.channel,
+            waitpoint = header.waitpoint,
+            bytes = message_data.len(),
+            "sending a private message"
+        );
+        self.send_raw(Message::Private(to, message_data.into()))
+            .await;
     }
 
-    async fn recv<T: DeserializeOwned>(
+    async fn recv<T: DeserializeOwned + Wire>(
         &self,
         header: MessageHeader,
     ) -> Result<(Participant, T), ProtocolError> {
+        trace::event!(
+            TRACE,
+            channel = %header.channel,
+            waitpoint = header.waitpoint,
+            "waiting for a message"
+        );
         let (from, data) = self.buffer.pop(header).await;
-        let decoded: Result<T, Box<dyn error::Error + Send + Sync>> =
-            decode(&data[MessageHeader::LEN..]).map_err(|e| e.into());
-        Ok((from, decoded?))
+        let format = self.version().format;
+        let decoded = decode_message(format, &data[MessageHeader::LEN..]).map_err(|e| {
+            ProtocolError::Malicious {
+                participant: from,
+                round: header.waitpoint,
+                fault: Fault::Serialization(e.to_string()),
+            }
+        })?;
+        Ok((from, decoded))
     }
 }
 
@@ -337,13 +496,13 @@ impl SharedChannel {
         self.header.next_waitpoint()
     }
 
-    pub async fn send_many<T: Serialize>(&self, waitpoint: Waitpoint, data: &T) {
+    pub async fn send_many<T: Serialize + Wire>(&self, waitpoint: Waitpoint, data: &T) {
         self.comms
             .send_many(self.header.with_waitpoint(waitpoint), data)
             .await
     }
 
-    pub async fn send_private<T: Serialize>(
+    pub async fn send_private<T: Serialize + Wire>(
         &self,
         waitpoint: Waitpoint,
         to: Participant,
@@ -354,12 +513,88 @@ impl SharedChannel {
             .await
     }
 
-    pub async fn recv<T: DeserializeOwned>(
+    pub async fn recv<T: DeserializeOwned + Wire>(
         &self,
         waitpoint: Waitpoint,
     ) -> Result<(Participant, T), ProtocolError> {
         self.comms.recv(self.header.with_waitpoint(waitpoint)).await
     }
+
+    /// Receive a broadcast message from every other participant, making sure
+    /// that everybody received the same messages.
+    ///
+    /// Each participant should have sent their message with [`Self::send_many`]
+    /// on `waitpoint`, with `mine` being the message we sent ourselves.
+    ///
+    /// Sending a message to everybody is just a fan out of private messages, so
+    /// a malicious participant could send different messages to different people.
+    /// To prevent this, after receiving all the messages, we echo a hash of each of them
+    /// to everybody else, and check that their echoes match what we received.
+    ///
+    /// If someone echoes a different version of their own message, or of ours,
+    /// then they're the one at fault. Otherwise, we can't know if the sender
+    /// or the participant echoing is lying, so we can't attribute the failure.
+    pub async fn recv_echoed<'p, T: Serialize + DeserializeOwned + Wire>(
+        &mut self,
+        participants: &'p ParticipantList,
+        me: Participant,
+        waitpoint: Waitpoint,
+        mine: T,
+    ) -> Result<ParticipantMap<'p, T>, ProtocolError> {
+        let mut received = ParticipantMap::new(participants);
+        received.put(me, mine);
+        while !received.full() {
+            let (from, data) = self.recv(waitpoint).await?;
+            received.put(from, data);
+        }
+
+        let order: Vec<Participant> = participants.clone().into();
+        let my_echo: Vec<Digest> = order.iter().map(|p| hash(&received[*p])).collect();
+
+        let echo_waitpoint = self.next_waitpoint();
+        self.send_many(echo_waitpoint, &my_echo).await;
+
+        let mut echoes = ParticipantMap::new(participants);
+        echoes.put(me, my_echo.clone());
+        while !echoes.full() {
+            let (from, echo): (_, Vec<Digest>) = self.recv(echo_waitpoint).await?;
+            if echo.len() != my_echo.len() {
+                return Err(ProtocolError::Malicious {
+                    participant: from,
+                    round: echo_waitpoint,
+                    fault: Fault::InvalidMessage("echo has the wrong length"),
+                });
+            }
+            echoes.put(from, echo);
+        }
+
+        let mut unattributed = false;
+        for k in participants.others(me) {
+            for (i, &p) in order.iter().enumerate() {
+                if echoes[k][i] == my_echo[i] {
+                    continue;
+                }
+                if p == k || p == me {
+                    // They echoed a different message than the one they, or we, sent.
+                    return Err(ProtocolError::Malicious {
+                        participant: k,
+                        round: waitpoint,
+                        fault: Fault::InconsistentBroadcast,
+                    });
+                }
+                unattributed = true;
+            }
+        }
+        // Somebody received a different message from someone else than we did.
+        if unattributed {
+            return Err(ProtocolError::Failed {
+                round: waitpoint,
+                fault: Fault::InconsistentBroadcast,
+            });
+        }
+
+        Ok(received)
+    }
 }
 
 /// Represents a private channel.
@@ -392,13 +627,13 @@ impl PrivateChannel {
         self.header.next_waitpoint()
     }
 
-    pub async fn send<T: Serialize>(&self, waitpoint: Waitpoint, data: &T) {
+    pub async fn send<T: Serialize + Wire>(&self, waitpoint: Waitpoint, data: &T) {
         self.comms
             .send_private(self.header.with_waitpoint(waitpoint), self.to, data)
             .await
     }
 
-    pub async fn recv<T: DeserializeOwned>(
+    pub async fn recv<T: DeserializeOwned + Wire>(
         &self,
         waitpoint: Waitpoint,
     ) -> Result<T, ProtocolError> {
@@ -464,7 +699,10 @@ impl<'a> Context<'a> {
 struct ProtocolExecutor<'a, T> {
     ctx: Context<'a>,
     ret_r: channel::Receiver<Result<T, ProtocolError>>,
+    /// The task running the protocol, which owns all of its state.
+    task: Option<Task<()>>,
     done: bool,
+    cancelled: bool,
 }
 
 impl<'a, T: Send + 'a> ProtocolExecutor<'a, T> {
@@ -472,7 +710,7 @@ impl<'a, T: Send + 'a> ProtocolExecutor<'a, T> {
         ctx: Context<'a>,
         fut: impl Future<Output = Result<T, ProtocolError>> + Send + 'a,
     ) -> Self {
-        let (ret_s, ret_r) = smol::channel::bounded(1);
+        let (ret_s, ret_r) = channel::bounded(1);
         let fut = async move {
             let res = fut.await;
             ret_s
@@ -481,23 +719,54 @@ impl<'a, T: Send + 'a> ProtocolExecutor<'a, T> {
                 .expect("failed to return result of protocol");
         };
 
-        ctx.executor.spawn(fut).detach();
+        let task = ctx.executor.spawn(fut);
 
         Self {
             ctx,
             ret_r,
+            task: Some(task),
             done: false,
+            cancelled: false,
         }
     }
 }
 
+impl<'a, T> ProtocolExecutor<'a, T> {
+    /// Drop the task running the protocol, and wipe the messages it left behind.
+    fn tear_down(&mut self) {
+        if let Some(task) = self.task.take() {
+            // Cancelling the task drops its future, along with any tasks it spawned.
+            block_on(self.ctx.executor.run(task.cancel()));
+            while self.ctx.executor.try_tick() {}
+        }
+        block_on(self.ctx.comms.wipe());
+    }
+}
+
+impl<'a, T> Drop for ProtocolExecutor<'a, T> {
+    fn drop(&mut self) {
+        self.tear_down();
+    }
+}
+
 impl<'a, T> Protocol for ProtocolExecutor<'a, T> {
     type Output = T;
 
     fn poke(&mut self) -> Result<Action<Self::Output>, ProtocolError> {
+        if self.cancelled {
+            return Err(ProtocolError::Cancelled);
+        }
         if self.done {
             return Ok(Action::Wait);
         }
+        if let Some((participant, round)) = block_on(self.ctx.comms.buffer.overflowed()) {
+            self.done = true;
+            return Err(ProtocolError::Malicious {
+                participant,
+                round,
+                fault: Fault::TooManyMessages,
+            });
+        }
         let fut_return = async {
             let out = self
                 .ret_r
@@ -531,20 +800,45 @@ impl<'a, T> Protocol for ProtocolExecutor<'a, T> {
                 .run(future::or(fut_outgoing, future::or(fut_return, fut_wait))),
         );
         match action {
-            Err(_) => self.done = true,
-            Ok(Action::Return(_)) => self.done = true,
+            // The error itself goes back to the caller.
+            Err(_) => {
+                trace::event!(DEBUG, "protocol failed");
+                self.done = true;
+            }
+            Ok(Action::Return(_)) => {
+                trace::event!(DEBUG, "protocol finished");
+                self.done = true;
+            }
             _ => {}
         };
         action
     }
 
     fn message(&mut self, from: Participant, data: MessageData) {
+        if self.cancelled {
+            wipe_message(data);
+            return;
+        }
         block_on(
             self.ctx
                 .executor
                 .run(self.ctx.comms.push_message(from, data)),
         );
     }
+
+    fn set_queue_limits(&mut self, limits: QueueLimits) {
+        block_on(self.ctx.comms.buffer.set_limits(limits));
+    }
+
+    fn set_format(&mut self, format: Format) {
+        self.ctx.comms.set_format(format);
+    }
+
+    fn cancel(&mut self) {
+        self.cancelled = true;
+        self.done = true;
+        self.tear_down();
+    }
 }
 
 /// Run a protocol, converting a future into an instance of the Protocol trait.
@@ -554,3 +848,281 @@ pub fn make_protocol<'a, T: Send + 'a>(
 ) -> impl Protocol<Output = T> + 'a {
     ProtocolExecutor::new(ctx, fut)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Run protocols to completion, collecting the result of each participant.
+    ///
+    /// Unlike `run_protocol`, this keeps going when some participants fail.
+    fn run_all<T>(
+        mut ps: Vec<(Participant, Box<dyn Protocol<Output = T>>)>,
+    ) -> Vec<Result<T, ProtocolError>> {
+        let mut out: Vec<Option<Result<T, ProtocolError>>> = ps.iter().map(|_| None).collect();
+        let mut progress = true;
+        while progress {
+            progress = false;
+            for i in 0..ps.len() {
+                while out[i].is_none() {
+                    let from = ps[i].0;
+                    match ps[i].1.poke() {
+                        Ok(Action::Wait) => break,
+                        Ok(Action::SendMany(m)) => {
+                            for j in (0..ps.len()).filter(|&j| j != i) {
+                                ps[j].1.message(from, m.clone());
+                            }
+                        }
+                        Ok(Action::SendPrivate(to, m)) => {
+                            let j = ps.iter().position(|(p, _)| *p == to).unwrap();
+                            ps[j].1.message(from, m);
+                        }
+                        Ok(Action::Return(r)) => out[i] = Some(Ok(r)),
+                        Err(e) => out[i] = Some(Err(e)),
+                    }
+                    progress = true;
+                }
+            }
+        }
+        out.into_iter()
+            .map(|r| r.expect("protocol got stuck"))
+            .collect()
+    }
+
+    /// Broadcast a value with echoes, returning the sum of everything received.
+    ///
+    /// If `equivocate_to` is set, a different value is sent to that participant.
+    async fn echo_sum(
+        ctx: Context<'_>,
+        participants: ParticipantList,
+        me: Participant,
+        equivocate_to: Option<Participant>,
+    ) -> Result<u64, ProtocolError> {
+        let mut chan = ctx.shared_channel();
+        let mine = u64::from(u32::from(me)) + 1;
+        let wait0 = chan.next_waitpoint();
+        for p in participants.others(me) {
+            let value = if Some(p) == equivocate_to {
+                mine + 100
+            } else {
+                mine
+            };
+            chan.send_private(wait0, p, &value).await;
+        }
+        let received = chan.recv_echoed(&participants, me, wait0, mine).await?;
+        let order: Vec<Participant> = participants.clone().into();
+        Ok(order.iter().map(|p| received[*p]).sum())
+    }
+
+    fn run_echo_sum(
+        participants: &[Participant],
+        liar: Option<(Participant, Participant)>,
+    ) -> Vec<Result<u64, ProtocolError>> {
+        let p_list = ParticipantList::new(participants).unwrap();
+        let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = u64>>)> = Vec::new();
+        for &p in participants {
+            let equivocate_to = liar.and_then(|(l, to)| (l == p).then_some(to));
+            let ctx = Context::new();
+            let fut = echo_sum(ctx.clone(), p_list.clone(), p, equivocate_to);
+            protocols.push((p, Box::new(make_protocol(ctx, fut))));
+        }
+        run_all(protocols)
+    }
+
+    #[test]
+    fn test_echo_broadcast() {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        for res in run_echo_sum(&participants, None) {
+            assert_eq!(res.unwrap(), 6);
+        }
+    }
+
+    #[test]
+    fn test_echo_broadcast_equivocation() {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let liar = participants[2];
+        let results = run_echo_sum(&participants, Some((liar, participants[1])));
+
+        // The victim can tell that the liar's echo doesn't match what it was sent.
+        let err = results[1].as_ref().unwrap_err();
+        assert_eq!(err.culprit(), Some(liar));
+        assert_eq!(err.fault(), Some(&Fault::InconsistentBroadcast));
+        // The other participant can only tell that something went wrong.
+        let err = results[0].as_ref().unwrap_err();
+        assert_eq!(err.culprit(), None);
+        assert_eq!(err.fault(), Some(&Fault::InconsistentBroadcast));
+    }
+
+    /// Receive a value in each of several rounds, returning their sum.
+    async fn recv_sum(ctx: Context<'_>, rounds: usize) -> Result<u64, ProtocolError> {
+        let mut chan = ctx.shared_channel();
+        let mut sum = 0;
+        for _ in 0..rounds {
+            let wait = chan.next_waitpoint();
+            let (_, value): (_, u64) = chan.recv(wait).await?;
+            sum += value;
+        }
+        Ok(sum)
+    }
+
+    fn message_for(waitpoint: Waitpoint, value: u64) -> MessageData {
+        message_in(Format::MessagePack, waitpoint, value)
+    }
+
+    fn message_in(format: Format, waitpoint: Waitpoint, value: u64) -> MessageData {
+        let header = MessageHeader::new(ChannelTag::root_shared()).with_waitpoint(waitpoint);
+        let version = Version {
+            format,
+            ..Version::CURRENT
+        };
+        encode_with_tag(format, &header.to_bytes(version), &value).into()
+    }
+
+    fn recv_sum_protocol(rounds: usize, limits: QueueLimits) -> impl Protocol<Output = u64> {
+        let ctx = Context::new();
+        let fut = recv_sum(ctx.clone(), rounds);
+        let mut protocol = make_protocol(ctx, fut);
+        protocol.set_queue_limits(limits);
+        protocol
+    }
+
+    #[test]
+    fn test_queue_limits() {
+        let honest = Participant::from(1u32);
+        let flooder = Participant::from(2u32);
+        let limits = QueueLimits {
+            max_messages: Some(2),
+            max_bytes: None,
+            overflow: Overflow::Drop,
+        };
+
+        // Messages over the limit get dropped, without affecting other participants.
+        let mut protocol = recv_sum_protocol(2, limits);
+        for i in 0..10 {
+            protocol.message(flooder, message_for(100, i));
+        }
+        protocol.message(honest, message_for(0, 1));
+        protocol.message(honest, message_for(1, 2));
+        assert!(matches!(protocol.poke(), Ok(Action::Return(3))));
+
+        // Consuming messages frees up space for new ones.
+        let mut protocol = recv_sum_protocol(3, limits);
+        for i in 0..3 {
+            protocol.message(honest, message_for(i, 1));
+            assert!(matches!(
+                protocol.poke(),
+                Ok(Action::Wait | Action::Return(3))
+            ));
+        }
+
+        // Bytes count too.
+        let limits = QueueLimits {
+            max_messages: None,
+            max_bytes: Some(message_for(0, 1).len()),
+            overflow: Overflow::Fail,
+        };
+        let mut protocol = recv_sum_protocol(2, limits);
+        protocol.message(flooder, message_for(100, 1));
+        assert!(matches!(protocol.poke(), Ok(Action::Wait)));
+        protocol.message(flooder, message_for(101, 1));
+        let err = protocol.poke().unwrap_err();
+        assert_eq!(err.culprit(), Some(flooder));
+        assert_eq!(err.round(), Some(101));
+        assert_eq!(err.fault(), Some(&Fault::TooManyMessages));
+        // The protocol is done after failing.
+        assert!(matches!(protocol.poke(), Ok(Action::Wait)));
+    }
+
+    #[test]
+    fn test_cancel() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        /// Stands in for a secret, recording when it gets dropped.
+        struct Secret(Arc<AtomicBool>);
+
+        impl Drop for Secret {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        let dropped = Arc::new(AtomicBool::new(false));
+        let secret = Secret(dropped.clone());
+        let ctx = Context::new();
+        let fut = {
+            let ctx = ctx.clone();
+            async move {
+                let _secret = secret;
+                recv_sum(ctx, 2).await
+            }
+        };
+        let mut protocol = ProtocolExecutor::new(ctx, fut);
+        let from = Participant::from(1u32);
+        protocol.message(from, message_for(0, 1));
+        protocol.message(from, message_for(5, 1));
+        assert!(matches!(protocol.poke(), Ok(Action::Wait)));
+        assert!(!dropped.load(Ordering::SeqCst));
+
+        protocol.cancel();
+        assert!(dropped.load(Ordering::SeqCst));
+        assert!(block_on(protocol.ctx.comms.buffer.queues.lock())
+            .messages
+            .is_empty());
+        // Messages no longer do anything, and the protocol stays cancelled.
+        protocol.message(from, message_for(1, 1));
+        assert!(matches!(protocol.poke(), Err(ProtocolError::Cancelled)));
+        assert!(matches!(protocol.poke(), Err(ProtocolError::Cancelled)));
+    }
+
+    #[test]
+    fn test_drop_tears_down() {
+        let ctx = Context::new();
+        let fut = recv_sum(ctx.clone(), 1);
+        let mut protocol = ProtocolExecutor::new(ctx.clone(), fut);
+        protocol.message(Participant::from(1u32), message_for(3, 1));
+        assert!(matches!(protocol.poke(), Ok(Action::Wait)));
+        drop(protocol);
+        assert!(block_on(ctx.comms.buffer.queues.lock()).messages.is_empty());
+    }
+
+    #[test]
+    fn test_header_version() {
+        let header = MessageHeader::new(ChannelTag::root_shared()).child(3);
+        let mut bytes = header.to_bytes(Version::CURRENT);
+        assert_eq!(
+            MessageHeader::from_bytes(&bytes, Version::CURRENT),
+            Some(header)
+        );
+        // Messages from other versions are ignored.
+        bytes[0] += 1;
+        assert_eq!(MessageHeader::from_bytes(&bytes, Version::CURRENT), None);
+    }
+
+    #[test]
+    fn test_formats() {
+        let from = Participant::from(1u32);
+        for &format in Format::ALL {
+            let mut protocol = recv_sum_protocol(2, QueueLimits::default());
+            protocol.set_format(format);
+            protocol.message(from, message_in(format, 0, 3));
+            // A message in another format is ignored, rather than misread.
+            let other = if format == Format::MessagePack {
+                Format::Postcard
+            } else {
+                Format::MessagePack
+            };
+            protocol.message(from, message_in(other, 1, 100));
+            assert!(matches!(protocol.poke(), Ok(Action::Wait)));
+            protocol.message(from, message_in(format, 1, 4));
+            assert!(matches!(protocol.poke(), Ok(Action::Return(7))));
+        }
+    }
+}
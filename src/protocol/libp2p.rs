@@ -0,0 +1,428 @@
+//! An implementation of [`Transport`] over libp2p.
+//!
+//! Messages are sent with a request-response behaviour, one request per message,
+//! which gives framing for free, and the empty response acknowledges each message.
+//! The node embedding this transport builds the [`Swarm`], choosing its transports,
+//! with [`Libp2pTransport::behaviour`] as its behaviour, and fixes the `PeerId` of
+//! every participant before the protocol starts. Messages from peers outside of
+//! this map are ignored, so the authentication of the libp2p transports, e.g. Noise,
+//! is what authenticates the sender of each message.
+//!
+//! Each message starts with a sequence number, as 8 little endian bytes.
+//! When a request fails, because the connection dropped, say, it gets sent again,
+//! redialing the peer, so the protocol never notices a connection coming back.
+//! If the peer can't be dialed, the message waits until it connects to us instead.
+//! The sequence numbers let the receiver ignore the copies of a message it already
+//! got, in case only the acknowledgement was lost. A peer which never comes back
+//! is caught by wrapping the protocol in a [`Deadline`](super::Deadline).
+//!
+//! Sending a message waits for its acknowledgement, so the last message we send
+//! is delivered before [`run_with_transport`](super::run_with_transport) returns.
+//! The acknowledgements we owe are only written out while the swarm is polled,
+//! so call [`Libp2pTransport::flush`] before dropping the swarm, or our peers will
+//! keep waiting for them.
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt, io,
+};
+
+use futures_lite::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, StreamExt};
+use libp2p::{
+    request_response::{
+        self, Event, InboundRequestId, Message, OutboundFailure, OutboundRequestId, ProtocolSupport,
+    },
+    swarm::SwarmEvent,
+    PeerId, StreamProtocol, Swarm,
+};
+
+use super::{transport::Transport, MessageData, Participant};
+
+/// The protocol name under which messages are exchanged.
+pub const PROTOCOL: StreamProtocol = StreamProtocol::new("/cait-sith/messages/1");
+
+/// The largest message, in bytes, that we're willing to send or receive.
+pub const MAX_MESSAGE_LEN: usize = 64 << 20;
+
+/// The number of bytes in the sequence number starting each message.
+const SEQ_LEN: usize = 8;
+
+/// The behaviour a swarm needs to carry a [`Libp2pTransport`].
+pub type Behaviour = request_response::Behaviour<MessageCodec>;
+
+/// The codec for our messages, which are raw bytes, with empty responses.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MessageCodec;
+
+async fn read_message(io: &mut (impl AsyncRead + Unpin + Send)) -> io::Result<MessageData> {
+    let mut data = Vec::new();
+    io.take(MAX_MESSAGE_LEN as u64 + 1)
+        .read_to_end(&mut data)
+        .await?;
+    if data.len() > MAX_MESSAGE_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("message exceeds the maximum of {MAX_MESSAGE_LEN} bytes"),
+        ));
+    }
+    Ok(data.into())
+}
+
+impl request_response::Codec for MessageCodec {
+    type Protocol = StreamProtocol;
+    type Request = MessageData;
+    type Response = ();
+
+    async fn read_request<T>(&mut self, _: &StreamProtocol, io: &mut T) -> io::Result<MessageData>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        read_message(io).await
+    }
+
+    async fn read_response<T>(&mut self, _: &StreamProtocol, io: &mut T) -> io::Result<()>
+    where
+        T: AsyncRead + Unpin + Send,
+    {
+        if !read_message(io).await?.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "acknowledgements should be empty",
+            ));
+        }
+        Ok(())
+    }
+
+    async fn write_request<T>(
+        &mut self,
+        _: &StreamProtocol,
+        io: &mut T,
+        data: MessageData,
+    ) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        io.write_all(&data).await?;
+        io.close().await
+    }
+
+    async fn write_response<T>(&mut self, _: &StreamProtocol, io: &mut T, _: ()) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin + Send,
+    {
+        io.close().await
+    }
+}
+
+/// Represents an error which can happen when using a [`Libp2pTransport`].
+#[derive(Debug)]
+pub enum Libp2pTransportError {
+    /// We tried to send a message to a participant without a known `PeerId`.
+    UnknownParticipant(Participant),
+    /// We tried to send a message longer than [`MAX_MESSAGE_LEN`].
+    MessageTooLarge(usize),
+    /// A participant doesn't speak our [`PROTOCOL`].
+    UnsupportedProtocol(Participant),
+    /// The swarm stopped producing events.
+    SwarmClosed,
+}
+
+impl fmt::Display for Libp2pTransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownParticipant(p) => write!(f, "no peer id for participant {p:?}"),
+            Self::MessageTooLarge(len) => write!(
+                f,
+                "message of {len} bytes exceeds the maximum of {MAX_MESSAGE_LEN}"
+            ),
+            Self::UnsupportedProtocol(p) => {
+                write!(f, "participant {p:?} doesn't support {PROTOCOL}")
+            }
+            Self::SwarmClosed => write!(f, "the swarm stopped producing events"),
+        }
+    }
+}
+
+impl std::error::Error for Libp2pTransportError {}
+
+/// A transport exchanging messages with the other participants over libp2p.
+pub struct Libp2pTransport {
+    swarm: Swarm<Behaviour>,
+    peers: HashMap<Participant, PeerId>,
+    participants: HashMap<PeerId, Participant>,
+    /// The sequence number of the next message we send to each participant.
+    next_sent: HashMap<Participant, u64>,
+    /// The sequence number of the next message we expect from each participant.
+    next_received: HashMap<Participant, u64>,
+    /// The messages waiting for an acknowledgement, so that they can be sent again.
+    unacknowledged: HashMap<OutboundRequestId, (PeerId, MessageData)>,
+    /// The messages to peers we failed to dial, waiting for them to connect to us.
+    undelivered: HashMap<PeerId, Vec<MessageData>>,
+    /// The requests whose acknowledgements we're still writing out.
+    responding: HashSet<InboundRequestId>,
+    incoming: VecDeque<(Participant, MessageData)>,
+}
+
+impl Libp2pTransport {
+    /// The behaviour to build the swarm passed to [`Libp2pTransport::new`] with.
+    pub fn behaviour() -> Behaviour {
+        Behaviour::new(
+            [(PROTOCOL, ProtocolSupport::Full)],
+            request_response::Config::default(),
+        )
+    }
+
+    /// Create a transport, given a swarm, and the `PeerId` of every other participant.
+    ///
+    /// The swarm needs to know how to reach these peers, either because they
+    /// dial us, or through [`Swarm::add_peer_address`].
+    pub fn new(swarm: Swarm<Behaviour>, peers: HashMap<Participant, PeerId>) -> Self {
+        let participants = peers.iter().map(|(&p, &id)| (id, p)).collect();
+        Self {
+            swarm,
+            peers,
+            participants,
+            next_sent: HashMap::new(),
+            next_received: HashMap::new(),
+            unacknowledged: HashMap::new(),
+            undelivered: HashMap::new(),
+            responding: HashSet::new(),
+            incoming: VecDeque::new(),
+        }
+    }
+
+    /// Wait until the messages we received have all been acknowledged.
+    ///
+    /// After the protocol finishes, this makes sure that the participants
+    /// who sent us their last messages don't wait for us forever.
+    pub async fn flush(&mut self) -> Result<(), Libp2pTransportError> {
+        while !self.responding.is_empty() {
+            self.poll_swarm().await?;
+        }
+        Ok(())
+    }
+
+    /// Get back the swarm, e.g. to reuse it for another protocol.
+    pub fn into_swarm(self) -> Swarm<Behaviour> {
+        self.swarm
+    }
+
+    fn send_request(&mut self, peer: PeerId, data: MessageData) {
+        let request_id = self.swarm.behaviour_mut().send_request(&peer, data.clone());
+        self.unacknowledged.insert(request_id, (peer, data));
+    }
+
+    /// Handle the next event of the swarm, queueing incoming messages.
+    async fn poll_swarm(&mut self) -> Result<(), Libp2pTransportError> {
+        let event = self
+            .swarm
+            .next()
+            .await
+            .ok_or(Libp2pTransportError::SwarmClosed)?;
+        let event = match event {
+            SwarmEvent::Behaviour(event) => event,
+            SwarmEvent::ConnectionEstablished { peer_id, .. } => {
+                for data in self.undelivered.remove(&peer_id).unwrap_or_default() {
+                    self.send_request(peer_id, data);
+                }
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+        match event {
+            Event::Message {
+                peer,
+                message:
+                    Message::Request {
+                        request_id,
+                        request,
+                        channel,
+                    },
+                ..
+            } => {
+                let Some(&from) = self.participants.get(&peer) else {
+                    return Ok(());
+                };
+                // Acknowledging fails if the connection dropped in the meantime,
+                // in which case the sender will send the message again.
+                if self
+                    .swarm
+                    .behaviour_mut()
+                    .send_response(channel, ())
+                    .is_ok()
+                {
+                    self.responding.insert(request_id);
+                }
+                if request.len() < SEQ_LEN {
+                    return Ok(());
+                }
+                let seq = u64::from_le_bytes(request[..SEQ_LEN].try_into().unwrap());
+                let expected = self.next_received.entry(from).or_default();
+                // Messages are acknowledged one at a time, so anything else is a copy.
+                if seq == *expected {
+                    *expected += 1;
+                    self.incoming.push_back((from, request.slice(SEQ_LEN..)));
+                }
+            }
+            Event::Message {
+                message: Message::Response { request_id, .. },
+                ..
+            } => {
+                self.unacknowledged.remove(&request_id);
+            }
+            Event::OutboundFailure {
+                request_id, error, ..
+            } => {
+                let Some((peer, data)) = self.unacknowledged.remove(&request_id) else {
+                    return Ok(());
+                };
+                match error {
+                    OutboundFailure::UnsupportedProtocols => {
+                        return Err(Libp2pTransportError::UnsupportedProtocol(
+                            self.participants[&peer],
+                        ));
+                    }
+                    // Dialing again right away would most likely fail again.
+                    OutboundFailure::DialFailure if !self.swarm.is_connected(&peer) => {
+                        self.undelivered.entry(peer).or_default().push(data);
+                    }
+                    _ => self.send_request(peer, data),
+                }
+            }
+            Event::InboundFailure { request_id, .. } | Event::ResponseSent { request_id, .. } => {
+                self.responding.remove(&request_id);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Transport for Libp2pTransport {
+    type Error = Libp2pTransportError;
+
+    async fn send(&mut self, to: Participant, data: MessageData) -> Result<(), Self::Error> {
+        let peer = *self
+            .peers
+            .get(&to)
+            .ok_or(Libp2pTransportError::UnknownParticipant(to))?;
+        if data.len() + SEQ_LEN > MAX_MESSAGE_LEN {
+            return Err(Libp2pTransportError::MessageTooLarge(data.len()));
+        }
+        let seq = self.next_sent.entry(to).or_default();
+        let mut request = Vec::with_capacity(SEQ_LEN + data.len());
+        request.extend_from_slice(&seq.to_le_bytes());
+        request.extend_from_slice(&data);
+        *seq += 1;
+
+        self.send_request(peer, request.into());
+        while !self.unacknowledged.is_empty() || !self.undelivered.is_empty() {
+            self.poll_swarm().await?;
+        }
+        Ok(())
+    }
+
+    async fn recv(&mut self) -> Result<(Participant, MessageData), Self::Error> {
+        loop {
+            if let Some(message) = self.incoming.pop_front() {
+                return Ok(message);
+            }
+            self.poll_swarm().await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{error::Error, time::Duration};
+
+    use k256::Secp256k1;
+    use libp2p::{
+        core::{transport::MemoryTransport, upgrade, Transport as _},
+        identity::Keypair,
+        noise, swarm, yamux, Multiaddr,
+    };
+    use smol::{block_on, Executor};
+
+    use super::*;
+    use crate::{keygen, protocol::run_with_transport};
+
+    fn memory_swarm(port: u64) -> (Swarm<Behaviour>, Multiaddr) {
+        let keypair = Keypair::generate_ed25519();
+        let transport = MemoryTransport::default()
+            .upgrade(upgrade::Version::V1)
+            .authenticate(noise::Config::new(&keypair).unwrap())
+            .multiplex(yamux::Config::default())
+            .boxed();
+        let config =
+            swarm::Config::without_executor().with_idle_connection_timeout(Duration::from_secs(60));
+        let mut swarm = Swarm::new(
+            transport,
+            Libp2pTransport::behaviour(),
+            keypair.public().to_peer_id(),
+            config,
+        );
+        let addr: Multiaddr = format!("/memory/{port}").parse().unwrap();
+        swarm.listen_on(addr.clone()).unwrap();
+        (swarm, addr)
+    }
+
+    #[test]
+    fn test_libp2p_keygen() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+
+        let mut swarms: Vec<_> = participants
+            .iter()
+            .map(|p| memory_swarm(0x6361_6974_0000 + u64::from(u32::from(*p))))
+            .collect();
+        let ids: HashMap<_, _> = participants
+            .iter()
+            .zip(&swarms)
+            .map(|(&p, (swarm, addr))| (p, (*swarm.local_peer_id(), addr.clone())))
+            .collect();
+        for (swarm, _) in &mut swarms {
+            for (id, addr) in ids.values() {
+                if id != swarm.local_peer_id() {
+                    swarm.add_peer_address(*id, addr.clone());
+                }
+            }
+        }
+
+        let ex = Executor::new();
+        let results = block_on(ex.run(async {
+            let tasks: Vec<_> = participants
+                .iter()
+                .zip(swarms)
+                .map(|(&me, (swarm, _))| {
+                    let peers = ids
+                        .iter()
+                        .filter(|(&p, _)| p != me)
+                        .map(|(&p, (id, _))| (p, *id))
+                        .collect();
+                    let participants = participants.clone();
+                    ex.spawn(async move {
+                        let mut transport = Libp2pTransport::new(swarm, peers);
+                        let protocol = keygen::<Secp256k1>(&participants, me, 2)?;
+                        let out =
+                            run_with_transport(protocol, &participants, me, &mut transport).await?;
+                        transport.flush().await?;
+                        Ok::<_, Box<dyn Error + Send + Sync>>(out)
+                    })
+                })
+                .collect();
+
+            let mut results = Vec::new();
+            for task in tasks {
+                results.push(task.await?);
+            }
+            Ok::<_, Box<dyn Error + Send + Sync>>(results)
+        }))
+        .map_err(|e| e as Box<dyn Error>)?;
+
+        assert_eq!(results[0].public_key, results[1].public_key);
+        assert_eq!(results[1].public_key, results[2].public_key);
+        Ok(())
+    }
+}
@@ -0,0 +1,343 @@
+//! This module provides a way to run many protocols at once, over a single message loop.
+//!
+//! A node usually runs several protocols concurrently: maybe a key generation,
+//! along with a few presignatures, and a few signatures. Each of them is its own
+//! [`Protocol`], and every message needs to reach the right one. A [`SessionManager`]
+//! owns all of these protocols, identified by a session id, and exposes a single
+//! `poke` and `message` interface for all of them, so that they can share one
+//! connection to each other participant.
+//!
+//! Each outgoing message is prefixed with the id of its session, which is used to route
+//! it on the other end. Protocols are also wrapped in a [`Session`], binding their messages
+//! to the session id, so that a message can't be routed to the wrong session.
+//!
+//! Participants don't all start a session at the same time, so messages for an
+//! unknown session are held on to, and delivered once that session gets added.
+//! Messages for a session which already finished are dropped.
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    Action, Format, InitializationError, MessageData, Participant, Protocol, ProtocolError,
+    QueueLimits, Session, SessionId,
+};
+
+/// Something a [`SessionManager`] wants done.
+#[derive(Debug)]
+pub enum SessionAction<T> {
+    /// None of the sessions can make progress without more messages.
+    Wait,
+    /// Send a message to every other participant.
+    SendMany(MessageData),
+    /// Send a message to a specific participant.
+    SendPrivate(Participant, MessageData),
+    /// A session returned, and was removed.
+    Return(SessionId, T),
+    /// A session failed, and was removed.
+    ///
+    /// The other sessions are unaffected, and keep running.
+    Failed(SessionId, ProtocolError),
+}
+
+/// The number of bytes of a session id, prefixed to each message.
+const ID_LEN: usize = 32;
+
+/// Prefix a message with the id of its session.
+fn frame(id: &SessionId, data: MessageData) -> MessageData {
+    let mut out = Vec::with_capacity(ID_LEN + data.len());
+    out.extend_from_slice(id.as_bytes());
+    out.extend_from_slice(&data);
+    out.into()
+}
+
+/// Split a message into the id of its session, and its contents.
+fn unframe(mut data: MessageData) -> Option<(SessionId, MessageData)> {
+    let id = SessionId::from_bytes(data.get(..ID_LEN)?.try_into().unwrap());
+    Some((id, data.split_off(ID_LEN)))
+}
+
+/// A protocol with its output converted to another type.
+struct MapOutput<P, F> {
+    protocol: P,
+    f: F,
+}
+
+impl<T, P: Protocol, F: FnMut(P::Output) -> T> Protocol for MapOutput<P, F> {
+    type Output = T;
+
+    fn poke(&mut self) -> Result<Action<Self::Output>, ProtocolError> {
+        Ok(match self.protocol.poke()? {
+            Action::Wait => Action::Wait,
+            Action::SendMany(data) => Action::SendMany(data),
+            Action::SendPrivate(to, data) => Action::SendPrivate(to, data),
+            Action::Return(output) => Action::Return((self.f)(output)),
+        })
+    }
+
+    fn message(&mut self, from: Participant, data: MessageData) {
+        self.protocol.message(from, data)
+    }
+
+    fn set_queue_limits(&mut self, limits: QueueLimits) {
+        self.protocol.set_queue_limits(limits)
+    }
+
+    fn set_format(&mut self, format: Format) {
+        self.protocol.set_format(format)
+    }
+
+    fn cancel(&mut self) {
+        self.protocol.cancel()
+    }
+}
+
+/// Many protocols, running concurrently, over a single message loop.
+///
+/// All of the protocols need to produce the same type of output. For protocols
+/// with different outputs, this can be an enum, with [`SessionManager::add_map`]
+/// wrapping the output of each protocol.
+pub struct SessionManager<T> {
+    me: Participant,
+    sessions: Vec<(SessionId, Box<dyn Protocol<Output = T>>)>,
+    /// The session to poke first, which rotates, so that no session starves the others.
+    next: usize,
+    pending: HashMap<SessionId, Vec<(Participant, MessageData)>>,
+    finished: HashSet<SessionId>,
+    queue_limits: QueueLimits,
+}
+
+impl<T: 'static> SessionManager<T> {
+    /// Create a manager without any sessions, for the participant `me`.
+    pub fn new(me: Participant) -> Self {
+        Self {
+            me,
+            sessions: Vec::new(),
+            next: 0,
+            pending: HashMap::new(),
+            finished: HashSet::new(),
+            queue_limits: QueueLimits::default(),
+        }
+    }
+
+    /// Add a protocol, in a session with a given id.
+    ///
+    /// Every participant needs to use the same id for the same session, and ids
+    /// can't be reused, even after a session finishes.
+    pub fn add(
+        &mut self,
+        id: SessionId,
+        protocol: impl Protocol<Output = T> + 'static,
+    ) -> Result<(), InitializationError> {
+        if self.finished.contains(&id) || self.sessions.iter().any(|(s, _)| *s == id) {
+            return Err(InitializationError::BadParameters(
+                "session id cannot be reused".to_string(),
+            ));
+        }
+        let mut protocol = Session::new(id, self.me, protocol);
+        protocol.set_queue_limits(self.queue_limits);
+        for (from, data) in self.pending.remove(&id).unwrap_or_default() {
+            protocol.message(from, data);
+        }
+        self.sessions.push((id, Box::new(protocol)));
+        Ok(())
+    }
+
+    /// Add a protocol, converting its output with a function.
+    pub fn add_map<P: Protocol + 'static>(
+        &mut self,
+        id: SessionId,
+        protocol: P,
+        f: impl FnMut(P::Output) -> T + 'static,
+    ) -> Result<(), InitializationError> {
+        self.add(id, MapOutput { protocol, f })
+    }
+
+    /// Abort a session, returning true if it was running.
+    pub fn remove(&mut self, id: SessionId) -> bool {
+        let Some(i) = self.sessions.iter().position(|(s, _)| *s == id) else {
+            return false;
+        };
+        self.finish(i);
+        true
+    }
+
+    /// Limit the messages each session holds on to, for each participant.
+    ///
+    /// This applies to the sessions already running, and to the ones added later.
+    /// See [`Protocol::set_queue_limits`]. Messages held for sessions which
+    /// haven't been added yet don't count against these limits.
+    pub fn set_queue_limits(&mut self, limits: QueueLimits) {
+        self.queue_limits = limits;
+        for (_, protocol) in &mut self.sessions {
+            protocol.set_queue_limits(limits);
+        }
+    }
+
+    /// The ids of the sessions currently running.
+    pub fn sessions(&self) -> impl Iterator<Item = SessionId> + '_ {
+        self.sessions.iter().map(|(id, _)| *id)
+    }
+
+    fn finish(&mut self, i: usize) -> SessionId {
+        let (id, _) = self.sessions.remove(i);
+        self.finished.insert(id);
+        id
+    }
+
+    /// Poke the sessions, until one of them has something to do.
+    pub fn poke(&mut self) -> SessionAction<T> {
+        for k in 0..self.sessions.len() {
+            let i = (self.next + k) % self.sessions.len();
+            let action = match self.sessions[i].1.poke() {
+                Ok(Action::Wait) => continue,
+                Ok(Action::SendMany(data)) => {
+                    SessionAction::SendMany(frame(&self.sessions[i].0, data))
+                }
+                Ok(Action::SendPrivate(to, data)) => {
+                    SessionAction::SendPrivate(to, frame(&self.sessions[i].0, data))
+                }
+                Ok(Action::Return(output)) => SessionAction::Return(self.finish(i), output),
+                Err(e) => SessionAction::Failed(self.finish(i), e),
+            };
+            self.next = i + 1;
+            return action;
+        }
+        SessionAction::Wait
+    }
+
+    /// Receive a message, routing it to its session.
+    ///
+    /// Malformed messages, and messages for finished sessions, are dropped.
+    pub fn message(&mut self, from: Participant, data: MessageData) {
+        let Some((id, data)) = unframe(data) else {
+            return;
+        };
+        if let Some((_, protocol)) = self.sessions.iter_mut().find(|(s, _)| *s == id) {
+            protocol.message(from, data);
+        } else if !self.finished.contains(&id) {
+            self.pending.entry(id).or_default().push((from, data));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use k256::Secp256k1;
+
+    use super::*;
+    use crate::{keygen, triples, KeygenOutput};
+
+    #[allow(clippy::large_enum_variant)]
+    enum Output {
+        Keygen(KeygenOutput<Secp256k1>),
+        Triple(triples::TripleGenerationOutput<Secp256k1>),
+    }
+
+    fn id(purpose: &[u8]) -> SessionId {
+        SessionId::derive(purpose, &[], 0, 0)
+    }
+
+    #[test]
+    fn test_unframe() {
+        let data = |bytes: &'static [u8]| MessageData::from_static(bytes);
+        assert_eq!(
+            unframe(frame(&id(b"id"), data(&[1, 2]))),
+            Some((id(b"id"), data(&[1, 2])))
+        );
+        assert_eq!(
+            unframe(frame(&id(b""), MessageData::new())),
+            Some((id(b""), MessageData::new()))
+        );
+        assert_eq!(unframe(data(&[1, 2, 3])), None);
+    }
+
+    #[test]
+    fn test_session_manager() {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let mut managers: Vec<_> = participants
+            .iter()
+            .map(|&p| SessionManager::<Output>::new(p))
+            .collect();
+        let add = |manager: &mut SessionManager<Output>, p: Participant, purpose: &[u8]| {
+            let id = SessionId::derive(purpose, &participants, 0, 0);
+            if purpose.starts_with(b"keygen") {
+                let protocol = keygen(&participants, p, 2).unwrap();
+                manager.add_map(id, protocol, Output::Keygen).unwrap();
+            } else {
+                let protocol = triples::generate_triple(&participants, p, 2).unwrap();
+                manager.add_map(id, protocol, Output::Triple).unwrap();
+            }
+        };
+        let purposes: [&[u8]; 3] = [b"keygen 0", b"keygen 1", b"triple 0"];
+        let ids = purposes.map(|purpose| SessionId::derive(purpose, &participants, 0, 0));
+
+        // The last participant starts its sessions late, so the others' messages
+        // for those sessions arrive before it knows about them.
+        for (i, manager) in managers.iter_mut().enumerate().take(2) {
+            for purpose in purposes.iter().cycle().skip(i).take(3) {
+                add(manager, participants[i], purpose);
+            }
+        }
+
+        let mut outputs: Vec<Vec<(SessionId, Output)>> =
+            participants.iter().map(|_| Vec::new()).collect();
+        let mut step = 0;
+        while outputs.iter().any(|out| out.len() < ids.len()) {
+            if step == 1 {
+                for purpose in purposes.iter().rev() {
+                    add(&mut managers[2], participants[2], purpose);
+                }
+            }
+            step += 1;
+            for i in 0..managers.len() {
+                loop {
+                    match managers[i].poke() {
+                        SessionAction::Wait => break,
+                        SessionAction::SendMany(data) => {
+                            for j in (0..managers.len()).filter(|&j| j != i) {
+                                managers[j].message(participants[i], data.clone());
+                            }
+                        }
+                        SessionAction::SendPrivate(to, data) => {
+                            let j = participants.iter().position(|&p| p == to).unwrap();
+                            managers[j].message(participants[i], data)
+                        }
+                        SessionAction::Return(id, output) => outputs[i].push((id, output)),
+                        SessionAction::Failed(id, e) => {
+                            panic!("session {} failed: {}", id, e)
+                        }
+                    }
+                }
+            }
+        }
+
+        for id in ids {
+            let mut found: Vec<_> = outputs
+                .iter()
+                .map(|out| &out.iter().find(|(s, _)| *s == id).unwrap().1)
+                .collect();
+            match found.pop().unwrap() {
+                Output::Keygen(last) => assert!(found
+                    .iter()
+                    .all(|o| matches!(o, Output::Keygen(o) if o.public_key == last.public_key))),
+                Output::Triple((_, last)) => assert!(found
+                    .iter()
+                    .all(|o| matches!(o, Output::Triple((_, o)) if o == last))),
+            }
+        }
+        // The sessions are finished, so their ids can't be reused, and messages for them are dropped.
+        assert!(managers.iter().all(|m| m.sessions().next().is_none()));
+        managers[0].message(
+            participants[1],
+            frame(&ids[0], MessageData::from_static(&[0])),
+        );
+        assert!(managers[0].pending.is_empty());
+        let protocol = keygen(&participants, participants[0], 2).unwrap();
+        assert!(managers[0]
+            .add_map(ids[0], protocol, Output::Keygen)
+            .is_err());
+    }
+}
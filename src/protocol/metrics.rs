@@ -0,0 +1,210 @@
+//! This module provides a way to measure what a protocol costs, as it runs.
+//!
+//! [`Metered`] wraps a protocol, and counts the messages and bytes it exchanges with
+//! each other participant, the number of rounds it goes through, and the time
+//! spent computing locally, inside of calls to `poke` and `message`. This is meant
+//! for monitoring a fleet of signers: the metrics can be read at any point,
+//! including after the protocol returns, or fails.
+//!
+//! The time measured is wall time, so it includes time where the thread was preempted,
+//! but not time spent waiting on the network, which happens outside the protocol.
+//! Memory usage isn't tracked, since this needs a global allocator to measure.
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use super::{Action, Format, MessageData, Participant, Protocol, ProtocolError, QueueLimits};
+
+/// A count of messages, and of the bytes they contain.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counter {
+    pub messages: usize,
+    pub bytes: usize,
+}
+
+impl Counter {
+    fn add(&mut self, data: &MessageData) {
+        self.messages += 1;
+        self.bytes += data.len();
+    }
+}
+
+/// The metrics collected about a protocol.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Metrics {
+    /// The messages sent to each other participant.
+    ///
+    /// A message sent to everyone counts once for each participant.
+    pub sent: HashMap<Participant, Counter>,
+    /// The messages received from each other participant.
+    pub received: HashMap<Participant, Counter>,
+    /// The number of rounds, which is the number of times the protocol started
+    /// sending messages, after having waited for some.
+    ///
+    /// This is a heuristic: if the messages for a round have all arrived before
+    /// the protocol gets poked, it never waits, and two rounds get counted as one.
+    pub rounds: usize,
+    /// The time spent computing locally.
+    pub compute_time: Duration,
+}
+
+impl Metrics {
+    /// The total of the messages sent to every participant.
+    pub fn total_sent(&self) -> Counter {
+        total(&self.sent)
+    }
+
+    /// The total of the messages received from every participant.
+    pub fn total_received(&self) -> Counter {
+        total(&self.received)
+    }
+}
+
+fn total(counters: &HashMap<Participant, Counter>) -> Counter {
+    counters
+        .values()
+        .fold(Counter::default(), |acc, c| Counter {
+            messages: acc.messages + c.messages,
+            bytes: acc.bytes + c.bytes,
+        })
+}
+
+/// A protocol, collecting metrics as it runs.
+pub struct Metered<P> {
+    protocol: P,
+    others: Vec<Participant>,
+    metrics: Metrics,
+    waited: bool,
+}
+
+impl<P: Protocol> Metered<P> {
+    /// Wrap a protocol, run by `me` with some participants.
+    pub fn new(protocol: P, participants: &[Participant], me: Participant) -> Self {
+        Self {
+            protocol,
+            others: participants.iter().copied().filter(|&p| p != me).collect(),
+            metrics: Metrics::default(),
+            waited: true,
+        }
+    }
+
+    /// The metrics collected so far.
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// Unwrap the underlying protocol.
+    pub fn into_inner(self) -> P {
+        self.protocol
+    }
+}
+
+impl<P: Protocol> Protocol for Metered<P> {
+    type Output = P::Output;
+
+    fn poke(&mut self) -> Result<Action<Self::Output>, ProtocolError> {
+        let start = Instant::now();
+        let action = self.protocol.poke();
+        self.metrics.compute_time += start.elapsed();
+
+        match &action {
+            Ok(Action::Wait) => self.waited = true,
+            Ok(Action::SendMany(data)) => {
+                for &p in &self.others {
+                    self.metrics.sent.entry(p).or_default().add(data);
+                }
+            }
+            Ok(Action::SendPrivate(to, data)) => {
+                self.metrics.sent.entry(*to).or_default().add(data)
+            }
+            Ok(Action::Return(_)) | Err(_) => {}
+        }
+        if matches!(action, Ok(Action::SendMany(_) | Action::SendPrivate(..))) && self.waited {
+            self.metrics.rounds += 1;
+            self.waited = false;
+        }
+        action
+    }
+
+    fn message(&mut self, from: Participant, data: MessageData) {
+        self.metrics.received.entry(from).or_default().add(&data);
+        let start = Instant::now();
+        self.protocol.message(from, data);
+        self.metrics.compute_time += start.elapsed();
+    }
+
+    fn set_queue_limits(&mut self, limits: QueueLimits) {
+        self.protocol.set_queue_limits(limits)
+    }
+
+    fn set_format(&mut self, format: Format) {
+        self.protocol.set_format(format)
+    }
+
+    fn cancel(&mut self) {
+        self.protocol.cancel()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use k256::Secp256k1;
+
+    use super::*;
+    use crate::keygen;
+
+    #[test]
+    fn test_metered_keygen() {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let mut protocols: Vec<_> = participants
+            .iter()
+            .map(|&p| {
+                let protocol = keygen::<Secp256k1>(&participants, p, 2).unwrap();
+                Metered::new(protocol, &participants, p)
+            })
+            .collect();
+
+        let mut done = vec![false; protocols.len()];
+        while done.iter().any(|d| !d) {
+            for i in 0..protocols.len() {
+                let from = participants[i];
+                while !done[i] {
+                    match protocols[i].poke().unwrap() {
+                        Action::Wait => break,
+                        Action::SendMany(data) => {
+                            for j in (0..protocols.len()).filter(|&j| j != i) {
+                                protocols[j].message(from, data.clone());
+                            }
+                        }
+                        Action::SendPrivate(to, data) => {
+                            let j = participants.iter().position(|&p| p == to).unwrap();
+                            protocols[j].message(from, data);
+                        }
+                        Action::Return(_) => done[i] = true,
+                    }
+                }
+            }
+        }
+
+        // The first participant gets poked before anyone else sends anything.
+        assert!(protocols[0].metrics().rounds > 1);
+        for (i, protocol) in protocols.iter().enumerate() {
+            let metrics = protocol.metrics();
+            assert!(metrics.rounds > 0);
+            assert!(!metrics.sent.contains_key(&participants[i]));
+            assert!(metrics.total_sent().bytes > 0);
+            // What we sent to someone is exactly what they received from us.
+            for (j, other) in protocols.iter().enumerate().filter(|(j, _)| *j != i) {
+                assert_eq!(
+                    metrics.sent[&participants[j]],
+                    other.metrics().received[&participants[i]]
+                );
+            }
+        }
+    }
+}
@@ -11,11 +11,68 @@ use ::serde::{Deserialize, Serialize};
 
 use crate::compat::CSCurve;
 
+/// Describes which check on the messages of a protocol failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault {
+    /// A revealed value didn't match its commitment.
+    BadCommitment,
+    /// A zero knowledge proof failed to verify.
+    BadProof,
+    /// A private share didn't match the public values it should be consistent with.
+    BadShare,
+    /// Participants received different versions of a message which should have been broadcast.
+    InconsistentBroadcast,
+    /// A message failed to deserialize.
+    Serialization(String),
+    /// A message was well formed, but its contents were invalid.
+    InvalidMessage(&'static str),
+    /// A value computed from the contributions of all participants was invalid.
+    InvalidResult(&'static str),
+    /// A participant sent more messages than we were willing to hold on to.
+    TooManyMessages,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Fault::BadCommitment => write!(f, "commitment did not match revealed value"),
+            Fault::BadProof => write!(f, "proof failed to verify"),
+            Fault::BadShare => write!(f, "bad private share"),
+            Fault::InconsistentBroadcast => write!(f, "inconsistent broadcast"),
+            Fault::Serialization(e) => write!(f, "message failed to decode: {}", e),
+            Fault::InvalidMessage(e) => write!(f, "invalid message: {}", e),
+            Fault::InvalidResult(e) => write!(f, "invalid result: {}", e),
+            Fault::TooManyMessages => write!(f, "too many buffered messages"),
+        }
+    }
+}
+
 /// Represents an error which can happen when running a protocol.
+///
+/// Failed checks come with the round they happened in, which is the index
+/// of the waitpoint the offending messages were received on, in their channel.
 #[derive(Debug)]
 pub enum ProtocolError {
-    /// Some assertion in the protocol failed.
-    AssertionFailed(String),
+    /// Some participant misbehaved, causing the protocol to fail.
+    Malicious {
+        participant: Participant,
+        round: u64,
+        fault: Fault,
+    },
+    /// Some check failed, but we can't tell which participant is responsible.
+    ///
+    /// Not every failure can be attributed: some checks only happen on values
+    /// aggregated across all participants, in which case we can't tell who cheated.
+    Failed { round: u64, fault: Fault },
+    /// The protocol made no progress before its deadline.
+    ///
+    /// This holds the participants we were still waiting on, which might be
+    /// empty if we did hear from everybody, but not enough to make progress.
+    Timeout(Vec<Participant>),
+    /// The protocol was cancelled locally, with [`Protocol::cancel`].
+    Cancelled,
+    /// The local application refused to let the protocol go ahead.
+    Denied,
     /// Some generic error happened.
     Other(Box<dyn error::Error + Send + Sync>),
 }
@@ -24,7 +81,51 @@ impl fmt::Display for ProtocolError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ProtocolError::Other(e) => write!(f, "{}", e),
-            ProtocolError::AssertionFailed(e) => write!(f, "assertion failed {}", e),
+            ProtocolError::Malicious {
+                participant,
+                round,
+                fault,
+            } => write!(
+                f,
+                "participant {:?} misbehaved in round {}: {}",
+                participant, round, fault
+            ),
+            ProtocolError::Failed { round, fault } => {
+                write!(f, "check failed in round {}: {}", round, fault)
+            }
+            ProtocolError::Timeout(ps) => write!(f, "timed out waiting on {:?}", ps),
+            ProtocolError::Cancelled => write!(f, "protocol was cancelled locally"),
+            ProtocolError::Denied => write!(f, "protocol was denied locally"),
+        }
+    }
+}
+
+impl ProtocolError {
+    /// Return the participant responsible for this error, if one was identified.
+    pub fn culprit(&self) -> Option<Participant> {
+        match self {
+            ProtocolError::Malicious { participant, .. } => Some(*participant),
+            _ => None,
+        }
+    }
+
+    /// Return the round in which a check failed, if this error comes from a failed check.
+    pub fn round(&self) -> Option<u64> {
+        match self {
+            ProtocolError::Malicious { round, .. } | ProtocolError::Failed { round, .. } => {
+                Some(*round)
+            }
+            _ => None,
+        }
+    }
+
+    /// Return which check failed, if this error comes from a failed check.
+    pub fn fault(&self) -> Option<&Fault> {
+        match self {
+            ProtocolError::Malicious { fault, .. } | ProtocolError::Failed { fault, .. } => {
+                Some(fault)
+            }
+            _ => None,
         }
     }
 }
@@ -92,9 +193,10 @@ impl From<u32> for Participant {
 
 /// Represents the data making up a message.
 ///
-/// We choose to just represent messages as opaque vectors of bytes, with all
-/// the serialization logic handled internally.
-pub type MessageData = Vec<u8>;
+/// We choose to just represent messages as opaque buffers of bytes, with all
+/// the serialization logic handled internally. These buffers are reference counted,
+/// so that cloning a message, to send it to everyone, doesn't copy its contents.
+pub type MessageData = bytes::Bytes;
 
 /// Represents an action by a participant in the protocol.
 ///
@@ -140,6 +242,69 @@ pub trait Protocol {
 
     /// Inform the protocol of a new message.
     fn message(&mut self, from: Participant, data: MessageData);
+
+    /// Limit the messages this protocol holds on to for each participant.
+    ///
+    /// Protocols buffer messages which arrive before they're needed, so without
+    /// a limit, a participant can make us use an arbitrary amount of memory,
+    /// by sending messages which are never consumed.
+    ///
+    /// Protocols which don't buffer messages can ignore this, which is what the
+    /// default implementation does. Protocols wrapping others should pass it along.
+    fn set_queue_limits(&mut self, limits: QueueLimits) {
+        let _ = limits;
+    }
+
+    /// Choose the format of the messages this protocol exchanges, see [`Format`].
+    ///
+    /// Messages in another format are ignored, so every participant needs to choose
+    /// the same one, for example with [`negotiate_version`]. This needs to be done
+    /// before the protocol is first poked.
+    ///
+    /// Protocols with a fixed format can ignore this, which is what the default
+    /// implementation does. Protocols wrapping others should pass it along.
+    fn set_format(&mut self, format: Format) {
+        let _ = format;
+    }
+
+    /// Cancel the protocol, tearing it down for good.
+    ///
+    /// This drops the secrets the protocol holds, and wipes the messages it buffered,
+    /// which might contain secret shares. Afterwards, poking the protocol returns
+    /// [`ProtocolError::Cancelled`], and messages are ignored.
+    /// Dropping a protocol has the same effect on its secrets.
+    ///
+    /// The default implementation does nothing, which is only correct for protocols
+    /// without any state worth tearing down. Protocols wrapping others should pass it along.
+    fn cancel(&mut self) {}
+}
+
+/// What to do with a message from a participant which is over its queue limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Drop the message.
+    ///
+    /// If the participant was honest, but just far ahead of us, the protocol
+    /// will be missing that message, and will end up waiting forever,
+    /// so this should be paired with a [`Deadline`].
+    #[default]
+    Drop,
+    /// Fail the protocol, blaming that participant, with [`Fault::TooManyMessages`].
+    Fail,
+}
+
+/// Limits on the messages a protocol holds on to for each participant.
+///
+/// Messages count against these limits from the time they're received,
+/// until the protocol consumes them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QueueLimits {
+    /// The number of messages, or no limit, if `None`.
+    pub max_messages: Option<usize>,
+    /// The total size of the messages, in bytes, or no limit, if `None`.
+    pub max_bytes: Option<usize>,
+    /// What to do with messages over the limit.
+    pub overflow: Overflow,
 }
 
 /// Run a protocol to completion, synchronously.
@@ -236,4 +401,52 @@ pub(crate) fn run_two_party_protocol<T0: fmt::Debug, T1: fmt::Debug>(
     Ok((out0.unwrap(), out1.unwrap()))
 }
 
+pub mod adversary;
+mod checkpoint;
+mod compression;
+mod coordinator;
+mod deadline;
+mod driver;
+mod ids;
 pub(crate) mod internal;
+#[cfg(any(feature = "libp2p", test))]
+pub mod libp2p;
+mod manager;
+mod metrics;
+mod retry;
+#[cfg(test)]
+pub(crate) mod scheduler;
+#[cfg(any(feature = "noise", test))]
+mod secure;
+mod session;
+pub mod simulator;
+#[cfg(any(feature = "tcp", test))]
+pub mod tcp;
+mod transcript;
+mod transport;
+mod version;
+mod weighted;
+
+pub use checkpoint::{Checkpoint, CheckpointRng, Checkpointed};
+#[cfg(feature = "lz4")]
+pub use compression::Lz4;
+#[cfg(feature = "zstd")]
+pub use compression::Zstd;
+pub use compression::{Codec, CompressedTransport, CompressionError, CompressionSettings};
+pub use coordinator::{
+    CoordinatedTransport, CoordinatedTransportError, Coordinator, Recipient, RoutedMessage,
+    RoutingError,
+};
+pub use deadline::Deadline;
+pub use driver::{run_async, Outgoing};
+pub use ids::{IdAction, ParticipantIds, WithIds};
+pub use manager::{SessionAction, SessionManager};
+pub use metrics::{Counter, Metered, Metrics};
+pub use retry::{Retry, RetryError};
+#[cfg(any(feature = "noise", test))]
+pub use secure::{IdentityKey, SecureTransport, SecureTransportError, NOISE_PARAMS};
+pub use session::{Session, SessionId};
+pub use transcript::{replay, Event, Recorder, Replayed, Transcript};
+pub use transport::{run_with_transport, Transport};
+pub use version::{negotiate_version, Format, Version, VersionNegotiation};
+pub use weighted::{Weighted, Weights};
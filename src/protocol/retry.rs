@@ -0,0 +1,221 @@
+//! This module provides a way to retry a protocol, excluding misbehaving participants.
+//!
+//! Signing needs some subset of the participants, and a single malicious participant
+//! in that subset can make it abort. If that participant is identified, then
+//! retrying without them, using another participant in their place, will eventually
+//! succeed, as long as there are enough honest participants to choose from.
+//! [`Retry`] keeps track of which participants have been excluded, and chooses
+//! the subset for each attempt.
+//!
+//! Presignatures are bound to the participants which produced them, so each attempt
+//! needs to presign again, with fresh triples, before signing with the new subset.
+//!
+//! All of the participants need to agree on the subset used in each attempt.
+//! Since a cheater could be caught by some participants but not others, the errors
+//! driving the retries should come from a single coordinator, or be agreed upon
+//! in some other way. Excluding a participant based on an error means trusting
+//! whoever reported that error, since a malicious participant can pretend
+//! to have caught someone else cheating.
+use std::{error, fmt};
+
+use super::{InitializationError, Participant, ProtocolError};
+use crate::participants::ParticipantList;
+
+/// Represents an error when retrying a protocol.
+#[derive(Debug)]
+pub enum RetryError {
+    /// After excluding misbehaving participants, not enough remain for another attempt.
+    ///
+    /// This holds the error from the last attempt.
+    NotEnoughParticipants(ProtocolError),
+    /// Every attempt failed, with this being the error from the last one.
+    TooManyAttempts(ProtocolError),
+}
+
+impl fmt::Display for RetryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetryError::NotEnoughParticipants(e) => {
+                write!(f, "not enough participants left to retry: {}", e)
+            }
+            RetryError::TooManyAttempts(e) => write!(f, "too many failed attempts: {}", e),
+        }
+    }
+}
+
+impl error::Error for RetryError {}
+
+/// Chooses the participants for each attempt at a protocol, excluding those who misbehaved.
+#[derive(Debug, Clone)]
+pub struct Retry {
+    candidates: Vec<Participant>,
+    size: usize,
+    excluded: Vec<Participant>,
+}
+
+impl Retry {
+    /// Create a new retry, choosing `size` participants out of some candidates.
+    ///
+    /// The candidates are listed in order of preference: each attempt uses
+    /// the first `size` of them which haven't been excluded.
+    pub fn new(candidates: &[Participant], size: usize) -> Result<Self, InitializationError> {
+        if size == 0 {
+            return Err(InitializationError::BadParameters(
+                "subset size must be at least 1".to_string(),
+            ));
+        }
+        if ParticipantList::new(candidates).is_none() {
+            return Err(InitializationError::BadParameters(
+                "candidate list contains duplicates".to_string(),
+            ));
+        }
+        if candidates.len() < size {
+            return Err(InitializationError::BadParameters(format!(
+                "subset size {} is larger than the number of candidates {}",
+                size,
+                candidates.len()
+            )));
+        }
+        Ok(Self {
+            candidates: candidates.to_vec(),
+            size,
+            excluded: Vec::new(),
+        })
+    }
+
+    /// The participants to use in the next attempt, if enough remain.
+    pub fn participants(&self) -> Option<Vec<Participant>> {
+        let out: Vec<_> = self
+            .candidates
+            .iter()
+            .copied()
+            .filter(|p| !self.excluded.contains(p))
+            .take(self.size)
+            .collect();
+        (out.len() == self.size).then_some(out)
+    }
+
+    /// The participants which have been excluded so far, in the order they were excluded.
+    pub fn excluded(&self) -> &[Participant] {
+        &self.excluded
+    }
+
+    /// Exclude a participant from all further attempts.
+    pub fn exclude(&mut self, participant: Participant) {
+        if self.candidates.contains(&participant) && !self.excluded.contains(&participant) {
+            self.excluded.push(participant);
+        }
+    }
+
+    /// Exclude the participants blamed for an error, returning whether any were.
+    ///
+    /// A participant is blamed if they were identified as the culprit, or
+    /// if they were among the participants we timed out waiting on.
+    pub fn report(&mut self, error: &ProtocolError) -> bool {
+        let before = self.excluded.len();
+        match error {
+            ProtocolError::Timeout(ps) => {
+                for p in ps {
+                    self.exclude(*p);
+                }
+            }
+            e => {
+                if let Some(p) = e.culprit() {
+                    self.exclude(p);
+                }
+            }
+        }
+        self.excluded.len() > before
+    }
+
+    /// Run a protocol until it succeeds, using at most `max_attempts` attempts.
+    ///
+    /// Each attempt is given the participants to run the protocol with. After a failed
+    /// attempt, the participants blamed for the failure are replaced by the next
+    /// candidates. A failure blaming nobody is retried with the same participants.
+    pub fn run<T>(
+        &mut self,
+        max_attempts: usize,
+        mut attempt: impl FnMut(&[Participant]) -> Result<T, ProtocolError>,
+    ) -> Result<T, RetryError> {
+        let mut last_error = None;
+        for _ in 0..max_attempts {
+            let participants = match self.participants() {
+                Some(ps) => ps,
+                None => break,
+            };
+            match attempt(&participants) {
+                Ok(out) => return Ok(out),
+                Err(e) => {
+                    self.report(&e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        let last_error = last_error
+            .unwrap_or_else(|| ProtocolError::Other("no attempts were made".to_string().into()));
+        if self.participants().is_none() {
+            Err(RetryError::NotEnoughParticipants(last_error))
+        } else {
+            Err(RetryError::TooManyAttempts(last_error))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocol::Fault;
+
+    fn malicious(participant: Participant) -> ProtocolError {
+        ProtocolError::Malicious {
+            participant,
+            round: 0,
+            fault: Fault::BadProof,
+        }
+    }
+
+    #[test]
+    fn test_retry() {
+        let candidates: Vec<_> = (0..5u32).map(Participant::from).collect();
+        let cheaters = [candidates[0], candidates[2]];
+
+        let mut retry = Retry::new(&candidates, 3).unwrap();
+        let mut attempts = Vec::new();
+        let out = retry.run(5, |ps| {
+            attempts.push(ps.to_vec());
+            match ps.iter().find(|p| cheaters.contains(p)) {
+                Some(p) => Err(malicious(*p)),
+                None => Ok(ps.to_vec()),
+            }
+        });
+        assert_eq!(
+            out.unwrap(),
+            vec![candidates[1], candidates[3], candidates[4]]
+        );
+        assert_eq!(attempts.len(), 3);
+        assert_eq!(retry.excluded(), &cheaters);
+
+        // Once too many are excluded, we give up.
+        let mut retry = Retry::new(&candidates, 4).unwrap();
+        let out = retry.run(5, |ps| -> Result<(), _> {
+            Err(ProtocolError::Timeout(ps[..1].to_vec()))
+        });
+        assert!(matches!(out, Err(RetryError::NotEnoughParticipants(_))));
+        assert_eq!(retry.excluded().len(), 2);
+
+        // Failures blaming nobody only retry so many times.
+        let mut retry = Retry::new(&candidates, 3).unwrap();
+        let out = retry.run(2, |_| -> Result<(), _> {
+            Err(ProtocolError::Failed {
+                round: 0,
+                fault: Fault::BadShare,
+            })
+        });
+        assert!(matches!(out, Err(RetryError::TooManyAttempts(_))));
+        assert!(retry.excluded().is_empty());
+
+        assert!(Retry::new(&candidates, 6).is_err());
+        assert!(Retry::new(&[candidates[0], candidates[0]], 1).is_err());
+    }
+}
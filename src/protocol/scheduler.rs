@@ -0,0 +1,178 @@
+//! A test harness running protocols under adversarial scheduling.
+//!
+//! [`run_protocol`](super::run_protocol) delivers messages as soon as they're sent,
+//! which hides bugs depending on the order messages arrive in. Here, every message
+//! sent goes into a pool, and each step delivers a message chosen at random from it,
+//! so messages can be delayed for arbitrarily long, and get interleaved in any order,
+//! across links, and within a single link. The choices come from a seed, so that
+//! a failing schedule can be replayed.
+use std::collections::HashMap;
+
+use magikitten::MeowRng;
+use rand_core::RngCore;
+
+use super::{Action, MessageData, Participant, Protocol, ProtocolError};
+
+/// Run a protocol to completion, delivering messages in an order chosen by a seed.
+#[allow(clippy::type_complexity)]
+pub(crate) fn run_protocol_scheduled<T>(
+    mut ps: Vec<(Participant, Box<dyn Protocol<Output = T>>)>,
+    seed: u64,
+) -> Result<Vec<(Participant, T)>, ProtocolError> {
+    let mut seed_bytes = [0u8; 32];
+    seed_bytes[..8].copy_from_slice(&seed.to_le_bytes());
+    let mut rng = MeowRng::new(&seed_bytes);
+
+    let indices: HashMap<Participant, usize> =
+        ps.iter().enumerate().map(|(i, (p, _))| (*p, i)).collect();
+    let size = ps.len();
+
+    let mut pool: Vec<(usize, Participant, MessageData)> = Vec::new();
+    let mut outputs: Vec<Option<T>> = (0..size).map(|_| None).collect();
+    let mut done = 0;
+    let mut to_poke: Vec<usize> = (0..size).collect();
+    loop {
+        for i in to_poke.drain(..) {
+            if outputs[i].is_some() {
+                continue;
+            }
+            let from = ps[i].0;
+            loop {
+                match ps[i].1.poke()? {
+                    Action::Wait => break,
+                    Action::SendMany(m) => {
+                        pool.extend((0..size).filter(|&j| j != i).map(|j| (j, from, m.clone())))
+                    }
+                    Action::SendPrivate(to, m) => pool.push((indices[&to], from, m)),
+                    Action::Return(r) => {
+                        outputs[i] = Some(r);
+                        done += 1;
+                        break;
+                    }
+                }
+            }
+        }
+        if done == size {
+            break;
+        }
+        if pool.is_empty() {
+            return Err(ProtocolError::Other(
+                format!("protocol stalled under schedule {}", seed).into(),
+            ));
+        }
+        let k = (rng.next_u64() % pool.len() as u64) as usize;
+        let (to, from, m) = pool.swap_remove(k);
+        ps[to].1.message(from, m);
+        to_poke.push(to);
+    }
+
+    Ok(ps
+        .into_iter()
+        .zip(outputs)
+        .map(|((p, _), out)| (p, out.unwrap()))
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use elliptic_curve::Field;
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::{
+        keygen, math::Polynomial, presign, sign, triples, KeygenOutput, PresignArguments,
+        PresignOutput,
+    };
+
+    fn participants(n: u32) -> Vec<Participant> {
+        (0..n).map(Participant::from).collect()
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn boxed<T, P: Protocol<Output = T> + 'static>(
+        ps: impl IntoIterator<Item = (Participant, P)>,
+    ) -> Vec<(Participant, Box<dyn Protocol<Output = T>>)> {
+        ps.into_iter()
+            .map(|(p, protocol)| {
+                let protocol: Box<dyn Protocol<Output = T>> = Box::new(protocol);
+                (p, protocol)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_scheduled_keygen() {
+        let participants = participants(4);
+        for seed in 0..16 {
+            let ps = boxed(
+                participants
+                    .iter()
+                    .map(|&p| (p, keygen::<Secp256k1>(&participants, p, 3).unwrap())),
+            );
+            let out = run_protocol_scheduled(ps, seed)
+                .unwrap_or_else(|e| panic!("schedule {}: {}", seed, e));
+            assert!(out.iter().all(|(_, o)| o.public_key == out[0].1.public_key));
+        }
+    }
+
+    #[test]
+    fn test_scheduled_triples() {
+        let participants = participants(3);
+        for seed in 0..2 {
+            let ps = boxed(participants.iter().map(|&p| {
+                let protocol = triples::generate_triple::<Secp256k1>(&participants, p, 2);
+                (p, protocol.unwrap())
+            }));
+            let out = run_protocol_scheduled(ps, seed)
+                .unwrap_or_else(|e| panic!("schedule {}: {}", seed, e));
+            let big_c = out[0].1 .1.big_c;
+            assert!(out.iter().all(|(_, (_, pub_))| pub_.big_c == big_c));
+        }
+    }
+
+    #[test]
+    fn test_scheduled_presign_and_sign() {
+        let participants = participants(3);
+        let threshold = 2;
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let public_key = (ProjectivePoint::GENERATOR * f.evaluate_zero()).to_affine();
+        let msg_hash = Scalar::random(&mut OsRng);
+
+        for seed in 0..8 {
+            let (pub0, shares0) = triples::deal::<Secp256k1>(&mut OsRng, &participants, threshold);
+            let (pub1, shares1) = triples::deal::<Secp256k1>(&mut OsRng, &participants, threshold);
+            let ps = boxed(participants.iter().zip(shares0).zip(shares1).map(
+                |((&p, share0), share1)| {
+                    let keygen_out = KeygenOutput {
+                        private_share: f.evaluate(&p.scalar::<Secp256k1>()),
+                        public_key,
+                    };
+                    let args = PresignArguments {
+                        triple0: (share0, pub0.clone()),
+                        triple1: (share1, pub1.clone()),
+                        keygen_out,
+                        threshold,
+                    };
+                    (
+                        p,
+                        presign(&participants, p, &participants, p, args).unwrap(),
+                    )
+                },
+            ));
+            let presignatures: Vec<(Participant, PresignOutput<Secp256k1>)> =
+                run_protocol_scheduled(ps, seed)
+                    .unwrap_or_else(|e| panic!("schedule {}: {}", seed, e));
+
+            let ps = boxed(presignatures.into_iter().map(|(p, presignature)| {
+                let protocol = sign(&participants, p, public_key, presignature, msg_hash);
+                (p, protocol.unwrap())
+            }));
+            let out = run_protocol_scheduled(ps, seed)
+                .unwrap_or_else(|e| panic!("schedule {}: {}", seed, e));
+            assert!(out
+                .iter()
+                .all(|(_, sig)| sig.verify(&public_key, &msg_hash)));
+        }
+    }
+}
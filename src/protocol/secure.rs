@@ -0,0 +1,615 @@
+//! An encrypted and authenticated layer on top of any [`Transport`].
+//!
+//! Protocols send private messages which must only be readable by their recipient,
+//! and rely on knowing who sent each message. [`SecureTransport`] provides this,
+//! given a long term [`IdentityKey`] for each participant, known in advance.
+//!
+//! Before running a protocol, each pair of participants runs the handshake of
+//! `Noise_KK_25519_ChaChaPoly_BLAKE2s`, using the `snow` crate, with the participant
+//! with the smaller identifier as the initiator. In the KK pattern, both sides know
+//! each other's static key beforehand, which is exactly our situation, so the handshake
+//! takes a single round trip, and authenticates both sides. The session identifier,
+//! and both participants, go into the prologue, so a handshake can't be replayed
+//! in another session. Since the ephemeral keys are thrown away afterwards,
+//! leaking an identity key later doesn't reveal past messages.
+//!
+//! After the handshake, each message is encrypted with the transport keys of Noise.
+//! These use a counter as the nonce, so messages can't be replayed, or dropped silently.
+//! This requires the underlying transport to deliver the messages between
+//! two participants in the order they were sent, as TCP does.
+//! Noise limits messages to 65535 bytes, so larger messages are split into several
+//! Noise messages, inside of a single message of the underlying transport, with
+//! their total length encrypted in the first one, so that truncating them fails.
+//!
+//! This layer needs the `noise` feature.
+use std::{
+    collections::{HashMap, VecDeque},
+    error, fmt,
+};
+
+use magikitten::MeowRng;
+use rand_core::{CryptoRngCore, OsRng, RngCore};
+use snow::{
+    params::{CipherChoice, DHChoice, HashChoice, NoiseParams},
+    resolvers::{CryptoResolver, DefaultResolver},
+    types::{Cipher, Dh, Hash, Random},
+    Builder, HandshakeState, TransportState,
+};
+use zeroize::Zeroize;
+
+use super::{internal::wipe_message, transport::Transport, MessageData, Participant, SessionId};
+use crate::crypto::fork_rng;
+
+/// The Noise protocol each pair of participants runs.
+pub const NOISE_PARAMS: &str = "Noise_KK_25519_ChaChaPoly_BLAKE2s";
+
+const PROLOGUE_LABEL: &[u8] = b"cait-sith v0.8.0 secure transport handshake";
+
+const HANDSHAKE: u8 = 0;
+const DATA: u8 = 1;
+
+/// The length prefixing the plaintext of each message.
+const LEN_LEN: usize = 8;
+/// The largest handshake message of the KK pattern: an ephemeral key, and an empty payload.
+const HANDSHAKE_LEN: usize = KEY_LEN + TAG_LEN;
+/// The largest Noise message, and the plaintext it holds.
+const MAX_NOISE_LEN: usize = 65535;
+const CHUNK_LEN: usize = MAX_NOISE_LEN - TAG_LEN;
+
+/// The number of bytes in an X25519 key.
+const KEY_LEN: usize = 32;
+/// The number of bytes in a ChaChaPoly tag.
+const TAG_LEN: usize = 16;
+
+fn noise_params() -> NoiseParams {
+    // Unwrapping is fine, since the parameters are a valid constant.
+    NOISE_PARAMS.parse().unwrap()
+}
+
+/// The long term X25519 key identifying a participant, for use with [`SecureTransport`].
+#[derive(Clone)]
+pub struct IdentityKey([u8; KEY_LEN]);
+
+impl IdentityKey {
+    /// Generate a new identity key.
+    pub fn generate(rng: &mut impl CryptoRngCore) -> Self {
+        let mut bytes = [0u8; KEY_LEN];
+        rng.fill_bytes(&mut bytes);
+        Self(bytes)
+    }
+
+    /// Use the bytes of an existing X25519 private key.
+    pub fn from_bytes(bytes: [u8; KEY_LEN]) -> Self {
+        Self(bytes)
+    }
+
+    /// The bytes of this private key, e.g. to store it.
+    pub fn to_bytes(&self) -> [u8; KEY_LEN] {
+        self.0
+    }
+
+    /// The public key, which other participants need to know in advance.
+    pub fn public_key(&self) -> [u8; KEY_LEN] {
+        // Unwrapping is fine, since the default resolver always provides X25519.
+        let mut dh = DefaultResolver.resolve_dh(&DHChoice::Curve25519).unwrap();
+        dh.set(&self.0);
+        // Unwrapping is fine, since X25519 public keys are 32 bytes.
+        dh.pubkey().try_into().unwrap()
+    }
+}
+
+impl Drop for IdentityKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// Gives snow our randomness, for its ephemeral keys, and the default primitives.
+struct Resolver {
+    seed: [u8; 32],
+}
+
+impl Resolver {
+    fn new(rng: &mut impl CryptoRngCore) -> Self {
+        let mut seed = [0u8; 32];
+        rng.fill_bytes(&mut seed);
+        Self { seed }
+    }
+}
+
+impl Drop for Resolver {
+    fn drop(&mut self) {
+        self.seed.zeroize();
+    }
+}
+
+struct Rng(MeowRng);
+
+impl Random for Rng {
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), snow::Error> {
+        self.0.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoResolver for Resolver {
+    fn resolve_rng(&self) -> Option<Box<dyn Random>> {
+        Some(Box::new(Rng(MeowRng::new(&self.seed))))
+    }
+
+    fn resolve_dh(&self, choice: &DHChoice) -> Option<Box<dyn Dh>> {
+        DefaultResolver.resolve_dh(choice)
+    }
+
+    fn resolve_hash(&self, choice: &HashChoice) -> Option<Box<dyn Hash>> {
+        DefaultResolver.resolve_hash(choice)
+    }
+
+    fn resolve_cipher(&self, choice: &CipherChoice) -> Option<Box<dyn Cipher>> {
+        DefaultResolver.resolve_cipher(choice)
+    }
+}
+
+/// Represents an error which can happen when using a [`SecureTransport`].
+#[derive(Debug)]
+pub enum SecureTransportError<E> {
+    /// The underlying transport failed.
+    Transport(E),
+    /// We received a message from a participant without a known identity key.
+    UnknownPeer(Participant),
+    /// A message from this participant failed to parse, decrypt, or authenticate.
+    BadMessage(Participant),
+}
+
+impl<E: fmt::Display> fmt::Display for SecureTransportError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "transport error: {}", e),
+            Self::UnknownPeer(p) => write!(f, "message from unknown participant {:?}", p),
+            Self::BadMessage(p) => write!(f, "bad message from participant {:?}", p),
+        }
+    }
+}
+
+impl<E: error::Error + 'static> error::Error for SecureTransportError<E> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Transport(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Start the handshake with a peer, as the initiator if we have the smaller identifier.
+fn handshake(
+    me: Participant,
+    peer: Participant,
+    identity: &IdentityKey,
+    peer_key: &[u8; KEY_LEN],
+    session_id: &SessionId,
+    rng: &mut impl CryptoRngCore,
+) -> Result<HandshakeState, snow::Error> {
+    let (initiator, responder) = (me.min(peer), me.max(peer));
+    let mut prologue = PROLOGUE_LABEL.to_vec();
+    prologue.extend_from_slice(session_id.as_bytes());
+    prologue.extend_from_slice(&initiator.bytes());
+    prologue.extend_from_slice(&responder.bytes());
+
+    let builder = Builder::with_resolver(noise_params(), Box::new(Resolver::new(rng)))
+        .prologue(&prologue)?
+        .local_private_key(&identity.0)?
+        .remote_public_key(peer_key)?;
+    if me == initiator {
+        builder.build_initiator()
+    } else {
+        builder.build_responder()
+    }
+}
+
+/// A transport encrypting and authenticating each message, using identity keys.
+pub struct SecureTransport<Tr> {
+    transport: Tr,
+    channels: HashMap<Participant, TransportState>,
+    buffered: VecDeque<(Participant, MessageData)>,
+}
+
+impl<Tr: Transport + Send> SecureTransport<Tr> {
+    /// Run a handshake with every other participant, over a transport.
+    ///
+    /// Every participant needs the public identity keys of all of the others, in `peers`,
+    /// and should use the same session identifier, which should be unique to this run.
+    pub async fn connect(
+        transport: Tr,
+        me: Participant,
+        identity: &IdentityKey,
+        peers: &HashMap<Participant, [u8; KEY_LEN]>,
+        session_id: &SessionId,
+    ) -> Result<Self, SecureTransportError<Tr::Error>> {
+        Self::connect_with_rng(transport, me, identity, peers, session_id, &mut OsRng).await
+    }
+
+    /// Like [`connect()`](Self::connect), except drawing all of the randomness from a given rng.
+    pub async fn connect_with_rng(
+        mut transport: Tr,
+        me: Participant,
+        identity: &IdentityKey,
+        peers: &HashMap<Participant, [u8; KEY_LEN]>,
+        session_id: &SessionId,
+        rng: &mut impl CryptoRngCore,
+    ) -> Result<Self, SecureTransportError<Tr::Error>> {
+        let mut rng = fork_rng(rng);
+        let bad = SecureTransportError::BadMessage;
+        let others: Vec<_> = peers.keys().copied().filter(|&p| p != me).collect();
+        let mut buf = [0u8; HANDSHAKE_LEN];
+
+        // We initiate the handshakes with the participants after us.
+        let mut initiated = HashMap::new();
+        for &p in others.iter().filter(|&&p| me < p) {
+            let mut state =
+                handshake(me, p, identity, &peers[&p], session_id, &mut rng).map_err(|_| bad(p))?;
+            let len = state.write_message(&[], &mut buf).map_err(|_| bad(p))?;
+            let mut hello = vec![HANDSHAKE];
+            hello.extend_from_slice(&buf[..len]);
+            transport
+                .send(p, hello.into())
+                .await
+                .map_err(SecureTransportError::Transport)?;
+            initiated.insert(p, state);
+        }
+
+        // Peers might finish their handshake before us, and start sending messages.
+        let mut buffered = VecDeque::new();
+        let mut channels = HashMap::with_capacity(others.len());
+        while channels.len() < others.len() {
+            let (from, data) = transport
+                .recv()
+                .await
+                .map_err(SecureTransportError::Transport)?;
+            if from == me || !peers.contains_key(&from) {
+                return Err(SecureTransportError::UnknownPeer(from));
+            }
+            match data.split_first() {
+                Some((&HANDSHAKE, message)) if !channels.contains_key(&from) => {
+                    let state = if me < from {
+                        // This is the response to our handshake.
+                        let mut state = initiated.remove(&from).ok_or(bad(from))?;
+                        state
+                            .read_message(message, &mut buf)
+                            .map_err(|_| bad(from))?;
+                        state
+                    } else {
+                        let mut state =
+                            handshake(me, from, identity, &peers[&from], session_id, &mut rng)
+                                .map_err(|_| bad(from))?;
+                        state
+                            .read_message(message, &mut buf)
+                            .map_err(|_| bad(from))?;
+                        let len = state.write_message(&[], &mut buf).map_err(|_| bad(from))?;
+                        let mut response = vec![HANDSHAKE];
+                        response.extend_from_slice(&buf[..len]);
+                        transport
+                            .send(from, response.into())
+                            .await
+                            .map_err(SecureTransportError::Transport)?;
+                        state
+                    };
+                    let channel = state.into_transport_mode().map_err(|_| bad(from))?;
+                    channels.insert(from, channel);
+                }
+                Some((&DATA, _)) => buffered.push_back((from, data)),
+                _ => return Err(bad(from)),
+            }
+        }
+
+        Ok(Self {
+            transport,
+            channels,
+            buffered,
+        })
+    }
+
+    fn decrypt(
+        &mut self,
+        from: Participant,
+        data: MessageData,
+    ) -> Result<MessageData, SecureTransportError<Tr::Error>> {
+        let bad = || SecureTransportError::BadMessage(from);
+        let channel = self
+            .channels
+            .get_mut(&from)
+            .ok_or(SecureTransportError::UnknownPeer(from))?;
+        let Some((&DATA, chunks)) = data.split_first() else {
+            return Err(bad());
+        };
+        let mut plaintext = vec![0u8; chunks.len()];
+        let mut len = 0;
+        for chunk in chunks.chunks(MAX_NOISE_LEN) {
+            match channel.read_message(chunk, &mut plaintext[len..]) {
+                Ok(read) => len += read,
+                Err(_) => {
+                    plaintext.zeroize();
+                    return Err(bad());
+                }
+            }
+        }
+        plaintext.truncate(len);
+        // The length was encrypted, so it only differs if chunks were cut off.
+        if len < LEN_LEN
+            || u64::from_le_bytes(plaintext[..LEN_LEN].try_into().unwrap())
+                != (len - LEN_LEN) as u64
+        {
+            plaintext.zeroize();
+            return Err(bad());
+        }
+        plaintext.drain(..LEN_LEN);
+        Ok(plaintext.into())
+    }
+}
+
+impl<Tr: Transport + Send> Transport for SecureTransport<Tr> {
+    type Error = SecureTransportError<Tr::Error>;
+
+    async fn send(&mut self, to: Participant, data: MessageData) -> Result<(), Self::Error> {
+        let channel = self
+            .channels
+            .get_mut(&to)
+            .ok_or(SecureTransportError::UnknownPeer(to))?;
+        let mut plaintext = Vec::with_capacity(LEN_LEN + data.len());
+        plaintext.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        plaintext.extend_from_slice(&data);
+        wipe_message(data);
+
+        let chunks = plaintext.len().div_ceil(CHUNK_LEN);
+        let mut out = vec![0u8; 1 + plaintext.len() + chunks * TAG_LEN];
+        out[0] = DATA;
+        let mut len = 1;
+        for chunk in plaintext.chunks(CHUNK_LEN) {
+            // Encrypting only fails for messages which are too long, or after 2^64 of them.
+            len += channel
+                .write_message(chunk, &mut out[len..])
+                .expect("encrypting a chunk should succeed");
+        }
+        plaintext.zeroize();
+        self.transport
+            .send(to, out.into())
+            .await
+            .map_err(SecureTransportError::Transport)
+    }
+
+    async fn recv(&mut self) -> Result<(Participant, MessageData), Self::Error> {
+        let (from, data) = match self.buffered.pop_front() {
+            Some(message) => message,
+            None => self
+                .transport
+                .recv()
+                .await
+                .map_err(SecureTransportError::Transport)?,
+        };
+        let plaintext = self.decrypt(from, data)?;
+        Ok((from, plaintext))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        error::Error,
+        sync::{Arc, Mutex},
+    };
+
+    use async_channel::{Receiver, Sender};
+    use futures_lite::future;
+    use k256::Secp256k1;
+    use smol::{block_on, Executor};
+
+    use super::*;
+    use crate::{keygen, protocol::run_with_transport};
+
+    /// A change to make to the messages carrying data.
+    type Tamper = Arc<Mutex<Option<fn(&mut Vec<u8>)>>>;
+
+    /// A transport over in memory channels, which can tamper with messages carrying data.
+    struct ChannelTransport {
+        me: Participant,
+        senders: HashMap<Participant, Sender<(Participant, MessageData)>>,
+        incoming: Receiver<(Participant, MessageData)>,
+        tamper: Tamper,
+    }
+
+    #[derive(Debug)]
+    struct Closed;
+
+    impl fmt::Display for Closed {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "channel closed")
+        }
+    }
+
+    impl error::Error for Closed {}
+
+    impl Transport for ChannelTransport {
+        type Error = Closed;
+
+        async fn send(&mut self, to: Participant, mut data: MessageData) -> Result<(), Closed> {
+            if let Some(tamper) = *self.tamper.lock().unwrap() {
+                if data[0] == DATA {
+                    let mut tampered = data.to_vec();
+                    tamper(&mut tampered);
+                    data = tampered.into();
+                }
+            }
+            self.senders[&to]
+                .send((self.me, data))
+                .await
+                .map_err(|_| Closed)
+        }
+
+        async fn recv(&mut self) -> Result<(Participant, MessageData), Closed> {
+            self.incoming.recv().await.map_err(|_| Closed)
+        }
+    }
+
+    fn transports(participants: &[Participant], tamper: &Tamper) -> Vec<ChannelTransport> {
+        let (senders, receivers): (HashMap<_, _>, Vec<_>) = participants
+            .iter()
+            .map(|&p| {
+                let (s, r) = async_channel::unbounded();
+                ((p, s), r)
+            })
+            .unzip();
+        participants
+            .iter()
+            .zip(receivers)
+            .map(|(&me, incoming)| ChannelTransport {
+                me,
+                senders: senders.clone(),
+                incoming,
+                tamper: tamper.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_secure_keygen() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let identities: Vec<_> = participants
+            .iter()
+            .map(|_| IdentityKey::generate(&mut OsRng))
+            .collect();
+        let peers: HashMap<_, _> = participants
+            .iter()
+            .zip(&identities)
+            .map(|(&p, key)| (p, key.public_key()))
+            .collect();
+        let tamper = Tamper::default();
+
+        let ex = Executor::new();
+        let results = block_on(ex.run(async {
+            let tasks: Vec<_> = transports(&participants, &tamper)
+                .into_iter()
+                .zip(identities)
+                .map(|(transport, identity)| {
+                    let me = transport.me;
+                    let participants = participants.clone();
+                    let peers = peers.clone();
+                    ex.spawn(async move {
+                        let mut transport = SecureTransport::connect(
+                            transport,
+                            me,
+                            &identity,
+                            &peers,
+                            &SessionId::derive(b"test", &participants, 0, 0),
+                        )
+                        .await?;
+                        let protocol = keygen::<Secp256k1>(&participants, me, 2)?;
+                        let out =
+                            run_with_transport(protocol, &participants, me, &mut transport).await?;
+                        Ok::<_, Box<dyn Error + Send + Sync>>(out)
+                    })
+                })
+                .collect();
+            let mut results = Vec::new();
+            for task in tasks {
+                results.push(task.await?);
+            }
+            Ok::<_, Box<dyn Error + Send + Sync>>(results)
+        }))
+        .map_err(|e| e as Box<dyn Error>)?;
+
+        assert_eq!(results[0].public_key, results[1].public_key);
+        assert_eq!(results[1].public_key, results[2].public_key);
+        Ok(())
+    }
+
+    /// Send a message from one participant to another, returning what the second received.
+    ///
+    /// The second participant only connects if it expects the identity key of the first.
+    fn send_one(
+        identities: &[IdentityKey; 2],
+        peers: &HashMap<Participant, [u8; 32]>,
+        tamper: &Tamper,
+        message: &[u8],
+    ) -> Result<(Participant, MessageData), SecureTransportError<Closed>> {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let session = SessionId::derive(b"test", &participants, 0, 0);
+        let mut transports = transports(&participants, tamper);
+        let t1 = transports.pop().unwrap();
+        let t0 = transports.pop().unwrap();
+        let send = async {
+            let mut a =
+                SecureTransport::connect(t0, participants[0], &identities[0], peers, &session)
+                    .await?;
+            a.send(participants[1], MessageData::copy_from_slice(message))
+                .await?;
+            // The sender never gets an answer if the handshake fails on the other side.
+            future::pending().await
+        };
+        let recv = async {
+            let mut b =
+                SecureTransport::connect(t1, participants[1], &identities[1], peers, &session)
+                    .await?;
+            b.recv().await
+        };
+        block_on(future::or(send, recv))
+    }
+
+    #[test]
+    fn test_secure_rejects_bad_messages() {
+        let participants = [Participant::from(0u32), Participant::from(1u32)];
+        let identities = [
+            IdentityKey::generate(&mut OsRng),
+            IdentityKey::generate(&mut OsRng),
+        ];
+        let mut peers: HashMap<_, _> = participants
+            .iter()
+            .zip(&identities)
+            .map(|(&p, key)| (p, key.public_key()))
+            .collect();
+        let tamper = Tamper::default();
+
+        let (from, data) = send_one(&identities, &peers, &tamper, b"hello").unwrap();
+        assert_eq!(from, participants[0]);
+        assert_eq!(data, b"hello"[..]);
+
+        // Messages too large for a single Noise message get split up.
+        let large: Vec<u8> = (0..3 * MAX_NOISE_LEN).map(|i| i as u8).collect();
+        let (_, data) = send_one(&identities, &peers, &tamper, &large).unwrap();
+        assert_eq!(data, large);
+
+        *tamper.lock().unwrap() = Some(|data| *data.last_mut().unwrap() ^= 1);
+        assert!(matches!(
+            send_one(&identities, &peers, &tamper, b"hello"),
+            Err(SecureTransportError::BadMessage(_))
+        ));
+
+        // Dropping the last chunks of a message leaves valid Noise messages, but not a valid message.
+        *tamper.lock().unwrap() = Some(|data| data.truncate(1 + MAX_NOISE_LEN));
+        assert!(matches!(
+            send_one(&identities, &peers, &tamper, &large),
+            Err(SecureTransportError::BadMessage(_))
+        ));
+        *tamper.lock().unwrap() = None;
+
+        // With the wrong identity key for the sender, the handshake fails.
+        peers.insert(
+            participants[0],
+            IdentityKey::generate(&mut OsRng).public_key(),
+        );
+        assert!(matches!(
+            send_one(&identities, &peers, &tamper, b"hello"),
+            Err(SecureTransportError::BadMessage(_))
+        ));
+    }
+
+    #[test]
+    fn test_identity_key_round_trip() {
+        let key = IdentityKey::generate(&mut OsRng);
+        let copy = IdentityKey::from_bytes(key.to_bytes());
+        assert_eq!(copy.public_key(), key.public_key());
+        assert_ne!(key.public_key(), key.to_bytes());
+    }
+}
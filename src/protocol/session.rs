@@ -0,0 +1,299 @@
+//! This module provides a way to bind the messages of a protocol to a session.
+//!
+//! Internally, messages are routed to the right part of a protocol by a header,
+//! which only depends on the structure of the protocol. This means that a message
+//! from one execution of a protocol is also a valid message for any other execution,
+//! and the caller has to make sure that sessions are never mixed.
+//!
+//! [`Session`] wraps a protocol so that every message it sends is tagged with
+//! the session it belongs to, along with who sent it, who it's for, and a sequence number.
+//! Messages with the wrong tag are dropped, as are messages whose sequence number
+//! was already seen, so that messages captured from one session
+//! can't be replayed into another one, nor replayed within the same session.
+//!
+//! Note that this relies on the transport authenticating senders: the tag itself
+//! isn't keyed, and only binds a message to its context.
+//!
+//! Session ids are [`SessionId`]s, rather than arbitrary bytes, and should be
+//! created with [`SessionId::derive`], which hashes everything distinguishing
+//! one execution from another. Reusing an id for two executions doesn't just mix
+//! up their messages: the OT extension used in triple generation, and the transcripts
+//! of proofs, rely on session ids being unique, and reusing one silently weakens them.
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::crypto::{hash, Digest};
+
+use super::{Action, Format, MessageData, Participant, Protocol, ProtocolError, QueueLimits};
+
+/// The label we use for deriving session ids.
+const SESSION_ID_LABEL: &[u8] = b"cait-sith session id";
+
+/// The number of bytes prepended to each message.
+const HEADER_LEN: usize = 32 + 8;
+
+/// Identifies a single execution of a protocol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct SessionId([u8; 32]);
+
+impl SessionId {
+    /// Derive the id of a session.
+    ///
+    /// The `purpose` says what the session is for, like `b"presign"`, and the participants
+    /// are those running it, in any order. Runs with the same purpose and participants
+    /// are then told apart by the `epoch`, which should change whenever the key does,
+    /// e.g. with each refresh, and by the `counter`, which should never repeat within an epoch.
+    pub fn derive(purpose: &[u8], participants: &[Participant], counter: u64, epoch: u64) -> Self {
+        let mut participants = participants.to_vec();
+        participants.sort_unstable();
+        participants.dedup();
+        let digest = hash(&(SESSION_ID_LABEL, purpose, participants, counter, epoch));
+        Self(digest.as_ref().try_into().unwrap())
+    }
+
+    /// Use some bytes as a session id directly.
+    ///
+    /// This is for ids agreed on some other way, which are known to be unique,
+    /// like the hash of a request everyone has signed.
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    /// The bytes making up this id.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|b| write!(f, "{:02x}", b))
+    }
+}
+
+fn tag(session: &SessionId, from: Participant, to: Option<Participant>) -> Digest {
+    hash(&(b"cait-sith session tag", session, from, to))
+}
+
+/// A protocol, with its messages bound to a given session.
+///
+/// All the participants need to wrap their protocol using the same session id,
+/// which should be unique for each execution of a protocol.
+pub struct Session<P> {
+    id: SessionId,
+    me: Participant,
+    protocol: P,
+    sequence: u64,
+    seen: HashMap<Participant, HashSet<u64>>,
+}
+
+impl<P: Protocol> Session<P> {
+    /// Wrap a protocol, for the participant `me`, in the session with a given id.
+    pub fn new(id: SessionId, me: Participant, protocol: P) -> Self {
+        Self {
+            id,
+            me,
+            protocol,
+            sequence: 0,
+            seen: HashMap::new(),
+        }
+    }
+
+    fn wrap(&mut self, to: Option<Participant>, data: MessageData) -> MessageData {
+        let mut out = Vec::with_capacity(HEADER_LEN + data.len());
+        out.extend_from_slice(tag(&self.id, self.me, to).as_ref());
+        out.extend_from_slice(&self.sequence.to_le_bytes());
+        out.extend_from_slice(&data);
+        self.sequence += 1;
+        out.into()
+    }
+
+    /// Unwrap the message, if it's a fresh message for us, in this session.
+    fn unwrap(&mut self, from: Participant, mut data: MessageData) -> Option<MessageData> {
+        if data.len() < HEADER_LEN {
+            return None;
+        }
+        let message_tag = &data[..32];
+        if message_tag != tag(&self.id, from, None).as_ref()
+            && message_tag != tag(&self.id, from, Some(self.me)).as_ref()
+        {
+            return None;
+        }
+        // Unwrapping is fine because we checked the length already.
+        let sequence = u64::from_le_bytes(data[32..HEADER_LEN].try_into().unwrap());
+        if !self.seen.entry(from).or_default().insert(sequence) {
+            return None;
+        }
+        Some(data.split_off(HEADER_LEN))
+    }
+
+    /// Unwrap the underlying protocol.
+    pub fn into_inner(self) -> P {
+        self.protocol
+    }
+}
+
+impl<P: Protocol> Protocol for Session<P> {
+    type Output = P::Output;
+
+    fn poke(&mut self) -> Result<Action<Self::Output>, ProtocolError> {
+        Ok(match self.protocol.poke()? {
+            Action::SendMany(data) => Action::SendMany(self.wrap(None, data)),
+            Action::SendPrivate(to, data) => Action::SendPrivate(to, self.wrap(Some(to), data)),
+            action => action,
+        })
+    }
+
+    fn message(&mut self, from: Participant, data: MessageData) {
+        if let Some(data) = self.unwrap(from, data) {
+            self.protocol.message(from, data);
+        }
+    }
+
+    fn set_queue_limits(&mut self, limits: QueueLimits) {
+        self.protocol.set_queue_limits(limits)
+    }
+
+    fn set_format(&mut self, format: Format) {
+        self.protocol.set_format(format)
+    }
+
+    fn cancel(&mut self) {
+        self.protocol.cancel()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use k256::Secp256k1;
+
+    use super::*;
+    use crate::{keygen, protocol::run_protocol, KeygenOutput};
+
+    /// A protocol recording the messages it receives.
+    struct Recorder(Vec<(Participant, MessageData)>);
+
+    impl Protocol for Recorder {
+        type Output = ();
+
+        fn poke(&mut self) -> Result<Action<()>, ProtocolError> {
+            Ok(Action::Wait)
+        }
+
+        fn message(&mut self, from: Participant, data: MessageData) {
+            self.0.push((from, data));
+        }
+    }
+
+    /// A protocol sending some messages, in order.
+    struct Sender(Vec<Action<()>>);
+
+    impl Protocol for Sender {
+        type Output = ();
+
+        fn poke(&mut self) -> Result<Action<()>, ProtocolError> {
+            Ok(self.0.pop().unwrap_or(Action::Wait))
+        }
+
+        fn message(&mut self, _from: Participant, _data: MessageData) {}
+    }
+
+    fn id(purpose: &[u8]) -> SessionId {
+        SessionId::derive(purpose, &[], 0, 0)
+    }
+
+    fn send(
+        session: SessionId,
+        from: Participant,
+        mut actions: Vec<Action<()>>,
+    ) -> Vec<MessageData> {
+        actions.reverse();
+        let mut sender = Session::new(session, from, Sender(actions));
+        let mut out = Vec::new();
+        while let Ok(Action::SendMany(data) | Action::SendPrivate(_, data)) = sender.poke() {
+            out.push(data);
+        }
+        out
+    }
+
+    #[test]
+    fn test_session_filtering() {
+        let p0 = Participant::from(0u32);
+        let p1 = Participant::from(1u32);
+        let p2 = Participant::from(2u32);
+        let mut receiver = Session::new(id(b"session a"), p1, Recorder(Vec::new()));
+        let data = |byte: u8| MessageData::from(vec![byte]);
+
+        let sent = send(
+            id(b"session a"),
+            p0,
+            vec![
+                Action::SendMany(data(1)),
+                Action::SendPrivate(p2, data(2)),
+                Action::SendPrivate(p1, data(3)),
+            ],
+        );
+        receiver.message(p0, sent[0].clone());
+        // Replays are dropped.
+        receiver.message(p0, sent[0].clone());
+        // So are messages claiming to come from somebody else.
+        receiver.message(p2, sent[0].clone());
+        // Or meant for somebody else.
+        receiver.message(p0, sent[1].clone());
+        receiver.message(p0, sent[2].clone());
+        // Or coming from another session.
+        let other = send(id(b"session b"), p2, vec![Action::SendMany(data(4))]);
+        receiver.message(p2, other[0].clone());
+        receiver.message(p0, data(5));
+
+        let received = receiver.into_inner().0;
+        assert_eq!(received, vec![(p0, data(1)), (p0, data(3))]);
+    }
+
+    #[test]
+    fn test_session_id_derivation() {
+        let p: Vec<_> = (0..3u32).map(Participant::from).collect();
+        let base = SessionId::derive(b"presign", &p, 0, 0);
+        assert_eq!(
+            base,
+            SessionId::derive(b"presign", &[p[2], p[0], p[1]], 0, 0)
+        );
+        let others = [
+            SessionId::derive(b"sign", &p, 0, 0),
+            SessionId::derive(b"presign", &p[..2], 0, 0),
+            SessionId::derive(b"presign", &p, 1, 0),
+            SessionId::derive(b"presign", &p, 0, 1),
+        ];
+        let mut all: HashSet<_> = others.iter().collect();
+        all.insert(&base);
+        assert_eq!(all.len(), others.len() + 1);
+        assert_eq!(base.to_string().len(), 64);
+    }
+
+    #[test]
+    fn test_session_keygen() {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        for &p in &participants {
+            let protocol = keygen(&participants, p, 2).unwrap();
+            protocols.push((p, Box::new(Session::new(id(b"keygen"), p, protocol))));
+        }
+
+        let result = run_protocol(protocols).unwrap();
+        let public_key = result[0].1.public_key;
+        assert!(result.iter().all(|(_, out)| out.public_key == public_key));
+    }
+}
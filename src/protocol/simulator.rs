@@ -0,0 +1,299 @@
+//! A simulated network, for testing protocols and the code driving them.
+//!
+//! [`run_protocol`](super::run_protocol) delivers every message immediately,
+//! and in order, which real networks don't. A [`Simulator`] instead runs the
+//! participants against a simulated network, in which each message takes some time
+//! to arrive, and might be dropped, or duplicated. Since each message on a link
+//! gets its own latency, messages can also arrive in a different order than they were sent.
+//!
+//! Time is simulated, rather than real, so a simulation runs as fast as the
+//! protocols allow, and all of its randomness comes from a given rng, so a simulation
+//! with a seeded rng can be replayed exactly.
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    time::Duration,
+};
+
+use rand_core::RngCore;
+
+use super::{Action, MessageData, Participant, Protocol, ProtocolError};
+
+/// How messages behave on a link between two participants.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LinkConfig {
+    /// The least amount of time a message takes to arrive.
+    pub min_latency: Duration,
+    /// The most amount of time a message takes to arrive.
+    ///
+    /// Each message gets a latency chosen uniformly between the minimum and this.
+    pub max_latency: Duration,
+    /// The probability that a message gets dropped.
+    pub drop_rate: f64,
+    /// The probability that a message gets delivered twice.
+    pub duplicate_rate: f64,
+}
+
+impl Default for LinkConfig {
+    /// A link delivering every message exactly once, immediately.
+    fn default() -> Self {
+        Self {
+            min_latency: Duration::ZERO,
+            max_latency: Duration::ZERO,
+            drop_rate: 0.0,
+            duplicate_rate: 0.0,
+        }
+    }
+}
+
+/// The result of a successful simulation.
+#[derive(Debug)]
+pub struct Simulation<T> {
+    /// The output of each participant, along with the simulated time at which it returned.
+    pub outputs: Vec<(Participant, Duration, T)>,
+    /// The number of messages delivered, including duplicates.
+    pub delivered: usize,
+    /// The number of messages dropped.
+    pub dropped: usize,
+    /// The number of messages delivered twice.
+    pub duplicated: usize,
+}
+
+/// A message in flight, ordered by arrival time, and then by the order it was sent in.
+type Event = Reverse<(Duration, u64, usize, Participant, MessageData)>;
+
+/// A network of participants running a protocol, over simulated links.
+pub struct Simulator<T> {
+    participants: Vec<(Participant, Box<dyn Protocol<Output = T>>)>,
+    default_link: LinkConfig,
+    links: HashMap<(Participant, Participant), LinkConfig>,
+}
+
+impl<T> Simulator<T> {
+    /// Create a simulator for some participants, with every link using a default config.
+    pub fn new(participants: Vec<(Participant, Box<dyn Protocol<Output = T>>)>) -> Self {
+        Self {
+            participants,
+            default_link: LinkConfig::default(),
+            links: HashMap::new(),
+        }
+    }
+
+    /// Use a config for all of the links without one of their own.
+    pub fn default_link(mut self, config: LinkConfig) -> Self {
+        self.default_link = config;
+        self
+    }
+
+    /// Use a config for messages sent from one participant to another.
+    ///
+    /// Links are directed, so this doesn't affect messages in the other direction.
+    pub fn link(mut self, from: Participant, to: Participant, config: LinkConfig) -> Self {
+        self.links.insert((from, to), config);
+        self
+    }
+
+    /// Run the simulation, until every participant returns.
+    ///
+    /// If the network runs out of messages before then, which can happen with
+    /// dropped messages, this fails with [`ProtocolError::Timeout`], holding the
+    /// participants which haven't returned.
+    pub fn run(mut self, rng: &mut impl RngCore) -> Result<Simulation<T>, ProtocolError> {
+        let indices: HashMap<Participant, usize> = self
+            .participants
+            .iter()
+            .enumerate()
+            .map(|(i, (p, _))| (*p, i))
+            .collect();
+        let ids: Vec<Participant> = self.participants.iter().map(|(p, _)| *p).collect();
+        let size = ids.len();
+        let links = &self.links;
+        let default_link = &self.default_link;
+
+        let mut queue: BinaryHeap<Event> = BinaryHeap::new();
+        let mut sent = 0u64;
+        let mut outputs: Vec<Option<(Duration, T)>> = (0..size).map(|_| None).collect();
+        let mut done = 0;
+        let mut out = Simulation {
+            outputs: Vec::with_capacity(size),
+            delivered: 0,
+            dropped: 0,
+            duplicated: 0,
+        };
+
+        let mut send = |queue: &mut BinaryHeap<Event>,
+                        out: &mut Simulation<T>,
+                        now: Duration,
+                        from: Participant,
+                        to: usize,
+                        data: MessageData| {
+            let config = links.get(&(from, ids[to])).unwrap_or(default_link);
+            if chance(rng, config.drop_rate) {
+                out.dropped += 1;
+                return;
+            }
+            let copies = if chance(rng, config.duplicate_rate) {
+                out.duplicated += 1;
+                2
+            } else {
+                1
+            };
+            for _ in 0..copies {
+                let arrival = now + latency(rng, config);
+                queue.push(Reverse((arrival, sent, to, from, data.clone())));
+                sent += 1;
+            }
+        };
+
+        let mut now = Duration::ZERO;
+        let mut to_poke: Vec<usize> = (0..size).collect();
+        loop {
+            for i in to_poke.drain(..) {
+                if outputs[i].is_some() {
+                    continue;
+                }
+                let from = ids[i];
+                loop {
+                    match self.participants[i].1.poke()? {
+                        Action::Wait => break,
+                        Action::SendMany(data) => {
+                            for j in (0..size).filter(|&j| j != i) {
+                                send(&mut queue, &mut out, now, from, j, data.clone());
+                            }
+                        }
+                        Action::SendPrivate(to, data) => {
+                            if let Some(&j) = indices.get(&to) {
+                                send(&mut queue, &mut out, now, from, j, data);
+                            }
+                        }
+                        Action::Return(r) => {
+                            outputs[i] = Some((now, r));
+                            done += 1;
+                            break;
+                        }
+                    }
+                }
+            }
+            if done == size {
+                break;
+            }
+
+            let Some(Reverse((arrival, _, to, from, data))) = queue.pop() else {
+                let waiting = ids
+                    .iter()
+                    .zip(&outputs)
+                    .filter(|(_, out)| out.is_none())
+                    .map(|(p, _)| *p)
+                    .collect();
+                return Err(ProtocolError::Timeout(waiting));
+            };
+            now = arrival;
+            out.delivered += 1;
+            if outputs[to].is_none() {
+                self.participants[to].1.message(from, data);
+                to_poke.push(to);
+            }
+        }
+
+        out.outputs = ids
+            .iter()
+            .zip(outputs)
+            .map(|(p, output)| {
+                let (time, output) = output.unwrap();
+                (*p, time, output)
+            })
+            .collect();
+        Ok(out)
+    }
+}
+
+/// Return true with a given probability.
+fn chance(rng: &mut impl RngCore, probability: f64) -> bool {
+    probability > 0.0 && (rng.next_u64() as f64) < probability * (u64::MAX as f64)
+}
+
+fn latency(rng: &mut impl RngCore, config: &LinkConfig) -> Duration {
+    let min = config.min_latency.as_nanos() as u64;
+    let max = config.max_latency.as_nanos() as u64;
+    if max <= min {
+        return config.min_latency;
+    }
+    Duration::from_nanos(min + rng.next_u64() % (max - min + 1))
+}
+
+#[cfg(test)]
+mod test {
+    use k256::Secp256k1;
+    use magikitten::MeowRng;
+
+    use super::*;
+    use crate::{keygen, KeygenOutput};
+
+    #[allow(clippy::type_complexity)]
+    fn keygen_protocols(
+        participants: &[Participant],
+    ) -> Vec<(
+        Participant,
+        Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+    )> {
+        participants
+            .iter()
+            .map(|&p| {
+                let protocol = keygen::<Secp256k1>(participants, p, 2).unwrap();
+                let protocol: Box<dyn Protocol<Output = _>> = Box::new(protocol);
+                (p, protocol)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_simulated_keygen() -> Result<(), ProtocolError> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let config = LinkConfig {
+            min_latency: Duration::from_millis(10),
+            max_latency: Duration::from_millis(100),
+            duplicate_rate: 0.3,
+            ..Default::default()
+        };
+        let slow = LinkConfig {
+            min_latency: Duration::from_secs(1),
+            max_latency: Duration::from_secs(2),
+            ..Default::default()
+        };
+        let simulation = Simulator::new(keygen_protocols(&participants))
+            .default_link(config)
+            .link(participants[0], participants[1], slow)
+            .run(&mut MeowRng::new(&[1u8; 32]))?;
+
+        assert_eq!(simulation.outputs.len(), 3);
+        let public_key = simulation.outputs[0].2.public_key;
+        assert!(simulation
+            .outputs
+            .iter()
+            .all(|(_, _, out)| out.public_key == public_key));
+        // Participant 1 needs messages from 0, over the slow link.
+        assert!(simulation.outputs[1].1 >= Duration::from_secs(1));
+        assert!(simulation.duplicated > 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulated_drops() {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let lossy = LinkConfig {
+            drop_rate: 1.0,
+            ..Default::default()
+        };
+        let result = Simulator::new(keygen_protocols(&participants))
+            .link(participants[0], participants[1], lossy)
+            .run(&mut MeowRng::new(&[2u8; 32]));
+        match result {
+            Err(ProtocolError::Timeout(waiting)) => assert!(waiting.contains(&participants[1])),
+            _ => panic!("expected a timeout"),
+        }
+    }
+}
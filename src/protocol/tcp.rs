@@ -0,0 +1,268 @@
+//! A reference implementation of [`Transport`] over TCP.
+//!
+//! Each pair of participants shares a single TCP connection.
+//! The participant with the smaller identifier dials, and the participant
+//! with the larger identifier listens, with the dialer starting the connection
+//! by sending its identifier. After that, each message is framed by prefixing it
+//! with its length, as 4 little endian bytes. Frames longer than [`MAX_FRAME_LEN`]
+//! are rejected, so that a peer can't make us allocate arbitrary amounts of memory.
+//!
+//! **Note:** this transport provides no encryption or authentication
+//! of the identifiers participants claim. It's intended to run on a trusted network,
+//! or underneath a layer providing these properties, like [`SecureTransport`](super::SecureTransport).
+use std::{collections::HashMap, io, net::SocketAddr, time::Duration};
+
+use smol::{
+    channel::{self, Receiver},
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    Task, Timer,
+};
+
+use super::{transport::Transport, MessageData, Participant};
+
+/// How long to wait between attempts to dial a participant who isn't listening yet.
+const RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// The largest message, in bytes, that we're willing to send or receive.
+pub const MAX_FRAME_LEN: usize = 64 << 20;
+
+fn frame_too_large(kind: io::ErrorKind, len: usize) -> io::Error {
+    io::Error::new(
+        kind,
+        format!("frame of {len} bytes exceeds the maximum of {MAX_FRAME_LEN}"),
+    )
+}
+
+async fn read_frame(stream: &mut (impl AsyncReadExt + Unpin)) -> io::Result<MessageData> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).await?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(frame_too_large(io::ErrorKind::InvalidData, len));
+    }
+    let mut data = vec![0u8; len];
+    stream.read_exact(&mut data).await?;
+    Ok(data.into())
+}
+
+async fn write_frame(stream: &mut TcpStream, data: &[u8]) -> io::Result<()> {
+    if data.len() > MAX_FRAME_LEN {
+        return Err(frame_too_large(io::ErrorKind::InvalidInput, data.len()));
+    }
+    // This can't fail, since the maximum length fits in 4 bytes.
+    let len = u32::try_from(data.len()).unwrap();
+    stream.write_all(&len.to_le_bytes()).await?;
+    stream.write_all(data).await?;
+    stream.flush().await
+}
+
+/// A transport connecting participants directly over TCP.
+pub struct TcpTransport {
+    streams: HashMap<Participant, TcpStream>,
+    incoming: Receiver<io::Result<(Participant, MessageData)>>,
+    _readers: Vec<Task<()>>,
+}
+
+impl TcpTransport {
+    /// Establish connections with all of the other participants.
+    ///
+    /// The listener should be bound to the address that the other participants
+    /// know us by, and `peers` should contain the address of every other participant.
+    ///
+    /// This will keep retrying to dial participants who are not listening yet,
+    /// so it only returns once everyone is connected.
+    pub async fn connect(
+        me: Participant,
+        listener: TcpListener,
+        peers: &HashMap<Participant, SocketAddr>,
+    ) -> io::Result<Self> {
+        let mut streams = HashMap::with_capacity(peers.len());
+
+        for (&p, addr) in peers.iter().filter(|(&p, _)| p > me) {
+            let mut stream = loop {
+                match TcpStream::connect(addr).await {
+                    Ok(stream) => break stream,
+                    Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                        Timer::after(RETRY_DELAY).await;
+                    }
+                    Err(e) => return Err(e),
+                }
+            };
+            stream.write_all(&me.bytes()).await?;
+            streams.insert(p, stream);
+        }
+
+        while streams.len() < peers.len() {
+            let (mut stream, _) = listener.accept().await?;
+            let mut id = [0u8; 4];
+            stream.read_exact(&mut id).await?;
+            let p = Participant::from(u32::from_le_bytes(id));
+            if p >= me || !peers.contains_key(&p) || streams.contains_key(&p) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("unexpected connection from {p:?}"),
+                ));
+            }
+            streams.insert(p, stream);
+        }
+
+        let (incoming_s, incoming) = channel::unbounded();
+        let readers = streams
+            .iter()
+            .map(|(&p, stream)| {
+                let mut stream = stream.clone();
+                let incoming_s = incoming_s.clone();
+                smol::spawn(async move {
+                    loop {
+                        let res = read_frame(&mut stream).await.map(|data| (p, data));
+                        // A peer closing its connection is done sending to us, which
+                        // isn't an error, since it may finish the protocol before we do.
+                        if matches!(&res, Err(e) if e.kind() == io::ErrorKind::UnexpectedEof) {
+                            return;
+                        }
+                        let failed = res.is_err();
+                        if incoming_s.send(res).await.is_err() || failed {
+                            return;
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            streams,
+            incoming,
+            _readers: readers,
+        })
+    }
+}
+
+impl Transport for TcpTransport {
+    type Error = io::Error;
+
+    async fn send(&mut self, to: Participant, data: MessageData) -> Result<(), Self::Error> {
+        let stream = self.streams.get_mut(&to).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("no connection to {to:?}"))
+        })?;
+        write_frame(stream, &data).await
+    }
+
+    async fn recv(&mut self) -> Result<(Participant, MessageData), Self::Error> {
+        self.incoming
+            .recv()
+            .await
+            .map_err(|_| io::Error::new(io::ErrorKind::UnexpectedEof, "all connections closed"))?
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use k256::Secp256k1;
+    use smol::{block_on, Executor};
+
+    use super::*;
+    use crate::{keygen, protocol::run_with_transport};
+
+    #[test]
+    fn test_tcp_keygen() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+
+        let ex = Executor::new();
+        let results = block_on(ex.run(async {
+            let mut listeners = Vec::new();
+            let mut addrs = HashMap::new();
+            for &p in &participants {
+                let listener = TcpListener::bind("127.0.0.1:0").await?;
+                addrs.insert(p, listener.local_addr()?);
+                listeners.push((p, listener));
+            }
+
+            let tasks: Vec<_> = listeners
+                .into_iter()
+                .map(|(me, listener)| {
+                    let mut peers = addrs.clone();
+                    peers.remove(&me);
+                    let participants = participants.clone();
+                    ex.spawn(async move {
+                        let mut transport = TcpTransport::connect(me, listener, &peers).await?;
+                        let protocol = keygen::<Secp256k1>(&participants, me, 2)?;
+                        let out =
+                            run_with_transport(protocol, &participants, me, &mut transport).await?;
+                        Ok::<_, Box<dyn Error + Send + Sync>>(out)
+                    })
+                })
+                .collect();
+
+            let mut results = Vec::new();
+            for task in tasks {
+                results.push(task.await?);
+            }
+            Ok::<_, Box<dyn Error + Send + Sync>>(results)
+        }))
+        .map_err(|e| e as Box<dyn Error>)?;
+
+        assert_eq!(results[0].public_key, results[1].public_key);
+        assert_eq!(results[1].public_key, results[2].public_key);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_frame_rejects_large_frames() {
+        let len = u32::try_from(MAX_FRAME_LEN + 1).unwrap();
+        let mut data: &[u8] = &len.to_le_bytes();
+        let err = block_on(read_frame(&mut data)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut frame = 3u32.to_le_bytes().to_vec();
+        frame.extend_from_slice(b"abc");
+        let mut data: &[u8] = &frame;
+        assert_eq!(block_on(read_frame(&mut data)).unwrap(), b"abc"[..]);
+    }
+
+    #[test]
+    fn test_peer_closing_isnt_an_error() -> Result<(), Box<dyn Error>> {
+        let participants = [0u32, 1, 2].map(Participant::from);
+
+        let ex = Executor::new();
+        block_on(ex.run(async {
+            let mut addrs = HashMap::new();
+            let mut listeners = Vec::new();
+            for &p in &participants {
+                let listener = TcpListener::bind("127.0.0.1:0").await?;
+                addrs.insert(p, listener.local_addr()?);
+                listeners.push(listener);
+            }
+            let mut transports = Vec::new();
+            let connecting: Vec<_> = participants
+                .iter()
+                .zip(listeners)
+                .map(|(&me, listener)| {
+                    let mut peers = addrs.clone();
+                    peers.remove(&me);
+                    ex.spawn(async move { TcpTransport::connect(me, listener, &peers).await })
+                })
+                .collect();
+            for task in connecting {
+                transports.push(task.await?);
+            }
+            let mut last = transports.pop().unwrap();
+            let mut middle = transports.pop().unwrap();
+
+            // The first participant leaves, before the last one sends anything.
+            drop(transports);
+            Timer::after(RETRY_DELAY).await;
+            let hello = MessageData::from_static(b"hello");
+            last.send(participants[1], hello.clone()).await?;
+            assert_eq!(middle.recv().await?, (participants[2], hello));
+            Ok::<_, Box<dyn Error + Send + Sync>>(())
+        }))
+        .map_err(|e| e as Box<dyn Error>)
+    }
+}
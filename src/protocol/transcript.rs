@@ -0,0 +1,373 @@
+//! Recording what a protocol does, and replaying it afterwards.
+//!
+//! When a protocol aborts in production, the error alone often isn't enough to
+//! figure out why. A [`Recorder`] wraps a protocol, and keeps a [`Transcript`]
+//! of every message it sends and receives, along with when that happened, and how
+//! the protocol ended. Transcripts can be serialized, and shipped off for debugging.
+//!
+//! [`replay()`] then drives a fresh instance of the protocol with the messages
+//! from a transcript, in the same order, reproducing the same abort. The replay also
+//! checks that the protocol sends the same messages it did originally. This only happens
+//! if the protocol gets the same randomness, which requires creating it with
+//! one of the `_with_rng` variants, like [`keygen_with_rng`](crate::keygen_with_rng),
+//! and the same seed. Without this, the checks on the messages received still run,
+//! but the messages sent will differ.
+//!
+//! **Note:** a transcript holds every message exchanged, including private ones,
+//! which contain secret shares meant for this participant. It shouldn't be
+//! stored or shared any more carelessly than a private share.
+//!
+//! Transcripts don't contain the seed; if you record the seed next to a transcript,
+//! it needs to be protected in the same way.
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Action, Format, MessageData, Participant, Protocol, ProtocolError, QueueLimits};
+
+/// Something which happened while running a protocol.
+///
+/// Each event has the time at which it happened, relative to the creation of the recorder.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    /// The protocol sent a message, to a specific participant, or to everyone.
+    Sent {
+        at: Duration,
+        to: Option<Participant>,
+        data: MessageData,
+    },
+    /// The protocol received a message from another participant.
+    Received {
+        at: Duration,
+        from: Participant,
+        data: MessageData,
+    },
+    /// The protocol returned its output.
+    Returned { at: Duration },
+    /// The protocol failed with an error.
+    Failed { at: Duration, error: String },
+}
+
+/// A record of the messages a protocol sent and received, in order.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Transcript {
+    events: Vec<Event>,
+}
+
+impl Transcript {
+    /// The events in this transcript, in the order they happened.
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+}
+
+/// Wraps a protocol, recording a transcript of everything it does.
+pub struct Recorder<P> {
+    protocol: P,
+    start: Instant,
+    transcript: Transcript,
+}
+
+impl<P: Protocol> Recorder<P> {
+    /// Start recording a protocol.
+    pub fn new(protocol: P) -> Self {
+        Self {
+            protocol,
+            start: Instant::now(),
+            transcript: Transcript::default(),
+        }
+    }
+
+    /// The transcript recorded so far.
+    pub fn transcript(&self) -> &Transcript {
+        &self.transcript
+    }
+
+    /// Stop recording, returning the transcript.
+    pub fn into_transcript(self) -> Transcript {
+        self.transcript
+    }
+
+    fn record(&mut self, event: impl FnOnce(Duration) -> Event) {
+        let at = self.start.elapsed();
+        self.transcript.events.push(event(at));
+    }
+}
+
+impl<P: Protocol> Protocol for Recorder<P> {
+    type Output = P::Output;
+
+    fn poke(&mut self) -> Result<Action<Self::Output>, ProtocolError> {
+        let action = match self.protocol.poke() {
+            Ok(action) => action,
+            Err(e) => {
+                let error = e.to_string();
+                self.record(|at| Event::Failed { at, error });
+                return Err(e);
+            }
+        };
+        match &action {
+            Action::Wait => {}
+            Action::SendMany(data) => self.record(|at| Event::Sent {
+                at,
+                to: None,
+                data: data.clone(),
+            }),
+            Action::SendPrivate(to, data) => self.record(|at| Event::Sent {
+                at,
+                to: Some(*to),
+                data: data.clone(),
+            }),
+            Action::Return(_) => self.record(|at| Event::Returned { at }),
+        }
+        Ok(action)
+    }
+
+    fn message(&mut self, from: Participant, data: MessageData) {
+        self.record(|at| Event::Received {
+            at,
+            from,
+            data: data.clone(),
+        });
+        self.protocol.message(from, data)
+    }
+
+    fn set_queue_limits(&mut self, limits: QueueLimits) {
+        self.protocol.set_queue_limits(limits)
+    }
+
+    fn set_format(&mut self, format: Format) {
+        self.protocol.set_format(format)
+    }
+
+    fn cancel(&mut self) {
+        self.protocol.cancel()
+    }
+}
+
+/// The result of replaying a transcript.
+#[derive(Debug)]
+pub struct Replayed<T> {
+    /// What the protocol ended with.
+    ///
+    /// This holds `None` if the transcript ran out before the protocol returned.
+    pub result: Result<Option<T>, ProtocolError>,
+    /// The index of the first event sending a message which the replay didn't send.
+    ///
+    /// This is `None` if the replay sent exactly the same messages as the transcript,
+    /// up until the point where it stopped.
+    pub diverged_at: Option<usize>,
+}
+
+/// Replay a transcript into a fresh instance of a protocol.
+///
+/// The messages received in the transcript are delivered in the same order, with
+/// the protocol getting poked before each of them, like it was originally.
+/// This stops when the protocol returns, fails, or when the transcript runs out.
+pub fn replay<P: Protocol>(mut protocol: P, transcript: &Transcript) -> Replayed<P::Output> {
+    let mut expected = transcript
+        .events
+        .iter()
+        .enumerate()
+        .filter_map(|(i, event)| match event {
+            Event::Sent { to, data, .. } => Some((i, to, data)),
+            _ => None,
+        });
+    let mut diverged_at = None;
+
+    let mut poke = |protocol: &mut P| -> Result<Option<P::Output>, ProtocolError> {
+        loop {
+            let (to, data) = match protocol.poke()? {
+                Action::Wait => return Ok(None),
+                Action::SendMany(data) => (None, data),
+                Action::SendPrivate(to, data) => (Some(to), data),
+                Action::Return(output) => return Ok(Some(output)),
+            };
+            let diverged = match expected.next() {
+                Some((i, expected_to, expected_data)) => {
+                    (*expected_to != to || *expected_data != data).then_some(i)
+                }
+                // The replay sent more than the original, so it diverges at the end.
+                None => Some(transcript.events.len()),
+            };
+            if diverged_at.is_none() {
+                diverged_at = diverged;
+            }
+        }
+    };
+
+    let mut result = poke(&mut protocol);
+    for event in &transcript.events {
+        if !matches!(result, Ok(None)) {
+            break;
+        }
+        if let Event::Received { from, data, .. } = event {
+            protocol.message(*from, data.clone());
+            result = poke(&mut protocol);
+        }
+    }
+    if result.is_ok() {
+        if let Some((i, _, _)) = expected.next() {
+            diverged_at.get_or_insert(i);
+        }
+    }
+    Replayed {
+        result,
+        diverged_at,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use k256::Secp256k1;
+    use magikitten::MeowRng;
+
+    use super::*;
+    use crate::{
+        keygen_with_rng,
+        protocol::adversary::{BitFlip, Cheating},
+        serde::{decode, encode},
+        KeygenOutput,
+    };
+
+    type Boxed<T> = Box<dyn Protocol<Output = T>>;
+
+    /// Run a protocol, recording one of the participants, until it returns or fails.
+    fn run_recorded<P: Protocol>(
+        me: Participant,
+        mut recorder: Recorder<P>,
+        mut others: Vec<(Participant, Boxed<P::Output>)>,
+    ) -> (Result<P::Output, ProtocolError>, Transcript) {
+        let mut ps: Vec<(Participant, &mut dyn Protocol<Output = P::Output>)> =
+            vec![(me, &mut recorder)];
+        ps.extend(
+            others
+                .iter_mut()
+                .map(|(p, protocol)| (*p, &mut **protocol as _)),
+        );
+        let indices: HashMap<Participant, usize> =
+            ps.iter().enumerate().map(|(i, (p, _))| (*p, i)).collect();
+
+        let mut done = vec![false; ps.len()];
+        let result = 'run: loop {
+            for i in 0..ps.len() {
+                while !done[i] {
+                    let action = match ps[i].1.poke() {
+                        Ok(action) => action,
+                        Err(e) if i == 0 => break 'run Err(e),
+                        // Someone else noticed something wrong first, but we want our own error.
+                        Err(_) => {
+                            done[i] = true;
+                            break;
+                        }
+                    };
+                    let from = ps[i].0;
+                    match action {
+                        Action::Wait => break,
+                        Action::SendMany(data) => {
+                            for j in (0..ps.len()).filter(|&j| j != i) {
+                                ps[j].1.message(from, data.clone());
+                            }
+                        }
+                        Action::SendPrivate(to, data) => ps[indices[&to]].1.message(from, data),
+                        Action::Return(output) => {
+                            done[i] = true;
+                            if i == 0 {
+                                break 'run Ok(output);
+                            }
+                        }
+                    }
+                }
+            }
+        };
+        drop(ps);
+        (result, recorder.into_transcript())
+    }
+
+    fn seed(p: Participant) -> [u8; 32] {
+        let mut seed = [0u8; 32];
+        seed[..4].copy_from_slice(&p.bytes());
+        seed
+    }
+
+    fn make_keygen(
+        participants: &[Participant],
+        p: Participant,
+        seed: &[u8; 32],
+    ) -> impl Protocol<Output = KeygenOutput<Secp256k1>> {
+        keygen_with_rng(participants, p, 2, &mut MeowRng::new(seed)).unwrap()
+    }
+
+    #[test]
+    fn test_replay_keygen() -> Result<(), Box<dyn std::error::Error>> {
+        let participants: Vec<_> = (0..3u32).map(Participant::from).collect();
+        let me = participants[0];
+        let others = participants[1..]
+            .iter()
+            .map(|&p| {
+                let protocol: Boxed<_> = Box::new(make_keygen(&participants, p, &seed(p)));
+                (p, protocol)
+            })
+            .collect();
+        let recorder = Recorder::new(make_keygen(&participants, me, &seed(me)));
+        let (output, transcript) = run_recorded(me, recorder, others);
+        let output = output?;
+        assert!(matches!(
+            transcript.events().last(),
+            Some(Event::Returned { .. })
+        ));
+        let transcript: Transcript = decode(&encode(&transcript))?;
+
+        let replayed = replay(make_keygen(&participants, me, &seed(me)), &transcript);
+        assert_eq!(replayed.diverged_at, None);
+        let replayed_output = replayed.result?.expect("replay didn't return");
+        assert_eq!(replayed_output.private_share, output.private_share);
+        assert_eq!(replayed_output.public_key, output.public_key);
+
+        // With different randomness, we send a different commitment from the start.
+        let replayed = replay(make_keygen(&participants, me, &[7u8; 32]), &transcript);
+        assert_eq!(replayed.diverged_at, Some(0));
+        Ok(())
+    }
+
+    #[test]
+    fn test_replay_abort() {
+        let participants: Vec<_> = (0..3u32).map(Participant::from).collect();
+        let me = participants[1];
+        let cheater = participants[0];
+        let others = vec![
+            (
+                cheater,
+                Box::new(Cheating::new(
+                    make_keygen(&participants, cheater, &seed(cheater)),
+                    BitFlip { message: 2, bit: 0 },
+                )) as Boxed<_>,
+            ),
+            (
+                participants[2],
+                Box::new(make_keygen(&participants, participants[2], &[2u8; 32])),
+            ),
+        ];
+        let recorder = Recorder::new(make_keygen(&participants, me, &seed(me)));
+        let (result, transcript) = run_recorded(me, recorder, others);
+        let Err(error) = result else {
+            panic!("cheating went unnoticed");
+        };
+        assert!(matches!(
+            transcript.events().last(),
+            Some(Event::Failed { error: e, .. }) if *e == error.to_string()
+        ));
+
+        let replayed = replay(make_keygen(&participants, me, &seed(me)), &transcript);
+        assert_eq!(replayed.diverged_at, None);
+        match replayed.result {
+            Err(e) => {
+                assert_eq!(e.to_string(), error.to_string());
+                assert_eq!(e.culprit(), Some(cheater));
+            }
+            Ok(_) => panic!("replay didn't reproduce the abort"),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//! This module provides an abstraction over the network used to run protocols.
+//!
+//! Protocols only ever ask for two things: sending a message to everybody else,
+//! or sending a message to a single participant.
+//! The first can be implemented with the second, so a transport only needs
+//! to be able to send framed messages to individual participants, and to receive
+//! messages from any of them.
+//!
+//! Given an implementation of [`Transport`], [`run_with_transport`] takes care
+//! of routing the actions of a protocol onto the network.
+//!
+//! This crate ships a plain TCP transport, behind the `tcp` feature, and a libp2p
+//! transport, behind the `libp2p` feature. The latter leaves building the swarm,
+//! and thus the choice of transports and of a libp2p executor, to the node
+//! embedding it. It keeps a map from each [`Participant`] to its `PeerId`, fixed
+//! before the protocol starts, and ignores messages from peers outside of it;
+//! that map is what authenticates senders. Messages use a request-response
+//! behaviour, with one request per message, which gives framing for free.
+//! Gossipsub isn't a good fit, even for messages sent to everyone, since protocols
+//! check broadcasts themselves, and private messages need to go to a single peer anyways.
+//! Connections dropping and coming back are handled inside the transport, by redialing,
+//! and resending messages, so that the protocol never notices; a peer which never
+//! comes back is caught by wrapping the protocol in a [`Deadline`](super::Deadline).
+//!
+//! Messages are reference counted buffers, which the serializer writes once, and which
+//! then go through the protocol, and into the transport, without being copied.
+//! A message sent to everyone is shared between all of its recipients, and the
+//! wrappers removing a prefix from incoming messages share the rest of the buffer.
+use std::{error, future::Future};
+
+use super::{Action, MessageData, Participant, Protocol, ProtocolError};
+
+/// Represents a way to exchange messages with the other participants of a protocol.
+///
+/// Implementations are responsible for framing, i.e. each call to `send`
+/// must correspond to exactly one message returned by `recv` on the other side,
+/// and for authenticating which participant a message came from.
+pub trait Transport {
+    type Error: error::Error + Send + Sync + 'static;
+
+    /// Send a message to a given participant.
+    fn send(
+        &mut self,
+        to: Participant,
+        data: MessageData,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send;
+
+    /// Receive the next message, from any participant, along with who sent it.
+    fn recv(
+        &mut self,
+    ) -> impl Future<Output = Result<(Participant, MessageData), Self::Error>> + Send;
+}
+
+/// Run a protocol to completion over a transport.
+///
+/// We need to know the list of participants, in order to send messages to all
+/// of them; this list may or may not include `me`.
+pub async fn run_with_transport<T, P, Tr>(
+    mut protocol: P,
+    participants: &[Participant],
+    me: Participant,
+    transport: &mut Tr,
+) -> Result<T, ProtocolError>
+where
+    P: Protocol<Output = T>,
+    Tr: Transport,
+{
+    let transport_error = |e: Tr::Error| ProtocolError::Other(Box::new(e));
+    loop {
+        match protocol.poke()? {
+            Action::Wait => {
+                let (from, data) = transport.recv().await.map_err(transport_error)?;
+                protocol.message(from, data);
+            }
+            Action::SendMany(data) => {
+                for &p in participants {
+                    if p == me {
+                        continue;
+                    }
+                    // This only copies a reference to the message.
+                    transport
+                        .send(p, data.clone())
+                        .await
+                        .map_err(transport_error)?;
+                }
+            }
+            Action::SendPrivate(to, data) => {
+                transport.send(to, data).await.map_err(transport_error)?;
+            }
+            Action::Return(out) => return Ok(out),
+        }
+    }
+}
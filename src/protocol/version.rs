@@ -0,0 +1,260 @@
+//! This module provides versioning for protocol messages.
+//!
+//! Every message produced by our protocols starts with the [`Version`] used to
+//! produce it, and messages with a different version are ignored, rather than
+//! being misinterpreted.
+//!
+//! When upgrading a fleet of nodes, some of them will be able to speak several versions,
+//! either because the protocols didn't change, or because they embed several
+//! versions of this library. Before starting a protocol, participants can run
+//! [`negotiate_version`], to agree on the highest version they all support.
+//! The messages of this handshake have a fixed format, which won't change
+//! between versions.
+use std::collections::HashMap;
+
+use super::{Action, Fault, MessageData, Participant, Protocol, ProtocolError};
+
+/// The encoding used for the contents of messages.
+///
+/// Every build speaks msgpack, and the `tagged`, `cbor`, and `postcard` features
+/// add the other formats. Protocols send messages in msgpack, unless another format
+/// is chosen with [`Protocol::set_format`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Format {
+    /// Messages are encoded with msgpack.
+    #[default]
+    MessagePack,
+    /// Messages are encoded as tagged fields, using the wire types of protobuf.
+    #[cfg(any(feature = "tagged", test))]
+    Tagged,
+    /// Messages are encoded with CBOR.
+    #[cfg(any(feature = "cbor", test))]
+    Cbor,
+    /// Messages are encoded with postcard.
+    #[cfg(any(feature = "postcard", test))]
+    Postcard,
+}
+
+impl Format {
+    /// All of the formats enabled in this build.
+    pub const ALL: &'static [Self] = &[
+        Format::MessagePack,
+        #[cfg(any(feature = "tagged", test))]
+        Format::Tagged,
+        #[cfg(any(feature = "cbor", test))]
+        Format::Cbor,
+        #[cfg(any(feature = "postcard", test))]
+        Format::Postcard,
+    ];
+
+    fn to_byte(self) -> u8 {
+        match self {
+            Format::MessagePack => 0,
+            #[cfg(any(feature = "tagged", test))]
+            Format::Tagged => 1,
+            #[cfg(any(feature = "cbor", test))]
+            Format::Cbor => 2,
+            #[cfg(any(feature = "postcard", test))]
+            Format::Postcard => 3,
+        }
+    }
+
+    fn from_byte(b: u8) -> Option<Self> {
+        Self::ALL.iter().copied().find(|f| f.to_byte() == b)
+    }
+}
+
+/// The version of the messages exchanged by a protocol.
+///
+/// Versions are ordered by their protocol version first, so the latest
+/// version of the protocols is preferred, regardless of the format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    /// Changes whenever the messages our protocols exchange change.
+    pub protocol: u8,
+    /// The encoding of messages.
+    pub format: Format,
+}
+
+impl Version {
+    /// The version spoken by this build of the library, with the default format.
+    pub const CURRENT: Self = Self {
+        protocol: 1,
+        format: Format::MessagePack,
+    };
+
+    /// The current version, in each of the formats enabled in this build.
+    ///
+    /// After agreeing on one of these with [`negotiate_version`], each participant
+    /// passes its format to [`Protocol::set_format`].
+    pub fn supported() -> Vec<Self> {
+        Format::ALL
+            .iter()
+            .map(|&format| Self {
+                format,
+                ..Self::CURRENT
+            })
+            .collect()
+    }
+
+    /// The number of bytes in this encoding.
+    pub(crate) const LEN: usize = 2;
+
+    pub(crate) fn to_bytes(self) -> [u8; Self::LEN] {
+        [self.protocol, self.format.to_byte()]
+    }
+
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        match bytes {
+            [protocol, format, ..] => Some(Self {
+                protocol: *protocol,
+                format: Format::from_byte(*format)?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+const HANDSHAKE_TAG: &[u8] = b"cait-sith versions";
+
+fn encode_versions(versions: &[Version]) -> MessageData {
+    let mut out = HANDSHAKE_TAG.to_vec();
+    for v in versions {
+        out.extend_from_slice(&v.to_bytes());
+    }
+    out.into()
+}
+
+fn decode_versions(data: &[u8]) -> Option<Vec<Version>> {
+    let data = data.strip_prefix(HANDSHAKE_TAG)?;
+    if data.len() % Version::LEN != 0 {
+        return None;
+    }
+    data.chunks_exact(Version::LEN)
+        .map(Version::from_bytes)
+        .collect()
+}
+
+/// A protocol for agreeing on the highest version supported by all participants.
+///
+/// Created with [`negotiate_version`].
+pub struct VersionNegotiation {
+    supported: Vec<Version>,
+    others: Vec<Participant>,
+    received: HashMap<Participant, Option<Vec<Version>>>,
+    sent: bool,
+    done: bool,
+}
+
+impl Protocol for VersionNegotiation {
+    type Output = Version;
+
+    fn poke(&mut self) -> Result<Action<Self::Output>, ProtocolError> {
+        if self.done {
+            return Ok(Action::Wait);
+        }
+        if !self.sent {
+            self.sent = true;
+            return Ok(Action::SendMany(encode_versions(&self.supported)));
+        }
+        if self.received.len() < self.others.len() {
+            return Ok(Action::Wait);
+        }
+        self.done = true;
+        let mut common = self.supported.clone();
+        for p in &self.others {
+            let theirs = self.received[p]
+                .as_ref()
+                .ok_or_else(|| ProtocolError::Malicious {
+                    participant: *p,
+                    round: 0,
+                    fault: Fault::InvalidMessage("malformed version list"),
+                })?;
+            common.retain(|v| theirs.contains(v));
+        }
+        common
+            .into_iter()
+            .max()
+            .map(Action::Return)
+            .ok_or_else(|| ProtocolError::Failed {
+                round: 0,
+                fault: Fault::InvalidResult("no version is supported by all participants"),
+            })
+    }
+
+    fn message(&mut self, from: Participant, data: MessageData) {
+        if self.others.contains(&from) {
+            self.received
+                .entry(from)
+                .or_insert_with(|| decode_versions(&data));
+        }
+    }
+}
+
+/// Agree on the highest version supported by all participants.
+///
+/// Each participant passes the versions it can speak, which will usually just
+/// be [`Version::CURRENT`], unless it embeds several versions of this library.
+pub fn negotiate_version(
+    participants: &[Participant],
+    me: Participant,
+    supported: &[Version],
+) -> VersionNegotiation {
+    VersionNegotiation {
+        supported: supported.to_vec(),
+        others: participants.iter().copied().filter(|&p| p != me).collect(),
+        received: HashMap::new(),
+        sent: false,
+        done: false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocol::run_protocol;
+
+    fn v(protocol: u8) -> Version {
+        Version {
+            protocol,
+            format: Format::MessagePack,
+        }
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn run_negotiation(supported: &[&[Version]]) -> Result<Vec<Version>, ProtocolError> {
+        let participants: Vec<Participant> =
+            (0..supported.len() as u32).map(Participant::from).collect();
+        let protocols: Vec<(Participant, Box<dyn Protocol<Output = Version>>)> = participants
+            .iter()
+            .zip(supported)
+            .map(|(p, s)| {
+                let protocol: Box<dyn Protocol<Output = Version>> =
+                    Box::new(negotiate_version(&participants, *p, s));
+                (*p, protocol)
+            })
+            .collect();
+        Ok(run_protocol(protocols)?
+            .into_iter()
+            .map(|(_, v)| v)
+            .collect())
+    }
+
+    #[test]
+    fn test_negotiate_version() {
+        let out = run_negotiation(&[&[v(1), v(2)], &[v(1), v(2), v(3)], &[v(2), v(1)]]).unwrap();
+        assert_eq!(out, vec![v(2); 3]);
+
+        assert!(run_negotiation(&[&[v(1)], &[v(2)]]).is_err());
+    }
+
+    #[test]
+    fn test_version_encoding() {
+        let mut versions = Version::supported();
+        assert!(versions.contains(&Version::CURRENT));
+        versions.push(v(7));
+        assert_eq!(decode_versions(&encode_versions(&versions)), Some(versions));
+        assert_eq!(decode_versions(b"garbage"), None);
+        assert_eq!(Version::from_bytes(&[1, 9]), None);
+    }
+}
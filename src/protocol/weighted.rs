@@ -0,0 +1,321 @@
+//! This module provides a way to give some participants more weight than others.
+//!
+//! A participant with weight `w` holds `w` shares, as if it were `w` separate
+//! participants, so that it counts `w` times towards the threshold. For example,
+//! an operator running in two data centers can count double, without running two nodes.
+//!
+//! [`Weights`] assigns each share a *virtual* participant, canonically, so that every
+//! party computes the same assignment. [`Weighted`] then runs one instance of
+//! a protocol per share, as the virtual participants of that share, routing messages
+//! between the instances of a party locally, and to other parties over a single connection.
+//! Any protocol works this way, so key generation, presigning, and signing all
+//! use the virtual participants, and thresholds count shares, rather than parties.
+use std::collections::{BTreeMap, VecDeque};
+
+use super::{
+    Action, Format, InitializationError, MessageData, Participant, Protocol, ProtocolError,
+    QueueLimits,
+};
+
+/// A canonical assignment of virtual participants to weighted participants.
+///
+/// Shares are assigned consecutive virtual participants, starting from 0,
+/// going through the participants in sorted order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Weights {
+    shares: BTreeMap<Participant, Vec<Participant>>,
+    owners: Vec<Participant>,
+}
+
+impl Weights {
+    /// Create an assignment from the weight of each participant, in any order.
+    ///
+    /// This fails if a participant appears twice, or has a weight of 0.
+    pub fn new(weights: &[(Participant, usize)]) -> Result<Self, InitializationError> {
+        let mut sorted = weights.to_vec();
+        sorted.sort();
+        let mut shares = BTreeMap::new();
+        let mut owners = Vec::new();
+        for (p, w) in sorted {
+            if w == 0 {
+                return Err(InitializationError::BadParameters(
+                    "weights must be at least 1".to_string(),
+                ));
+            }
+            let virtuals = (owners.len()..owners.len() + w)
+                .map(|i| Participant::from(i as u32))
+                .collect();
+            if shares.insert(p, virtuals).is_some() {
+                return Err(InitializationError::BadParameters(
+                    "participants cannot have more than one weight".to_string(),
+                ));
+            }
+            owners.extend(std::iter::repeat_n(p, w));
+        }
+        Ok(Self { shares, owners })
+    }
+
+    /// The total weight, which is the number of virtual participants.
+    pub fn total(&self) -> usize {
+        self.owners.len()
+    }
+
+    /// All the virtual participants.
+    pub fn participants(&self) -> Vec<Participant> {
+        (0..self.owners.len() as u32)
+            .map(Participant::from)
+            .collect()
+    }
+
+    /// The virtual participants for the shares of a participant.
+    pub fn shares(&self, participant: Participant) -> &[Participant] {
+        self.shares
+            .get(&participant)
+            .map(|v| v.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// The participant holding the share of a virtual participant, if it exists.
+    pub fn owner(&self, virtual_participant: Participant) -> Option<Participant> {
+        self.owners
+            .get(u32::from(virtual_participant) as usize)
+            .copied()
+    }
+}
+
+/// Marks a message as being for all the virtual participants of a party.
+const EVERYONE: u32 = u32::MAX;
+
+fn tag(from: Participant, to: u32, data: &[u8]) -> MessageData {
+    let mut out = Vec::with_capacity(8 + data.len());
+    out.extend_from_slice(&from.bytes());
+    out.extend_from_slice(&to.to_le_bytes());
+    out.extend_from_slice(data);
+    out.into()
+}
+
+/// A protocol run with one instance per share of a weighted participant.
+///
+/// This returns the output of each instance, along with its virtual participant.
+/// Errors still refer to virtual participants; use [`Weights::owner`] to find
+/// out who is responsible for them.
+pub struct Weighted<P: Protocol> {
+    weights: Weights,
+    me: Participant,
+    instances: Vec<(Participant, P, Option<P::Output>)>,
+    /// Messages to send, to a single participant, or to everyone.
+    outbox: VecDeque<(Option<Participant>, MessageData)>,
+    done: bool,
+}
+
+impl<P: Protocol> Weighted<P> {
+    /// Create an instance of a protocol for each of our shares.
+    ///
+    /// The function passed is given all the virtual participants, along with the
+    /// virtual participant of one of our shares, and should create the protocol for that share.
+    pub fn new(
+        weights: Weights,
+        me: Participant,
+        mut make: impl FnMut(&[Participant], Participant) -> Result<P, InitializationError>,
+    ) -> Result<Self, InitializationError> {
+        let mine = weights.shares(me).to_vec();
+        if mine.is_empty() {
+            return Err(InitializationError::BadParameters(
+                "weights must contain this participant".to_string(),
+            ));
+        }
+        let participants = weights.participants();
+        let instances = mine
+            .into_iter()
+            .map(|v| Ok((v, make(&participants, v)?, None)))
+            .collect::<Result<_, InitializationError>>()?;
+        Ok(Self {
+            weights,
+            me,
+            instances,
+            outbox: VecDeque::new(),
+            done: false,
+        })
+    }
+
+    /// The weights this protocol is run with.
+    pub fn weights(&self) -> &Weights {
+        &self.weights
+    }
+
+    fn deliver(&mut self, from: Participant, to: Option<Participant>, data: MessageData) {
+        for (v, instance, _) in &mut self.instances {
+            if *v != from && to.is_none_or(|to| to == *v) {
+                instance.message(from, data.clone());
+            }
+        }
+    }
+
+    /// Poke every instance until none of them make progress.
+    fn run_instances(&mut self) -> Result<(), ProtocolError> {
+        let mut progress = true;
+        while progress {
+            progress = false;
+            for i in 0..self.instances.len() {
+                let (v, instance, out) = &mut self.instances[i];
+                if out.is_some() {
+                    continue;
+                }
+                let v = *v;
+                match instance.poke()? {
+                    Action::Wait => continue,
+                    Action::Return(o) => *out = Some(o),
+                    Action::SendMany(data) => {
+                        self.outbox.push_back((None, tag(v, EVERYONE, &data)));
+                        self.deliver(v, None, data);
+                    }
+                    Action::SendPrivate(to, data) => match self.weights.owner(to) {
+                        Some(owner) if owner == self.me => self.deliver(v, Some(to), data),
+                        Some(owner) => self
+                            .outbox
+                            .push_back((Some(owner), tag(v, u32::from(to), &data))),
+                        None => {
+                            return Err(ProtocolError::Other(
+                                format!("protocol sent a message to unknown participant {:?}", to)
+                                    .into(),
+                            ))
+                        }
+                    },
+                }
+                progress = true;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<P: Protocol> Protocol for Weighted<P> {
+    type Output = Vec<(Participant, P::Output)>;
+
+    fn poke(&mut self) -> Result<Action<Self::Output>, ProtocolError> {
+        if self.done {
+            return Ok(Action::Wait);
+        }
+        if self.outbox.is_empty() {
+            self.run_instances()?;
+        }
+        match self.outbox.pop_front() {
+            Some((None, data)) => return Ok(Action::SendMany(data)),
+            Some((Some(to), data)) => return Ok(Action::SendPrivate(to, data)),
+            None => {}
+        }
+        if self.instances.iter().all(|(_, _, out)| out.is_some()) {
+            self.done = true;
+            let out = std::mem::take(&mut self.instances)
+                .into_iter()
+                .filter_map(|(v, _, out)| Some((v, out?)))
+                .collect();
+            return Ok(Action::Return(out));
+        }
+        Ok(Action::Wait)
+    }
+
+    fn message(&mut self, from: Participant, data: MessageData) {
+        if data.len() < 8 {
+            return;
+        }
+        // Unwrapping is fine because we checked the length already.
+        let from_virtual = Participant::from(u32::from_le_bytes(data[..4].try_into().unwrap()));
+        let to = u32::from_le_bytes(data[4..8].try_into().unwrap());
+        // Participants can only speak for their own shares.
+        if from == self.me || self.weights.owner(from_virtual) != Some(from) {
+            return;
+        }
+        let to = (to != EVERYONE).then_some(Participant::from(to));
+        self.deliver(from_virtual, to, data.slice(8..));
+    }
+
+    /// Set the limits for each of our instances.
+    fn set_queue_limits(&mut self, limits: QueueLimits) {
+        for (_, instance, _) in &mut self.instances {
+            instance.set_queue_limits(limits);
+        }
+    }
+
+    fn set_format(&mut self, format: Format) {
+        for (_, instance, _) in &mut self.instances {
+            instance.set_format(format);
+        }
+    }
+
+    fn cancel(&mut self) {
+        for (_, instance, _) in &mut self.instances {
+            instance.cancel();
+        }
+        self.outbox.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
+
+    use super::*;
+    use crate::{keygen, participants::ParticipantList, protocol::run_protocol, KeygenOutput};
+
+    #[test]
+    fn test_weights() {
+        let [a, b, c] = [5u32, 1, 3].map(Participant::from);
+        let weights = Weights::new(&[(a, 1), (b, 2), (c, 1)]).unwrap();
+        assert_eq!(weights.total(), 4);
+        assert_eq!(
+            weights.shares(b),
+            &[Participant::from(0u32), Participant::from(1u32)]
+        );
+        assert_eq!(weights.shares(a), &[Participant::from(3u32)]);
+        assert_eq!(weights.owner(Participant::from(2u32)), Some(c));
+        assert_eq!(weights.owner(Participant::from(4u32)), None);
+
+        assert!(Weights::new(&[(a, 1), (a, 2)]).is_err());
+        assert!(Weights::new(&[(a, 0)]).is_err());
+    }
+
+    #[test]
+    #[allow(clippy::type_complexity)]
+    fn test_weighted_keygen() -> Result<(), Box<dyn std::error::Error>> {
+        let [a, b, c] = [0u32, 1, 2].map(Participant::from);
+        let weights = Weights::new(&[(a, 2), (b, 1), (c, 1)])?;
+        let threshold = 3;
+
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = Vec<(Participant, KeygenOutput<Secp256k1>)>>>,
+        )> = Vec::new();
+        for p in [a, b, c] {
+            let protocol = Weighted::new(weights.clone(), p, |participants, v| {
+                keygen::<Secp256k1>(participants, v, threshold)
+            })?;
+            protocols.push((p, Box::new(protocol)));
+        }
+        let result = run_protocol(protocols)?;
+
+        let public_key = result[0].1[0].1.public_key;
+        let mut shares = Vec::new();
+        for (p, outs) in result {
+            assert_eq!(outs.len(), weights.shares(p).len());
+            for (v, out) in outs {
+                assert_eq!(weights.owner(v), Some(p));
+                assert_eq!(out.public_key, public_key);
+                if p != c {
+                    shares.push((v, out.private_share));
+                }
+            }
+        }
+
+        // The participant with weight 2, along with any other, can reconstruct the key.
+        let virtuals: Vec<Participant> = shares.iter().map(|(v, _)| *v).collect();
+        let p_list = ParticipantList::new(&virtuals).unwrap();
+        let x = shares
+            .iter()
+            .map(|(v, x_v)| p_list.lagrange::<Secp256k1>(*v) * x_v)
+            .fold(Scalar::ZERO, |acc, x| acc + x);
+        assert_eq!(ProjectivePoint::GENERATOR * x, public_key);
+
+        Ok(())
+    }
+}
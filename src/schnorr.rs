@@ -0,0 +1,720 @@
+//! This module provides threshold Schnorr signatures, following BIP340.
+//!
+//! These signatures use the same key shares as ECDSA signatures, produced by
+//! [`keygen`](crate::keygen), so the same participants can sign for both
+//! ECDSA and Taproot outputs.
+//!
+//! Schnorr signatures are linear in the nonce and the private key, which makes them
+//! much simpler to produce than ECDSA signatures: no triples are needed.
+//! Instead, a presignature is just a shared nonce, generated in advance
+//! by running key generation again. This presignature must **never** be reused,
+//! otherwise the private key can be recovered.
+//!
+//! Since the nonce is fixed before the message is known, signers must only ever
+//! have a single signing session open at a time, unless they trust whoever chooses
+//! the messages. With many concurrent sessions, the ROS attack lets whoever chooses
+//! the messages produce a signature on a message the signers never saw, which is
+//! what the binding factor of FROST prevents, at the cost of presignatures with two nonces.
+//!
+//! BIP340 uses x-only public keys and nonces, implicitly choosing the point
+//! with an even y coordinate. Because the shares of a key can be negated, to
+//! produce shares of the negated key, each participant can do this locally.
+use elliptic_curve::{
+    ops::Reduce, point::AffineCoordinates, Field, Group, PrimeField, ScalarPrimitive,
+};
+use k256::{AffinePoint, FieldBytes, ProjectivePoint, Scalar, Secp256k1, U256};
+use rand_core::{CryptoRngCore, OsRng};
+use sha2::{Digest, Sha256};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::{
+    crypto::fork_rng,
+    fiat_shamir::FiatShamirBackend,
+    keyshare::{do_keygen, ProofTransform},
+    participants::{ParticipantCounter, ParticipantList},
+    protocol::{
+        internal::{make_protocol, Context, SharedChannel},
+        Fault, InitializationError, Participant, Protocol, ProtocolError,
+    },
+    vault::ShareVault,
+    KeygenOutput,
+};
+
+/// Compute a tagged hash, as defined in BIP340.
+fn tagged_hash(tag: &[u8], data: &[&[u8]]) -> FieldBytes {
+    let tag_hash = Sha256::digest(tag);
+    let mut hasher = Sha256::new();
+    hasher.update(tag_hash);
+    hasher.update(tag_hash);
+    for d in data {
+        hasher.update(d);
+    }
+    hasher.finalize()
+}
+
+/// Return the point with an even y coordinate, and whether or not it was negated.
+fn even_y(point: &AffinePoint) -> (AffinePoint, bool) {
+    if point.y_is_odd().into() {
+        ((-ProjectivePoint::from(*point)).to_affine(), true)
+    } else {
+        (*point, false)
+    }
+}
+
+/// The BIP340 challenge, for a nonce, x-only public key, and message.
+fn challenge(r: &FieldBytes, public_key: &AffinePoint, msg: &[u8]) -> Scalar {
+    let e = tagged_hash(b"BIP0340/challenge", &[r, &public_key.x(), msg]);
+    <Scalar as Reduce<U256>>::reduce_bytes(&e)
+}
+
+/// A Schnorr signature, following BIP340.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchnorrSignature {
+    /// The x coordinate of the nonce point.
+    pub r: FieldBytes,
+    /// The response scalar.
+    pub s: Scalar,
+}
+
+impl SchnorrSignature {
+    /// Serialize this signature as the 64 bytes specified in BIP340.
+    pub fn to_bytes(&self) -> [u8; 64] {
+        let mut out = [0u8; 64];
+        out[..32].copy_from_slice(&self.r);
+        out[32..].copy_from_slice(&self.s.to_repr());
+        out
+    }
+
+    /// Verify this signature, for the x coordinate of a public key.
+    ///
+    /// The public key can have either parity, since only its x coordinate matters.
+    #[must_use]
+    pub fn verify(&self, public_key: &AffinePoint, msg: &[u8]) -> bool {
+        let (public_key, _) = even_y(public_key);
+        let e = challenge(&self.r, &public_key, msg);
+        let big_r = ProjectivePoint::GENERATOR * self.s - ProjectivePoint::from(public_key) * e;
+        if bool::from(big_r.is_identity()) {
+            return false;
+        }
+        let big_r = big_r.to_affine();
+        !bool::from(big_r.y_is_odd()) && big_r.x() == self.r
+    }
+}
+
+/// The output of the Schnorr presigning protocol.
+///
+/// This is a shared nonce, which must only ever be used for a single signature,
+/// in a single session, with no other session open at the same time.
+#[derive(Debug, Clone)]
+pub struct SchnorrPresignOutput {
+    /// The public nonce point.
+    pub big_r: AffinePoint,
+    /// Our share of the nonce.
+    pub k: Scalar,
+}
+
+impl Zeroize for SchnorrPresignOutput {
+    fn zeroize(&mut self) {
+        self.k.zeroize();
+    }
+}
+
+impl Drop for SchnorrPresignOutput {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SchnorrPresignOutput {}
+
+impl KeygenOutput<Secp256k1> {
+    /// Apply the taproot tweak, as defined in BIP341, to this key share.
+    ///
+    /// The merkle root is that of the script tree, if any. Without one, this
+    /// applies the tweak recommended by BIP86, for outputs which are only spendable by key.
+    ///
+    /// Each participant can do this locally, and the resulting output can be
+    /// used to produce Schnorr signatures for the tweaked output key.
+    pub fn taproot_tweak(&self, merkle_root: Option<&[u8; 32]>) -> Self {
+        let (internal_key, negated) = even_y(&self.public_key);
+        let mut data: Vec<&[u8]> = Vec::with_capacity(2);
+        let x = internal_key.x();
+        data.push(&x);
+        if let Some(merkle_root) = merkle_root {
+            data.push(merkle_root);
+        }
+        let t = <Scalar as Reduce<U256>>::reduce_bytes(&tagged_hash(b"TapTweak", &data));
+
+        let private_share = if negated {
+            -self.private_share
+        } else {
+            self.private_share
+        };
+        KeygenOutput {
+            private_share: private_share + t,
+            public_key: (ProjectivePoint::from(internal_key) + ProjectivePoint::GENERATOR * t)
+                .to_affine(),
+        }
+    }
+}
+
+async fn do_schnorr_presign(
+    rng: magikitten::MeowRng,
+    chan: SharedChannel,
+    participants: ParticipantList,
+    me: Participant,
+    threshold: usize,
+) -> Result<SchnorrPresignOutput, ProtocolError> {
+    let out = do_keygen::<Secp256k1>(
+        rng,
+        chan,
+        participants,
+        me,
+        threshold,
+        ProofTransform::FiatShamir,
+        FiatShamirBackend::default(),
+    )
+    .await?;
+    Ok(SchnorrPresignOutput {
+        big_r: out.public_key,
+        k: out.private_share,
+    })
+}
+
+/// The presigning protocol for Schnorr signatures.
+///
+/// This generates a shared nonce, with a given threshold, which must match
+/// that of the key it will be used with.
+pub fn schnorr_presign(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+) -> Result<impl Protocol<Output = SchnorrPresignOutput>, InitializationError> {
+    schnorr_presign_with_rng(participants, me, threshold, &mut OsRng)
+}
+
+/// Like [schnorr_presign()], except drawing all of the randomness from a given rng.
+pub fn schnorr_presign_with_rng(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = SchnorrPresignOutput>, InitializationError> {
+    if participants.len() < 2 {
+        return Err(InitializationError::BadParameters(format!(
+            "participant count cannot be < 2, found: {}",
+            participants.len()
+        )));
+    };
+    if threshold > participants.len() {
+        return Err(InitializationError::BadParameters(
+            "threshold must be <= participant count".to_string(),
+        ));
+    }
+
+    let participants = ParticipantList::new(participants).ok_or_else(|| {
+        InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
+    })?;
+
+    if !participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain this participant".to_string(),
+        ));
+    }
+
+    let ctx = Context::new();
+    let fut = do_schnorr_presign(
+        fork_rng(rng),
+        ctx.shared_channel(),
+        participants,
+        me,
+        threshold,
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
+/// Respond to a challenge, returning the sum of everyone's responses, and the round.
+///
+/// BIP340 implicitly uses the points with an even y coordinate,
+/// so we negate our shares whenever the points need to be negated.
+async fn do_respond<K: ShareVault<Secp256k1>>(
+    chan: &mut SharedChannel,
+    participants: &ParticipantList,
+    me: Participant,
+    keygen_out: K,
+    presignature: &SchnorrPresignOutput,
+    e: Scalar,
+) -> Result<(Scalar, u64), ProtocolError> {
+    let (_, negate_x) = even_y(&keygen_out.public_key());
+    let (_, negate_k) = even_y(&presignature.big_r);
+    let k_i = if negate_k {
+        -presignature.k
+    } else {
+        presignature.k
+    };
+
+    let lambda = participants.lagrange::<Secp256k1>(me);
+    let e_lambda = if negate_x { -lambda * e } else { lambda * e };
+    let s_i = keygen_out
+        .mul_add(&e_lambda, &(lambda * k_i))
+        .map_err(|e| ProtocolError::Other(Box::new(e)))?;
+
+    let wait0 = chan.next_waitpoint();
+    {
+        let s_i: ScalarPrimitive<Secp256k1> = s_i.into();
+        chan.send_many(wait0, &s_i).await;
+    }
+
+    let mut seen = ParticipantCounter::new(participants);
+    let mut s = s_i;
+    seen.put(me);
+    while !seen.full() {
+        let (from, s_j): (_, ScalarPrimitive<Secp256k1>) = chan.recv(wait0).await?;
+        if !seen.put(from) {
+            continue;
+        }
+        s += Scalar::from(s_j);
+    }
+
+    Ok((s, wait0))
+}
+
+async fn do_schnorr_sign<K: ShareVault<Secp256k1>>(
+    mut chan: SharedChannel,
+    participants: ParticipantList,
+    me: Participant,
+    keygen_out: K,
+    presignature: SchnorrPresignOutput,
+    msg: Vec<u8>,
+) -> Result<SchnorrSignature, ProtocolError> {
+    let (public_key, _) = even_y(&keygen_out.public_key());
+    let (big_r, _) = even_y(&presignature.big_r);
+    let e = challenge(&big_r.x(), &public_key, &msg);
+    let (s, round) = do_respond(&mut chan, &participants, me, keygen_out, &presignature, e).await?;
+
+    let sig = SchnorrSignature { r: big_r.x(), s };
+    if !sig.verify(&public_key, &msg) {
+        return Err(ProtocolError::Failed {
+            round,
+            fault: Fault::InvalidResult("signature failed to verify"),
+        });
+    }
+
+    Ok(sig)
+}
+
+/// What a user needs to turn a blind signature into a Schnorr signature.
+///
+/// This is produced by [`schnorr_blind`], along with the blinded challenge to send to the signers.
+#[derive(Debug, Clone)]
+pub struct SchnorrUnblinder {
+    public_key: AffinePoint,
+    big_r: AffinePoint,
+    challenge: Scalar,
+    alpha: Scalar,
+    r: FieldBytes,
+    msg: Vec<u8>,
+}
+
+impl Zeroize for SchnorrUnblinder {
+    fn zeroize(&mut self) {
+        self.alpha.zeroize();
+    }
+}
+
+impl Drop for SchnorrUnblinder {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for SchnorrUnblinder {}
+
+impl SchnorrUnblinder {
+    /// Turn the response of the signers into a signature on the message.
+    ///
+    /// This returns `None` if the response is invalid.
+    pub fn unblind(&self, s: Scalar) -> Option<SchnorrSignature> {
+        let expected = ProjectivePoint::from(self.big_r)
+            + ProjectivePoint::from(self.public_key) * self.challenge;
+        if ProjectivePoint::GENERATOR * s != expected {
+            return None;
+        }
+        let sig = SchnorrSignature {
+            r: self.r,
+            s: s + self.alpha,
+        };
+        sig.verify(&self.public_key, &self.msg).then_some(sig)
+    }
+}
+
+/// Blind a message, to have it signed without the signers learning it.
+///
+/// This is run by the user requesting a signature, with the public nonce of the presignature
+/// the signers will use. This returns the challenge to give to [`schnorr_sign_blind`], and
+/// the secret needed to unblind the response into a signature on the message.
+///
+/// **WARNING** Blind Schnorr signatures are only secure if each signer runs
+/// a single signing session at a time. With many concurrent sessions, the ROS attack
+/// lets a user produce one more signature than the number of sessions.
+pub fn schnorr_blind(
+    public_key: &AffinePoint,
+    big_r: &AffinePoint,
+    msg: &[u8],
+) -> (Scalar, SchnorrUnblinder) {
+    schnorr_blind_with_rng(public_key, big_r, msg, &mut OsRng)
+}
+
+/// Like [schnorr_blind()], except drawing all of the randomness from a given rng.
+pub fn schnorr_blind_with_rng(
+    public_key: &AffinePoint,
+    big_r: &AffinePoint,
+    msg: &[u8],
+    rng: &mut impl CryptoRngCore,
+) -> (Scalar, SchnorrUnblinder) {
+    let (public_key, _) = even_y(public_key);
+    let (big_r, _) = even_y(big_r);
+    // The blinded nonce needs an even y coordinate, which happens half the time.
+    loop {
+        let alpha = Scalar::random(&mut *rng);
+        let beta = Scalar::random(&mut *rng);
+        let blinded = ProjectivePoint::from(big_r)
+            + ProjectivePoint::GENERATOR * alpha
+            + ProjectivePoint::from(public_key) * beta;
+        if bool::from(blinded.is_identity()) {
+            continue;
+        }
+        let blinded = blinded.to_affine();
+        if bool::from(blinded.y_is_odd()) {
+            continue;
+        }
+        let r = blinded.x();
+        let c = challenge(&r, &public_key, msg) + beta;
+        let unblinder = SchnorrUnblinder {
+            public_key,
+            big_r,
+            challenge: c,
+            alpha,
+            r,
+            msg: msg.to_vec(),
+        };
+        return (c, unblinder);
+    }
+}
+
+async fn do_schnorr_sign_blind<K: ShareVault<Secp256k1>>(
+    mut chan: SharedChannel,
+    participants: ParticipantList,
+    me: Participant,
+    keygen_out: K,
+    presignature: SchnorrPresignOutput,
+    c: Scalar,
+) -> Result<Scalar, ProtocolError> {
+    let (public_key, _) = even_y(&keygen_out.public_key());
+    let (big_r, _) = even_y(&presignature.big_r);
+    let (s, round) = do_respond(&mut chan, &participants, me, keygen_out, &presignature, c).await?;
+
+    if ProjectivePoint::GENERATOR * s
+        != ProjectivePoint::from(big_r) + ProjectivePoint::from(public_key) * c
+    {
+        return Err(ProtocolError::Failed {
+            round,
+            fault: Fault::InvalidResult("blind signature failed to verify"),
+        });
+    }
+
+    Ok(s)
+}
+
+/// The signing protocol for blind Schnorr signatures.
+///
+/// This signs a challenge produced by [`schnorr_blind`], without learning the message,
+/// returning a response which the user can then unblind into a BIP340 signature.
+/// Since signers can't see what they sign, this should only be used for things like
+/// issuing credentials, where any message is acceptable.
+///
+/// The same warning as [`schnorr_blind`] applies: signers must not run many sessions at once.
+pub fn schnorr_sign_blind<K: ShareVault<Secp256k1> + Send + 'static>(
+    participants: &[Participant],
+    me: Participant,
+    keygen_out: K,
+    presignature: SchnorrPresignOutput,
+    challenge: Scalar,
+) -> Result<impl Protocol<Output = Scalar>, InitializationError> {
+    if participants.len() < 2 {
+        return Err(InitializationError::BadParameters(format!(
+            "participant count cannot be < 2, found: {}",
+            participants.len()
+        )));
+    };
+
+    let participants = ParticipantList::new(participants).ok_or_else(|| {
+        InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
+    })?;
+
+    if !participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain this participant".to_string(),
+        ));
+    }
+
+    let ctx = Context::new();
+    let fut = do_schnorr_sign_blind(
+        ctx.shared_channel(),
+        participants,
+        me,
+        keygen_out,
+        presignature,
+        challenge,
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
+/// The signing protocol for Schnorr signatures, following BIP340.
+///
+/// Unlike ECDSA signing, BIP340 hashes the message itself, along with the nonce
+/// and the public key, so this takes the message directly.
+///
+/// To sign for a taproot output, use the output of [`KeygenOutput::taproot_tweak`].
+/// The share can also be kept in any [`ShareVault`].
+///
+/// **WARNING** Like blind signatures, this is only secure if each signer runs a single
+/// signing session at a time, or if the messages come from someone the signers trust.
+/// With many concurrent sessions, the ROS attack lets whoever chooses the messages
+/// produce one more signature than the number of sessions.
+pub fn schnorr_sign<K: ShareVault<Secp256k1> + Send + 'static>(
+    participants: &[Participant],
+    me: Participant,
+    keygen_out: K,
+    presignature: SchnorrPresignOutput,
+    msg: &[u8],
+) -> Result<impl Protocol<Output = SchnorrSignature>, InitializationError> {
+    if participants.len() < 2 {
+        return Err(InitializationError::BadParameters(format!(
+            "participant count cannot be < 2, found: {}",
+            participants.len()
+        )));
+    };
+
+    let participants = ParticipantList::new(participants).ok_or_else(|| {
+        InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
+    })?;
+
+    if !participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain this participant".to_string(),
+        ));
+    }
+
+    let ctx = Context::new();
+    let fut = do_schnorr_sign(
+        ctx.shared_channel(),
+        participants,
+        me,
+        keygen_out,
+        presignature,
+        msg.to_vec(),
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
+#[cfg(test)]
+mod test {
+    use std::error::Error;
+
+    use k256::schnorr::{Signature, VerifyingKey};
+
+    use super::*;
+    use crate::{keygen, protocol::run_protocol};
+
+    #[allow(clippy::type_complexity)]
+    fn run_keygen(
+        participants: &[Participant],
+        threshold: usize,
+    ) -> Result<Vec<(Participant, KeygenOutput<Secp256k1>)>, Box<dyn Error>> {
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        for p in participants {
+            let protocol = keygen(participants, *p, threshold)?;
+            protocols.push((*p, Box::new(protocol)));
+        }
+        Ok(run_protocol(protocols)?)
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn run_sign(
+        participants: &[Participant],
+        threshold: usize,
+        keys: &[(Participant, KeygenOutput<Secp256k1>)],
+        msg: &[u8],
+    ) -> Result<SchnorrSignature, Box<dyn Error>> {
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = SchnorrPresignOutput>>,
+        )> = Vec::with_capacity(participants.len());
+        for p in participants {
+            let protocol = schnorr_presign(participants, *p, threshold)?;
+            protocols.push((*p, Box::new(protocol)));
+        }
+        let presignatures = run_protocol(protocols)?;
+
+        // Sign with only a threshold of participants.
+        let signers: Vec<_> = presignatures
+            .iter()
+            .take(threshold)
+            .map(|(p, _)| *p)
+            .collect();
+        let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = SchnorrSignature>>)> =
+            Vec::with_capacity(signers.len());
+        for (p, presignature) in presignatures.into_iter().take(threshold) {
+            let keygen_out = keys.iter().find(|(q, _)| *q == p).unwrap().1.clone();
+            let protocol = schnorr_sign(&signers, p, keygen_out, presignature, msg)?;
+            protocols.push((p, Box::new(protocol)));
+        }
+        let result = run_protocol(protocols)?;
+        assert!(result.iter().all(|(_, sig)| *sig == result[0].1));
+        Ok(result[0].1.clone())
+    }
+
+    #[test]
+    fn test_schnorr_sign() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+        let msg = b"hello?";
+
+        let keys = run_keygen(&participants, threshold)?;
+        let public_key = keys[0].1.public_key;
+
+        // Run a few times, to cover the different parities of the key and nonce.
+        for _ in 0..4 {
+            let sig = run_sign(&participants, threshold, &keys, msg)?;
+            let verifying_key = VerifyingKey::from_bytes(&public_key.x())?;
+            verifying_key.verify_raw(msg, &Signature::try_from(&sig.to_bytes()[..])?)?;
+        }
+
+        // Only the participants signing can take part.
+        let outsider = SchnorrPresignOutput {
+            big_r: public_key,
+            k: Scalar::ONE,
+        };
+        assert!(schnorr_sign(
+            &participants[..2],
+            participants[2],
+            keys[2].1.clone(),
+            outsider,
+            msg
+        )
+        .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_schnorr_sign_blind() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+        let msg = b"a credential";
+
+        let keys = run_keygen(&participants, threshold)?;
+        let public_key = keys[0].1.public_key;
+
+        // Run a few times, to cover the different parities of the key and nonce.
+        for _ in 0..4 {
+            let mut protocols: Vec<(
+                Participant,
+                Box<dyn Protocol<Output = SchnorrPresignOutput>>,
+            )> = Vec::with_capacity(participants.len());
+            for p in &participants {
+                let protocol = schnorr_presign(&participants, *p, threshold)?;
+                protocols.push((*p, Box::new(protocol)));
+            }
+            let presignatures = run_protocol(protocols)?;
+            let (c, unblinder) = schnorr_blind(&public_key, &presignatures[0].1.big_r, msg);
+
+            let signers: Vec<_> = presignatures
+                .iter()
+                .take(threshold)
+                .map(|(p, _)| *p)
+                .collect();
+            let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = Scalar>>)> =
+                Vec::with_capacity(signers.len());
+            for (p, presignature) in presignatures.into_iter().take(threshold) {
+                let keygen_out = keys.iter().find(|(q, _)| *q == p).unwrap().1.clone();
+                let protocol = schnorr_sign_blind(&signers, p, keygen_out, presignature, c)?;
+                protocols.push((p, Box::new(protocol)));
+            }
+            let result = run_protocol(protocols)?;
+            let s = result[0].1;
+
+            // The signers never see the nonce of the final signature.
+            let sig = unblinder.unblind(s).unwrap();
+            let verifying_key = VerifyingKey::from_bytes(&public_key.x())?;
+            verifying_key.verify_raw(msg, &Signature::try_from(&sig.to_bytes()[..])?)?;
+            assert!(unblinder.unblind(s + Scalar::ONE).is_none());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_schnorr_sign_taproot() -> Result<(), Box<dyn Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+        let msg = b"hello taproot";
+
+        let keys = run_keygen(&participants, threshold)?;
+        let merkle_root = [42u8; 32];
+        for merkle_root in [None, Some(&merkle_root)] {
+            let tweaked: Vec<_> = keys
+                .iter()
+                .map(|(p, out)| (*p, out.taproot_tweak(merkle_root)))
+                .collect();
+            let output_key = tweaked[0].1.public_key;
+            assert!(tweaked.iter().all(|(_, out)| out.public_key == output_key));
+
+            let sig = run_sign(&participants, threshold, &tweaked, msg)?;
+            let verifying_key = VerifyingKey::from_bytes(&output_key.x())?;
+            verifying_key.verify_raw(msg, &Signature::try_from(&sig.to_bytes()[..])?)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_taproot_tweak_vector() {
+        // From the BIP86 test vectors, for the first receiving address of the first account.
+        let internal_key =
+            hex_decode("cc8a4bc64d897bddc5fbc2f670f7a8ba0b386779106cf1223c6fc5d7cd6fc115");
+        let output_key =
+            hex_decode("a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c");
+
+        let internal_key = VerifyingKey::from_bytes(&internal_key).unwrap();
+        let out = KeygenOutput::<Secp256k1> {
+            private_share: Scalar::ZERO,
+            public_key: *internal_key.as_affine(),
+        }
+        .taproot_tweak(None);
+        assert_eq!(&out.public_key.x()[..], &output_key[..]);
+    }
+
+    fn hex_decode(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+}
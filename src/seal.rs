@@ -0,0 +1,396 @@
+//! Encryption at rest for key shares.
+//!
+//! A key share needs to be persisted, and should be encrypted when it is.
+//! [`KeygenOutput::seal`] encrypts a share, either under a 32 byte key, or under
+//! a passphrase, from which a key is derived with Argon2id, and produces a blob,
+//! which [`KeygenOutput::unseal`] decrypts.
+//!
+//! The blob starts with a magic string and a version number, followed by
+//! the kind of key used. For passphrases, this is followed by the Argon2id parameters
+//! and the salt, so that the parameters can be strengthened over time, without
+//! breaking older blobs. The rest is a nonce, the encrypted share, and a tag.
+//! Everything before the encrypted share is authenticated, along with the curve.
+//!
+//! Since the parameters in a blob can only be authenticated after running Argon2id,
+//! unsealing first checks them against some limits, so that a forged blob can't
+//! make us allocate arbitrary amounts of memory, or spin for arbitrarily long.
+use std::{error, fmt};
+
+use argon2::{Algorithm, Argon2, Version};
+use ck_meow::Meow;
+use rand_core::{CryptoRngCore, OsRng};
+use serde::{de::DeserializeOwned, Serialize};
+use zeroize::Zeroize;
+
+use crate::{
+    compat::CSCurve,
+    serde::{decode, encode},
+    KeygenOutput,
+};
+
+const MAGIC: &[u8; 8] = b"CSSEALED";
+const VERSION: u16 = 1;
+const SEAL_LABEL: &[u8] = b"cait-sith v0.8.0 seal";
+
+const KIND_KEY: u8 = 1;
+const KIND_PASSPHRASE: u8 = 2;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 32;
+const TAG_LEN: usize = 32;
+
+/// A key used to seal a key share.
+#[derive(Clone, Copy)]
+pub enum SealKey<'a> {
+    /// A passphrase, from which a key gets derived with Argon2id.
+    Passphrase(&'a [u8]),
+    /// A uniformly random key, used directly.
+    Key(&'a [u8; 32]),
+}
+
+impl fmt::Debug for SealKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SealKey::Passphrase(_) => write!(f, "Passphrase(..)"),
+            SealKey::Key(_) => write!(f, "Key(..)"),
+        }
+    }
+}
+
+/// The cost parameters of Argon2id, used when sealing with a passphrase.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SealParams {
+    /// The memory to use, in KiB.
+    pub memory_kib: u32,
+    /// The number of passes over the memory.
+    pub iterations: u32,
+    /// The number of lanes.
+    pub parallelism: u32,
+}
+
+impl Default for SealParams {
+    /// The second recommended option of RFC 9106: 64 MiB of memory, 3 passes, and 4 lanes.
+    fn default() -> Self {
+        Self {
+            memory_kib: 64 * 1024,
+            iterations: 3,
+            parallelism: 4,
+        }
+    }
+}
+
+impl SealParams {
+    /// The largest parameters [`KeygenOutput::unseal`] accepts: 1 GiB of memory, 16 passes,
+    /// and 16 lanes.
+    pub const LIMITS: Self = Self {
+        memory_kib: 1024 * 1024,
+        iterations: 16,
+        parallelism: 16,
+    };
+
+    fn argon2(&self) -> Result<Argon2<'static>, argon2::Error> {
+        let params =
+            argon2::Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))?;
+        Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+    }
+
+    fn within(&self, limits: &Self) -> bool {
+        self.memory_kib <= limits.memory_kib
+            && self.iterations <= limits.iterations
+            && self.parallelism <= limits.parallelism
+    }
+}
+
+/// Represents an error which can happen when unsealing a key share.
+#[derive(Debug)]
+pub enum SealError {
+    /// The blob isn't well formed, for the reason given.
+    Corrupted(String),
+    /// The blob was sealed with a version of the format we don't understand.
+    UnsupportedVersion(u16),
+    /// The blob was sealed with a passphrase, and we were given a key, or vice versa.
+    WrongKind,
+    /// The blob asks for more expensive Argon2id parameters than the limits allow.
+    TooExpensive(SealParams),
+    /// The blob failed to decrypt, because the key is wrong, or the blob was modified.
+    Decryption,
+}
+
+impl fmt::Display for SealError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Corrupted(e) => write!(f, "corrupted sealed share: {}", e),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported version {} for sealed share", v),
+            Self::WrongKind => write!(f, "sealed share uses a different kind of key"),
+            Self::TooExpensive(p) => write!(
+                f,
+                "sealed share needs {} KiB, {} passes, and {} lanes, which exceeds the limits",
+                p.memory_kib, p.iterations, p.parallelism
+            ),
+            Self::Decryption => write!(f, "failed to decrypt sealed share"),
+        }
+    }
+}
+
+impl error::Error for SealError {}
+
+/// Derive the encryption key, from the key kind, and its parameters.
+///
+/// The parameters need to have been checked already.
+fn derive_key(key: SealKey<'_>, params: &SealParams, salt: &[u8]) -> [u8; 32] {
+    match key {
+        SealKey::Key(k) => *k,
+        SealKey::Passphrase(passphrase) => {
+            let mut out = [0u8; 32];
+            params
+                .argon2()
+                .and_then(|argon2| argon2.hash_password_into(passphrase, salt, &mut out))
+                .expect("Argon2id parameters should have been checked");
+            out
+        }
+    }
+}
+
+fn meow<C: CSCurve>(key: &[u8; 32], header: &[u8]) -> Meow {
+    let mut meow = Meow::new(SEAL_LABEL);
+    meow.key(key, false);
+    meow.meta_ad(b"curve", false);
+    meow.ad(C::NAME, false);
+    meow.meta_ad(b"header", false);
+    meow.ad(header, false);
+    meow
+}
+
+impl<C: CSCurve> KeygenOutput<C>
+where
+    Self: Serialize + DeserializeOwned,
+{
+    /// Encrypt this share under a key, producing a blob which can be stored.
+    ///
+    /// Passphrases are stretched with the default [`SealParams`].
+    pub fn seal(&self, key: SealKey<'_>) -> Vec<u8> {
+        self.seal_with_params(key, &SealParams::default(), &mut OsRng)
+    }
+
+    /// Like [`seal()`](Self::seal), except with given parameters for stretching passphrases,
+    /// and drawing all of the randomness from a given rng.
+    ///
+    /// The parameters are ignored when sealing with a key.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the parameters aren't allowed by Argon2id, which needs at least
+    /// one pass, and at least 8 KiB of memory for each lane.
+    pub fn seal_with_params(
+        &self,
+        key: SealKey<'_>,
+        params: &SealParams,
+        rng: &mut impl CryptoRngCore,
+    ) -> Vec<u8> {
+        let mut header = Vec::new();
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        let mut salt = [0u8; SALT_LEN];
+        match key {
+            SealKey::Key(_) => header.push(KIND_KEY),
+            SealKey::Passphrase(_) => {
+                assert!(params.argon2().is_ok(), "invalid Argon2id parameters");
+                rng.fill_bytes(&mut salt);
+                header.push(KIND_PASSPHRASE);
+                header.extend_from_slice(&params.memory_kib.to_le_bytes());
+                header.extend_from_slice(&params.iterations.to_le_bytes());
+                header.extend_from_slice(&params.parallelism.to_le_bytes());
+                header.extend_from_slice(&salt);
+            }
+        }
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill_bytes(&mut nonce);
+        header.extend_from_slice(&nonce);
+
+        let mut secret = derive_key(key, params, &salt);
+        let mut meow = meow::<C>(&secret, &header);
+        secret.zeroize();
+
+        let mut data = encode(self);
+        meow.send_enc(&mut data, false);
+        let mut tag = [0u8; TAG_LEN];
+        meow.send_mac(&mut tag);
+
+        let mut out = header;
+        out.extend_from_slice(&data);
+        out.extend_from_slice(&tag);
+        out
+    }
+
+    /// Decrypt a share sealed with [`seal()`](Self::seal), using the same key.
+    ///
+    /// This reads the parameters used to stretch a passphrase from the blob itself,
+    /// rejecting any above [`SealParams::LIMITS`].
+    pub fn unseal(blob: &[u8], key: SealKey<'_>) -> Result<Self, SealError> {
+        Self::unseal_with_limits(blob, key, &SealParams::LIMITS)
+    }
+
+    /// Like [`unseal()`](Self::unseal), except rejecting blobs whose parameters exceed any of
+    /// the given limits, before doing any work.
+    pub fn unseal_with_limits(
+        blob: &[u8],
+        key: SealKey<'_>,
+        limits: &SealParams,
+    ) -> Result<Self, SealError> {
+        let corrupted = |e: &str| SealError::Corrupted(e.to_string());
+
+        let mut rest = blob;
+        let mut take = |n: usize| -> Result<&[u8], SealError> {
+            if rest.len() < n {
+                return Err(corrupted("blob is too short"));
+            }
+            let (head, tail) = rest.split_at(n);
+            rest = tail;
+            Ok(head)
+        };
+
+        if take(MAGIC.len())? != MAGIC {
+            return Err(corrupted("missing header"));
+        }
+        let version = u16::from_le_bytes(take(2)?.try_into().unwrap());
+        if version != VERSION {
+            return Err(SealError::UnsupportedVersion(version));
+        }
+        let kind = take(1)?[0];
+        let mut params = SealParams::default();
+        let mut salt: &[u8] = &[];
+        match (kind, key) {
+            (KIND_KEY, SealKey::Key(_)) => {}
+            (KIND_PASSPHRASE, SealKey::Passphrase(_)) => {
+                let mut read_u32 = || -> Result<u32, SealError> {
+                    Ok(u32::from_le_bytes(take(4)?.try_into().unwrap()))
+                };
+                params = SealParams {
+                    memory_kib: read_u32()?,
+                    iterations: read_u32()?,
+                    parallelism: read_u32()?,
+                };
+                if !params.within(limits) {
+                    return Err(SealError::TooExpensive(params));
+                }
+                if params.argon2().is_err() {
+                    return Err(corrupted("invalid Argon2id parameters"));
+                }
+                salt = take(SALT_LEN)?;
+            }
+            (KIND_KEY | KIND_PASSPHRASE, _) => return Err(SealError::WrongKind),
+            _ => return Err(corrupted("unknown kind of key")),
+        }
+        take(NONCE_LEN)?;
+        if rest.len() < TAG_LEN {
+            return Err(corrupted("blob is too short"));
+        }
+        let header_len = blob.len() - rest.len();
+        let (header, rest) = blob.split_at(header_len);
+        let (data, tag) = rest.split_at(rest.len() - TAG_LEN);
+
+        let mut secret = derive_key(key, &params, salt);
+        let mut meow = meow::<C>(&secret, header);
+        secret.zeroize();
+
+        let mut data = data.to_vec();
+        meow.recv_enc(&mut data, false);
+        let mut tag = <[u8; TAG_LEN]>::try_from(tag).unwrap();
+        if meow.recv_mac(&mut tag).is_err() {
+            data.zeroize();
+            return Err(SealError::Decryption);
+        }
+        let out = decode(&data).map_err(|e| corrupted(&e.to_string()));
+        data.zeroize();
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use elliptic_curve::Field;
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
+
+    use super::*;
+
+    fn keygen_out() -> KeygenOutput<Secp256k1> {
+        let private_share = Scalar::random(&mut OsRng);
+        KeygenOutput {
+            private_share,
+            public_key: (ProjectivePoint::GENERATOR * Scalar::random(&mut OsRng)).into(),
+        }
+    }
+
+    // Small parameters, so that the tests stay fast.
+    const PARAMS: SealParams = SealParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 2,
+    };
+
+    #[test]
+    fn test_seal_with_key() -> Result<(), SealError> {
+        let out = keygen_out();
+        let key = [7u8; 32];
+        let blob = out.seal(SealKey::Key(&key));
+        assert_eq!(&blob[..MAGIC.len()], MAGIC);
+
+        let unsealed = KeygenOutput::<Secp256k1>::unseal(&blob, SealKey::Key(&key))?;
+        assert_eq!(unsealed.private_share, out.private_share);
+        assert_eq!(unsealed.public_key, out.public_key);
+
+        let res = KeygenOutput::<Secp256k1>::unseal(&blob, SealKey::Key(&[8u8; 32]));
+        assert!(matches!(res, Err(SealError::Decryption)));
+        let res = KeygenOutput::<Secp256k1>::unseal(&blob, SealKey::Passphrase(b"hunter2"));
+        assert!(matches!(res, Err(SealError::WrongKind)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_seal_with_passphrase() -> Result<(), SealError> {
+        let out = keygen_out();
+        let passphrase = SealKey::Passphrase(b"correct horse battery staple");
+        let mut blob = out.seal_with_params(passphrase, &PARAMS, &mut OsRng);
+
+        let unsealed = KeygenOutput::<Secp256k1>::unseal(&blob, passphrase)?;
+        assert_eq!(unsealed.private_share, out.private_share);
+
+        let res = KeygenOutput::<Secp256k1>::unseal(&blob, SealKey::Passphrase(b"hunter2"));
+        assert!(matches!(res, Err(SealError::Decryption)));
+
+        // Tampering with the parameters, or the ciphertext, is detected.
+        for i in [MAGIC.len() + 3, blob.len() - TAG_LEN - 1] {
+            blob[i] ^= 1;
+            let res = KeygenOutput::<Secp256k1>::unseal(&blob, passphrase);
+            assert!(matches!(res, Err(SealError::Decryption)));
+            blob[i] ^= 1;
+        }
+        let res = KeygenOutput::<Secp256k1>::unseal(&blob[..20], passphrase);
+        assert!(matches!(res, Err(SealError::Corrupted(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_unseal_checks_limits() -> Result<(), SealError> {
+        let out = keygen_out();
+        let passphrase = SealKey::Passphrase(b"correct horse battery staple");
+        let blob = out.seal_with_params(passphrase, &PARAMS, &mut OsRng);
+
+        let limits = SealParams {
+            iterations: 0,
+            ..SealParams::LIMITS
+        };
+        let res = KeygenOutput::<Secp256k1>::unseal_with_limits(&blob, passphrase, &limits);
+        assert!(matches!(res, Err(SealError::TooExpensive(p)) if p == PARAMS));
+
+        // A forged blob asking for 4 TiB of memory is rejected without trying.
+        let mut forged = blob.clone();
+        let memory = MAGIC.len() + 3;
+        forged[memory..memory + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let res = KeygenOutput::<Secp256k1>::unseal(&forged, passphrase);
+        assert!(matches!(res, Err(SealError::TooExpensive(_))));
+
+        KeygenOutput::<Secp256k1>::unseal_with_limits(&blob, passphrase, &PARAMS)?;
+        Ok(())
+    }
+}
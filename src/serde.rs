@@ -1,9 +1,18 @@
-use std::io::Write;
+use std::{error, io::Write};
 
 use crate::compat::{CSCurve, SerializablePoint};
+use crate::protocol::Format;
 use ecdsa::elliptic_curve::ScalarPrimitive;
 use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize, Serializer};
 
+// Schemas are only read by the tagged encoding, but every message type has one.
+#[cfg_attr(not(feature = "tagged"), allow(dead_code))]
+mod schema;
+#[cfg(any(feature = "tagged", test))]
+mod tagged;
+
+pub use schema::{Schema, Wire};
+
 /// Encode an arbitrary serializable value into a vec.
 pub fn encode<T: Serialize>(val: &T) -> Vec<u8> {
     rmp_serde::encode::to_vec(val).expect("failed to encode value")
@@ -14,12 +23,25 @@ pub fn encode_writer<T: Serialize, W: Write>(w: &mut W, val: &T) {
     rmp_serde::encode::write(w, val).expect("failed to encode value");
 }
 
-/// Encode an arbitrary serializable with a tag.
-pub fn encode_with_tag<T: Serialize>(tag: &[u8], val: &T) -> Vec<u8> {
+/// Encode an arbitrary serializable with a tag, in a given format.
+///
+/// This is used for protocol messages, which are encoded as msgpack by default,
+/// or as tagged fields, CBOR, or postcard, with the features of the same name.
+pub fn encode_with_tag<T: Serialize + Wire>(format: Format, tag: &[u8], val: &T) -> Vec<u8> {
     // Matches rmp_serde's internal default.
     let mut out = Vec::with_capacity(128);
     out.extend_from_slice(tag);
-    rmp_serde::encode::write(&mut out, val).expect("failed to encode value");
+    match format {
+        Format::MessagePack => {
+            rmp_serde::encode::write(&mut out, val).expect("failed to encode value")
+        }
+        #[cfg(any(feature = "tagged", test))]
+        Format::Tagged => tagged::encode_into(&mut out, val).expect("failed to encode value"),
+        #[cfg(any(feature = "cbor", test))]
+        Format::Cbor => ciborium::into_writer(val, &mut out).expect("failed to encode value"),
+        #[cfg(any(feature = "postcard", test))]
+        Format::Postcard => out = postcard::to_extend(val, out).expect("failed to encode value"),
+    }
     out
 }
 
@@ -74,3 +96,169 @@ where
 pub fn decode<T: DeserializeOwned>(input: &[u8]) -> Result<T, rmp_serde::decode::Error> {
     rmp_serde::decode::from_slice(input)
 }
+
+/// Decode a protocol message, encoded with [`encode_with_tag`] in a given format, after its tag.
+///
+/// Messages with anything after the value are rejected, in every format.
+pub fn decode_message<T: DeserializeOwned + Wire>(
+    format: Format,
+    input: &[u8],
+) -> Result<T, Box<dyn error::Error + Send + Sync>> {
+    match format {
+        Format::MessagePack => {
+            let mut rest = input;
+            let out = T::deserialize(&mut rmp_serde::Deserializer::new(&mut rest))?;
+            if !rest.is_empty() {
+                return Err("trailing bytes after message".into());
+            }
+            Ok(out)
+        }
+        #[cfg(any(feature = "tagged", test))]
+        Format::Tagged => Ok(tagged::decode(input)?),
+        #[cfg(any(feature = "cbor", test))]
+        Format::Cbor => {
+            let mut rest = input;
+            let out = ciborium::from_reader(&mut rest)?;
+            if !rest.is_empty() {
+                return Err("trailing bytes after message".into());
+            }
+            Ok(out)
+        }
+        #[cfg(any(feature = "postcard", test))]
+        Format::Postcard => {
+            let (out, rest) = postcard::take_from_bytes(input)?;
+            if !rest.is_empty() {
+                return Err("trailing bytes after message".into());
+            }
+            Ok(out)
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::{fs, path::PathBuf};
+
+    use elliptic_curve::{Field, ScalarPrimitive};
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
+    use serde::{de::DeserializeOwned, Serialize};
+
+    use super::{decode, decode_message, encode, encode_with_tag};
+    use crate::{
+        compat::SerializablePoint,
+        protocol::{Format, Participant},
+        triples::{TriplePub, TripleShare},
+        KeygenOutput, SignatureShare,
+    };
+
+    /// Check that a value encodes to the bytes in a golden file, and decodes back from them.
+    ///
+    /// Running the tests with `CAIT_SITH_BLESS=1` rewrites the files instead, which should
+    /// only be done when deliberately changing the encoding.
+    pub(crate) fn check_golden<T: Serialize + DeserializeOwned>(name: &str, value: &T) {
+        let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "testdata", "encoding", name]
+            .iter()
+            .collect();
+        let hex: String = encode(value).iter().map(|b| format!("{:02x}", b)).collect();
+        if std::env::var_os("CAIT_SITH_BLESS").is_some() {
+            fs::write(&path, format!("{}\n", hex)).unwrap();
+            return;
+        }
+        let golden = fs::read_to_string(&path).unwrap();
+        assert_eq!(hex, golden.trim(), "encoding of {} changed", name);
+        let bytes: Vec<u8> = (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect();
+        let decoded: T = decode(&bytes).unwrap();
+        assert_eq!(encode(&decoded), bytes, "decoding of {} isn't stable", name);
+    }
+
+    fn scalar(x: u64) -> Scalar {
+        Scalar::from(x).pow_vartime([x])
+    }
+
+    #[test]
+    fn test_golden_encodings() {
+        let g = ProjectivePoint::GENERATOR;
+        let (a, b) = (scalar(0x0123_4567), scalar(0x89ab_cdef));
+        let c = a * b;
+
+        check_golden("participant.hex", &Participant::from(0xdead_beefu32));
+        check_golden(
+            "keygen_output.hex",
+            &KeygenOutput::<Secp256k1> {
+                private_share: a,
+                public_key: (g * a).to_affine(),
+            },
+        );
+        check_golden("triple_share.hex", &TripleShare::<Secp256k1> { a, b, c });
+        check_golden(
+            "triple_pub.hex",
+            &TriplePub::<Secp256k1> {
+                big_a: (g * a).to_affine(),
+                big_b: (g * b).to_affine(),
+                big_c: (g * c).to_affine(),
+                participants: vec![Participant::from(1u32), Participant::from(300u32)],
+                threshold: 2,
+            },
+        );
+        check_golden(
+            "signature_share.hex",
+            &SignatureShare::<Secp256k1> {
+                participant: Participant::from(2u32),
+                big_r: (g * b).to_affine(),
+                s: c,
+            },
+        );
+    }
+
+    #[test]
+    fn test_message_formats() {
+        let g = ProjectivePoint::GENERATOR;
+        let (a, b) = (scalar(0x0123_4567), scalar(0x89ab_cdef));
+        // Messages are the values protocols send, which all have a schema.
+        let value = (
+            vec![
+                SerializablePoint::<Secp256k1>::from_projective(&(g * a)),
+                SerializablePoint::from_projective(&(g * b)),
+            ],
+            vec![ScalarPrimitive::<Secp256k1>::from(a * b)],
+            Participant::from(300u32),
+            2usize,
+        );
+        type Value = (
+            Vec<SerializablePoint<Secp256k1>>,
+            Vec<ScalarPrimitive<Secp256k1>>,
+            Participant,
+            usize,
+        );
+        for &format in Format::ALL {
+            let bytes = encode_with_tag(format, b"tag", &value);
+            let message = bytes.strip_prefix(b"tag").unwrap();
+            let decoded: Value = decode_message(format, message).unwrap();
+            assert_eq!(
+                encode_with_tag(format, b"tag", &decoded),
+                bytes,
+                "{format:?}"
+            );
+
+            let truncated = &message[..message.len() - 1];
+            assert!(
+                decode_message::<Value>(format, truncated).is_err(),
+                "{format:?}"
+            );
+            let mut longer = message.to_vec();
+            longer.push(0);
+            assert!(
+                decode_message::<Value>(format, &longer).is_err(),
+                "{format:?}"
+            );
+        }
+        // Postcard describes nothing but the values themselves.
+        assert_eq!(
+            encode_with_tag(Format::Postcard, b"", &(Participant::from(7u32), 150u32)),
+            vec![0x07, 0x96, 0x01]
+        );
+    }
+}
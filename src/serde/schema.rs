@@ -0,0 +1,375 @@
+//! This module describes the layout of protocol messages in the tagged encoding.
+//!
+//! Each type sent in a message implements [`Wire`], giving its protobuf type,
+//! and, for messages, the number of each of their fields. These schemas are
+//! written out by hand, next to the types they describe, and mirror the
+//! messages declared in `proto/cait_sith.proto`, which is checked against them.
+//!
+//! Numbers are part of the wire format: once a field has a number, it keeps it.
+use elliptic_curve::ScalarPrimitive;
+
+use crate::compat::{CSCurve, SerializablePoint};
+use crate::protocol::Participant;
+
+/// The protobuf type of a value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schema {
+    Uint32,
+    Uint64,
+    Bool,
+    Bytes,
+    /// A message, with its fields, in increasing order of their numbers.
+    Message(Vec<Field>),
+    /// A repeated field, or, anywhere else, a message containing one as field 1.
+    Repeated(Box<Schema>),
+}
+
+impl Schema {
+    /// A message with some fields, given by their name, number, and type.
+    pub fn message<const N: usize>(fields: [(&'static str, u32, Schema); N]) -> Self {
+        Self::Message(
+            fields
+                .into_iter()
+                .map(|(name, number, schema)| Field {
+                    name,
+                    number,
+                    schema,
+                })
+                .collect(),
+        )
+    }
+
+    pub fn repeated(element: Schema) -> Self {
+        Self::Repeated(Box::new(element))
+    }
+
+    /// Whether values of this type are varints, which repeated fields pack together.
+    pub fn is_varint(&self) -> bool {
+        matches!(self, Self::Uint32 | Self::Uint64 | Self::Bool)
+    }
+}
+
+/// A field of a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    /// The name of the field, as seen by serde, which tuples don't use.
+    pub name: &'static str,
+    pub number: u32,
+    pub schema: Schema,
+}
+
+/// Represents a type which can be sent in the tagged encoding.
+pub trait Wire {
+    fn schema() -> Schema;
+}
+
+macro_rules! impl_wire {
+    ($($ty:ty => $schema:ident),*) => {
+        $(
+            impl Wire for $ty {
+                fn schema() -> Schema {
+                    Schema::$schema
+                }
+            }
+        )*
+    };
+}
+
+impl_wire! {
+    bool => Bool,
+    u8 => Uint32,
+    u16 => Uint32,
+    u32 => Uint32,
+    u64 => Uint64,
+    usize => Uint64,
+    Participant => Uint32
+}
+
+impl<T: Wire + ?Sized> Wire for &T {
+    fn schema() -> Schema {
+        T::schema()
+    }
+}
+
+impl<T: Wire> Wire for Vec<T> {
+    fn schema() -> Schema {
+        Schema::repeated(T::schema())
+    }
+}
+
+impl<const N: usize> Wire for [u8; N] {
+    fn schema() -> Schema {
+        Schema::Bytes
+    }
+}
+
+impl<C: CSCurve, const N: usize> Wire for [SerializablePoint<C>; N] {
+    fn schema() -> Schema {
+        Schema::repeated(Schema::Bytes)
+    }
+}
+
+impl<C: CSCurve> Wire for SerializablePoint<C> {
+    fn schema() -> Schema {
+        Schema::Bytes
+    }
+}
+
+impl<C: CSCurve> Wire for ScalarPrimitive<C> {
+    fn schema() -> Schema {
+        Schema::Bytes
+    }
+}
+
+// Tuples are messages with the element at position `n` as field `n`, starting from 1.
+macro_rules! impl_wire_tuple {
+    ($($name:ident = $number:literal),*) => {
+        impl<$($name: Wire),*> Wire for ($($name,)*) {
+            fn schema() -> Schema {
+                Schema::message([$(("", $number, $name::schema())),*])
+            }
+        }
+    };
+}
+
+impl_wire_tuple!(A = 1);
+impl_wire_tuple!(A = 1, B = 2);
+impl_wire_tuple!(A = 1, B = 2, C = 3);
+impl_wire_tuple!(A = 1, B = 2, C = 3, D = 4);
+impl_wire_tuple!(A = 1, B = 2, C = 3, D = 4, E = 5);
+impl_wire_tuple!(A = 1, B = 2, C = 3, D = 4, E = 5, F = 6);
+impl_wire_tuple!(A = 1, B = 2, C = 3, D = 4, E = 5, F = 6, G = 7);
+
+#[cfg(test)]
+mod test {
+    use std::{
+        collections::{HashMap, HashSet},
+        fs,
+        path::PathBuf,
+    };
+
+    use elliptic_curve::ScalarPrimitive;
+    use k256::Secp256k1;
+
+    use super::*;
+    use crate::{
+        crypto::{Commitment, Digest, Randomizer},
+        math::GroupPolynomial,
+        proofs::{dlog, dlogeq, fischlin},
+        triples::{
+            bits::{BitMatrix, DoubleBitVector},
+            mta::MTAScalars,
+        },
+    };
+
+    type S = ScalarPrimitive<Secp256k1>;
+    type P = SerializablePoint<Secp256k1>;
+    type Poly = GroupPolynomial<Secp256k1>;
+    type Dlog = dlog::Proof<Secp256k1>;
+    type Dlogeq = dlogeq::Proof<Secp256k1>;
+
+    /// A field of a message in a `.proto` file: whether it's repeated, its type, and number.
+    type ProtoField = (bool, String, u32);
+
+    /// Parse the messages of a `.proto` file, which only has flat messages of plain fields.
+    fn parse_proto(source: &str) -> HashMap<String, Vec<ProtoField>> {
+        let mut tokens = Vec::new();
+        for line in source.lines() {
+            let line = line.split("//").next().unwrap();
+            let spaced = line
+                .replace('{', " { ")
+                .replace('}', " } ")
+                .replace('=', " = ")
+                .replace(';', " ; ");
+            tokens.extend(spaced.split_whitespace().map(str::to_owned));
+        }
+
+        let mut messages = HashMap::new();
+        let mut tokens = tokens.iter().map(String::as_str).peekable();
+        while let Some(token) = tokens.next() {
+            match token {
+                "syntax" | "package" => {
+                    let statement: Vec<_> = tokens.by_ref().take_while(|t| *t != ";").collect();
+                    if token == "syntax" {
+                        assert_eq!(statement, ["=", "\"proto3\""]);
+                    }
+                }
+                "message" => {
+                    let name = tokens.next().unwrap().to_owned();
+                    assert_eq!(tokens.next(), Some("{"));
+                    let mut fields = Vec::new();
+                    while tokens.next_if_eq(&"}").is_none() {
+                        let mut statement: Vec<_> =
+                            tokens.by_ref().take_while(|t| *t != ";").collect();
+                        let repeated = statement.first() == Some(&"repeated");
+                        if repeated {
+                            statement.remove(0);
+                        }
+                        match statement[..] {
+                            [ty, _, "=", number] => {
+                                fields.push((repeated, ty.to_owned(), number.parse().unwrap()))
+                            }
+                            _ => panic!("unexpected field {:?} in {}", statement, name),
+                        }
+                    }
+                    assert!(messages.insert(name, fields).is_none());
+                }
+                _ => panic!("unexpected {:?}", token),
+            }
+        }
+        messages
+    }
+
+    /// The schema of a message in a `.proto` file, without names.
+    fn proto_schema(messages: &HashMap<String, Vec<ProtoField>>, name: &str) -> Schema {
+        let fields = messages
+            .get(name)
+            .unwrap_or_else(|| panic!("missing message {}", name));
+        Schema::Message(
+            fields
+                .iter()
+                .map(|(repeated, ty, number)| {
+                    let schema = match ty.as_str() {
+                        "uint32" => Schema::Uint32,
+                        "uint64" => Schema::Uint64,
+                        "bool" => Schema::Bool,
+                        "bytes" => Schema::Bytes,
+                        other => proto_schema(messages, other),
+                    };
+                    Field {
+                        name: "",
+                        number: *number,
+                        schema: if *repeated {
+                            Schema::repeated(schema)
+                        } else {
+                            schema
+                        },
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// The schema of a message as it's sent, without names, with each value which
+    /// isn't a message or a field of one wrapped into a message, as field 1.
+    fn sent_schema(schema: Schema) -> Schema {
+        match schema {
+            Schema::Message(fields) => Schema::Message(
+                fields
+                    .into_iter()
+                    .map(|field| Field {
+                        name: "",
+                        number: field.number,
+                        schema: field_schema(field.schema),
+                    })
+                    .collect(),
+            ),
+            schema => Schema::message([("", 1, field_schema(schema))]),
+        }
+    }
+
+    fn field_schema(schema: Schema) -> Schema {
+        match schema {
+            Schema::Repeated(element)
+                if matches!(*element, Schema::Bytes) || element.is_varint() =>
+            {
+                Schema::Repeated(element)
+            }
+            Schema::Repeated(element) => Schema::repeated(sent_schema(*element)),
+            Schema::Message(_) => sent_schema(schema),
+            schema => schema,
+        }
+    }
+
+    #[test]
+    fn test_proto_matches_schemas() {
+        let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "proto", "cait_sith.proto"]
+            .iter()
+            .collect();
+        let messages = parse_proto(&fs::read_to_string(path).unwrap());
+
+        // The types sent by each protocol, with the messages declaring them.
+        let sent = [
+            ("DlogProof", Dlog::schema()),
+            ("Scalar", S::schema()),
+            ("Scalars", Vec::<S>::schema()),
+            ("Commitment", Commitment::schema()),
+            ("Commitments", Vec::<Commitment>::schema()),
+            ("Digest", Digest::schema()),
+            ("Digests", Vec::<Digest>::schema()),
+            ("KeygenReveal", <(Poly, Randomizer, Dlog)>::schema()),
+            (
+                "KeygenRevealFischlin",
+                <(Poly, Randomizer, fischlin::Proof<Secp256k1>)>::schema(),
+            ),
+            (
+                "KeygenManyReveal",
+                <(Vec<Poly>, Randomizer, Vec<Dlog>)>::schema(),
+            ),
+            ("HandoffDealing", <(Poly, Dlog)>::schema()),
+            ("ScalarPairs", Vec::<(S, S)>::schema()),
+            ("ScalarTriple", <(S, S, S)>::schema()),
+            (
+                "TripleReveal",
+                <(Poly, Poly, Poly, Randomizer, Dlog, Dlog)>::schema(),
+            ),
+            (
+                "TripleManyReveal",
+                <(
+                    Vec<Poly>,
+                    Vec<Poly>,
+                    Vec<Poly>,
+                    Vec<Randomizer>,
+                    Vec<Dlog>,
+                    Vec<Dlog>,
+                )>::schema(),
+            ),
+            ("ScalarPair", <(S, S)>::schema()),
+            ("ScalarsPair", <(Vec<S>, Vec<S>)>::schema()),
+            ("PointDlogeq", <(P, Dlogeq)>::schema()),
+            ("PointsDlogeq", <(Vec<P>, Vec<Dlogeq>)>::schema()),
+            ("PointDlog", <(P, Dlog)>::schema()),
+            ("PointsDlog", <(Vec<P>, Vec<Dlog>)>::schema()),
+            ("SacrificeOpening", <(Digest, S, S)>::schema()),
+            ("Point", P::schema()),
+            ("Points", Vec::<P>::schema()),
+            ("Seed", <[u8; 32]>::schema()),
+            (
+                "OtExtensionCheck",
+                <(DoubleBitVector, Vec<DoubleBitVector>)>::schema(),
+            ),
+            ("BitMatrix", BitMatrix::schema()),
+            ("MtaScalars", MTAScalars::<Secp256k1>::schema()),
+            ("MtaChallenge", <(S, [u8; 32])>::schema()),
+            ("Contribution", <([P; 4], Randomizer)>::schema()),
+            ("TwoPartyKeygenReveal", <(P, Randomizer, Dlog)>::schema()),
+            ("TwoPartyPoints", <((P, P, P), Randomizer)>::schema()),
+            (
+                "TwoPartyPresignReveal",
+                <(S, S, P, Dlogeq, P, P, Dlogeq)>::schema(),
+            ),
+        ];
+
+        for (name, schema) in &sent {
+            assert_eq!(
+                proto_schema(&messages, name),
+                sent_schema(schema.clone()),
+                "{} doesn't match its schema",
+                name
+            );
+        }
+
+        // Every message declared is sent, or part of one which is.
+        let mut used = HashSet::new();
+        let mut todo: Vec<&str> = sent.iter().map(|(name, _)| *name).collect();
+        while let Some(name) = todo.pop() {
+            if used.insert(name) {
+                todo.extend(messages[name].iter().map(|(_, ty, _)| ty.as_str()));
+                todo.retain(|ty| messages.contains_key(*ty));
+            }
+        }
+        for name in messages.keys() {
+            assert!(used.contains(name.as_str()), "{} is never sent", name);
+        }
+    }
+}
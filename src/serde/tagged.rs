@@ -0,0 +1,1214 @@
+//! This module provides a tagged field encoding for protocol messages.
+//!
+//! Messages are encoded as protobuf, following the schema of their type, given by [`Wire`].
+//! The same schemas are declared in `proto/cait_sith.proto`, so that other implementations,
+//! or anything in between, can parse and check messages with the usual protobuf tooling:
+//!
+//! - Integers and booleans are varints.
+//! - Scalars, points, digests, commitments, and bit vectors are byte strings.
+//! - Structs and tuples are messages, with the field numbers given by their schema.
+//! - Vectors and arrays are repeated fields, with varints being packed, as in proto3.
+//!   A vector anywhere but in a field of a message, e.g. inside of another vector,
+//!   is a message containing it as field 1.
+//!
+//! Fields are written in the order of their numbers, and, as in proto3, fields holding zero,
+//! `false`, or an empty byte string are left out, and read back as such when missing.
+//! Reading a message is stricter than protobuf is: the fields of the message may come
+//! in any order, but unknown fields, and fields appearing more than once, are rejected.
+//!
+//! A message whose type isn't itself a message, like a single scalar, is sent as field 1
+//! of a message. On the wire, this comes after the header identifying the channel and round
+//! of the message.
+use std::{error, fmt};
+
+use serde::{
+    de::{
+        self,
+        value::{BorrowedStrDeserializer, SeqDeserializer},
+        DeserializeOwned, Visitor,
+    },
+    ser::{self, Impossible, Serialize},
+};
+
+use super::schema::{Field, Schema, Wire};
+
+/// Represents an error when encoding or decoding a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error(String);
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Self(msg.to_string())
+    }
+}
+
+type Result<T> = std::result::Result<T, Error>;
+
+fn error<T>(msg: &str) -> Result<T> {
+    Err(Error(msg.to_string()))
+}
+
+fn mismatch<T>(schema: &Schema) -> Result<T> {
+    let expected = match schema {
+        Schema::Uint32 => "a uint32",
+        Schema::Uint64 => "a uint64",
+        Schema::Bool => "a bool",
+        Schema::Bytes => "bytes",
+        Schema::Message(_) => "a message",
+        Schema::Repeated(_) => "a repeated field",
+    };
+    Err(Error(format!(
+        "value doesn't match its schema, expected {expected}"
+    )))
+}
+
+fn unsupported<T>() -> Result<T> {
+    error("value has no representation in the tagged encoding")
+}
+
+const VARINT: u64 = 0;
+const LEN: u64 = 2;
+
+fn put_varint(out: &mut Vec<u8>, mut x: u64) {
+    while x >= 0x80 {
+        out.push((x as u8) | 0x80);
+        x >>= 7;
+    }
+    out.push(x as u8);
+}
+
+fn put_tag(out: &mut Vec<u8>, field: u32, wire_type: u64) {
+    put_varint(out, (u64::from(field) << 3) | wire_type);
+}
+
+fn put_len(out: &mut Vec<u8>, field: u32, data: &[u8]) {
+    put_tag(out, field, LEN);
+    put_varint(out, data.len() as u64);
+    out.extend_from_slice(data);
+}
+
+/// Where a value is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    /// As the fields of the top level message, without a tag.
+    Body,
+    /// As a field of a message, which is left out if it holds the default value.
+    Field,
+    /// As an element of a repeated field.
+    Element,
+    /// As an element of a packed repeated field, without a tag.
+    Packed,
+}
+
+/// How a compound value presents its elements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Seq,
+    Tuple,
+    Struct,
+}
+
+// Generates the methods of a serializer for values without a representation.
+macro_rules! unsupported_serialize {
+    ($($method:ident($($arg:ident: $ty:ty),*) -> $ret:ty;)*) => {
+        $(
+            fn $method(self, $(_: $ty),*) -> Result<$ret> {
+                unsupported()
+            }
+        )*
+    };
+}
+
+/// Serializes a value as a given field, following its schema.
+struct FieldSerializer<'a, 's> {
+    out: &'a mut Vec<u8>,
+    number: u32,
+    schema: &'s Schema,
+    slot: Slot,
+}
+
+impl<'a, 's> FieldSerializer<'a, 's> {
+    fn varint(self, v: u64) -> Result<()> {
+        match self.slot {
+            Slot::Field if v == 0 => {}
+            Slot::Packed => put_varint(self.out, v),
+            _ => {
+                put_tag(self.out, self.number, VARINT);
+                put_varint(self.out, v);
+            }
+        }
+        Ok(())
+    }
+
+    fn compound(self, kind: Kind) -> Result<Compound<'a, 's>> {
+        match (self.schema, kind) {
+            (Schema::Bytes, Kind::Seq | Kind::Tuple) => Ok(Compound::Bytes {
+                ser: self,
+                buf: Vec::new(),
+            }),
+            (Schema::Repeated(element), Kind::Seq | Kind::Tuple) => Ok(Compound::Repeated {
+                ser: self,
+                element,
+                buf: Vec::new(),
+                packed: Vec::new(),
+            }),
+            (Schema::Message(fields), Kind::Tuple | Kind::Struct) => Ok(Compound::Message {
+                ser: self,
+                fields,
+                slots: vec![Vec::new(); fields.len()],
+                index: 0,
+                by_name: kind == Kind::Struct,
+            }),
+            _ => mismatch(self.schema),
+        }
+    }
+}
+
+impl<'a, 's> ser::Serializer for FieldSerializer<'a, 's> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Compound<'a, 's>;
+    type SerializeTuple = Compound<'a, 's>;
+    type SerializeTupleStruct = Compound<'a, 's>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Compound<'a, 's>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        if self.schema != &Schema::Bool {
+            return mismatch(self.schema);
+        }
+        self.varint(u64::from(v))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        match self.schema {
+            Schema::Uint64 => {}
+            Schema::Uint32 if v <= u64::from(u32::MAX) => {}
+            _ => return mismatch(self.schema),
+        }
+        self.varint(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        if self.schema != &Schema::Bytes {
+            return mismatch(self.schema);
+        }
+        if !(self.slot == Slot::Field && v.is_empty()) {
+            put_len(self.out, self.number, v);
+        }
+        Ok(())
+    }
+
+    unsupported_serialize! {
+        serialize_i8(v: i8) -> ();
+        serialize_i16(v: i16) -> ();
+        serialize_i32(v: i32) -> ();
+        serialize_i64(v: i64) -> ();
+        serialize_f32(v: f32) -> ();
+        serialize_f64(v: f64) -> ();
+        serialize_char(v: char) -> ();
+        serialize_str(v: &str) -> ();
+        serialize_none() -> ();
+        serialize_unit() -> ();
+        serialize_unit_struct(name: &'static str) -> ();
+        serialize_unit_variant(name: &'static str, index: u32, variant: &'static str) -> ();
+        serialize_tuple_variant(
+            name: &'static str,
+            index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> Impossible<(), Error>;
+        serialize_map(len: Option<usize>) -> Impossible<(), Error>;
+        serialize_struct_variant(
+            name: &'static str,
+            index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> Impossible<(), Error>;
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<()> {
+        unsupported()
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()> {
+        unsupported()
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a, 's>> {
+        self.compound(Kind::Seq)
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'a, 's>> {
+        self.compound(Kind::Tuple)
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a, 's>> {
+        self.compound(Kind::Tuple)
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a, 's>> {
+        self.compound(Kind::Struct)
+    }
+}
+
+/// Serializes the elements of a byte string, a repeated field, or a message.
+enum Compound<'a, 's> {
+    Bytes {
+        ser: FieldSerializer<'a, 's>,
+        buf: Vec<u8>,
+    },
+    Repeated {
+        ser: FieldSerializer<'a, 's>,
+        element: &'s Schema,
+        buf: Vec<u8>,
+        packed: Vec<u8>,
+    },
+    Message {
+        ser: FieldSerializer<'a, 's>,
+        fields: &'s [Field],
+        slots: Vec<Vec<u8>>,
+        index: usize,
+        by_name: bool,
+    },
+}
+
+impl<'a, 's> Compound<'a, 's> {
+    fn element<T: ?Sized + Serialize>(&mut self, name: &str, value: &T) -> Result<()> {
+        match self {
+            Self::Bytes { buf, .. } => value.serialize(ByteSerializer { out: buf }),
+            Self::Repeated {
+                ser,
+                element,
+                buf,
+                packed,
+            } => {
+                // A repeated field outside of a message is wrapped in one, as field 1.
+                let number = if ser.slot == Slot::Field {
+                    ser.number
+                } else {
+                    1
+                };
+                let (out, slot) = if element.is_varint() {
+                    (packed, Slot::Packed)
+                } else {
+                    (buf, Slot::Element)
+                };
+                value.serialize(FieldSerializer {
+                    out,
+                    number,
+                    schema: element,
+                    slot,
+                })
+            }
+            Self::Message {
+                fields,
+                slots,
+                index,
+                by_name,
+                ..
+            } => {
+                let i = if *by_name {
+                    fields
+                        .iter()
+                        .position(|f| f.name == name)
+                        .ok_or_else(|| Error(format!("field {name} isn't in the schema")))?
+                } else {
+                    *index
+                };
+                *index += 1;
+                let field = fields
+                    .get(i)
+                    .ok_or_else(|| Error("more elements than the schema has fields".to_string()))?;
+                value.serialize(FieldSerializer {
+                    out: &mut slots[i],
+                    number: field.number,
+                    schema: &field.schema,
+                    slot: Slot::Field,
+                })
+            }
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Self::Bytes { ser, buf } => ser::Serializer::serialize_bytes(ser, &buf),
+            Self::Repeated {
+                ser,
+                mut buf,
+                packed,
+                ..
+            } => {
+                let nested = ser.slot != Slot::Field;
+                if !packed.is_empty() {
+                    put_len(&mut buf, if nested { 1 } else { ser.number }, &packed);
+                }
+                if nested {
+                    put_len(ser.out, ser.number, &buf);
+                } else {
+                    ser.out.extend_from_slice(&buf);
+                }
+                Ok(())
+            }
+            Self::Message { ser, slots, .. } => {
+                let body = slots.concat();
+                if ser.slot == Slot::Body {
+                    ser.out.extend_from_slice(&body);
+                } else {
+                    put_len(ser.out, ser.number, &body);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl<'a, 's> ser::SerializeSeq for Compound<'a, 's> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.element("", value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a, 's> ser::SerializeTuple for Compound<'a, 's> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.element("", value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a, 's> ser::SerializeTupleStruct for Compound<'a, 's> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.element("", value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+impl<'a, 's> ser::SerializeStruct for Compound<'a, 's> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.element(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+/// Serializes an element of a byte string, which can only be a byte.
+struct ByteSerializer<'a> {
+    out: &'a mut Vec<u8>,
+}
+
+impl<'a> ser::Serializer for ByteSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        self.out.push(v);
+        Ok(())
+    }
+
+    unsupported_serialize! {
+        serialize_bool(v: bool) -> ();
+        serialize_i8(v: i8) -> ();
+        serialize_i16(v: i16) -> ();
+        serialize_i32(v: i32) -> ();
+        serialize_i64(v: i64) -> ();
+        serialize_u16(v: u16) -> ();
+        serialize_u32(v: u32) -> ();
+        serialize_u64(v: u64) -> ();
+        serialize_f32(v: f32) -> ();
+        serialize_f64(v: f64) -> ();
+        serialize_char(v: char) -> ();
+        serialize_str(v: &str) -> ();
+        serialize_bytes(v: &[u8]) -> ();
+        serialize_none() -> ();
+        serialize_unit() -> ();
+        serialize_unit_struct(name: &'static str) -> ();
+        serialize_unit_variant(name: &'static str, index: u32, variant: &'static str) -> ();
+        serialize_seq(len: Option<usize>) -> Impossible<(), Error>;
+        serialize_tuple(len: usize) -> Impossible<(), Error>;
+        serialize_tuple_struct(name: &'static str, len: usize) -> Impossible<(), Error>;
+        serialize_tuple_variant(
+            name: &'static str,
+            index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> Impossible<(), Error>;
+        serialize_map(len: Option<usize>) -> Impossible<(), Error>;
+        serialize_struct(name: &'static str, len: usize) -> Impossible<(), Error>;
+        serialize_struct_variant(
+            name: &'static str,
+            index: u32,
+            variant: &'static str,
+            len: usize
+        ) -> Impossible<(), Error>;
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<()> {
+        unsupported()
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _value: &T,
+    ) -> Result<()> {
+        unsupported()
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()> {
+        unsupported()
+    }
+}
+
+/// The value of a single field, as it appears on the wire.
+#[derive(Debug, Clone, Copy)]
+enum Value<'de> {
+    Varint(u64),
+    Len(&'de [u8]),
+}
+
+/// Reads the fields of a message.
+struct Reader<'de> {
+    input: &'de [u8],
+}
+
+impl<'de> Reader<'de> {
+    fn new(input: &'de [u8]) -> Self {
+        Self { input }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.input.is_empty()
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'de [u8]> {
+        if n > self.input.len() {
+            return error("unexpected end of message");
+        }
+        let (out, rest) = self.input.split_at(n);
+        self.input = rest;
+        Ok(out)
+    }
+
+    fn varint(&mut self) -> Result<u64> {
+        let mut out = 0u64;
+        for i in 0..10 {
+            let b = self.take(1)?[0];
+            if i == 9 && b > 1 {
+                return error("varint overflows 64 bits");
+            }
+            out |= u64::from(b & 0x7F) << (7 * i);
+            if b & 0x80 == 0 {
+                return Ok(out);
+            }
+        }
+        error("varint overflows 64 bits")
+    }
+
+    fn field(&mut self) -> Result<(u32, Value<'de>)> {
+        let tag = self.varint()?;
+        let number = u32::try_from(tag >> 3)
+            .ok()
+            .filter(|&n| n > 0)
+            .ok_or_else(|| Error("invalid field number".to_string()))?;
+        let value = match tag & 7 {
+            VARINT => Value::Varint(self.varint()?),
+            LEN => {
+                let len = usize::try_from(self.varint()?)
+                    .map_err(|_| Error("length doesn't fit in memory".to_string()))?;
+                Value::Len(self.take(len)?)
+            }
+            _ => return error("unsupported wire type"),
+        };
+        Ok((number, value))
+    }
+}
+
+/// Read the fields of a message, collecting the values of each field of its schema.
+fn read_message<'de>(fields: &[Field], input: &'de [u8]) -> Result<Vec<Vec<Value<'de>>>> {
+    let mut out = vec![Vec::new(); fields.len()];
+    let mut reader = Reader::new(input);
+    while !reader.is_empty() {
+        let (number, value) = reader.field()?;
+        let i = fields
+            .iter()
+            .position(|f| f.number == number)
+            .ok_or_else(|| Error(format!("unknown field {number}")))?;
+        if !matches!(fields[i].schema, Schema::Repeated(_)) && !out[i].is_empty() {
+            return Err(Error(format!("field {number} appears more than once")));
+        }
+        out[i].push(value);
+    }
+    Ok(out)
+}
+
+/// Read the values of a field wrapped in a message, as field 1.
+fn read_wrapped<'de>(schema: &Schema, input: &'de [u8]) -> Result<Vec<Value<'de>>> {
+    let mut out = Vec::new();
+    let mut reader = Reader::new(input);
+    while !reader.is_empty() {
+        let (number, value) = reader.field()?;
+        if number != 1 {
+            return Err(Error(format!("unknown field {number}")));
+        }
+        if !matches!(schema, Schema::Repeated(_)) && !out.is_empty() {
+            return error("field 1 appears more than once");
+        }
+        out.push(value);
+    }
+    Ok(out)
+}
+
+/// Deserializes a field of a message, from all of the values it has on the wire.
+///
+/// Fields other than repeated ones have at most one value, with none meaning the default.
+struct FieldDeserializer<'s, 'de> {
+    schema: &'s Schema,
+    values: Vec<Value<'de>>,
+}
+
+impl<'s, 'de> FieldDeserializer<'s, 'de> {
+    /// The deserializer for an element of a repeated field.
+    fn element(schema: &'s Schema, value: Value<'de>) -> Result<Self> {
+        let values = match (schema, value) {
+            // A repeated field outside of a message is wrapped in one, as field 1.
+            (Schema::Repeated(_), Value::Len(data)) => read_wrapped(schema, data)?,
+            (Schema::Repeated(_), _) => return mismatch(schema),
+            _ => vec![value],
+        };
+        Ok(Self { schema, values })
+    }
+
+    fn varint(&self) -> Result<u64> {
+        match self.values.first() {
+            None => Ok(0),
+            Some(Value::Varint(x)) => Ok(*x),
+            Some(_) => error("expected a varint"),
+        }
+    }
+
+    fn uint(&self) -> Result<u64> {
+        let x = self.varint()?;
+        match self.schema {
+            Schema::Uint64 => Ok(x),
+            Schema::Uint32 if x <= u64::from(u32::MAX) => Ok(x),
+            Schema::Uint32 => error("integer out of range"),
+            _ => mismatch(self.schema),
+        }
+    }
+
+    fn len(&self) -> Result<&'de [u8]> {
+        match self.values.first() {
+            None => Ok(&[]),
+            Some(Value::Len(data)) => Ok(data),
+            Some(_) => error("expected a length delimited field"),
+        }
+    }
+
+    fn bytes(&self) -> Result<&'de [u8]> {
+        if self.schema != &Schema::Bytes {
+            return mismatch(self.schema);
+        }
+        self.len()
+    }
+
+    fn visit_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let mut seq = SeqDeserializer::new(self.bytes()?.iter().copied());
+        let out = visitor.visit_seq(&mut seq)?;
+        seq.end()?;
+        Ok(out)
+    }
+
+    fn visit_repeated<V: Visitor<'de>>(self, element: &'s Schema, visitor: V) -> Result<V::Value> {
+        let values = if element.is_varint() {
+            // Accept both packed and unpacked elements, like protobuf does.
+            let mut unpacked = Vec::new();
+            for value in self.values {
+                match value {
+                    Value::Varint(_) => unpacked.push(value),
+                    Value::Len(data) => {
+                        let mut reader = Reader::new(data);
+                        while !reader.is_empty() {
+                            unpacked.push(Value::Varint(reader.varint()?));
+                        }
+                    }
+                }
+            }
+            unpacked
+        } else {
+            self.values
+        };
+        let mut elements = Elements {
+            schema: element,
+            values: values.into_iter(),
+        };
+        let out = visitor.visit_seq(&mut elements)?;
+        if elements.values.len() > 0 {
+            return error("more elements than expected");
+        }
+        Ok(out)
+    }
+
+    fn visit_message<V: Visitor<'de>>(
+        self,
+        fields: &'s [Field],
+        by_name: bool,
+        visitor: V,
+    ) -> Result<V::Value> {
+        let mut access = Fields {
+            fields,
+            values: read_message(fields, self.len()?)?.into_iter(),
+            index: 0,
+            value: None,
+        };
+        let out = if by_name {
+            visitor.visit_map(&mut access)?
+        } else {
+            visitor.visit_seq(&mut access)?
+        };
+        if access.index < fields.len() {
+            return error("fewer elements than the schema has fields");
+        }
+        Ok(out)
+    }
+}
+
+// Generates the methods of a deserializer for values without a representation.
+macro_rules! unsupported_deserialize {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, $(_: $ty,)* _visitor: V) -> Result<V::Value> {
+                unsupported()
+            }
+        )*
+    };
+}
+
+impl<'s, 'de> de::Deserializer<'de> for FieldDeserializer<'s, 'de> {
+    type Error = Error;
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.schema {
+            Schema::Uint32 | Schema::Uint64 => visitor.visit_u64(self.uint()?),
+            Schema::Bool => self.deserialize_bool(visitor),
+            Schema::Bytes => self.deserialize_bytes(visitor),
+            Schema::Message(fields) => self.visit_message(fields, false, visitor),
+            Schema::Repeated(element) => self.visit_repeated(element, visitor),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.schema != &Schema::Bool {
+            return mismatch(self.schema);
+        }
+        match self.varint()? {
+            0 => visitor.visit_bool(false),
+            1 => visitor.visit_bool(true),
+            _ => error("invalid boolean"),
+        }
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u8(convert(self.uint()?)?)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u16(convert(self.uint()?)?)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u32(convert(self.uint()?)?)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_u64(self.uint()?)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_borrowed_bytes(self.bytes()?)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.schema {
+            Schema::Bytes => self.visit_bytes(visitor),
+            Schema::Repeated(element) => self.visit_repeated(element, visitor),
+            _ => mismatch(self.schema),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        match self.schema {
+            Schema::Message(fields) => self.visit_message(fields, false, visitor),
+            _ => self.deserialize_seq(visitor),
+        }
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_tuple(len, visitor)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.schema {
+            Schema::Message(fields) => self.visit_message(fields, true, visitor),
+            _ => mismatch(self.schema),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    unsupported_deserialize! {
+        deserialize_i8();
+        deserialize_i16();
+        deserialize_i32();
+        deserialize_i64();
+        deserialize_f32();
+        deserialize_f64();
+        deserialize_char();
+        deserialize_str();
+        deserialize_string();
+        deserialize_option();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_map();
+        deserialize_enum(name: &'static str, variants: &'static [&'static str]);
+        deserialize_identifier();
+    }
+}
+
+fn convert<T: TryFrom<u64>>(x: u64) -> Result<T> {
+    T::try_from(x).map_err(|_| Error("integer out of range".to_string()))
+}
+
+/// Deserializes the elements of a repeated field.
+struct Elements<'s, 'de> {
+    schema: &'s Schema,
+    values: std::vec::IntoIter<Value<'de>>,
+}
+
+impl<'s, 'de> de::SeqAccess<'de> for Elements<'s, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>> {
+        match self.values.next() {
+            None => Ok(None),
+            Some(value) => seed
+                .deserialize(FieldDeserializer::element(self.schema, value)?)
+                .map(Some),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
+    }
+}
+
+/// Deserializes the fields of a message, in the order of its schema.
+///
+/// Tuples see these as a sequence, and structs as a map from the names of the fields.
+struct Fields<'s, 'de> {
+    fields: &'s [Field],
+    values: std::vec::IntoIter<Vec<Value<'de>>>,
+    index: usize,
+    value: Option<FieldDeserializer<'s, 'de>>,
+}
+
+impl<'s, 'de> Fields<'s, 'de> {
+    fn next_field(&mut self) -> Option<(&'s Field, FieldDeserializer<'s, 'de>)> {
+        let field = self.fields.get(self.index)?;
+        self.index += 1;
+        let values = self
+            .values
+            .next()
+            .expect("there should be values for each field");
+        Some((
+            field,
+            FieldDeserializer {
+                schema: &field.schema,
+                values,
+            },
+        ))
+    }
+}
+
+impl<'s, 'de> de::SeqAccess<'de> for Fields<'s, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>> {
+        match self.next_field() {
+            None => Ok(None),
+            Some((_, value)) => seed.deserialize(value).map(Some),
+        }
+    }
+}
+
+impl<'s, 'de> de::MapAccess<'de> for Fields<'s, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.next_field() {
+            None => Ok(None),
+            Some((field, value)) => {
+                self.value = Some(value);
+                seed.deserialize(BorrowedStrDeserializer::new(field.name))
+                    .map(Some)
+            }
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| Error("map value without a key".to_string()))?;
+        seed.deserialize(value)
+    }
+}
+
+/// Encode a value as a tagged message, appending it to some output.
+pub fn encode_into<T: Serialize + Wire>(out: &mut Vec<u8>, val: &T) -> Result<()> {
+    let schema = T::schema();
+    let (number, slot) = match schema {
+        Schema::Message(_) => (0, Slot::Body),
+        _ => (1, Slot::Field),
+    };
+    val.serialize(FieldSerializer {
+        out,
+        number,
+        schema: &schema,
+        slot,
+    })
+}
+
+/// Decode a value from a tagged message.
+pub fn decode<T: DeserializeOwned + Wire>(input: &[u8]) -> Result<T> {
+    let schema = T::schema();
+    let values = match schema {
+        Schema::Message(_) => vec![Value::Len(input)],
+        _ => read_wrapped(&schema, input)?,
+    };
+    T::deserialize(FieldDeserializer {
+        schema: &schema,
+        values,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use ecdsa::elliptic_curve::ScalarPrimitive;
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
+    use rand_core::OsRng;
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::{
+        compat::SerializablePoint,
+        crypto::{hash, Digest},
+        fiat_shamir::{FiatShamir, Transcript},
+        proofs::{dlog, dlogeq, fischlin},
+        protocol::Participant,
+        serde::{
+            deserialize_projective_points, deserialize_scalar, serialize_projective_points,
+            serialize_scalar,
+        },
+    };
+
+    fn encode<T: Serialize + Wire>(val: &T) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode_into(&mut out, val).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_wire_format() {
+        assert_eq!(encode(&150u32), vec![0x08, 0x96, 0x01]);
+        assert_eq!(encode(&(1u32, true)), vec![0x08, 0x01, 0x10, 0x01]);
+        // Default values are left out.
+        assert_eq!(encode(&(0u32, false)), Vec::<u8>::new());
+        // Bytes are only byte strings when the schema says so.
+        assert_eq!(encode(&[1u8, 2, 3]), vec![0x0A, 0x03, 1, 2, 3]);
+        assert_eq!(encode(&(1u8, 2u8)), vec![0x08, 0x01, 0x10, 0x02]);
+        // Repeated varints are packed.
+        assert_eq!(encode(&vec![1u32, 2]), vec![0x0A, 0x02, 0x01, 0x02]);
+        assert_eq!(
+            encode(&vec![vec![1u32], vec![]]),
+            vec![0x0A, 0x03, 0x0A, 0x01, 0x01, 0x0A, 0x00]
+        );
+        assert_eq!(encode(&Participant::from(7u32)), vec![0x08, 0x07]);
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Message {
+        #[serde(serialize_with = "serialize_projective_points::<Secp256k1, _>")]
+        #[serde(deserialize_with = "deserialize_projective_points::<Secp256k1, _>")]
+        points: Vec<ProjectivePoint>,
+        from: Participant,
+        #[serde(serialize_with = "serialize_scalar::<Secp256k1, _>")]
+        #[serde(deserialize_with = "deserialize_scalar::<Secp256k1, _>")]
+        scalar: Scalar,
+        digests: Vec<Vec<Digest>>,
+        counts: Vec<u64>,
+        flag: bool,
+    }
+
+    impl Wire for Message {
+        fn schema() -> Schema {
+            Schema::message([
+                ("from", 1, Schema::Uint32),
+                ("scalar", 2, Schema::Bytes),
+                ("points", 4, Schema::repeated(Schema::Bytes)),
+                ("digests", 5, <Vec<Vec<Digest>>>::schema()),
+                ("counts", 6, <Vec<u64>>::schema()),
+                ("flag", 7, Schema::Bool),
+            ])
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let message = Message {
+            points: (0..3)
+                .map(|_| ProjectivePoint::GENERATOR * Scalar::generate_biased(&mut OsRng))
+                .collect(),
+            from: Participant::from(3u32),
+            scalar: Scalar::generate_biased(&mut OsRng),
+            digests: vec![vec![], vec![hash(&1u32), hash(&2u32)]],
+            counts: vec![0, u64::MAX],
+            flag: false,
+        };
+        let bytes = encode(&message);
+        assert_eq!(decode::<Message>(&bytes).unwrap(), message);
+        // Fields are written by number, whatever the order of their declaration.
+        assert_eq!(&bytes[..2], &[0x08, 0x03]);
+        assert_eq!(bytes[2], 0x12);
+
+        let digest = hash(&message.from);
+        assert_eq!(decode::<Digest>(&encode(&digest)).unwrap(), digest);
+        let pair = (
+            SerializablePoint::<Secp256k1>::from_projective(&message.points[0]),
+            ScalarPrimitive::<Secp256k1>::from(message.scalar),
+        );
+        let decoded: (SerializablePoint<Secp256k1>, ScalarPrimitive<Secp256k1>) =
+            decode(&encode(&pair)).unwrap();
+        assert_eq!(decoded.0.to_projective(), message.points[0]);
+        assert_eq!(decoded.1, pair.1);
+    }
+
+    #[test]
+    fn test_proofs_round_trip() {
+        // Proofs are structs, whose fields are found by name in their schema.
+        let x = Scalar::generate_biased(&mut OsRng);
+        let big_x = ProjectivePoint::GENERATOR * x;
+        let big_h = ProjectivePoint::GENERATOR * Scalar::generate_biased(&mut OsRng);
+        let transcript = Transcript::new(b"protocol");
+
+        let statement = dlog::Statement::<Secp256k1> { public: &big_x };
+        let witness = dlog::Witness { x: &x };
+        let proof = dlog::prove(
+            &mut OsRng,
+            &mut transcript.forked(b"party", &[1]),
+            statement,
+            witness,
+        );
+        let proof: dlog::Proof<Secp256k1> = decode(&encode(&proof)).unwrap();
+        assert!(dlog::verify(
+            &mut transcript.forked(b"party", &[1]),
+            statement,
+            &proof
+        ));
+
+        let proof = fischlin::prove(
+            &mut OsRng,
+            &mut transcript.forked(b"party", &[1]),
+            statement,
+            witness,
+        );
+        let proof: fischlin::Proof<Secp256k1> = decode(&encode(&proof)).unwrap();
+        assert!(fischlin::verify(
+            &mut transcript.forked(b"party", &[1]),
+            statement,
+            &proof
+        ));
+
+        let statement = dlogeq::Statement::<Secp256k1> {
+            public0: &big_x,
+            generator1: &big_h,
+            public1: &(big_h * x),
+        };
+        let witness = dlogeq::Witness { x: &x };
+        let proof = dlogeq::prove(
+            &mut OsRng,
+            &mut transcript.forked(b"party", &[1]),
+            statement,
+            witness,
+        );
+        let proof: dlogeq::Proof<Secp256k1> = decode(&encode(&proof)).unwrap();
+        assert!(dlogeq::verify(
+            &mut transcript.forked(b"party", &[1]),
+            statement,
+            &proof
+        ));
+    }
+
+    #[test]
+    fn test_reads_like_protobuf() {
+        // Fields in any order, with missing ones being the default.
+        assert_eq!(
+            decode::<(u32, u32, bool)>(&[0x10, 0x05, 0x08, 0x01]).unwrap(),
+            (1, 5, false)
+        );
+        // Unpacked repeated varints.
+        assert_eq!(
+            decode::<Vec<u32>>(&[0x08, 0x01, 0x08, 0x02]).unwrap(),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn test_rejects_bad_messages() {
+        let bytes = encode(&(1u32, 2u32));
+        // Truncated.
+        assert!(decode::<(u32, u32)>(&bytes[..bytes.len() - 1]).is_err());
+        // Unknown fields.
+        assert!(decode::<(u32,)>(&bytes).is_err());
+        // Repeated fields.
+        assert!(decode::<(u32, u32)>(&[0x08, 0x01, 0x08, 0x01]).is_err());
+        // Wrong wire type.
+        assert!(decode::<(u32, [u8; 1])>(&bytes).is_err());
+        // Out of range.
+        assert!(decode::<u8>(&encode(&256u32)).is_err());
+        assert!(decode::<u32>(&encode(&(1u64 << 32))).is_err());
+        // A scalar with the wrong length.
+        assert!(decode::<ScalarPrimitive<Secp256k1>>(&encode(&[0u8; 31])).is_err());
+        // An array with the wrong number of elements.
+        let points = vec![SerializablePoint::<Secp256k1>::from_projective(
+            &ProjectivePoint::GENERATOR,
+        )];
+        assert!(decode::<[SerializablePoint<Secp256k1>; 2]>(&encode(&points)).is_err());
+    }
+}
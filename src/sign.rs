@@ -1,14 +1,24 @@
-use elliptic_curve::{ops::Invert, scalar::IsHigh, Field, Group, ScalarPrimitive};
+use std::{collections::HashSet, ops::Add};
+
+use ecdsa::SignatureSize;
+#[allow(deprecated)]
+use elliptic_curve::generic_array::ArrayLength;
+use elliptic_curve::{
+    ops::Invert, point::AffineCoordinates, scalar::IsHigh, Field, FieldBytesSize, Group,
+    PrimeField, ScalarPrimitive,
+};
+use serde::{Deserialize, Serialize};
 use subtle::ConditionallySelectable;
 
 use crate::{
     compat::{self, CSCurve},
+    ledger::NonceLedger,
     participants::{ParticipantCounter, ParticipantList},
     protocol::{
         internal::{make_protocol, Context, SharedChannel},
-        InitializationError, Participant, Protocol, ProtocolError,
+        Fault, InitializationError, Participant, Protocol, ProtocolError,
     },
-    PresignOutput,
+    BoundPresignOutput, PresignOutput, PresignatureId,
 };
 
 /// Represents a signature with extra information, to support different variants of ECDSA.
@@ -22,6 +32,10 @@ use crate::{
 ///
 /// To support these variants, this simply gives you a normal signature, along with the entire
 /// first point.
+///
+/// The signatures produced by this library always have a low `s` value,
+/// with the first point negated accordingly, so that functions like
+/// [`FullSignature::recovery_id`] are consistent with `s`.
 #[derive(Clone)]
 pub struct FullSignature<C: CSCurve> {
     /// This is the entire first point.
@@ -31,6 +45,36 @@ pub struct FullSignature<C: CSCurve> {
 }
 
 impl<C: CSCurve> FullSignature<C> {
+    /// Create a signature with a low `s` value, negating the first point if necessary.
+    ///
+    /// Since `(r, s)` and `(r, -s)` are both valid signatures, with `-s`
+    /// corresponding to the nonce point `-R`, this doesn't change the validity of the signature.
+    pub fn normalized(big_r: C::AffinePoint, s: C::Scalar) -> Self {
+        let high = s.is_high();
+        let big_r = C::ProjectivePoint::from(big_r);
+        let big_r = C::ProjectivePoint::conditional_select(&big_r, &(-big_r), high);
+        Self {
+            big_r: big_r.into(),
+            s: C::Scalar::conditional_select(&s, &(-s), high),
+        }
+    }
+
+    /// The recovery id of this signature, as used by Ethereum, among others.
+    ///
+    /// The lowest bit is the parity of the y coordinate of the first point,
+    /// and the next bit is set if its x coordinate was reduced to get `r`.
+    /// This allows recovering the public key from the signature and message.
+    ///
+    /// For Ethereum transactions, `v` is this value plus 27, or plus `35 + 2 * chain_id`
+    /// with replay protection. Note that Ethereum requires `s` to be low, which is
+    /// the case for signatures produced by this library.
+    pub fn recovery_id(&self) -> u8 {
+        let y_odd = self.big_r.y_is_odd().unwrap_u8();
+        let r = compat::x_coordinate::<C>(&self.big_r);
+        let x_reduced = u8::from(r.to_repr() != self.big_r.x());
+        (x_reduced << 1) | y_odd
+    }
+
     #[must_use]
     pub fn verify(&self, public_key: &C::AffinePoint, msg_hash: &C::Scalar) -> bool {
         let r: C::Scalar = compat::x_coordinate::<C>(&self.big_r);
@@ -44,14 +88,55 @@ impl<C: CSCurve> FullSignature<C> {
     }
 }
 
-async fn do_sign<C: CSCurve>(
-    mut chan: SharedChannel,
-    participants: ParticipantList,
+// The signature types of the ecdsa crate are sized with generic-array, whose `ArrayLength`
+// is deprecated in favor of its next major version, which these crates don't use yet.
+#[allow(deprecated)]
+impl<C: CSCurve> FullSignature<C>
+where
+    SignatureSize<C>: ArrayLength<u8>,
+{
+    /// Convert this signature to the format of the `ecdsa` crate, keeping only `r` of the first point.
+    ///
+    /// This is what most libraries expect, and can be checked with the `VerifyingKey` of crates
+    /// like `k256`. Libraries which also need the recovery id can get it with [`FullSignature::recovery_id`].
+    /// This fails if `r` or `s` is zero, which never happens for valid signatures.
+    pub fn to_ecdsa(&self) -> Result<ecdsa::Signature<C>, ecdsa::Error> {
+        let r = compat::x_coordinate::<C>(&self.big_r);
+        ecdsa::Signature::from_scalars(r.to_repr(), self.s.to_repr())
+    }
+
+    /// Serialize this signature as ASN.1 DER, as used by X.509, TLS, and Bitcoin, among others.
+    pub fn to_der(&self) -> Result<ecdsa::der::Signature<C>, ecdsa::Error>
+    where
+        ecdsa::der::MaxSize<C>: ArrayLength<u8>,
+        <FieldBytesSize<C> as Add>::Output: Add<ecdsa::der::MaxOverhead> + ArrayLength<u8>,
+    {
+        Ok(self.to_ecdsa()?.to_der())
+    }
+}
+
+/// A participant's share of a signature.
+///
+/// Rather than having every participant run the signing protocol, each of them
+/// can produce a share with [`signature_share`], and send it to a designated
+/// aggregator, which then uses [`FullSignature::combine`] to get the signature.
+/// Shares reveal nothing beyond the signature itself, so they can be sent in the clear.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignatureShare<C: CSCurve> {
+    /// The participant who produced this share.
+    pub participant: Participant,
+    /// The public nonce commitment from the presignature.
+    pub big_r: C::AffinePoint,
+    /// The share of the second scalar of the signature.
+    pub s: C::Scalar,
+}
+
+fn compute_share<C: CSCurve>(
+    participants: &ParticipantList,
     me: Participant,
-    public_key: C::AffinePoint,
-    presignature: PresignOutput<C>,
+    presignature: &PresignOutput<C>,
     msg_hash: C::Scalar,
-) -> Result<FullSignature<C>, ProtocolError> {
+) -> SignatureShare<C> {
     // Spec 1.1
     let lambda = participants.lagrange::<C>(me);
     let k_i = lambda * presignature.k;
@@ -63,6 +148,89 @@ async fn do_sign<C: CSCurve>(
     let r = compat::x_coordinate::<C>(&presignature.big_r);
     let s_i: C::Scalar = msg_hash * k_i + r * sigma_i;
 
+    SignatureShare {
+        participant: me,
+        big_r: presignature.big_r,
+        s: s_i,
+    }
+}
+
+impl<C: CSCurve> FullSignature<C> {
+    /// Combine signature shares into a full signature, checking that it's valid.
+    ///
+    /// There must be exactly one share for each of the participants which were used
+    /// to create the shares, otherwise the resulting signature will be invalid.
+    pub fn combine(
+        shares: &[SignatureShare<C>],
+        public_key: &C::AffinePoint,
+        msg_hash: &C::Scalar,
+    ) -> Result<Self, ProtocolError> {
+        let first = shares
+            .first()
+            .ok_or_else(|| ProtocolError::Other("no signature shares to combine".into()))?;
+        let mut seen = HashSet::new();
+        let mut s = C::Scalar::ZERO;
+        for share in shares {
+            if !seen.insert(share.participant) {
+                return Err(ProtocolError::Failed {
+                    round: 0,
+                    fault: Fault::InvalidMessage("duplicate signature share"),
+                });
+            }
+            if share.big_r != first.big_r {
+                return Err(ProtocolError::Failed {
+                    round: 0,
+                    fault: Fault::InvalidMessage("signature shares use different presignatures"),
+                });
+            }
+            s += share.s;
+        }
+
+        // Spec 2.3
+        let sig = Self::normalized(first.big_r, s);
+        if !sig.verify(public_key, msg_hash) {
+            return Err(ProtocolError::Failed {
+                round: 0,
+                fault: Fault::InvalidResult("signature failed to verify"),
+            });
+        }
+
+        // Spec 2.4
+        Ok(sig)
+    }
+}
+
+/// Application code deciding whether a message should be signed.
+///
+/// This is consulted by [`sign_with_policy`], right before computing our share
+/// of the signature, so that nothing gets signed without being approved.
+pub trait SigningPolicy<C: CSCurve> {
+    /// Decide whether to sign a message hash, along with some participants.
+    fn approve(&mut self, msg_hash: &C::Scalar, participants: &[Participant]) -> bool;
+}
+
+impl<C: CSCurve, F: FnMut(&C::Scalar, &[Participant]) -> bool> SigningPolicy<C> for F {
+    fn approve(&mut self, msg_hash: &C::Scalar, participants: &[Participant]) -> bool {
+        self(msg_hash, participants)
+    }
+}
+
+async fn do_sign<C: CSCurve>(
+    mut chan: SharedChannel,
+    participants: ParticipantList,
+    me: Participant,
+    public_key: C::AffinePoint,
+    presignature: PresignOutput<C>,
+    msg_hash: C::Scalar,
+    mut policy: impl SigningPolicy<C>,
+) -> Result<FullSignature<C>, ProtocolError> {
+    if !policy.approve(&msg_hash, &Vec::from(participants.clone())) {
+        return Err(ProtocolError::Denied);
+    }
+
+    // Spec 1.1 - 1.3
+    let s_i = compute_share(&participants, me, &presignature, msg_hash).s;
+
     // Spec 1.4
     let wait0 = chan.next_waitpoint();
     {
@@ -84,71 +252,108 @@ async fn do_sign<C: CSCurve>(
 
     // Spec 2.3
     // Optionally, normalize s
-    s.conditional_assign(&(-s), s.is_high());
-    let sig = FullSignature {
-        big_r: presignature.big_r,
-        s,
-    };
+    let sig = FullSignature::normalized(presignature.big_r, s);
     if !sig.verify(&public_key, &msg_hash) {
-        return Err(ProtocolError::AssertionFailed(
-            "signature failed to verify".to_string(),
-        ));
+        return Err(ProtocolError::Failed {
+            round: wait0,
+            fault: Fault::InvalidResult("signature failed to verify"),
+        });
     }
 
     // Spec 2.4
     Ok(sig)
 }
 
+/// Compute our share of a signature, to be combined with [`FullSignature::combine`].
+///
+/// This performs the same computation as [`sign`], without sending any messages.
+/// The participants must be the same ones whose shares will be combined.
+///
+/// **WARNING** The same caveats as [`sign`] apply: the message must be hashed,
+/// and the presignature must never be used again.
 pub fn signature_share<C: CSCurve>(
-    participants: Vec<Participant>,
+    participants: &[Participant],
     me: Participant,
-    // public_key: C::AffinePoint,
     presignature: PresignOutput<C>,
     msg_hash: C::Scalar,
-) -> Result<C::Scalar, ProtocolError> {
-    let p_list = ParticipantList::new(&participants).unwrap();
-    // Spec 1.1
-    let lambda = p_list.lagrange::<C>(me);
-    let k_i = lambda * presignature.k;
+) -> Result<SignatureShare<C>, InitializationError> {
+    let participants = ParticipantList::new(participants).ok_or_else(|| {
+        InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
+    })?;
+    if !participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain this participant".to_string(),
+        ));
+    }
+    Ok(compute_share(&participants, me, &presignature, msg_hash))
+}
 
-    // Spec 1.2
-    let sigma_i = lambda * presignature.sigma;
+async fn do_sign_many<C: CSCurve>(
+    mut chan: SharedChannel,
+    participants: ParticipantList,
+    me: Participant,
+    public_key: C::AffinePoint,
+    presignatures: Vec<PresignOutput<C>>,
+    msg_hashes: Vec<C::Scalar>,
+) -> Result<Vec<FullSignature<C>>, ProtocolError> {
+    let mut s: Vec<C::Scalar> = presignatures
+        .iter()
+        .zip(&msg_hashes)
+        .map(|(presignature, msg_hash)| compute_share(&participants, me, presignature, *msg_hash).s)
+        .collect();
 
-    // Spec 1.3
-    let r = compat::x_coordinate::<C>(&presignature.big_r);
-    let s_i: C::Scalar = msg_hash * k_i + r * sigma_i;
+    let wait0 = chan.next_waitpoint();
+    {
+        let s_i: Vec<ScalarPrimitive<C>> = s.iter().map(|s_i| (*s_i).into()).collect();
+        chan.send_many(wait0, &s_i).await;
+    }
 
-    Ok(s_i)
-}
+    let mut seen = ParticipantCounter::new(&participants);
+    seen.put(me);
+    while !seen.full() {
+        let (from, s_j): (_, Vec<ScalarPrimitive<C>>) = chan.recv(wait0).await?;
+        if !seen.put(from) {
+            continue;
+        }
+        if s_j.len() != s.len() {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait0,
+                fault: Fault::InvalidMessage("wrong number of signature shares"),
+            });
+        }
+        for (s, s_j) in s.iter_mut().zip(s_j) {
+            *s += C::Scalar::from(s_j);
+        }
+    }
 
-pub async fn combine_signature_shares<C: CSCurve>(
-    shares: Vec<C::Scalar>,
-    public_key: C::AffinePoint,
-    // presignature: PresignOutput<C>,
-    presignature_big_r: C::AffinePoint,
-    msg_hash: C::Scalar,
-) -> Result<FullSignature<C>, ProtocolError> {
-    let mut s: C::Scalar = shares[0].into();
-    for s_j in shares.iter().skip(1) {
-        s += C::Scalar::from(*s_j)
+    let mut out = Vec::with_capacity(s.len());
+    for ((presignature, msg_hash), s) in presignatures.iter().zip(&msg_hashes).zip(s) {
+        let sig = FullSignature::normalized(presignature.big_r, s);
+        if !sig.verify(&public_key, msg_hash) {
+            return Err(ProtocolError::Failed {
+                round: wait0,
+                fault: Fault::InvalidResult("signature failed to verify"),
+            });
+        }
+        out.push(sig);
     }
+    Ok(out)
+}
 
-    // Spec 2.3
-    // Optionally, normalize s
-    s.conditional_assign(&(-s), s.is_high());
-    let sig = FullSignature {
-        // big_r: presignature.big_r,
-        big_r: presignature_big_r,
-        s,
+fn check_sign_participants(
+    participants: &[Participant],
+) -> Result<ParticipantList, InitializationError> {
+    if participants.len() < 2 {
+        return Err(InitializationError::BadParameters(format!(
+            "participant count cannot be < 2, found: {}",
+            participants.len()
+        )));
     };
-    if !sig.verify(&public_key, &msg_hash) {
-        return Err(ProtocolError::AssertionFailed(
-            "signature failed to verify".to_string(),
-        ));
-    }
 
-    // Spec 2.4
-    Ok(sig)
+    ParticipantList::new(participants).ok_or_else(|| {
+        InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
+    })
 }
 
 /// The signature protocol, allowing us to use a presignature to sign a message.
@@ -163,16 +368,34 @@ pub fn sign<C: CSCurve>(
     presignature: PresignOutput<C>,
     msg_hash: C::Scalar,
 ) -> Result<impl Protocol<Output = FullSignature<C>>, InitializationError> {
-    if participants.len() < 2 {
-        return Err(InitializationError::BadParameters(format!(
-            "participant count cannot be < 2, found: {}",
-            participants.len()
-        )));
-    };
+    let participants = check_sign_participants(participants)?;
 
-    let participants = ParticipantList::new(participants).ok_or_else(|| {
-        InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
-    })?;
+    let ctx = Context::new();
+    let fut = do_sign(
+        ctx.shared_channel(),
+        participants,
+        me,
+        public_key,
+        presignature,
+        msg_hash,
+        |_: &C::Scalar, _: &[Participant]| true,
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
+/// Like [`sign`], except asking a policy for approval before signing the message.
+///
+/// If the policy denies the message, the protocol fails with [`ProtocolError::Denied`],
+/// without having computed or sent anything.
+pub fn sign_with_policy<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    public_key: C::AffinePoint,
+    presignature: PresignOutput<C>,
+    msg_hash: C::Scalar,
+    policy: impl SigningPolicy<C> + Send + 'static,
+) -> Result<impl Protocol<Output = FullSignature<C>>, InitializationError> {
+    let participants = check_sign_participants(participants)?;
 
     let ctx = Context::new();
     let fut = do_sign(
@@ -182,22 +405,236 @@ pub fn sign<C: CSCurve>(
         public_key,
         presignature,
         msg_hash,
+        policy,
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
+/// Like [`sign`], except using a presignature bound to a message, or a policy.
+///
+/// This fails if the binding doesn't allow signing this message, under this policy.
+/// The policy is only checked for presignatures bound to a policy.
+pub fn sign_bound<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    public_key: C::AffinePoint,
+    presignature: BoundPresignOutput<C>,
+    msg_hash: C::Scalar,
+    policy: Option<&[u8; 32]>,
+) -> Result<impl Protocol<Output = FullSignature<C>>, InitializationError> {
+    let presignature = presignature.unbind(&msg_hash, policy)?;
+    sign(participants, me, public_key, presignature, msg_hash)
+}
+
+/// Like [`signature_share`], except using a presignature bound to a message, or a policy.
+///
+/// See [`sign_bound`].
+pub fn signature_share_bound<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    presignature: BoundPresignOutput<C>,
+    msg_hash: C::Scalar,
+    policy: Option<&[u8; 32]>,
+) -> Result<SignatureShare<C>, InitializationError> {
+    let presignature = presignature.unbind(&msg_hash, policy)?;
+    signature_share(participants, me, presignature, msg_hash)
+}
+
+/// As [`sign`], but signing many messages at once, each with its own presignature.
+///
+/// The message at each position gets signed with the presignature at the same position,
+/// producing the signatures in the same order. This takes a single round, like [`sign`],
+/// so it's much faster than running [`sign`] once per message.
+///
+/// Every participant needs to pass the same presignatures, and the same messages,
+/// in the same order. The same caveats as [`sign`] apply to each message and presignature.
+pub fn sign_many<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    public_key: C::AffinePoint,
+    presignatures: Vec<PresignOutput<C>>,
+    msg_hashes: Vec<C::Scalar>,
+) -> Result<impl Protocol<Output = Vec<FullSignature<C>>>, InitializationError> {
+    let participants = check_sign_participants(participants)?;
+    if presignatures.is_empty() {
+        return Err(InitializationError::BadParameters(
+            "must sign at least one message".to_string(),
+        ));
+    }
+    if presignatures.len() != msg_hashes.len() {
+        return Err(InitializationError::BadParameters(
+            "need exactly one presignature per message".to_string(),
+        ));
+    }
+    let mut ids = HashSet::new();
+    if !presignatures
+        .iter()
+        .all(|presignature| ids.insert(PresignatureId::of(presignature)))
+    {
+        return Err(InitializationError::BadParameters(
+            "cannot use the same presignature twice".to_string(),
+        ));
+    }
+
+    let ctx = Context::new();
+    let fut = do_sign_many(
+        ctx.shared_channel(),
+        participants,
+        me,
+        public_key,
+        presignatures,
+        msg_hashes,
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
+async fn do_sign_tolerant<C: CSCurve>(
+    mut chan: SharedChannel,
+    participants: ParticipantList,
+    me: Participant,
+    threshold: usize,
+    public_key: C::AffinePoint,
+    presignature: PresignOutput<C>,
+    msg_hash: C::Scalar,
+) -> Result<FullSignature<C>, ProtocolError> {
+    // Without a Lagrange coefficient, this is a share of s, as with the presignature.
+    let r = compat::x_coordinate::<C>(&presignature.big_r);
+    let s_i = msg_hash * presignature.k + r * presignature.sigma;
+
+    let wait0 = chan.next_waitpoint();
+    {
+        let s_i: ScalarPrimitive<C> = s_i.into();
+        chan.send_many(wait0, &s_i).await;
+    }
+
+    let mut shares = vec![(me, s_i)];
+    let mut seen = ParticipantCounter::new(&participants);
+    seen.put(me);
+    while shares.len() < threshold {
+        let (from, s_j): (_, ScalarPrimitive<C>) = chan.recv(wait0).await?;
+        if !seen.put(from) {
+            continue;
+        }
+        shares.push((from, s_j.into()));
+    }
+
+    let responders: Vec<_> = shares.iter().map(|(p, _)| *p).collect();
+    // Unwrapping is fine, since the counter only lets each participant in once.
+    let responders = ParticipantList::new(&responders).unwrap();
+    let s = shares
+        .into_iter()
+        .map(|(p, s_j)| responders.lagrange::<C>(p) * s_j)
+        .fold(C::Scalar::ZERO, Add::add);
+
+    let sig = FullSignature::normalized(presignature.big_r, s);
+    if !sig.verify(&public_key, &msg_hash) {
+        return Err(ProtocolError::Failed {
+            round: wait0,
+            fault: Fault::InvalidResult("signature failed to verify"),
+        });
+    }
+    Ok(sig)
+}
+
+/// As [`sign`], but tolerating participants which drop out midway.
+///
+/// The protocol is started with more participants than the threshold, and every
+/// participant finishes as soon as it has the shares of a threshold of them,
+/// including its own, without waiting on the others.
+///
+/// This needs a presignature which any threshold of the participants can use,
+/// like the ones from [`presign_tolerant`](crate::presign_tolerant).
+/// The same caveats as [`sign`] apply.
+pub fn sign_tolerant<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    public_key: C::AffinePoint,
+    presignature: PresignOutput<C>,
+    msg_hash: C::Scalar,
+) -> Result<impl Protocol<Output = FullSignature<C>>, InitializationError> {
+    let participants = check_sign_participants(participants)?;
+    if !participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain this participant".to_string(),
+        ));
+    }
+    if threshold == 0 || threshold > participants.len() {
+        return Err(InitializationError::BadParameters(format!(
+            "threshold must be between 1 and the participant count, found: {}",
+            threshold
+        )));
+    }
+
+    let ctx = Context::new();
+    let fut = do_sign_tolerant(
+        ctx.shared_channel(),
+        participants,
+        me,
+        threshold,
+        public_key,
+        presignature,
+        msg_hash,
     );
     Ok(make_protocol(ctx, fut))
 }
 
+fn record_use<C: CSCurve>(
+    ledger: &mut impl NonceLedger,
+    id: PresignatureId,
+    msg_hash: &C::Scalar,
+) -> Result<(), InitializationError> {
+    ledger
+        .record(id, msg_hash.to_repr().as_ref())
+        .map_err(|e| InitializationError::BadParameters(format!("cannot use presignature: {}", e)))
+}
+
+/// Like [`sign`], except recording the use of the presignature in a ledger first.
+///
+/// This fails if the ledger says the presignature was already used for another message,
+/// even by a previous run of this process. Signing the same message again is allowed.
+pub fn sign_with_ledger<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    public_key: C::AffinePoint,
+    presignature: PresignOutput<C>,
+    msg_hash: C::Scalar,
+    ledger: &mut impl NonceLedger,
+) -> Result<impl Protocol<Output = FullSignature<C>>, InitializationError> {
+    let id = PresignatureId::of(&presignature);
+    let protocol = sign(participants, me, public_key, presignature, msg_hash)?;
+    record_use::<C>(ledger, id, &msg_hash)?;
+    Ok(protocol)
+}
+
+/// Like [`signature_share`], except recording the use of the presignature in a ledger first.
+///
+/// See [`sign_with_ledger`].
+pub fn signature_share_with_ledger<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    presignature: PresignOutput<C>,
+    msg_hash: C::Scalar,
+    ledger: &mut impl NonceLedger,
+) -> Result<SignatureShare<C>, InitializationError> {
+    let id = PresignatureId::of(&presignature);
+    let share = signature_share(participants, me, presignature, msg_hash)?;
+    record_use::<C>(ledger, id, &msg_hash)?;
+    Ok(share)
+}
+
 #[cfg(test)]
 mod test {
     use std::error::Error;
 
     use ecdsa::Signature;
     use k256::{
-        ecdsa::signature::Verifier, ecdsa::VerifyingKey, ProjectivePoint, PublicKey, Scalar,
-        Secp256k1,
+        ecdsa::signature::hazmat::PrehashVerifier, ecdsa::RecoveryId, ecdsa::VerifyingKey,
+        ProjectivePoint, PublicKey, Scalar, Secp256k1,
     };
     use rand_core::OsRng;
 
-    use crate::{compat::scalar_hash, math::Polynomial, protocol::run_protocol};
+    use crate::{compat::scalar_hash, math::Polynomial, protocol::run_protocol, PresignBinding};
 
     use super::*;
 
@@ -233,6 +670,7 @@ mod test {
                     big_r: big_k,
                     k: g.evaluate(&p_scalar),
                     sigma: h.evaluate(&p_scalar),
+                    k_inv: Scalar::ZERO,
                 };
                 let protocol = sign(
                     &participants,
@@ -248,9 +686,439 @@ mod test {
             let sig = result[0].1.clone();
             let sig =
                 Signature::from_scalars(compat::x_coordinate::<Secp256k1>(&sig.big_r), sig.s)?;
-            VerifyingKey::from(&PublicKey::from_affine(public_key).unwrap())
-                .verify(&msg[..], &sig)?;
+            let verifying_key = VerifyingKey::from(&PublicKey::from_affine(public_key).unwrap());
+            let prehash = scalar_hash(msg).to_bytes();
+            verifying_key.verify_prehash(&prehash, &sig)?;
+
+            let recovery_id = RecoveryId::from_byte(result[0].1.recovery_id()).unwrap();
+            let recovered = VerifyingKey::recover_from_prehash(&prehash, &sig, recovery_id)?;
+            assert_eq!(recovered, verifying_key);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_many() -> Result<(), Box<dyn Error>> {
+        let threshold = 2;
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let public_key = (ProjectivePoint::GENERATOR * f.evaluate_zero()).to_affine();
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+
+        let msg_hashes: Vec<_> = [&b"hello?"[..], b"goodbye?", b"again?"]
+            .iter()
+            .map(|msg| scalar_hash(msg))
+            .collect();
+        let mut presignatures = vec![Vec::new(); participants.len()];
+        for _ in &msg_hashes {
+            let g = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+            let k: Scalar = g.evaluate_zero();
+            let big_k = (ProjectivePoint::GENERATOR * k.invert().unwrap()).to_affine();
+            let h = Polynomial::<Secp256k1>::extend_random(
+                &mut OsRng,
+                threshold,
+                &(k * f.evaluate_zero()),
+            );
+            for (p, out) in participants.iter().zip(&mut presignatures) {
+                out.push(PresignOutput::<Secp256k1> {
+                    big_r: big_k,
+                    k: g.evaluate(&p.scalar::<Secp256k1>()),
+                    sigma: h.evaluate(&p.scalar::<Secp256k1>()),
+                    k_inv: Scalar::ZERO,
+                });
+            }
+        }
+
+        let duplicated = vec![presignatures[0][0].clone(), presignatures[0][0].clone()];
+        assert!(sign_many(
+            &participants,
+            participants[0],
+            public_key,
+            duplicated,
+            msg_hashes[..2].to_vec()
+        )
+        .is_err());
+        assert!(sign_many(
+            &participants,
+            participants[0],
+            public_key,
+            presignatures[0].clone(),
+            msg_hashes[..2].to_vec()
+        )
+        .is_err());
+
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = Vec<FullSignature<Secp256k1>>>>,
+        )> = Vec::with_capacity(participants.len());
+        for (&p, presignatures) in participants.iter().zip(presignatures) {
+            let protocol = sign_many(
+                &participants,
+                p,
+                public_key,
+                presignatures,
+                msg_hashes.clone(),
+            )?;
+            protocols.push((p, Box::new(protocol)));
+        }
+        let result = run_protocol(protocols)?;
+        for (_, sigs) in &result {
+            assert_eq!(sigs.len(), msg_hashes.len());
+            for (sig, msg_hash) in sigs.iter().zip(&msg_hashes) {
+                assert!(sig.verify(&public_key, msg_hash));
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_tolerant() -> Result<(), Box<dyn Error>> {
+        let threshold = 3;
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let x = f.evaluate_zero();
+        let public_key = (ProjectivePoint::GENERATOR * x).to_affine();
+        let g = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let k: Scalar = g.evaluate_zero();
+        let big_k = (ProjectivePoint::GENERATOR * k.invert().unwrap()).to_affine();
+        let h = Polynomial::<Secp256k1>::extend_random(&mut OsRng, threshold, &(k * x));
+        let msg_hash = scalar_hash(b"hello?");
+
+        // The first participant never shows up.
+        let participants: Vec<_> = (0..4u32).map(Participant::from).collect();
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = FullSignature<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        for p in &participants[1..] {
+            let p_scalar = p.scalar::<Secp256k1>();
+            let presignature = PresignOutput {
+                big_r: big_k,
+                k: g.evaluate(&p_scalar),
+                sigma: h.evaluate(&p_scalar),
+                k_inv: Scalar::ZERO,
+            };
+            let protocol = sign_tolerant(
+                &participants,
+                *p,
+                threshold,
+                public_key,
+                presignature,
+                msg_hash,
+            )?;
+            protocols.push((*p, Box::new(protocol)));
+        }
+
+        let result = run_protocol(protocols)?;
+        assert_eq!(result.len(), 3);
+        for (_, sig) in &result {
+            assert!(sig.verify(&public_key, &msg_hash));
+        }
+
+        let presignature = PresignOutput::<Secp256k1> {
+            big_r: big_k,
+            k: Scalar::ONE,
+            sigma: Scalar::ONE,
+            k_inv: Scalar::ZERO,
+        };
+        assert!(sign_tolerant(
+            &participants,
+            participants[0],
+            5,
+            public_key,
+            presignature,
+            msg_hash
+        )
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_ecdsa() -> Result<(), Box<dyn Error>> {
+        let x = Scalar::generate_biased(&mut OsRng);
+        let k = Scalar::generate_biased(&mut OsRng);
+        let public_key = (ProjectivePoint::GENERATOR * x).to_affine();
+        let big_r = (ProjectivePoint::GENERATOR * k.invert().unwrap()).to_affine();
+        let msg = b"hello?";
+        let msg_hash = scalar_hash(msg);
+        let r = compat::x_coordinate::<Secp256k1>(&big_r);
+        let sig = FullSignature::<Secp256k1>::normalized(big_r, k * (msg_hash + r * x));
+
+        let verifying_key = VerifyingKey::from_affine(public_key)?;
+        let ecdsa_sig = sig.to_ecdsa()?;
+        verifying_key.verify_prehash(&msg_hash.to_bytes(), &ecdsa_sig)?;
+        let der = sig.to_der()?;
+        verifying_key.verify_prehash(&msg_hash.to_bytes(), &der)?;
+        assert_eq!(Signature::from_der(der.as_bytes())?, ecdsa_sig);
+
+        let zero = FullSignature::<Secp256k1> {
+            big_r,
+            s: Scalar::ZERO,
+        };
+        assert!(zero.to_ecdsa().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_normalized() {
+        let big_r = (ProjectivePoint::GENERATOR * Scalar::generate_biased(&mut OsRng)).to_affine();
+        let s = Scalar::generate_biased(&mut OsRng);
+        for s in [s, -s] {
+            let sig = FullSignature::<Secp256k1>::normalized(big_r, s);
+            assert!(!bool::from(sig.s.is_high()));
+            if bool::from(s.is_high()) {
+                assert_eq!(sig.s, -s);
+                assert_eq!(sig.big_r, (-ProjectivePoint::from(big_r)).to_affine());
+            } else {
+                assert_eq!(sig.s, s);
+                assert_eq!(sig.big_r, big_r);
+            }
         }
+    }
+    #[test]
+    fn test_combine_shares() -> Result<(), Box<dyn Error>> {
+        let threshold = 2;
+        let msg = b"hello?";
+
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let x = f.evaluate_zero();
+        let public_key = (ProjectivePoint::GENERATOR * x).to_affine();
+
+        let g = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let k: Scalar = g.evaluate_zero();
+        let big_k = (ProjectivePoint::GENERATOR * k.invert().unwrap()).to_affine();
+        let h = Polynomial::<Secp256k1>::extend_random(&mut OsRng, threshold, &(k * x));
+
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let mut shares = Vec::new();
+        for p in &participants {
+            let p_scalar = p.scalar::<Secp256k1>();
+            let presignature = PresignOutput::<Secp256k1> {
+                big_r: big_k,
+                k: g.evaluate(&p_scalar),
+                sigma: h.evaluate(&p_scalar),
+                k_inv: Scalar::ZERO,
+            };
+            shares.push(signature_share(
+                &participants,
+                *p,
+                presignature,
+                scalar_hash(msg),
+            )?);
+        }
+
+        let sig = FullSignature::combine(&shares, &public_key, &scalar_hash(msg))?;
+        let sig = Signature::from_scalars(compat::x_coordinate::<Secp256k1>(&sig.big_r), sig.s)?;
+        let verifying_key = VerifyingKey::from(&PublicKey::from_affine(public_key).unwrap());
+        verifying_key.verify_prehash(&scalar_hash(msg).to_bytes(), &sig)?;
+
+        let duplicated = vec![shares[0].clone(), shares[0].clone()];
+        assert!(FullSignature::combine(&duplicated, &public_key, &scalar_hash(msg)).is_err());
+        assert!(FullSignature::combine(&shares[..1], &public_key, &scalar_hash(msg)).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bound_presignature() -> Result<(), Box<dyn Error>> {
+        let threshold = 2;
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let public_key = (ProjectivePoint::GENERATOR * f.evaluate_zero()).to_affine();
+        let g = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let k: Scalar = g.evaluate_zero();
+        let big_k = (ProjectivePoint::GENERATOR * k.invert().unwrap()).to_affine();
+        let h =
+            Polynomial::<Secp256k1>::extend_random(&mut OsRng, threshold, &(k * f.evaluate_zero()));
+
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let presignature = |p: Participant| PresignOutput::<Secp256k1> {
+            big_r: big_k,
+            k: g.evaluate(&p.scalar::<Secp256k1>()),
+            sigma: h.evaluate(&p.scalar::<Secp256k1>()),
+            k_inv: Scalar::ZERO,
+        };
+        let msg_hash = scalar_hash(b"hello?");
+        let other_hash = scalar_hash(b"goodbye?");
+        let policy = [7u8; 32];
+
+        let bound = presignature(participants[0]).bind(PresignBinding::Message(msg_hash));
+        assert_eq!(bound.big_r(), big_k);
+        assert!(signature_share_bound(
+            &participants,
+            participants[0],
+            bound.clone(),
+            other_hash,
+            None
+        )
+        .is_err());
+        assert!(sign_bound(
+            &participants,
+            participants[0],
+            public_key,
+            bound,
+            other_hash,
+            None
+        )
+        .is_err());
+
+        let bound = presignature(participants[1]).bind(PresignBinding::Policy(policy));
+        assert!(signature_share_bound(
+            &participants,
+            participants[1],
+            bound.clone(),
+            msg_hash,
+            Some(&[8u8; 32])
+        )
+        .is_err());
+        assert!(signature_share_bound(
+            &participants,
+            participants[1],
+            bound.clone(),
+            msg_hash,
+            None
+        )
+        .is_err());
+
+        let shares = vec![
+            signature_share_bound(
+                &participants,
+                participants[0],
+                presignature(participants[0]).bind(PresignBinding::Message(msg_hash)),
+                msg_hash,
+                None,
+            )?,
+            signature_share_bound(
+                &participants,
+                participants[1],
+                bound,
+                msg_hash,
+                Some(&policy),
+            )?,
+        ];
+        FullSignature::combine(&shares, &public_key, &msg_hash)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_sign_with_policy() -> Result<(), Box<dyn Error>> {
+        let threshold = 2;
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let public_key = (ProjectivePoint::GENERATOR * f.evaluate_zero()).to_affine();
+        let g = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let k: Scalar = g.evaluate_zero();
+        let big_k = (ProjectivePoint::GENERATOR * k.invert().unwrap()).to_affine();
+        let h =
+            Polynomial::<Secp256k1>::extend_random(&mut OsRng, threshold, &(k * f.evaluate_zero()));
+
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let presignature = |p: Participant| PresignOutput::<Secp256k1> {
+            big_r: big_k,
+            k: g.evaluate(&p.scalar::<Secp256k1>()),
+            sigma: h.evaluate(&p.scalar::<Secp256k1>()),
+            k_inv: Scalar::ZERO,
+        };
+        let allowed = scalar_hash(b"hello?");
+        let policy =
+            move |msg_hash: &Scalar, ps: &[Participant]| *msg_hash == allowed && ps.len() == 2;
+
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = FullSignature<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        for &p in &participants {
+            let protocol = sign_with_policy(
+                &participants,
+                p,
+                public_key,
+                presignature(p),
+                allowed,
+                policy,
+            )?;
+            protocols.push((p, Box::new(protocol)));
+        }
+        let result = run_protocol(protocols)?;
+        assert!(result[0].1.verify(&public_key, &allowed));
+
+        let mut denied = sign_with_policy(
+            &participants,
+            participants[0],
+            public_key,
+            presignature(participants[0]),
+            scalar_hash(b"goodbye?"),
+            policy,
+        )?;
+        assert!(matches!(denied.poke(), Err(ProtocolError::Denied)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_signature_share_with_ledger() -> Result<(), Box<dyn Error>> {
+        use crate::ledger::FileNonceLedger;
+        use rand_core::RngCore;
+
+        let threshold = 2;
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let public_key = (ProjectivePoint::GENERATOR * f.evaluate_zero()).to_affine();
+        let g = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let k: Scalar = g.evaluate_zero();
+        let big_k = (ProjectivePoint::GENERATOR * k.invert().unwrap()).to_affine();
+        let h =
+            Polynomial::<Secp256k1>::extend_random(&mut OsRng, threshold, &(k * f.evaluate_zero()));
+
+        let dir = std::env::temp_dir().join(format!("cait-sith-sign-{:016x}", OsRng.next_u64()));
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let presignature = |p: Participant| {
+            let p_scalar = p.scalar::<Secp256k1>();
+            PresignOutput::<Secp256k1> {
+                big_r: big_k,
+                k: g.evaluate(&p_scalar),
+                sigma: h.evaluate(&p_scalar),
+                k_inv: Scalar::ZERO,
+            }
+        };
+        let mut shares = Vec::new();
+        for p in &participants {
+            let mut ledger = FileNonceLedger::open(dir.join(format!("{:?}", p)))?;
+            shares.push(signature_share_with_ledger(
+                &participants,
+                *p,
+                presignature(*p),
+                scalar_hash(b"hello"),
+                &mut ledger,
+            )?);
+        }
+        FullSignature::combine(&shares, &public_key, &scalar_hash(b"hello"))?;
+
+        // After a restart, the same presignature can be used again only for the same message.
+        let p = participants[0];
+        let mut ledger = FileNonceLedger::open(dir.join(format!("{:?}", p)))?;
+        signature_share_with_ledger(
+            &participants,
+            p,
+            presignature(p),
+            scalar_hash(b"hello"),
+            &mut ledger,
+        )?;
+        assert!(signature_share_with_ledger(
+            &participants,
+            p,
+            presignature(p),
+            scalar_hash(b"goodbye"),
+            &mut ledger,
+        )
+        .is_err());
+        assert!(sign_with_ledger(
+            &participants,
+            p,
+            public_key,
+            presignature(p),
+            scalar_hash(b"goodbye"),
+            &mut ledger,
+        )
+        .is_err());
+
+        std::fs::remove_dir_all(dir)?;
         Ok(())
     }
 }
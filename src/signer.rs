@@ -0,0 +1,537 @@
+//! A single entry point for generating a key, and signing with it.
+//!
+//! Signing with the individual protocols means generating triples, turning pairs of them
+//! into presignatures, and making sure that each triple and presignature only ever gets
+//! used once, on top of running key generation, and routing the messages of all of these.
+//! [`ThresholdSigner`] does all of it, owning the key share, a pool of triples, a
+//! [`PresignaturePool`], and a [`SessionManager`] running the protocols,
+//! and exposing three operations:
+//!
+//! - [`ThresholdSigner::generate_key`], which starts key generation;
+//! - [`ThresholdSigner::maintain`], which starts generating triples and presignatures,
+//!   whenever fewer of them than configured are left;
+//! - [`ThresholdSigner::sign`], which signs a message hash, with the next presignature.
+//!
+//! Like the protocols themselves, the signer doesn't do any networking: it needs
+//! to be poked, and passed the messages of the other participants, until it has
+//! something to report, like a finished signature.
+//!
+//! Every participant needs to call these operations in the same order, since each
+//! of them starts sessions, whose ids come from counting the operations of each kind.
+//! The triples and presignatures of each session are then used in the order
+//! of the sessions, so that every participant picks the same presignature for
+//! each signature. Since participants can finish sessions at different times,
+//! signing can fail for lack of a presignature on some participants, when others
+//! already have one; this just needs to be retried, on all participants, once
+//! [`ThresholdSigner::presignatures_available`] is non-zero everywhere.
+//!
+//! This keeps everything in memory. Nodes needing to persist triples, or presignatures,
+//! across restarts, should use the individual protocols, along with a
+//! [`TripleStore`](crate::triples::TripleStore), and a [`NonceLedger`](crate::NonceLedger).
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use crate::{
+    compat::CSCurve,
+    keygen,
+    participants::ParticipantList,
+    presign_many,
+    protocol::{
+        InitializationError, MessageData, Participant, ProtocolError, SessionAction, SessionId,
+        SessionManager,
+    },
+    sign,
+    triples::{generate_triple, StoredTriple},
+    FullSignature, KeygenOutput, PresignArguments, PresignOutput, PresignaturePool,
+};
+
+/// How many triples and presignatures a [`ThresholdSigner`] keeps around.
+#[derive(Debug, Clone, Copy)]
+pub struct SignerConfig {
+    /// Start generating triples whenever fewer than this many are available, or being generated.
+    ///
+    /// This needs to be at least twice the size of a batch of presignatures.
+    pub triple_watermark: usize,
+    /// Start presigning whenever fewer than this many presignatures are available, or being created.
+    pub presignature_watermark: usize,
+    /// The number of presignatures created at once, each using two triples.
+    pub presignature_batch: usize,
+    /// Distinguishes the sessions of this signer from those of earlier signers,
+    /// with the same participants, which should have used a different epoch.
+    pub epoch: u64,
+}
+
+impl Default for SignerConfig {
+    fn default() -> Self {
+        Self {
+            triple_watermark: 16,
+            presignature_watermark: 4,
+            presignature_batch: 4,
+            epoch: 0,
+        }
+    }
+}
+
+/// Something a [`ThresholdSigner`] wants done, or wants to report.
+pub enum SignerAction<C: CSCurve> {
+    /// Nothing can make progress without more messages.
+    Wait,
+    /// Send a message to every other participant.
+    SendMany(MessageData),
+    /// Send a message to a specific participant.
+    SendPrivate(Participant, MessageData),
+    /// Key generation finished, producing this public key.
+    KeyGenerated(C::AffinePoint),
+    /// A signing session finished, with a valid signature.
+    Signed(SessionId, FullSignature<C>),
+    /// A session failed.
+    ///
+    /// The presignature used by a failed signature is gone, so signing the
+    /// message again means calling [`ThresholdSigner::sign`] again.
+    Failed(SessionId, ProtocolError),
+}
+
+const KEYGEN: &[u8] = b"signer keygen";
+const TRIPLE: &[u8] = b"signer triple";
+const PRESIGN: &[u8] = b"signer presign";
+const SIGN: &[u8] = b"signer sign";
+
+/// What each session of the signer is doing.
+#[derive(Debug, Clone, Copy)]
+enum Job {
+    Keygen,
+    Triple(u64),
+    Presign(u64),
+    Sign,
+}
+
+#[allow(clippy::large_enum_variant)]
+enum Output<C: CSCurve> {
+    Key(KeygenOutput<C>),
+    Triple(StoredTriple<C>),
+    Presignatures(Vec<PresignOutput<C>>),
+    Signature(FullSignature<C>),
+}
+
+/// A participant in a threshold key, generating it, and signing with it.
+pub struct ThresholdSigner<C: CSCurve> {
+    participants: Vec<Participant>,
+    me: Participant,
+    threshold: usize,
+    config: SignerConfig,
+    key: Option<KeygenOutput<C>>,
+    manager: SessionManager<Output<C>>,
+    jobs: HashMap<SessionId, Job>,
+    keygen_started: bool,
+    /// Triples which finished out of order, or failed, by session.
+    finished_triples: BTreeMap<u64, Option<StoredTriple<C>>>,
+    next_triple: u64,
+    triples: VecDeque<StoredTriple<C>>,
+    /// Batches of presignatures which finished out of order, or failed, by session.
+    finished_presignatures: BTreeMap<u64, Vec<PresignOutput<C>>>,
+    next_presignature: u64,
+    presignatures: PresignaturePool<C>,
+    triples_started: u64,
+    presigns_started: u64,
+    signs_started: u64,
+    /// The triples available or being generated, counting failures as soon as we see them.
+    triples_expected: usize,
+    /// Ditto, for presignatures.
+    presignatures_expected: usize,
+}
+
+impl<C: CSCurve> ThresholdSigner<C> {
+    /// Create a signer for `me`, amongst some participants, without a key yet.
+    ///
+    /// Any `threshold` of the participants can later sign, although signing
+    /// with this signer needs all of them.
+    pub fn new(
+        participants: &[Participant],
+        me: Participant,
+        threshold: usize,
+        config: SignerConfig,
+    ) -> Result<Self, InitializationError> {
+        if participants.len() < 2 {
+            return Err(InitializationError::BadParameters(format!(
+                "participant count cannot be < 2, found: {}",
+                participants.len()
+            )));
+        }
+        if threshold == 0 || threshold > participants.len() {
+            return Err(InitializationError::BadParameters(
+                "threshold must be between 1 and the participant count".to_string(),
+            ));
+        }
+        if config.presignature_batch == 0 {
+            return Err(InitializationError::BadParameters(
+                "presignature batch size must be at least 1".to_string(),
+            ));
+        }
+        if config.triple_watermark < 2 * config.presignature_batch {
+            return Err(InitializationError::BadParameters(
+                "triple watermark must allow for a batch of presignatures".to_string(),
+            ));
+        }
+        if ParticipantList::new(participants).is_none() {
+            return Err(InitializationError::BadParameters(
+                "participant list cannot contain duplicates".to_string(),
+            ));
+        }
+        if !participants.contains(&me) {
+            return Err(InitializationError::BadParameters(
+                "participant list must contain this participant".to_string(),
+            ));
+        }
+        Ok(Self {
+            participants: participants.to_vec(),
+            me,
+            threshold,
+            config,
+            key: None,
+            manager: SessionManager::new(me),
+            jobs: HashMap::new(),
+            keygen_started: false,
+            finished_triples: BTreeMap::new(),
+            next_triple: 0,
+            triples: VecDeque::new(),
+            finished_presignatures: BTreeMap::new(),
+            next_presignature: 0,
+            presignatures: PresignaturePool::new(0, config.presignature_batch)?,
+            triples_started: 0,
+            presigns_started: 0,
+            signs_started: 0,
+            triples_expected: 0,
+            presignatures_expected: 0,
+        })
+    }
+
+    /// Create a signer for `me`, with an existing key share.
+    pub fn with_key(
+        participants: &[Participant],
+        me: Participant,
+        threshold: usize,
+        key: KeygenOutput<C>,
+        config: SignerConfig,
+    ) -> Result<Self, InitializationError> {
+        let mut signer = Self::new(participants, me, threshold, config)?;
+        signer.key = Some(key);
+        signer.keygen_started = true;
+        Ok(signer)
+    }
+
+    /// Our share of the key, once there is one.
+    pub fn key(&self) -> Option<&KeygenOutput<C>> {
+        self.key.as_ref()
+    }
+
+    /// The number of triples ready to be used for presigning.
+    pub fn triples_available(&self) -> usize {
+        self.triples.len()
+    }
+
+    /// The number of presignatures ready to be used for signing.
+    pub fn presignatures_available(&self) -> usize {
+        self.presignatures.len()
+    }
+
+    fn session_id(&self, purpose: &[u8], counter: u64) -> SessionId {
+        SessionId::derive(purpose, &self.participants, counter, self.config.epoch)
+    }
+
+    /// Start generating the key.
+    ///
+    /// This fails if there already is a key, or if its generation already started.
+    pub fn generate_key(&mut self) -> Result<SessionId, InitializationError> {
+        if self.keygen_started {
+            return Err(InitializationError::BadParameters(
+                "key generation already started".to_string(),
+            ));
+        }
+        let id = self.session_id(KEYGEN, 0);
+        let protocol = keygen::<C>(&self.participants, self.me, self.threshold)?;
+        self.manager.add_map(id, protocol, Output::Key)?;
+        self.jobs.insert(id, Job::Keygen);
+        self.keygen_started = true;
+        Ok(id)
+    }
+
+    /// Start generating triples, and presignatures, if fewer than configured are left.
+    ///
+    /// Triples get generated even without a key, but presignatures need the key,
+    /// and enough triples to be available. This should be called regularly, on every
+    /// participant, for example after each signature.
+    pub fn maintain(&mut self) -> Result<(), InitializationError> {
+        self.maintain_presignatures()?;
+        while self.triples_expected < self.config.triple_watermark {
+            let id = self.session_id(TRIPLE, self.triples_started);
+            let protocol = generate_triple::<C>(&self.participants, self.me, self.threshold)?;
+            self.manager.add_map(id, protocol, Output::Triple)?;
+            self.jobs.insert(id, Job::Triple(self.triples_started));
+            self.triples_started += 1;
+            self.triples_expected += 1;
+        }
+        Ok(())
+    }
+
+    fn maintain_presignatures(&mut self) -> Result<(), InitializationError> {
+        let batch = self.config.presignature_batch;
+        let Some(key) = &self.key else {
+            return Ok(());
+        };
+        if self.presignatures_expected >= self.config.presignature_watermark
+            || self.triples.len() < 2 * batch
+        {
+            return Ok(());
+        }
+        let args = (0..batch)
+            .map(|_| PresignArguments {
+                triple0: self.triples.pop_front().expect("enough triples"),
+                triple1: self.triples.pop_front().expect("enough triples"),
+                keygen_out: key.clone(),
+                threshold: self.threshold,
+            })
+            .collect();
+        self.triples_expected -= 2 * batch;
+        let id = self.session_id(PRESIGN, self.presigns_started);
+        let protocol = presign_many(
+            &self.participants,
+            self.me,
+            &self.participants,
+            self.me,
+            args,
+        )?;
+        self.manager.add_map(id, protocol, Output::Presignatures)?;
+        self.jobs.insert(id, Job::Presign(self.presigns_started));
+        self.presigns_started += 1;
+        self.presignatures_expected += batch;
+        Ok(())
+    }
+
+    /// Start signing a message hash, with the next presignature.
+    ///
+    /// This fails if there's no key yet, or no presignature available.
+    ///
+    /// **WARNING** As with [`sign`](crate::sign), the message must be hashed first.
+    pub fn sign(&mut self, msg_hash: C::Scalar) -> Result<SessionId, InitializationError> {
+        let Some(key) = &self.key else {
+            return Err(InitializationError::BadParameters(
+                "there is no key to sign with yet".to_string(),
+            ));
+        };
+        let public_key = key.public_key;
+        let Some((_, presignature)) = self.presignatures.take_next() else {
+            return Err(InitializationError::BadParameters(
+                "no presignature available".to_string(),
+            ));
+        };
+        self.presignatures_expected -= 1;
+        let id = self.session_id(SIGN, self.signs_started);
+        let protocol = sign(
+            &self.participants,
+            self.me,
+            public_key,
+            presignature,
+            msg_hash,
+        )?;
+        self.manager.add_map(id, protocol, Output::Signature)?;
+        self.jobs.insert(id, Job::Sign);
+        self.signs_started += 1;
+        Ok(id)
+    }
+
+    /// Record the outcome of a session, returning what to report, if anything.
+    fn finish(
+        &mut self,
+        id: SessionId,
+        result: Result<Output<C>, ProtocolError>,
+    ) -> Option<SignerAction<C>> {
+        let job = self.jobs.remove(&id)?;
+        match (job, result) {
+            (Job::Keygen, Ok(Output::Key(key))) => {
+                let public_key = key.public_key;
+                self.key = Some(key);
+                return Some(SignerAction::KeyGenerated(public_key));
+            }
+            (Job::Keygen, Err(e)) => {
+                self.keygen_started = false;
+                return Some(SignerAction::Failed(id, e));
+            }
+            (Job::Triple(i), result) => {
+                let triple = match result {
+                    Ok(Output::Triple(triple)) => Some(triple),
+                    _ => None,
+                };
+                if triple.is_none() {
+                    self.triples_expected -= 1;
+                }
+                self.finished_triples.insert(i, triple);
+                while let Some(triple) = self.finished_triples.remove(&self.next_triple) {
+                    self.triples.extend(triple);
+                    self.next_triple += 1;
+                }
+            }
+            (Job::Presign(i), result) => {
+                let presignatures = match result {
+                    Ok(Output::Presignatures(presignatures)) => presignatures,
+                    _ => {
+                        self.presignatures_expected -= self.config.presignature_batch;
+                        Vec::new()
+                    }
+                };
+                self.finished_presignatures.insert(i, presignatures);
+                while let Some(presignatures) =
+                    self.finished_presignatures.remove(&self.next_presignature)
+                {
+                    for presignature in presignatures {
+                        // A fresh presignature can only collide with an existing one with negligible probability.
+                        if self.presignatures.insert(presignature).is_err() {
+                            self.presignatures_expected -= 1;
+                        }
+                    }
+                    self.next_presignature += 1;
+                }
+            }
+            (Job::Sign, Ok(Output::Signature(sig))) => return Some(SignerAction::Signed(id, sig)),
+            (_, Err(e)) => return Some(SignerAction::Failed(id, e)),
+            (_, Ok(_)) => unreachable!("sessions produce the output of their kind"),
+        }
+        None
+    }
+
+    /// Poke the sessions, until one of them has something to do, or to report.
+    ///
+    /// Finished triples and presignatures are added to the pools, without being reported,
+    /// while failures are always reported, even for triples and presignatures.
+    pub fn poke(&mut self) -> SignerAction<C> {
+        loop {
+            let (id, result) = match self.manager.poke() {
+                SessionAction::Wait => return SignerAction::Wait,
+                SessionAction::SendMany(data) => return SignerAction::SendMany(data),
+                SessionAction::SendPrivate(to, data) => return SignerAction::SendPrivate(to, data),
+                SessionAction::Return(id, output) => (id, Ok(output)),
+                SessionAction::Failed(id, e) => (id, Err(e)),
+            };
+            if let Some(action) = self.finish(id, result) {
+                return action;
+            }
+        }
+    }
+
+    /// Receive a message from another participant.
+    pub fn message(&mut self, from: Participant, data: MessageData) {
+        self.manager.message(from, data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use k256::{ProjectivePoint, Secp256k1};
+
+    use super::*;
+    use crate::compat::scalar_hash;
+
+    /// Drive every signer until none of them can make progress, collecting what they report.
+    #[allow(clippy::type_complexity)]
+    fn drive(
+        signers: &mut [(Participant, ThresholdSigner<Secp256k1>)],
+    ) -> Vec<Vec<SignerAction<Secp256k1>>> {
+        let mut reports: Vec<Vec<_>> = signers.iter().map(|_| Vec::new()).collect();
+        let mut progress = true;
+        while progress {
+            progress = false;
+            for i in 0..signers.len() {
+                loop {
+                    let from = signers[i].0;
+                    match signers[i].1.poke() {
+                        SignerAction::Wait => break,
+                        SignerAction::SendMany(data) => {
+                            for (j, (_, signer)) in signers.iter_mut().enumerate() {
+                                if j != i {
+                                    signer.message(from, data.clone());
+                                }
+                            }
+                        }
+                        SignerAction::SendPrivate(to, data) => {
+                            let j = signers.iter().position(|(p, _)| *p == to).unwrap();
+                            signers[j].1.message(from, data);
+                        }
+                        SignerAction::Failed(id, e) => panic!("session {} failed: {}", id, e),
+                        report => reports[i].push(report),
+                    }
+                    progress = true;
+                }
+            }
+        }
+        reports
+    }
+
+    #[test]
+    fn test_threshold_signer() -> Result<(), Box<dyn std::error::Error>> {
+        let participants: Vec<_> = (0..3u32).map(Participant::from).collect();
+        let config = SignerConfig {
+            triple_watermark: 2,
+            presignature_watermark: 1,
+            presignature_batch: 1,
+            epoch: 0,
+        };
+        let mut signers = participants
+            .iter()
+            .map(|&p| Ok((p, ThresholdSigner::new(&participants, p, 2, config)?)))
+            .collect::<Result<Vec<_>, InitializationError>>()?;
+
+        for (_, signer) in &mut signers {
+            signer.generate_key()?;
+            assert!(signer.generate_key().is_err());
+            // Triples don't need the key.
+            signer.maintain()?;
+        }
+        let reports = drive(&mut signers);
+        let public_key = signers[0].1.key().unwrap().public_key;
+        for ((_, signer), reports) in signers.iter().zip(&reports) {
+            assert!(matches!(
+                reports[..],
+                [SignerAction::KeyGenerated(pk)] if pk == public_key
+            ));
+            assert_eq!(signer.triples_available(), 2);
+            assert_eq!(signer.presignatures_available(), 0);
+        }
+
+        let p_list = ParticipantList::new(&participants[..2]).unwrap();
+        let x = p_list.lagrange::<Secp256k1>(participants[0])
+            * signers[0].1.key().unwrap().private_share
+            + p_list.lagrange::<Secp256k1>(participants[1])
+                * signers[1].1.key().unwrap().private_share;
+        assert_eq!(ProjectivePoint::GENERATOR * x, public_key);
+
+        let msg_hash = scalar_hash(b"hello?");
+        assert!(signers[0].1.sign(msg_hash).is_err());
+
+        // Presigning uses up the triples, which get replaced.
+        for (_, signer) in &mut signers {
+            signer.maintain()?;
+            assert_eq!(signer.triples_available(), 0);
+        }
+        drive(&mut signers);
+        for (_, signer) in &signers {
+            assert_eq!(signer.presignatures_available(), 1);
+            assert_eq!(signer.triples_available(), 2);
+        }
+
+        let mut ids = Vec::new();
+        for (_, signer) in &mut signers {
+            ids.push(signer.sign(msg_hash)?);
+        }
+        let reports = drive(&mut signers);
+        for reports in &reports {
+            let [SignerAction::Signed(id, sig)] = &reports[..] else {
+                panic!("expected a single signature");
+            };
+            assert_eq!(*id, ids[0]);
+            assert!(sig.verify(&public_key, &msg_hash));
+        }
+        for (_, signer) in &mut signers {
+            assert_eq!(signer.presignatures_available(), 0);
+            assert!(signer.sign(msg_hash).is_err());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+//! Montgomery arithmetic modulo a 256 bit prime, shared by the base field and the scalar field.
+use elliptic_curve::bigint::{modular::montgomery_reduction, Limb, Word, U256};
+
+/// The limbs of an element, in Montgomery form.
+pub(super) type Words = [Word; U256::LIMBS];
+
+/// A modulus, along with the constants needed for Montgomery arithmetic.
+pub(super) struct Modulus {
+    pub value: U256,
+    /// R^2 mod m, where R = 2^256.
+    r2: U256,
+    /// -m^-1 mod 2^w, where w is the size of a limb.
+    neg_inv: Limb,
+}
+
+impl Modulus {
+    pub const fn new(value: U256) -> Self {
+        let r = U256::MAX.const_rem(&value).0.wrapping_add(&U256::ONE);
+        let r2 = U256::const_rem_wide(r.square_wide(), &value).0;
+        let neg_inv = Limb(
+            Word::MIN.wrapping_sub(value.inv_mod2k_vartime(Word::BITS as usize).as_limbs()[0].0),
+        );
+        Self { value, r2, neg_inv }
+    }
+
+    pub const fn add(&self, a: &Words, b: &Words) -> Words {
+        U256::from_words(*a)
+            .add_mod(&U256::from_words(*b), &self.value)
+            .to_words()
+    }
+
+    pub const fn sub(&self, a: &Words, b: &Words) -> Words {
+        U256::from_words(*a)
+            .sub_mod(&U256::from_words(*b), &self.value)
+            .to_words()
+    }
+
+    pub const fn neg(&self, a: &Words) -> Words {
+        U256::from_words(*a).neg_mod(&self.value).to_words()
+    }
+
+    pub const fn mul(&self, a: &Words, b: &Words) -> Words {
+        let wide = U256::from_words(*a).mul_wide(&U256::from_words(*b));
+        montgomery_reduction(&wide, &self.value, self.neg_inv).to_words()
+    }
+
+    pub const fn to_montgomery(&self, a: &Words) -> Words {
+        self.mul(a, self.r2.as_words())
+    }
+
+    pub const fn out_of_montgomery(&self, a: &Words) -> Words {
+        montgomery_reduction(
+            &(U256::from_words(*a), U256::ZERO),
+            &self.value,
+            self.neg_inv,
+        )
+        .to_words()
+    }
+}
@@ -0,0 +1,188 @@
+//! Arithmetic modulo p = 2^251 + 17 * 2^192 + 1, the base field of the Stark curve.
+// The field element macro from primeorder uses generic-array, which is deprecated in favor of
+// its next major version, which these crates don't use yet.
+#![allow(clippy::assign_op_pattern, clippy::op_ref, deprecated)]
+
+use core::{
+    fmt::{self, Debug},
+    iter::{Product, Sum},
+    ops::{AddAssign, MulAssign, Neg, SubAssign},
+};
+
+use elliptic_curve::{
+    bigint::U256,
+    ff::{helpers::sqrt_tonelli_shanks, PrimeField},
+    subtle::{Choice, ConstantTimeEq, CtOption},
+};
+
+use super::{
+    arithmetic::{Modulus, Words},
+    FieldBytes, Stark,
+};
+
+const MODULUS_HEX: &str = "0800000000000011000000000000000000000000000000000000000000000001";
+
+const MODULUS: U256 = U256::from_be_hex(MODULUS_HEX);
+
+const P: Modulus = Modulus::new(MODULUS);
+
+/// p - 2, for inverting with Fermat's little theorem.
+const P_MINUS_2: [u64; 4] = [
+    0xffffffffffffffff,
+    0xffffffffffffffff,
+    0xffffffffffffffff,
+    0x0800000000000010,
+];
+
+/// (t - 1) / 2, where p - 1 = 2^S * t, for computing square roots.
+const T_MINUS_1_OVER_2: [u64; 4] = [0x0400000000000008, 0, 0, 0];
+
+const fn fe_add(a: &Words, b: &Words) -> Words {
+    P.add(a, b)
+}
+
+const fn fe_sub(a: &Words, b: &Words) -> Words {
+    P.sub(a, b)
+}
+
+const fn fe_neg(a: &Words) -> Words {
+    P.neg(a)
+}
+
+const fn fe_mul(a: &Words, b: &Words) -> Words {
+    P.mul(a, b)
+}
+
+const fn fe_square(a: &Words) -> Words {
+    P.mul(a, a)
+}
+
+const fn fe_to_montgomery(a: &Words) -> Words {
+    P.to_montgomery(a)
+}
+
+const fn fe_from_montgomery(a: &Words) -> Words {
+    P.out_of_montgomery(a)
+}
+
+/// An element of the base field of the Stark curve.
+///
+/// Elements are kept in Montgomery form.
+#[derive(Clone, Copy)]
+pub struct FieldElement(U256);
+
+primeorder::impl_mont_field_element!(
+    Stark,
+    FieldElement,
+    FieldBytes,
+    U256,
+    MODULUS,
+    Words,
+    fe_from_montgomery,
+    fe_to_montgomery,
+    fe_add,
+    fe_sub,
+    fe_mul,
+    fe_neg,
+    fe_square
+);
+
+impl FieldElement {
+    /// Returns the multiplicative inverse of self, if self is non-zero.
+    pub fn invert(&self) -> CtOption<Self> {
+        CtOption::new(self.invert_unchecked(), !self.is_zero())
+    }
+
+    const fn invert_unchecked(&self) -> Self {
+        self.pow_vartime(&P_MINUS_2)
+    }
+
+    /// Returns the square root of self, if it exists.
+    pub fn sqrt(&self) -> CtOption<Self> {
+        sqrt_tonelli_shanks(self, T_MINUS_1_OVER_2)
+    }
+}
+
+impl PrimeField for FieldElement {
+    type Repr = FieldBytes;
+
+    const MODULUS: &'static str = MODULUS_HEX;
+    const NUM_BITS: u32 = 252;
+    const CAPACITY: u32 = 251;
+    const TWO_INV: Self = Self::from_u64(2).invert_unchecked();
+    const MULTIPLICATIVE_GENERATOR: Self = Self::from_u64(3);
+    const S: u32 = 192;
+    const ROOT_OF_UNITY: Self =
+        Self::from_hex("005282db87529cfa3f0464519c8b0fa5ad187148e11a61616070024f42f8ef94");
+    const ROOT_OF_UNITY_INV: Self = Self::ROOT_OF_UNITY.invert_unchecked();
+    const DELTA: Self =
+        Self::from_hex("00f66926e0ff773515a10661aa1f4d7e5cd6a000cc515a33cea96fa9c5262e50");
+
+    fn from_repr(bytes: FieldBytes) -> CtOption<Self> {
+        Self::from_bytes(&bytes)
+    }
+
+    fn to_repr(&self) -> FieldBytes {
+        self.to_bytes()
+    }
+
+    fn is_odd(&self) -> Choice {
+        self.is_odd()
+    }
+}
+
+impl Debug for FieldElement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FieldElement(0x{:X})", &self.to_canonical())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use elliptic_curve::Field;
+    use rand_core::OsRng;
+
+    use super::*;
+
+    #[test]
+    fn test_field_arithmetic() {
+        let two = FieldElement::from_u64(2);
+        assert_eq!(two * FieldElement::TWO_INV, FieldElement::ONE);
+        assert_eq!(-FieldElement::ONE + FieldElement::ONE, FieldElement::ZERO);
+        assert_eq!(
+            FieldElement::from_hex(MODULUS_HEX),
+            FieldElement::ZERO,
+            "the modulus should reduce to zero"
+        );
+
+        for _ in 0..16 {
+            let x = FieldElement::random(&mut OsRng);
+            assert_eq!(FieldElement::from_repr(x.to_repr()).unwrap(), x);
+            assert_eq!(x * x.invert().unwrap(), FieldElement::ONE);
+            let root = x.square().sqrt().unwrap();
+            assert!(root == x || root == -x);
+        }
+        assert!(bool::from(
+            FieldElement::MULTIPLICATIVE_GENERATOR.sqrt().is_none()
+        ));
+    }
+
+    #[test]
+    fn test_field_constants() {
+        // The root of unity has order exactly 2^S.
+        let mut x = FieldElement::ROOT_OF_UNITY;
+        for _ in 0..FieldElement::S - 1 {
+            x = x.square();
+        }
+        assert_eq!(x, -FieldElement::ONE);
+        assert_eq!(
+            FieldElement::ROOT_OF_UNITY * FieldElement::ROOT_OF_UNITY_INV,
+            FieldElement::ONE
+        );
+        let mut delta = FieldElement::MULTIPLICATIVE_GENERATOR;
+        for _ in 0..FieldElement::S {
+            delta = delta.square();
+        }
+        assert_eq!(delta, FieldElement::DELTA);
+    }
+}
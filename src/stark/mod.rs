@@ -0,0 +1,126 @@
+//! This module provides the Stark curve, used by Starknet.
+//!
+//! This is the curve `y^2 = x^3 + x + b` over the field of size `p = 2^251 + 17 * 2^192 + 1`,
+//! whose points form a group of prime order. Starknet accounts commonly check ECDSA
+//! signatures over this curve, so implementing [`CSCurve`](crate::CSCurve) for it lets
+//! keys generated by this library control such accounts, with the usual signing protocol.
+//!
+//! Starknet signs message hashes which are field elements below `2^251`. These are also
+//! below the order of the curve, so they can be turned into a [`Scalar`] directly, with
+//! [`PrimeField::from_repr`](elliptic_curve::ff::PrimeField::from_repr).
+//!
+//! None of the RustCrypto crates implement this curve, so the arithmetic lives here.
+//! Points use the generic formulas from `primeorder`, and field elements use generic
+//! Montgomery arithmetic, which is constant time, but slower than a dedicated implementation.
+mod arithmetic;
+mod field;
+mod scalar;
+
+use ecdsa::hazmat::VerifyPrimitive;
+use elliptic_curve::{
+    bigint::U256, consts::U32, Curve, CurveArithmetic, FieldBytesEncoding, PrimeCurve,
+    PrimeCurveArithmetic,
+};
+use primeorder::{point_arithmetic, PrimeCurveParams};
+
+pub use field::FieldElement;
+pub use scalar::Scalar;
+
+/// The Stark curve.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, PartialOrd, Ord)]
+pub struct Stark;
+
+/// The encoding of a field element, or of a scalar.
+pub type FieldBytes = elliptic_curve::FieldBytes<Stark>;
+
+/// A point on the Stark curve, in affine coordinates.
+pub type AffinePoint = primeorder::AffinePoint<Stark>;
+
+/// A point on the Stark curve, in projective coordinates.
+pub type ProjectivePoint = primeorder::ProjectivePoint<Stark>;
+
+impl Curve for Stark {
+    type FieldBytesSize = U32;
+    type Uint = U256;
+
+    const ORDER: U256 = U256::from_be_hex(scalar::ORDER_HEX);
+}
+
+impl PrimeCurve for Stark {}
+
+impl FieldBytesEncoding<Stark> for U256 {}
+
+impl CurveArithmetic for Stark {
+    type AffinePoint = AffinePoint;
+    type ProjectivePoint = ProjectivePoint;
+    type Scalar = Scalar;
+}
+
+impl PrimeCurveArithmetic for Stark {
+    type CurveGroup = ProjectivePoint;
+}
+
+impl PrimeCurveParams for Stark {
+    type FieldElement = FieldElement;
+    type PointArithmetic = point_arithmetic::EquationAIsGeneric;
+
+    const EQUATION_A: FieldElement = FieldElement::ONE;
+
+    const EQUATION_B: FieldElement =
+        FieldElement::from_hex("06f21413efbe40de150e596d72f7a8c5609ad26c15c915c1f4cdfcb99cee9e89");
+
+    const GENERATOR: (FieldElement, FieldElement) = (
+        FieldElement::from_hex("01ef15c18599971b7beced415a40f0c7deacfd9b0d1819e03d723d8bc943cfca"),
+        FieldElement::from_hex("005668060aa49730b7be4801df46ec62de53ecd11abe43a32873000c36e8dc1f"),
+    );
+}
+
+impl VerifyPrimitive<Stark> for AffinePoint {}
+
+#[cfg(test)]
+mod test {
+    use elliptic_curve::{
+        bigint::ArrayEncoding, ff::PrimeField, point::AffineCoordinates, sec1::ToEncodedPoint,
+        Field, Group,
+    };
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::serde::{decode, encode};
+
+    #[test]
+    fn test_generator() {
+        let g = ProjectivePoint::generator();
+        assert!(bool::from((g * -Scalar::ONE + g).is_identity()));
+
+        // Computed independently, with affine formulas.
+        let k = Scalar::from_repr(
+            U256::from_be_hex("0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef")
+                .to_be_byte_array(),
+        )
+        .unwrap();
+        let point = (g * k).to_affine();
+        let encoded = point.to_encoded_point(false);
+        assert_eq!(
+            encoded.x().unwrap(),
+            &U256::from_be_hex("01b4fc4a44546eecebc2339b6e6c5ddcbd3f3cdbc5fa23eab2bd800d831a1b2d")
+                .to_be_byte_array()
+        );
+        assert_eq!(
+            encoded.y().unwrap(),
+            &U256::from_be_hex("05edb8ee53a04b17251184549a66cb25809e92437785a75796f0b36b3befee98")
+                .to_be_byte_array()
+        );
+        assert_eq!(point.x(), *encoded.x().unwrap());
+    }
+
+    #[test]
+    fn test_point_serialization() -> Result<(), Box<dyn std::error::Error>> {
+        for _ in 0..8 {
+            let point = (ProjectivePoint::generator() * Scalar::random(&mut OsRng)).to_affine();
+            let decoded: AffinePoint = decode(&encode(&point))?;
+            assert_eq!(decoded, point);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,279 @@
+//! Arithmetic modulo the order of the Stark curve.
+// The field element macro from primeorder uses generic-array, which is deprecated in favor of
+// its next major version, which these crates don't use yet.
+#![allow(clippy::assign_op_pattern, clippy::op_ref, deprecated)]
+
+use core::{
+    cmp::Ordering,
+    fmt::{self, Debug},
+    iter::{Product, Sum},
+    ops::{AddAssign, MulAssign, Neg, ShrAssign, SubAssign},
+};
+
+use elliptic_curve::{
+    bigint::{ArrayEncoding, U256, U512},
+    consts::U64,
+    ff::PrimeField,
+    generic_array::GenericArray,
+    ops::{Invert, Reduce},
+    scalar::{FromUintUnchecked, IsHigh},
+    subtle::{Choice, ConstantTimeEq, ConstantTimeGreater, CtOption},
+    ScalarPrimitive,
+};
+
+use super::{
+    arithmetic::{Modulus, Words},
+    FieldBytes, Stark,
+};
+
+pub(super) const ORDER_HEX: &str =
+    "0800000000000010ffffffffffffffffb781126dcae7b2321e66a241adc64d2f";
+
+const ORDER: U256 = U256::from_be_hex(ORDER_HEX);
+
+const N: Modulus = Modulus::new(ORDER);
+
+/// (n - 1) / 2, above which scalars are considered high.
+const FRAC_ORDER_2: U256 =
+    U256::from_be_hex("04000000000000087fffffffffffffffdbc08936e573d9190f335120d6e32697");
+
+/// n - 2, for inverting with Fermat's little theorem.
+const N_MINUS_2: [u64; 4] = [
+    0x1e66a241adc64d2d,
+    0xb781126dcae7b232,
+    0xffffffffffffffff,
+    0x0800000000000010,
+];
+
+/// (n + 1) / 4, for computing square roots, since n = 3 mod 4.
+const N_PLUS_1_OVER_4: [u64; 4] = [
+    0x8799a8906b71934c,
+    0xede0449b72b9ec8c,
+    0x3fffffffffffffff,
+    0x0200000000000004,
+];
+
+const fn sc_add(a: &Words, b: &Words) -> Words {
+    N.add(a, b)
+}
+
+const fn sc_sub(a: &Words, b: &Words) -> Words {
+    N.sub(a, b)
+}
+
+const fn sc_neg(a: &Words) -> Words {
+    N.neg(a)
+}
+
+const fn sc_mul(a: &Words, b: &Words) -> Words {
+    N.mul(a, b)
+}
+
+const fn sc_square(a: &Words) -> Words {
+    N.mul(a, a)
+}
+
+const fn sc_to_montgomery(a: &Words) -> Words {
+    N.to_montgomery(a)
+}
+
+const fn sc_from_montgomery(a: &Words) -> Words {
+    N.out_of_montgomery(a)
+}
+
+/// A scalar, modulo the order of the Stark curve.
+///
+/// Scalars are kept in Montgomery form.
+#[derive(Clone, Copy)]
+pub struct Scalar(U256);
+
+primeorder::impl_mont_field_element!(
+    Stark,
+    Scalar,
+    FieldBytes,
+    U256,
+    ORDER,
+    Words,
+    sc_from_montgomery,
+    sc_to_montgomery,
+    sc_add,
+    sc_sub,
+    sc_mul,
+    sc_neg,
+    sc_square
+);
+
+impl Scalar {
+    /// Returns the multiplicative inverse of self, if self is non-zero.
+    pub fn invert(&self) -> CtOption<Self> {
+        CtOption::new(self.invert_unchecked(), !self.is_zero())
+    }
+
+    const fn invert_unchecked(&self) -> Self {
+        self.pow_vartime(&N_MINUS_2)
+    }
+
+    /// Returns the square root of self, if it exists.
+    pub fn sqrt(&self) -> CtOption<Self> {
+        let root = self.pow_vartime(&N_PLUS_1_OVER_4);
+        CtOption::new(root, root.square().ct_eq(self))
+    }
+}
+
+impl PrimeField for Scalar {
+    type Repr = FieldBytes;
+
+    const MODULUS: &'static str = ORDER_HEX;
+    const NUM_BITS: u32 = 252;
+    const CAPACITY: u32 = 251;
+    const TWO_INV: Self = Self::from_u64(2).invert_unchecked();
+    const MULTIPLICATIVE_GENERATOR: Self = Self::from_u64(3);
+    const S: u32 = 1;
+    const ROOT_OF_UNITY: Self =
+        Self::from_hex("0800000000000010ffffffffffffffffb781126dcae7b2321e66a241adc64d2e");
+    const ROOT_OF_UNITY_INV: Self = Self::ROOT_OF_UNITY;
+    const DELTA: Self = Self::from_u64(9);
+
+    fn from_repr(bytes: FieldBytes) -> CtOption<Self> {
+        Self::from_bytes(&bytes)
+    }
+
+    fn to_repr(&self) -> FieldBytes {
+        self.to_bytes()
+    }
+
+    fn is_odd(&self) -> Choice {
+        self.is_odd()
+    }
+}
+
+impl Debug for Scalar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Scalar(0x{:X})", &self.to_canonical())
+    }
+}
+
+impl AsRef<Scalar> for Scalar {
+    fn as_ref(&self) -> &Scalar {
+        self
+    }
+}
+
+impl PartialOrd for Scalar {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.to_canonical().partial_cmp(&other.to_canonical())
+    }
+}
+
+impl IsHigh for Scalar {
+    fn is_high(&self) -> Choice {
+        self.to_canonical().ct_gt(&FRAC_ORDER_2)
+    }
+}
+
+impl Invert for Scalar {
+    type Output = CtOption<Self>;
+
+    fn invert(&self) -> CtOption<Self> {
+        self.invert()
+    }
+}
+
+impl ShrAssign<usize> for Scalar {
+    fn shr_assign(&mut self, rhs: usize) {
+        *self = Self::from_uint_unchecked(self.to_canonical().shr_vartime(rhs));
+    }
+}
+
+impl FromUintUnchecked for Scalar {
+    type Uint = U256;
+
+    fn from_uint_unchecked(uint: U256) -> Self {
+        Self::from_uint_unchecked(uint)
+    }
+}
+
+impl Reduce<U256> for Scalar {
+    type Bytes = FieldBytes;
+
+    fn reduce(n: U256) -> Self {
+        Self::from_uint_unchecked(n.const_rem(&ORDER).0)
+    }
+
+    fn reduce_bytes(bytes: &FieldBytes) -> Self {
+        Self::reduce(U256::from_be_byte_array(*bytes))
+    }
+}
+
+impl Reduce<U512> for Scalar {
+    type Bytes = GenericArray<u8, U64>;
+
+    fn reduce(n: U512) -> Self {
+        let (hi, lo) = n.split();
+        Self::from_uint_unchecked(U256::const_rem_wide((lo, hi), &ORDER).0)
+    }
+
+    fn reduce_bytes(bytes: &Self::Bytes) -> Self {
+        Self::reduce(U512::from_be_byte_array(*bytes))
+    }
+}
+
+impl From<ScalarPrimitive<Stark>> for Scalar {
+    fn from(scalar: ScalarPrimitive<Stark>) -> Self {
+        Self::from_uint_unchecked(*scalar.as_uint())
+    }
+}
+
+impl From<Scalar> for ScalarPrimitive<Stark> {
+    fn from(scalar: Scalar) -> Self {
+        // Scalars are always reduced, so this can't fail.
+        ScalarPrimitive::new(scalar.to_canonical()).unwrap()
+    }
+}
+
+impl From<Scalar> for FieldBytes {
+    fn from(scalar: Scalar) -> Self {
+        scalar.to_bytes()
+    }
+}
+
+impl From<Scalar> for U256 {
+    fn from(scalar: Scalar) -> Self {
+        scalar.to_canonical()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use elliptic_curve::Field;
+    use rand_core::OsRng;
+
+    use super::*;
+
+    #[test]
+    fn test_scalar_arithmetic() {
+        assert_eq!(Scalar::from_u64(2) * Scalar::TWO_INV, Scalar::ONE);
+        assert_eq!(Scalar::from_hex(ORDER_HEX), Scalar::ZERO);
+        assert!(bool::from((-Scalar::ONE).is_high()));
+        assert!(!bool::from(Scalar::ONE.is_high()));
+
+        for _ in 0..16 {
+            let x = Scalar::random(&mut OsRng);
+            assert_eq!(Scalar::from_repr(x.to_repr()).unwrap(), x);
+            assert_eq!(x * x.invert().unwrap(), Scalar::ONE);
+            let root = x.square().sqrt().unwrap();
+            assert!(root == x || root == -x);
+            assert_eq!(Scalar::from(ScalarPrimitive::from(x)), x);
+        }
+        assert!(bool::from(
+            Scalar::MULTIPLICATIVE_GENERATOR.sqrt().is_none()
+        ));
+
+        // Reducing 2^256 - 1 gives the same thing as computing it in the field.
+        let max = <Scalar as Reduce<U256>>::reduce(U256::MAX);
+        let two_64 = Scalar::from(u64::MAX) + Scalar::ONE;
+        assert_eq!(max, two_64.square().square() - Scalar::ONE);
+        let wide = <Scalar as Reduce<U512>>::reduce(U512::MAX);
+        assert_eq!(wide, two_64.square().square().square() - Scalar::ONE);
+    }
+}
@@ -1,24 +1,44 @@
-use k256::{AffinePoint, Secp256k1};
+use ecdsa::{signature::Verifier, VerifyingKey};
+use k256::Secp256k1;
+use p256::NistP256;
 use rand_core::OsRng;
 
 use crate::{
-    compat::scalar_hash,
+    compat::{scalar_hash, CSCurve},
     keygen, presign,
-    protocol::{run_protocol, Participant, Protocol},
+    protocol::{run_protocol, Fault, Format, Participant, Protocol, ProtocolError, Retry},
     sign,
     triples::{self, TriplePub, TripleShare},
     FullSignature, KeygenOutput, PresignArguments, PresignOutput,
 };
 
-fn run_keygen(
+/// Run protocols to completion, with their messages encoded in a given format.
+#[allow(clippy::type_complexity)]
+fn run_protocol_in<T>(
+    format: Format,
+    mut protocols: Vec<(Participant, Box<dyn Protocol<Output = T>>)>,
+) -> Vec<(Participant, T)> {
+    for (_, protocol) in &mut protocols {
+        protocol.set_format(format);
+    }
+    run_protocol(protocols).unwrap()
+}
+
+fn run_keygen<C: CSCurve>(
+    participants: Vec<Participant>,
+    threshold: usize,
+) -> Vec<(Participant, KeygenOutput<C>)> {
+    run_keygen_in(Format::MessagePack, participants, threshold)
+}
+
+fn run_keygen_in<C: CSCurve>(
+    format: Format,
     participants: Vec<Participant>,
     threshold: usize,
-) -> Vec<(Participant, KeygenOutput<Secp256k1>)> {
+) -> Vec<(Participant, KeygenOutput<C>)> {
     #[allow(clippy::type_complexity)]
-    let mut protocols: Vec<(
-        Participant,
-        Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
-    )> = Vec::with_capacity(participants.len());
+    let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = KeygenOutput<C>>>)> =
+        Vec::with_capacity(participants.len());
 
     for p in participants.iter() {
         let protocol = keygen(&participants, *p, threshold);
@@ -27,33 +47,47 @@ fn run_keygen(
         protocols.push((*p, Box::new(protocol)));
     }
 
-    run_protocol(protocols).unwrap()
+    run_protocol_in(format, protocols)
 }
 
-fn run_presign(
-    participants: Vec<(Participant, KeygenOutput<Secp256k1>)>,
-    shares0: Vec<TripleShare<Secp256k1>>,
-    shares1: Vec<TripleShare<Secp256k1>>,
-    pub0: &TriplePub<Secp256k1>,
-    pub1: &TriplePub<Secp256k1>,
+fn run_presign<C: CSCurve>(
+    participants: Vec<(Participant, KeygenOutput<C>)>,
+    shares0: Vec<TripleShare<C>>,
+    shares1: Vec<TripleShare<C>>,
+    pub0: &TriplePub<C>,
+    pub1: &TriplePub<C>,
     threshold: usize,
-) -> Vec<(Participant, PresignOutput<Secp256k1>)> {
+) -> Vec<(Participant, PresignOutput<C>)> {
+    run_presign_in(
+        Format::MessagePack,
+        participants,
+        shares0,
+        shares1,
+        pub0,
+        pub1,
+        threshold,
+    )
+}
+
+fn run_presign_in<C: CSCurve>(
+    format: Format,
+    participants: Vec<(Participant, KeygenOutput<C>)>,
+    shares0: Vec<TripleShare<C>>,
+    shares1: Vec<TripleShare<C>>,
+    pub0: &TriplePub<C>,
+    pub1: &TriplePub<C>,
+    threshold: usize,
+) -> Vec<(Participant, PresignOutput<C>)> {
     assert!(participants.len() == shares0.len());
     assert!(participants.len() == shares1.len());
 
     #[allow(clippy::type_complexity)]
-    let mut protocols: Vec<(
-        Participant,
-        Box<dyn Protocol<Output = PresignOutput<Secp256k1>>>,
-    )> = Vec::with_capacity(participants.len());
+    let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = PresignOutput<C>>>)> =
+        Vec::with_capacity(participants.len());
 
     let participant_list: Vec<Participant> = participants.iter().map(|(p, _)| *p).collect();
 
-    for (((p, keygen_out), share0), share1) in participants
-        .into_iter()
-        .zip(shares0.into_iter())
-        .zip(shares1.into_iter())
-    {
+    for (((p, keygen_out), share0), share1) in participants.into_iter().zip(shares0).zip(shares1) {
         let protocol = presign(
             &participant_list,
             p,
@@ -71,40 +105,48 @@ fn run_presign(
         protocols.push((p, Box::new(protocol)));
     }
 
-    run_protocol(protocols).unwrap()
+    run_protocol_in(format, protocols)
+}
+
+fn run_sign<C: CSCurve>(
+    participants: Vec<(Participant, PresignOutput<C>)>,
+    public_key: C::AffinePoint,
+    msg_hash: C::Scalar,
+) -> Vec<(Participant, FullSignature<C>)> {
+    run_sign_in(Format::MessagePack, participants, public_key, msg_hash)
 }
 
 #[allow(clippy::type_complexity)]
-fn run_sign(
-    participants: Vec<(Participant, PresignOutput<Secp256k1>)>,
-    public_key: AffinePoint,
-    msg: &[u8],
-) -> Vec<(Participant, FullSignature<Secp256k1>)> {
-    let mut protocols: Vec<(
-        Participant,
-        Box<dyn Protocol<Output = FullSignature<Secp256k1>>>,
-    )> = Vec::with_capacity(participants.len());
+fn run_sign_in<C: CSCurve>(
+    format: Format,
+    participants: Vec<(Participant, PresignOutput<C>)>,
+    public_key: C::AffinePoint,
+    msg_hash: C::Scalar,
+) -> Vec<(Participant, FullSignature<C>)> {
+    let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = FullSignature<C>>>)> =
+        Vec::with_capacity(participants.len());
 
     let participant_list: Vec<Participant> = participants.iter().map(|(p, _)| *p).collect();
 
     for (p, presign_out) in participants.into_iter() {
-        let protocol = sign(
-            &participant_list,
-            p,
-            public_key,
-            presign_out,
-            scalar_hash(msg),
-        );
+        let protocol = sign(&participant_list, p, public_key, presign_out, msg_hash);
         assert!(protocol.is_ok());
         let protocol = protocol.unwrap();
         protocols.push((p, Box::new(protocol)));
     }
 
-    run_protocol(protocols).unwrap()
+    run_protocol_in(format, protocols)
 }
 
-#[test]
-fn test_e2e() {
+/// Run keygen, presigning, and signing end to end, returning the public key and signature.
+pub(crate) fn run_e2e<C: CSCurve>(msg_hash: C::Scalar) -> (C::AffinePoint, FullSignature<C>) {
+    run_e2e_with(|_| msg_hash)
+}
+
+/// Like [run_e2e()], except computing the message hash once the public key is known.
+pub(crate) fn run_e2e_with<C: CSCurve>(
+    msg_hash: impl FnOnce(C::AffinePoint) -> C::Scalar,
+) -> (C::AffinePoint, FullSignature<C>) {
     let participants = vec![
         Participant::from(0u32),
         Participant::from(1u32),
@@ -112,7 +154,7 @@ fn test_e2e() {
     ];
     let t = 3;
 
-    let mut keygen_result = run_keygen(participants.clone(), t);
+    let mut keygen_result = run_keygen::<C>(participants.clone(), t);
     keygen_result.sort_by_key(|(p, _)| *p);
 
     let public_key = keygen_result[0].1.public_key;
@@ -125,7 +167,175 @@ fn test_e2e() {
     let mut presign_result = run_presign(keygen_result, shares0, shares1, &pub0, &pub1, t);
     presign_result.sort_by_key(|(p, _)| *p);
 
+    let mut sign_result = run_sign(presign_result, public_key, msg_hash(public_key));
+    let sig = sign_result.pop().unwrap().1;
+    for (_, other) in sign_result {
+        assert_eq!(other.big_r, sig.big_r);
+        assert_eq!(other.s, sig.s);
+    }
+
+    (public_key, sig)
+}
+
+#[test]
+fn test_e2e() {
+    run_e2e::<Secp256k1>(scalar_hash(b"hello world"));
+}
+
+#[test]
+fn test_e2e_p256() {
+    use elliptic_curve::{ops::Reduce, point::AffineCoordinates};
+    use p256::{FieldBytes, Scalar, U256};
+    use sha2::{Digest, Sha256};
+
     let msg = b"hello world";
+    let digest = Sha256::digest(msg);
+    let msg_hash = <Scalar as Reduce<U256>>::reduce_bytes(&digest);
+
+    let (public_key, sig) = run_e2e::<NistP256>(msg_hash);
+
+    let r = <Scalar as Reduce<U256>>::reduce_bytes(&sig.big_r.x());
+    let sig =
+        p256::ecdsa::Signature::from_scalars(FieldBytes::from(r), FieldBytes::from(sig.s)).unwrap();
+    let vk = VerifyingKey::<NistP256>::from_affine(public_key).unwrap();
+    assert!(vk.verify(msg, &sig).is_ok());
+}
+
+#[test]
+fn test_e2e_stark() {
+    use ecdsa::signature::hazmat::PrehashVerifier;
+    use elliptic_curve::{ff::PrimeField, ops::Reduce, point::AffineCoordinates};
+    use sha2::Digest;
+
+    use crate::stark::{FieldBytes, Scalar, Stark};
+
+    // Starknet signs hashes which fit in 251 bits.
+    let mut prehash = FieldBytes::default();
+    prehash[1..].copy_from_slice(&sha2::Sha256::digest(b"hello world")[1..]);
+    prehash[0] = 0;
+    let msg_hash = Scalar::from_repr(prehash).unwrap();
+
+    let (public_key, sig) = run_e2e::<Stark>(msg_hash);
+    assert!(sig.verify(&public_key, &msg_hash));
+
+    let r = <Scalar as Reduce<elliptic_curve::bigint::U256>>::reduce_bytes(&sig.big_r.x());
+    let sig = ecdsa::Signature::<Stark>::from_scalars(r, sig.s).unwrap();
+    let vk = VerifyingKey::<Stark>::from_affine(public_key).unwrap();
+    assert!(vk.verify_prehash(&prehash, &sig).is_ok());
+}
+
+#[test]
+fn test_e2e_tagged() {
+    // Every message has a schema, so that everything can run in the tagged encoding.
+    let participants: Vec<_> = (0..3u32).map(Participant::from).collect();
+    let t = 2;
+
+    let mut keygen_result = run_keygen_in::<Secp256k1>(Format::Tagged, participants.clone(), t);
+    keygen_result.sort_by_key(|(p, _)| *p);
+    let public_key = keygen_result[0].1.public_key;
+
+    let mut triples = Vec::new();
+    for _ in 0..2 {
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = _>>)> = Vec::new();
+        for &p in &participants {
+            let protocol = triples::generate_triple::<Secp256k1>(&participants, p, t).unwrap();
+            protocols.push((p, Box::new(protocol)));
+        }
+        let mut result = run_protocol_in(Format::Tagged, protocols);
+        result.sort_by_key(|(p, _)| *p);
+        triples.push(result);
+    }
+    let (shares0, pubs0): (Vec<_>, Vec<_>) = triples[0].iter().map(|(_, t)| t.clone()).unzip();
+    let (shares1, pubs1): (Vec<_>, Vec<_>) = triples[1].iter().map(|(_, t)| t.clone()).unzip();
+
+    let presign_result = run_presign_in(
+        Format::Tagged,
+        keygen_result,
+        shares0,
+        shares1,
+        &pubs0[0],
+        &pubs1[0],
+        t,
+    );
+    let msg_hash = scalar_hash(b"hello world");
+    let (_, sig) = run_sign_in(Format::Tagged, presign_result, public_key, msg_hash)
+        .pop()
+        .unwrap();
+    assert!(sig.verify(&public_key, &msg_hash));
+}
+
+#[test]
+fn test_serialization_p256() {
+    let participants = vec![
+        Participant::from(0u32),
+        Participant::from(1u32),
+        Participant::from(2u32),
+    ];
+    let t = 2;
+
+    for (_, out) in run_keygen::<NistP256>(participants.clone(), t) {
+        let bytes = rmp_serde::encode::to_vec(&out).unwrap();
+        let decoded: KeygenOutput<NistP256> = rmp_serde::decode::from_slice(&bytes).unwrap();
+        assert_eq!(decoded.private_share, out.private_share);
+        assert_eq!(decoded.public_key, out.public_key);
+    }
+
+    let (triple_pub, shares) = triples::deal::<NistP256>(&mut OsRng, &participants, t);
+    let bytes = rmp_serde::encode::to_vec(&triple_pub).unwrap();
+    let decoded: TriplePub<NistP256> = rmp_serde::decode::from_slice(&bytes).unwrap();
+    assert_eq!(decoded.big_a, triple_pub.big_a);
+    assert_eq!(decoded.big_b, triple_pub.big_b);
+    assert_eq!(decoded.big_c, triple_pub.big_c);
+    assert_eq!(decoded.participants, triple_pub.participants);
+    assert_eq!(decoded.threshold, triple_pub.threshold);
+
+    for share in shares {
+        let bytes = rmp_serde::encode::to_vec(&share).unwrap();
+        let decoded: TripleShare<NistP256> = rmp_serde::decode::from_slice(&bytes).unwrap();
+        assert_eq!(decoded.a, share.a);
+        assert_eq!(decoded.b, share.b);
+        assert_eq!(decoded.c, share.c);
+    }
+}
+
+#[test]
+fn test_sign_with_retry() {
+    let participants: Vec<_> = (0..4u32).map(Participant::from).collect();
+    let cheater = participants[1];
+    let t = 2;
+
+    let mut keygen_result = run_keygen::<Secp256k1>(participants.clone(), t);
+    keygen_result.sort_by_key(|(p, _)| *p);
+    let public_key = keygen_result[0].1.public_key;
+    let msg_hash = scalar_hash(b"hello world");
+
+    let mut retry = Retry::new(&participants, 3).unwrap();
+    let sig = retry
+        .run(3, |subset| {
+            if subset.contains(&cheater) {
+                return Err(ProtocolError::Malicious {
+                    participant: cheater,
+                    round: 0,
+                    fault: Fault::BadProof,
+                });
+            }
+            // Presignatures are bound to their participants, so each attempt presigns again.
+            let keys = keygen_result
+                .iter()
+                .filter(|(p, _)| subset.contains(p))
+                .cloned()
+                .collect();
+            let (pub0, shares0) = triples::deal(&mut OsRng, subset, t);
+            let (pub1, shares1) = triples::deal(&mut OsRng, subset, t);
+            let presign_result = run_presign(keys, shares0, shares1, &pub0, &pub1, t);
+            Ok(run_sign(presign_result, public_key, msg_hash)
+                .pop()
+                .unwrap()
+                .1)
+        })
+        .unwrap();
 
-    run_sign(presign_result, public_key, msg);
+    assert_eq!(retry.excluded(), &[cheater]);
+    assert!(sig.verify(&public_key, &msg_hash));
 }
@@ -0,0 +1,140 @@
+//! Optional instrumentation of protocols with `tracing`, behind the `tracing` feature.
+//!
+//! When a ceremony gets stuck, the question is which round each participant is in,
+//! and which messages it's still waiting on. With this feature, the executor emits
+//! an event for every message sent, received, and waited on, at the `TRACE` level,
+//! along with the channel, the waitpoint, and the size, and a `DEBUG` event when
+//! a protocol finishes or fails. Each multiplication with another participant, in triple
+//! generation, runs inside of a `DEBUG` span naming that participant, with spans for each
+//! of its phases: the oblivious transfer setup, its extension, and the conversions.
+//!
+//! Without the feature, these macros expand to nothing, so builds pay nothing for them.
+
+/// Emit an event with `tracing`, at a given level, if the feature is enabled.
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {{
+        #[cfg(any(feature = "tracing", test))]
+        {
+            ::tracing::event!(::tracing::Level::$level, $($arg)+);
+        }
+    }};
+}
+
+/// Run a future inside of a `DEBUG` span, if the feature is enabled.
+macro_rules! instrument {
+    ($fut:expr, $name:literal $(, $($field:tt)+)?) => {{
+        #[cfg(any(feature = "tracing", test))]
+        let fut = ::tracing::Instrument::instrument(
+            $fut,
+            ::tracing::debug_span!($name $(, $($field)+)?),
+        );
+        #[cfg(not(any(feature = "tracing", test)))]
+        let fut = $fut;
+        fut
+    }};
+}
+
+pub(crate) use event;
+pub(crate) use instrument;
+
+#[cfg(test)]
+mod test {
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    };
+
+    use k256::Secp256k1;
+    use tracing::{
+        field::{Field, Visit},
+        span, Event, Metadata, Subscriber,
+    };
+
+    use crate::{
+        protocol::{run_protocol, Participant, Protocol},
+        triples::{generate_triple, TripleGenerationOutput},
+    };
+
+    /// The messages of the events and the names of the spans we saw, in order.
+    #[derive(Default)]
+    struct Recorded {
+        events: Mutex<Vec<String>>,
+        spans: Mutex<Vec<&'static str>>,
+        next_id: AtomicU64,
+    }
+
+    struct Message<'a>(&'a mut String);
+
+    impl Visit for Message<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            if field.name() == "message" {
+                *self.0 = format!("{value:?}");
+            }
+        }
+    }
+
+    struct Recorder(Arc<Recorded>);
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+            self.0.spans.lock().unwrap().push(span.metadata().name());
+            span::Id::from_u64(self.0.next_id.fetch_add(1, Ordering::SeqCst) + 1)
+        }
+
+        fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut message = String::new();
+            event.record(&mut Message(&mut message));
+            self.0.events.lock().unwrap().push(message);
+        }
+
+        fn enter(&self, _: &span::Id) {}
+
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    #[test]
+    fn test_triple_generation_is_traced() {
+        let recorded = Arc::new(Recorded::default());
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+
+        tracing::subscriber::with_default(Recorder(recorded.clone()), || {
+            #[allow(clippy::type_complexity)]
+            let mut protocols: Vec<(
+                Participant,
+                Box<dyn Protocol<Output = TripleGenerationOutput<Secp256k1>>>,
+            )> = Vec::with_capacity(participants.len());
+            for &p in &participants {
+                let protocol = generate_triple::<Secp256k1>(&participants, p, 2).unwrap();
+                protocols.push((p, Box::new(protocol)));
+            }
+            run_protocol(protocols).unwrap();
+        });
+
+        let events = recorded.events.lock().unwrap();
+        for message in [
+            "sending a message to everyone",
+            "sending a private message",
+            "waiting for a message",
+            "received a message",
+            "protocol finished",
+        ] {
+            assert!(events.iter().any(|e| e == message), "no {message:?} event");
+        }
+        let spans = recorded.spans.lock().unwrap();
+        for name in ["batch random ot", "random ot extension", "mta"] {
+            // Both participants run each phase, once for each of the two multiplications.
+            assert!(
+                spans.iter().filter(|&&s| s == name).count() >= 2,
+                "no {name:?} span"
+            );
+        }
+    }
+}
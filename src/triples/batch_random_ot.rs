@@ -1,16 +1,18 @@
 use ck_meow::Meow;
 use elliptic_curve::{Field, Group};
+use futures_lite::stream::{self, StreamExt};
+use magikitten::MeowRng;
 use rand_core::OsRng;
-use smol::stream::{self, StreamExt};
 use std::sync::Arc;
 use subtle::ConditionallySelectable;
 
 use crate::{
     compat::{CSCurve, SerializablePoint},
     constants::SECURITY_PARAMETER,
+    crypto::fork_rng,
     protocol::{
         internal::{make_protocol, Context, PrivateChannel},
-        run_two_party_protocol, Participant, ProtocolError,
+        run_two_party_protocol, Fault, Participant, ProtocolError,
     },
     serde::encode,
 };
@@ -40,11 +42,12 @@ fn hash<C: CSCurve>(
 type BatchRandomOTOutputSender = (SquareBitMatrix, SquareBitMatrix);
 
 pub async fn batch_random_ot_sender<C: CSCurve>(
+    mut rng: MeowRng,
     ctx: Context<'_>,
     mut chan: PrivateChannel,
 ) -> Result<BatchRandomOTOutputSender, ProtocolError> {
     // Spec 1
-    let y = C::Scalar::random(&mut OsRng);
+    let y = C::Scalar::random(&mut rng);
     let big_y = C::ProjectivePoint::generator() * y;
     let big_z = big_y * y;
 
@@ -73,7 +76,9 @@ pub async fn batch_random_ot_sender<C: CSCurve>(
     Ok((big_k0.try_into().unwrap(), big_k1.try_into().unwrap()))
 }
 
+#[allow(clippy::needless_range_loop)]
 pub async fn batch_random_ot_sender_many<C: CSCurve, const N: usize>(
+    mut rng: MeowRng,
     ctx: Context<'_>,
     mut chan: PrivateChannel,
 ) -> Result<Vec<BatchRandomOTOutputSender>, ProtocolError> {
@@ -83,7 +88,7 @@ pub async fn batch_random_ot_sender_many<C: CSCurve, const N: usize>(
     let mut yv = vec![];
     for _ in 0..N {
         // Spec 1
-        let y = C::Scalar::random(&mut OsRng);
+        let y = C::Scalar::random(&mut rng);
         let big_y = C::ProjectivePoint::generator() * y;
         let big_z = big_y * y;
         yv.push(y);
@@ -95,7 +100,7 @@ pub async fn batch_random_ot_sender_many<C: CSCurve, const N: usize>(
     let mut big_y_affine_v = vec![];
     for i in 0..N {
         let big_y = &big_y_v[i];
-        let big_y_affine = SerializablePoint::<C>::from_projective(&big_y);
+        let big_y_affine = SerializablePoint::<C>::from_projective(big_y);
         big_y_affine_v.push(big_y_affine);
     }
     chan.send(wait0, &big_y_affine_v).await;
@@ -153,6 +158,7 @@ pub async fn batch_random_ot_sender_many<C: CSCurve, const N: usize>(
 type BatchRandomOTOutputReceiver = (BitVector, SquareBitMatrix);
 
 pub async fn batch_random_ot_receiver<C: CSCurve>(
+    mut rng: MeowRng,
     ctx: Context<'_>,
     mut chan: PrivateChannel,
 ) -> Result<BatchRandomOTOutputReceiver, ProtocolError> {
@@ -161,18 +167,20 @@ pub async fn batch_random_ot_receiver<C: CSCurve>(
     let big_y_affine: SerializablePoint<C> = chan.recv(wait0).await?;
     let big_y = big_y_affine.to_projective();
     if bool::from(big_y.is_identity()) {
-        return Err(ProtocolError::AssertionFailed(
-            "Big y in batch random OT was zero.".into(),
-        ));
+        return Err(ProtocolError::Failed {
+            round: wait0,
+            fault: Fault::InvalidMessage("big y in batch random OT was zero"),
+        });
     }
 
-    let delta = BitVector::random(&mut OsRng);
+    let delta = BitVector::random(&mut rng);
 
     let tasks = delta.bits().enumerate().map(|(i, d_i)| {
         let mut chan = chan.child(i as u64);
+        let mut rng = fork_rng(&mut rng);
         ctx.spawn(async move {
             // Step 4
-            let x_i = C::Scalar::random(&mut OsRng);
+            let x_i = C::Scalar::random(&mut rng);
             let mut big_x_i = C::ProjectivePoint::generator() * x_i;
             big_x_i.conditional_assign(&(big_x_i + big_y), d_i);
 
@@ -190,7 +198,9 @@ pub async fn batch_random_ot_receiver<C: CSCurve>(
     Ok((delta, big_k.try_into().unwrap()))
 }
 
+#[allow(clippy::needless_range_loop)]
 pub async fn batch_random_ot_receiver_many<C: CSCurve, const N: usize>(
+    mut rng: MeowRng,
     ctx: Context<'_>,
     mut chan: PrivateChannel,
 ) -> Result<Vec<BatchRandomOTOutputReceiver>, ProtocolError> {
@@ -205,12 +215,13 @@ pub async fn batch_random_ot_receiver_many<C: CSCurve, const N: usize>(
         let big_y_affine = big_y_affine_v[i];
         let big_y = big_y_affine.to_projective();
         if bool::from(big_y.is_identity()) {
-            return Err(ProtocolError::AssertionFailed(
-                "Big y in batch random OT was zero.".into(),
-            ));
+            return Err(ProtocolError::Failed {
+                round: wait0,
+                fault: Fault::InvalidMessage("big y in batch random OT was zero"),
+            });
         }
 
-        let delta = BitVector::random(&mut OsRng);
+        let delta = BitVector::random(&mut rng);
         big_y_v.push(big_y);
         deltav.push(delta);
     }
@@ -238,13 +249,14 @@ pub async fn batch_random_ot_receiver_many<C: CSCurve, const N: usize>(
         let d_i_v = choices[i].clone();
         let big_y_v_arc = big_y_v_arc.clone();
         let big_y_affine_v_arc = big_y_affine_v_arc.clone();
+        let mut rng = fork_rng(&mut rng);
         let task = ctx.spawn(async move {
             let mut x_i_v = Vec::new();
             let mut big_x_i_v = Vec::new();
             for j in 0..N {
                 let d_i = d_i_v[j];
                 // Step 4
-                let x_i = C::Scalar::random(&mut OsRng);
+                let x_i = C::Scalar::random(&mut rng);
                 let mut big_x_i = C::ProjectivePoint::generator() * x_i;
                 big_x_i.conditional_assign(&(big_x_i + big_y_v_arc[j]), d_i);
                 x_i_v.push(x_i);
@@ -311,11 +323,19 @@ pub(crate) fn run_batch_random_ot<C: CSCurve>(
         r,
         &mut make_protocol(
             ctx_s.clone(),
-            batch_random_ot_sender::<C>(ctx_s.clone(), ctx_s.private_channel(s, r)),
+            batch_random_ot_sender::<C>(
+                fork_rng(&mut OsRng),
+                ctx_s.clone(),
+                ctx_s.private_channel(s, r),
+            ),
         ),
         &mut make_protocol(
             ctx_r.clone(),
-            batch_random_ot_receiver::<C>(ctx_r.clone(), ctx_r.private_channel(r, s)),
+            batch_random_ot_receiver::<C>(
+                fork_rng(&mut OsRng),
+                ctx_r.clone(),
+                ctx_r.private_channel(r, s),
+            ),
         ),
     )
 }
@@ -339,11 +359,19 @@ pub(crate) fn run_batch_random_ot_many<C: CSCurve, const N: usize>() -> Result<
         r,
         &mut make_protocol(
             ctx_s.clone(),
-            batch_random_ot_sender_many::<C, N>(ctx_s.clone(), ctx_s.private_channel(s, r)),
+            batch_random_ot_sender_many::<C, N>(
+                fork_rng(&mut OsRng),
+                ctx_s.clone(),
+                ctx_s.private_channel(s, r),
+            ),
         ),
         &mut make_protocol(
             ctx_r.clone(),
-            batch_random_ot_receiver_many::<C, N>(ctx_r.clone(), ctx_r.private_channel(r, s)),
+            batch_random_ot_receiver_many::<C, N>(
+                fork_rng(&mut OsRng),
+                ctx_r.clone(),
+                ctx_r.private_channel(r, s),
+            ),
         ),
     )
 }
@@ -369,8 +397,8 @@ mod test {
             .zip(k_delta.matrix.rows())
         {
             assert_eq!(
-                BitVector::conditional_select(row0, row1, delta_i),
-                *row_delta
+                BitVector::conditional_select(&row0, &row1, delta_i),
+                row_delta
             );
         }
     }
@@ -392,8 +420,8 @@ mod test {
                 .zip(k_delta.matrix.rows())
             {
                 assert_eq!(
-                    BitVector::conditional_select(row0, row1, delta_i),
-                    *row_delta
+                    BitVector::conditional_select(&row0, &row1, delta_i),
+                    row_delta
                 );
             }
         }
@@ -1,21 +1,96 @@
+use aes::cipher::{KeyIvInit, StreamCipher};
 use auto_ops::impl_op_ex;
 use ck_meow::Meow;
 use rand_core::CryptoRngCore;
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    ser::SerializeTuple,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
-use crate::constants::SECURITY_PARAMETER;
+use crate::{
+    constants::SECURITY_PARAMETER,
+    parallel::par_map_range,
+    serde::{Schema, Wire},
+};
 
-pub const SEC_PARAM_64: usize = (SECURITY_PARAMETER + 64 - 1) / 64;
-pub const SEC_PARAM_8: usize = (SECURITY_PARAMETER + 8 - 1) / 8;
+pub const SEC_PARAM_64: usize = SECURITY_PARAMETER.div_ceil(64);
+pub const SEC_PARAM_8: usize = SECURITY_PARAMETER.div_ceil(8);
 
 /// Represents a vector of bits.
 ///
 /// This vector will have the size of our security parameter, which is useful
 /// for most of our OT extension protocols.
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BitVector([u64; SEC_PARAM_64]);
 
+/// Serialize words as a tuple of bytes, with each word in little endian order.
+///
+/// This makes bit `j` of a vector bit `j % 8` of byte `j / 8`, regardless of the platform.
+fn serialize_words<S: Serializer>(words: &[u64], serializer: S) -> Result<S::Ok, S::Error> {
+    let mut tuple = serializer.serialize_tuple(8 * words.len())?;
+    for byte in words.iter().flat_map(|w| w.to_le_bytes()) {
+        tuple.serialize_element(&byte)?;
+    }
+    tuple.end()
+}
+
+/// Deserialize words written by [`serialize_words`].
+fn deserialize_words<'de, D: Deserializer<'de>, const N: usize>(
+    deserializer: D,
+) -> Result<[u64; N], D::Error> {
+    struct WordsVisitor<const N: usize>;
+
+    impl<'de, const N: usize> Visitor<'de> for WordsVisitor<N> {
+        type Value = [u64; N];
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            write!(f, "a tuple of {} bytes", 8 * N)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut out = [0u64; N];
+            for i in 0..8 * N {
+                let b: u8 = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+                out[i / 8] |= u64::from(b) << (8 * (i % 8));
+            }
+            Ok(out)
+        }
+    }
+
+    deserializer.deserialize_tuple(8 * N, WordsVisitor::<N>)
+}
+
+impl Serialize for BitVector {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_words(&self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for BitVector {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_words(deserializer).map(Self)
+    }
+}
+
+impl Wire for BitVector {
+    fn schema() -> Schema {
+        Schema::Bytes
+    }
+}
+
+// Bit vectors are small, and copied around, so they can't be zeroized on drop.
+// Instead, the containers holding them take care of that.
+impl Zeroize for BitVector {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
 impl BitVector {
     pub fn zero() -> Self {
         Self([0u64; SEC_PARAM_64])
@@ -149,10 +224,34 @@ impl_op_ex!(!|u: &BitVector| -> BitVector { u.not() });
 /// A BitVector of double the size.
 ///
 /// This is useful because it's quicker to avoid reducing the result of GF multiplication.
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
 pub struct DoubleBitVector([u64; Self::SIZE]);
 
+impl Serialize for DoubleBitVector {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_words(&self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for DoubleBitVector {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserialize_words(deserializer).map(Self)
+    }
+}
+
+impl Wire for DoubleBitVector {
+    fn schema() -> Schema {
+        Schema::Bytes
+    }
+}
+
+impl Zeroize for DoubleBitVector {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
 impl DoubleBitVector {
     const SIZE: usize = 2 * SEC_PARAM_64;
 
@@ -199,49 +298,144 @@ impl_op_ex!(^= |u: &mut DoubleBitVector, v: &DoubleBitVector| { u.xor_mut(v) });
 /// The context string for our PRG.
 const PRG_CTX: &[u8] = b"cait-sith v0.8.0 correlated OT PRG";
 
+/// The block cipher used by [`OtPrg::Aes`], whose keys are as long as our rows.
+#[cfg(not(feature = "security-256"))]
+type PrgCipher = aes::Aes128;
+#[cfg(feature = "security-256")]
+type PrgCipher = aes::Aes256;
+
+/// The pseudorandom generator expanding the rows of the OT extension setup.
+///
+/// This expansion dominates the cost of triple generation.
+/// Every participant needs to use the same generator.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OtPrg {
+    /// Meow, which is what the rest of the protocol uses.
+    #[default]
+    Meow,
+    /// AES in counter mode, keyed with each row, with a nonce derived from the session id.
+    ///
+    /// This is much faster on processors with AES instructions, and falls back to
+    /// a constant time software implementation on the others.
+    Aes,
+}
+
 /// Represents a matrix of bits.
 ///
 /// Each row of this matrix is a `BitVector`, although we might have more or less
 /// rows.
 ///
+/// The rows are stored one after the other, in a single buffer of words,
+/// and [`BitMatrix::rows`] and [`BitMatrix::chunks`] provide views over them.
+///
 /// This is a fundamental object used for our OT extension protocol.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone)]
 #[cfg_attr(test, derive(PartialEq, Eq))]
-pub struct BitMatrix(Vec<BitVector>);
+pub struct BitMatrix(Vec<u64>);
+
+impl Zeroize for BitMatrix {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Drop for BitMatrix {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for BitMatrix {}
+
+// We serialize the matrix as a list of rows, like the vectors composing it.
+impl Serialize for BitMatrix {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.rows())
+    }
+}
+
+impl<'de> Deserialize<'de> for BitMatrix {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut rows = Vec::<BitVector>::deserialize(deserializer)?;
+        let out = rows.iter().copied().collect();
+        rows.zeroize();
+        Ok(out)
+    }
+}
+
+impl Wire for BitMatrix {
+    fn schema() -> Schema {
+        Schema::repeated(Schema::Bytes)
+    }
+}
+
+/// A view over consecutive rows of a [`BitMatrix`].
+#[derive(Debug, Clone, Copy)]
+pub struct BitRows<'a>(&'a [u64]);
+
+impl<'a> BitRows<'a> {
+    /// Return the number of rows in this view.
+    pub fn len(&self) -> usize {
+        self.0.len() / SEC_PARAM_64
+    }
+
+    /// Iterate over the rows in this view.
+    pub fn iter(&self) -> impl Iterator<Item = BitVector> + 'a {
+        self.0
+            .chunks_exact(SEC_PARAM_64)
+            .map(|words| BitVector(words.try_into().unwrap()))
+    }
+}
 
 impl BitMatrix {
     /// Create a random matrix of a certain chunk size.
     ///
     /// Each chunk will have a security parameter's worth of rows.
     pub fn random(rng: &mut impl CryptoRngCore, height: usize) -> Self {
-        assert!(height % SECURITY_PARAMETER == 0);
-        Self((0..height).map(|_| BitVector::random(rng)).collect())
+        assert!(height.is_multiple_of(SECURITY_PARAMETER));
+        Self((0..height * SEC_PARAM_64).map(|_| rng.next_u64()).collect())
     }
 
     /// Create a new matrix from a list of rows.
     pub fn from_rows<'a>(rows: impl IntoIterator<Item = &'a BitVector>) -> Self {
-        Self(rows.into_iter().copied().collect())
+        rows.into_iter().copied().collect()
     }
 
     /// Return the number of rows in this matrix.
     pub fn height(&self) -> usize {
-        self.0.len()
+        self.0.len() / SEC_PARAM_64
     }
 
     /// Iterate over the rows of this matrix.
-    pub fn rows(&self) -> impl Iterator<Item = &BitVector> {
-        self.0.iter()
+    pub fn rows(&self) -> impl Iterator<Item = BitVector> + '_ {
+        BitRows(&self.0).iter()
     }
 
-    /// Iterate over a given column in chunks.
-    pub fn column_chunks(&self, j: usize) -> impl Iterator<Item = BitVector> + '_ {
-        self.0.chunks_exact(SECURITY_PARAMETER).map(move |chunk| {
-            let mut out = BitVector::zero();
-            for (i, c_i) in chunk.iter().enumerate() {
-                out.0[i / 64] |= c_i.bit(j) << (i % 64);
+    /// Iterate over chunks of a given number of rows.
+    pub fn chunks(&self, size: usize) -> impl Iterator<Item = BitRows<'_>> {
+        self.0.chunks_exact(size * SEC_PARAM_64).map(BitRows)
+    }
+
+    /// Transpose this matrix.
+    ///
+    /// The height of this matrix must be a multiple of the security parameter.
+    /// Each column of this matrix becomes `height / SECURITY_PARAMETER` rows of the result,
+    /// with the columns appearing in order, so that [`BitMatrix::chunks`] with that size
+    /// iterates over the columns.
+    pub fn transpose(&self) -> Self {
+        assert!(self.height().is_multiple_of(SECURITY_PARAMETER));
+        let n = self.height() / SECURITY_PARAMETER;
+        const BLOCK: usize = SECURITY_PARAMETER * SEC_PARAM_64;
+
+        let blocks = par_map_range(n, |i| transpose_square(&self.0[BLOCK * i..BLOCK * (i + 1)]));
+        let mut out = BitMatrix(vec![0u64; self.0.len()]);
+        for (i, block) in blocks.into_iter().enumerate() {
+            for (j, t_j) in block.into_iter().enumerate() {
+                let row = j * n + i;
+                out.0[SEC_PARAM_64 * row..SEC_PARAM_64 * (row + 1)].copy_from_slice(&t_j.0);
             }
-            out
-        })
+        }
+        out
     }
 
     /// Modify this matrix by xoring it with another.
@@ -259,8 +453,10 @@ impl BitMatrix {
     }
 
     pub fn and_vec_mut(&mut self, v: &BitVector) {
-        for self_i in &mut self.0 {
-            *self_i &= v;
+        for row in self.0.chunks_exact_mut(SEC_PARAM_64) {
+            for (row_i, v_i) in row.iter_mut().zip(v.0.iter()) {
+                *row_i &= v_i;
+            }
         }
     }
 
@@ -273,10 +469,53 @@ impl BitMatrix {
 
 impl FromIterator<BitVector> for BitMatrix {
     fn from_iter<T: IntoIterator<Item = BitVector>>(iter: T) -> Self {
-        Self(iter.into_iter().collect())
+        Self(iter.into_iter().flat_map(|v| v.0).collect())
+    }
+}
+
+/// Transpose a 64x64 block of bits in place.
+///
+/// This recursively swaps the off diagonal quadrants of the block, working on
+/// whole words at a time, rather than on individual bits.
+fn transpose_64(block: &mut [u64; 64]) {
+    let mut j = 32;
+    let mut m: u64 = 0x0000_0000_FFFF_FFFF;
+    while j != 0 {
+        let mut k = 0;
+        while k < 64 {
+            let t = ((block[k] >> j) ^ block[k + j]) & m;
+            block[k] ^= t << j;
+            block[k + j] ^= t;
+            k = (k + j + 1) & !j;
+        }
+        j >>= 1;
+        m ^= m << j;
     }
 }
 
+/// Transpose a square matrix, with a security parameter's worth of rows.
+///
+/// The rows are given as consecutive words, in the same layout as a [`BitMatrix`].
+/// This works in blocks of 64x64 bits, which fit in cache, and are transposed
+/// a word at a time.
+fn transpose_square(rows: &[u64]) -> [BitVector; SECURITY_PARAMETER] {
+    debug_assert_eq!(rows.len(), SECURITY_PARAMETER * SEC_PARAM_64);
+    let mut out = [BitVector::zero(); SECURITY_PARAMETER];
+    let mut block = [0u64; 64];
+    for r in 0..SEC_PARAM_64 {
+        for w in 0..SEC_PARAM_64 {
+            for (k, b_k) in block.iter_mut().enumerate() {
+                *b_k = rows[SEC_PARAM_64 * (64 * r + k) + w];
+            }
+            transpose_64(&mut block);
+            for (k, b_k) in block.iter().enumerate() {
+                out[64 * w + k].0[r] = *b_k;
+            }
+        }
+    }
+    out
+}
+
 impl_op_ex!(^ |u: &BitMatrix, v: &BitMatrix| -> BitMatrix { u.xor(v) });
 impl_op_ex!(^= |u: &mut BitMatrix, v: &BitMatrix| { u.xor_mut(v) });
 impl_op_ex!(&|u: &BitMatrix, v: &BitVector| -> BitMatrix { u.and_vec(v) });
@@ -286,6 +525,8 @@ pub struct SquareBitMatrix {
     pub matrix: BitMatrix,
 }
 
+impl ZeroizeOnDrop for SquareBitMatrix {}
+
 impl TryFrom<BitMatrix> for SquareBitMatrix {
     type Error = ();
 
@@ -300,36 +541,67 @@ impl TryFrom<BitMatrix> for SquareBitMatrix {
 impl SquareBitMatrix {
     /// Expand transpose expands each row to contain `chunks * SECURITY_PARAMETER` bits, and then transposes
     /// the resulting matrix.
-    pub fn expand_transpose(&self, sid: &[u8], rows: usize) -> BitMatrix {
-        assert!(rows % SECURITY_PARAMETER == 0);
+    ///
+    /// Each row is expanded with the given generator, separating different sessions with `sid`.
+    pub fn expand_transpose(&self, prg: OtPrg, sid: &[u8], rows: usize) -> BitMatrix {
+        assert!(rows.is_multiple_of(SECURITY_PARAMETER));
 
         let mut meow = Meow::new(PRG_CTX);
         meow.meta_ad(b"sid", false);
         meow.ad(sid, false);
 
-        let mut out = BitMatrix(vec![BitVector::zero(); rows]);
-
         // How many bytes to get rows bits?
-        let row8 = (rows + 7) / 8;
-        for (j, row) in self.matrix.0.iter().enumerate() {
-            // Expand the row
-            let mut expanded = vec![0u8; row8];
-            // We need to clone to make each row use the same prefix.
-            let mut meow = meow.clone();
-            meow.meta_ad(b"row", false);
-            meow.ad(b"", false);
-            for u in row.0 {
-                meow.ad(&u.to_le_bytes(), true);
+        let row8 = rows.div_ceil(8);
+        let expanded: Vec<Vec<u8>> = match prg {
+            OtPrg::Meow => par_map_range(self.matrix.height(), |j| {
+                let row = &self.matrix.0[SEC_PARAM_64 * j..SEC_PARAM_64 * (j + 1)];
+                // Expand the row
+                let mut expanded = vec![0u8; row8];
+                // We need to clone to make each row use the same prefix.
+                let mut meow = meow.clone();
+                meow.meta_ad(b"row", false);
+                meow.ad(b"", false);
+                for u in row {
+                    meow.ad(&u.to_le_bytes(), true);
+                }
+                meow.prf(&mut expanded, false);
+                expanded
+            }),
+            OtPrg::Aes => {
+                // The counter takes the last 4 bytes of the block, which leaves room
+                // for 64 GiB of output per row, far more than any batch needs.
+                let mut nonce = [0u8; 16];
+                meow.meta_ad(b"aes nonce", false);
+                meow.prf(&mut nonce[..12], false);
+                par_map_range(self.matrix.height(), |j| {
+                    let mut key = BitVector::zero();
+                    key.0
+                        .copy_from_slice(&self.matrix.0[SEC_PARAM_64 * j..SEC_PARAM_64 * (j + 1)]);
+                    let mut key_bytes = key.bytes();
+                    let mut cipher =
+                        ctr::Ctr32BE::<PrgCipher>::new(&key_bytes.into(), &nonce.into());
+                    key_bytes.zeroize();
+                    key.zeroize();
+                    let mut expanded = vec![0u8; row8];
+                    cipher.apply_keystream(&mut expanded);
+                    expanded
+                })
             }
-            meow.prf(&mut expanded, false);
-
-            // Now, write into the correct column
-            for i in 0..rows {
-                out.0[i].0[j / 64] |= u64::from((expanded[i / 8] >> (i % 8)) & 1) << (j % 64);
+        };
+
+        // Now, transpose the expanded rows, one square block at a time.
+        let blocks = par_map_range(rows / SECURITY_PARAMETER, |c| {
+            let mut block = [0u64; SECURITY_PARAMETER * SEC_PARAM_64];
+            for (b_j, expanded_j) in block.chunks_exact_mut(SEC_PARAM_64).zip(&expanded) {
+                let bytes = &expanded_j[SEC_PARAM_8 * c..SEC_PARAM_8 * (c + 1)];
+                for (b_j_k, chunk) in b_j.iter_mut().zip(bytes.chunks_exact(8)) {
+                    *b_j_k = u64::from_le_bytes(chunk.try_into().unwrap());
+                }
             }
-        }
+            transpose_square(&block)
+        });
 
-        out
+        blocks.into_iter().flatten().collect()
     }
 }
 
@@ -339,10 +611,24 @@ impl SquareBitMatrix {
 #[derive(Debug, Clone)]
 pub struct ChoiceVector(Vec<BitVector>);
 
+impl Zeroize for ChoiceVector {
+    fn zeroize(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl Drop for ChoiceVector {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ZeroizeOnDrop for ChoiceVector {}
+
 impl ChoiceVector {
     /// Generate a random vector with a certain number of bits.
     pub fn random(rng: &mut impl CryptoRngCore, size: usize) -> Self {
-        assert!(size > 0 && size % SECURITY_PARAMETER == 0);
+        assert!(size > 0 && size.is_multiple_of(SECURITY_PARAMETER));
 
         let data = (0..(size / SECURITY_PARAMETER))
             .map(|_| BitVector::random(rng))
@@ -367,13 +653,124 @@ impl ChoiceVector {
 
 #[cfg(test)]
 mod test {
+    use rand_core::OsRng;
+
     use super::*;
+    #[cfg(not(feature = "security-256"))]
+    use crate::serde::test::check_golden;
+    use crate::serde::{decode, encode};
+
+    #[test]
+    fn test_transpose() {
+        let height = 3 * SECURITY_PARAMETER;
+        let n = height / SECURITY_PARAMETER;
+        let m = BitMatrix::random(&mut OsRng, height);
+        let t = m.transpose();
+        assert_eq!(t.height(), height);
+        for (j, column) in t.chunks(n).enumerate() {
+            for (i, row) in m.rows().enumerate() {
+                let chunk = column.iter().nth(i / SECURITY_PARAMETER).unwrap();
+                assert_eq!(chunk.bit(i % SECURITY_PARAMETER), row.bit(j));
+            }
+        }
+    }
+
+    #[test]
+    fn test_expand_transpose() {
+        let rows = 2 * SECURITY_PARAMETER;
+        let sid = b"test sid";
+        let k =
+            SquareBitMatrix::try_from(BitMatrix::random(&mut OsRng, SECURITY_PARAMETER)).unwrap();
+        let t = k.expand_transpose(OtPrg::Meow, sid, rows);
+
+        // Compare against expanding each row, and writing the bits one at a time.
+        let mut meow = Meow::new(PRG_CTX);
+        meow.meta_ad(b"sid", false);
+        meow.ad(sid, false);
+        let mut expected = vec![BitVector::zero(); rows];
+        for (j, row) in k.matrix.rows().enumerate() {
+            let mut expanded = vec![0u8; rows / 8];
+            let mut meow = meow.clone();
+            meow.meta_ad(b"row", false);
+            meow.ad(b"", false);
+            for u in row.0 {
+                meow.ad(&u.to_le_bytes(), true);
+            }
+            meow.prf(&mut expanded, false);
+            for (i, e_i) in expected.iter_mut().enumerate() {
+                e_i.0[j / 64] |= u64::from((expanded[i / 8] >> (i % 8)) & 1) << (j % 64);
+            }
+        }
+        assert_eq!(t, BitMatrix::from_rows(&expected));
+    }
+
+    #[test]
+    fn test_expand_transpose_aes() {
+        use aes::cipher::{BlockEncrypt, KeyInit};
+
+        let rows = 2 * SECURITY_PARAMETER;
+        let sid = b"test sid";
+        let k =
+            SquareBitMatrix::try_from(BitMatrix::random(&mut OsRng, SECURITY_PARAMETER)).unwrap();
+        let t = k.expand_transpose(OtPrg::Aes, sid, rows);
+
+        // Compare against encrypting each counter block directly.
+        let mut nonce = [0u8; 16];
+        let mut meow = Meow::new(PRG_CTX);
+        meow.meta_ad(b"sid", false);
+        meow.ad(sid, false);
+        meow.meta_ad(b"aes nonce", false);
+        meow.prf(&mut nonce[..12], false);
+        let mut expected = vec![BitVector::zero(); rows];
+        for (j, row) in k.matrix.rows().enumerate() {
+            let cipher = PrgCipher::new(&row.bytes().into());
+            let mut expanded = Vec::new();
+            for counter in 0..(rows / 128) as u32 {
+                let mut block = nonce;
+                block[12..].copy_from_slice(&counter.to_be_bytes());
+                let mut block = block.into();
+                cipher.encrypt_block(&mut block);
+                expanded.extend_from_slice(&block);
+            }
+            for (i, e_i) in expected.iter_mut().enumerate() {
+                e_i.0[j / 64] |= u64::from((expanded[i / 8] >> (i % 8)) & 1) << (j % 64);
+            }
+        }
+        assert_eq!(t, BitMatrix::from_rows(&expected));
+    }
+
+    #[test]
+    #[cfg(not(feature = "security-256"))]
+    fn test_golden_bit_vector() {
+        let bytes: [u8; SEC_PARAM_8] = core::array::from_fn(|i| (17 * i) as u8);
+        let v = BitVector::from_bytes(&bytes);
+        // Byte i holds bits 8i to 8i + 7, whatever the endianness of the platform.
+        assert_eq!((0..8).map(|j| v.bit(j)).sum::<u64>(), 0);
+        assert_eq!((v.bit(8), v.bit(9), v.bit(12)), (1, 0, 1));
+        check_golden("bit_vector.hex", &v);
+    }
+
+    #[test]
+    fn test_serialization_as_rows() {
+        let m = BitMatrix::random(&mut OsRng, SECURITY_PARAMETER);
+        let rows: Vec<BitVector> = m.rows().collect();
+        let data = encode(&m);
+        assert_eq!(data, encode(&rows));
+        assert_eq!(decode::<BitMatrix>(&data).unwrap(), m);
+    }
 
     #[test]
     fn test_gf_multiplication() {
-        let a = BitVector([0b10, 0b10]);
-        let b = BitVector([0b100, 0b100]);
-        let c = DoubleBitVector([0b1000, 0, 0b1000, 0]);
+        // (x + x^65) * (x^2 + x^66) = x^3 + x^131
+        let mut a = BitVector::zero();
+        a.0[0] = 0b10;
+        a.0[1] = 0b10;
+        let mut b = BitVector::zero();
+        b.0[0] = 0b100;
+        b.0[1] = 0b100;
+        let mut c = DoubleBitVector::zero();
+        c.0[0] = 0b1000;
+        c.0[2] = 0b1000;
         assert_eq!(a.gf_mul(&b), c);
     }
 }
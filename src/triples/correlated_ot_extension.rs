@@ -1,14 +1,15 @@
 use crate::protocol::{
     internal::{make_protocol, Context, PrivateChannel},
-    run_two_party_protocol, Participant, ProtocolError,
+    run_two_party_protocol, Fault, Participant, ProtocolError,
 };
 
-use super::bits::{BitMatrix, BitVector, SquareBitMatrix};
+use super::bits::{BitMatrix, BitVector, OtPrg, SquareBitMatrix};
 
 /// Parameters we need for the correlated OT.
 #[derive(Debug, Clone, Copy)]
 pub struct CorrelatedOtParams<'sid> {
     pub(crate) sid: &'sid [u8],
+    pub(crate) prg: OtPrg,
     pub(crate) batch_size: usize,
 }
 
@@ -19,17 +20,16 @@ pub async fn correlated_ot_sender(
     k: &SquareBitMatrix,
 ) -> Result<BitMatrix, ProtocolError> {
     // Spec 2
-    let t = k.expand_transpose(params.sid, params.batch_size);
+    let t = k.expand_transpose(params.prg, params.sid, params.batch_size);
 
     // Spec 5
     let wait0 = chan.next_waitpoint();
     let u: BitMatrix = chan.recv(wait0).await?;
     if u.height() != params.batch_size {
-        return Err(ProtocolError::AssertionFailed(format!(
-            "expected matrix of height {} found {}",
-            params.batch_size,
-            u.height()
-        )));
+        return Err(ProtocolError::Failed {
+            round: wait0,
+            fault: Fault::InvalidMessage("matrix has the wrong height"),
+        });
     }
 
     // Spec 6
@@ -47,8 +47,8 @@ pub async fn correlated_ot_receiver(
 ) -> BitMatrix {
     assert_eq!(x.height(), params.batch_size);
     // Spec 1
-    let t0 = k0.expand_transpose(params.sid, params.batch_size);
-    let t1 = k1.expand_transpose(params.sid, params.batch_size);
+    let t0 = k0.expand_transpose(params.prg, params.sid, params.batch_size);
+    let t1 = k1.expand_transpose(params.prg, params.sid, params.batch_size);
 
     // Spec 3
     let u = &t0 ^ t1 ^ x;
@@ -66,6 +66,7 @@ fn run_correlated_ot(
     (delta, k): (BitVector, &SquareBitMatrix),
     (k0, k1, x): (&SquareBitMatrix, &SquareBitMatrix, &BitMatrix),
     sid: &[u8],
+    prg: OtPrg,
     batch_size: usize,
 ) -> Result<(BitMatrix, BitMatrix), ProtocolError> {
     let s = Participant::from(0u32);
@@ -73,7 +74,11 @@ fn run_correlated_ot(
     let ctx_s = Context::new();
     let ctx_r = Context::new();
 
-    let params = CorrelatedOtParams { sid, batch_size };
+    let params = CorrelatedOtParams {
+        sid,
+        prg,
+        batch_size,
+    };
 
     run_two_party_protocol(
         s,
@@ -102,9 +107,12 @@ mod test {
     fn test_correlated_ot() -> Result<(), ProtocolError> {
         let ((k0, k1), (delta, k)) = run_batch_random_ot::<Secp256k1>()?;
         let batch_size = 256;
-        let x = BitMatrix::random(&mut OsRng, batch_size);
-        let (q, t) = run_correlated_ot((delta, &k), (&k0, &k1, &x), b"test sid", batch_size)?;
-        assert_eq!(t ^ (x & delta), q);
+        for prg in [OtPrg::Meow, OtPrg::Aes] {
+            let x = BitMatrix::random(&mut OsRng, batch_size);
+            let (q, t) =
+                run_correlated_ot((delta, &k), (&k0, &k1, &x), b"test sid", prg, batch_size)?;
+            assert_eq!(t ^ (x & delta), q);
+        }
         Ok(())
     }
 }
@@ -1,23 +1,25 @@
 use elliptic_curve::{Field, Group, ScalarPrimitive};
-use magikitten::Transcript;
-use rand_core::OsRng;
+use magikitten::MeowRng;
+use rand_core::{CryptoRngCore, OsRng};
 
 use crate::crypto::{Commitment, Randomizer};
 use crate::triples::multiplication::multiplication_many;
 use crate::{
+    commitments::{CommitmentScheme, HashCommitments},
     compat::{CSCurve, SerializablePoint},
-    crypto::{commit, hash, Digest},
+    crypto::{fork_rng, hash, Digest},
+    fiat_shamir::{FiatShamir, FiatShamirBackend, Transcript},
     math::{GroupPolynomial, Polynomial},
     participants::{ParticipantCounter, ParticipantList, ParticipantMap},
     proofs::{dlog, dlogeq},
     protocol::{
         internal::{make_protocol, Context},
-        InitializationError, Participant, Protocol, ProtocolError,
+        Fault, InitializationError, Participant, Protocol, ProtocolError,
     },
     serde::encode,
 };
 
-use super::{multiplication::multiplication, TriplePub, TripleShare};
+use super::{bits::OtPrg, multiplication::multiplication, TriplePub, TripleShare};
 
 /// The output of running the triple generation protocol.
 pub type TripleGenerationOutput<C> = (TripleShare<C>, TriplePub<C>);
@@ -26,15 +28,20 @@ pub type TripleGenerationOutputMany<C> = Vec<(TripleShare<C>, TriplePub<C>)>;
 
 const LABEL: &[u8] = b"cait-sith v0.8.0 triple generation";
 
+/// The scheme we use to commit to our polynomials.
+const COMMITMENTS: HashCommitments = HashCommitments;
+
 async fn do_generation<C: CSCurve>(
+    mut rng: MeowRng,
     ctx: Context<'_>,
     participants: ParticipantList,
     me: Participant,
     threshold: usize,
+    prg: OtPrg,
+    backend: FiatShamirBackend,
 ) -> Result<TripleGenerationOutput<C>, ProtocolError> {
-    let mut rng = OsRng;
     let mut chan = ctx.shared_channel();
-    let mut transcript = Transcript::new(LABEL);
+    let mut transcript = Transcript::with_backend(backend, LABEL);
 
     // Spec 1.1
     transcript.message(b"group", C::NAME);
@@ -59,7 +66,8 @@ async fn do_generation<C: CSCurve>(
     let big_l_i = l.commit();
 
     // Spec 1.5
-    let (my_commitment, my_randomizer) = commit(&mut rng, &(&big_e_i, &big_f_i, &big_l_i));
+    let (my_commitment, my_randomizer) =
+        COMMITMENTS.commit(&mut rng, &(&big_e_i, &big_f_i, &big_l_i));
 
     // Spec 1.6
     let wait0 = chan.next_waitpoint();
@@ -84,7 +92,16 @@ async fn do_generation<C: CSCurve>(
         let ctx = ctx.clone();
         let e0 = e.evaluate_zero();
         let f0 = f.evaluate_zero();
-        multiplication::<C>(ctx, my_confirmation, participants.clone(), me, e0, f0)
+        multiplication::<C>(
+            fork_rng(&mut rng),
+            ctx,
+            my_confirmation,
+            prg,
+            participants.clone(),
+            me,
+            e0,
+            f0,
+        )
     };
     let multiplication_task = ctx.spawn(fut);
 
@@ -154,9 +171,11 @@ async fn do_generation<C: CSCurve>(
             continue;
         }
         if confirmation != my_confirmation {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "confirmation from {from:?} did not match expectation"
-            )));
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait1,
+                fault: Fault::InconsistentBroadcast,
+            });
         }
     }
 
@@ -165,6 +184,7 @@ async fn do_generation<C: CSCurve>(
     let mut big_f = big_f_i;
     let mut big_l = big_l_i;
     let mut big_e_j_zero = ParticipantMap::new(&participants);
+    let mut all_polys = ParticipantMap::new(&participants);
     seen.clear();
     seen.put(me);
     while !seen.full() {
@@ -197,24 +217,31 @@ async fn do_generation<C: CSCurve>(
             || their_big_f.len() != threshold
             || their_big_l.len() != threshold
         {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "polynomial from {from:?} has the wrong length"
-            )));
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait2,
+                fault: Fault::InvalidMessage("polynomial has the wrong length"),
+            });
         }
 
         if !bool::from(their_big_l.evaluate_zero().is_identity()) {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "L(0) from {from:?} is not 0"
-            )));
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait2,
+                fault: Fault::InvalidMessage("L(0) is not 0"),
+            });
         }
 
-        if !all_commitments[from].check(
+        if !COMMITMENTS.check(
+            &all_commitments[from],
             &(&their_big_e, &their_big_f, &their_big_l),
             &their_randomizer,
         ) {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "commitment from {from:?} did not match revealed F"
-            )));
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait2,
+                fault: Fault::BadCommitment,
+            });
         }
 
         let statement0 = dlog::Statement::<C> {
@@ -225,9 +252,11 @@ async fn do_generation<C: CSCurve>(
             statement0,
             &their_phi_proof0,
         ) {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "dlog proof from {from:?} failed to verify"
-            )));
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait2,
+                fault: Fault::BadProof,
+            });
         }
 
         let statement1 = dlog::Statement::<C> {
@@ -238,18 +267,22 @@ async fn do_generation<C: CSCurve>(
             statement1,
             &their_phi_proof1,
         ) {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "dlog proof from {from:?} failed to verify"
-            )));
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait2,
+                fault: Fault::BadProof,
+            });
         }
 
         big_e_j_zero.put(from, their_big_e.evaluate_zero());
         big_e += &their_big_e;
         big_f += &their_big_f;
         big_l += &their_big_l;
+        all_polys.put(from, (their_big_e, their_big_f, their_big_l));
     }
 
     // Spec 3.5 + 3.6
+    let mut all_a_b_j_i = ParticipantMap::new(&participants);
     seen.clear();
     seen.put(me);
     while !seen.full() {
@@ -258,17 +291,36 @@ async fn do_generation<C: CSCurve>(
         if !seen.put(from) {
             continue;
         }
-        a_i += &a_j_i.into();
-        b_i += &b_j_i.into();
+        let a_j_i = C::Scalar::from(a_j_i);
+        let b_j_i = C::Scalar::from(b_j_i);
+        a_i += &a_j_i;
+        b_i += &b_j_i;
+        all_a_b_j_i.put(from, (a_j_i, b_j_i));
     }
 
     // Spec 3.7
     if big_e.evaluate(&me.scalar::<C>()) != C::ProjectivePoint::generator() * a_i
         || big_f.evaluate(&me.scalar::<C>()) != C::ProjectivePoint::generator() * b_i
     {
-        return Err(ProtocolError::AssertionFailed(
-            "received bad private share".to_string(),
-        ));
+        // Only check the shares individually once we know one of them is bad.
+        for p in participants.others(me) {
+            let (their_big_e, their_big_f, _) = &all_polys[p];
+            let (a_j_i, b_j_i) = all_a_b_j_i[p];
+            if their_big_e.evaluate(&me.scalar::<C>()) != C::ProjectivePoint::generator() * a_j_i
+                || their_big_f.evaluate(&me.scalar::<C>())
+                    != C::ProjectivePoint::generator() * b_j_i
+            {
+                return Err(ProtocolError::Malicious {
+                    participant: p,
+                    round: wait3,
+                    fault: Fault::BadShare,
+                });
+            }
+        }
+        return Err(ProtocolError::Failed {
+            round: wait3,
+            fault: Fault::BadShare,
+        });
     }
 
     // Spec 3.8
@@ -324,9 +376,11 @@ async fn do_generation<C: CSCurve>(
             statement,
             &their_phi_proof,
         ) {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "dlogeq proof from {from:?} failed to verify"
-            )));
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait4,
+                fault: Fault::BadProof,
+            });
         }
 
         big_c += big_c_j;
@@ -374,6 +428,7 @@ async fn do_generation<C: CSCurve>(
     seen.clear();
     seen.put(me);
     let mut hat_big_c = hat_big_c_i;
+    let mut all_hat_big_c = ParticipantMap::new(&participants);
     while !seen.full() {
         let (from, (their_hat_big_c, their_phi_proof)): (_, (SerializablePoint<C>, _)) =
             chan.recv(wait5).await?;
@@ -390,11 +445,14 @@ async fn do_generation<C: CSCurve>(
             statement,
             &their_phi_proof,
         ) {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "dlog proof from {from:?} failed to verify"
-            )));
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait5,
+                fault: Fault::BadProof,
+            });
         }
         hat_big_c += &their_hat_big_c;
+        all_hat_big_c.put(from, their_hat_big_c);
     }
 
     // Spec 5.3
@@ -402,12 +460,14 @@ async fn do_generation<C: CSCurve>(
 
     // Spec 5.4
     if big_l.evaluate_zero() != big_c {
-        return Err(ProtocolError::AssertionFailed(
-            "final polynomial doesn't match C value".to_owned(),
-        ));
+        return Err(ProtocolError::Failed {
+            round: wait5,
+            fault: Fault::InvalidResult("final polynomial doesn't match C value"),
+        });
     }
 
     // Spec 5.5 + 5.6
+    let mut all_c_j_i = ParticipantMap::new(&participants);
     seen.clear();
     seen.put(me);
     while !seen.full() {
@@ -415,14 +475,30 @@ async fn do_generation<C: CSCurve>(
         if !seen.put(from) {
             continue;
         }
-        c_i += C::Scalar::from(c_j_i);
+        let c_j_i = C::Scalar::from(c_j_i);
+        c_i += c_j_i;
+        all_c_j_i.put(from, c_j_i);
     }
 
     // Spec 5.7
     if big_l.evaluate(&me.scalar::<C>()) != C::ProjectivePoint::generator() * c_i {
-        return Err(ProtocolError::AssertionFailed(
-            "received bad private share of c".to_string(),
-        ));
+        for p in participants.others(me) {
+            let mut their_big_l = all_polys[p].2.clone();
+            their_big_l.set_zero(all_hat_big_c[p]);
+            if their_big_l.evaluate(&me.scalar::<C>())
+                != C::ProjectivePoint::generator() * all_c_j_i[p]
+            {
+                return Err(ProtocolError::Malicious {
+                    participant: p,
+                    round: wait6,
+                    fault: Fault::BadShare,
+                });
+            }
+        }
+        return Err(ProtocolError::Failed {
+            round: wait6,
+            fault: Fault::BadShare,
+        });
     }
 
     let big_a = big_e.evaluate_zero().into();
@@ -445,15 +521,17 @@ async fn do_generation<C: CSCurve>(
     ))
 }
 
+#[allow(clippy::needless_range_loop, clippy::type_complexity)]
 async fn do_generation_many<C: CSCurve, const N: usize>(
+    mut rng: MeowRng,
     ctx: Context<'_>,
     participants: ParticipantList,
     me: Participant,
     threshold: usize,
+    prg: OtPrg,
 ) -> Result<TripleGenerationOutputMany<C>, ProtocolError> {
     assert!(N > 0);
 
-    let mut rng = OsRng;
     let mut chan = ctx.shared_channel();
     let mut transcript = Transcript::new(LABEL);
 
@@ -490,7 +568,8 @@ async fn do_generation_many<C: CSCurve, const N: usize>(
         let big_l_i = l.commit();
 
         // Spec 1.5
-        let (my_commitment, my_randomizer) = commit(&mut rng, &(&big_e_i, &big_f_i, &big_l_i));
+        let (my_commitment, my_randomizer) =
+            COMMITMENTS.commit(&mut rng, &(&big_e_i, &big_f_i, &big_l_i));
 
         my_commitments.push(my_commitment);
         my_randomizers.push(my_randomizer);
@@ -541,8 +620,10 @@ async fn do_generation_many<C: CSCurve, const N: usize>(
         let e0_v: Vec<_> = e_v.iter().map(|e| e.evaluate_zero()).collect();
         let f0_v: Vec<_> = f_v.iter().map(|f| f.evaluate_zero()).collect();
         multiplication_many::<C, N>(
+            fork_rng(&mut rng),
             ctx,
             my_confirmations.clone(),
+            prg,
             participants.clone(),
             me,
             e0_v,
@@ -644,9 +725,11 @@ async fn do_generation_many<C: CSCurve, const N: usize>(
             continue;
         }
         if confirmation != my_confirmations {
-            return Err(ProtocolError::AssertionFailed(format!(
-                "confirmation from {from:?} did not match expectation"
-            )));
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait1,
+                fault: Fault::InconsistentBroadcast,
+            });
         }
     }
 
@@ -661,6 +744,7 @@ async fn do_generation_many<C: CSCurve, const N: usize>(
         big_l_v.push(big_l_i_v[i].clone());
         big_e_j_zero_v.push(ParticipantMap::new(&participants));
     }
+    let mut all_polys_v = ParticipantMap::new(&participants);
     seen.clear();
     seen.put(me);
     while !seen.full() {
@@ -689,70 +773,99 @@ async fn do_generation_many<C: CSCurve, const N: usize>(
             continue;
         }
 
+        if [
+            their_big_e_v.len(),
+            their_big_f_v.len(),
+            their_big_l_v.len(),
+            their_randomizers.len(),
+            their_phi_proof0_v.len(),
+            their_phi_proof1_v.len(),
+        ]
+        .iter()
+        .any(|&len| len != N)
+        {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait2,
+                fault: Fault::InvalidMessage("wrong number of triples"),
+            });
+        }
+
         for i in 0..N {
             let all_commitments = &all_commitments_vec[i];
             let their_big_e = &their_big_e_v[i];
             let their_big_f = &their_big_f_v[i];
             let their_big_l = &their_big_l_v[i];
             let their_randomizer = &their_randomizers[i];
-            let their_phi_proof0 = &their_phi_proof0_v[i];
-            let their_phi_proof1 = &their_phi_proof1_v[i];
             if their_big_e.len() != threshold
                 || their_big_f.len() != threshold
                 || their_big_l.len() != threshold
             {
-                return Err(ProtocolError::AssertionFailed(format!(
-                    "polynomial from {from:?} has the wrong length"
-                )));
+                return Err(ProtocolError::Malicious {
+                    participant: from,
+                    round: wait2,
+                    fault: Fault::InvalidMessage("polynomial has the wrong length"),
+                });
             }
             if !bool::from(their_big_l.evaluate_zero().is_identity()) {
-                return Err(ProtocolError::AssertionFailed(format!(
-                    "L(0) from {from:?} is not 0"
-                )));
+                return Err(ProtocolError::Malicious {
+                    participant: from,
+                    round: wait2,
+                    fault: Fault::InvalidMessage("L(0) is not 0"),
+                });
             }
-            if !all_commitments[from].check(
+            if !COMMITMENTS.check(
+                &all_commitments[from],
                 &(&their_big_e, &their_big_f, &their_big_l),
                 their_randomizer,
             ) {
-                return Err(ProtocolError::AssertionFailed(format!(
-                    "commitment from {from:?} did not match revealed F"
-                )));
+                return Err(ProtocolError::Malicious {
+                    participant: from,
+                    round: wait2,
+                    fault: Fault::BadCommitment,
+                });
             }
-            let statement0 = dlog::Statement::<C> {
-                public: &their_big_e.evaluate_zero(),
-            };
-            if !dlog::verify(
-                &mut transcript.forked(b"dlog0", &from.bytes()),
-                statement0,
-                their_phi_proof0,
-            ) {
-                return Err(ProtocolError::AssertionFailed(format!(
-                    "dlog proof from {from:?} failed to verify"
-                )));
-            }
-
-            let statement1 = dlog::Statement::<C> {
-                public: &their_big_f.evaluate_zero(),
-            };
-            if !dlog::verify(
-                &mut transcript.forked(b"dlog1", &from.bytes()),
-                statement1,
-                their_phi_proof1,
-            ) {
-                return Err(ProtocolError::AssertionFailed(format!(
-                    "dlog proof from {from:?} failed to verify"
-                )));
-            }
-
             big_e_j_zero_v[i].put(from, their_big_e.evaluate_zero());
 
-            big_e_v[i] += &their_big_e;
-            big_f_v[i] += &their_big_f;
-            big_l_v[i] += &their_big_l;
+            big_e_v[i] += their_big_e;
+            big_f_v[i] += their_big_f;
+            big_l_v[i] += their_big_l;
         }
+
+        let their_publics0: Vec<_> = their_big_e_v.iter().map(|e| e.evaluate_zero()).collect();
+        let their_publics1: Vec<_> = their_big_f_v.iter().map(|f| f.evaluate_zero()).collect();
+        let proofs0 = their_publics0
+            .iter()
+            .zip(&their_phi_proof0_v)
+            .map(|(public, proof)| {
+                (
+                    transcript.forked(b"dlog0", &from.bytes()),
+                    dlog::Statement::<C> { public },
+                    proof,
+                )
+            });
+        let proofs1 = their_publics1
+            .iter()
+            .zip(&their_phi_proof1_v)
+            .map(|(public, proof)| {
+                (
+                    transcript.forked(b"dlog1", &from.bytes()),
+                    dlog::Statement::<C> { public },
+                    proof,
+                )
+            });
+        if !dlog::verify_batch(&mut rng, proofs0.chain(proofs1)) {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait2,
+                fault: Fault::BadProof,
+            });
+        }
+        all_polys_v.put(from, (their_big_e_v, their_big_f_v, their_big_l_v));
     }
 
     // Spec 3.5 + 3.6
+    let mut all_a_b_j_i_v = ParticipantMap::new(&participants);
     seen.clear();
     seen.put(me);
     while !seen.full() {
@@ -767,6 +880,7 @@ async fn do_generation_many<C: CSCurve, const N: usize>(
             a_i_v[i] += &(*a_j_i).into();
             b_i_v[i] += &(*b_j_i).into();
         }
+        all_a_b_j_i_v.put(from, (a_j_i_v, b_j_i_v));
     }
 
     let mut big_c_i_points = vec![];
@@ -782,9 +896,26 @@ async fn do_generation_many<C: CSCurve, const N: usize>(
         let check1 = big_e.evaluate(&me.scalar::<C>()) != C::ProjectivePoint::generator() * a_i;
         let check2 = big_f.evaluate(&me.scalar::<C>()) != C::ProjectivePoint::generator() * b_i;
         if check1 || check2 {
-            return Err(ProtocolError::AssertionFailed(
-                "received bad private share".to_string(),
-            ));
+            // Only check the shares individually once we know one of them is bad.
+            for p in participants.others(me) {
+                let (their_big_e_v, their_big_f_v, _) = &all_polys_v[p];
+                let (a_j_i_v, b_j_i_v) = &all_a_b_j_i_v[p];
+                if their_big_e_v[i].evaluate(&me.scalar::<C>())
+                    != C::ProjectivePoint::generator() * C::Scalar::from(a_j_i_v[i])
+                    || their_big_f_v[i].evaluate(&me.scalar::<C>())
+                        != C::ProjectivePoint::generator() * C::Scalar::from(b_j_i_v[i])
+                {
+                    return Err(ProtocolError::Malicious {
+                        participant: p,
+                        round: wait3,
+                        fault: Fault::BadShare,
+                    });
+                }
+            }
+            return Err(ProtocolError::Failed {
+                round: wait3,
+                fault: Fault::BadShare,
+            });
         }
         // Spec 3.8
         let big_c_i = big_f.evaluate_zero() * e.evaluate_zero();
@@ -829,29 +960,36 @@ async fn do_generation_many<C: CSCurve, const N: usize>(
         if !seen.put(from) {
             continue;
         }
-        for i in 0..N {
-            let big_e_j_zero = &big_e_j_zero_v[i];
-            let big_f = &big_f_v[i];
-
-            let big_c_j = big_c_j_v[i].to_projective();
-            let their_phi_proof = &their_phi_proofs[i];
-
-            let statement = dlogeq::Statement::<C> {
-                public0: &big_e_j_zero[from],
-                generator1: &big_f.evaluate_zero(),
-                public1: &big_c_j,
-            };
-
-            if !dlogeq::verify(
-                &mut transcript.forked(b"dlogeq0", &from.bytes()),
-                statement,
-                their_phi_proof,
-            ) {
-                return Err(ProtocolError::AssertionFailed(format!(
-                    "dlogeq proof from {from:?} failed to verify"
-                )));
-            }
-            big_c_v[i] += big_c_j;
+        if big_c_j_v.len() != N || their_phi_proofs.len() != N {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait4,
+                fault: Fault::InvalidMessage("wrong number of triples"),
+            });
+        }
+
+        let big_c_j_v: Vec<_> = big_c_j_v.iter().map(|p| p.to_projective()).collect();
+        let generators1: Vec<_> = big_f_v.iter().map(|f| f.evaluate_zero()).collect();
+        let proofs = (0..N).map(|i| {
+            (
+                transcript.forked(b"dlogeq0", &from.bytes()),
+                dlogeq::Statement::<C> {
+                    public0: &big_e_j_zero_v[i][from],
+                    generator1: &generators1[i],
+                    public1: &big_c_j_v[i],
+                },
+                &their_phi_proofs[i],
+            )
+        });
+        if !dlogeq::verify_batch(&mut rng, proofs) {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait4,
+                fault: Fault::BadProof,
+            });
+        }
+        for (big_c, big_c_j) in big_c_v.iter_mut().zip(&big_c_j_v) {
+            *big_c += big_c_j;
         }
     }
 
@@ -917,6 +1055,7 @@ async fn do_generation_many<C: CSCurve, const N: usize>(
     for i in 0..N {
         hat_big_c_v.push(hat_big_c_i_v[i]);
     }
+    let mut all_hat_big_c_v = ParticipantMap::new(&participants);
 
     while !seen.full() {
         let (from, (their_hat_big_c_i_points, their_phi_proofs)): (
@@ -926,24 +1065,39 @@ async fn do_generation_many<C: CSCurve, const N: usize>(
         if !seen.put(from) {
             continue;
         }
-        for i in 0..N {
-            let their_hat_big_c = their_hat_big_c_i_points[i].to_projective();
-            let their_phi_proof = &their_phi_proofs[i];
-
-            let statement = dlog::Statement::<C> {
-                public: &their_hat_big_c,
-            };
-            if !dlog::verify(
-                &mut transcript.forked(b"dlog2", &from.bytes()),
-                statement,
-                their_phi_proof,
-            ) {
-                return Err(ProtocolError::AssertionFailed(format!(
-                    "dlog proof from {from:?} failed to verify"
-                )));
-            }
-            hat_big_c_v[i] += &their_hat_big_c;
+        if their_hat_big_c_i_points.len() != N || their_phi_proofs.len() != N {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait5,
+                fault: Fault::InvalidMessage("wrong number of triples"),
+            });
         }
+
+        let their_hat_big_c_v: Vec<_> = their_hat_big_c_i_points
+            .iter()
+            .map(|p| p.to_projective())
+            .collect();
+        let proofs = their_hat_big_c_v
+            .iter()
+            .zip(&their_phi_proofs)
+            .map(|(public, proof)| {
+                (
+                    transcript.forked(b"dlog2", &from.bytes()),
+                    dlog::Statement::<C> { public },
+                    proof,
+                )
+            });
+        if !dlog::verify_batch(&mut rng, proofs) {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait5,
+                fault: Fault::BadProof,
+            });
+        }
+        for (hat_big_c, their_hat_big_c) in hat_big_c_v.iter_mut().zip(&their_hat_big_c_v) {
+            *hat_big_c += their_hat_big_c;
+        }
+        all_hat_big_c_v.put(from, their_hat_big_c_i_points);
     }
 
     for i in 0..N {
@@ -956,13 +1110,15 @@ async fn do_generation_many<C: CSCurve, const N: usize>(
 
         // Spec 5.4
         if big_l.evaluate_zero() != *big_c {
-            return Err(ProtocolError::AssertionFailed(
-                "final polynomial doesn't match C value".to_owned(),
-            ));
+            return Err(ProtocolError::Failed {
+                round: wait5,
+                fault: Fault::InvalidResult("final polynomial doesn't match C value"),
+            });
         }
     }
 
     // Spec 5.5 + 5.6
+    let mut all_c_j_i_v = ParticipantMap::new(&participants);
     seen.clear();
     seen.put(me);
     while !seen.full() {
@@ -974,6 +1130,7 @@ async fn do_generation_many<C: CSCurve, const N: usize>(
             let c_j_i = c_j_i_v[i];
             c_i_v[i] += C::Scalar::from(c_j_i);
         }
+        all_c_j_i_v.put(from, c_j_i_v);
     }
 
     let mut ret = vec![];
@@ -988,9 +1145,23 @@ async fn do_generation_many<C: CSCurve, const N: usize>(
         let big_c = &big_c_v[i];
 
         if big_l.evaluate(&me.scalar::<C>()) != C::ProjectivePoint::generator() * c_i {
-            return Err(ProtocolError::AssertionFailed(
-                "received bad private share of c".to_string(),
-            ));
+            for p in participants.others(me) {
+                let mut their_big_l = all_polys_v[p].2[i].clone();
+                their_big_l.set_zero(all_hat_big_c_v[p][i].to_projective());
+                if their_big_l.evaluate(&me.scalar::<C>())
+                    != C::ProjectivePoint::generator() * C::Scalar::from(all_c_j_i_v[p][i])
+                {
+                    return Err(ProtocolError::Malicious {
+                        participant: p,
+                        round: wait6,
+                        fault: Fault::BadShare,
+                    });
+                }
+            }
+            return Err(ProtocolError::Failed {
+                round: wait6,
+                fault: Fault::BadShare,
+            });
         }
         let big_a = big_e.evaluate_zero().into();
         let big_b = big_f.evaluate_zero().into();
@@ -1026,6 +1197,62 @@ pub fn generate_triple<C: CSCurve>(
     participants: &[Participant],
     me: Participant,
     threshold: usize,
+) -> Result<impl Protocol<Output = TripleGenerationOutput<C>>, InitializationError> {
+    generate_triple_with_rng(participants, me, threshold, &mut OsRng)
+}
+
+/// Like [`generate_triple`], except drawing all of the randomness from a given rng.
+///
+/// Given the same rng state and the same messages, the protocol will behave
+/// the exact same way, which is useful to resume it after a restart.
+pub fn generate_triple_with_rng<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = TripleGenerationOutput<C>>, InitializationError> {
+    generate_triple_with_prg_with_rng(participants, me, threshold, OtPrg::default(), rng)
+}
+
+/// Like [`generate_triple`], except choosing the generator used by the OT extensions.
+///
+/// Using [`OtPrg::Aes`] makes triple generation much faster on processors with
+/// AES instructions. Every participant needs to use the same generator.
+pub fn generate_triple_with_prg<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    prg: OtPrg,
+) -> Result<impl Protocol<Output = TripleGenerationOutput<C>>, InitializationError> {
+    generate_triple_with_prg_with_rng(participants, me, threshold, prg, &mut OsRng)
+}
+
+/// Like [`generate_triple_with_prg`], except drawing all of the randomness from a given rng.
+pub fn generate_triple_with_prg_with_rng<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    prg: OtPrg,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = TripleGenerationOutput<C>>, InitializationError> {
+    generate_triple_with_options(
+        participants,
+        me,
+        threshold,
+        prg,
+        FiatShamirBackend::default(),
+        rng,
+    )
+}
+
+/// Set up triple generation, with every choice it allows, as done by [`TripleConfig`](crate::TripleConfig).
+pub(crate) fn generate_triple_with_options<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    prg: OtPrg,
+    backend: FiatShamirBackend,
+    rng: &mut impl CryptoRngCore,
 ) -> Result<impl Protocol<Output = TripleGenerationOutput<C>>, InitializationError> {
     if participants.len() < 2 {
         return Err(InitializationError::BadParameters(format!(
@@ -1045,7 +1272,15 @@ pub fn generate_triple<C: CSCurve>(
     })?;
 
     let ctx = Context::new();
-    let fut = do_generation(ctx.clone(), participants, me, threshold);
+    let fut = do_generation(
+        fork_rng(rng),
+        ctx.clone(),
+        participants,
+        me,
+        threshold,
+        prg,
+        backend,
+    );
     Ok(make_protocol(ctx, fut))
 }
 
@@ -1054,6 +1289,33 @@ pub fn generate_triple_many<C: CSCurve, const N: usize>(
     participants: &[Participant],
     me: Participant,
     threshold: usize,
+) -> Result<impl Protocol<Output = TripleGenerationOutputMany<C>>, InitializationError> {
+    generate_triple_many_with_rng::<C, N>(participants, me, threshold, &mut OsRng)
+}
+
+/// As [`generate_triple_with_rng`] but for many triples at once
+pub fn generate_triple_many_with_rng<C: CSCurve, const N: usize>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = TripleGenerationOutputMany<C>>, InitializationError> {
+    generate_triple_many_with_prg_with_rng::<C, N>(
+        participants,
+        me,
+        threshold,
+        OtPrg::default(),
+        rng,
+    )
+}
+
+/// As [`generate_triple_with_prg_with_rng`] but for many triples at once
+pub fn generate_triple_many_with_prg_with_rng<C: CSCurve, const N: usize>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    prg: OtPrg,
+    rng: &mut impl CryptoRngCore,
 ) -> Result<impl Protocol<Output = TripleGenerationOutputMany<C>>, InitializationError> {
     if participants.len() < 2 {
         return Err(InitializationError::BadParameters(format!(
@@ -1073,7 +1335,8 @@ pub fn generate_triple_many<C: CSCurve, const N: usize>(
     })?;
 
     let ctx = Context::new();
-    let fut = do_generation_many::<C, N>(ctx.clone(), participants, me, threshold);
+    let fut =
+        do_generation_many::<C, N>(fork_rng(rng), ctx.clone(), participants, me, threshold, prg);
     Ok(make_protocol(ctx, fut))
 }
 
@@ -1087,7 +1350,10 @@ mod test {
         triples::generate_triple,
     };
 
-    use super::{generate_triple_many, TripleGenerationOutput, TripleGenerationOutputMany};
+    use super::{
+        generate_triple_many, generate_triple_with_prg, OtPrg, TripleGenerationOutput,
+        TripleGenerationOutputMany,
+    };
 
     #[test]
     fn test_triple_generation() -> Result<(), ProtocolError> {
@@ -1120,7 +1386,7 @@ mod test {
         let triple_pub = result[2].1 .1.clone();
 
         let participants = vec![result[0].0, result[1].0, result[2].0];
-        let triple_shares = vec![
+        let triple_shares = [
             result[0].1 .0.clone(),
             result[1].1 .0.clone(),
             result[2].1 .0.clone(),
@@ -1178,7 +1444,7 @@ mod test {
         let triple_pub = result[2].1[0].1.clone();
 
         let participants = vec![result[0].0, result[1].0, result[2].0];
-        let triple_shares = vec![
+        let triple_shares = [
             result[0].1[0].0.clone(),
             result[1].1[0].0.clone(),
             result[2].1[0].0.clone(),
@@ -1204,4 +1470,37 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_triple_generation_with_aes() -> Result<(), ProtocolError> {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = TripleGenerationOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+
+        for &p in &participants {
+            let protocol = generate_triple_with_prg(&participants, p, 2, OtPrg::Aes).unwrap();
+            protocols.push((p, Box::new(protocol)));
+        }
+
+        let result = run_protocol(protocols)?;
+        let p_list = ParticipantList::new(&participants).unwrap();
+        let interpolate = |f: &dyn Fn(usize) -> k256::Scalar| {
+            result
+                .iter()
+                .enumerate()
+                .map(|(i, (p, _))| p_list.lagrange::<Secp256k1>(*p) * f(i))
+                .sum::<k256::Scalar>()
+        };
+        let a = interpolate(&|i| result[i].1 .0.a);
+        let b = interpolate(&|i| result[i].1 .0.b);
+        let c = interpolate(&|i| result[i].1 .0.c);
+        assert_eq!(ProjectivePoint::GENERATOR * a, result[0].1 .1.big_a);
+        assert_eq!(a * b, c);
+
+        Ok(())
+    }
 }
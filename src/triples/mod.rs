@@ -28,6 +28,7 @@
 use elliptic_curve::{Field, Group};
 use rand_core::CryptoRngCore;
 use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
 use crate::{compat::CSCurve, math::Polynomial, protocol::Participant};
 
@@ -59,6 +60,22 @@ pub struct TripleShare<C: CSCurve> {
     pub c: C::Scalar,
 }
 
+impl<C: CSCurve> Zeroize for TripleShare<C> {
+    fn zeroize(&mut self) {
+        self.a.zeroize();
+        self.b.zeroize();
+        self.c.zeroize();
+    }
+}
+
+impl<C: CSCurve> Drop for TripleShare<C> {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl<C: CSCurve> ZeroizeOnDrop for TripleShare<C> {}
+
 /// Create a new triple from scratch.
 ///
 /// This can be used to generate a triple if you then trust the person running
@@ -146,11 +163,31 @@ pub fn deal_many<C: CSCurve, const N: usize>(
 }
 
 mod batch_random_ot;
-mod bits;
+pub(crate) mod bits;
 mod correlated_ot_extension;
 mod generation;
-mod mta;
-mod multiplication;
+pub(crate) mod mta;
+pub(crate) mod multiplication;
 mod random_ot_extension;
-
-pub use generation::{generate_triple, generate_triple_many, TripleGenerationOutput};
+mod sacrifice;
+mod source;
+mod store;
+mod stream;
+
+pub use bits::OtPrg;
+pub(crate) use generation::generate_triple_with_options;
+pub use generation::{
+    generate_triple, generate_triple_many, generate_triple_many_with_prg_with_rng,
+    generate_triple_many_with_rng, generate_triple_with_prg, generate_triple_with_prg_with_rng,
+    generate_triple_with_rng, TripleGenerationOutput,
+};
+pub use mta::{mta, mta_with_rng};
+pub use multiplication::{multiply, multiply_with_rng};
+pub use random_ot_extension::{
+    random_ot_receiver, random_ot_receiver_with_rng, random_ot_sender, random_ot_sender_with_rng,
+    RandomOTExtensionReceiverOut, RandomOTExtensionSenderOut,
+};
+pub use sacrifice::verify_triple;
+pub use source::TripleSource;
+pub use store::{FileTripleStore, FileTripleStoreError, StoredTriple, TripleId, TripleStore};
+pub use stream::{generate_triple_stream, generate_triple_stream_with_rng, TripleStream};
@@ -1,19 +1,30 @@
 use elliptic_curve::{Field, ScalarPrimitive};
 use magikitten::MeowRng;
-use rand_core::{OsRng, RngCore};
+use rand_core::{CryptoRngCore, OsRng, RngCore};
 use serde::{Deserialize, Serialize};
 use std::slice::Iter;
 use subtle::{Choice, ConditionallySelectable};
 
 use crate::{
     compat::CSCurve,
+    constants::SECURITY_PARAMETER,
+    crypto::{fork_rng, hash},
     protocol::{
         internal::{make_protocol, Context, PrivateChannel},
-        run_two_party_protocol, Participant, ProtocolError,
+        run_two_party_protocol, Fault, InitializationError, Participant, Protocol, ProtocolError,
     },
+    serde::{Schema, Wire},
 };
 
-struct MTAScalars<C: CSCurve>(Vec<(ScalarPrimitive<C>, ScalarPrimitive<C>)>);
+use super::{
+    batch_random_ot::{batch_random_ot_receiver, batch_random_ot_sender},
+    bits::OtPrg,
+    random_ot_extension::{
+        random_ot_extension_receiver, random_ot_extension_sender, RandomOtExtensionParams,
+    },
+};
+
+pub(crate) struct MTAScalars<C: CSCurve>(Vec<(ScalarPrimitive<C>, ScalarPrimitive<C>)>);
 
 impl<C: CSCurve> MTAScalars<C> {
     const SCALAR_LEN: usize = (C::BITS + 7) >> 3;
@@ -56,8 +67,16 @@ impl<'de, C: CSCurve> Deserialize<'de> for MTAScalars<C> {
     }
 }
 
+// The pairs of scalars are concatenated into a single byte string.
+impl<C: CSCurve> Wire for MTAScalars<C> {
+    fn schema() -> Schema {
+        Schema::Bytes
+    }
+}
+
 /// The sender for multiplicative to additive conversion.
 pub async fn mta_sender<C: CSCurve>(
+    mut rng: MeowRng,
     mut chan: PrivateChannel,
     v: Vec<(C::Scalar, C::Scalar)>,
     a: C::Scalar,
@@ -65,7 +84,7 @@ pub async fn mta_sender<C: CSCurve>(
     let size = v.len();
 
     // Step 1
-    let delta: Vec<_> = (0..size).map(|_| C::Scalar::random(&mut OsRng)).collect();
+    let delta: Vec<_> = (0..size).map(|_| C::Scalar::random(&mut rng)).collect();
 
     // Step 2
     let c: MTAScalars<C> = MTAScalars(
@@ -97,6 +116,7 @@ pub async fn mta_sender<C: CSCurve>(
 
 /// The receiver for multiplicative to additive conversion.
 pub async fn mta_receiver<C: CSCurve>(
+    mut rng: MeowRng,
     mut chan: PrivateChannel,
     tv: Vec<(Choice, C::Scalar)>,
     b: C::Scalar,
@@ -107,9 +127,10 @@ pub async fn mta_receiver<C: CSCurve>(
     let wait0 = chan.next_waitpoint();
     let c: MTAScalars<C> = chan.recv(wait0).await?;
     if c.len() != tv.len() {
-        return Err(ProtocolError::AssertionFailed(
-            "length of c was incorrect".to_owned(),
-        ));
+        return Err(ProtocolError::Failed {
+            round: wait0,
+            fault: Fault::InvalidMessage("c has the wrong length"),
+        });
     }
     let mut m = tv.iter().zip(c.iter()).map(|((t_i, v_i), (c0_i, c1_i))| {
         C::Scalar::conditional_select(&(*c0_i).into(), &(*c1_i).into(), *t_i) - v_i
@@ -117,7 +138,7 @@ pub async fn mta_receiver<C: CSCurve>(
 
     // Step 4
     let mut seed = [0u8; 32];
-    OsRng.fill_bytes(&mut seed);
+    rng.fill_bytes(&mut seed);
     let mut prng = MeowRng::new(&seed);
     let chi: Vec<C::Scalar> = (1..size).map(|_| C::Scalar::random(&mut prng)).collect();
 
@@ -143,6 +164,91 @@ pub async fn mta_receiver<C: CSCurve>(
     Ok(beta)
 }
 
+const LABEL: &[u8] = b"cait-sith v0.8.0 mta";
+
+async fn do_mta<C: CSCurve>(
+    mut rng: MeowRng,
+    ctx: Context<'_>,
+    me: Participant,
+    other: Participant,
+    x: C::Scalar,
+) -> Result<C::Scalar, ProtocolError> {
+    let chan = ctx.private_channel(me, other);
+    // The setup is fresh for each run, so the session only needs to name the parties.
+    let sid = hash(&(LABEL, me.min(other), me.max(other)));
+    let batch_size = C::BITS + SECURITY_PARAMETER;
+    if other < me {
+        let (delta, k) =
+            batch_random_ot_receiver::<C>(fork_rng(&mut rng), ctx.clone(), chan.child(0)).await?;
+        let v = random_ot_extension_sender::<C>(
+            fork_rng(&mut rng),
+            chan.child(1),
+            RandomOtExtensionParams {
+                sid: sid.as_ref(),
+                prg: OtPrg::default(),
+                batch_size,
+            },
+            delta,
+            &k,
+        )
+        .await?;
+        mta_sender::<C>(rng, chan.child(2), v, x).await
+    } else {
+        let (k0, k1) =
+            batch_random_ot_sender::<C>(fork_rng(&mut rng), ctx.clone(), chan.child(0)).await?;
+        let tv = random_ot_extension_receiver::<C>(
+            fork_rng(&mut rng),
+            chan.child(1),
+            RandomOtExtensionParams {
+                sid: sid.as_ref(),
+                prg: OtPrg::default(),
+                batch_size,
+            },
+            &k0,
+            &k1,
+        )
+        .await?;
+        mta_receiver::<C>(rng, chan.child(2), tv, x).await
+    }
+}
+
+/// The multiplicative to additive conversion protocol, between two participants.
+///
+/// Each participant has a secret scalar `x`, and they end up with additive
+/// shares of the product of their secrets: the outputs of both participants
+/// sum to `x_me * x_other`, while neither learns anything about the other's input.
+///
+/// This runs a fresh oblivious transfer setup, so it needs no prior setup.
+///
+/// A malicious participant can't learn the other's input, but they can
+/// make the outputs sum to something other than the product.
+/// Protocols built on this need to check the result if that matters,
+/// as triple generation does.
+pub fn mta<C: CSCurve>(
+    me: Participant,
+    other: Participant,
+    x: C::Scalar,
+) -> Result<impl Protocol<Output = C::Scalar>, InitializationError> {
+    mta_with_rng::<C>(me, other, x, &mut OsRng)
+}
+
+/// Like [mta()], except drawing all of the randomness from a given rng.
+pub fn mta_with_rng<C: CSCurve>(
+    me: Participant,
+    other: Participant,
+    x: C::Scalar,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = C::Scalar>, InitializationError> {
+    if me == other {
+        return Err(InitializationError::BadParameters(
+            "the other participant must be different from this participant".to_string(),
+        ));
+    }
+    let ctx = Context::new();
+    let fut = do_mta::<C>(fork_rng(rng), ctx.clone(), me, other, x);
+    Ok(make_protocol(ctx, fut))
+}
+
 /// Run the multiplicative to additive protocol
 #[allow(dead_code, clippy::type_complexity)]
 fn run_mta<C: CSCurve>(
@@ -159,11 +265,11 @@ fn run_mta<C: CSCurve>(
         r,
         &mut make_protocol(
             ctx_s.clone(),
-            mta_sender::<C>(ctx_s.private_channel(s, r), v, a),
+            mta_sender::<C>(fork_rng(&mut OsRng), ctx_s.private_channel(s, r), v, a),
         ),
         &mut make_protocol(
             ctx_r.clone(),
-            mta_receiver::<C>(ctx_r.private_channel(r, s), tv, b),
+            mta_receiver::<C>(fork_rng(&mut OsRng), ctx_r.private_channel(r, s), tv, b),
         ),
     )
 }
@@ -174,9 +280,8 @@ mod test {
     use k256::{Scalar, Secp256k1};
     use rand_core::RngCore;
 
-    use crate::constants::SECURITY_PARAMETER;
-
     use super::*;
+    use crate::protocol::run_protocol;
 
     #[test]
     fn test_mta() -> Result<(), ProtocolError> {
@@ -206,4 +311,22 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_mta_protocol() -> Result<(), ProtocolError> {
+        let participants = [Participant::from(0u32), Participant::from(1u32)];
+        let x = participants.map(|_| Scalar::generate_biased(&mut OsRng));
+
+        let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = Scalar>>)> = Vec::new();
+        for (i, p) in participants.iter().enumerate() {
+            let protocol = mta::<Secp256k1>(*p, participants[1 - i], x[i]).unwrap();
+            protocols.push((*p, Box::new(protocol)));
+        }
+        let result = run_protocol(protocols)?;
+
+        assert_eq!(result[0].1 + result[1].1, x[0] * x[1]);
+        assert!(mta::<Secp256k1>(participants[0], participants[0], x[0]).is_err());
+
+        Ok(())
+    }
 }
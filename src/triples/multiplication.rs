@@ -2,17 +2,21 @@ use crate::triples::batch_random_ot::{batch_random_ot_receiver_many, batch_rando
 use crate::{
     compat::CSCurve,
     constants::SECURITY_PARAMETER,
-    crypto::Digest,
+    crypto::{fork_rng, hash, Digest},
     participants::ParticipantList,
     protocol::{
-        internal::{Context, PrivateChannel},
-        Participant, ProtocolError,
+        internal::{make_protocol, Context, PrivateChannel},
+        InitializationError, Participant, Protocol, ProtocolError,
     },
+    trace,
 };
+use magikitten::MeowRng;
+use rand_core::{CryptoRngCore, OsRng};
 use std::sync::Arc;
 
 use super::{
     batch_random_ot::{batch_random_ot_receiver, batch_random_ot_sender},
+    bits::OtPrg,
     mta::{mta_receiver, mta_sender},
     random_ot_extension::{
         random_ot_extension_receiver, random_ot_extension_sender, RandomOtExtensionParams,
@@ -20,32 +24,49 @@ use super::{
 };
 
 pub async fn multiplication_sender<'a, C: CSCurve>(
+    mut rng: MeowRng,
     ctx: Context<'a>,
     chan: PrivateChannel,
     sid: &[u8],
+    prg: OtPrg,
     a_i: &C::Scalar,
     b_i: &C::Scalar,
 ) -> Result<C::Scalar, ProtocolError> {
     // First, run a fresh batch random OT ourselves
-    let (delta, k) = batch_random_ot_receiver::<C>(ctx.clone(), chan.child(0)).await?;
+    let (delta, k) = trace::instrument!(
+        batch_random_ot_receiver::<C>(fork_rng(&mut rng), ctx.clone(), chan.child(0)),
+        "batch random ot"
+    )
+    .await?;
 
     let batch_size = C::BITS + SECURITY_PARAMETER;
     // Step 1
-    let mut res0 = random_ot_extension_sender::<C>(
-        chan.child(1),
-        RandomOtExtensionParams {
-            sid,
-            batch_size: 2 * batch_size,
-        },
-        delta,
-        &k,
+    let mut res0 = trace::instrument!(
+        random_ot_extension_sender::<C>(
+            fork_rng(&mut rng),
+            chan.child(1),
+            RandomOtExtensionParams {
+                sid,
+                prg,
+                batch_size: 2 * batch_size,
+            },
+            delta,
+            &k,
+        ),
+        "random ot extension"
     )
     .await?;
     let res1 = res0.split_off(batch_size);
 
     // Step 2
-    let task0 = ctx.spawn(mta_sender::<C>(chan.child(2), res0, *a_i));
-    let task1 = ctx.spawn(mta_sender::<C>(chan.child(3), res1, *b_i));
+    let task0 = ctx.spawn(trace::instrument!(
+        mta_sender::<C>(fork_rng(&mut rng), chan.child(2), res0, *a_i,),
+        "mta"
+    ));
+    let task1 = ctx.spawn(trace::instrument!(
+        mta_sender::<C>(fork_rng(&mut rng), chan.child(3), res1, *b_i,),
+        "mta"
+    ));
 
     // Step 3
     let gamma0 = ctx.run(task0).await?;
@@ -55,16 +76,22 @@ pub async fn multiplication_sender<'a, C: CSCurve>(
 }
 
 pub async fn multiplication_sender_many<'a, C: CSCurve, const N: usize>(
+    mut rng: MeowRng,
     ctx: Context<'a>,
     chan: PrivateChannel,
     sid: &[Digest],
+    prg: OtPrg,
     a_iv: &[C::Scalar],
     b_iv: &[C::Scalar],
 ) -> Result<Vec<C::Scalar>, ProtocolError> {
     assert!(N > 0);
     let mut ret = vec![];
     // First, run a fresh batch random OT ourselves
-    let dkv = batch_random_ot_receiver_many::<C, N>(ctx.clone(), chan.child(0)).await?;
+    let dkv = trace::instrument!(
+        batch_random_ot_receiver_many::<C, N>(fork_rng(&mut rng), ctx.clone(), chan.child(0)),
+        "batch random ot"
+    )
+    .await?;
     for i in 0..N {
         let (delta, k) = &dkv[i];
         let a_i = &a_iv[i];
@@ -72,21 +99,32 @@ pub async fn multiplication_sender_many<'a, C: CSCurve, const N: usize>(
 
         let batch_size = C::BITS + SECURITY_PARAMETER;
         // Step 1
-        let mut res0 = random_ot_extension_sender::<C>(
-            chan.child(1),
-            RandomOtExtensionParams {
-                sid: sid[i].as_ref(),
-                batch_size: 2 * batch_size,
-            },
-            *delta,
-            k,
+        let mut res0 = trace::instrument!(
+            random_ot_extension_sender::<C>(
+                fork_rng(&mut rng),
+                chan.child(1),
+                RandomOtExtensionParams {
+                    sid: sid[i].as_ref(),
+                    prg,
+                    batch_size: 2 * batch_size,
+                },
+                *delta,
+                k,
+            ),
+            "random ot extension"
         )
         .await?;
         let res1 = res0.split_off(batch_size);
 
         // Step 2
-        let task0 = ctx.spawn(mta_sender::<C>(chan.child(2), res0, *a_i));
-        let task1 = ctx.spawn(mta_sender::<C>(chan.child(3), res1, *b_i));
+        let task0 = ctx.spawn(trace::instrument!(
+            mta_sender::<C>(fork_rng(&mut rng), chan.child(2), res0, *a_i,),
+            "mta"
+        ));
+        let task1 = ctx.spawn(trace::instrument!(
+            mta_sender::<C>(fork_rng(&mut rng), chan.child(3), res1, *b_i,),
+            "mta"
+        ));
 
         // Step 3
         let gamma0 = ctx.run(task0).await?;
@@ -98,32 +136,49 @@ pub async fn multiplication_sender_many<'a, C: CSCurve, const N: usize>(
 }
 
 pub async fn multiplication_receiver<'a, C: CSCurve>(
+    mut rng: MeowRng,
     ctx: Context<'a>,
     chan: PrivateChannel,
     sid: &[u8],
+    prg: OtPrg,
     a_i: &C::Scalar,
     b_i: &C::Scalar,
 ) -> Result<C::Scalar, ProtocolError> {
     // First, run a fresh batch random OT ourselves
-    let (k0, k1) = batch_random_ot_sender::<C>(ctx.clone(), chan.child(0)).await?;
+    let (k0, k1) = trace::instrument!(
+        batch_random_ot_sender::<C>(fork_rng(&mut rng), ctx.clone(), chan.child(0)),
+        "batch random ot"
+    )
+    .await?;
 
     let batch_size = C::BITS + SECURITY_PARAMETER;
     // Step 1
-    let mut res0 = random_ot_extension_receiver::<C>(
-        chan.child(1),
-        RandomOtExtensionParams {
-            sid,
-            batch_size: 2 * batch_size,
-        },
-        &k0,
-        &k1,
+    let mut res0 = trace::instrument!(
+        random_ot_extension_receiver::<C>(
+            fork_rng(&mut rng),
+            chan.child(1),
+            RandomOtExtensionParams {
+                sid,
+                prg,
+                batch_size: 2 * batch_size,
+            },
+            &k0,
+            &k1,
+        ),
+        "random ot extension"
     )
     .await?;
     let res1 = res0.split_off(batch_size);
 
     // Step 2
-    let task0 = ctx.spawn(mta_receiver::<C>(chan.child(2), res0, *b_i));
-    let task1 = ctx.spawn(mta_receiver::<C>(chan.child(3), res1, *a_i));
+    let task0 = ctx.spawn(trace::instrument!(
+        mta_receiver::<C>(fork_rng(&mut rng), chan.child(2), res0, *b_i,),
+        "mta"
+    ));
+    let task1 = ctx.spawn(trace::instrument!(
+        mta_receiver::<C>(fork_rng(&mut rng), chan.child(3), res1, *a_i,),
+        "mta"
+    ));
 
     // Step 3
     let gamma0 = ctx.run(task0).await?;
@@ -133,16 +188,22 @@ pub async fn multiplication_receiver<'a, C: CSCurve>(
 }
 
 pub async fn multiplication_receiver_many<'a, C: CSCurve, const N: usize>(
+    mut rng: MeowRng,
     ctx: Context<'a>,
     chan: PrivateChannel,
     sid: &[Digest],
+    prg: OtPrg,
     a_iv: &[C::Scalar],
     b_iv: &[C::Scalar],
 ) -> Result<Vec<C::Scalar>, ProtocolError> {
     assert!(N > 0);
     let mut ret = vec![];
     // First, run a fresh batch random OT ourselves
-    let dkv = batch_random_ot_sender_many::<C, N>(ctx.clone(), chan.child(0)).await?;
+    let dkv = trace::instrument!(
+        batch_random_ot_sender_many::<C, N>(fork_rng(&mut rng), ctx.clone(), chan.child(0)),
+        "batch random ot"
+    )
+    .await?;
     for i in 0..N {
         let (k0, k1) = &dkv[i];
         let a_i = &a_iv[i];
@@ -150,21 +211,32 @@ pub async fn multiplication_receiver_many<'a, C: CSCurve, const N: usize>(
 
         let batch_size = C::BITS + SECURITY_PARAMETER;
         // Step 1
-        let mut res0 = random_ot_extension_receiver::<C>(
-            chan.child(1),
-            RandomOtExtensionParams {
-                sid: sid[i].as_ref(),
-                batch_size: 2 * batch_size,
-            },
-            k0,
-            k1,
+        let mut res0 = trace::instrument!(
+            random_ot_extension_receiver::<C>(
+                fork_rng(&mut rng),
+                chan.child(1),
+                RandomOtExtensionParams {
+                    sid: sid[i].as_ref(),
+                    prg,
+                    batch_size: 2 * batch_size,
+                },
+                k0,
+                k1,
+            ),
+            "random ot extension"
         )
         .await?;
         let res1 = res0.split_off(batch_size);
 
         // Step 2
-        let task0 = ctx.spawn(mta_receiver::<C>(chan.child(2), res0, *b_i));
-        let task1 = ctx.spawn(mta_receiver::<C>(chan.child(3), res1, *a_i));
+        let task0 = ctx.spawn(trace::instrument!(
+            mta_receiver::<C>(fork_rng(&mut rng), chan.child(2), res0, *b_i,),
+            "mta"
+        ));
+        let task1 = ctx.spawn(trace::instrument!(
+            mta_receiver::<C>(fork_rng(&mut rng), chan.child(3), res1, *a_i,),
+            "mta"
+        ));
 
         // Step 3
         let gamma0 = ctx.run(task0).await?;
@@ -175,9 +247,12 @@ pub async fn multiplication_receiver_many<'a, C: CSCurve, const N: usize>(
     Ok(ret)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn multiplication<C: CSCurve>(
+    mut rng: MeowRng,
     ctx: Context<'_>,
     sid: Digest,
+    prg: OtPrg,
     participants: ParticipantList,
     me: Participant,
     a_i: C::Scalar,
@@ -188,13 +263,16 @@ pub async fn multiplication<C: CSCurve>(
         let fut = {
             let ctx = ctx.clone();
             let chan = ctx.private_channel(me, p);
-            async move {
+            let rng = fork_rng(&mut rng);
+            let fut = async move {
                 if p < me {
-                    multiplication_sender::<C>(ctx, chan, sid.as_ref(), &a_i, &b_i).await
+                    multiplication_sender::<C>(rng, ctx, chan, sid.as_ref(), prg, &a_i, &b_i).await
                 } else {
-                    multiplication_receiver::<C>(ctx, chan, sid.as_ref(), &a_i, &b_i).await
+                    multiplication_receiver::<C>(rng, ctx, chan, sid.as_ref(), prg, &a_i, &b_i)
+                        .await
                 }
-            }
+            };
+            trace::instrument!(fut, "multiplication", peer = u32::from(p))
         };
         tasks.push(ctx.spawn(fut));
     }
@@ -205,9 +283,12 @@ pub async fn multiplication<C: CSCurve>(
     Ok(out)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn multiplication_many<C: CSCurve, const N: usize>(
+    mut rng: MeowRng,
     ctx: Context<'_>,
     sid: Vec<Digest>,
+    prg: OtPrg,
     participants: ParticipantList,
     me: Participant,
     av_iv: Vec<C::Scalar>,
@@ -225,27 +306,33 @@ pub async fn multiplication_many<C: CSCurve, const N: usize>(
         let fut = {
             let ctx = ctx.clone();
             let chan = ctx.private_channel(me, p);
-            async move {
+            let rng = fork_rng(&mut rng);
+            let fut = async move {
                 if p < me {
                     multiplication_sender_many::<C, N>(
+                        rng,
                         ctx,
                         chan,
                         sid_arc.as_slice(),
+                        prg,
                         av_iv_arc.as_slice(),
                         bv_iv_arc.as_slice(),
                     )
                     .await
                 } else {
                     multiplication_receiver_many::<C, N>(
+                        rng,
                         ctx,
                         chan,
                         sid_arc.as_slice(),
+                        prg,
                         av_iv_arc.as_slice(),
                         bv_iv_arc.as_slice(),
                     )
                     .await
                 }
-            }
+            };
+            trace::instrument!(fut, "multiplication", peer = u32::from(p))
         };
         tasks.push(ctx.spawn(fut));
     }
@@ -265,13 +352,73 @@ pub async fn multiplication_many<C: CSCurve, const N: usize>(
     Ok(outs)
 }
 
+const LABEL: &[u8] = b"cait-sith v0.8.0 multiplication";
+
+/// The multiplication protocol, turning additive shares of two secrets into
+/// additive shares of their product.
+///
+/// Each participant has additive shares `a_i` and `b_i`, of secrets `a` and `b`,
+/// and ends up with an additive share of `a * b`. Each pair of participants
+/// runs a fresh oblivious transfer setup, followed by two conversions, as in [mta()](super::mta).
+///
+/// Like those conversions, a malicious participant can't learn anything about the
+/// shares of others, but they can make the result wrong, so protocols built
+/// on this need to check it if that matters, as triple generation does.
+pub fn multiply<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    a_i: C::Scalar,
+    b_i: C::Scalar,
+) -> Result<impl Protocol<Output = C::Scalar>, InitializationError> {
+    multiply_with_rng::<C>(participants, me, a_i, b_i, &mut OsRng)
+}
+
+/// Like [multiply()], except drawing all of the randomness from a given rng.
+pub fn multiply_with_rng<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    a_i: C::Scalar,
+    b_i: C::Scalar,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = C::Scalar>, InitializationError> {
+    if participants.len() < 2 {
+        return Err(InitializationError::BadParameters(format!(
+            "participant count cannot be < 2, found: {}",
+            participants.len()
+        )));
+    };
+    let participants = ParticipantList::new(participants).ok_or_else(|| {
+        InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
+    })?;
+    if !participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain this participant".to_string(),
+        ));
+    }
+
+    // The setups are fresh for each run, so the session only needs to name the parties.
+    let sid = hash(&(LABEL, &participants));
+    let ctx = Context::new();
+    let fut = multiplication::<C>(
+        fork_rng(rng),
+        ctx.clone(),
+        sid,
+        OtPrg::default(),
+        participants,
+        me,
+        a_i,
+        b_i,
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
 #[cfg(test)]
 mod test {
     use k256::{Scalar, Secp256k1};
     use rand_core::OsRng;
 
     use crate::{
-        crypto::hash,
+        crypto::{fork_rng, hash},
         participants::ParticipantList,
         protocol::{
             internal::{make_protocol, Context},
@@ -279,7 +426,7 @@ mod test {
         },
     };
 
-    use super::multiplication;
+    use super::{multiplication, multiply, OtPrg};
 
     #[test]
     fn test_multiplication() -> Result<(), ProtocolError> {
@@ -310,8 +457,10 @@ mod test {
             let prot = make_protocol(
                 ctx.clone(),
                 multiplication::<Secp256k1>(
+                    fork_rng(&mut OsRng),
                     ctx,
                     sid,
+                    OtPrg::default(),
                     ParticipantList::new(&participants).unwrap(),
                     *p,
                     a_i,
@@ -330,4 +479,29 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_multiply() -> Result<(), ProtocolError> {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let a: Vec<Scalar> = participants
+            .iter()
+            .map(|_| Scalar::generate_biased(&mut OsRng))
+            .collect();
+        let b: Vec<Scalar> = participants
+            .iter()
+            .map(|_| Scalar::generate_biased(&mut OsRng))
+            .collect();
+
+        let mut protocols: Vec<(Participant, Box<dyn Protocol<Output = Scalar>>)> = Vec::new();
+        for (i, p) in participants.iter().enumerate() {
+            let protocol = multiply::<Secp256k1>(&participants, *p, a[i], b[i]).unwrap();
+            protocols.push((*p, Box::new(protocol)));
+        }
+        let result = run_protocol(protocols)?;
+
+        let c = result.iter().fold(Scalar::ZERO, |acc, (_, c_i)| acc + c_i);
+        assert_eq!((a[0] + a[1]) * (b[0] + b[1]), c);
+
+        Ok(())
+    }
 }
@@ -1,20 +1,24 @@
 use ck_meow::Meow;
 use elliptic_curve::CurveArithmetic;
 use magikitten::MeowRng;
-use rand_core::{OsRng, RngCore};
+use rand_core::{CryptoRngCore, OsRng, RngCore};
 use subtle::{Choice, ConditionallySelectable, ConstantTimeEq};
+use zeroize::Zeroize;
 
 use crate::{
     compat::CSCurve,
     constants::SECURITY_PARAMETER,
+    crypto::{fork_rng, hash},
+    parallel::par_map,
     protocol::{
         internal::{make_protocol, Context, PrivateChannel},
-        run_two_party_protocol, Participant, ProtocolError,
+        run_two_party_protocol, Fault, InitializationError, Participant, Protocol, ProtocolError,
     },
 };
 
 use super::{
-    bits::{BitMatrix, BitVector, ChoiceVector, DoubleBitVector, SquareBitMatrix},
+    batch_random_ot::{batch_random_ot_receiver, batch_random_ot_sender},
+    bits::{BitMatrix, BitRows, BitVector, ChoiceVector, DoubleBitVector, OtPrg, SquareBitMatrix},
     correlated_ot_extension::{correlated_ot_receiver, correlated_ot_sender, CorrelatedOtParams},
 };
 
@@ -29,7 +33,9 @@ fn hash_to_scalar<C: CSCurve>(i: usize, v: &BitVector) -> C::Scalar {
     meow.prf(&mut seed, false);
     // Could in theory avoid one PRF call by using a more direct RNG wrapper
     // over the prf function, but oh well.
-    C::sample_scalar_constant_time(&mut MeowRng::new(&seed))
+    let out = C::sample_scalar_constant_time(&mut MeowRng::new(&seed));
+    seed.zeroize();
+    out
 }
 
 fn adjust_size(size: usize) -> usize {
@@ -46,6 +52,7 @@ fn adjust_size(size: usize) -> usize {
 #[derive(Debug, Clone, Copy)]
 pub struct RandomOtExtensionParams<'sid> {
     pub sid: &'sid [u8],
+    pub prg: OtPrg,
     pub batch_size: usize,
 }
 
@@ -59,6 +66,7 @@ pub type RandomOTExtensionSenderOut<C> = Vec<(
 pub type RandomOTExtensionReceiverOut<C> = Vec<(Choice, <C as CurveArithmetic>::Scalar)>;
 
 pub async fn random_ot_extension_sender<C: CSCurve>(
+    mut rng: MeowRng,
     mut chan: PrivateChannel,
     params: RandomOtExtensionParams<'_>,
     delta: BitVector,
@@ -71,6 +79,7 @@ pub async fn random_ot_extension_sender<C: CSCurve>(
         chan.child(0),
         CorrelatedOtParams {
             sid: params.sid,
+            prg: params.prg,
             batch_size: adjusted_size,
         },
         delta,
@@ -80,7 +89,7 @@ pub async fn random_ot_extension_sender<C: CSCurve>(
 
     // Step 5
     let mut seed = [0u8; 32];
-    OsRng.fill_bytes(&mut seed);
+    rng.fill_bytes(&mut seed);
     let wait0 = chan.next_waitpoint();
     chan.send(wait0, &seed).await;
 
@@ -96,39 +105,46 @@ pub async fn random_ot_extension_sender<C: CSCurve>(
 
     // Step 10
     if small_t.len() != SECURITY_PARAMETER {
-        return Err(ProtocolError::AssertionFailed(
-            "small t of incorrect length".to_owned(),
-        ));
+        return Err(ProtocolError::Failed {
+            round: wait1,
+            fault: Fault::InvalidMessage("small t has the wrong length"),
+        });
     }
 
-    for (j, small_t_j) in small_t.iter().enumerate() {
+    let q_transposed = q.transpose();
+    let q_columns: Vec<BitRows> = q_transposed.chunks(mu).collect();
+    let checks = par_map(&q_columns, |j, q_j| {
         let delta_j = Choice::from(delta.bit(j) as u8);
 
         let mut small_q_j = DoubleBitVector::zero();
-        for (q_i, chi_i) in q.column_chunks(j).zip(chi.iter()) {
+        for (q_i, chi_i) in q_j.iter().zip(chi.iter()) {
             small_q_j ^= q_i.gf_mul(chi_i);
         }
 
         let delta_j_x =
             DoubleBitVector::conditional_select(&DoubleBitVector::zero(), &small_x, delta_j);
-        if !bool::from(small_q_j.ct_eq(&(small_t_j ^ delta_j_x))) {
-            return Err(ProtocolError::AssertionFailed("q check failed".to_owned()));
-        }
+        small_q_j.ct_eq(&(small_t[j] ^ delta_j_x))
+    });
+    if !bool::from(checks.into_iter().fold(Choice::from(1), |acc, c| acc & c)) {
+        return Err(ProtocolError::Failed {
+            round: wait1,
+            fault: Fault::InvalidMessage("q check failed"),
+        });
     }
 
     // Step 14
-    let mut out = Vec::with_capacity(params.batch_size);
-
-    for (i, q_i) in q.rows().take(params.batch_size).enumerate() {
+    let q_rows: Vec<BitVector> = q.rows().take(params.batch_size).collect();
+    let out = par_map(&q_rows, |i, q_i| {
         let v0_i = hash_to_scalar::<C>(i, q_i);
         let v1_i = hash_to_scalar::<C>(i, &(q_i ^ delta));
-        out.push((v0_i, v1_i))
-    }
+        (v0_i, v1_i)
+    });
 
     Ok(out)
 }
 
 pub async fn random_ot_extension_receiver<C: CSCurve>(
+    mut rng: MeowRng,
     mut chan: PrivateChannel,
     params: RandomOtExtensionParams<'_>,
     k0: &SquareBitMatrix,
@@ -137,7 +153,7 @@ pub async fn random_ot_extension_receiver<C: CSCurve>(
     let adjusted_size = adjust_size(params.batch_size);
 
     // Step 1
-    let b = ChoiceVector::random(&mut OsRng, adjusted_size);
+    let b = ChoiceVector::random(&mut rng, adjusted_size);
     let x: BitMatrix = b
         .bits()
         .map(|b_i| BitVector::conditional_select(&BitVector::zero(), &!BitVector::zero(), b_i))
@@ -148,6 +164,7 @@ pub async fn random_ot_extension_receiver<C: CSCurve>(
         chan.child(0),
         CorrelatedOtParams {
             sid: params.sid,
+            prg: params.prg,
             batch_size: adjusted_size,
         },
         k0,
@@ -172,28 +189,23 @@ pub async fn random_ot_extension_receiver<C: CSCurve>(
     for (b_i, chi_i) in b.chunks().zip(chi.iter()) {
         small_x.xor_mut(&b_i.gf_mul(chi_i));
     }
-    let small_t: Vec<_> = (0..SECURITY_PARAMETER)
-        .map(|j| {
-            let mut small_t_j = DoubleBitVector::zero();
-            for (t_i, chi_i) in t.column_chunks(j).zip(chi.iter()) {
-                small_t_j ^= t_i.gf_mul(chi_i);
-            }
-            small_t_j
-        })
-        .collect();
+    let t_transposed = t.transpose();
+    let t_columns: Vec<BitRows> = t_transposed.chunks(mu).collect();
+    let small_t = par_map(&t_columns, |_, t_j| {
+        let mut small_t_j = DoubleBitVector::zero();
+        for (t_i, chi_i) in t_j.iter().zip(chi.iter()) {
+            small_t_j ^= t_i.gf_mul(chi_i);
+        }
+        small_t_j
+    });
 
     // Step 11
     let wait1 = chan.next_waitpoint();
     chan.send(wait1, &(small_x, small_t)).await;
 
     // Step 15
-    let out: Vec<_> = b
-        .bits()
-        .zip(t.rows())
-        .take(params.batch_size)
-        .enumerate()
-        .map(|(i, (b_i, t_i))| (b_i, hash_to_scalar::<C>(i, t_i)))
-        .collect();
+    let b_t: Vec<(Choice, BitVector)> = b.bits().zip(t.rows()).take(params.batch_size).collect();
+    let out = par_map(&b_t, |i, (b_i, t_i)| (*b_i, hash_to_scalar::<C>(i, t_i)));
 
     Ok(out)
 }
@@ -217,22 +229,165 @@ fn run_random_ot<C: CSCurve>(
     let ctx_s = Context::new();
     let ctx_r = Context::new();
 
-    let params = RandomOtExtensionParams { sid, batch_size };
+    let params = RandomOtExtensionParams {
+        sid,
+        prg: OtPrg::default(),
+        batch_size,
+    };
 
     run_two_party_protocol(
         s,
         r,
         &mut make_protocol(
             ctx_s.clone(),
-            random_ot_extension_sender::<C>(ctx_s.private_channel(s, r), params, delta, k),
+            random_ot_extension_sender::<C>(
+                fork_rng(&mut OsRng),
+                ctx_s.private_channel(s, r),
+                params,
+                delta,
+                k,
+            ),
         ),
         &mut make_protocol(
             ctx_r.clone(),
-            random_ot_extension_receiver::<C>(ctx_r.private_channel(r, s), params, k0, k1),
+            random_ot_extension_receiver::<C>(
+                fork_rng(&mut OsRng),
+                ctx_r.private_channel(r, s),
+                params,
+                k0,
+                k1,
+            ),
         ),
     )
 }
 
+const LABEL: &[u8] = b"cait-sith v0.8.0 random OT";
+
+fn check_ot_parameters(
+    me: Participant,
+    other: Participant,
+    count: usize,
+) -> Result<(), InitializationError> {
+    if me == other {
+        return Err(InitializationError::BadParameters(
+            "the other participant must be different from this participant".to_string(),
+        ));
+    }
+    if count == 0 {
+        return Err(InitializationError::BadParameters(
+            "count cannot be 0".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+async fn do_random_ot_sender<C: CSCurve>(
+    mut rng: MeowRng,
+    ctx: Context<'_>,
+    me: Participant,
+    receiver: Participant,
+    count: usize,
+) -> Result<RandomOTExtensionSenderOut<C>, ProtocolError> {
+    let chan = ctx.private_channel(me, receiver);
+    // The setup is fresh for each run, so the session only needs to name the parties.
+    let sid = hash(&(LABEL, me, receiver));
+    let (delta, k) =
+        batch_random_ot_receiver::<C>(fork_rng(&mut rng), ctx.clone(), chan.child(0)).await?;
+    random_ot_extension_sender::<C>(
+        rng,
+        chan.child(1),
+        RandomOtExtensionParams {
+            sid: sid.as_ref(),
+            prg: OtPrg::default(),
+            batch_size: count,
+        },
+        delta,
+        &k,
+    )
+    .await
+}
+
+async fn do_random_ot_receiver<C: CSCurve>(
+    mut rng: MeowRng,
+    ctx: Context<'_>,
+    me: Participant,
+    sender: Participant,
+    count: usize,
+) -> Result<RandomOTExtensionReceiverOut<C>, ProtocolError> {
+    let chan = ctx.private_channel(me, sender);
+    let sid = hash(&(LABEL, sender, me));
+    let (k0, k1) =
+        batch_random_ot_sender::<C>(fork_rng(&mut rng), ctx.clone(), chan.child(0)).await?;
+    random_ot_extension_receiver::<C>(
+        rng,
+        chan.child(1),
+        RandomOtExtensionParams {
+            sid: sid.as_ref(),
+            prg: OtPrg::default(),
+            batch_size: count,
+        },
+        &k0,
+        &k1,
+    )
+    .await
+}
+
+/// The sender side of random oblivious transfer, between two participants.
+///
+/// This produces `count` pairs of random scalars. The receiver, running
+/// [random_ot_receiver()], learns one scalar from each pair, of its own random choice,
+/// while the sender learns nothing about which ones.
+///
+/// This runs a fresh base OT setup, which is then extended to `count` transfers,
+/// so it's much cheaper per transfer with large counts.
+/// The setup protects against malicious parties, but a malicious receiver can still
+/// make the extension fail depending on a few of its choices.
+pub fn random_ot_sender<C: CSCurve>(
+    me: Participant,
+    receiver: Participant,
+    count: usize,
+) -> Result<impl Protocol<Output = RandomOTExtensionSenderOut<C>>, InitializationError> {
+    random_ot_sender_with_rng::<C>(me, receiver, count, &mut OsRng)
+}
+
+/// Like [random_ot_sender()], except drawing all of the randomness from a given rng.
+pub fn random_ot_sender_with_rng<C: CSCurve>(
+    me: Participant,
+    receiver: Participant,
+    count: usize,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = RandomOTExtensionSenderOut<C>>, InitializationError> {
+    check_ot_parameters(me, receiver, count)?;
+    let ctx = Context::new();
+    let fut = do_random_ot_sender::<C>(fork_rng(rng), ctx.clone(), me, receiver, count);
+    Ok(make_protocol(ctx, fut))
+}
+
+/// The receiver side of random oblivious transfer, see [random_ot_sender()].
+///
+/// This produces `count` random choice bits, along with the corresponding
+/// scalar from each of the sender's pairs.
+pub fn random_ot_receiver<C: CSCurve>(
+    me: Participant,
+    sender: Participant,
+    count: usize,
+) -> Result<impl Protocol<Output = RandomOTExtensionReceiverOut<C>>, InitializationError> {
+    random_ot_receiver_with_rng::<C>(me, sender, count, &mut OsRng)
+}
+
+/// Like [random_ot_receiver()], except drawing all of the randomness from a given rng.
+pub fn random_ot_receiver_with_rng<C: CSCurve>(
+    me: Participant,
+    sender: Participant,
+    count: usize,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = RandomOTExtensionReceiverOut<C>>, InitializationError> {
+    check_ot_parameters(me, sender, count)?;
+    let ctx = Context::new();
+    let fut = do_random_ot_receiver::<C>(fork_rng(rng), ctx.clone(), me, sender, count);
+    Ok(make_protocol(ctx, fut))
+}
+
 #[cfg(test)]
 mod test {
     use crate::triples::batch_random_ot::run_batch_random_ot;
@@ -254,4 +409,24 @@ mod test {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_random_ot_protocol() -> Result<(), ProtocolError> {
+        let s = Participant::from(0u32);
+        let r = Participant::from(1u32);
+        let count = 200;
+        let (sender_out, receiver_out) = run_two_party_protocol(
+            s,
+            r,
+            &mut random_ot_sender::<Secp256k1>(s, r, count).unwrap(),
+            &mut random_ot_receiver::<Secp256k1>(r, s, count).unwrap(),
+        )?;
+        assert_eq!(sender_out.len(), count);
+        assert_eq!(receiver_out.len(), count);
+        for ((v0_i, v1_i), (b_i, vb_i)) in sender_out.iter().zip(receiver_out.iter()) {
+            assert_eq!(*vb_i, Scalar::conditional_select(v0_i, v1_i, *b_i));
+        }
+        assert!(random_ot_sender::<Secp256k1>(s, r, 0).is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,240 @@
+//! This module provides a way to check that a triple is valid, by sacrificing another one.
+//!
+//! Triple generation makes sure that the triples it produces are valid,
+//! but triples are then usually stored for a while, before being used.
+//! If that storage gets corrupted or tampered with, then we'd only notice
+//! once a presignature made with that triple produces an invalid signature.
+//!
+//! The idea of the check is the classic "sacrifice" technique: given a triple
+//! `(a, b, c)` to check, and another triple `(x, y, z)`, which will be thrown away,
+//! the participants pick a random `t`, and open `ρ = t * a - x` and `σ = b - y`.
+//! These reveal nothing about `a` and `b`, since they're masked by `x` and `y`.
+//! If both triples are valid, then:
+//! ```text
+//! t * c = z + σ * x + ρ * y + σ * ρ
+//! ```
+//! which we check twice: once on the shares, by opening `t * c - z - σ * x - ρ * y`,
+//! which should be `σ * ρ`, and once using the public commitments to `c`, `x`, `y`, and `z`.
+//! If either triple is invalid, this only holds for one value of `t`,
+//! so an invalid triple is caught with overwhelming probability.
+use elliptic_curve::{Field, Group, ScalarPrimitive};
+use magikitten::MeowRng;
+
+use crate::{
+    compat::CSCurve,
+    crypto::{hash, Digest},
+    participants::ParticipantList,
+    protocol::{
+        internal::{make_protocol, Context, SharedChannel},
+        Fault, InitializationError, Participant, Protocol, ProtocolError,
+    },
+};
+
+use super::{TripleGenerationOutput, TripleId, TriplePub};
+
+/// Derive the challenge `t` from the public parts of both triples.
+///
+/// This is fine, since those are fixed before the check starts, and
+/// we make sure that everybody agrees on them.
+fn challenge<C: CSCurve>(transcript: &Digest) -> C::Scalar {
+    let seed: [u8; 32] = transcript
+        .as_ref()
+        .try_into()
+        .expect("digests should be 32 bytes");
+    C::sample_scalar_constant_time(&mut MeowRng::new(&seed))
+}
+
+async fn do_verify<C: CSCurve>(
+    mut chan: SharedChannel,
+    participants: ParticipantList,
+    me: Participant,
+    triple: TripleGenerationOutput<C>,
+    sacrifice: TripleGenerationOutput<C>,
+) -> Result<TripleGenerationOutput<C>, ProtocolError> {
+    let transcript = hash(&(
+        b"cait-sith triple sacrifice",
+        TripleId::of(&triple.1),
+        TripleId::of(&sacrifice.1),
+        &triple.1.participants,
+        triple.1.threshold,
+    ));
+    let t = challenge::<C>(&transcript);
+
+    let lambda = participants.lagrange::<C>(me);
+    let rho_i: ScalarPrimitive<C> = (lambda * (t * triple.0.a - sacrifice.0.a)).into();
+    let sigma_i: ScalarPrimitive<C> = (lambda * (triple.0.b - sacrifice.0.b)).into();
+
+    let wait0 = chan.next_waitpoint();
+    let mine = (transcript, rho_i, sigma_i);
+    chan.send_many(wait0, &mine).await;
+    let received = chan.recv_echoed(&participants, me, wait0, mine).await?;
+
+    let mut rho = C::Scalar::ZERO;
+    let mut sigma = C::Scalar::ZERO;
+    let order: Vec<Participant> = participants.clone().into();
+    for p in order {
+        let (their_transcript, rho_j, sigma_j) = &received[p];
+        if *their_transcript != transcript {
+            return Err(ProtocolError::Malicious {
+                participant: p,
+                round: wait0,
+                fault: Fault::InvalidMessage("checking a different pair of triples"),
+            });
+        }
+        rho += C::Scalar::from(*rho_j);
+        sigma += C::Scalar::from(*sigma_j);
+    }
+
+    // Now, open t * c - z - σ * x - ρ * y, checking that the shares are consistent.
+    let tau_i: ScalarPrimitive<C> = (lambda
+        * (t * triple.0.c - sacrifice.0.c - sigma * sacrifice.0.a - rho * sacrifice.0.b))
+        .into();
+    let wait1 = chan.next_waitpoint();
+    chan.send_many(wait1, &tau_i).await;
+    let received = chan.recv_echoed(&participants, me, wait1, tau_i).await?;
+    let order: Vec<Participant> = participants.clone().into();
+    let tau: C::Scalar = order.iter().map(|p| C::Scalar::from(received[*p])).sum();
+    if tau != sigma * rho {
+        return Err(ProtocolError::Failed {
+            round: wait1,
+            fault: Fault::BadShare,
+        });
+    }
+
+    // And check the same relation against the public commitments.
+    let big_x = C::ProjectivePoint::from(sacrifice.1.big_a);
+    let big_y = C::ProjectivePoint::from(sacrifice.1.big_b);
+    let big_z = C::ProjectivePoint::from(sacrifice.1.big_c);
+    let expected =
+        big_z + big_x * sigma + big_y * rho + C::ProjectivePoint::generator() * (sigma * rho);
+    if C::ProjectivePoint::from(triple.1.big_c) * t != expected {
+        return Err(ProtocolError::Failed {
+            round: wait1,
+            fault: Fault::InvalidResult("triple does not satisfy a * b = c"),
+        });
+    }
+
+    Ok(triple)
+}
+
+fn validate<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    triple: &TriplePub<C>,
+    sacrifice: &TriplePub<C>,
+) -> Result<ParticipantList, InitializationError> {
+    let participants = ParticipantList::new(participants).ok_or_else(|| {
+        InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
+    })?;
+    if !participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain this participant".to_string(),
+        ));
+    }
+    if triple.participants != sacrifice.participants || triple.threshold != sacrifice.threshold {
+        return Err(InitializationError::BadParameters(
+            "both triples must have the same participants and threshold".to_string(),
+        ));
+    }
+    let triple_participants = ParticipantList::new(&triple.participants).ok_or_else(|| {
+        InitializationError::BadParameters("triple participants contain duplicates".to_string())
+    })?;
+    let order: Vec<Participant> = participants.clone().into();
+    if !order.iter().all(|p| triple_participants.contains(*p)) {
+        return Err(InitializationError::BadParameters(
+            "participants must have taken part in generating the triples".to_string(),
+        ));
+    }
+    if participants.len() < triple.threshold {
+        return Err(InitializationError::BadParameters(format!(
+            "need at least {} participants to check these triples, found: {}",
+            triple.threshold,
+            participants.len()
+        )));
+    }
+    Ok(participants)
+}
+
+/// Check that a triple is valid, by sacrificing another one.
+///
+/// This returns the triple once it's been checked. The sacrificed triple
+/// is consumed, and must not be used for anything else, since part of it is revealed.
+///
+/// Any set of at least `threshold` participants of the two triples can run this check.
+pub fn verify_triple<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    triple: TripleGenerationOutput<C>,
+    sacrifice: TripleGenerationOutput<C>,
+) -> Result<impl Protocol<Output = TripleGenerationOutput<C>>, InitializationError> {
+    let participants = validate(participants, me, &triple.1, &sacrifice.1)?;
+
+    let ctx = Context::new();
+    let fut = do_verify(ctx.shared_channel(), participants, me, triple, sacrifice);
+    Ok(make_protocol(ctx, fut))
+}
+
+#[cfg(test)]
+mod test {
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::{
+        protocol::run_protocol,
+        triples::{deal, TripleShare},
+    };
+
+    #[allow(clippy::type_complexity)]
+    fn run_verify(
+        participants: &[Participant],
+        triple: (TriplePub<Secp256k1>, Vec<TripleShare<Secp256k1>>),
+        sacrifice: (TriplePub<Secp256k1>, Vec<TripleShare<Secp256k1>>),
+    ) -> Result<Vec<(Participant, TripleGenerationOutput<Secp256k1>)>, ProtocolError> {
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = TripleGenerationOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        for ((p, share), sacrifice_share) in participants.iter().zip(triple.1).zip(sacrifice.1) {
+            let protocol = verify_triple(
+                participants,
+                *p,
+                (share, triple.0.clone()),
+                (sacrifice_share, sacrifice.0.clone()),
+            )
+            .unwrap();
+            protocols.push((*p, Box::new(protocol)));
+        }
+        run_protocol(protocols)
+    }
+
+    #[test]
+    fn test_verify_triple() {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+
+        let triple = deal::<Secp256k1>(&mut OsRng, &participants, threshold);
+        let sacrifice = deal::<Secp256k1>(&mut OsRng, &participants, threshold);
+        let result = run_verify(&participants, triple.clone(), sacrifice).unwrap();
+        assert!(result
+            .iter()
+            .all(|(_, (_, triple_pub))| *triple_pub == triple.0));
+
+        // Tampering with a share gets caught.
+        let (triple_pub, mut shares) = deal::<Secp256k1>(&mut OsRng, &participants, threshold);
+        shares[1].c += Scalar::ONE;
+        let sacrifice = deal::<Secp256k1>(&mut OsRng, &participants, threshold);
+        assert!(run_verify(&participants, (triple_pub, shares), sacrifice).is_err());
+
+        // So does tampering with the public part.
+        let (mut triple_pub, shares) = deal::<Secp256k1>(&mut OsRng, &participants, threshold);
+        triple_pub.big_c =
+            (ProjectivePoint::from(triple_pub.big_c) + ProjectivePoint::GENERATOR).to_affine();
+        let sacrifice = deal::<Secp256k1>(&mut OsRng, &participants, threshold);
+        assert!(run_verify(&participants, (triple_pub, shares), sacrifice).is_err());
+    }
+}
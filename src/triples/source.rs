@@ -0,0 +1,35 @@
+//! A way for presigning to obtain triples from any provider.
+//!
+//! Presigning needs two triples, which could come from generating them just before,
+//! from a [`TripleStore`] on disk, or from a remote service, say.
+//! A [`TripleSource`] abstracts over this, handing out the triple with a given
+//! identifier, so that [`presign_from_source`](crate::presign_from_source) can
+//! fetch the triples itself. Every participant asks its own source for
+//! the same identifiers, since these are derived from the public part of each triple.
+use std::error;
+
+use crate::compat::CSCurve;
+
+use super::{StoredTriple, TripleId, TripleStore};
+
+/// Something from which triples can be taken.
+pub trait TripleSource<C: CSCurve> {
+    type Error: error::Error + Send + Sync + 'static;
+
+    /// Take the triple with a given identifier.
+    ///
+    /// Once taken, a triple must never be handed out again, even if the
+    /// protocol using it fails.
+    fn take(&mut self, id: TripleId) -> Result<StoredTriple<C>, Self::Error>;
+}
+
+/// Taking a triple from a store reserves it.
+///
+/// The triple should be consumed once the presignature using it exists.
+impl<C: CSCurve, S: TripleStore<C>> TripleSource<C> for S {
+    type Error = S::Error;
+
+    fn take(&mut self, id: TripleId) -> Result<StoredTriple<C>, Self::Error> {
+        self.reserve(id)
+    }
+}
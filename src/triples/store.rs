@@ -0,0 +1,388 @@
+//! Persistent storage for triples.
+//!
+//! Triples are expensive to generate, so nodes will usually generate them in advance,
+//! and keep a stockpile around, which needs to survive restarts.
+//! Like presignatures, triples must **never** be used more than once.
+//!
+//! The [`TripleStore`] trait captures the lifecycle of a stored triple: it gets saved,
+//! then reserved for some presignature, then consumed once that presignature exists.
+//! A reserved triple is never handed out again, even after a restart, since it
+//! might already have been used partially.
+//!
+//! [`FileTripleStore`] implements this trait with one file per triple,
+//! tagging each file with a version, and a checksum, so that corrupted
+//! or mismatched files get rejected rather than silently used.
+use std::{
+    error, fmt, fs,
+    io::{self, Write},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+};
+
+use elliptic_curve::point::AffineCoordinates;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    compat::CSCurve,
+    crypto::{hash, Digest},
+    serde::{decode, encode},
+};
+
+use super::{TriplePub, TripleShare};
+
+/// Identifies a triple.
+///
+/// This is derived from the public part of the triple, so all participants
+/// holding shares of a triple agree on its identifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TripleId(Digest);
+
+impl TripleId {
+    /// The identifier for a given triple.
+    pub fn of<C: CSCurve>(triple_pub: &TriplePub<C>) -> Self {
+        let points = [&triple_pub.big_a, &triple_pub.big_b, &triple_pub.big_c]
+            .map(|p| (p.x().to_vec(), p.y_is_odd().unwrap_u8()));
+        Self(hash(&(C::NAME, points)))
+    }
+
+    fn to_hex(self) -> String {
+        self.0
+            .as_ref()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+/// A stored triple, consisting of our share, and the public part.
+pub type StoredTriple<C> = (TripleShare<C>, TriplePub<C>);
+
+/// A place to keep triples, guaranteeing that each is used at most once.
+pub trait TripleStore<C: CSCurve> {
+    type Error: error::Error + Send + Sync + 'static;
+
+    /// Save a new triple, returning its identifier.
+    fn save(&mut self, triple: StoredTriple<C>) -> Result<TripleId, Self::Error>;
+
+    /// Load a triple which hasn't been consumed yet, if it exists.
+    fn load(&self, id: TripleId) -> Result<Option<StoredTriple<C>>, Self::Error>;
+
+    /// The identifiers of the triples which are available to be reserved.
+    fn available(&self) -> Result<Vec<TripleId>, Self::Error>;
+
+    /// Reserve a triple, making it unavailable for any other use.
+    ///
+    /// This should be done before starting the protocol using the triple,
+    /// and fails if the triple isn't available.
+    fn reserve(&mut self, id: TripleId) -> Result<StoredTriple<C>, Self::Error>;
+
+    /// Consume a reserved triple, removing it for good.
+    fn consume(&mut self, id: TripleId) -> Result<(), Self::Error>;
+}
+
+/// Represents an error which can happen when using a [`FileTripleStore`].
+#[derive(Debug)]
+pub enum FileTripleStoreError {
+    /// Some error happened reading or writing a file.
+    Io(io::Error),
+    /// A file didn't contain a valid triple, for the reason given.
+    Corrupted(PathBuf, String),
+    /// A file was written with a version of the format we don't understand.
+    UnsupportedVersion(PathBuf, u16),
+    /// This triple isn't available to be reserved.
+    NotAvailable(TripleId),
+    /// This triple hasn't been reserved, so it can't be consumed.
+    NotReserved(TripleId),
+}
+
+impl fmt::Display for FileTripleStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {}", e),
+            Self::Corrupted(path, e) => write!(f, "corrupted triple in {:?}: {}", path, e),
+            Self::UnsupportedVersion(path, v) => {
+                write!(f, "unsupported version {} for triple in {:?}", v, path)
+            }
+            Self::NotAvailable(id) => write!(f, "triple {:?} is not available", id),
+            Self::NotReserved(id) => write!(f, "triple {:?} is not reserved", id),
+        }
+    }
+}
+
+impl error::Error for FileTripleStoreError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FileTripleStoreError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+const MAGIC: &[u8; 8] = b"CSTRIPLE";
+const VERSION: u16 = 1;
+const CHECKSUM_LEN: usize = 32;
+const AVAILABLE_EXT: &str = "triple";
+const RESERVED_EXT: &str = "reserved";
+
+/// The checksum covering the contents of a file.
+#[derive(Serialize)]
+struct Checksummed<'a> {
+    version: u16,
+    curve: &'a [u8],
+    payload: &'a [u8],
+}
+
+/// A triple store keeping each triple in its own file, inside of a directory.
+///
+/// Each file starts with a magic string and a version number, which are followed
+/// by the serialized triple, and a checksum over all of this, along with the curve.
+///
+/// Files are written to a temporary location, and then moved into place,
+/// so that a crash never leaves a partially written triple behind.
+/// Reserving a triple renames its file, so the reservation persists across restarts.
+pub struct FileTripleStore<C: CSCurve> {
+    dir: PathBuf,
+    _curve: PhantomData<C>,
+}
+
+impl<C: CSCurve> FileTripleStore<C>
+where
+    StoredTriple<C>: Serialize + DeserializeOwned,
+{
+    /// Open a store in a given directory, creating it if necessary.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, FileTripleStoreError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            _curve: PhantomData,
+        })
+    }
+
+    fn path(&self, id: TripleId, ext: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", id.to_hex(), ext))
+    }
+
+    fn write(&self, path: &Path, triple: &StoredTriple<C>) -> Result<(), FileTripleStoreError> {
+        let payload = encode(triple);
+        let checksum = hash(&Checksummed {
+            version: VERSION,
+            curve: C::NAME,
+            payload: &payload,
+        });
+
+        let mut data = Vec::with_capacity(MAGIC.len() + 2 + payload.len() + CHECKSUM_LEN);
+        data.extend_from_slice(MAGIC);
+        data.extend_from_slice(&VERSION.to_le_bytes());
+        data.extend_from_slice(&payload);
+        data.extend_from_slice(checksum.as_ref());
+
+        let tmp = path.with_extension("tmp");
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    fn read(&self, path: &Path) -> Result<StoredTriple<C>, FileTripleStoreError> {
+        let data = fs::read(path)?;
+        let corrupted =
+            |e: &str| FileTripleStoreError::Corrupted(path.to_path_buf(), e.to_string());
+
+        let header_len = MAGIC.len() + 2;
+        if data.len() < header_len + CHECKSUM_LEN || &data[..MAGIC.len()] != MAGIC {
+            return Err(corrupted("missing header"));
+        }
+        let version = u16::from_le_bytes([data[MAGIC.len()], data[MAGIC.len() + 1]]);
+        if version != VERSION {
+            return Err(FileTripleStoreError::UnsupportedVersion(
+                path.to_path_buf(),
+                version,
+            ));
+        }
+        let (payload, checksum) =
+            data[header_len..].split_at(data.len() - header_len - CHECKSUM_LEN);
+        let expected = hash(&Checksummed {
+            version,
+            curve: C::NAME,
+            payload,
+        });
+        if expected.as_ref() != checksum {
+            return Err(corrupted("checksum mismatch"));
+        }
+        decode(payload).map_err(|e| corrupted(&e.to_string()))
+    }
+}
+
+impl<C: CSCurve> TripleStore<C> for FileTripleStore<C>
+where
+    StoredTriple<C>: Serialize + DeserializeOwned,
+{
+    type Error = FileTripleStoreError;
+
+    fn save(&mut self, triple: StoredTriple<C>) -> Result<TripleId, Self::Error> {
+        let id = TripleId::of(&triple.1);
+        if self.path(id, RESERVED_EXT).exists() {
+            // Saving this triple again must not make it available again.
+            return Err(FileTripleStoreError::NotAvailable(id));
+        }
+        self.write(&self.path(id, AVAILABLE_EXT), &triple)?;
+        Ok(id)
+    }
+
+    fn load(&self, id: TripleId) -> Result<Option<StoredTriple<C>>, Self::Error> {
+        for ext in [AVAILABLE_EXT, RESERVED_EXT] {
+            let path = self.path(id, ext);
+            if path.exists() {
+                return self.read(&path).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    fn available(&self) -> Result<Vec<TripleId>, Self::Error> {
+        let mut out = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(AVAILABLE_EXT) {
+                continue;
+            }
+            // Checking the contents also makes sure the file name matches the triple.
+            let (_, triple_pub) = self.read(&path)?;
+            let id = TripleId::of(&triple_pub);
+            if path != self.path(id, AVAILABLE_EXT) {
+                return Err(FileTripleStoreError::Corrupted(
+                    path,
+                    "file name doesn't match triple".to_string(),
+                ));
+            }
+            out.push(id);
+        }
+        Ok(out)
+    }
+
+    fn reserve(&mut self, id: TripleId) -> Result<StoredTriple<C>, Self::Error> {
+        let path = self.path(id, AVAILABLE_EXT);
+        if !path.exists() {
+            return Err(FileTripleStoreError::NotAvailable(id));
+        }
+        let triple = self.read(&path)?;
+        fs::rename(&path, self.path(id, RESERVED_EXT))?;
+        Ok(triple)
+    }
+
+    fn consume(&mut self, id: TripleId) -> Result<(), Self::Error> {
+        let path = self.path(id, RESERVED_EXT);
+        if !path.exists() {
+            return Err(FileTripleStoreError::NotReserved(id));
+        }
+        fs::remove_file(path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use k256::Secp256k1;
+    use rand_core::{OsRng, RngCore};
+
+    use super::*;
+    use crate::{protocol::Participant, triples::deal};
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("cait-sith-triples-{:016x}", OsRng.next_u64()))
+    }
+
+    fn stored_triples(n: usize) -> Vec<StoredTriple<Secp256k1>> {
+        let participants = [Participant::from(0u32), Participant::from(1u32)];
+        (0..n)
+            .map(|_| {
+                let (triple_pub, shares) = deal(&mut OsRng, &participants, 2);
+                (shares[0].clone(), triple_pub)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_file_store_lifecycle() -> Result<(), FileTripleStoreError> {
+        let dir = temp_dir();
+        let mut store = FileTripleStore::<Secp256k1>::open(&dir)?;
+        let triples = stored_triples(2);
+        let id0 = store.save(triples[0].clone())?;
+        let id1 = store.save(triples[1].clone())?;
+
+        // Reopening the store sees the same triples.
+        let mut store = FileTripleStore::<Secp256k1>::open(&dir)?;
+        let mut available = store.available()?;
+        available.sort_by_key(|id| id.to_hex());
+        let mut expected = vec![id0, id1];
+        expected.sort_by_key(|id| id.to_hex());
+        assert_eq!(available, expected);
+        assert_eq!(store.load(id0)?.unwrap().1, triples[0].1);
+
+        let (share, triple_pub) = store.reserve(id0)?;
+        assert_eq!(share.a, triples[0].0.a);
+        assert_eq!(triple_pub, triples[0].1);
+        assert_eq!(store.available()?, vec![id1]);
+
+        // A reserved triple can't be reserved again, or saved again, even after restarting.
+        let mut store = FileTripleStore::<Secp256k1>::open(&dir)?;
+        assert!(matches!(
+            store.reserve(id0),
+            Err(FileTripleStoreError::NotAvailable(_))
+        ));
+        assert!(matches!(
+            store.save(triples[0].clone()),
+            Err(FileTripleStoreError::NotAvailable(_))
+        ));
+        assert!(matches!(
+            store.consume(id1),
+            Err(FileTripleStoreError::NotReserved(_))
+        ));
+
+        store.consume(id0)?;
+        assert!(store.load(id0)?.is_none());
+        assert!(matches!(
+            store.consume(id0),
+            Err(FileTripleStoreError::NotReserved(_))
+        ));
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_store_integrity() -> Result<(), FileTripleStoreError> {
+        let dir = temp_dir();
+        let mut store = FileTripleStore::<Secp256k1>::open(&dir)?;
+        let id = store.save(stored_triples(1).remove(0))?;
+        let path = store.path(id, AVAILABLE_EXT);
+
+        let mut data = fs::read(&path)?;
+        let last = data.len() - CHECKSUM_LEN - 1;
+        data[last] ^= 1;
+        fs::write(&path, &data)?;
+        assert!(matches!(
+            store.reserve(id),
+            Err(FileTripleStoreError::Corrupted(_, _))
+        ));
+
+        data[last] ^= 1;
+        data[MAGIC.len()] = 2;
+        fs::write(&path, &data)?;
+        assert!(matches!(
+            store.load(id),
+            Err(FileTripleStoreError::UnsupportedVersion(_, 2))
+        ));
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
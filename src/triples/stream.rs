@@ -0,0 +1,229 @@
+//! This module provides a way to generate many triples with bounded memory.
+//!
+//! [`generate_triple_many`](super::generate_triple_many) keeps the intermediate
+//! state for every triple in the batch in memory at once, including the matrices
+//! used for oblivious transfer, which adds up quickly for large batches.
+//!
+//! [`TripleStream`] instead generates triples in consecutive batches of `N`,
+//! only holding the state of one batch at a time, so `N` bounds memory use,
+//! and makes each batch available as soon as it's done, through [`TripleStream::take_ready`].
+//! Smaller batches use less memory, at the cost of more rounds of communication.
+use magikitten::MeowRng;
+use rand_core::{CryptoRngCore, OsRng};
+
+use crate::{
+    compat::CSCurve,
+    crypto::fork_rng,
+    protocol::{Action, InitializationError, MessageData, Participant, Protocol, ProtocolError},
+};
+
+use super::{generate_triple_many_with_rng, TripleGenerationOutput};
+
+type Batch<C> = Box<dyn Protocol<Output = Vec<TripleGenerationOutput<C>>>>;
+
+/// A protocol generating triples in batches, see [`generate_triple_stream`].
+///
+/// The protocol returns the triples which haven't been taken with [`TripleStream::take_ready`].
+pub struct TripleStream<C: CSCurve, const N: usize> {
+    participants: Vec<Participant>,
+    me: Participant,
+    threshold: usize,
+    rng: MeowRng,
+    remaining: usize,
+    in_batch: usize,
+    index: u64,
+    batch: Option<Batch<C>>,
+    ready: Vec<TripleGenerationOutput<C>>,
+    pending: Vec<(u64, Participant, MessageData)>,
+    done: bool,
+}
+
+impl<C: CSCurve, const N: usize> TripleStream<C, N> {
+    fn start_batch(&mut self) -> Result<(), InitializationError> {
+        if self.remaining == 0 {
+            self.batch = None;
+            return Ok(());
+        }
+        // Once fewer than N triples are left, generate them one at a time.
+        let (mut batch, size): (Batch<C>, usize) = if self.remaining >= N {
+            let protocol = generate_triple_many_with_rng::<C, N>(
+                &self.participants,
+                self.me,
+                self.threshold,
+                &mut self.rng,
+            )?;
+            (Box::new(protocol), N)
+        } else {
+            let protocol = generate_triple_many_with_rng::<C, 1>(
+                &self.participants,
+                self.me,
+                self.threshold,
+                &mut self.rng,
+            )?;
+            (Box::new(protocol), 1)
+        };
+        self.remaining -= size;
+        self.in_batch = size;
+        self.index += 1;
+        let index = self.index;
+        for (_, from, data) in self.pending.iter().filter(|(i, _, _)| *i == index) {
+            batch.message(*from, data.clone());
+        }
+        self.pending.retain(|(i, _, _)| *i > index);
+        self.batch = Some(batch);
+        Ok(())
+    }
+
+    fn tag(&self, data: MessageData) -> MessageData {
+        let mut out = Vec::with_capacity(8 + data.len());
+        out.extend_from_slice(&self.index.to_le_bytes());
+        out.extend_from_slice(&data);
+        out.into()
+    }
+
+    /// Take the triples generated so far.
+    pub fn take_ready(&mut self) -> Vec<TripleGenerationOutput<C>> {
+        std::mem::take(&mut self.ready)
+    }
+
+    /// The number of triples which haven't been generated yet.
+    pub fn remaining(&self) -> usize {
+        self.remaining + self.in_batch
+    }
+}
+
+impl<C: CSCurve, const N: usize> Protocol for TripleStream<C, N> {
+    type Output = Vec<TripleGenerationOutput<C>>;
+
+    fn poke(&mut self) -> Result<Action<Self::Output>, ProtocolError> {
+        loop {
+            let action = match self.batch.as_mut() {
+                None if self.done => return Ok(Action::Wait),
+                None => {
+                    self.done = true;
+                    return Ok(Action::Return(self.take_ready()));
+                }
+                Some(batch) => batch.poke()?,
+            };
+            match action {
+                Action::Wait => return Ok(Action::Wait),
+                Action::SendMany(data) => return Ok(Action::SendMany(self.tag(data))),
+                Action::SendPrivate(to, data) => {
+                    return Ok(Action::SendPrivate(to, self.tag(data)))
+                }
+                Action::Return(triples) => {
+                    self.ready.extend(triples);
+                    self.in_batch = 0;
+                    self.start_batch()
+                        .map_err(|e| ProtocolError::Other(Box::new(e)))?;
+                }
+            }
+        }
+    }
+
+    fn message(&mut self, from: Participant, mut data: MessageData) {
+        if data.len() < 8 {
+            return;
+        }
+        // Unwrapping is fine because we checked the length already.
+        let index = u64::from_le_bytes(data[..8].try_into().unwrap());
+        let data = data.split_off(8);
+        if index == self.index {
+            if let Some(batch) = self.batch.as_mut() {
+                batch.message(from, data);
+            }
+        } else if index == self.index + 1 {
+            // Others can only be a batch ahead of us, since each batch needs our messages.
+            self.pending.push((index, from, data));
+        }
+    }
+}
+
+/// Generate `count` triples, in batches of `N` triples at a time.
+///
+/// This requires a setup phase to have been conducted with these parties
+/// previously.
+pub fn generate_triple_stream<C: CSCurve, const N: usize>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    count: usize,
+) -> Result<TripleStream<C, N>, InitializationError> {
+    generate_triple_stream_with_rng(participants, me, threshold, count, &mut OsRng)
+}
+
+/// Like [`generate_triple_stream`], except drawing all of the randomness from a given rng.
+pub fn generate_triple_stream_with_rng<C: CSCurve, const N: usize>(
+    participants: &[Participant],
+    me: Participant,
+    threshold: usize,
+    count: usize,
+    rng: &mut impl CryptoRngCore,
+) -> Result<TripleStream<C, N>, InitializationError> {
+    if N == 0 {
+        return Err(InitializationError::BadParameters(
+            "batch size cannot be 0".to_string(),
+        ));
+    }
+    let mut stream = TripleStream {
+        participants: participants.to_vec(),
+        me,
+        threshold,
+        rng: fork_rng(rng),
+        remaining: count,
+        in_batch: 0,
+        index: 0,
+        batch: None,
+        ready: Vec::new(),
+        pending: Vec::new(),
+        done: false,
+    };
+    stream.start_batch()?;
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod test {
+    use k256::{ProjectivePoint, Secp256k1};
+
+    use super::*;
+    use crate::{participants::ParticipantList, protocol::run_protocol};
+
+    #[test]
+    fn test_triple_stream() -> Result<(), ProtocolError> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let threshold = 2;
+        let count = 3;
+
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = Vec<TripleGenerationOutput<Secp256k1>>>>,
+        )> = Vec::with_capacity(participants.len());
+        for &p in &participants {
+            let stream =
+                generate_triple_stream::<Secp256k1, 2>(&participants, p, threshold, count).unwrap();
+            assert_eq!(stream.remaining(), count);
+            protocols.push((p, Box::new(stream)));
+        }
+        let result = run_protocol(protocols)?;
+
+        let p_list = ParticipantList::new(&participants).unwrap();
+        for i in 0..count {
+            let triple_pub = &result[0].1[i].1;
+            assert!(result.iter().all(|(_, out)| out[i].1 == *triple_pub));
+            let mut c = ProjectivePoint::IDENTITY;
+            for (p, out) in &result {
+                c += ProjectivePoint::GENERATOR * (p_list.lagrange::<Secp256k1>(*p) * out[i].0.c);
+            }
+            assert_eq!(c, triple_pub.big_c);
+        }
+        assert!(result.iter().all(|(_, out)| out.len() == count));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,246 @@
+//! Tweaking a shared key, additively or multiplicatively.
+//!
+//! Given shares `x_i` of a key `x`, adding `t` to every share produces shares of `x + t`,
+//! and multiplying every share by `t` produces shares of `t * x`, because shares are
+//! evaluations of a polynomial. The public key can be tweaked in the same way, so
+//! each participant can apply a tweak locally. This is what taproot tweaks,
+//! pay-to-contract, and stealth addresses need. For example, a taproot tweak
+//! first negates the key if it has an odd y coordinate, with `Tweak::Mul(-1)`,
+//! and then adds the tap tweak, with `Tweak::Add`.
+//!
+//! Tweaking locally is only safe if everyone applies the same tweak. Otherwise,
+//! the shares no longer fit together, and every later signature will fail.
+//! [`tweak()`] runs a single round, in which the participants check that they
+//! agree on the tweak, and on the key it applies to, before applying it.
+use elliptic_curve::{Group, ScalarPrimitive};
+
+use crate::{
+    compat::{CSCurve, SerializablePoint},
+    crypto::{hash, Digest},
+    participants::{ParticipantCounter, ParticipantList},
+    protocol::{
+        internal::{make_protocol, Context, SharedChannel},
+        Fault, InitializationError, Participant, Protocol, ProtocolError,
+    },
+    KeygenOutput,
+};
+
+/// A tweak to apply to a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tweak<C: CSCurve> {
+    /// Add a scalar to the private key.
+    Add(C::Scalar),
+    /// Multiply the private key by a scalar.
+    Mul(C::Scalar),
+}
+
+impl<C: CSCurve> Tweak<C> {
+    /// Apply this tweak to a public key, returning `None` if the result is the identity.
+    pub fn apply_public(&self, public_key: &C::AffinePoint) -> Option<C::AffinePoint> {
+        let public_key = C::ProjectivePoint::from(*public_key);
+        let out = match self {
+            Tweak::Add(t) => public_key + C::ProjectivePoint::generator() * t,
+            Tweak::Mul(t) => public_key * t,
+        };
+        (!bool::from(out.is_identity())).then(|| out.into())
+    }
+}
+
+impl<C: CSCurve> KeygenOutput<C> {
+    /// Apply a tweak to this share, and to the public key.
+    ///
+    /// If every participant applies the same tweak, the resulting shares are shares
+    /// of the tweaked key, with the same threshold, and can be used anywhere
+    /// the output of key generation can.
+    ///
+    /// This returns `None` if the tweaked key would be zero.
+    pub fn tweaked(&self, tweak: &Tweak<C>) -> Option<Self> {
+        let public_key = tweak.apply_public(&self.public_key)?;
+        let private_share = match tweak {
+            Tweak::Add(t) => self.private_share + t,
+            Tweak::Mul(t) => self.private_share * t,
+        };
+        Some(KeygenOutput {
+            private_share,
+            public_key,
+        })
+    }
+}
+
+async fn do_tweak<C: CSCurve>(
+    mut chan: SharedChannel,
+    participants: ParticipantList,
+    me: Participant,
+    tweaked: KeygenOutput<C>,
+    confirmation: Digest,
+) -> Result<KeygenOutput<C>, ProtocolError> {
+    let wait0 = chan.next_waitpoint();
+    chan.send_many(wait0, &confirmation).await;
+
+    let mut seen = ParticipantCounter::new(&participants);
+    seen.put(me);
+    while !seen.full() {
+        let (from, their_confirmation): (_, Digest) = chan.recv(wait0).await?;
+        if !seen.put(from) {
+            continue;
+        }
+        if their_confirmation != confirmation {
+            return Err(ProtocolError::Failed {
+                round: wait0,
+                fault: Fault::InvalidMessage("participants disagree on the tweak"),
+            });
+        }
+    }
+
+    Ok(tweaked)
+}
+
+/// Agree on a tweak with the other participants, and then apply it to our share.
+///
+/// This takes a single round, in which each participant checks that everyone else
+/// is applying the same tweak, to the same public key, failing otherwise.
+/// The output is [`KeygenOutput::tweaked`], and the tweak must not produce a zero key.
+pub fn tweak<C: CSCurve>(
+    participants: &[Participant],
+    me: Participant,
+    keygen_out: KeygenOutput<C>,
+    tweak: Tweak<C>,
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    if participants.len() < 2 {
+        return Err(InitializationError::BadParameters(format!(
+            "participant count cannot be < 2, found: {}",
+            participants.len()
+        )));
+    };
+    let participants = ParticipantList::new(participants).ok_or_else(|| {
+        InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
+    })?;
+    if !participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain this participant".to_string(),
+        ));
+    }
+    let tweaked = keygen_out.tweaked(&tweak).ok_or_else(|| {
+        InitializationError::BadParameters("tweak produces a zero key".to_string())
+    })?;
+
+    let (kind, t): (&[u8], ScalarPrimitive<C>) = match tweak {
+        Tweak::Add(t) => (b"add", t.into()),
+        Tweak::Mul(t) => (b"mul", t.into()),
+    };
+    let confirmation = hash(&(
+        C::NAME,
+        kind,
+        t,
+        SerializablePoint::<C>::from_projective(&keygen_out.public_key.into()),
+    ));
+
+    let ctx = Context::new();
+    let fut = do_tweak(
+        ctx.shared_channel(),
+        participants,
+        me,
+        tweaked,
+        confirmation,
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
+#[cfg(test)]
+mod test {
+    use elliptic_curve::Field;
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::{math::Polynomial, protocol::run_protocol};
+
+    #[test]
+    fn test_tweak() -> Result<(), Box<dyn std::error::Error>> {
+        let participants = vec![
+            Participant::from(0u32),
+            Participant::from(1u32),
+            Participant::from(2u32),
+        ];
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, 2);
+        let x = f.evaluate_zero();
+        let public_key = (ProjectivePoint::GENERATOR * x).to_affine();
+        let additive = Tweak::Add(Scalar::random(&mut OsRng));
+        let multiplicative = Tweak::Mul(Scalar::random(&mut OsRng));
+
+        let mut outputs: Vec<_> = participants
+            .iter()
+            .map(|p| KeygenOutput::<Secp256k1> {
+                private_share: f.evaluate(&p.scalar::<Secp256k1>()),
+                public_key,
+            })
+            .collect();
+        for t in [additive, multiplicative] {
+            #[allow(clippy::type_complexity)]
+            let mut protocols: Vec<(
+                Participant,
+                Box<dyn Protocol<Output = KeygenOutput<_>>>,
+            )> = Vec::new();
+            for (p, out) in participants.iter().zip(outputs.drain(..)) {
+                protocols.push((*p, Box::new(tweak(&participants, *p, out, t)?)));
+            }
+            let mut result = run_protocol(protocols)?;
+            result.sort_by_key(|(p, _)| *p);
+            outputs = result.into_iter().map(|(_, out)| out).collect();
+        }
+
+        let (Tweak::Add(a), Tweak::Mul(m)) = (additive, multiplicative) else {
+            unreachable!()
+        };
+        let expected = (x + a) * m;
+        let list = ParticipantList::new(&participants[..2]).unwrap();
+        let reconstructed: Scalar = participants[..2]
+            .iter()
+            .zip(&outputs)
+            .map(|(p, out)| list.lagrange::<Secp256k1>(*p) * out.private_share)
+            .sum();
+        assert_eq!(reconstructed, expected);
+        assert!(outputs
+            .iter()
+            .all(|out| out.public_key == (ProjectivePoint::GENERATOR * expected).to_affine()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tweak_disagreement() {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let public_key = (ProjectivePoint::GENERATOR * Scalar::ONE).to_affine();
+
+        #[allow(clippy::type_complexity)]
+        let protocols: Vec<(Participant, Box<dyn Protocol<Output = KeygenOutput<_>>>)> =
+            participants
+                .iter()
+                .zip(1u64..)
+                .map(|(p, t)| {
+                    let out = KeygenOutput::<Secp256k1> {
+                        private_share: Scalar::ONE,
+                        public_key,
+                    };
+                    let protocol = tweak(&participants, *p, out, Tweak::Add(Scalar::from(t)));
+                    let protocol: Box<dyn Protocol<Output = _>> = Box::new(protocol.unwrap());
+                    (*p, protocol)
+                })
+                .collect();
+        assert!(matches!(
+            run_protocol(protocols),
+            Err(ProtocolError::Failed { .. })
+        ));
+
+        let out = KeygenOutput::<Secp256k1> {
+            private_share: Scalar::ONE,
+            public_key,
+        };
+        assert!(tweak(
+            &participants,
+            participants[0],
+            out,
+            Tweak::Mul(Scalar::ZERO)
+        )
+        .is_err());
+    }
+}
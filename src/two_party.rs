@@ -0,0 +1,561 @@
+//! A fast path for the common case of two participants, who both need to sign.
+//!
+//! The general protocols work with any number of participants, and any threshold,
+//! which they pay for with extra rounds, and extra bytes: broadcasts get echoed, to
+//! make sure that everyone saw the same messages, and presigning consumes triples,
+//! which take many rounds to generate. With only two participants, there's nobody
+//! to equivocate to, so nothing needs echoing, and presigning can compute the few
+//! products it needs directly, with a single oblivious transfer setup between them.
+//!
+//! The outputs are the same as those of the general protocols, so the two can be mixed:
+//! a key from [`keygen_two_party`] can be used with [`presign`](crate::presign), and
+//! a presignature from [`presign_two_party`] with [`sign`](crate::sign), as long as
+//! the participants are the same two, with a threshold of 2.
+use elliptic_curve::{Field, Group, ScalarPrimitive};
+use magikitten::MeowRng;
+use rand_core::{CryptoRngCore, OsRng};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    commitments::{CommitmentScheme, HashCommitments},
+    compat::{CSCurve, SerializablePoint},
+    crypto::{fork_rng, hash, Commitment, Randomizer},
+    fiat_shamir::{FiatShamir, Transcript},
+    participants::ParticipantList,
+    proofs::{dlog, dlogeq},
+    protocol::{
+        internal::{make_protocol, Context, SharedChannel, Waitpoint},
+        Fault, InitializationError, Participant, Protocol, ProtocolError,
+    },
+    serde::{encode, Wire},
+    sign,
+    triples::{multiplication::multiplication_many, OtPrg},
+    vault::ShareVault,
+    FullSignature, KeygenOutput, PresignOutput,
+};
+
+const KEYGEN_LABEL: &[u8] = b"cait-sith v0.8.0 two-party keygen";
+const PRESIGN_LABEL: &[u8] = b"cait-sith v0.8.0 two-party presign";
+
+/// The scheme we use to commit to our share of the public key.
+const COMMITMENTS: HashCommitments = HashCommitments;
+
+/// Check that the two participants are different, returning the list of both.
+fn check_pair(me: Participant, other: Participant) -> Result<ParticipantList, InitializationError> {
+    if me == other {
+        return Err(InitializationError::BadParameters(
+            "the other participant must be different from this participant".to_string(),
+        ));
+    }
+    // Unwrapping is fine, since the participants are different.
+    Ok(ParticipantList::new(&[me, other]).unwrap())
+}
+
+fn transcript<C: CSCurve>(label: &'static [u8], participants: &ParticipantList) -> Transcript {
+    let mut transcript = Transcript::new(label);
+    transcript.message(b"group", C::NAME);
+    transcript.message(b"participants", &encode(participants));
+    transcript
+}
+
+/// Receive a message from the other participant, ignoring anyone else.
+async fn recv_from<T: DeserializeOwned + Wire>(
+    chan: &SharedChannel,
+    waitpoint: Waitpoint,
+    other: Participant,
+) -> Result<T, ProtocolError> {
+    loop {
+        let (from, data) = chan.recv(waitpoint).await?;
+        if from == other {
+            return Ok(data);
+        }
+    }
+}
+
+/// The inverse of our Lagrange coefficient, which turns additive shares into those
+/// the general protocols expect, since they multiply them by that coefficient.
+fn lagrange_inv<C: CSCurve>(participants: &ParticipantList, me: Participant) -> C::Scalar {
+    // Unwrapping is fine, since the coefficients of two different participants are never zero.
+    participants.lagrange::<C>(me).invert().unwrap()
+}
+
+async fn do_keygen_two_party<C: CSCurve>(
+    mut rng: MeowRng,
+    mut chan: SharedChannel,
+    participants: ParticipantList,
+    me: Participant,
+    other: Participant,
+) -> Result<KeygenOutput<C>, ProtocolError> {
+    let mut transcript = transcript::<C>(KEYGEN_LABEL, &participants);
+
+    // Our share of the private key is additive, so the public key is the sum of both shares.
+    let x_i = C::Scalar::random(&mut rng);
+    let big_x_i = C::ProjectivePoint::generator() * x_i;
+    let (my_commitment, my_randomizer) =
+        COMMITMENTS.commit(&mut rng, &SerializablePoint::<C>::from_projective(&big_x_i));
+
+    // We only open our commitment after seeing theirs, so that neither of us
+    // can choose their share based on the other's.
+    let wait0 = chan.next_waitpoint();
+    chan.send_many(wait0, &my_commitment).await;
+    let their_commitment: Commitment = recv_from(&chan, wait0, other).await?;
+
+    let commitments = if me < other {
+        (my_commitment, their_commitment)
+    } else {
+        (their_commitment, my_commitment)
+    };
+    transcript.message(b"commitments", &encode(&commitments));
+
+    let statement = dlog::Statement::<C> { public: &big_x_i };
+    let witness = dlog::Witness::<C> { x: &x_i };
+    let my_proof = dlog::prove(
+        &mut rng,
+        &mut transcript.forked(b"dlog0", &me.bytes()),
+        statement,
+        witness,
+    );
+
+    let wait1 = chan.next_waitpoint();
+    chan.send_many(
+        wait1,
+        &(
+            SerializablePoint::<C>::from_projective(&big_x_i),
+            my_randomizer,
+            my_proof,
+        ),
+    )
+    .await;
+
+    let (big_x_j, their_randomizer, their_proof): (
+        SerializablePoint<C>,
+        Randomizer,
+        dlog::Proof<C>,
+    ) = recv_from(&chan, wait1, other).await?;
+    if !COMMITMENTS.check(&their_commitment, &big_x_j, &their_randomizer) {
+        return Err(ProtocolError::Malicious {
+            participant: other,
+            round: wait1,
+            fault: Fault::BadCommitment,
+        });
+    }
+    let big_x_j = big_x_j.to_projective();
+    let statement = dlog::Statement::<C> { public: &big_x_j };
+    if !dlog::verify(
+        &mut transcript.forked(b"dlog0", &other.bytes()),
+        statement,
+        &their_proof,
+    ) {
+        return Err(ProtocolError::Malicious {
+            participant: other,
+            round: wait1,
+            fault: Fault::BadProof,
+        });
+    }
+
+    Ok(KeygenOutput {
+        private_share: x_i * lagrange_inv::<C>(&participants, me),
+        public_key: (big_x_i + big_x_j).into(),
+    })
+}
+
+/// Generate a key shared between exactly two participants, who both need to sign.
+///
+/// This takes two rounds, rather than the four of [`keygen`](crate::keygen),
+/// and sends a single point, instead of a polynomial, and a share for each participant.
+/// The output is the same as that of [`keygen`](crate::keygen), with a threshold of 2.
+pub fn keygen_two_party<C: CSCurve>(
+    me: Participant,
+    other: Participant,
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    keygen_two_party_with_rng(me, other, &mut OsRng)
+}
+
+/// Like [keygen_two_party()], except drawing all of the randomness from a given rng.
+pub fn keygen_two_party_with_rng<C: CSCurve>(
+    me: Participant,
+    other: Participant,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = KeygenOutput<C>>, InitializationError> {
+    let participants = check_pair(me, other)?;
+    let ctx = Context::new();
+    let fut = do_keygen_two_party(fork_rng(rng), ctx.shared_channel(), participants, me, other);
+    Ok(make_protocol(ctx, fut))
+}
+
+async fn do_presign_two_party<C: CSCurve, K: ShareVault<C>>(
+    mut rng: MeowRng,
+    ctx: Context<'_>,
+    participants: ParticipantList,
+    me: Participant,
+    other: Participant,
+    keygen_out: K,
+) -> Result<PresignOutput<C>, ProtocolError> {
+    let vault_error = |e: K::Error| ProtocolError::Other(Box::new(e));
+    let mut chan = ctx.shared_channel();
+    let mut transcript = transcript::<C>(PRESIGN_LABEL, &participants);
+
+    // Additive shares of the nonce k, and of two random masks, d and b.
+    let k_i = C::Scalar::random(&mut rng);
+    let d_i = C::Scalar::random(&mut rng);
+    let b_i = C::Scalar::random(&mut rng);
+    let big_k_i = C::ProjectivePoint::generator() * k_i;
+    let big_d_i = C::ProjectivePoint::generator() * d_i;
+    let big_b_i = C::ProjectivePoint::generator() * b_i;
+
+    // Instead of consuming triples, we compute k * d, and k * b, directly.
+    // Only random values go into the oblivious transfers, never the private share.
+    let fut = {
+        let ctx = ctx.clone();
+        // The setups are fresh for each run, so the sessions only need to name the parties.
+        let sid = vec![
+            hash(&(PRESIGN_LABEL, b"kd", &participants)),
+            hash(&(PRESIGN_LABEL, b"kb", &participants)),
+        ];
+        multiplication_many::<C, 2>(
+            fork_rng(&mut rng),
+            ctx,
+            sid,
+            OtPrg::default(),
+            participants.clone(),
+            me,
+            vec![k_i, k_i],
+            vec![d_i, b_i],
+        )
+    };
+    let multiplication_task = ctx.spawn(fut);
+
+    // We commit to our points before seeing theirs, so that neither of us can
+    // choose theirs to cancel out the other's, making the sum something they know.
+    let my_points = (
+        SerializablePoint::<C>::from_projective(&big_k_i),
+        SerializablePoint::<C>::from_projective(&big_d_i),
+        SerializablePoint::<C>::from_projective(&big_b_i),
+    );
+    let (my_commitment, my_randomizer) = COMMITMENTS.commit(&mut rng, &my_points);
+
+    let wait0 = chan.next_waitpoint();
+    chan.send_many(wait0, &my_commitment).await;
+    let their_commitment: Commitment = recv_from(&chan, wait0, other).await?;
+
+    let commitments = if me < other {
+        (my_commitment, their_commitment)
+    } else {
+        (their_commitment, my_commitment)
+    };
+    transcript.message(b"commitments", &encode(&commitments));
+
+    let wait1 = chan.next_waitpoint();
+    chan.send_many(wait1, &(&my_points, my_randomizer)).await;
+    #[allow(clippy::type_complexity)]
+    let (their_points, their_randomizer): (
+        (
+            SerializablePoint<C>,
+            SerializablePoint<C>,
+            SerializablePoint<C>,
+        ),
+        Randomizer,
+    ) = recv_from(&chan, wait1, other).await?;
+    if !COMMITMENTS.check(&their_commitment, &their_points, &their_randomizer) {
+        return Err(ProtocolError::Malicious {
+            participant: other,
+            round: wait1,
+            fault: Fault::BadCommitment,
+        });
+    }
+    let (big_k_j, big_d_j, big_b_j) = their_points;
+    let big_k_j = big_k_j.to_projective();
+    let big_d = big_d_i + big_d_j.to_projective();
+    let big_b = big_b_i + big_b_j.to_projective();
+
+    let products = ctx.run(multiplication_task).await?;
+    let (kd_i, kb_i) = (products[0], products[1]);
+
+    // Opening x + b reveals nothing about x, since b is random.
+    let lambda = participants.lagrange::<C>(me);
+    let xb_i = keygen_out.mul_add(&lambda, &b_i).map_err(vault_error)?;
+
+    // The oblivious transfers let the other participant make the products wrong,
+    // so we prove that our share of k matches what we sent, letting us check kd.
+    let big_kd_i = big_d * k_i;
+    let statement = dlogeq::Statement::<C> {
+        public0: &big_k_i,
+        generator1: &big_d,
+        public1: &big_kd_i,
+    };
+    let witness = dlogeq::Witness { x: &k_i };
+    let my_kd_proof = dlogeq::prove(
+        &mut rng,
+        &mut transcript.forked(b"dlogeq0", &me.bytes()),
+        statement,
+        witness,
+    );
+
+    // We can't open kb, since it would reveal k * x, so we check it in the exponent.
+    // Our share of G * kb reveals nothing more, since it's G * kb, minus their share.
+    let big_kb_i = big_b * k_i;
+    let statement = dlogeq::Statement::<C> {
+        public0: &big_k_i,
+        generator1: &big_b,
+        public1: &big_kb_i,
+    };
+    let my_kb_proof = dlogeq::prove(
+        &mut rng,
+        &mut transcript.forked(b"dlogeq1", &me.bytes()),
+        statement,
+        witness,
+    );
+    let big_kb_share_i = C::ProjectivePoint::generator() * kb_i;
+
+    let wait2 = chan.next_waitpoint();
+    {
+        let kd_i: ScalarPrimitive<C> = kd_i.into();
+        let xb_i: ScalarPrimitive<C> = xb_i.into();
+        chan.send_many(
+            wait2,
+            &(
+                kd_i,
+                xb_i,
+                SerializablePoint::<C>::from_projective(&big_kd_i),
+                my_kd_proof,
+                SerializablePoint::<C>::from_projective(&big_kb_i),
+                SerializablePoint::<C>::from_projective(&big_kb_share_i),
+                my_kb_proof,
+            ),
+        )
+        .await;
+    }
+
+    #[allow(clippy::type_complexity)]
+    let (kd_j, xb_j, big_kd_j, their_kd_proof, big_kb_j, big_kb_share_j, their_kb_proof): (
+        ScalarPrimitive<C>,
+        ScalarPrimitive<C>,
+        SerializablePoint<C>,
+        dlogeq::Proof<C>,
+        SerializablePoint<C>,
+        SerializablePoint<C>,
+        dlogeq::Proof<C>,
+    ) = recv_from(&chan, wait2, other).await?;
+    let big_kd_j = big_kd_j.to_projective();
+    let big_kb_j = big_kb_j.to_projective();
+    let kd_statement = dlogeq::Statement::<C> {
+        public0: &big_k_j,
+        generator1: &big_d,
+        public1: &big_kd_j,
+    };
+    let kb_statement = dlogeq::Statement::<C> {
+        public0: &big_k_j,
+        generator1: &big_b,
+        public1: &big_kb_j,
+    };
+    if !dlogeq::verify(
+        &mut transcript.forked(b"dlogeq0", &other.bytes()),
+        kd_statement,
+        &their_kd_proof,
+    ) || !dlogeq::verify(
+        &mut transcript.forked(b"dlogeq1", &other.bytes()),
+        kb_statement,
+        &their_kb_proof,
+    ) {
+        return Err(ProtocolError::Malicious {
+            participant: other,
+            round: wait2,
+            fault: Fault::BadProof,
+        });
+    }
+
+    let kd = kd_i + C::Scalar::from(kd_j);
+    let xb = xb_i + C::Scalar::from(xb_j);
+    let big_x: C::ProjectivePoint = keygen_out.public_key().into();
+    if C::ProjectivePoint::generator() * kd != big_kd_i + big_kd_j
+        || big_kb_share_i + big_kb_share_j.to_projective() != big_kb_i + big_kb_j
+        || C::ProjectivePoint::generator() * xb != big_x + big_b
+    {
+        return Err(ProtocolError::Malicious {
+            participant: other,
+            round: wait2,
+            fault: Fault::BadShare,
+        });
+    }
+
+    let kd_inv: Option<C::Scalar> = kd.invert().into();
+    let kd_inv = kd_inv.ok_or_else(|| ProtocolError::Failed {
+        round: wait2,
+        fault: Fault::InvalidResult("kd is zero"),
+    })?;
+    let big_r = big_d * kd_inv;
+
+    // k * (x + b) - k * b = k * x.
+    let sigma_i = xb * k_i - kb_i;
+
+    let lambda_inv = lagrange_inv::<C>(&participants, me);
+    Ok(PresignOutput {
+        big_r: big_r.into(),
+        k: k_i * lambda_inv,
+        sigma: sigma_i * lambda_inv,
+        k_inv: d_i * kd_inv * lambda_inv,
+    })
+}
+
+/// Create a presignature between exactly two participants, without any triples.
+///
+/// The products presigning needs are computed directly, with oblivious transfer,
+/// followed by three rounds, so this replaces both generating two triples, and
+/// running [`presign`](crate::presign) with them. The output is the same as that of
+/// [`presign`](crate::presign), and can be used with [`sign`](crate::sign), or
+/// [`sign_two_party`], with the same two participants.
+///
+/// The key can come from [`keygen_two_party`], or from [`keygen`](crate::keygen),
+/// with these two participants, and a threshold of 2.
+pub fn presign_two_party<C: CSCurve, K: ShareVault<C> + Send + 'static>(
+    me: Participant,
+    other: Participant,
+    keygen_out: K,
+) -> Result<impl Protocol<Output = PresignOutput<C>>, InitializationError> {
+    presign_two_party_with_rng(me, other, keygen_out, &mut OsRng)
+}
+
+/// Like [presign_two_party()], except drawing all of the randomness from a given rng.
+pub fn presign_two_party_with_rng<C: CSCurve, K: ShareVault<C> + Send + 'static>(
+    me: Participant,
+    other: Participant,
+    keygen_out: K,
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = PresignOutput<C>>, InitializationError> {
+    let participants = check_pair(me, other)?;
+    let ctx = Context::new();
+    let fut = do_presign_two_party(
+        fork_rng(rng),
+        ctx.clone(),
+        participants,
+        me,
+        other,
+        keygen_out,
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
+/// Sign a message between exactly two participants.
+///
+/// Signing already takes a single round, so this is [`sign`](crate::sign),
+/// with the participants given as a pair.
+///
+/// **WARNING** As with [`sign`](crate::sign), you must hash an actual message
+/// before passing it to this function.
+pub fn sign_two_party<C: CSCurve>(
+    me: Participant,
+    other: Participant,
+    public_key: C::AffinePoint,
+    presignature: PresignOutput<C>,
+    msg_hash: C::Scalar,
+) -> Result<impl Protocol<Output = FullSignature<C>>, InitializationError> {
+    check_pair(me, other)?;
+    sign(&[me, other], me, public_key, presignature, msg_hash)
+}
+
+#[cfg(test)]
+mod test {
+    use k256::{ProjectivePoint, Secp256k1};
+
+    use super::*;
+    use crate::{compat::scalar_hash, presign, protocol::run_protocol, triples, PresignArguments};
+
+    #[test]
+    fn test_two_party() -> Result<(), Box<dyn std::error::Error>> {
+        let participants = [Participant::from(3u32), Participant::from(7u32)];
+
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = KeygenOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(2);
+        for me in participants {
+            let other = other_of(&participants, me);
+            protocols.push((me, Box::new(keygen_two_party(me, other)?)));
+        }
+        let keys = run_protocol(protocols)?;
+        let public_key = keys[0].1.public_key;
+        assert_eq!(keys[1].1.public_key, public_key);
+
+        // The shares lie on a line through the private key, like those of any other keygen.
+        let p_list = ParticipantList::new(&participants).unwrap();
+        let x = keys
+            .iter()
+            .map(|(p, key)| p_list.lagrange::<Secp256k1>(*p) * key.private_share)
+            .sum::<k256::Scalar>();
+        assert_eq!((ProjectivePoint::GENERATOR * x).to_affine(), public_key);
+
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = PresignOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(2);
+        for (me, key) in &keys {
+            let other = other_of(&participants, *me);
+            protocols.push((*me, Box::new(presign_two_party(*me, other, key.clone())?)));
+        }
+        let presignatures = run_protocol(protocols)?;
+
+        let msg_hash = scalar_hash(b"hello?");
+        let signature = sign_both(&participants, public_key, presignatures, msg_hash)?;
+        assert!(signature.verify(&public_key, &msg_hash));
+
+        // The key also works with the general presigning protocol.
+        let (triple0_pub, triple0_shares) = triples::deal(&mut OsRng, &participants, 2);
+        let (triple1_pub, triple1_shares) = triples::deal(&mut OsRng, &participants, 2);
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = PresignOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(2);
+        for (i, p) in participants.iter().enumerate() {
+            let key = keys.iter().find(|(q, _)| q == p).unwrap().1.clone();
+            let args = PresignArguments {
+                triple0: (triple0_shares[i].clone(), triple0_pub.clone()),
+                triple1: (triple1_shares[i].clone(), triple1_pub.clone()),
+                keygen_out: key,
+                threshold: 2,
+            };
+            let protocol = presign(&participants, *p, &participants, *p, args)?;
+            protocols.push((*p, Box::new(protocol)));
+        }
+        let presignatures = run_protocol(protocols)?;
+        let signature = sign_both(&participants, public_key, presignatures, msg_hash)?;
+        assert!(signature.verify(&public_key, &msg_hash));
+        Ok(())
+    }
+
+    fn other_of(participants: &[Participant; 2], me: Participant) -> Participant {
+        if me == participants[0] {
+            participants[1]
+        } else {
+            participants[0]
+        }
+    }
+
+    fn sign_both(
+        participants: &[Participant; 2],
+        public_key: k256::AffinePoint,
+        presignatures: Vec<(Participant, PresignOutput<Secp256k1>)>,
+        msg_hash: k256::Scalar,
+    ) -> Result<FullSignature<Secp256k1>, Box<dyn std::error::Error>> {
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = FullSignature<Secp256k1>>>,
+        )> = Vec::with_capacity(2);
+        for (me, presignature) in presignatures {
+            let other = other_of(participants, me);
+            let protocol = sign_two_party(me, other, public_key, presignature, msg_hash)?;
+            protocols.push((me, Box::new(protocol)));
+        }
+        let mut signatures = run_protocol(protocols)?;
+        Ok(signatures.pop().unwrap().1)
+    }
+
+    #[test]
+    fn test_two_party_rejects_self() {
+        let me = Participant::from(0u32);
+        assert!(keygen_two_party::<Secp256k1>(me, me).is_err());
+    }
+}
@@ -0,0 +1,117 @@
+//! This module provides an abstraction over where private shares are kept.
+//!
+//! The protocols using a private share only ever need to compute `a * x_i + b`,
+//! for some share `x_i`, and scalars `a` and `b`. A [`ShareVault`] exposes exactly
+//! this operation, so that the share can be kept inside of an HSM, a TPM, or a secure
+//! enclave, without the raw scalar ever living in ordinary process memory.
+//!
+//! [`KeygenOutput`] implements this trait directly, so the protocols can also
+//! be used with shares kept in memory.
+//!
+//! Note that a vault doesn't stop a compromised process from using the share,
+//! but it does stop it from leaking, for example through a memory dump.
+use std::{convert::Infallible, error};
+
+use crate::{compat::CSCurve, KeygenOutput};
+
+/// A place holding a private share, which can compute with it, without revealing it.
+pub trait ShareVault<C: CSCurve> {
+    type Error: error::Error + Send + Sync + 'static;
+
+    /// The public key of the key this is a share of.
+    fn public_key(&self) -> C::AffinePoint;
+
+    /// Compute `a * x_i + b`, where `x_i` is the private share.
+    fn mul_add(&self, a: &C::Scalar, b: &C::Scalar) -> Result<C::Scalar, Self::Error>;
+}
+
+impl<C: CSCurve> ShareVault<C> for KeygenOutput<C> {
+    type Error = Infallible;
+
+    fn public_key(&self) -> C::AffinePoint {
+        self.public_key
+    }
+
+    fn mul_add(&self, a: &C::Scalar, b: &C::Scalar) -> Result<C::Scalar, Self::Error> {
+        Ok(*a * self.private_share + b)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use k256::{ProjectivePoint, Scalar, Secp256k1};
+    use rand_core::OsRng;
+
+    use super::*;
+    use crate::{
+        math::Polynomial,
+        participants::ParticipantList,
+        presign,
+        protocol::{run_protocol, Participant, Protocol},
+        triples, PresignArguments, PresignOutput,
+    };
+
+    /// A vault which never hands out its share.
+    struct Sealed {
+        share: Scalar,
+        public_key: k256::AffinePoint,
+    }
+
+    impl ShareVault<Secp256k1> for Sealed {
+        type Error = Infallible;
+
+        fn public_key(&self) -> k256::AffinePoint {
+            self.public_key
+        }
+
+        fn mul_add(&self, a: &Scalar, b: &Scalar) -> Result<Scalar, Self::Error> {
+            Ok(a * &self.share + b)
+        }
+    }
+
+    #[test]
+    fn test_presign_with_vault() {
+        let participants = vec![Participant::from(0u32), Participant::from(1u32)];
+        let threshold = 2;
+        let f = Polynomial::<Secp256k1>::random(&mut OsRng, threshold);
+        let big_x = (ProjectivePoint::GENERATOR * f.evaluate_zero()).to_affine();
+        let (triple0_pub, triple0_shares) = triples::deal(&mut OsRng, &participants, threshold);
+        let (triple1_pub, triple1_shares) = triples::deal(&mut OsRng, &participants, threshold);
+
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = PresignOutput<Secp256k1>>>,
+        )> = Vec::with_capacity(participants.len());
+        for ((p, triple0), triple1) in participants.iter().zip(triple0_shares).zip(triple1_shares) {
+            let vault = Sealed {
+                share: f.evaluate(&p.scalar::<Secp256k1>()),
+                public_key: big_x,
+            };
+            let protocol = presign(
+                &participants,
+                *p,
+                &participants,
+                *p,
+                PresignArguments {
+                    triple0: (triple0, triple0_pub.clone()),
+                    triple1: (triple1, triple1_pub.clone()),
+                    keygen_out: vault,
+                    threshold,
+                },
+            )
+            .unwrap();
+            protocols.push((*p, Box::new(protocol)));
+        }
+        let result = run_protocol(protocols).unwrap();
+
+        let p_list = ParticipantList::new(&participants).unwrap();
+        let mut k = Scalar::ZERO;
+        let mut sigma = Scalar::ZERO;
+        for (p, out) in &result {
+            k += p_list.lagrange::<Secp256k1>(*p) * out.k;
+            sigma += p_list.lagrange::<Secp256k1>(*p) * out.sigma;
+        }
+        assert_eq!(sigma, k * f.evaluate_zero());
+    }
+}
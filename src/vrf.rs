@@ -0,0 +1,359 @@
+//! This module provides a threshold verifiable random function, under the shared key.
+//!
+//! This follows the structure of ECVRF: the input is hashed to a point `H`,
+//! and the output is derived from `Gamma = x * H`, along with a proof that
+//! `Gamma` and the public key `X` have the same discrete logarithm, relative to `H` and `G`.
+//! Since only the holders of the key can compute `Gamma`, and the proof shows
+//! that there's only one possible value for it, anyone can check that the output
+//! is the right one for a given input, without being able to predict it.
+//!
+//! The participants evaluating the function each contribute `x_i * H`, weighted by their
+//! lagrange coefficient, and produce the proof together, like a Schnorr signature:
+//! they first commit to their nonces, then reveal them, and finally send their responses,
+//! which are checked individually, so that a misbehaving participant can be identified.
+//!
+//! The proof uses our own transcript based Fiat-Shamir transform, and the hash to curve
+//! uses our own domain separation, so this isn't interoperable with RFC 9381.
+use elliptic_curve::{
+    group::cofactor::CofactorGroup,
+    hash2curve::{ExpandMsgXmd, GroupDigest},
+    Field, Group, ScalarPrimitive,
+};
+use rand_core::{CryptoRngCore, OsRng};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{
+    compat::{CSCurve, SerializablePoint},
+    crypto::{commit, fork_rng, hash, Commitment, Randomizer},
+    fiat_shamir::{FiatShamir, Transcript},
+    participants::{ParticipantCounter, ParticipantList, ParticipantMap},
+    protocol::{
+        internal::{make_protocol, Context, SharedChannel},
+        Fault, InitializationError, Participant, Protocol, ProtocolError,
+    },
+    serde::{deserialize_scalar, encode, serialize_scalar},
+    KeygenOutput,
+};
+
+/// The label we use for the transcript of the proof.
+const LABEL: &[u8] = b"cait-sith v0.8.0 vrf";
+/// The domain separation tag we use for hashing the input to a point.
+const HASH_TO_CURVE_DST: &[u8] = b"cait-sith v0.8.0 vrf hash to curve";
+/// The label we use for deriving the output.
+const OUTPUT_LABEL: &[u8] = b"cait-sith v0.8.0 vrf output";
+
+/// Hash the input of the function to a point, bound to the public key.
+fn hash_to_curve<C>(public_key: &C::ProjectivePoint, input: &[u8]) -> C::ProjectivePoint
+where
+    C: CSCurve + GroupDigest,
+    C::ProjectivePoint: CofactorGroup,
+{
+    let public_key = encode(&SerializablePoint::<C>::from_projective(public_key));
+    // This only fails if the tag is too long, which ours isn't.
+    C::hash_from_bytes::<ExpandMsgXmd<Sha256>>(&[C::NAME, &public_key, input], &[HASH_TO_CURVE_DST])
+        .expect("hashing to a point should not fail")
+}
+
+/// Compute the challenge of the proof.
+fn challenge<C: CSCurve>(
+    public_key: &C::ProjectivePoint,
+    big_h: &C::ProjectivePoint,
+    gamma: &C::ProjectivePoint,
+    big_u: &C::ProjectivePoint,
+    big_v: &C::ProjectivePoint,
+) -> C::Scalar {
+    let mut transcript = Transcript::new(LABEL);
+    transcript.message(b"group", C::NAME);
+    transcript.message(
+        b"points",
+        &encode(
+            &[public_key, big_h, gamma, big_u, big_v].map(SerializablePoint::<C>::from_projective),
+        ),
+    );
+    C::Scalar::random(&mut transcript.challenge(b"challenge"))
+}
+
+/// The output of the function, along with a proof that it's correct.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "")]
+pub struct VrfProof<C: CSCurve> {
+    gamma: SerializablePoint<C>,
+    #[serde(
+        serialize_with = "serialize_scalar::<C, _>",
+        deserialize_with = "deserialize_scalar::<C, _>"
+    )]
+    c: C::Scalar,
+    #[serde(
+        serialize_with = "serialize_scalar::<C, _>",
+        deserialize_with = "deserialize_scalar::<C, _>"
+    )]
+    s: C::Scalar,
+}
+
+impl<C: CSCurve> VrfProof<C> {
+    /// The output of the function, without checking the proof.
+    pub fn output(&self) -> [u8; 32] {
+        let digest = hash(&(OUTPUT_LABEL, C::NAME, self.gamma));
+        let mut out = [0u8; 32];
+        out.copy_from_slice(digest.as_ref());
+        out
+    }
+
+    /// Check this proof for an input, returning the output of the function if it's valid.
+    pub fn verify(&self, public_key: &C::AffinePoint, input: &[u8]) -> Option<[u8; 32]>
+    where
+        C: GroupDigest,
+        C::ProjectivePoint: CofactorGroup,
+    {
+        let public_key = C::ProjectivePoint::from(*public_key);
+        let big_h = hash_to_curve::<C>(&public_key, input);
+        let gamma = self.gamma.to_projective();
+        let big_u = C::ProjectivePoint::generator() * self.s - public_key * self.c;
+        let big_v = big_h * self.s - gamma * self.c;
+        (challenge::<C>(&public_key, &big_h, &gamma, &big_u, &big_v) == self.c)
+            .then(|| self.output())
+    }
+}
+
+/// What a participant reveals: its weighted share of the key, times `G` and `H`,
+/// and its nonce, times `G` and `H`.
+type Contribution<C> = [SerializablePoint<C>; 4];
+
+async fn do_vrf_evaluate<C>(
+    mut rng: impl CryptoRngCore,
+    mut chan: SharedChannel,
+    participants: ParticipantList,
+    me: Participant,
+    keygen_out: KeygenOutput<C>,
+    input: Vec<u8>,
+) -> Result<VrfProof<C>, ProtocolError>
+where
+    C: CSCurve + GroupDigest,
+    C::ProjectivePoint: CofactorGroup,
+{
+    let public_key = C::ProjectivePoint::from(keygen_out.public_key);
+    let big_h = hash_to_curve::<C>(&public_key, &input);
+
+    let x_i = participants.lagrange::<C>(me) * keygen_out.private_share;
+    let k_i = C::Scalar::random(&mut rng);
+    let mine: Contribution<C> = [
+        C::ProjectivePoint::generator() * x_i,
+        big_h * x_i,
+        C::ProjectivePoint::generator() * k_i,
+        big_h * k_i,
+    ]
+    .map(|p| SerializablePoint::from_projective(&p));
+    let (my_commitment, my_randomizer) = commit(&mut rng, &mine);
+
+    // First, commit to our contribution, so that nobody can choose theirs based on ours.
+    let wait0 = chan.next_waitpoint();
+    chan.send_many(wait0, &my_commitment).await;
+
+    let mut all_commitments = ParticipantMap::new(&participants);
+    all_commitments.put(me, my_commitment);
+    while !all_commitments.full() {
+        let (from, commitment): (_, Commitment) = chan.recv(wait0).await?;
+        all_commitments.put(from, commitment);
+    }
+
+    let wait1 = chan.next_waitpoint();
+    chan.send_many(wait1, &(&mine, &my_randomizer)).await;
+
+    let mut contributions = ParticipantMap::new(&participants);
+    contributions.put(me, mine);
+    let mut seen = ParticipantCounter::new(&participants);
+    seen.put(me);
+    while !seen.full() {
+        let (from, (theirs, their_randomizer)): (_, (Contribution<C>, Randomizer)) =
+            chan.recv(wait1).await?;
+        if !seen.put(from) {
+            continue;
+        }
+        if !all_commitments[from].check(&theirs, &their_randomizer) {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait1,
+                fault: Fault::BadCommitment,
+            });
+        }
+        contributions.put(from, theirs);
+    }
+
+    let order: Vec<Participant> = participants.clone().into();
+    let mut sums = [C::ProjectivePoint::identity(); 4];
+    for &p in &order {
+        for (sum, point) in sums.iter_mut().zip(contributions[p]) {
+            *sum += point.to_projective();
+        }
+    }
+    let [big_x, gamma, big_u, big_v] = sums;
+    if big_x != public_key {
+        return Err(ProtocolError::Failed {
+            round: wait1,
+            fault: Fault::BadShare,
+        });
+    }
+    let c = challenge::<C>(&public_key, &big_h, &gamma, &big_u, &big_v);
+
+    // Then, respond to the challenge, like in a Schnorr signature.
+    let s_i = k_i + c * x_i;
+    let wait2 = chan.next_waitpoint();
+    {
+        let s_i: ScalarPrimitive<C> = s_i.into();
+        chan.send_many(wait2, &s_i).await;
+    }
+
+    let mut s = s_i;
+    seen.clear();
+    seen.put(me);
+    while !seen.full() {
+        let (from, s_j): (_, ScalarPrimitive<C>) = chan.recv(wait2).await?;
+        if !seen.put(from) {
+            continue;
+        }
+        let s_j = C::Scalar::from(s_j);
+        let [big_x_j, gamma_j, big_u_j, big_v_j] =
+            contributions[from].map(SerializablePoint::to_projective);
+        if C::ProjectivePoint::generator() * s_j != big_u_j + big_x_j * c
+            || big_h * s_j != big_v_j + gamma_j * c
+        {
+            return Err(ProtocolError::Malicious {
+                participant: from,
+                round: wait2,
+                fault: Fault::BadShare,
+            });
+        }
+        s += s_j;
+    }
+
+    let proof = VrfProof {
+        gamma: SerializablePoint::from_projective(&gamma),
+        c,
+        s,
+    };
+    if proof.verify(&keygen_out.public_key, &input).is_none() {
+        return Err(ProtocolError::Failed {
+            round: wait2,
+            fault: Fault::InvalidResult("vrf proof failed to verify"),
+        });
+    }
+
+    Ok(proof)
+}
+
+/// The protocol for evaluating the verifiable random function on some input.
+///
+/// The participants need to contain at least a threshold of the participants which
+/// generated the key. Any such set produces the same output, for the same input.
+pub fn vrf_evaluate<C>(
+    participants: &[Participant],
+    me: Participant,
+    keygen_out: KeygenOutput<C>,
+    input: &[u8],
+) -> Result<impl Protocol<Output = VrfProof<C>>, InitializationError>
+where
+    C: CSCurve + GroupDigest,
+    C::ProjectivePoint: CofactorGroup,
+{
+    vrf_evaluate_with_rng(participants, me, keygen_out, input, &mut OsRng)
+}
+
+/// Like [vrf_evaluate()], except drawing all of the randomness from a given rng.
+pub fn vrf_evaluate_with_rng<C>(
+    participants: &[Participant],
+    me: Participant,
+    keygen_out: KeygenOutput<C>,
+    input: &[u8],
+    rng: &mut impl CryptoRngCore,
+) -> Result<impl Protocol<Output = VrfProof<C>>, InitializationError>
+where
+    C: CSCurve + GroupDigest,
+    C::ProjectivePoint: CofactorGroup,
+{
+    if participants.len() < 2 {
+        return Err(InitializationError::BadParameters(format!(
+            "participant count cannot be < 2, found: {}",
+            participants.len()
+        )));
+    };
+
+    let participants = ParticipantList::new(participants).ok_or_else(|| {
+        InitializationError::BadParameters("participant list cannot contain duplicates".to_string())
+    })?;
+
+    if !participants.contains(me) {
+        return Err(InitializationError::BadParameters(
+            "participant list must contain this participant".to_string(),
+        ));
+    }
+
+    let ctx = Context::new();
+    let fut = do_vrf_evaluate(
+        fork_rng(rng),
+        ctx.shared_channel(),
+        participants,
+        me,
+        keygen_out,
+        input.to_vec(),
+    );
+    Ok(make_protocol(ctx, fut))
+}
+
+#[cfg(test)]
+mod test {
+    use k256::Secp256k1;
+
+    use super::*;
+    use crate::{
+        deal_key,
+        protocol::run_protocol,
+        serde::{decode, encode},
+    };
+
+    fn run_vrf(
+        outputs: &[(Participant, KeygenOutput<Secp256k1>)],
+        input: &[u8],
+    ) -> Result<VrfProof<Secp256k1>, Box<dyn std::error::Error>> {
+        let participants: Vec<_> = outputs.iter().map(|(p, _)| *p).collect();
+        #[allow(clippy::type_complexity)]
+        let mut protocols: Vec<(
+            Participant,
+            Box<dyn Protocol<Output = VrfProof<Secp256k1>>>,
+        )> = Vec::with_capacity(outputs.len());
+        for (p, output) in outputs {
+            let protocol = vrf_evaluate(&participants, *p, output.clone(), input)?;
+            protocols.push((*p, Box::new(protocol)));
+        }
+        let result = run_protocol(protocols)?;
+        assert!(result
+            .iter()
+            .all(|(_, proof)| encode(proof) == encode(&result[0].1)));
+        Ok(result[0].1.clone())
+    }
+
+    #[test]
+    fn test_vrf_evaluate() -> Result<(), Box<dyn std::error::Error>> {
+        let participants: Vec<_> = (0..4u32).map(Participant::from).collect();
+        let (_, outputs) = deal_key::<Secp256k1>(&participants, 3, None)?;
+        let public_key = outputs[0].1.public_key;
+
+        let proof = run_vrf(&outputs[..3], b"epoch 1")?;
+        let proof: VrfProof<Secp256k1> = decode(&encode(&proof))?;
+        let output = proof.verify(&public_key, b"epoch 1").unwrap();
+        assert_eq!(output, proof.output());
+        assert!(proof.verify(&public_key, b"epoch 2").is_none());
+
+        // Another set of participants gets the same output, with a different proof.
+        let other = run_vrf(&outputs[1..], b"epoch 1")?;
+        assert_eq!(other.verify(&public_key, b"epoch 1"), Some(output));
+        assert_ne!(encode(&other), encode(&proof));
+
+        let next = run_vrf(&outputs[1..], b"epoch 2")?;
+        assert_ne!(next.verify(&public_key, b"epoch 2").unwrap(), output);
+
+        // Too few participants can't evaluate the function.
+        assert!(run_vrf(&outputs[..2], b"epoch 1").is_err());
+        Ok(())
+    }
+}
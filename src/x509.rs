@@ -0,0 +1,330 @@
+//! Signing X.509 structures with the threshold key.
+//!
+//! Certificate signing requests, and certificates, consist of a structure to be signed,
+//! followed by the algorithm and the signature. This module builds the former,
+//! for signing requests, or takes it as is, for certificates, and then produces
+//! the hash to pass to [`sign`](crate::sign), and assembles the result once the
+//! participants have produced a signature. This lets a threshold key act as a
+//! certificate authority, or as the identity of a TLS server, without any participant
+//! holding the key.
+//!
+//! Signatures use ECDSA with SHA-256, as is usual for 256 bit curves.
+//! Everything is encoded in DER, which callers can wrap in PEM if needed.
+use std::{error, fmt};
+
+use ecdsa::hazmat::bits2field;
+use elliptic_curve::{
+    ops::Reduce,
+    pkcs8::AssociatedOid,
+    sec1::{FromEncodedPoint, ModulusSize, ToEncodedPoint},
+    FieldBytesSize, PrimeField,
+};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    compat::{self, CSCurve},
+    export::public_key_to_der,
+    FullSignature,
+};
+
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const INTEGER: u8 = 0x02;
+const BIT_STRING: u8 = 0x03;
+const UTF8_STRING: u8 = 0x0c;
+const PRINTABLE_STRING: u8 = 0x13;
+/// The tag of the attributes of a signing request, which are implicitly tagged with `[0]`.
+const ATTRIBUTES: u8 = 0xa0;
+
+/// The encoded `AlgorithmIdentifier` for ECDSA with SHA-256, without parameters.
+const ECDSA_WITH_SHA256: &[u8] = &[
+    0x30, 0x0a, 0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02,
+];
+
+/// Represents an error which can happen when building an X.509 structure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum X509Error {
+    /// The subject name is invalid, for the reason given.
+    InvalidName(String),
+    /// The public key can't be encoded, which only happens for the identity.
+    InvalidPublicKey,
+    /// The structure to sign isn't a DER encoded sequence.
+    InvalidStructure,
+}
+
+impl fmt::Display for X509Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidName(e) => write!(f, "invalid subject name: {}", e),
+            Self::InvalidPublicKey => write!(f, "public key cannot be encoded"),
+            Self::InvalidStructure => write!(f, "structure to sign isn't a DER sequence"),
+        }
+    }
+}
+
+impl error::Error for X509Error {}
+
+/// An attribute of a distinguished name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameAttribute {
+    /// The common name, `CN`, e.g. the domain name of a TLS server.
+    CommonName,
+    /// The country, `C`, as a two letter code.
+    Country,
+    /// The state or province, `ST`.
+    State,
+    /// The locality, `L`.
+    Locality,
+    /// The organization, `O`.
+    Organization,
+    /// The organizational unit, `OU`.
+    OrganizationalUnit,
+}
+
+impl NameAttribute {
+    /// The encoded object identifier of this attribute, under `2.5.4`.
+    fn oid(self) -> [u8; 5] {
+        let arc = match self {
+            Self::CommonName => 3,
+            Self::Country => 6,
+            Self::Locality => 7,
+            Self::State => 8,
+            Self::Organization => 10,
+            Self::OrganizationalUnit => 11,
+        };
+        [0x06, 0x03, 0x55, 0x04, arc]
+    }
+}
+
+/// Encode some contents, preceded by their tag and length.
+fn tlv(tag: u8, contents: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    if contents.len() < 0x80 {
+        out.push(contents.len() as u8);
+    } else {
+        let len = contents.len().to_be_bytes();
+        let len = &len[len.iter().position(|&b| b != 0).unwrap()..];
+        out.push(0x80 | len.len() as u8);
+        out.extend_from_slice(len);
+    }
+    out.extend_from_slice(contents);
+    out
+}
+
+/// Encode a non-negative integer, from its big endian bytes.
+fn integer(bytes: &[u8]) -> Vec<u8> {
+    let start = bytes
+        .iter()
+        .position(|&b| b != 0)
+        .unwrap_or(bytes.len() - 1);
+    let mut contents = Vec::with_capacity(bytes.len() + 1);
+    if bytes[start] & 0x80 != 0 {
+        contents.push(0);
+    }
+    contents.extend_from_slice(&bytes[start..]);
+    tlv(INTEGER, &contents)
+}
+
+fn encode_name(subject: &[(NameAttribute, &str)]) -> Result<Vec<u8>, X509Error> {
+    let mut rdns = Vec::new();
+    for &(attribute, value) in subject {
+        if value.is_empty() {
+            return Err(X509Error::InvalidName(format!("{:?} is empty", attribute)));
+        }
+        let value = if attribute == NameAttribute::Country {
+            if value.len() != 2 || !value.bytes().all(|b| b.is_ascii_uppercase()) {
+                return Err(X509Error::InvalidName(
+                    "country must be a two letter code".to_string(),
+                ));
+            }
+            tlv(PRINTABLE_STRING, value.as_bytes())
+        } else {
+            tlv(UTF8_STRING, value.as_bytes())
+        };
+        let mut pair = attribute.oid().to_vec();
+        pair.extend_from_slice(&value);
+        rdns.extend_from_slice(&tlv(SET, &tlv(SEQUENCE, &pair)));
+    }
+    Ok(tlv(SEQUENCE, &rdns))
+}
+
+/// An X.509 structure, waiting to be signed by the threshold key.
+#[derive(Debug, Clone)]
+pub struct ToBeSigned {
+    der: Vec<u8>,
+}
+
+impl ToBeSigned {
+    /// Build the information of a certificate signing request, following PKCS #10.
+    ///
+    /// The request asks for a certificate for `public_key`, with a given subject,
+    /// and no further attributes. It needs to be signed with the same key.
+    pub fn certification_request<C>(
+        subject: &[(NameAttribute, &str)],
+        public_key: &C::AffinePoint,
+    ) -> Result<Self, X509Error>
+    where
+        C: CSCurve + AssociatedOid,
+        C::AffinePoint: FromEncodedPoint<C> + ToEncodedPoint<C>,
+        FieldBytesSize<C>: ModulusSize,
+    {
+        let mut info = integer(&[0]);
+        info.extend_from_slice(&encode_name(subject)?);
+        info.extend_from_slice(
+            &public_key_to_der::<C>(public_key).map_err(|_| X509Error::InvalidPublicKey)?,
+        );
+        info.extend_from_slice(&tlv(ATTRIBUTES, &[]));
+        Ok(Self {
+            der: tlv(SEQUENCE, &info),
+        })
+    }
+
+    /// Sign an encoded `TBSCertificate`, built by other tooling.
+    ///
+    /// Its signature field needs to contain [`ToBeSigned::signature_algorithm`],
+    /// and its issuer needs to be the subject whose key signs it.
+    pub fn tbs_certificate(der: Vec<u8>) -> Result<Self, X509Error> {
+        let Some((&tag, rest)) = der.split_first() else {
+            return Err(X509Error::InvalidStructure);
+        };
+        // Re-encoding the contents with our own header checks that the length is right.
+        let header_len = match rest.first() {
+            Some(&b) if b < 0x80 => 2,
+            Some(&b) => 2 + usize::from(b & 0x7f),
+            None => return Err(X509Error::InvalidStructure),
+        };
+        if tag != SEQUENCE || der.len() < header_len || tlv(SEQUENCE, &der[header_len..]) != der {
+            return Err(X509Error::InvalidStructure);
+        }
+        Ok(Self { der })
+    }
+
+    /// The encoded `AlgorithmIdentifier` of the signatures we produce.
+    pub fn signature_algorithm() -> &'static [u8] {
+        ECDSA_WITH_SHA256
+    }
+
+    /// The encoded structure to be signed.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// The hash of the structure, to be signed with [`sign`](crate::sign).
+    pub fn message_hash<C: CSCurve>(&self) -> C::Scalar {
+        let digest = Sha256::digest(&self.der);
+        let field = bits2field::<C>(&digest).expect("SHA-256 should be long enough");
+        <C::Scalar as Reduce<C::Uint>>::reduce_bytes(&field)
+    }
+
+    /// Assemble the signed structure, with a signature over [`ToBeSigned::message_hash`].
+    pub fn finish<C: CSCurve>(&self, signature: &FullSignature<C>) -> Vec<u8> {
+        let r = compat::x_coordinate::<C>(&signature.big_r);
+        let mut sig = integer(r.to_repr().as_ref());
+        sig.extend_from_slice(&integer(signature.s.to_repr().as_ref()));
+        let mut bits = vec![0];
+        bits.extend_from_slice(&tlv(SEQUENCE, &sig));
+
+        let mut out = self.der.clone();
+        out.extend_from_slice(ECDSA_WITH_SHA256);
+        out.extend_from_slice(&tlv(BIT_STRING, &bits));
+        tlv(SEQUENCE, &out)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ecdsa::signature::Verifier;
+    use k256::{
+        ecdsa::{Signature, VerifyingKey},
+        AffinePoint, Secp256k1,
+    };
+
+    use super::*;
+    use crate::test::run_e2e_with;
+
+    /// Split a signed structure into the signed part and the signature.
+    fn split_signed(signed: &[u8], tbs_len: usize) -> (&[u8], Signature) {
+        let header_len = match signed[1] {
+            b if b < 0x80 => 2,
+            b => 2 + usize::from(b & 0x7f),
+        };
+        let (tbs, rest) = signed[header_len..].split_at(tbs_len);
+        let rest = rest.strip_prefix(ECDSA_WITH_SHA256).unwrap();
+        assert_eq!(rest[..3], [BIT_STRING, (rest.len() - 2) as u8, 0]);
+        (tbs, Signature::from_der(&rest[3..]).unwrap())
+    }
+
+    #[test]
+    fn test_der_lengths() {
+        assert_eq!(tlv(SEQUENCE, &[]), [0x30, 0x00]);
+        assert_eq!(tlv(SEQUENCE, &[0; 0x80])[..3], [0x30, 0x81, 0x80]);
+        assert_eq!(tlv(SEQUENCE, &[0; 0x100])[..4], [0x30, 0x82, 0x01, 0x00]);
+        assert_eq!(integer(&[0, 0]), [0x02, 0x01, 0x00]);
+        assert_eq!(integer(&[0, 0x80]), [0x02, 0x02, 0x00, 0x80]);
+    }
+
+    #[test]
+    fn test_certification_request() -> Result<(), X509Error> {
+        let subject = [
+            (NameAttribute::Country, "CH"),
+            (NameAttribute::CommonName, "threshold.example"),
+        ];
+        let mut request = None;
+        let (public_key, sig) = run_e2e_with::<Secp256k1>(|public_key| {
+            let tbs = ToBeSigned::certification_request::<Secp256k1>(&subject, &public_key)
+                .expect("the request should be valid");
+            let hash = tbs.message_hash::<Secp256k1>();
+            request = Some(tbs);
+            hash
+        });
+        let request = request.unwrap();
+        let csr = request.finish(&sig);
+
+        let (tbs, sig) = split_signed(&csr, request.as_bytes().len());
+        assert_eq!(tbs, request.as_bytes());
+        let vk = VerifyingKey::from_affine(public_key).unwrap();
+        assert!(vk.verify(tbs, &sig).is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn test_certification_request_encoding() -> Result<(), X509Error> {
+        let tbs = ToBeSigned::certification_request::<Secp256k1>(
+            &[(NameAttribute::CommonName, "a")],
+            &AffinePoint::GENERATOR,
+        )?;
+        let der = tbs.as_bytes();
+        // The version, then the name, as a single RDN with a UTF-8 common name.
+        assert_eq!(
+            der[..19],
+            [
+                0x30, 0x6b, 0x02, 0x01, 0x00, 0x30, 0x0c, 0x31, 0x0a, 0x30, 0x08, 0x06, 0x03, 0x55,
+                0x04, 0x03, 0x0c, 0x01, b'a'
+            ]
+        );
+        assert_eq!(der[der.len() - 2..], [0xa0, 0x00]);
+        assert_eq!(ToBeSigned::tbs_certificate(der.to_vec())?.as_bytes(), der);
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_inputs() {
+        let country = ToBeSigned::certification_request::<Secp256k1>(
+            &[(NameAttribute::Country, "Switzerland")],
+            &AffinePoint::GENERATOR,
+        );
+        assert!(matches!(country, Err(X509Error::InvalidName(_))));
+        let identity = ToBeSigned::certification_request::<Secp256k1>(
+            &[(NameAttribute::CommonName, "a")],
+            &AffinePoint::IDENTITY,
+        );
+        assert_eq!(identity.err(), Some(X509Error::InvalidPublicKey));
+
+        for der in [vec![], vec![0x30], vec![0x30, 0x02, 0x00], vec![0x31, 0x00]] {
+            assert_eq!(
+                ToBeSigned::tbs_certificate(der).err(),
+                Some(X509Error::InvalidStructure)
+            );
+        }
+    }
+}
@@ -0,0 +1,323 @@
+//! JavaScript bindings for cait-sith, over secp256k1, through `wasm-bindgen`.
+//!
+//! These let a browser take part in a ceremony, as one of its participants.
+//! Each protocol is a [`Ceremony`], driven like any other protocol of the library:
+//! poke it, and act on what it asks for, until it waits for messages,
+//! passing each message from another participant to [`Ceremony::message`].
+//! Carrying messages between participants, say over a WebSocket, is left to the caller.
+//!
+//! Values crossing into JavaScript are bytes:
+//!
+//! - Key shares and presignatures are the versioned envelopes of the library,
+//!   which are also what should be persisted.
+//! - Triples are the msgpack encoding of a triple share, and its public part,
+//!   as described in `docs/encoding.md`.
+//! - Signatures are the 64 bytes of `r` followed by `s`, and message hashes are
+//!   32 bytes, in big endian order, reduced modulo the order of the curve if needed.
+//!
+//! Participants are identified with numbers, as in the library.
+use cait_sith::{
+    protocol::{self, MessageData, Participant, Protocol, ProtocolError},
+    triples::{self, TripleGenerationOutput},
+    KeygenOutput, PresignArguments, PresignOutput, Versioned,
+};
+use k256::{elliptic_curve::ops::Reduce, FieldBytes, Scalar, Secp256k1, U256};
+use wasm_bindgen::prelude::*;
+
+/// What a [`Ceremony`] asks of the caller.
+#[wasm_bindgen]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionKind {
+    /// Wait for a message from another participant.
+    Wait,
+    /// Send the data to every other participant.
+    SendMany,
+    /// Send the data privately to a single participant.
+    SendPrivate,
+    /// The ceremony has returned its output, as the data.
+    Return,
+}
+
+/// An action returned when poking a [`Ceremony`].
+#[wasm_bindgen]
+#[derive(Debug, Clone)]
+pub struct Action {
+    kind: ActionKind,
+    to: Option<u32>,
+    data: Vec<u8>,
+}
+
+#[wasm_bindgen]
+impl Action {
+    #[wasm_bindgen(getter)]
+    pub fn kind(&self) -> ActionKind {
+        self.kind
+    }
+
+    /// The participant to send a private message to.
+    #[wasm_bindgen(getter)]
+    pub fn to(&self) -> Option<u32> {
+        self.to
+    }
+
+    /// The message to send, or the output of the ceremony.
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+}
+
+/// A protocol, with its output encoded for JavaScript.
+struct Encoded<P, F> {
+    protocol: P,
+    encode: F,
+}
+
+trait Run {
+    fn poke(&mut self) -> Result<protocol::Action<Vec<u8>>, ProtocolError>;
+
+    fn message(&mut self, from: Participant, data: MessageData);
+}
+
+impl<P, F> Run for Encoded<P, F>
+where
+    P: Protocol,
+    F: FnMut(P::Output) -> Result<Vec<u8>, ProtocolError>,
+{
+    fn poke(&mut self) -> Result<protocol::Action<Vec<u8>>, ProtocolError> {
+        Ok(match self.protocol.poke()? {
+            protocol::Action::Wait => protocol::Action::Wait,
+            protocol::Action::SendMany(data) => protocol::Action::SendMany(data),
+            protocol::Action::SendPrivate(to, data) => protocol::Action::SendPrivate(to, data),
+            protocol::Action::Return(output) => protocol::Action::Return((self.encode)(output)?),
+        })
+    }
+
+    fn message(&mut self, from: Participant, data: MessageData) {
+        self.protocol.message(from, data)
+    }
+}
+
+/// A protocol being run by this participant.
+#[wasm_bindgen]
+pub struct Ceremony(Box<dyn Run>);
+
+impl Ceremony {
+    fn new<P, F>(protocol: P, encode: F) -> Self
+    where
+        P: Protocol + 'static,
+        F: FnMut(P::Output) -> Result<Vec<u8>, ProtocolError> + 'static,
+    {
+        Self(Box::new(Encoded { protocol, encode }))
+    }
+}
+
+#[wasm_bindgen]
+impl Ceremony {
+    /// Poke the ceremony, returning the next thing to do.
+    ///
+    /// This should be called again until it asks to wait, or returns its output.
+    pub fn poke(&mut self) -> Result<Action, JsError> {
+        Ok(match self.0.poke()? {
+            protocol::Action::Wait => Action {
+                kind: ActionKind::Wait,
+                to: None,
+                data: Vec::new(),
+            },
+            protocol::Action::SendMany(data) => Action {
+                kind: ActionKind::SendMany,
+                to: None,
+                data: data.to_vec(),
+            },
+            protocol::Action::SendPrivate(to, data) => Action {
+                kind: ActionKind::SendPrivate,
+                to: Some(to.into()),
+                data: data.to_vec(),
+            },
+            protocol::Action::Return(data) => Action {
+                kind: ActionKind::Return,
+                to: None,
+                data,
+            },
+        })
+    }
+
+    /// Pass a message from another participant to the ceremony.
+    pub fn message(&mut self, from: u32, data: &[u8]) {
+        self.0
+            .message(from.into(), MessageData::copy_from_slice(data))
+    }
+}
+
+fn to_participants(ids: &[u32]) -> Vec<Participant> {
+    ids.iter().copied().map(Participant::from).collect()
+}
+
+fn other(e: impl ToString) -> ProtocolError {
+    ProtocolError::Other(e.to_string().into())
+}
+
+/// Generate a key, returning our share of it.
+#[wasm_bindgen]
+pub fn keygen(participants: &[u32], me: u32, threshold: usize) -> Result<Ceremony, JsError> {
+    let protocol =
+        cait_sith::keygen::<Secp256k1>(&to_participants(participants), me.into(), threshold)?;
+    Ok(Ceremony::new(protocol, |out: KeygenOutput<Secp256k1>| {
+        Ok(out.to_envelope())
+    }))
+}
+
+/// Generate a triple, returning our share of it, with its public part.
+#[wasm_bindgen]
+pub fn generate_triple(
+    participants: &[u32],
+    me: u32,
+    threshold: usize,
+) -> Result<Ceremony, JsError> {
+    let protocol = triples::generate_triple::<Secp256k1>(
+        &to_participants(participants),
+        me.into(),
+        threshold,
+    )?;
+    Ok(Ceremony::new(
+        protocol,
+        |out: TripleGenerationOutput<Secp256k1>| rmp_serde::to_vec(&out).map_err(other),
+    ))
+}
+
+/// Create a presignature, from a key share, and two unused triples.
+#[wasm_bindgen]
+pub fn presign(
+    participants: &[u32],
+    me: u32,
+    key: &[u8],
+    triple0: &[u8],
+    triple1: &[u8],
+    threshold: usize,
+) -> Result<Ceremony, JsError> {
+    let participants = to_participants(participants);
+    let args = PresignArguments {
+        triple0: rmp_serde::from_slice(triple0)?,
+        triple1: rmp_serde::from_slice(triple1)?,
+        keygen_out: KeygenOutput::<Secp256k1>::from_envelope(key)?,
+        threshold,
+    };
+    let protocol = cait_sith::presign(&participants, me.into(), &participants, me.into(), args)?;
+    Ok(Ceremony::new(protocol, |out: PresignOutput<Secp256k1>| {
+        Ok(out.to_envelope())
+    }))
+}
+
+/// Sign a message hash with the key, using a presignature, which must never be used again.
+#[wasm_bindgen]
+pub fn sign(
+    participants: &[u32],
+    me: u32,
+    key: &[u8],
+    presignature: &[u8],
+    msg_hash: &[u8],
+) -> Result<Ceremony, JsError> {
+    let msg_hash: [u8; 32] = msg_hash
+        .try_into()
+        .map_err(|_| JsError::new("message hashes are 32 bytes"))?;
+    let msg_hash = <Scalar as Reduce<U256>>::reduce_bytes(&FieldBytes::from(msg_hash));
+    let public_key = KeygenOutput::<Secp256k1>::from_envelope(key)?.public_key;
+    let presignature = PresignOutput::<Secp256k1>::from_envelope(presignature)?;
+    let protocol = cait_sith::sign(
+        &to_participants(participants),
+        me.into(),
+        public_key,
+        presignature,
+        msg_hash,
+    )?;
+    Ok(Ceremony::new(
+        protocol,
+        |sig: cait_sith::FullSignature<Secp256k1>| {
+            Ok(sig.to_ecdsa().map_err(other)?.to_bytes().to_vec())
+        },
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use k256::ecdsa::{signature::hazmat::PrehashVerifier, Signature, VerifyingKey};
+
+    use super::*;
+
+    /// Run ceremonies between participants, returning their outputs, in order.
+    fn run(mut ceremonies: Vec<(u32, Ceremony)>) -> Vec<Vec<u8>> {
+        let mut outputs: Vec<Option<Vec<u8>>> = vec![None; ceremonies.len()];
+        while outputs.iter().any(Option::is_none) {
+            for i in 0..ceremonies.len() {
+                if outputs[i].is_some() {
+                    continue;
+                }
+                loop {
+                    let from = ceremonies[i].0;
+                    let action = ceremonies[i].1.poke().unwrap();
+                    match action.kind() {
+                        ActionKind::Wait => break,
+                        ActionKind::SendMany => {
+                            for (p, c) in ceremonies.iter_mut() {
+                                if *p != from {
+                                    c.message(from, &action.data());
+                                }
+                            }
+                        }
+                        ActionKind::SendPrivate => {
+                            let to = action.to().unwrap();
+                            let (_, c) = ceremonies.iter_mut().find(|(p, _)| *p == to).unwrap();
+                            c.message(from, &action.data());
+                        }
+                        ActionKind::Return => {
+                            outputs[i] = Some(action.data());
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        outputs.into_iter().map(Option::unwrap).collect()
+    }
+
+    #[test]
+    fn test_e2e() {
+        let participants = [1, 2, 3];
+        let threshold = 2;
+        let each = |f: &dyn Fn(usize, u32) -> Ceremony| {
+            run(participants
+                .iter()
+                .enumerate()
+                .map(|(i, &p)| (p, f(i, p)))
+                .collect())
+        };
+
+        let keys = each(&|_, p| keygen(&participants, p, threshold).unwrap());
+        let triples0 = each(&|_, p| generate_triple(&participants, p, threshold).unwrap());
+        let triples1 = each(&|_, p| generate_triple(&participants, p, threshold).unwrap());
+        let presignatures = each(&|i, p| {
+            presign(
+                &participants,
+                p,
+                &keys[i],
+                &triples0[i],
+                &triples1[i],
+                threshold,
+            )
+            .unwrap()
+        });
+        let msg_hash = [7u8; 32];
+        let signatures =
+            each(&|i, p| sign(&participants, p, &keys[i], &presignatures[i], &msg_hash).unwrap());
+
+        let public_key = KeygenOutput::<Secp256k1>::from_envelope(&keys[0])
+            .unwrap()
+            .public_key;
+        let key = VerifyingKey::from_affine(public_key).unwrap();
+        for signature in &signatures {
+            assert_eq!(signature, &signatures[0]);
+            let signature = Signature::from_slice(signature).unwrap();
+            key.verify_prehash(&msg_hash, &signature).unwrap();
+        }
+    }
+}